@@ -1,4 +1,4 @@
 <?xml version="1.0" encoding="UTF-8"?>
-<tileset version="1.10" tiledversion="1.11.2" name="colliders" tilewidth="8" tileheight="8" tilecount="3" columns="3">
- <image source="pixil-frame-0.png" width="24" height="8"/>
+<tileset version="1.10" tiledversion="1.11.2" name="colliders" tilewidth="8" tileheight="8" tilecount="6" columns="6">
+ <image source="pixil-frame-0.png" width="48" height="8"/>
 </tileset>
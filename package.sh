@@ -39,11 +39,6 @@ if [[ "$windows_only" == "true" || ("$windows_only" == "" && "&ubuntu_only" == "
 
     echo "Copied from ./assets to ${win_x86_dir}/assets}";
 
-    if [ ! -d "${win_x86_dir}/storage" ]; then
-        mkdir ${win_x86_dir}/storage;
-        echo "Created ${win_x86_dir}/storage";
-    fi
-
     if [ "$release" == "true" ]; then
         cp ./README.md ${win_x86_dir}/README.md;
         echo "Copied from ./README.md to ${win_x86_dir}/README.md";
@@ -77,11 +72,6 @@ if [[ "$ubuntu_only" == "true" || ("$ubuntu_only" == "" && "&windows_only" == ""
 
     echo "Copied from ./assets to ${ubuntu_x86_dir}/assets";
 
-    if [ ! -d "${ubuntu_x86_dir}/storage" ]; then
-        mkdir ${ubuntu_x86_dir}/storage;
-        echo "Created ${ubuntu_x86_dir}/storage";
-    fi
-
     if [ "$release" == "true" ]; then
         cp ./README.md ${ubuntu_x86_dir}/README.md;
         echo "Copied from ./README.md to ${ubuntu_x86_dir}/README.md";
@@ -4,44 +4,104 @@ use rapier2d::{na::Vector2, prelude::RigidBodyHandle};
 
 use crate::{
   controls::ControlsSystem,
+  data::DataSystem,
+  dialogue::DialogueSystem,
+  ecs::{Boss, Damageable, Dying, Enemy, Entity, EntityHandle, Faction},
   load_map::MapAbilityType,
   physics::PhysicsSystem,
   save::SaveData,
-  system::System,
+  settings::SettingsSystem,
+  system::{Requirement, System},
   units::{PhysicsVector, UnitConvert, UnitConvert2},
 };
 
 const BOOST_MOD: f32 = 5.5;
+/* Frames between taps of a movement direction for them to count as a dash */
+const DASH_DOUBLE_TAP_WINDOW: usize = 12;
+/* A boost pressed this close to a charge being ready still goes off once it is */
+const BOOST_BUFFER_FRAMES: usize = 8;
+/* Gap between boosts spending consecutive charges, longer than the buffer so one press
+only spends one charge */
+const BOOST_CHAIN_FRAMES: f32 = 10.0;
+/* After the chain snaps it can't be attached again until this runs out */
+const CHAIN_REATTACH_FRAMES: f32 = 90.0;
+const HACK_RANGE: f32 = 6.0;
+pub const HACK_CHANNEL_FRAMES: f32 = 90.0;
+const HACK_COOLDOWN_FRAMES: f32 = 300.0;
 
 pub struct AbilitySystem {
   pub acquired_boost: bool,
   pub acquired_chain: bool,
+  pub acquired_magnetize: bool,
   pub boost_force: Option<Vector2<f32>>,
-  pub current_boost_cooldown: f32,
-  pub max_boost_cooldown: f32,
+  pub boost_charges: i32,
+  pub max_boost_charges: i32,
+  /* Frames spent recharging the next missing charge */
+  pub boost_recharge: f32,
+  pub boost_recharge_frames: f32,
+  /* Counts down after each boost before another charge can be spent */
+  pub boost_chain_cooldown: f32,
   pub chain_to_mount_point: Option<RigidBodyHandle>,
   pub chain_activated: bool,
   pub kill_chain: bool,
+  pub chain_cooldown: f32,
+  /* Held rather than pressed, the player stays latched until it is released */
+  pub magnetize: bool,
+  pub acquired_hack: bool,
+  /* The enemy being channeled on, the channel breaking if the button is let go, the enemy
+  leaves range or the player is hit */
+  pub hack_target: Option<EntityHandle>,
+  pub hack_channel: f32,
+  /* Set on the frame a channel completes, turning the enemy */
+  pub hack: Option<EntityHandle>,
+  pub hack_cooldown: f32,
+  /* Names of the engine upgrades found, applied to the movement profile */
+  pub engine_upgrades: Vec<String>,
 }
 
 impl System for AbilitySystem {
   type Input = SaveData;
 
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<ControlsSystem<_>>(),
+      Requirement::of::<DataSystem>(),
+      Requirement::of::<PhysicsSystem>(),
+      Requirement::of::<DialogueSystem>(),
+      Requirement::of::<SettingsSystem>(),
+    ]
+  }
+
   fn start(
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> std::rc::Rc<dyn System<Input = Self::Input>>
   where
     Self: Sized,
   {
+    let data_system = ctx.require::<DataSystem>().unwrap();
+    let movement = data_system.data.movement.stats(&ctx.input.engine_upgrades);
+
     Rc::new(AbilitySystem {
       acquired_boost: ctx.input.acquired_boost,
       acquired_chain: ctx.input.acquired_chain,
+      acquired_magnetize: ctx.input.acquired_magnetize,
       boost_force: None,
-      current_boost_cooldown: 240.0, // TODO: Load from save data
-      max_boost_cooldown: 240.0,
+      boost_charges: movement.boost_charges,
+      max_boost_charges: movement.boost_charges,
+      boost_recharge: 0.0,
+      boost_recharge_frames: movement.boost_recharge_frames,
+      boost_chain_cooldown: 0.0,
       chain_to_mount_point: None,
       chain_activated: false,
       kill_chain: false,
+      chain_cooldown: 0.0,
+      magnetize: false,
+      acquired_hack: ctx.input.acquired_hack,
+      hack_target: None,
+      hack_channel: 0.0,
+      hack: None,
+      hack_cooldown: 0.0,
+      engine_upgrades: ctx.input.engine_upgrades.clone(),
     })
   }
 
@@ -49,22 +109,70 @@ impl System for AbilitySystem {
     &self,
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> std::rc::Rc<dyn System<Input = Self::Input>> {
-    let controls_system = ctx.get::<ControlsSystem<_>>().unwrap();
+    let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
+    let dialogue_system = ctx.require::<DialogueSystem>().unwrap();
+
+    if controls_system.simulation_frozen() || dialogue_system.playing() {
+      return Rc::new(AbilitySystem {
+        boost_force: None,
+        chain_to_mount_point: None,
+        kill_chain: false,
+        magnetize: false,
+        hack: None,
+        engine_upgrades: self.engine_upgrades.clone(),
+        ..*self
+      });
+    }
+
+    /* Double tapping a movement direction boosts the same as the boost button */
+    let dash_double_tapped = [
+      |controls: &ControlsSystem<_>| controls.left_stick.x() > 0.0,
+      |controls: &ControlsSystem<_>| controls.left_stick.x() < 0.0,
+      |controls: &ControlsSystem<_>| controls.left_stick.y() > 0.0,
+      |controls: &ControlsSystem<_>| controls.left_stick.y() < 0.0,
+    ]
+    .into_iter()
+    .any(|direction| controls_system.double_tapped(direction, DASH_DOUBLE_TAP_WINDOW));
+
+    let boost_requested = controls_system.boost
+      && controls_system.pressed_within(|controls| controls.boost, BOOST_BUFFER_FRAMES);
 
-    let (boost_force, current_boost_cooldown) = if controls_system.boost
+    let boosting = (boost_requested || dash_double_tapped)
       && controls_system.left_stick != PhysicsVector::zero()
       && self.acquired_boost
-      && self.current_boost_cooldown == 0.0
-    {
-      (
-        Some(controls_system.left_stick.into_vec().normalize() * BOOST_MOD),
-        self.max_boost_cooldown,
-      )
+      && self.boost_charges > 0
+      && self.boost_chain_cooldown == 0.0;
+
+    let boost_force =
+      boosting.then(|| controls_system.left_stick.into_vec().normalize() * BOOST_MOD);
+
+    /* MARK: Recharge boost */
+    let data_system = ctx.require::<DataSystem>().unwrap();
+    let movement = data_system.data.movement.stats(&self.engine_upgrades);
+
+    let settings_system = ctx.require::<SettingsSystem>().unwrap();
+    let infinite_boost = settings_system.settings.assist.infinite_boost;
+
+    let boost_charges = self.boost_charges - if boosting && !infinite_boost { 1 } else { 0 };
+    let boost_recharge = if boost_charges < movement.boost_charges {
+      self.boost_recharge + controls_system.time_scale
     } else {
-      (None, (self.current_boost_cooldown - 1.0).max(0.0))
+      0.0
     };
 
-    let physics_system = ctx.get::<PhysicsSystem>().unwrap();
+    let (boost_charges, boost_recharge) = if boost_recharge >= movement.boost_recharge_frames {
+      (boost_charges + 1, 0.0)
+    } else {
+      (boost_charges.min(movement.boost_charges), boost_recharge)
+    };
+
+    let boost_chain_cooldown = if boosting {
+      BOOST_CHAIN_FRAMES
+    } else {
+      (self.boost_chain_cooldown - controls_system.time_scale).max(0.0)
+    };
+
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
 
     let acquired_boost = self.acquired_boost
       || physics_system
@@ -78,14 +186,30 @@ impl System for AbilitySystem {
         .iter()
         .any(|new_ability| matches!(new_ability, MapAbilityType::Chain));
 
-    let kill_chain = self.chain_activated
-      && controls_system.chain
-      && !controls_system.last_frame.as_ref().unwrap().chain;
+    let acquired_magnetize = self.acquired_magnetize
+      || physics_system
+        .new_abilities
+        .iter()
+        .any(|new_ability| matches!(new_ability, MapAbilityType::Magnetize));
+
+    let magnetize = self.acquired_magnetize && controls_system.magnetize;
+
+    /* The chain snapping under tension or enemy fire lets go the same as releasing it */
+    let chain_broke = self.chain_activated && physics_system.chain_broke;
+
+    let chain_cooldown = if chain_broke {
+      CHAIN_REATTACH_FRAMES
+    } else {
+      (self.chain_cooldown - controls_system.time_scale).max(0.0)
+    };
+
+    let kill_chain =
+      self.chain_activated && !chain_broke && controls_system.pressed(|controls| controls.chain);
 
     let chain_to_mount_point = if self.acquired_chain
       && !self.chain_activated
-      && controls_system.chain
-      && !controls_system.last_frame.as_ref().unwrap().chain
+      && self.chain_cooldown == 0.0
+      && controls_system.pressed(|controls| controls.chain)
     {
       physics_system
         .mount_points_in_range
@@ -111,17 +235,94 @@ impl System for AbilitySystem {
       None
     };
 
-    let chain_activated = (self.chain_activated || chain_to_mount_point.is_some()) && !kill_chain;
+    let chain_activated =
+      (self.chain_activated || chain_to_mount_point.is_some()) && !kill_chain && !chain_broke;
+
+    /* MARK: Channel hacks */
+    let acquired_hack = self.acquired_hack
+      || physics_system
+        .new_abilities
+        .iter()
+        .any(|new_ability| matches!(new_ability, MapAbilityType::Hack));
+
+    let player_translation =
+      *physics_system.rigid_body_set[physics_system.player_handle].translation();
+    let player_hit = physics_system
+      .entities
+      .get(&EntityHandle::RigidBody(physics_system.player_handle))
+      .and_then(|player| player.components.get::<Damageable>())
+      .is_some_and(|damageable| damageable.current_hitstun > 0.0);
+
+    /* Bosses can't be turned, nor can corpses or anything already on the player's side */
+    let hackable = |entity: &Entity| {
+      entity.components.get::<Enemy>().is_some()
+        && entity.components.get::<Boss>().is_none()
+        && entity.components.get::<Dying>().is_none()
+        && entity.components.get::<Damageable>().is_some()
+        && Faction::Player.hostile_to(Faction::of(&entity.components), false)
+    };
+    let in_range = |entity: &Entity| {
+      (entity
+        .handle
+        .translation(&physics_system.rigid_body_set, &physics_system.collider_set)
+        - player_translation)
+        .magnitude()
+        <= HACK_RANGE
+    };
+
+    let hack_cooldown = (self.hack_cooldown - controls_system.time_scale).max(0.0);
+
+    let hack_target =
+      if self.acquired_hack && controls_system.hack && self.hack_cooldown == 0.0 && !player_hit {
+        self
+          .hack_target
+          .and_then(|target| physics_system.entities.get(&target))
+          .filter(|entity| hackable(entity) && in_range(entity))
+          .or_else(|| physics_system.nearest_entity(player_translation, HACK_RANGE, hackable))
+          .map(|entity| entity.handle)
+      } else {
+        None
+      };
+
+    let hack_channel = match hack_target {
+      Some(target) if self.hack_target == Some(target) => {
+        self.hack_channel + controls_system.time_scale
+      }
+      _ => 0.0,
+    };
+
+    let (hack, hack_target, hack_channel, hack_cooldown) = if hack_channel >= HACK_CHANNEL_FRAMES {
+      (hack_target, None, 0.0, HACK_COOLDOWN_FRAMES)
+    } else {
+      (None, hack_target, hack_channel, hack_cooldown)
+    };
 
     Rc::new(AbilitySystem {
       acquired_boost,
       acquired_chain,
+      acquired_magnetize,
       boost_force,
-      current_boost_cooldown,
-      max_boost_cooldown: self.max_boost_cooldown,
+      boost_charges,
+      max_boost_charges: movement.boost_charges,
+      boost_recharge,
+      boost_recharge_frames: movement.boost_recharge_frames,
+      boost_chain_cooldown,
       chain_to_mount_point,
       chain_activated,
       kill_chain,
+      chain_cooldown,
+      magnetize,
+      acquired_hack,
+      hack_target,
+      hack_channel,
+      hack,
+      hack_cooldown,
+      engine_upgrades: self
+        .engine_upgrades
+        .iter()
+        .cloned()
+        .chain(physics_system.new_engine_upgrades.iter().cloned())
+        .collect(),
     })
   }
 }
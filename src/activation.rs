@@ -0,0 +1,390 @@
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use rpds::HashTrieMapSync;
+
+use crate::ecs::{
+  Activator, And, ComponentSet, Engine, Entity, EntityHandle, Gate, Id, Or, SimpleActivatable,
+  TouchSensor,
+};
+
+/* Pure passes over the activator graph, run in this order every frame by
+PhysicsSystem. Each pass only reads activations produced by earlier passes or
+the previous frame, so they can be stepped without a physics world. */
+
+pub type Entities = HashTrieMapSync<EntityHandle, Arc<Entity>>;
+
+pub const ENGINE_MAX_SPEED: f32 = 0.005;
+
+/* Fewer entities than this to a job and handing them to another thread costs more than it saves */
+const MIN_ENTITIES_PER_JOB: usize = 256;
+
+/* Works out each entity's new components from a snapshot of them all on rayon's threads, then
+puts back only the entities that changed. No update sees another from the same pass and the
+updates are collected in snapshot order, so the merge is the same however the work was split */
+pub fn update_components(
+  entities: &Entities,
+  update: impl Fn(&Entity) -> Option<ComponentSet> + Sync,
+) -> Entities {
+  let (entities, _) = update_components_with(entities, |entity| Some((update(entity)?, ())));
+  return entities;
+}
+
+/* Same as update_components, also handing back what each update produced alongside its
+components in snapshot order, for the caller to apply to the world outside the ECS */
+pub fn update_components_with<T: Send>(
+  entities: &Entities,
+  update: impl Fn(&Entity) -> Option<(ComponentSet, T)> + Sync,
+) -> (Entities, Vec<(EntityHandle, T)>) {
+  let snapshot = entities.values().collect::<Vec<_>>();
+  let updates = snapshot
+    .par_iter()
+    .with_min_len(MIN_ENTITIES_PER_JOB)
+    .filter_map(|entity| Some((entity.handle, update(entity)?)))
+    .collect::<Vec<_>>();
+
+  return updates.into_iter().fold(
+    (entities.clone(), vec![]),
+    |(entities, mut outputs), (handle, (components, output))| {
+      outputs.push((handle, output));
+      (
+        entities.insert(handle, Arc::new(Entity { handle, components })),
+        outputs,
+      )
+    },
+  );
+}
+
+pub fn activation_of(entities: &Entities, activator_id: i32) -> Option<f32> {
+  entities.iter().find_map(|(_, entity)| {
+    if let Some(activator) = entity.components.get::<Activator>()
+      && let Some(id) = entity.components.get::<Id>()
+      && activator_id == id.id
+    {
+      Some(activator.activation)
+    } else {
+      None
+    }
+  })
+}
+
+fn activations_of_pair(entities: &Entities, activator_ids: (i32, i32)) -> Option<(f32, f32)> {
+  Some((
+    activation_of(entities, activator_ids.0)?,
+    activation_of(entities, activator_ids.1)?,
+  ))
+}
+
+/* MARK: Single node rules */
+
+pub fn and_activation(activation_1: f32, activation_2: f32) -> f32 {
+  activation_1.min(activation_2)
+}
+
+pub fn or_activation(activation_1: f32, activation_2: f32) -> f32 {
+  (activation_1 + activation_2).min(1.0)
+}
+
+/* Gates latch, never falling below the highest activation they have seen */
+pub fn gate_activation(gate: &Gate, incoming_activation: f32) -> f32 {
+  incoming_activation.max(gate.highest_historical_activation)
+}
+
+/* Engines oscillate between 0 and 1 at a speed set by their incoming activation, moving
+`time_scale` frames' worth each step */
+pub fn engine_step(
+  engine: &Engine,
+  activation: f32,
+  incoming_activation: f32,
+  time_scale: f32,
+) -> (f32, bool) {
+  let activation_change = ENGINE_MAX_SPEED * incoming_activation * time_scale;
+
+  let (activation, currently_increasing) = if engine.currently_increasing {
+    if activation >= 1.0 {
+      (activation - activation_change, false)
+    } else {
+      (activation + activation_change, true)
+    }
+  } else if activation <= 0.0 {
+    (activation + activation_change, true)
+  } else {
+    (activation - activation_change, false)
+  };
+
+  (activation.clamp(0.0, 1.0), currently_increasing)
+}
+
+/* MARK: Passes */
+
+pub fn touch_sensor_pass(
+  entities: &Entities,
+  touching: impl Fn(&EntityHandle) -> bool + Sync,
+) -> Entities {
+  update_components(entities, |entity| {
+    let touch_sensor = entity.components.get::<TouchSensor>()?;
+    entity.components.get::<Activator>()?;
+
+    let activation = if touching(&entity.handle) {
+      touch_sensor.target_activation
+    } else {
+      0.0
+    };
+    Some(entity.components.with(Activator { activation }))
+  })
+}
+
+pub fn engine_pass(entities: &Entities, time_scale: f32) -> Entities {
+  update_components(entities, |entity| {
+    let engine = entity.components.get::<Engine>()?;
+    let activator = entity.components.get::<Activator>()?;
+    let incoming_activation = engine
+      .activator_id
+      .map(|activator_id| activation_of(entities, activator_id))
+      .unwrap_or(Some(0.0))?;
+
+    let (activation, currently_increasing) = engine_step(
+      &engine,
+      activator.activation,
+      incoming_activation,
+      time_scale,
+    );
+
+    Some(
+      entity
+        .components
+        .with(Engine {
+          activator_id: engine.activator_id,
+          currently_increasing,
+        })
+        .with(Activator { activation }),
+    )
+  })
+}
+
+pub fn simple_activatable_pass(entities: &Entities) -> Entities {
+  update_components(entities, |entity| {
+    let activatable = entity.components.get::<SimpleActivatable>()?;
+    let activation = activation_of(entities, activatable.activator_id)?;
+
+    Some(entity.components.with(SimpleActivatable {
+      activation,
+      activator_id: activatable.activator_id,
+    }))
+  })
+}
+
+pub fn and_pass(entities: &Entities) -> Entities {
+  update_components(entities, |entity| {
+    let and = entity.components.get::<And>()?;
+    let (activation_1, activation_2) = activations_of_pair(entities, and.activator_ids)?;
+
+    Some(entity.components.with(Activator {
+      activation: and_activation(activation_1, activation_2),
+    }))
+  })
+}
+
+pub fn or_pass(entities: &Entities) -> Entities {
+  update_components(entities, |entity| {
+    let or = entity.components.get::<Or>()?;
+    let (activation_1, activation_2) = activations_of_pair(entities, or.activator_ids)?;
+
+    Some(entity.components.with(Activator {
+      activation: or_activation(activation_1, activation_2),
+    }))
+  })
+}
+
+pub fn gate_pass(entities: &Entities) -> Entities {
+  update_components(entities, |entity| {
+    let gate = entity.components.get::<Gate>()?;
+    let incoming_activation = activation_of(entities, gate.activator_id)?;
+
+    let activation = gate_activation(&gate, incoming_activation);
+    Some(entity.components.with(Activator { activation }).with(Gate {
+      activator_id: gate.activator_id,
+      highest_historical_activation: activation,
+    }))
+  })
+}
+
+/* Everything after touch sensors, which need the narrow phase to resolve */
+pub fn logic_passes(entities: &Entities, time_scale: f32) -> Entities {
+  let entities = engine_pass(entities, time_scale);
+  let entities = simple_activatable_pass(&entities);
+  let entities = and_pass(&entities);
+  let entities = or_pass(&entities);
+  gate_pass(&entities)
+}
+
+#[cfg(test)]
+mod tests {
+  use rapier2d::prelude::ColliderHandle;
+
+  use super::*;
+  use crate::ecs::ComponentSet;
+
+  const EPSILON: f32 = 1e-5;
+  const GRAPHS: u64 = 100;
+  const FRAMES: usize = 120;
+
+  /* Small xorshift so every failing graph can be rebuilt from its seed */
+  struct Rng(u64);
+
+  impl Rng {
+    fn next(&mut self) -> u64 {
+      self.0 ^= self.0 << 13;
+      self.0 ^= self.0 >> 7;
+      self.0 ^= self.0 << 17;
+      self.0
+    }
+
+    fn unit(&mut self) -> f32 {
+      (self.next() % 10_001) as f32 / 10_000.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+      (self.next() % bound as u64) as usize
+    }
+  }
+
+  fn handle(index: usize) -> EntityHandle {
+    EntityHandle::Collider(ColliderHandle::from_raw_parts(index as u32, 0))
+  }
+
+  fn node(index: usize, components: ComponentSet) -> (EntityHandle, Arc<Entity>) {
+    (
+      handle(index),
+      Arc::new(Entity {
+        handle: handle(index),
+        components: components
+          .insert(Id { id: index as i32 })
+          .insert(Activator { activation: 0.0 }),
+      }),
+    )
+  }
+
+  /* Touch sensors first, then logic nodes which only reference earlier ids */
+  fn random_graph(rng: &mut Rng) -> Entities {
+    let sensor_count = 1 + rng.below(4);
+    let logic_count = 1 + rng.below(12);
+
+    let sensors = (0..sensor_count)
+      .map(|index| {
+        node(
+          index,
+          ComponentSet::new().insert(TouchSensor {
+            target_activation: rng.unit(),
+          }),
+        )
+      })
+      .collect::<Vec<_>>();
+
+    let logic = (sensor_count..sensor_count + logic_count)
+      .map(|index| {
+        let kind = rng.below(5);
+        let mut input = || rng.below(index) as i32;
+        let components = match kind {
+          0 => ComponentSet::new().insert(And {
+            activator_ids: (input(), input()),
+          }),
+          1 => ComponentSet::new().insert(Or {
+            activator_ids: (input(), input()),
+          }),
+          2 => ComponentSet::new().insert(Gate {
+            activator_id: input(),
+            highest_historical_activation: 0.0,
+          }),
+          3 => ComponentSet::new().insert(Engine {
+            activator_id: Some(input()),
+            currently_increasing: true,
+          }),
+          _ => ComponentSet::new().insert(SimpleActivatable {
+            activation: 0.0,
+            activator_id: input(),
+          }),
+        };
+        node(index, components)
+      })
+      .collect::<Vec<_>>();
+
+    sensors.into_iter().chain(logic).collect()
+  }
+
+  fn activation(entities: &Entities, handle: &EntityHandle) -> f32 {
+    entities[handle]
+      .components
+      .get::<Activator>()
+      .unwrap()
+      .activation
+  }
+
+  fn assert_in_unit_range(entities: &Entities, seed: u64) {
+    entities.iter().for_each(|(handle, _)| {
+      let activation = activation(entities, handle);
+      assert!(
+        (-EPSILON..=1.0 + EPSILON).contains(&activation),
+        "seed {}: activation {} out of range",
+        seed,
+        activation
+      );
+    });
+  }
+
+  #[test]
+  fn activator_graph_invariants() {
+    (1..=GRAPHS).for_each(|seed| {
+      let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+      let mut entities = random_graph(&mut rng);
+
+      (0..FRAMES).for_each(|_| {
+        let touching = entities
+          .keys()
+          .filter(|_| rng.below(2) == 0)
+          .cloned()
+          .collect::<Vec<_>>();
+
+        let sensed = touch_sensor_pass(&entities, |handle| touching.contains(handle));
+        let engines = engine_pass(&sensed, 1.0);
+        let simple = simple_activatable_pass(&engines);
+        let ands = and_pass(&simple);
+
+        /* And never exceeds its weakest input */
+        ands.iter().for_each(|(handle, entity)| {
+          if let Some(and) = entity.components.get::<And>() {
+            let (activation_1, activation_2) =
+              activations_of_pair(&simple, and.activator_ids).unwrap();
+            assert!(
+              activation(&ands, handle) <= activation_1.min(activation_2) + EPSILON,
+              "seed {}: and above its weakest input",
+              seed
+            );
+          }
+        });
+
+        let next = gate_pass(&or_pass(&ands));
+
+        /* The stepped stages match what PhysicsSystem runs */
+        let combined = logic_passes(&sensed, 1.0);
+        combined.iter().for_each(|(handle, _)| {
+          assert_eq!(activation(&next, handle), activation(&combined, handle));
+        });
+
+        /* Gates only ever rise */
+        next.iter().for_each(|(handle, entity)| {
+          if entity.components.get::<Gate>().is_some() {
+            assert!(
+              activation(&next, handle) >= activation(&entities, handle) - EPSILON,
+              "seed {}: gate fell",
+              seed
+            );
+          }
+        });
+
+        assert_in_unit_range(&next, seed);
+        entities = next;
+      });
+    });
+  }
+}
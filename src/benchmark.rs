@@ -0,0 +1,307 @@
+use std::{fs, rc::Rc, time::Instant};
+
+use rapier2d::prelude::*;
+use rpds::HashTrieMap;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "scripting")]
+use crate::script::ScriptRegistry;
+use crate::{
+  data::try_load_game_data,
+  enemy,
+  log::{self, LogLevel},
+  menu::SaveToLoad,
+  physics::{FrameInputs, FrameState, MapLoad, PHYSICS_STAGES, load_new_map},
+  procgen::{self, Constraints, flag_value},
+  save::load_save,
+  settings::Assist,
+  units::{PhysicsVector, UnitConvert2},
+};
+
+/* As in `--benchmark 600`, the frame count is optional */
+pub const BENCHMARK_FLAG: &str = "--benchmark";
+/* A report from an earlier run to compare against, as in `--benchmark-baseline before.json` */
+const BASELINE_FLAG: &str = "--benchmark-baseline";
+/* Fills a bigger stress room, as in `--benchmark-enemies 2000` for thousands of entities */
+const ENEMIES_FLAG: &str = "--benchmark-enemies";
+/* Caps the threads the per-entity passes run on, as in `--benchmark-threads 1` to compare a
+single core against all of them */
+const THREADS_FLAG: &str = "--benchmark-threads";
+
+const DEFAULT_FRAMES: usize = 600;
+
+/* The stress room is generated the same way every run, so reports can be compared */
+const STRESS_MAP_SEED: u64 = 677;
+const STRESS_MAP_NAME: &str = "benchmark";
+const STRESS_MAP_ENEMY_BUDGET: i32 = 160;
+/* The room grows with the budget so enemies stay about as crowded as in the default room */
+fn stress_map_constraints(enemy_budget: i32) -> Constraints {
+  let scale = (enemy_budget as f32 / STRESS_MAP_ENEMY_BUDGET as f32)
+    .sqrt()
+    .max(1.0);
+  Constraints {
+    width: (120.0 * scale) as i32,
+    height: (80.0 * scale) as i32,
+    enemy_budget,
+    pickups: vec![],
+  }
+}
+
+/* A part has only regressed once it's this much slower than the baseline, and by more than
+the noise floor, since the fastest stages take a few microseconds */
+const REGRESSION_TOLERANCE: f32 = 0.25;
+const REGRESSION_NOISE_FLOOR_MS: f32 = 0.05;
+
+/* Milliseconds spent in one part of the frame over the whole run */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Timing {
+  pub name: String,
+  pub mean_ms: f32,
+  pub median_ms: f32,
+  pub p95_ms: f32,
+  pub max_ms: f32,
+}
+
+impl Timing {
+  fn of(name: &str, samples: &[f32]) -> Self {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let percentile = |percent: usize| sorted[(sorted.len() - 1) * percent / 100];
+
+    Self {
+      name: name.to_string(),
+      mean_ms: sorted.iter().sum::<f32>() / sorted.len() as f32,
+      median_ms: percentile(50),
+      p95_ms: percentile(95),
+      max_ms: percentile(100),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BenchmarkReport {
+  pub frames: usize,
+  /* Entities in the room at the start and end of the run */
+  pub entities_start: usize,
+  pub entities_end: usize,
+  /* Threads the per-entity passes could spread over, reports from before this was kept have 0 */
+  #[serde(default)]
+  pub threads: usize,
+  /* Enemy decisions, then every physics stage in order, then rebuilding the system */
+  pub timings: Vec<Timing>,
+  pub total: Timing,
+}
+
+/* The player circles around the room so that enemies have something moving to chase */
+fn scripted_left_stick(frame: usize) -> PhysicsVector {
+  let angle = frame as f32 * 0.02;
+  PhysicsVector::from_vec(vector![angle.cos(), angle.sin()])
+}
+
+fn benchmark(frames: usize, enemy_budget: i32) -> Result<BenchmarkReport, String> {
+  let data = try_load_game_data()?;
+  let save_data = load_save(&SaveToLoad::Initial(Default::default()))?;
+  let map = procgen::generate(STRESS_MAP_SEED, &stress_map_constraints(enemy_budget))?.map;
+  let player_spawn_id = map
+    .player_spawns
+    .first()
+    .ok_or("the stress map has no player spawn")?
+    .id;
+  #[cfg(feature = "scripting")]
+  let scripts = ScriptRegistry::load();
+
+  let initial = load_new_map(MapLoad {
+    map: &map,
+    map_name: STRESS_MAP_NAME,
+    map_states: Rc::new(HashTrieMap::new()),
+    acquired_modules: &save_data.acquired_items,
+    target_player_spawn_id: player_spawn_id,
+    player_health: save_data.player_health,
+    player_max_health: save_data.player_max_health,
+    boost_acquired: save_data.acquired_boost,
+    chain_acquired: save_data.acquired_chain,
+    magnetize_acquired: save_data.acquired_magnetize,
+    hack_acquired: save_data.acquired_hack,
+    engine_upgrades: &save_data.engine_upgrades,
+    discovered_logs: &save_data.discovered_logs,
+    player_translation_override: None,
+    entry: None,
+    player_two_health: None,
+    enemy_definitions: &data.enemies,
+    difficulty: save_data.difficulty,
+    suspended_room: None,
+  });
+  let entities_start = initial.entities.size();
+  let movement = data.movement.stats(&save_data.engine_upgrades);
+  let chunks = Rc::new(HashTrieMap::new());
+
+  let names = ["ai"]
+    .into_iter()
+    .chain(PHYSICS_STAGES.iter().map(|(name, _)| *name))
+    .chain(["rebuild"])
+    .collect::<Vec<_>>();
+
+  let (physics_system, samples, totals) = (0..frames).fold(
+    (initial, vec![Vec::new(); names.len()], Vec::new()),
+    |(physics_system, mut samples, mut totals), frame| {
+      let frame_started = Instant::now();
+
+      let started = Instant::now();
+      let enemy_decisions = enemy::decide(
+        &physics_system,
+        &data.enemies,
+        #[cfg(feature = "scripting")]
+        &scripts,
+      );
+      samples[0].push(started.elapsed().as_secs_f32() * 1000.0);
+
+      let inputs = FrameInputs {
+        frame_count: physics_system.frame_count,
+        simulated_frames: physics_system.simulated_frames,
+        time_scale: 1.0,
+        whole_frame: true,
+        player_handle: physics_system.player_handle,
+        map_name: &physics_system.map_name,
+        left_stick: scripted_left_stick(frame),
+        coop_gamepad_claimed: false,
+        coop_left_stick: PhysicsVector::from_vec(vector![0.0, 0.0]),
+        saved_player_two_health: None,
+        boost_force: None,
+        interact: false,
+        dismount: false,
+        projectiles: &[],
+        player_two_projectiles: &[],
+        drone_specs: &[],
+        enemy_decisions: &enemy_decisions,
+        enemy_definitions: &data.enemies,
+        entity_limits: data.limits,
+        movement,
+        difficulty: save_data.difficulty,
+        chain_to_mount_point: None,
+        kill_chain: false,
+        magnetize: false,
+        hack: None,
+        assist: Assist::default(),
+        physics_pipeline: &physics_system.physics_pipeline,
+        integration_parameters: physics_system.integration_parameters,
+        cycle_phase: physics_system.cycle_phase(),
+        friendly_fire: physics_system.friendly_fire,
+        chunks: Rc::clone(&chunks),
+      };
+
+      let frame_state = PHYSICS_STAGES.iter().enumerate().fold(
+        FrameState::from_system(&physics_system),
+        |frame_state, (index, (_, stage))| {
+          let started = Instant::now();
+          let frame_state = stage(frame_state, &inputs);
+          samples[index + 1].push(started.elapsed().as_secs_f32() * 1000.0);
+          frame_state
+        },
+      );
+
+      let started = Instant::now();
+      let next = physics_system.with_frame(
+        frame_state,
+        1.0,
+        Rc::clone(&physics_system.rewind_snapshots),
+      );
+      samples[names.len() - 1].push(started.elapsed().as_secs_f32() * 1000.0);
+
+      totals.push(frame_started.elapsed().as_secs_f32() * 1000.0);
+      (Rc::new(next), samples, totals)
+    },
+  );
+
+  Ok(BenchmarkReport {
+    frames,
+    entities_start,
+    entities_end: physics_system.entities.size(),
+    threads: rayon::current_num_threads(),
+    timings: names
+      .iter()
+      .zip(&samples)
+      .map(|(name, samples)| Timing::of(name, samples))
+      .collect(),
+    total: Timing::of("total", &totals),
+  })
+}
+
+/* Parts of the frame whose mean went up past the tolerance since the baseline */
+fn regressions(report: &BenchmarkReport, baseline: &BenchmarkReport) -> Vec<String> {
+  report
+    .timings
+    .iter()
+    .chain([&report.total])
+    .filter_map(|timing| {
+      let before = baseline
+        .timings
+        .iter()
+        .chain([&baseline.total])
+        .find(|before| before.name == timing.name)?;
+
+      (timing.mean_ms > before.mean_ms * (1.0 + REGRESSION_TOLERANCE)
+        && timing.mean_ms - before.mean_ms > REGRESSION_NOISE_FLOOR_MS)
+        .then(|| {
+          format!(
+            "{} went from {:.3}ms to {:.3}ms",
+            timing.name, before.mean_ms, timing.mean_ms
+          )
+        })
+    })
+    .collect()
+}
+
+/* Prints the report as JSON, and fails if a baseline was given and anything regressed */
+pub fn run() -> bool {
+  /* Keep whatever the game logs out of the report */
+  log::configure(LogLevel::Error, false);
+
+  let frames = flag_value(BENCHMARK_FLAG)
+    .and_then(|frames| frames.parse::<usize>().ok())
+    .unwrap_or(DEFAULT_FRAMES)
+    .max(1);
+  let enemy_budget = flag_value(ENEMIES_FLAG)
+    .and_then(|budget| budget.parse::<i32>().ok())
+    .unwrap_or(STRESS_MAP_ENEMY_BUDGET);
+  /* Without the flag rayon picks one thread per core */
+  let threads = flag_value(THREADS_FLAG)
+    .and_then(|threads| threads.parse::<usize>().ok())
+    .unwrap_or(0);
+
+  let report = rayon::ThreadPoolBuilder::new()
+    .num_threads(threads)
+    .build()
+    .map_err(|error| error.to_string())
+    .and_then(|pool| pool.install(|| benchmark(frames, enemy_budget)));
+  let report = match report {
+    Ok(report) => report,
+    Err(error) => {
+      eprintln!("couldn't run the benchmark: {}", error);
+      return false;
+    }
+  };
+  println!("{}", serde_json::to_string_pretty(&report).unwrap());
+
+  let Some(baseline_path) = flag_value(BASELINE_FLAG) else {
+    return true;
+  };
+  let baseline = fs::read_to_string(&baseline_path)
+    .map_err(|error| error.to_string())
+    .and_then(|raw| {
+      serde_json::from_str::<BenchmarkReport>(&raw).map_err(|error| error.to_string())
+    });
+
+  match baseline {
+    Ok(baseline) => {
+      let regressions = regressions(&report, &baseline);
+      regressions
+        .iter()
+        .for_each(|regression| eprintln!("regressed: {}", regression));
+      regressions.is_empty()
+    }
+    Err(error) => {
+      eprintln!("couldn't read the baseline {}: {}", baseline_path, error);
+      false
+    }
+  }
+}
@@ -7,14 +7,34 @@ use macroquad::{
 use rapier2d::{na::Vector2, prelude::*};
 
 use crate::{
+  controls::{ControlMode, ControlsSystem},
+  dialogue::DialogueSystem,
   load_map::MapSystem,
+  menu::MenuSystem,
   physics::PhysicsSystem,
   save::SaveData,
-  system::System,
+  system::{Requirement, System},
   units::{PhysicsVector, ScreenVector, UnitConvert2, vec_zero},
 };
 
 const CAMERA_SCREEN_MARGIN: f32 = 0.4;
+/* Fraction of the remaining distance covered each frame while a dialogue pans */
+const CAMERA_PAN_RATE: f32 = 0.08;
+
+/* Screen pixels per frame at full tilt, slower when zoomed in */
+const PHOTO_PAN_SPEED: f32 = 12.0;
+/* Fraction the zoom changes by each frame it's held, or per scroll notch */
+const PHOTO_ZOOM_RATE: f32 = 0.03;
+const PHOTO_ZOOM_MIN: f32 = 0.5;
+const PHOTO_ZOOM_MAX: f32 = 3.0;
+
+/* Fraction of the screen kept clear around the co-op players when zooming out to fit both */
+const COOP_FRAME_MARGIN: f32 = 0.15;
+/* Past this the players are left to catch up with each other */
+const COOP_ZOOM_MIN: f32 = 0.5;
+/* Fraction of the way to the zoom that fits both players covered each frame */
+const COOP_ZOOM_RATE: f32 = 0.1;
+
 fn camera_screen_bounds() -> Rect {
   return Rect {
     x: CAMERA_SCREEN_MARGIN * screen_width(),
@@ -25,10 +45,10 @@ fn camera_screen_bounds() -> Rect {
 }
 
 fn get_camera_translation_change(player_translation: ScreenVector) -> Vector2<f32> {
-  let bounds_offset_left = -1.0 * (camera_screen_bounds().x - player_translation.x()).max(0.0);
+  let bounds_offset_left = -(camera_screen_bounds().x - player_translation.x()).max(0.0);
   let bounds_offset_right =
     (player_translation.x() - (camera_screen_bounds().x + camera_screen_bounds().w)).max(0.0);
-  let bounds_offset_down = -1.0 * (camera_screen_bounds().y - player_translation.y()).max(0.0);
+  let bounds_offset_down = -(camera_screen_bounds().y - player_translation.y()).max(0.0);
   let bounds_offset_up =
     (player_translation.y() - (camera_screen_bounds().y + camera_screen_bounds().h)).max(0.0);
   let bounds_offset_total = vector![
@@ -45,15 +65,54 @@ fn get_camera_translation_change(player_translation: ScreenVector) -> Vector2<f3
 
 pub struct CameraSystem {
   pub translation: Vector2<f32>,
+  /* Only ever changed in photo mode */
+  pub zoom: f32,
+  /* Where the camera was when photo mode opened, to go back to once it closes */
+  photo_origin: Option<Vector2<f32>>,
+}
+
+impl CameraSystem {
+  /* Where something at the given screen position is drawn once the zoom, which scales about the
+  middle of the screen, is applied */
+  pub fn zoomed(&self, screen_pos: ScreenVector) -> ScreenVector {
+    let center = vector![screen_width() / 2.0, screen_height() / 2.0];
+    ScreenVector::from_vec(center + (screen_pos.into_vec() - center) * self.zoom)
+  }
+}
+
+/* Zoomed out just far enough to keep both players on screen, with the camera on their midpoint */
+fn coop_zoom(player_offset: Vector2<f32>) -> f32 {
+  let fit = |offset: f32, extent: f32| {
+    if offset == 0.0 {
+      1.0
+    } else {
+      (0.5 - COOP_FRAME_MARGIN) * extent / offset.abs()
+    }
+  };
+
+  fit(player_offset.x, screen_width())
+    .min(fit(player_offset.y, screen_height()))
+    .clamp(COOP_ZOOM_MIN, 1.0)
 }
 
 impl System for CameraSystem {
   type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<MapSystem>(),
+      Requirement::of::<PhysicsSystem>(),
+      Requirement::of::<DialogueSystem>(),
+      Requirement::of::<MenuSystem<_>>(),
+      Requirement::of::<ControlsSystem<_>>(),
+    ]
+  }
+
   fn start(ctx: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
   where
     Self: Sized,
   {
-    let map_system = ctx.get::<MapSystem>().unwrap();
+    let map_system = ctx.require::<MapSystem>().unwrap();
 
     return Rc::new(Self {
       translation: map_system
@@ -68,6 +127,8 @@ impl System for CameraSystem {
         .into_pos(vec_zero())
         .into_vec()
         - vector![screen_width() / 2.0, screen_height() / 2.0],
+      zoom: 1.0,
+      photo_origin: None,
     });
   }
 
@@ -75,9 +136,11 @@ impl System for CameraSystem {
     &self,
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> Rc<dyn System<Input = Self::Input>> {
-    let map_system = ctx.get::<MapSystem>().unwrap();
+    let map_system = ctx.require::<MapSystem>().unwrap();
 
-    if let Some(map) = map_system.map.as_ref() {
+    if let Some(map) = map_system.map.as_ref()
+      && !map_system.hot_reloaded
+    {
       return Rc::new(Self {
         translation: map
           .player_spawns
@@ -88,18 +151,94 @@ impl System for CameraSystem {
           .into_pos(vec_zero())
           .into_vec()
           - vector![screen_width() / 2.0, screen_height() / 2.0],
+        zoom: 1.0,
+        photo_origin: None,
+      });
+    }
+
+    /* MARK: Photo mode frees the camera */
+    let menu_system = ctx.require::<MenuSystem<_>>().unwrap();
+
+    if menu_system.in_photo_mode() {
+      let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
+
+      /* The mouse aims relative to the player, so the keyboard pans with movement instead. The
+      right stick zooms instead of panning while it's clicked in */
+      let pan = match controls_system.control_mode {
+        ControlMode::GamePad if controls_system.zoom != 0.0 => vec_zero(),
+        ControlMode::GamePad => controls_system.right_stick.into_vec(),
+        ControlMode::Keyboard => controls_system
+          .left_stick
+          .into_vec()
+          .try_normalize(0.0)
+          .unwrap_or(vec_zero()),
+      };
+
+      let zoom = (self.zoom * (1.0 + controls_system.zoom * PHOTO_ZOOM_RATE))
+        .clamp(PHOTO_ZOOM_MIN, PHOTO_ZOOM_MAX);
+
+      return Rc::new(Self {
+        translation: self.translation + vector![pan.x, -pan.y] * PHOTO_PAN_SPEED / zoom,
+        zoom,
+        photo_origin: self.photo_origin.or(Some(self.translation)),
+      });
+    }
+
+    if let Some(photo_origin) = self.photo_origin {
+      return Rc::new(Self {
+        translation: photo_origin,
+        zoom: 1.0,
+        photo_origin: None,
       });
     }
 
-    let physics_system = ctx.get::<PhysicsSystem>().unwrap();
+    /* MARK: Dialogue pans ease towards their focus */
+    let dialogue_system = ctx.require::<DialogueSystem>().unwrap();
+
+    if let Some(camera_focus) = dialogue_system.camera_focus() {
+      let focused_translation = PhysicsVector::from_vec(camera_focus)
+        .into_pos(vec_zero())
+        .into_vec()
+        - vector![screen_width() / 2.0, screen_height() / 2.0];
+
+      return Rc::new(Self {
+        translation: self.translation + (focused_translation - self.translation) * CAMERA_PAN_RATE,
+        zoom: 1.0,
+        photo_origin: None,
+      });
+    }
+
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+
+    /* With a co-op partner the camera follows the midpoint between both players, zooming out to
+    keep both in frame */
+    let player_translation =
+      *physics_system.rigid_body_set[physics_system.player_handle].translation();
+    let (followed_translation, target_zoom) = match physics_system.player_two_handle {
+      Some(player_two_handle) => {
+        let player_two_translation = physics_system.rigid_body_set[player_two_handle].translation();
+        (
+          (player_translation + player_two_translation) / 2.0,
+          coop_zoom(
+            PhysicsVector::from_vec(*player_two_translation)
+              .into_pos(self.translation)
+              .into_vec()
+              - PhysicsVector::from_vec(player_translation)
+                .into_pos(self.translation)
+                .into_vec(),
+          ),
+        )
+      }
+      None => (player_translation, 1.0),
+    };
 
-    let player_translation = PhysicsVector::from_vec(
-      *physics_system.rigid_body_set[physics_system.player_handle].translation(),
-    )
-    .into_pos(self.translation);
+    let player_translation =
+      PhysicsVector::from_vec(followed_translation).into_pos(self.translation);
 
     return Rc::new(Self {
       translation: self.translation + get_camera_translation_change(player_translation),
+      zoom: self.zoom + (target_zoom - self.zoom) * COOP_ZOOM_RATE,
+      photo_origin: None,
     });
   }
 }
@@ -0,0 +1,256 @@
+use std::{collections::HashMap, rc::Rc};
+
+use rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  ecs::{Damageable, Enemy, EntityHandle, Faction},
+  load_map::{ChallengeCondition, ChallengeRoom},
+  physics::PhysicsSystem,
+  save::SaveData,
+  system::{Requirement, System},
+};
+
+/* Challenge times are given in seconds of game time rather than real time */
+const SIMULATED_FRAMES_PER_SECOND: f64 = 60.0;
+/* Ghost runs keep the player's position every this many simulated frames, and are interpolated between */
+const GHOST_SAMPLE_FRAMES: f64 = 6.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Medal {
+  Bronze,
+  Silver,
+  Gold,
+}
+
+fn medal_for(challenge: &ChallengeRoom, time: f32) -> Option<Medal> {
+  [
+    (challenge.gold, Medal::Gold),
+    (challenge.silver, Medal::Silver),
+    (challenge.bronze, Medal::Bronze),
+  ]
+  .into_iter()
+  .find(|(target, _)| time <= *target)
+  .map(|(_, medal)| medal)
+}
+
+/* Shown once the player leaves a challenge room through an exit */
+#[derive(Clone, Debug)]
+pub struct ChallengeResult {
+  pub time: f32,
+  pub medal: Option<Medal>,
+  /* The room's best medal before this attempt */
+  pub best: Option<Medal>,
+  pub failed_conditions: Vec<ChallengeCondition>,
+}
+
+/* The fastest clean run through a challenge room, played back alongside later attempts */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChallengeGhost {
+  pub time: f32,
+  pub path: Vec<(f32, f32)>,
+}
+
+pub struct ChallengeSystem {
+  pub map_name: String,
+  /* Game time the room was entered at */
+  pub start_frames: f64,
+  /* Health the player had last frame, to notice them being hurt */
+  pub player_health: f32,
+  pub damaged: bool,
+  /* Best medal earned in each challenge room, keyed by map name */
+  pub medals: Rc<HashMap<String, Medal>>,
+  /* Player positions through the current challenge room so far */
+  pub recording: Rc<Vec<(f32, f32)>>,
+  /* Best clean run through each challenge room, keyed by map name */
+  pub ghosts: Rc<HashMap<String, ChallengeGhost>>,
+  /* Set on the frame the player leaves a challenge room */
+  pub result: Option<ChallengeResult>,
+}
+
+impl ChallengeSystem {
+  /* Seconds of game time spent in the room so far */
+  pub fn time(&self, physics_system: &PhysicsSystem) -> f32 {
+    ((physics_system.simulated_frames - self.start_frames) / SIMULATED_FRAMES_PER_SECOND) as f32
+  }
+
+  /* Where the best run was at this point of the current attempt, gone once that run reached the exit */
+  pub fn ghost_translation(&self, physics_system: &PhysicsSystem) -> Option<Vector<f32>> {
+    physics_system.challenge.as_ref()?;
+    let ghost = self.ghosts.get(&self.map_name)?;
+
+    let sample = (physics_system.simulated_frames - self.start_frames) / GHOST_SAMPLE_FRAMES;
+    let index = sample.floor() as usize;
+    let (from, to) = (ghost.path.get(index)?, ghost.path.get(index + 1)?);
+    let progress = sample.fract() as f32;
+
+    Some(vector![
+      from.0 + (to.0 - from.0) * progress,
+      from.1 + (to.1 - from.1) * progress
+    ])
+  }
+}
+
+fn player_translation(physics_system: &PhysicsSystem) -> (f32, f32) {
+  let translation = physics_system.rigid_body_set[physics_system.player_handle].translation();
+  (translation.x, translation.y)
+}
+
+fn player_health(physics_system: &PhysicsSystem) -> Option<f32> {
+  physics_system
+    .entities
+    .get(&EntityHandle::RigidBody(physics_system.player_handle))
+    .and_then(|player| player.components.get::<Damageable>())
+    .map(|damageable| damageable.health)
+}
+
+impl System for ChallengeSystem {
+  type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![Requirement::of::<PhysicsSystem>()]
+  }
+
+  fn start(ctx: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
+  where
+    Self: Sized,
+  {
+    Rc::new(Self {
+      map_name: ctx.input.map_name.clone(),
+      start_frames: 0.0,
+      player_health: ctx.input.player_health,
+      damaged: false,
+      medals: Rc::new(ctx.input.medals.clone()),
+      recording: Rc::new(vec![]),
+      ghosts: Rc::new(ctx.input.challenge_ghosts.clone()),
+      result: None,
+    })
+  }
+
+  fn run(
+    &self,
+    ctx: &crate::system::ProcessContext<Self::Input>,
+  ) -> Rc<dyn System<Input = Self::Input>> {
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+    let player_health = player_health(&physics_system).unwrap_or(self.player_health);
+
+    /* MARK: Start the clock over on entering a room */
+    if physics_system.map_name != self.map_name {
+      return Rc::new(Self {
+        map_name: physics_system.map_name.clone(),
+        start_frames: physics_system.simulated_frames,
+        player_health,
+        damaged: false,
+        medals: Rc::clone(&self.medals),
+        recording: Rc::new(vec![]),
+        ghosts: Rc::clone(&self.ghosts),
+        result: None,
+      });
+    }
+
+    let damaged = self.damaged || player_health < self.player_health;
+
+    /* MARK: Record the run for a ghost */
+    let samples_due = ((physics_system.simulated_frames - self.start_frames) / GHOST_SAMPLE_FRAMES)
+      .floor() as usize
+      + 1;
+    let recording = if physics_system.challenge.is_some() && self.recording.len() < samples_due {
+      Rc::new(
+        self
+          .recording
+          .iter()
+          .copied()
+          .chain([player_translation(&physics_system)])
+          .collect(),
+      )
+    } else {
+      Rc::clone(&self.recording)
+    };
+
+    let (Some(challenge), Some(_)) = (&physics_system.challenge, &physics_system.load_new_map)
+    else {
+      return Rc::new(Self {
+        map_name: self.map_name.clone(),
+        player_health,
+        damaged,
+        medals: Rc::clone(&self.medals),
+        recording,
+        ghosts: Rc::clone(&self.ghosts),
+        result: None,
+        ..*self
+      });
+    };
+
+    /* MARK: Award a medal for the time the exit was reached in */
+    let enemies_left = physics_system.entities.values().any(|entity| {
+      entity.components.get::<Enemy>().is_some()
+        && Faction::Player.hostile_to(Faction::of(&entity.components), false)
+    });
+    let failed_conditions = challenge
+      .conditions
+      .iter()
+      .copied()
+      .filter(|condition| match condition {
+        ChallengeCondition::NoDamage => damaged,
+        ChallengeCondition::AllKills => enemies_left,
+      })
+      .collect::<Vec<_>>();
+
+    let time = self.time(&physics_system);
+    let medal = if failed_conditions.is_empty() {
+      medal_for(challenge, time)
+    } else {
+      None
+    };
+    let best = self.medals.get(&self.map_name).copied();
+
+    let medals = match medal {
+      Some(medal) if Some(medal) > best => Rc::new(
+        self
+          .medals
+          .iter()
+          .map(|(map_name, medal)| (map_name.clone(), *medal))
+          .chain([(self.map_name.clone(), medal)])
+          .collect(),
+      ),
+      _ => Rc::clone(&self.medals),
+    };
+
+    /* MARK: Keep the run as the room's ghost if it's the fastest clean one */
+    let best_ghost_time = self.ghosts.get(&self.map_name).map(|ghost| ghost.time);
+    let ghosts = if failed_conditions.is_empty() && best_ghost_time.is_none_or(|best| time < best) {
+      Rc::new(
+        self
+          .ghosts
+          .iter()
+          .map(|(map_name, ghost)| (map_name.clone(), ghost.clone()))
+          .chain([(
+            self.map_name.clone(),
+            ChallengeGhost {
+              time,
+              path: recording.as_ref().clone(),
+            },
+          )])
+          .collect(),
+      )
+    } else {
+      Rc::clone(&self.ghosts)
+    };
+
+    Rc::new(Self {
+      map_name: self.map_name.clone(),
+      player_health,
+      damaged,
+      medals,
+      recording,
+      ghosts,
+      result: Some(ChallengeResult {
+        time,
+        medal,
+        best,
+        failed_conditions,
+      }),
+      ..*self
+    })
+  }
+}
@@ -0,0 +1,89 @@
+use std::sync::OnceLock;
+
+use rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::{data::try_load_collisions, log};
+
+/* Every kind of collider as far as collision groups go. Which layers touch which is data, in
+`assets/data/collisions.json`, so adding a layer doesn't mean going through every builder */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub enum CollisionLayer {
+  Wall,
+  Player,
+  PlayerProjectile,
+  Enemy,
+  EnemyProjectile,
+  PlayerInteractible,
+  Chain,
+  Hazard,
+  /* Blasts set off by the player, apart from the projectile that set them off */
+  Explosion,
+  /* The part of a player's blast that reaches the players themselves */
+  SelfBlast,
+  /* Pieces of broken walls, which only bounce off other walls */
+  Debris,
+  /* Blasts that catch everything, set off by explosive barrels */
+  Blast,
+}
+
+const LAYER_COUNT: usize = CollisionLayer::Blast as usize + 1;
+
+impl CollisionLayer {
+  pub const fn group(self) -> Group {
+    Group::from_bits_truncate(1 << self as u32)
+  }
+}
+
+/* Used when the data file can't be read, like when running from outside the game directory */
+const BUILT_IN_COLLISIONS: &str = include_str!("../assets/data/collisions.json");
+
+/* Like the palette and the locale tables, collision groups are needed from all over */
+static MATRIX: OnceLock<[Group; LAYER_COUNT]> = OnceLock::new();
+
+/* A pair only has to be listed under one of its two layers, since rapier pairs two colliders only
+when each one's filter lets the other through */
+fn load_matrix() -> [Group; LAYER_COUNT] {
+  let definitions = try_load_collisions().unwrap_or_else(|error| {
+    log::error(
+      "collision",
+      format!(
+        "couldn't load the collision layers, using the built in ones: {}",
+        error
+      ),
+    );
+    serde_json::from_str(BUILT_IN_COLLISIONS).unwrap()
+  });
+
+  definitions.iter().fold(
+    [Group::empty(); LAYER_COUNT],
+    |mut matrix, (layer, others)| {
+      others.iter().for_each(|other| {
+        matrix[*layer as usize] |= other.group();
+        matrix[*other as usize] |= layer.group();
+      });
+      matrix
+    },
+  )
+}
+
+/* Every layer the given one touches */
+pub fn interactions(layer: CollisionLayer) -> Group {
+  MATRIX.get_or_init(load_matrix)[layer as usize]
+}
+
+pub fn groups(layer: CollisionLayer) -> InteractionGroups {
+  InteractionGroups {
+    memberships: layer.group(),
+    filter: interactions(layer),
+    test_mode: InteractionTestMode::And,
+  }
+}
+
+/* For colliders that deliberately leave out some of what their layer touches */
+pub fn groups_except(layer: CollisionLayer, excluded: Group) -> InteractionGroups {
+  InteractionGroups {
+    filter: interactions(layer).difference(excluded),
+    ..groups(layer)
+  }
+}
@@ -5,17 +5,24 @@ use std::{
 };
 
 use crate::{
-  controls::{ControlsSystem, angle_from_vec},
-  ecs::{ComponentSet, ExplodeOnCollision},
-  load_map::{
-    COLLISION_GROUP_ENEMY, COLLISION_GROUP_PLAYER_PROJECTILE, COLLISION_GROUP_WALL, MapSystem,
+  collision::{CollisionLayer, groups},
+  controls::{ControlsSystem, CoopControlsSystem, angle_from_vec},
+  data::{DataSystem, LevelBonus, WeaponDefinition, WeaponDefinitions},
+  dialogue::DialogueSystem,
+  ecs::{
+    Bounces, ComponentSet, CriticalHit, Enemy, EntityHandle, ExplodeOnCollision, Faction,
+    OnHitStatus, PowerUpKind,
   },
+  load_map::{MapSystem, WALL_QUERY_GROUPS, player_self_blast_force_groups},
+  log,
   menu::MenuSystem,
   physics::PhysicsSystem,
-  save::SaveData,
-  system::System,
+  save::{LoadoutPreset, SaveData},
+  settings::SettingsSystem,
+  system::{Requirement, System},
   units::{PhysicsVector, ScreenVector, UnitConvert, UnitConvert2},
 };
+use macroquad::rand::RandGenerator;
 use rapier2d::{
   na::{ArrayStorage, Const, Matrix, Vector2},
   prelude::*,
@@ -117,46 +124,139 @@ pub struct Projectile {
   pub initial_impulse: PhysicsVector,
   pub force_mod: f32,
   pub damage: f32,
+  pub on_hit_status: Option<OnHitStatus>,
   pub component_set: ComponentSet,
+  /* Swept against what it could hit in between steps rather than only where it lands */
+  pub ccd: bool,
 }
 
 #[derive(Clone, Copy)]
 enum ProjectileType {
   Plasma,
   Missile,
+  #[allow(dead_code)]
   Laser,
+  Drone,
 }
 
 #[derive(Clone)]
 pub struct Weapon {
   projectile_type: ProjectileType,
+  definition: WeaponDefinition,
   slot_positions: HashTrieSet<SlotPosition>,
   damage_mod: f32,
   velocity_mod: f32,
+  crit_chance: f32,
+  crit_multiplier: f32,
   current_cooldown: f32,
   max_cooldown: f32,
   reversed: bool,
+  heat: f32,
+  heat_capacity: f32,
+  overheated: bool,
 }
 
 impl Weapon {
-  pub fn reduce_cooldown(&self) -> Self {
+  /* Base stats improved by each level the module has been fused up */
+  fn levelled(&self, level: u32, levels: &LevelBonus) -> Self {
+    Self {
+      damage_mod: self.damage_mod * (1.0 + levels.damage * level as f32),
+      max_cooldown: self.max_cooldown * (1.0 - levels.cooldown).powi(level as i32),
+      ..self.clone()
+    }
+  }
+
+  /* Holds off firing for at least this many frames */
+  pub fn delay(&self, frames: f32) -> Self {
+    Self {
+      current_cooldown: self.current_cooldown.max(frames),
+      ..self.clone()
+    }
+  }
+
+  pub fn reduce_cooldown(&self, time_scale: f32) -> Self {
     let current_cooldown = if self.current_cooldown > 0.0 {
-      self.current_cooldown - 1.0
+      self.current_cooldown - time_scale
     } else {
       self.current_cooldown
     };
 
+    let heat = self.definition.heat.map_or(0.0, |heat_definition| {
+      (self.heat - heat_definition.cooling * time_scale).max(0.0)
+    });
+
     Self {
       current_cooldown,
+      heat,
+      overheated: self.overheated && heat > 0.0,
       ..self.clone()
     }
   }
 
-  pub fn fire_if_ready(&self, available_slots: ProjectileSlots) -> (Self, Vec<Projectile>) {
-    if self.current_cooldown > 0.0 {
+  /* How full the weapon's heat is, and whether it's cooling off from overheating */
+  pub fn heat(&self) -> Option<(f32, bool)> {
+    self
+      .definition
+      .heat
+      .map(|_| ((self.heat / self.heat_capacity).min(1.0), self.overheated))
+  }
+
+  pub fn fire_if_ready(
+    &self,
+    available_slots: ProjectileSlots,
+    rng: &RandGenerator,
+  ) -> (Self, Vec<Projectile>) {
+    if self.current_cooldown > 0.0 || self.overheated {
       return (self.clone(), Vec::new());
     }
 
+    let heat = self.heat
+      + self
+        .definition
+        .heat
+        .map_or(0.0, |heat_definition| heat_definition.per_shot);
+
+    (
+      Weapon {
+        current_cooldown: self.max_cooldown,
+        heat,
+        overheated: self.definition.heat.is_some() && heat >= self.heat_capacity,
+        ..self.clone()
+      },
+      self
+        .aimed_projectiles(&available_slots)
+        .into_iter()
+        .map(|projectile| {
+          let variance = rng.gen_range(
+            -self.definition.damage_variance,
+            self.definition.damage_variance,
+          );
+          let critical = rng.gen_range(0.0, 1.0) < self.crit_chance;
+
+          let damage = projectile.damage
+            * (1.0 + variance)
+            * if critical { self.crit_multiplier } else { 1.0 };
+
+          Projectile {
+            damage,
+            component_set: if critical {
+              projectile.component_set.insert(CriticalHit)
+            } else {
+              projectile.component_set
+            },
+            ..projectile
+          }
+        })
+        .collect(),
+    )
+  }
+
+  /* One projectile per slot the weapon fires from, before the damage roll. Drones have none */
+  pub fn aimed_projectiles(&self, available_slots: &ProjectileSlots) -> Vec<Projectile> {
+    if self.drone_spec().is_some() {
+      return Vec::new();
+    }
+
     let slot_positions = if self
       .slot_positions
       .get(&SlotPosition::FrontDoubleLeft)
@@ -171,104 +271,170 @@ impl Weapon {
       &self.slot_positions
     };
 
-    (
-      Weapon {
-        current_cooldown: self.max_cooldown,
-        ..self.clone()
-      },
-      slot_positions
-        .iter()
-        .map(|slot_position| {
-          let base_projectile = base_projectile_from_weapon_type(self.projectile_type);
+    slot_positions
+      .iter()
+      .map(|slot_position| {
+        let base_projectile =
+          base_projectile_from_weapon_type(self.projectile_type, &self.definition);
 
-          let slot = available_slots.get(slot_position).unwrap();
+        let slot = available_slots.get(slot_position).unwrap();
 
-          let initial_impulse = distance_projection_physics(
+        Projectile {
+          damage: base_projectile.damage * self.damage_mod,
+          offset: slot.offset,
+          initial_impulse: distance_projection_physics(
             slot.angle,
-            base_speed_from_projectile_type(self.projectile_type) * self.velocity_mod,
-          );
+            self.definition.speed * self.velocity_mod,
+          ),
+          ..base_projectile
+        }
+      })
+      .collect()
+  }
+}
 
-          Projectile {
-            collider: base_projectile.collider,
+/* Drones fire on their own from PhysicsSystem rather than from the player's slots */
+#[derive(Clone)]
+pub struct DroneSpec {
+  pub cooldown: f32,
+  pub speed: f32,
+  pub projectile: Projectile,
+}
+
+impl Weapon {
+  pub fn drone_spec(&self) -> Option<DroneSpec> {
+    match self.projectile_type {
+      ProjectileType::Drone => {
+        let base_projectile =
+          base_projectile_from_weapon_type(self.projectile_type, &self.definition);
+
+        Some(DroneSpec {
+          cooldown: self.max_cooldown,
+          speed: self.definition.speed * self.velocity_mod,
+          projectile: Projectile {
             damage: base_projectile.damage * self.damage_mod,
-            offset: slot.offset,
-            component_set: base_projectile.component_set,
-            initial_impulse,
-            force_mod: base_projectile.force_mod,
-          }
+            ..base_projectile
+          },
         })
-        .collect(),
-    )
+      }
+      _ => None,
+    }
   }
 }
 
-fn base_projectile_from_weapon_type(projectile_type: ProjectileType) -> Projectile {
-  let collision_groups = InteractionGroups {
-    memberships: COLLISION_GROUP_PLAYER_PROJECTILE,
-    filter: COLLISION_GROUP_ENEMY.union(COLLISION_GROUP_WALL),
-    ..Default::default()
-  };
+fn base_projectile_from_weapon_type(
+  projectile_type: ProjectileType,
+  definition: &WeaponDefinition,
+) -> Projectile {
+  let collision_groups = groups(CollisionLayer::PlayerProjectile);
 
-  match projectile_type {
+  let projectile = match projectile_type {
     ProjectileType::Plasma => Projectile {
       collider: ColliderBuilder::ball(0.15)
         .collision_groups(collision_groups)
         .build(),
-      damage: 10.0,
-      force_mod: 0.0,
+      damage: definition.damage,
+      force_mod: definition.force_mod,
+      on_hit_status: definition.on_hit_status,
       component_set: ComponentSet::new(),
       initial_impulse: PhysicsVector::zero(),
       offset: PhysicsVector::zero(),
+      ccd: definition.ccd,
     },
     ProjectileType::Missile => Projectile {
       collider: ColliderBuilder::cuboid(0.3, 0.3)
         .collision_groups(collision_groups)
         .build(),
-      damage: 20.0,
-      force_mod: 2.0,
-      component_set: ComponentSet::new().insert(ExplodeOnCollision {
-        radius: 1.5,
-        strength: -0.5,
-        damage: 5.0,
-        interaction_groups: collision_groups,
-      }),
+      damage: definition.damage,
+      force_mod: definition.force_mod,
+      on_hit_status: definition.on_hit_status,
+      component_set: match &definition.explosion {
+        Some(explosion) => ComponentSet::new().insert(ExplodeOnCollision {
+          radius: explosion.radius,
+          strength: explosion.strength,
+          damage: explosion.damage,
+          interaction_groups: groups(CollisionLayer::Explosion),
+          force_groups: if explosion.self_blast {
+            player_self_blast_force_groups()
+          } else {
+            groups(CollisionLayer::Explosion)
+          },
+          self_blast: explosion.self_blast,
+        }),
+        None => ComponentSet::new(),
+      },
+      initial_impulse: PhysicsVector::zero(),
+      offset: PhysicsVector::zero(),
+      ccd: definition.ccd,
+    },
+    ProjectileType::Drone => Projectile {
+      collider: ColliderBuilder::ball(0.1)
+        .collision_groups(collision_groups)
+        .build(),
+      damage: definition.damage,
+      force_mod: definition.force_mod,
+      on_hit_status: definition.on_hit_status,
+      component_set: ComponentSet::new(),
       initial_impulse: PhysicsVector::zero(),
       offset: PhysicsVector::zero(),
+      ccd: definition.ccd,
     },
     ProjectileType::Laser => todo!(),
-  }
-}
+  };
 
-fn base_speed_from_projectile_type(projectile_type: ProjectileType) -> f32 {
-  match projectile_type {
-    ProjectileType::Plasma => 1.0,
-    ProjectileType::Missile => 0.01,
-    ProjectileType::Laser => 1.0,
+  if definition.max_bounces > 0 {
+    Projectile {
+      component_set: projectile.component_set.insert(Bounces {
+        count: 0,
+        max: definition.max_bounces,
+      }),
+      ..projectile
+    }
+  } else {
+    projectile
   }
 }
 
-fn weapon_with_defaults(projectile_type: ProjectileType, max_cooldown: f32) -> Weapon {
+fn weapon_with_defaults(projectile_type: ProjectileType, definition: &WeaponDefinition) -> Weapon {
   Weapon {
     projectile_type,
-    max_cooldown,
+    definition: definition.clone(),
+    max_cooldown: definition.cooldown,
     slot_positions: ht_set![],
-    current_cooldown: max_cooldown,
+    current_cooldown: definition.cooldown,
     damage_mod: 1.0,
     velocity_mod: 1.0,
+    crit_chance: definition.crit_chance,
+    crit_multiplier: definition.crit_multiplier,
     reversed: false,
+    heat: 0.0,
+    heat_capacity: definition
+      .heat
+      .map_or(0.0, |heat_definition| heat_definition.capacity),
+    overheated: false,
   }
 }
 
 /* WeaponComponent Implementations */
 
 // PLSM
-fn plasma() -> Weapon {
-  weapon_with_defaults(ProjectileType::Plasma, 30.0)
+fn plasma(weapon_definitions: &WeaponDefinitions) -> Weapon {
+  weapon_with_defaults(ProjectileType::Plasma, &weapon_definitions.plasma)
 }
 
 // MSLE
-fn missile() -> Weapon {
-  weapon_with_defaults(ProjectileType::Missile, 75.0)
+fn missile(weapon_definitions: &WeaponDefinitions) -> Weapon {
+  weapon_with_defaults(ProjectileType::Missile, &weapon_definitions.missile)
+}
+
+// DRON
+fn drone(weapon_definitions: &WeaponDefinitions) -> Weapon {
+  weapon_with_defaults(ProjectileType::Drone, &weapon_definitions.drone)
+}
+
+/* Not a module, only fired while the player is mounted on a turret */
+fn turret(weapon_definitions: &WeaponDefinitions) -> Weapon {
+  weapon_with_defaults(ProjectileType::Plasma, &weapon_definitions.turret)
 }
 
 // F2SL
@@ -330,17 +496,53 @@ fn double_freq_75_damage(weapon: &Weapon) -> Weapon {
   }
 }
 
-pub type UnequippedModules = Vec<WeaponModuleKind>;
+// C75F
+fn crit_chance_75_freq(weapon: &Weapon) -> Weapon {
+  Weapon {
+    crit_chance: weapon.crit_chance + 0.25,
+    max_cooldown: weapon.max_cooldown * 1.5,
+    ..weapon.clone()
+  }
+}
+
+// C50F
+fn crit_chance_50_freq(weapon: &Weapon) -> Weapon {
+  Weapon {
+    crit_chance: weapon.crit_chance + 0.5,
+    max_cooldown: weapon.max_cooldown * 2.0,
+    ..weapon.clone()
+  }
+}
+
+// HSNK
+fn heat_sink(weapon: &Weapon) -> Weapon {
+  Weapon {
+    heat_capacity: weapon.heat_capacity * 1.5,
+    damage_mod: weapon.damage_mod * 0.8,
+    ..weapon.clone()
+  }
+}
+
+// OVCH
+fn overcharge(weapon: &Weapon) -> Weapon {
+  Weapon {
+    damage_mod: weapon.damage_mod * 1.5,
+    heat_capacity: weapon.heat_capacity * 0.6,
+    ..weapon.clone()
+  }
+}
+
+pub type UnequippedModules = Vec<ModuleInstance>;
 
 pub const EQUIP_SLOTS_WIDTH: i32 = 4;
 pub const EQUIP_SLOTS_HEIGHT: i32 = 4;
 
 pub type EquippedModules = Matrix<
-  Option<WeaponModuleKind>,
+  Option<ModuleInstance>,
   Const<{ EQUIP_SLOTS_HEIGHT as usize }>,
   Const<{ EQUIP_SLOTS_WIDTH as usize }>,
   ArrayStorage<
-    Option<WeaponModuleKind>,
+    Option<ModuleInstance>,
     { EQUIP_SLOTS_HEIGHT as usize },
     { EQUIP_SLOTS_WIDTH as usize },
   >,
@@ -350,15 +552,64 @@ pub type EquippedModules = Matrix<
 pub enum WeaponModuleKind {
   Plasma,
   Missile,
+  Drone,
   Front2Slot,
   FortyFiveSlot,
   SideSlot,
   MirrorSlot,
   DoubleDamage75Freq,
   DoubleFreq75Damage,
+  CritChance75Freq,
+  CritChance50Freq,
+  HeatSink,
+  Overcharge,
+}
+
+/* A module the player owns, levelled up by fusing modules together */
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(from = "SavedModule", into = "SavedModule")]
+pub struct ModuleInstance {
+  pub kind: WeaponModuleKind,
+  pub level: u32,
 }
 
-type Generator = fn() -> Weapon;
+impl From<WeaponModuleKind> for ModuleInstance {
+  fn from(kind: WeaponModuleKind) -> Self {
+    Self { kind, level: 0 }
+  }
+}
+
+/* Modules are written as just their kind until they are levelled up, so saves from before
+levels still load */
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(untagged)]
+enum SavedModule {
+  Kind(WeaponModuleKind),
+  Levelled { kind: WeaponModuleKind, level: u32 },
+}
+
+impl From<SavedModule> for ModuleInstance {
+  fn from(saved_module: SavedModule) -> Self {
+    match saved_module {
+      SavedModule::Kind(kind) => Self::from(kind),
+      SavedModule::Levelled { kind, level } => Self { kind, level },
+    }
+  }
+}
+
+impl From<ModuleInstance> for SavedModule {
+  fn from(module: ModuleInstance) -> Self {
+    match module.level {
+      0 => SavedModule::Kind(module.kind),
+      level => SavedModule::Levelled {
+        kind: module.kind,
+        level,
+      },
+    }
+  }
+}
+
+type Generator = fn(&WeaponDefinitions) -> Weapon;
 type Modulator = fn(&Weapon) -> Weapon;
 type RcModulator = Rc<dyn Fn(&Weapon) -> Weapon>;
 
@@ -370,7 +621,7 @@ pub enum Direction {
   Right,
 }
 use Direction::*;
-use rpds::{HashTrieSet, ht_set};
+use rpds::{HashTrieSet, List, ht_set};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone)]
@@ -383,6 +634,7 @@ pub fn weapon_module_from_kind(kind: WeaponModuleKind) -> WeaponModule {
   match kind {
     WeaponModuleKind::Plasma => WeaponModule::Generator(plasma),
     WeaponModuleKind::Missile => WeaponModule::Generator(missile),
+    WeaponModuleKind::Drone => WeaponModule::Generator(drone),
     WeaponModuleKind::Front2Slot => {
       WeaponModule::Modulator(Rc::new(front_2_slot), HashSet::from([Down]))
     }
@@ -401,6 +653,18 @@ pub fn weapon_module_from_kind(kind: WeaponModuleKind) -> WeaponModule {
     WeaponModuleKind::MirrorSlot => {
       WeaponModule::Modulator(Rc::new(mirror_slot), HashSet::from([Down]))
     }
+    WeaponModuleKind::CritChance75Freq => {
+      WeaponModule::Modulator(Rc::new(crit_chance_75_freq), HashSet::from([Left]))
+    }
+    WeaponModuleKind::CritChance50Freq => {
+      WeaponModule::Modulator(Rc::new(crit_chance_50_freq), HashSet::from([Right]))
+    }
+    WeaponModuleKind::HeatSink => {
+      WeaponModule::Modulator(Rc::new(heat_sink), HashSet::from([Left]))
+    }
+    WeaponModuleKind::Overcharge => {
+      WeaponModule::Modulator(Rc::new(overcharge), HashSet::from([Right]))
+    }
   }
 }
 
@@ -412,7 +676,7 @@ fn build_adjacent_modules(
     None
   } else {
     equipped_modules.data.0[current_module_position.y][current_module_position.x - 1]
-      .map(weapon_module_from_kind)
+      .map(|module| weapon_module_from_kind(module.kind))
       .and_then(|weapon_module| match weapon_module {
         WeaponModule::Generator(_) => None,
         WeaponModule::Modulator(modulator, attachment_points) => {
@@ -434,7 +698,7 @@ fn build_adjacent_modules(
     None
   } else {
     equipped_modules.data.0[current_module_position.y][current_module_position.x + 1]
-      .map(weapon_module_from_kind)
+      .map(|module| weapon_module_from_kind(module.kind))
       .and_then(|weapon_module| match weapon_module {
         WeaponModule::Generator(_) => None,
         WeaponModule::Modulator(modulator, attachment_points) => {
@@ -456,7 +720,7 @@ fn build_adjacent_modules(
     None
   } else {
     equipped_modules.data.0[current_module_position.y - 1][current_module_position.x]
-      .map(weapon_module_from_kind)
+      .map(|module| weapon_module_from_kind(module.kind))
       .and_then(|weapon_module| match weapon_module {
         WeaponModule::Generator(_) => None,
         WeaponModule::Modulator(modulator, attachment_points) => {
@@ -478,7 +742,7 @@ fn build_adjacent_modules(
     None
   } else {
     equipped_modules.data.0[current_module_position.y + 1][current_module_position.x]
-      .map(weapon_module_from_kind)
+      .map(|module| weapon_module_from_kind(module.kind))
       .and_then(|weapon_module| match weapon_module {
         WeaponModule::Generator(_) => None,
         WeaponModule::Modulator(modulator, attachment_points) => {
@@ -508,7 +772,10 @@ fn build_adjacent_modules(
     )
 }
 
-fn build_weapons(equipped_modules: EquippedModules) -> Vec<Weapon> {
+fn build_weapons(
+  equipped_modules: EquippedModules,
+  weapon_definitions: &WeaponDefinitions,
+) -> Vec<Weapon> {
   equipped_modules
     .data
     .0
@@ -520,15 +787,14 @@ fn build_weapons(equipped_modules: EquippedModules) -> Vec<Weapon> {
         .iter()
         .enumerate()
         .map(|(x, value)| {
-          value.map(
-            |weapon_module_kind| match weapon_module_from_kind(weapon_module_kind) {
-              WeaponModule::Modulator(_, _) => None,
-              WeaponModule::Generator(generator) => Some(build_adjacent_modules(
-                equipped_modules,
-                vector![x, y],
-              )(&generator())),
-            },
-          )
+          value.map(|module| match weapon_module_from_kind(module.kind) {
+            WeaponModule::Modulator(_, _) => None,
+            WeaponModule::Generator(generator) => {
+              Some(build_adjacent_modules(equipped_modules, vector![x, y])(
+                &generator(weapon_definitions).levelled(module.level, &weapon_definitions.levels),
+              ))
+            }
+          })
         })
         .collect::<Vec<_>>()
     })
@@ -597,28 +863,330 @@ pub struct CombatSystem {
   pub current_weapons: Vec<Weapon>,
   pub new_projectiles: Vec<Projectile>,
   pub acquired_items: Vec<(String, i32)>,
+  pub credits: i32,
   pub reticle_angle: f32,
+  pub lock_on_target: Option<EntityHandle>,
+  /* The co-op partner fights with a loadout preset of their own, built from the same modules */
+  pub player_two_loadout: usize,
+  pub player_two_weapons: Vec<Weapon>,
+  pub player_two_projectiles: Vec<Projectile>,
+  pub player_two_reticle_angle: f32,
+  /* Predicted path of each shot the player's weapons would fire, empty unless enabled */
+  pub trajectory_preview: Vec<Vec<Vector<f32>>>,
+  /* Where the line from the player along their aim stops, None unless enabled */
+  pub aim_line: Option<Vector<f32>>,
+  /* Always LOADOUT_PRESETS long */
+  pub loadout_presets: Vec<LoadoutPreset>,
+  pub active_loadout: usize,
+  /* Counts down from a loadout swap, while the new loadout is named on screen */
+  pub loadout_swap_frames: i32,
+  pub power_ups: Vec<ActivePowerUp>,
+  /* Fires in place of the player's weapons while they're mounted on a turret */
+  pub turret_weapon: Weapon,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ActivePowerUp {
+  pub kind: PowerUpKind,
+  pub frames_left: f32,
+}
+
+/* About ten seconds from when it's picked up */
+const POWER_UP_FRAMES: f32 = 600.0;
+/* Cooldowns run down this many times as fast under rapid fire */
+const RAPID_FIRE_COOLDOWN_RATE: f32 = 2.0;
+const DAMAGE_BOOST_MULTIPLIER: f32 = 1.5;
+/* Scales both the player's acceleration and their speed limit */
+const SPEED_BOOST_MULTIPLIER: f32 = 1.3;
+
+/* Picking up a power-up that's already running starts its timer over */
+fn pick_up_power_ups(
+  power_ups: &[ActivePowerUp],
+  picked_up: &List<PowerUpKind>,
+) -> Vec<ActivePowerUp> {
+  picked_up
+    .iter()
+    .fold(power_ups.to_vec(), |power_ups, &kind| {
+      power_ups
+        .into_iter()
+        .filter(|power_up| power_up.kind != kind)
+        .chain([ActivePowerUp {
+          kind,
+          frames_left: POWER_UP_FRAMES,
+        }])
+        .collect()
+    })
+}
+
+pub const LOADOUT_PRESETS: usize = 3;
+const LOADOUT_NAMES: [&str; LOADOUT_PRESETS] = ["A", "B", "C"];
+/* Weapons are held off this long after a swap while enemies are around */
+const LOADOUT_SWAP_DELAY_FRAMES: f32 = 45.0;
+const LOADOUT_SWAP_BANNER_FRAMES: i32 = 90;
+
+/* The saved presets padded out to LOADOUT_PRESETS, with the active one matching what's equipped */
+fn loadout_presets(save_data: &SaveData, active_loadout: usize) -> Vec<LoadoutPreset> {
+  (0..LOADOUT_PRESETS)
+    .map(|index| {
+      let preset = save_data
+        .loadout_presets
+        .get(index)
+        .cloned()
+        .unwrap_or_else(|| LoadoutPreset {
+          name: LOADOUT_NAMES[index].to_string(),
+          ..Default::default()
+        });
+      if index == active_loadout {
+        LoadoutPreset {
+          equipped_modules: save_data.equipped_modules,
+          ..preset
+        }
+      } else {
+        preset
+      }
+    })
+    .collect()
+}
+
+/* Puts every equipped module back with the unequipped ones and fills the preset's slots from
+them. Slots whose module isn't there, e.g. as it was sold, are left empty */
+fn equip_preset(
+  equipped_modules: EquippedModules,
+  unequipped_modules: &UnequippedModules,
+  preset: &LoadoutPreset,
+) -> (EquippedModules, UnequippedModules) {
+  let mut pool = unequipped_modules
+    .iter()
+    .chain(equipped_modules.data.0.iter().flatten().flatten())
+    .cloned()
+    .collect::<Vec<_>>();
+
+  let slots = preset.equipped_modules.map(|column| {
+    column.map(|slot| {
+      let index = pool.iter().position(|&module| Some(module) == slot)?;
+      Some(pool.remove(index))
+    })
+  });
+
+  (EquippedModules::from_data(ArrayStorage(slots)), pool)
+}
+
+/* The partner's preset, filled from everything the player owns whether or not player one has
+it equipped */
+fn player_two_modules(
+  equipped_modules: EquippedModules,
+  unequipped_modules: &UnequippedModules,
+  loadout_presets: &[LoadoutPreset],
+  player_two_loadout: usize,
+) -> EquippedModules {
+  equip_preset(
+    equipped_modules,
+    unequipped_modules,
+    &loadout_presets[player_two_loadout],
+  )
+  .0
+}
+
+/* About a second of flight, which covers a missile's slow start */
+const TRAJECTORY_PREVIEW_FRAMES: usize = 60;
+/* Comfortably past the edge of the screen when nothing is in the way */
+const AIM_LINE_RANGE: f32 = 30.0;
+
+/* Half angle of the cone in front of the reticle that lock on searches */
+const LOCK_ON_CONE: f32 = PI / 6.0;
+const LOCK_ON_RANGE: f32 = 12.0;
+/* Targets are only locked onto when a shot this wide could reach them */
+const LOCK_ON_SHOT_RADIUS: f32 = 0.1;
+/* Holding lock on this long retargets instead of just toggling */
+const LOCK_ON_HOLD_FRAMES: usize = 20;
+
+fn find_lock_on_target(physics_system: &PhysicsSystem, reticle_angle: f32) -> Option<EntityHandle> {
+  let player_translation =
+    *physics_system.rigid_body_set[physics_system.player_handle].translation();
+  let reticle_direction = distance_projection_physics(reticle_angle, 1.0).into_vec();
+
+  physics_system
+    .nearest_entity(player_translation, LOCK_ON_RANGE, |entity| {
+      let offset = entity
+        .handle
+        .translation(&physics_system.rigid_body_set, &physics_system.collider_set)
+        - player_translation;
+
+      entity.components.get::<Enemy>().is_some()
+        && Faction::Player.hostile_to(Faction::of(&entity.components), false)
+        && offset.angle(&reticle_direction) <= LOCK_ON_CONE
+        && physics_system
+          .shapecast(
+            &Ball::new(LOCK_ON_SHOT_RADIUS),
+            player_translation,
+            offset,
+            QueryFilter::new().groups(WALL_QUERY_GROUPS),
+          )
+          .is_none()
+    })
+    .map(|entity| entity.handle)
+}
+
+/* Half angle of the window around the reticle that aim assist considers */
+const AIM_ASSIST_WINDOW: f32 = PI / 12.0;
+const AIM_ASSIST_RANGE: f32 = 12.0;
+
+/* Bends the angle towards the enemy closest to it by the given fraction */
+fn aim_assisted_angle(physics_system: &PhysicsSystem, reticle_angle: f32, strength: f32) -> f32 {
+  if strength <= 0.0 {
+    return reticle_angle;
+  }
+
+  let player_translation =
+    *physics_system.rigid_body_set[physics_system.player_handle].translation();
+
+  physics_system
+    .entities_within(player_translation, AIM_ASSIST_RANGE)
+    .filter(|(_, entity)| {
+      entity.components.get::<Enemy>().is_some()
+        && Faction::Player.hostile_to(Faction::of(&entity.components), false)
+    })
+    .map(|(translation, _)| translation - player_translation)
+    /* No bending towards enemies behind walls */
+    .filter(|offset| {
+      physics_system
+        .raycast(
+          player_translation,
+          *offset,
+          offset.magnitude(),
+          QueryFilter::new().groups(WALL_QUERY_GROUPS),
+        )
+        .is_none()
+    })
+    .map(|offset| {
+      /* Wrapped into -PI..PI so the bend always takes the short way round */
+      (angle_from_vec(PhysicsVector::from_vec(offset)) - reticle_angle + PI).rem_euclid(2.0 * PI)
+        - PI
+    })
+    .filter(|angle_difference| angle_difference.abs() <= AIM_ASSIST_WINDOW)
+    .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+    .map(|angle_difference| reticle_angle + angle_difference * strength)
+    .unwrap_or(reticle_angle)
+}
+
+fn fire_weapons(
+  weapons: &[Weapon],
+  firing: bool,
+  fire_angle: f32,
+  damage_mod: f32,
+  rng: &RandGenerator,
+) -> (Vec<Weapon>, Vec<Projectile>) {
+  let weapons_firing: Vec<(Weapon, Vec<Projectile>)> = if firing {
+    weapons
+      .iter()
+      .map(|weapon| weapon.fire_if_ready(get_slot_positions(fire_angle), rng))
+      .collect()
+  } else {
+    weapons
+      .iter()
+      .map(|weapon| (weapon.clone(), Vec::new()))
+      .collect()
+  };
+
+  let new_weapons = weapons_firing
+    .iter()
+    .map(|(weapon, _)| weapon.clone())
+    .collect();
+
+  let new_projectiles = weapons_firing
+    .iter()
+    .flat_map(|(_, projectiles)| projectiles.clone())
+    .map(|projectile| Projectile {
+      damage: projectile.damage * damage_mod,
+      ..projectile
+    })
+    .collect();
+
+  (new_weapons, new_projectiles)
+}
+
+impl CombatSystem {
+  /* Applied to the player's movement while the speed power-up lasts */
+  pub fn speed_mod(&self) -> f32 {
+    if self
+      .power_ups
+      .iter()
+      .any(|power_up| power_up.kind == PowerUpKind::Speed)
+    {
+      SPEED_BOOST_MULTIPLIER
+    } else {
+      1.0
+    }
+  }
+
+  pub fn drone_specs(&self) -> Vec<DroneSpec> {
+    self
+      .current_weapons
+      .iter()
+      .filter_map(Weapon::drone_spec)
+      .collect()
+  }
 }
 
 impl System for CombatSystem {
   type Input = SaveData;
 
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<DataSystem>(),
+      Requirement::of::<PhysicsSystem>(),
+      Requirement::of::<MapSystem>(),
+      Requirement::of::<MenuSystem<_>>(),
+      Requirement::of::<ControlsSystem<_>>(),
+      Requirement::of::<CoopControlsSystem>(),
+      Requirement::of::<SettingsSystem>(),
+      Requirement::of::<DialogueSystem>(),
+    ]
+  }
+
   fn start(ctx: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
   where
     Self: Sized,
   {
     let save_data = ctx.input.clone();
+    let data_system = ctx.require::<DataSystem>().unwrap();
 
     /* Initialize default equipped weapons */
     let equipped_modules = EquippedModules::from_data(ArrayStorage(save_data.equipped_modules));
+    let active_loadout = save_data.active_loadout.min(LOADOUT_PRESETS - 1);
+    let loadout_presets = loadout_presets(&save_data, active_loadout);
+    let player_two_loadout = save_data.player_two_loadout.min(LOADOUT_PRESETS - 1);
+
+    let player_two_weapons = build_weapons(
+      player_two_modules(
+        equipped_modules,
+        &save_data.unequipped_modules,
+        &loadout_presets,
+        player_two_loadout,
+      ),
+      &data_system.data.weapons,
+    );
 
     Rc::new(Self {
       unequipped_modules: save_data.unequipped_modules,
       equipped_modules,
-      current_weapons: build_weapons(equipped_modules),
+      current_weapons: build_weapons(equipped_modules, &data_system.data.weapons),
       new_projectiles: vec![],
       reticle_angle: 0.0,
+      lock_on_target: None,
       acquired_items: save_data.acquired_items,
+      credits: save_data.credits,
+      player_two_weapons,
+      player_two_loadout,
+      player_two_projectiles: vec![],
+      player_two_reticle_angle: 0.0,
+      trajectory_preview: vec![],
+      aim_line: None,
+      loadout_presets,
+      active_loadout,
+      loadout_swap_frames: 0,
+      power_ups: vec![],
+      turret_weapon: turret(&data_system.data.weapons),
     })
   }
 
@@ -627,22 +1195,22 @@ impl System for CombatSystem {
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> Rc<dyn System<Input = Self::Input>> {
     /* Add new unequipped modules from item pickups */
-    let physics_system = ctx.get::<PhysicsSystem>().unwrap();
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
 
     let unequipped_modules = self
       .unequipped_modules
       .iter()
+      .cloned()
       .chain(
         physics_system
           .new_weapon_modules
           .iter()
-          .map(|(_, module)| module),
+          .map(|&(_, module)| ModuleInstance::from(module)),
       )
-      .cloned()
       .collect();
 
     /* Mark new item pickups as acquired */
-    let map_system = ctx.get::<MapSystem>().unwrap();
+    let map_system = ctx.require::<MapSystem>().unwrap();
 
     let acquired_items = self
       .acquired_items
@@ -652,75 +1220,398 @@ impl System for CombatSystem {
         physics_system
           .new_weapon_modules
           .iter()
-          .map(|(id, _)| (map_system.current_map_name.clone(), *id)),
+          .filter_map(|(id, _)| Some((map_system.current_map_name.clone(), (*id)?))),
       )
       .collect();
 
-    let menu_system = ctx.get::<MenuSystem<_>>().unwrap();
+    let credits = self.credits + physics_system.new_credits;
+
+    /* Power-ups don't carry over into the next room */
+    let power_ups = if physics_system.load_new_map.is_some() {
+      Vec::new()
+    } else {
+      pick_up_power_ups(&self.power_ups, &physics_system.new_power_ups)
+    };
+
+    let menu_system = ctx.require::<MenuSystem<_>>().unwrap();
+    let data_system = ctx.require::<DataSystem>().unwrap();
 
     if !menu_system.active_menus.is_empty() {
       if let Some(inventory_update) = &menu_system.inventory_update {
+        let loadout_presets = self
+          .loadout_presets
+          .iter()
+          .enumerate()
+          .map(|(index, preset)| {
+            if index == self.active_loadout {
+              LoadoutPreset {
+                equipped_modules: inventory_update.equipped_modules.data.0,
+                ..preset.clone()
+              }
+            } else {
+              preset.clone()
+            }
+          })
+          .collect::<Vec<_>>();
+
         return Rc::new(Self {
           unequipped_modules: inventory_update.unequipped_modules.clone(),
           equipped_modules: inventory_update.equipped_modules,
-          current_weapons: build_weapons(inventory_update.equipped_modules),
+          current_weapons: build_weapons(
+            inventory_update.equipped_modules,
+            &data_system.data.weapons,
+          ),
           new_projectiles: Vec::new(),
           reticle_angle: self.reticle_angle,
+          lock_on_target: self.lock_on_target,
           acquired_items: self.acquired_items.clone(),
+          credits,
+          player_two_weapons: build_weapons(
+            player_two_modules(
+              inventory_update.equipped_modules,
+              &inventory_update.unequipped_modules,
+              &loadout_presets,
+              self.player_two_loadout,
+            ),
+            &data_system.data.weapons,
+          ),
+          player_two_loadout: self.player_two_loadout,
+          player_two_projectiles: Vec::new(),
+          player_two_reticle_angle: self.player_two_reticle_angle,
+          trajectory_preview: Vec::new(),
+          aim_line: None,
+          loadout_presets,
+          active_loadout: self.active_loadout,
+          loadout_swap_frames: 0,
+          power_ups,
+          turret_weapon: self.turret_weapon.clone(),
         });
       }
 
       return Rc::new(Self {
         unequipped_modules,
         acquired_items,
+        credits,
+        power_ups,
+        new_projectiles: Vec::new(),
+        player_two_projectiles: Vec::new(),
         ..self.clone()
       });
     }
 
+    let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
+    let dialogue_system = ctx.require::<DialogueSystem>().unwrap();
+
+    if controls_system.simulation_frozen() || dialogue_system.playing() {
+      return Rc::new(Self {
+        new_projectiles: Vec::new(),
+        player_two_projectiles: Vec::new(),
+        ..self.clone()
+      });
+    }
+
+    /* MARK: Swap loadouts, keeping the one left in step with what was equipped */
+    let loadout_step = i32::from(controls_system.pressed(|controls| controls.loadout_next))
+      - i32::from(controls_system.pressed(|controls| controls.loadout_previous));
+
+    let (active_loadout, equipped_modules, unequipped_modules) = if loadout_step != 0 {
+      let active_loadout =
+        (self.active_loadout as i32 + loadout_step).rem_euclid(LOADOUT_PRESETS as i32) as usize;
+      let (equipped_modules, unequipped_modules) = equip_preset(
+        self.equipped_modules,
+        &unequipped_modules,
+        &self.loadout_presets[active_loadout],
+      );
+      log::info(
+        "combat",
+        format!(
+          "swapped to loadout {}",
+          self.loadout_presets[active_loadout].name
+        ),
+      );
+      (active_loadout, equipped_modules, unequipped_modules)
+    } else {
+      (
+        self.active_loadout,
+        self.equipped_modules,
+        unequipped_modules,
+      )
+    };
+
+    let loadout_presets = self
+      .loadout_presets
+      .iter()
+      .enumerate()
+      .map(|(index, preset)| LoadoutPreset {
+        equipped_modules: if index == active_loadout {
+          equipped_modules.data.0
+        } else if index == self.active_loadout {
+          self.equipped_modules.data.0
+        } else {
+          preset.equipped_modules
+        },
+        ..preset.clone()
+      })
+      .collect::<Vec<_>>();
+
+    /* Swapping mid fight costs a moment before the new weapons can fire */
+    let enemies_around = physics_system.entities.values().any(|entity| {
+      entity.components.get::<Enemy>().is_some()
+        && Faction::Player.hostile_to(Faction::of(&entity.components), false)
+    });
+    let swap_delay = if enemies_around {
+      LOADOUT_SWAP_DELAY_FRAMES
+    } else {
+      0.0
+    };
+
+    /* Rebuild weapons from fresh definitions after a hot reload or a loadout swap */
+    let current_weapons = if loadout_step != 0 {
+      build_weapons(equipped_modules, &data_system.data.weapons)
+        .iter()
+        .map(|weapon| weapon.delay(swap_delay))
+        .collect()
+    } else if data_system.reloaded {
+      build_weapons(self.equipped_modules, &data_system.data.weapons)
+    } else {
+      self.current_weapons.clone()
+    };
+
+    /* MARK: The co-op partner swaps between presets on their own gamepad */
+    let coop_controls_system = ctx.require::<CoopControlsSystem>().unwrap();
+
+    let player_two_loadout = (self.player_two_loadout as i32 + coop_controls_system.loadout_step)
+      .rem_euclid(LOADOUT_PRESETS as i32) as usize;
+
+    let player_two_weapons = if coop_controls_system.loadout_step != 0 || data_system.reloaded {
+      let swap_delay = if coop_controls_system.loadout_step != 0 {
+        log::info(
+          "combat",
+          format!(
+            "player two swapped to loadout {}",
+            loadout_presets[player_two_loadout].name
+          ),
+        );
+        swap_delay
+      } else {
+        0.0
+      };
+
+      build_weapons(
+        player_two_modules(
+          equipped_modules,
+          &unequipped_modules,
+          &loadout_presets,
+          player_two_loadout,
+        ),
+        &data_system.data.weapons,
+      )
+      .iter()
+      .map(|weapon| weapon.delay(swap_delay))
+      .collect()
+    } else {
+      self.player_two_weapons.clone()
+    };
+
+    /* MARK: Run power-ups down, leaving the ones with time left */
+    let power_ups = power_ups
+      .iter()
+      .map(|power_up| ActivePowerUp {
+        frames_left: power_up.frames_left - controls_system.time_scale,
+        ..*power_up
+      })
+      .filter(|power_up| power_up.frames_left > 0.0)
+      .collect::<Vec<_>>();
+
+    let cooldown_rate = if power_ups
+      .iter()
+      .any(|power_up| power_up.kind == PowerUpKind::RapidFire)
+    {
+      RAPID_FIRE_COOLDOWN_RATE
+    } else {
+      1.0
+    };
+    let damage_mod = if power_ups
+      .iter()
+      .any(|power_up| power_up.kind == PowerUpKind::DamageBoost)
+    {
+      DAMAGE_BOOST_MULTIPLIER
+    } else {
+      1.0
+    };
+
     /* Decrement cooldown for active weapons */
-    let reduced_cooldown_weapons: Vec<Weapon> = self
-      .current_weapons
+    let reduced_cooldown_weapons: Vec<Weapon> = current_weapons
       .iter()
-      .map(Weapon::reduce_cooldown)
+      .map(|weapon| weapon.reduce_cooldown(controls_system.time_scale * cooldown_rate))
       .collect();
 
-    let controls_system = ctx.get::<ControlsSystem<_>>().unwrap();
+    let reduced_cooldown_player_two_weapons: Vec<Weapon> = player_two_weapons
+      .iter()
+      .map(|weapon| weapon.reduce_cooldown(controls_system.time_scale * cooldown_rate))
+      .collect();
+
+    let turret_weapon = if data_system.reloaded {
+      turret(&data_system.data.weapons)
+    } else {
+      self.turret_weapon.clone()
+    }
+    .reduce_cooldown(controls_system.time_scale * cooldown_rate);
 
-    let reticle_angle = if controls_system.right_stick.into_vec() == vector![0.0, 0.0] {
+    let settings_system = ctx.require::<SettingsSystem>().unwrap();
+
+    let aimed_reticle_angle = if controls_system.right_stick.into_vec() == vector![0.0, 0.0] {
       self.reticle_angle
     } else {
       angle_from_vec(controls_system.right_stick)
     };
 
-    let weapons_firing: Vec<(Weapon, Vec<Projectile>)> = if controls_system.firing {
-      reduced_cooldown_weapons
+    /* Tapping lock on toggles it and holding it picks a new target along the aim; a locked
+    target that died is released */
+    let lock_on_pressed = controls_system.pressed(|controls| controls.lock_on);
+    let lock_on_retarget = controls_system
+      .held_for(|controls| controls.lock_on, LOCK_ON_HOLD_FRAMES)
+      && !controls_system.held_for(|controls| controls.lock_on, LOCK_ON_HOLD_FRAMES + 1);
+
+    let lock_on_target = match self.lock_on_target {
+      _ if lock_on_retarget => find_lock_on_target(&physics_system, aimed_reticle_angle),
+      Some(_) if lock_on_pressed => None,
+      None if lock_on_pressed => find_lock_on_target(&physics_system, aimed_reticle_angle),
+      target => target.filter(|handle| physics_system.entities.contains_key(handle)),
+    };
+
+    let reticle_angle = match lock_on_target {
+      Some(handle) => angle_from_vec(PhysicsVector::from_vec(
+        handle.translation(&physics_system.rigid_body_set, &physics_system.collider_set)
+          - physics_system.rigid_body_set[physics_system.player_handle].translation(),
+      )),
+      None => aimed_reticle_angle,
+    };
+
+    /* Damage rolls are seeded by frame so replays stay deterministic */
+    let rng = RandGenerator::new();
+    rng.srand(physics_system.frame_count as u64);
+
+    /* Locked on shots are already exact, so only free aim is assisted */
+    let fire_angle = match lock_on_target {
+      Some(_) => reticle_angle,
+      None => aim_assisted_angle(
+        &physics_system,
+        reticle_angle,
+        settings_system.settings.aim_assist.strength(),
+      ),
+    };
+
+    /* MARK: A mounted turret fires instead, while the player's own weapons keep cooling down */
+    let mounted = physics_system.mounted_turret.is_some();
+
+    let (new_weapons, weapon_projectiles) = fire_weapons(
+      &reduced_cooldown_weapons,
+      controls_system.firing && !mounted,
+      fire_angle,
+      damage_mod,
+      &rng,
+    );
+
+    let (turret_weapons, turret_projectiles) = fire_weapons(
+      &[turret_weapon],
+      controls_system.firing && mounted,
+      fire_angle,
+      damage_mod,
+      &rng,
+    );
+
+    let new_projectiles = weapon_projectiles
+      .into_iter()
+      .chain(turret_projectiles)
+      .collect::<Vec<_>>();
+
+    /* Previewed from where the shots would leave now, moving with the player */
+    let trajectory_preview = if settings_system.settings.trajectory_preview {
+      let player_body = &physics_system.rigid_body_set[physics_system.player_handle];
+      let available_slots = get_slot_positions(fire_angle);
+      let previewed_weapons = if mounted {
+        &turret_weapons
+      } else {
+        &new_weapons
+      };
+
+      previewed_weapons
         .iter()
-        .map(|weapon| weapon.fire_if_ready(get_slot_positions(reticle_angle)))
+        .flat_map(|weapon| weapon.aimed_projectiles(&available_slots))
+        .map(|projectile| {
+          physics_system.predict_path(
+            &projectile,
+            player_body.translation() + projectile.offset.into_vec(),
+            *player_body.linvel(),
+            TRAJECTORY_PREVIEW_FRAMES,
+          )
+        })
         .collect()
     } else {
-      reduced_cooldown_weapons
-        .iter()
-        .map(|weapon| (weapon.clone(), Vec::new()))
-        .collect()
+      Vec::new()
     };
 
-    let new_weapons = weapons_firing
-      .iter()
-      .map(|(weapon, _)| weapon.clone())
-      .collect();
+    /* Cut short at the first wall, showing what cover would block */
+    let aim_line = settings_system.settings.aim_line.then(|| {
+      let player_translation =
+        *physics_system.rigid_body_set[physics_system.player_handle].translation();
+      let direction = distance_projection_physics(fire_angle, 1.0).into_vec();
+
+      let distance = physics_system
+        .raycast(
+          player_translation,
+          direction,
+          AIM_LINE_RANGE,
+          QueryFilter::new().groups(WALL_QUERY_GROUPS),
+        )
+        .map_or(AIM_LINE_RANGE, |(_, intersection)| {
+          intersection.time_of_impact
+        });
 
-    let new_projectiles = weapons_firing
-      .iter()
-      .flat_map(|(_, projectiles)| projectiles.clone())
-      .collect();
+      player_translation + direction * distance
+    });
+
+    /* MARK: Co-op partner aims freely without lock on or aim assist */
+    let player_two_reticle_angle =
+      if coop_controls_system.right_stick.into_vec() == vector![0.0, 0.0] {
+        self.player_two_reticle_angle
+      } else {
+        angle_from_vec(coop_controls_system.right_stick)
+      };
+
+    let (player_two_weapons, player_two_projectiles) = fire_weapons(
+      &reduced_cooldown_player_two_weapons,
+      coop_controls_system.firing && physics_system.player_two_handle.is_some(),
+      player_two_reticle_angle,
+      damage_mod,
+      &rng,
+    );
 
     Rc::new(Self {
       unequipped_modules,
-      equipped_modules: self.equipped_modules,
+      equipped_modules,
       current_weapons: new_weapons,
       new_projectiles,
       reticle_angle,
+      lock_on_target,
       acquired_items,
+      credits,
+      player_two_loadout,
+      player_two_weapons,
+      player_two_projectiles,
+      player_two_reticle_angle,
+      trajectory_preview,
+      aim_line,
+      loadout_presets,
+      active_loadout,
+      loadout_swap_frames: if loadout_step != 0 {
+        LOADOUT_SWAP_BANNER_FRAMES
+      } else {
+        (self.loadout_swap_frames - i32::from(controls_system.whole_frame)).max(0)
+      },
+      power_ups,
+      turret_weapon: turret_weapons[0].clone(),
     })
   }
 }
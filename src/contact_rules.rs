@@ -0,0 +1,113 @@
+use rapier2d::prelude::*;
+
+use crate::ecs::Faction;
+
+/* Gameplay rules about which contacts the solver sees, so that one-way blocks and allies passing
+through each other live here instead of in more collision group constants. A collider opts in by
+carrying rule flags in its user data, and the rules only ever drop contacts its collision groups
+already allow */
+pub struct ContactRules;
+
+/* Only solid against things hitting the side its local up points to, like a door that can be
+pushed through from behind */
+const ONE_WAY: u128 = 1 << 0;
+/* Passes through other bodies that also pass through allies, as long as their sides are friendly */
+const PASS_THROUGH_ALLIES: u128 = 1 << 1;
+
+/* Contacts this far off of a one-way collider's up don't count as coming from the solid side */
+const ONE_WAY_ALLOWED_ANGLE: f32 = 0.25 * std::f32::consts::PI;
+
+/* The faction sits above the flags, so it's only read back from colliders with the flag set */
+const FACTION_SHIFT: u32 = 64;
+const FACTION_PLAYER: u128 = 1;
+const FACTION_ENEMY: u128 = 2;
+
+fn encode_faction(faction: Faction) -> u128 {
+  (match faction {
+    Faction::Neutral => 0,
+    Faction::Player => FACTION_PLAYER,
+    Faction::Enemy(group) => (FACTION_ENEMY << 32) | group as u32 as u128,
+  }) << FACTION_SHIFT
+}
+
+fn decode_faction(user_data: u128) -> Faction {
+  let faction = user_data >> FACTION_SHIFT;
+  match faction >> 32 {
+    FACTION_ENEMY => Faction::Enemy(faction as u32 as i32),
+    _ if faction == FACTION_PLAYER => Faction::Player,
+    _ => Faction::Neutral,
+  }
+}
+
+pub fn one_way(collider: ColliderBuilder) -> ColliderBuilder {
+  collider
+    .user_data(ONE_WAY)
+    .active_hooks(ActiveHooks::MODIFY_SOLVER_CONTACTS)
+}
+
+pub fn pass_through_allies(collider: &mut Collider, faction: Faction) {
+  collider.user_data = (collider.user_data & !(u128::MAX << FACTION_SHIFT))
+    | PASS_THROUGH_ALLIES
+    | encode_faction(faction);
+  collider.set_active_hooks(collider.active_hooks() | ActiveHooks::FILTER_CONTACT_PAIRS);
+}
+
+/* The same bookkeeping as rapier's own one-way platforms, but for a platform on either side of
+the pair. Once a contact starts from the wrong side it stays ignored until the two separate, so
+nothing gets spat out halfway through */
+const CONTACT_UNKNOWN: u32 = 0;
+const CONTACT_ALLOWED: u32 = 1;
+const CONTACT_FORBIDDEN: u32 = 2;
+
+fn update_one_way(context: &mut ContactModificationContext, platform: ColliderHandle) {
+  let up = context.colliders[platform].rotation() * vector![0.0, 1.0];
+  /* The normal points out of the first collider */
+  let normal_out_of_platform = if platform == context.collider1 {
+    *context.normal
+  } else {
+    -*context.normal
+  };
+  let from_solid_side = normal_out_of_platform.dot(&up) >= ONE_WAY_ALLOWED_ANGLE.cos();
+
+  let separated = context
+    .solver_contacts
+    .iter()
+    .all(|contact| contact.dist > 0.0);
+
+  *context.user_data = match *context.user_data {
+    CONTACT_ALLOWED if context.solver_contacts.is_empty() => CONTACT_UNKNOWN,
+    CONTACT_ALLOWED => CONTACT_ALLOWED,
+    CONTACT_UNKNOWN if from_solid_side => CONTACT_ALLOWED,
+    CONTACT_FORBIDDEN if from_solid_side && separated => CONTACT_ALLOWED,
+    state => {
+      context.solver_contacts.clear();
+      /* Shapes touching at exactly one point have no normal to judge by yet */
+      if state == CONTACT_UNKNOWN && context.normal.norm_squared() <= 0.1 {
+        CONTACT_UNKNOWN
+      } else {
+        CONTACT_FORBIDDEN
+      }
+    }
+  };
+}
+
+impl PhysicsHooks for ContactRules {
+  fn filter_contact_pair(&self, context: &PairFilterContext) -> Option<SolverFlags> {
+    let user_data1 = context.colliders[context.collider1].user_data;
+    let user_data2 = context.colliders[context.collider2].user_data;
+
+    let allies = (user_data1 & user_data2 & PASS_THROUGH_ALLIES) != 0
+      && !decode_faction(user_data1).hostile_to(decode_faction(user_data2), false);
+
+    (!allies).then_some(SolverFlags::COMPUTE_IMPULSES)
+  }
+
+  fn modify_solver_contacts(&self, context: &mut ContactModificationContext) {
+    if let Some(platform) = [context.collider1, context.collider2]
+      .into_iter()
+      .find(|handle| context.colliders[*handle].user_data & ONE_WAY != 0)
+    {
+      update_one_way(context, platform);
+    }
+  }
+}
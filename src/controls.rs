@@ -1,19 +1,30 @@
-use std::{cell::RefCell, f32::consts::PI, marker::PhantomData, rc::Rc};
+use std::{cell::RefCell, collections::VecDeque, f32::consts::PI, marker::PhantomData, rc::Rc};
 
-use gilrs::{Axis, Button, Gilrs};
-use macroquad::input::{KeyCode, MouseButton, is_key_down, is_mouse_button_down, mouse_position};
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+use macroquad::input::{
+  KeyCode, MouseButton, is_key_down, is_mouse_button_down, mouse_position, mouse_wheel,
+};
 use rapier2d::{na::Vector2, prelude::*};
 
 use crate::{
   camera::CameraSystem,
+  log,
+  menu::MenuSystem,
   physics::PhysicsSystem,
   save::SaveData,
-  system::{ProcessContext, System},
+  settings::{BoostBinding, SettingsSystem},
+  system::{ProcessContext, Requirement, System},
   units::{PhysicsVector, UnitConvert, UnitConvert2, vec_zero},
 };
 
 const INPUT_FORCE: f32 = 0.1;
 
+/* How many past frames of input are kept for the history queries */
+const INPUT_HISTORY_FRAMES: usize = 30;
+
+/* F5 freezes the simulation while rendering carries on, F6 then advances it one tick */
+const DEBUG_STEPPING: bool = cfg!(debug_assertions);
+
 #[derive(Clone, Copy)]
 pub enum ControlMode {
   GamePad,
@@ -24,6 +35,8 @@ pub enum ControlMode {
 pub struct ControlsSystem<Input> {
   pub left_stick: PhysicsVector,
   pub right_stick: PhysicsVector,
+  /* Photo mode zoom, 1 zooming in, -1 out */
+  pub zoom: f32,
   pub menu_up: bool,
   pub menu_down: bool,
   pub menu_left: bool,
@@ -31,17 +44,98 @@ pub struct ControlsSystem<Input> {
   pub menu_confirm: bool,
   pub menu_cancel: bool,
   pub firing: bool,
+  /* The fire button itself, which only differs from firing when fire is toggled */
+  pub fire_held: bool,
   pub inventory: bool,
   pub pause: bool,
   pub map: bool,
   pub boost: bool,
   pub chain: bool,
+  pub magnetize: bool,
+  /* Held to channel a hack on the nearest enemy */
+  pub hack: bool,
+  pub lock_on: bool,
+  /* Cycle through the loadout presets */
+  pub loadout_previous: bool,
+  pub loadout_next: bool,
+  /* Takes over a mounted turret the player is standing on */
+  pub interact: bool,
+  /* Winds puzzle rooms back a few seconds */
+  pub rewind: bool,
+  pub debug_pause: bool,
+  pub debug_step: bool,
+  pub debug_paused: bool,
+  /* Fraction of a frame the simulation advances by, below 1 when the game is slowed down */
+  pub time_scale: f32,
+  /* Counters kept in whole frames, and logic stepped once a frame, only advance once the
+  slowed down time passes a whole frame so they keep pace with everything scaled */
+  pub whole_frame: bool,
+  /* Slowed down time carried over towards the next whole frame */
+  pub frame_clock: f32,
   pub last_frame: Option<Rc<ControlsSystem<Input>>>,
+  /* Past frames of input, most recent first, each with its own history stripped */
+  pub history: Rc<VecDeque<Rc<ControlsSystem<Input>>>>,
   pub gilrs: Rc<RefCell<Gilrs>>,
   pub control_mode: ControlMode,
+  /* The gamepad that input is read from, claimed by the first one to press a button */
+  pub active_gamepad: Option<GamepadId>,
+  /* Set when the active gamepad drops out, until another device takes over */
+  pub gamepad_disconnected: bool,
   pub phantom: PhantomData<Input>,
 }
 
+impl<Input> ControlsSystem<Input> {
+  pub fn pressed(&self, button: fn(&Self) -> bool) -> bool {
+    button(self)
+      && !self
+        .last_frame
+        .as_ref()
+        .is_some_and(|last_frame| button(last_frame))
+  }
+
+  /* The held state of a button this frame followed by each remembered frame before it */
+  fn held_history(&self, button: fn(&Self) -> bool) -> impl Iterator<Item = bool> + '_ {
+    std::iter::once(button(self)).chain(self.history.iter().map(move |frame| button(frame)))
+  }
+
+  /* How many frames ago each press of a button within the history began */
+  fn press_ages(&self, button: fn(&Self) -> bool) -> impl Iterator<Item = usize> + '_ {
+    self
+      .held_history(button)
+      .zip(
+        self
+          .held_history(button)
+          .skip(1)
+          .chain(std::iter::once(false)),
+      )
+      .enumerate()
+      .filter(|(_, (held, held_before))| *held && !*held_before)
+      .map(|(age, _)| age)
+  }
+
+  /* Whether a press of the button began within the last `frames` frames, this one included */
+  pub fn pressed_within(&self, button: fn(&Self) -> bool, frames: usize) -> bool {
+    self.press_ages(button).any(|age| age < frames)
+  }
+
+  /* Pressed this frame, having also been pressed and released within the last `window` frames */
+  pub fn double_tapped(&self, button: fn(&Self) -> bool, window: usize) -> bool {
+    let mut press_ages = self.press_ages(button);
+
+    press_ages.next() == Some(0) && press_ages.next().is_some_and(|age| age <= window)
+  }
+
+  /* Whether the button has been held down for at least the last `frames` frames */
+  pub fn held_for(&self, button: fn(&Self) -> bool, frames: usize) -> bool {
+    frames <= self.history.len() + 1 && self.held_history(button).take(frames).all(|held| held)
+  }
+
+  /* Simulation systems skip their update while this holds */
+  pub fn simulation_frozen(&self) -> bool {
+    self.gamepad_disconnected || self.debug_paused && !self.pressed(|controls| controls.debug_step)
+  }
+}
+
 pub fn angle_from_vec(direction: PhysicsVector) -> f32 {
   let base_angle = direction.into_vec().angle(&vector![1.0, 0.0]);
 
@@ -59,10 +153,14 @@ struct StickBindings {
 
 const CONTROLLER_DEADZONE: f32 = 0.2;
 
-fn handle_stick_input(gilrs: &Gilrs, bindings: StickBindings) -> PhysicsVector {
-  let input_vectors = gilrs
-    .gamepads()
-    .map(|(_, gamepad)| {
+fn handle_stick_input(
+  gilrs: &Gilrs,
+  gamepad_id: Option<GamepadId>,
+  bindings: StickBindings,
+) -> PhysicsVector {
+  gamepad_id
+    .and_then(|gamepad_id| gilrs.connected_gamepad(gamepad_id))
+    .map(|gamepad| {
       let horizontal_axis_value = gamepad
         .axis_data(bindings.horizontal)
         .map(|axis_data| axis_data.value())
@@ -72,44 +170,63 @@ fn handle_stick_input(gilrs: &Gilrs, bindings: StickBindings) -> PhysicsVector {
         .map(|axis_data| axis_data.value())
         .unwrap_or(0.0);
 
-      let base_vec = vector![horizontal_axis_value, vertical_axis_value];
+      let base_vec: Vector2<f32> = vector![horizontal_axis_value, vertical_axis_value];
 
       if base_vec.magnitude() < CONTROLLER_DEADZONE {
-        vec_zero()
+        PhysicsVector::zero()
       } else {
-        base_vec.normalize() * INPUT_FORCE
+        PhysicsVector::from_vec(base_vec.normalize() * INPUT_FORCE)
       }
     })
-    .collect::<Vec<_>>();
-
-  if input_vectors.is_empty() {
-    PhysicsVector::zero()
-  } else {
-    PhysicsVector::from_vec(input_vectors.iter().sum::<Vector2<f32>>() / input_vectors.len() as f32)
-  }
+    .unwrap_or(PhysicsVector::zero())
 }
 
-fn handle_button_input(gilrs: &Gilrs, button: Button) -> bool {
-  gilrs.gamepads().any(|(_, gamepad)| {
-    gamepad
-      .button_data(button)
-      .map(|button_data| button_data.is_pressed())
-      .unwrap_or(false)
-  })
+fn handle_button_input(gilrs: &Gilrs, gamepad_id: Option<GamepadId>, button: Button) -> bool {
+  gamepad_id
+    .and_then(|gamepad_id| gilrs.connected_gamepad(gamepad_id))
+    .and_then(|gamepad| {
+      gamepad
+        .button_data(button)
+        .map(|button_data| button_data.is_pressed())
+    })
+    .unwrap_or(false)
 }
 
-impl<Input: Clone + 'static> System for ControlsSystem<Input> {
+impl<Input: Clone + Default + 'static> System for ControlsSystem<Input> {
   type Input = Input;
 
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::when::<SaveData, PhysicsSystem>(),
+      Requirement::when::<SaveData, CameraSystem>(),
+      Requirement::when::<SaveData, SettingsSystem>(),
+    ]
+  }
+
   fn start(_: &ProcessContext<Input>) -> Rc<dyn System<Input = Self::Input>> {
     let gilrs = Gilrs::new().unwrap();
 
     Rc::new(Self {
       left_stick: PhysicsVector::zero(),
       right_stick: PhysicsVector::zero(),
+      zoom: 0.0,
       boost: false,
       chain: false,
+      magnetize: false,
+      hack: false,
+      lock_on: false,
+      loadout_previous: false,
+      loadout_next: false,
+      interact: false,
+      rewind: false,
+      debug_pause: false,
+      debug_step: false,
+      debug_paused: false,
+      time_scale: 1.0,
+      whole_frame: true,
+      frame_clock: 0.0,
       firing: false,
+      fire_held: false,
       inventory: false,
       menu_down: false,
       menu_left: false,
@@ -121,7 +238,10 @@ impl<Input: Clone + 'static> System for ControlsSystem<Input> {
       map: false,
       gilrs: Rc::new(RefCell::new(gilrs)),
       last_frame: None,
+      history: Rc::new(VecDeque::new()),
       control_mode: ControlMode::Keyboard,
+      active_gamepad: None,
+      gamepad_disconnected: false,
       phantom: PhantomData,
     })
   }
@@ -137,6 +257,18 @@ impl<Input: Clone + 'static> System for ControlsSystem<Input> {
     let kbd_tab_pressed = is_key_down(KeyCode::Tab);
     let kbd_ctl_pressed = is_key_down(KeyCode::LeftControl);
     let kbd_c_pressed = is_key_down(KeyCode::C);
+    let kbd_f_pressed = is_key_down(KeyCode::F);
+    let kbd_q_pressed = is_key_down(KeyCode::Q);
+    let kbd_r_pressed = is_key_down(KeyCode::R);
+    let kbd_z_pressed = is_key_down(KeyCode::Z);
+    let kbd_x_pressed = is_key_down(KeyCode::X);
+    let kbd_g_pressed = is_key_down(KeyCode::G);
+    let kbd_t_pressed = is_key_down(KeyCode::T);
+    let kbd_space_pressed = is_key_down(KeyCode::Space);
+
+    let debug_pause = DEBUG_STEPPING && is_key_down(KeyCode::F5);
+    let debug_step = DEBUG_STEPPING && is_key_down(KeyCode::F6);
+    let debug_paused = self.debug_paused ^ (debug_pause && !self.debug_pause);
 
     let lmb_pressed = is_mouse_button_down(MouseButton::Left);
     let rmb_pressed = is_mouse_button_down(MouseButton::Right);
@@ -150,16 +282,57 @@ impl<Input: Clone + 'static> System for ControlsSystem<Input> {
       | kbd_tab_pressed
       | kbd_ctl_pressed
       | kbd_c_pressed
+      | kbd_f_pressed
+      | kbd_q_pressed
+      | kbd_r_pressed
+      | kbd_z_pressed
+      | kbd_x_pressed
+      | kbd_g_pressed
+      | kbd_t_pressed
+      | kbd_space_pressed
       | lmb_pressed
       | rmb_pressed;
 
     let mut gilrs = self.gilrs.as_ref().borrow_mut();
 
+    /* MARK: Track which gamepad has control */
+    let release_gamepad = ctx
+      .get::<MenuSystem<_>>()
+      .is_some_and(|menu_system| menu_system.release_controller);
+
+    let mut active_gamepad = if release_gamepad {
+      None
+    } else {
+      self.active_gamepad
+    };
+    let mut gamepad_disconnected = self.gamepad_disconnected;
     let mut incoming_gamepad_input = false;
 
     while let Some(event) = gilrs.next_event() {
-      if matches!(event.event, gilrs::EventType::ButtonChanged(_, _, _)) {
-        incoming_gamepad_input = true
+      match event.event {
+        /* Only a press claims control, releasing the button that gave control up shouldn't take
+        it straight back */
+        EventType::ButtonPressed(_, _) if active_gamepad.is_none() => {
+          log::info("controls", format!("gamepad {} claimed control", event.id));
+          active_gamepad = Some(event.id);
+          incoming_gamepad_input = true;
+          gamepad_disconnected = false;
+        }
+        EventType::ButtonChanged(_, _, _) if active_gamepad == Some(event.id) => {
+          incoming_gamepad_input = true;
+          gamepad_disconnected = false;
+        }
+        EventType::Connected => {
+          log::info("controls", format!("gamepad {} connected", event.id));
+        }
+        EventType::Disconnected => {
+          log::info("controls", format!("gamepad {} disconnected", event.id));
+          if active_gamepad == Some(event.id) {
+            active_gamepad = None;
+            gamepad_disconnected = matches!(self.control_mode, ControlMode::GamePad);
+          }
+        }
+        _ => {}
       }
     }
 
@@ -171,39 +344,130 @@ impl<Input: Clone + 'static> System for ControlsSystem<Input> {
       self.control_mode
     };
 
-    Rc::new(match control_mode {
-      ControlMode::GamePad => Self {
-        left_stick: handle_stick_input(
-          &gilrs,
-          StickBindings {
-            vertical: Axis::LeftStickY,
-            horizontal: Axis::LeftStickX,
-          },
-        ),
-        right_stick: handle_stick_input(
+    /* Picking the keyboard back up also resumes */
+    let gamepad_disconnected = gamepad_disconnected && !incoming_kbd_mouse_input;
+
+    /* MARK: Record input history */
+    let last_frame = Rc::new(Self {
+      last_frame: None,
+      history: Rc::new(VecDeque::new()),
+      ..self.clone()
+    });
+
+    let history = Rc::new(
+      std::iter::once(Rc::clone(&last_frame))
+        .chain(self.history.iter().cloned())
+        .take(INPUT_HISTORY_FRAMES)
+        .collect::<VecDeque<_>>(),
+    );
+
+    /* MARK: Accessibility transforms */
+    let settings = ctx
+      .downcast::<SaveData>()
+      .map(|ctx| ctx.require::<SettingsSystem>().unwrap().settings)
+      .unwrap_or_default();
+
+    let fire_held = match control_mode {
+      ControlMode::GamePad => handle_button_input(&gilrs, active_gamepad, Button::RightTrigger2),
+      ControlMode::Keyboard => lmb_pressed,
+    };
+
+    /* Confirm shares the fire button, so menus leave a toggled fire as it was */
+    let menu_open = ctx
+      .get::<MenuSystem<_>>()
+      .is_some_and(|menu_system| !menu_system.active_menus.is_empty());
+
+    let firing = if !settings.toggle_fire {
+      fire_held
+    } else if menu_open {
+      self.firing
+    } else {
+      self.firing != (fire_held && !self.fire_held)
+    };
+
+    let boost = match (control_mode, settings.boost_binding) {
+      (ControlMode::GamePad, BoostBinding::Trigger) => {
+        handle_button_input(&gilrs, active_gamepad, Button::LeftTrigger2)
+      }
+      (ControlMode::GamePad, BoostBinding::FaceButton) => {
+        handle_button_input(&gilrs, active_gamepad, Button::South)
+      }
+      (ControlMode::Keyboard, BoostBinding::Trigger) => kbd_ctl_pressed,
+      (ControlMode::Keyboard, BoostBinding::FaceButton) => kbd_space_pressed,
+    };
+
+    /* MARK: Game speed */
+    let time_scale = settings.game_speed.factor();
+    let frame_clock = self.frame_clock + time_scale;
+    let whole_frame = frame_clock >= 1.0;
+    let frame_clock = if whole_frame {
+      frame_clock - 1.0
+    } else {
+      frame_clock
+    };
+
+    let controls = match control_mode {
+      ControlMode::GamePad => {
+        let right_stick = handle_stick_input(
           &gilrs,
+          active_gamepad,
           StickBindings {
             vertical: Axis::RightStickY,
             horizontal: Axis::RightStickX,
           },
-        ),
-        menu_up: handle_button_input(&gilrs, Button::DPadUp),
-        menu_down: handle_button_input(&gilrs, Button::DPadDown),
-        menu_left: handle_button_input(&gilrs, Button::DPadLeft),
-        menu_right: handle_button_input(&gilrs, Button::DPadRight),
-        firing: handle_button_input(&gilrs, Button::RightTrigger2),
-        inventory: handle_button_input(&gilrs, Button::West),
-        pause: handle_button_input(&gilrs, Button::Select),
-        map: handle_button_input(&gilrs, Button::North),
-        boost: handle_button_input(&gilrs, Button::LeftTrigger2),
-        chain: handle_button_input(&gilrs, Button::LeftTrigger),
-        menu_cancel: handle_button_input(&gilrs, Button::East),
-        menu_confirm: handle_button_input(&gilrs, Button::South),
-        gilrs: Rc::clone(&self.gilrs),
-        control_mode,
-        last_frame: Some(Rc::new(self.clone())),
-        phantom: PhantomData,
-      },
+        );
+
+        Self {
+          left_stick: handle_stick_input(
+            &gilrs,
+            active_gamepad,
+            StickBindings {
+              vertical: Axis::LeftStickY,
+              horizontal: Axis::LeftStickX,
+            },
+          ),
+          right_stick,
+          /* Photo mode zooms with the right stick while it's clicked in, and pans with it otherwise */
+          zoom: if handle_button_input(&gilrs, active_gamepad, Button::RightThumb) {
+            right_stick.y()
+          } else {
+            0.0
+          },
+          menu_up: handle_button_input(&gilrs, active_gamepad, Button::DPadUp),
+          menu_down: handle_button_input(&gilrs, active_gamepad, Button::DPadDown),
+          menu_left: handle_button_input(&gilrs, active_gamepad, Button::DPadLeft),
+          menu_right: handle_button_input(&gilrs, active_gamepad, Button::DPadRight),
+          firing,
+          fire_held,
+          inventory: handle_button_input(&gilrs, active_gamepad, Button::West),
+          pause: handle_button_input(&gilrs, active_gamepad, Button::Select),
+          map: handle_button_input(&gilrs, active_gamepad, Button::North),
+          boost,
+          chain: handle_button_input(&gilrs, active_gamepad, Button::LeftTrigger),
+          magnetize: handle_button_input(&gilrs, active_gamepad, Button::RightTrigger),
+          hack: handle_button_input(&gilrs, active_gamepad, Button::LeftThumb),
+          lock_on: handle_button_input(&gilrs, active_gamepad, Button::RightThumb),
+          loadout_previous: handle_button_input(&gilrs, active_gamepad, Button::DPadLeft),
+          loadout_next: handle_button_input(&gilrs, active_gamepad, Button::DPadRight),
+          interact: handle_button_input(&gilrs, active_gamepad, Button::Start),
+          rewind: handle_button_input(&gilrs, active_gamepad, Button::Mode),
+          debug_pause,
+          debug_step,
+          debug_paused,
+          time_scale,
+          whole_frame,
+          frame_clock,
+          menu_cancel: handle_button_input(&gilrs, active_gamepad, Button::East),
+          menu_confirm: handle_button_input(&gilrs, active_gamepad, Button::South),
+          gilrs: Rc::clone(&self.gilrs),
+          control_mode,
+          active_gamepad,
+          gamepad_disconnected,
+          last_frame: Some(last_frame),
+          history,
+          phantom: PhantomData,
+        }
+      }
       ControlMode::Keyboard => {
         let left_stick_denormalized = vector![
           if kbd_a_pressed { -1.0 } else { 0.0 } + if kbd_d_pressed { 1.0 } else { 0.0 },
@@ -211,15 +475,17 @@ impl<Input: Clone + 'static> System for ControlsSystem<Input> {
         ];
 
         let right_stick_denormalized = if let Some(ctx) = ctx.downcast::<SaveData>() {
-          let physics_system = ctx.get::<PhysicsSystem>().unwrap();
-          let camera_system = ctx.get::<CameraSystem>().unwrap();
+          let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+          let camera_system = ctx.require::<CameraSystem>().unwrap();
 
           let mouse_pos = mouse_position();
 
-          let player_screen_position = PhysicsVector::from_vec(
-            *physics_system.rigid_body_set[physics_system.player_handle].translation(),
-          )
-          .into_pos(camera_system.translation);
+          let player_screen_position = camera_system.zoomed(
+            PhysicsVector::from_vec(
+              *physics_system.rigid_body_set[physics_system.player_handle].translation(),
+            )
+            .into_pos(camera_system.translation),
+          );
 
           let base_stick = vector![mouse_pos.0, mouse_pos.1] - player_screen_position.into_vec();
           vector![base_stick[0], -base_stick[1]]
@@ -240,24 +506,156 @@ impl<Input: Clone + 'static> System for ControlsSystem<Input> {
           } else {
             right_stick_denormalized.normalize()
           }),
+          zoom: match mouse_wheel().1 {
+            scroll if scroll > 0.0 => 1.0,
+            scroll if scroll < 0.0 => -1.0,
+            _ => 0.0,
+          },
           menu_up: kbd_w_pressed,
           menu_down: kbd_s_pressed,
           menu_left: kbd_a_pressed,
           menu_right: kbd_d_pressed,
-          firing: lmb_pressed,
+          firing,
+          fire_held,
           inventory: kbd_e_pressed,
           pause: kbd_esc_pressed,
           map: kbd_tab_pressed,
-          boost: kbd_ctl_pressed,
+          boost,
           chain: kbd_c_pressed,
+          magnetize: kbd_f_pressed,
+          hack: kbd_r_pressed,
+          lock_on: kbd_q_pressed,
+          loadout_previous: kbd_z_pressed,
+          loadout_next: kbd_x_pressed,
+          interact: kbd_g_pressed,
+          rewind: kbd_t_pressed,
+          debug_pause,
+          debug_step,
+          debug_paused,
+          time_scale,
+          whole_frame,
+          frame_clock,
           menu_cancel: rmb_pressed,
           menu_confirm: lmb_pressed,
           gilrs: Rc::clone(&self.gilrs),
           control_mode,
-          last_frame: Some(Rc::new(self.clone())),
+          active_gamepad,
+          gamepad_disconnected,
+          last_frame: Some(last_frame),
+          history,
           phantom: PhantomData,
         }
       }
+    };
+
+    let right_stick = if settings.sticky_aim && controls.right_stick == PhysicsVector::zero() {
+      self.right_stick
+    } else {
+      controls.right_stick
+    };
+
+    Rc::new(Self {
+      right_stick,
+      ..controls
+    })
+  }
+}
+
+/* MARK: Co-op */
+
+/* Input for the second player, read from whichever other gamepad pressed start
+to join while co-op is enabled */
+#[derive(Clone)]
+pub struct CoopControlsSystem {
+  pub gamepad: Option<GamepadId>,
+  pub left_stick: PhysicsVector,
+  pub right_stick: PhysicsVector,
+  pub firing: bool,
+  /* 1 or -1 on the frame the partner steps through the loadout presets */
+  pub loadout_step: i32,
+  /* Whether the previous and next loadout buttons were held last frame */
+  loadout_held: (bool, bool),
+}
+
+impl Default for CoopControlsSystem {
+  fn default() -> Self {
+    Self {
+      gamepad: None,
+      left_stick: PhysicsVector::zero(),
+      right_stick: PhysicsVector::zero(),
+      firing: false,
+      loadout_step: 0,
+      loadout_held: (false, false),
+    }
+  }
+}
+
+impl System for CoopControlsSystem {
+  type Input = SaveData;
+
+  fn start(_: &ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>> {
+    Rc::new(Self::default())
+  }
+
+  fn run(&self, ctx: &ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>> {
+    let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
+    let settings_system = ctx.require::<SettingsSystem>().unwrap();
+
+    if !settings_system.settings.coop {
+      return Rc::new(Self::default());
+    }
+
+    let gilrs = controls_system.gilrs.as_ref().borrow();
+
+    let is_free = |gamepad_id: &GamepadId| {
+      gilrs.connected_gamepad(*gamepad_id).is_some()
+        && controls_system.active_gamepad != Some(*gamepad_id)
+    };
+
+    let gamepad = self.gamepad.filter(is_free).or_else(|| {
+      gilrs
+        .gamepads()
+        .find(|(gamepad_id, gamepad)| is_free(gamepad_id) && gamepad.is_pressed(Button::Start))
+        .map(|(gamepad_id, _)| gamepad_id)
+    });
+
+    if let Some(gamepad_id) = gamepad
+      && gamepad != self.gamepad
+    {
+      log::info(
+        "controls",
+        format!("gamepad {} joined as player two", gamepad_id),
+      );
+    }
+
+    let loadout_held = (
+      handle_button_input(&gilrs, gamepad, Button::DPadLeft),
+      handle_button_input(&gilrs, gamepad, Button::DPadRight),
+    );
+    let loadout_step = i32::from(loadout_held.1 && !self.loadout_held.1)
+      - i32::from(loadout_held.0 && !self.loadout_held.0);
+
+    Rc::new(Self {
+      gamepad,
+      left_stick: handle_stick_input(
+        &gilrs,
+        gamepad,
+        StickBindings {
+          vertical: Axis::LeftStickY,
+          horizontal: Axis::LeftStickX,
+        },
+      ),
+      right_stick: handle_stick_input(
+        &gilrs,
+        gamepad,
+        StickBindings {
+          vertical: Axis::RightStickY,
+          horizontal: Axis::RightStickX,
+        },
+      ),
+      firing: handle_button_input(&gilrs, gamepad, Button::RightTrigger2),
+      loadout_step,
+      loadout_held,
     })
   }
 }
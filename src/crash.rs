@@ -0,0 +1,86 @@
+use std::{
+  collections::VecDeque,
+  fs,
+  panic::{self, PanicHookInfo},
+  sync::Mutex,
+  time,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::save::{SaveData, save_data_path, write_save};
+
+/* Recovery state lives outside the systems so the panic hook can still reach
+it once the game loop has unwound */
+static LAST_SNAPSHOT: Mutex<Option<SaveData>> = Mutex::new(None);
+static EVENT_HISTORY: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+const EVENT_HISTORY_LENGTH: usize = 64;
+
+fn timestamp() -> String {
+  let sys_time: DateTime<Utc> = time::SystemTime::now().into();
+  sys_time.format("%+").to_string()
+}
+
+pub fn record_event(event: String) {
+  if let Ok(mut event_history) = EVENT_HISTORY.lock() {
+    if event_history.len() >= EVENT_HISTORY_LENGTH {
+      event_history.pop_front();
+    }
+    event_history.push_back(format!("[{}] {}", timestamp(), event));
+  }
+}
+
+pub fn record_snapshot(save_data: SaveData) {
+  if let Ok(mut last_snapshot) = LAST_SNAPSHOT.lock() {
+    *last_snapshot = Some(save_data);
+  }
+}
+
+/* Everything here is best effort, a failure to write just means less to recover */
+fn write_crash_files(info: &PanicHookInfo) {
+  let timestamp = timestamp();
+
+  /* try_lock in case the panic happened while one of these was held */
+  let emergency_save = LAST_SNAPSHOT
+    .try_lock()
+    .ok()
+    .and_then(|last_snapshot| last_snapshot.clone())
+    .and_then(|save_data| {
+      let save_name = format!("save_{}_emergency", timestamp);
+      write_save(&save_name, &save_data).ok()?;
+      Some(save_name)
+    });
+
+  let event_history = EVENT_HISTORY
+    .try_lock()
+    .map(|event_history| event_history.iter().cloned().collect::<Vec<_>>())
+    .unwrap_or_default();
+
+  let crash_log = [
+    format!("{}", info),
+    format!(
+      "emergency save: {}",
+      emergency_save.unwrap_or("none".to_string())
+    ),
+    "recent events:".to_string(),
+  ]
+  .into_iter()
+  .chain(event_history)
+  .collect::<Vec<_>>()
+  .join("\n");
+
+  let _ = fs::write(
+    save_data_path(&format!("crash_{}.log", timestamp)),
+    crash_log,
+  );
+}
+
+pub fn install_panic_handler() {
+  let default_hook = panic::take_hook();
+
+  panic::set_hook(Box::new(move |info| {
+    write_crash_files(info);
+    default_hook(info);
+  }));
+}
@@ -0,0 +1,172 @@
+use std::{fs, rc::Rc};
+
+use chrono::{Datelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  ecs::{Enemy, Faction},
+  log,
+  physics::PhysicsSystem,
+  save::SaveData,
+  score::ScoreSystem,
+  storage,
+  system::{Requirement, System},
+};
+
+/* Run times are given in seconds of game time rather than real time */
+const SIMULATED_FRAMES_PER_SECOND: f64 = 60.0;
+
+/* Everyone playing on the same day gets the same arena, e.g. 20261018 */
+pub fn daily_seed() -> u64 {
+  let today = Utc::now().date_naive();
+  today.year() as u64 * 10000 + today.month() as u64 * 100 + today.day() as u64
+}
+
+/* A finished run, in the form it's written out for comparing against other players */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DailyRun {
+  pub seed: u64,
+  pub score: i32,
+  pub time: f32,
+  pub best_combo: i32,
+}
+
+impl DailyRun {
+  /* Higher scores win, with faster times breaking ties */
+  fn beats(&self, other: &DailyRun) -> bool {
+    self.score > other.score || (self.score == other.score && self.time < other.time)
+  }
+}
+
+/* Shown once every enemy in the arena is dead */
+#[derive(Clone, Debug)]
+pub struct DailyResult {
+  pub run: DailyRun,
+  /* The seed's best run before this one */
+  pub best: Option<DailyRun>,
+}
+
+impl DailyResult {
+  pub fn new_best(&self) -> bool {
+    self.best.as_ref().is_none_or(|best| self.run.beats(best))
+  }
+}
+
+fn daily_run_path(seed: u64) -> String {
+  storage::data_path(&format!("daily_{}.json", seed))
+}
+
+fn load_best_run(seed: u64) -> Option<DailyRun> {
+  fs::read_to_string(daily_run_path(seed))
+    .ok()
+    .and_then(|raw_file| serde_json::from_str(&raw_file).ok())
+}
+
+fn write_best_run(run: &DailyRun) {
+  if let Err(error) = fs::create_dir_all(storage::data_dir()).and_then(|_| {
+    fs::write(
+      daily_run_path(run.seed),
+      serde_json::to_string_pretty(run).unwrap(),
+    )
+  }) {
+    log::error("daily", format!("failed to write daily run: {}", error));
+  }
+}
+
+/* Times daily arena runs, finishing the run once the arena has been cleared */
+pub struct DailySystem {
+  /* None outside of daily runs */
+  pub seed: Option<u64>,
+  /* Enemies are only there once the arena has loaded, so it can't be cleared before then */
+  pub enemies_seen: bool,
+  pub cleared: bool,
+  /* Set on the frame the arena is cleared */
+  pub finished: Option<DailyResult>,
+}
+
+impl DailySystem {
+  /* Seconds of game time since the run started */
+  pub fn time(&self, physics_system: &PhysicsSystem) -> f32 {
+    (physics_system.simulated_frames / SIMULATED_FRAMES_PER_SECOND) as f32
+  }
+}
+
+impl System for DailySystem {
+  type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<PhysicsSystem>(),
+      Requirement::of::<ScoreSystem>(),
+    ]
+  }
+
+  fn start(ctx: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
+  where
+    Self: Sized,
+  {
+    Rc::new(Self {
+      seed: ctx.input.daily_seed,
+      enemies_seen: false,
+      cleared: false,
+      finished: None,
+    })
+  }
+
+  fn run(
+    &self,
+    ctx: &crate::system::ProcessContext<Self::Input>,
+  ) -> Rc<dyn System<Input = Self::Input>> {
+    let Some(seed) = self.seed.filter(|_| !self.cleared) else {
+      return Rc::new(Self {
+        finished: None,
+        ..*self
+      });
+    };
+
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+    let score_system = ctx.require::<ScoreSystem>().unwrap();
+
+    let enemies_left = physics_system.entities.values().any(|entity| {
+      entity.components.get::<Enemy>().is_some()
+        && Faction::Player.hostile_to(Faction::of(&entity.components), false)
+    });
+
+    if enemies_left || !self.enemies_seen {
+      return Rc::new(Self {
+        enemies_seen: self.enemies_seen || enemies_left,
+        finished: None,
+        ..*self
+      });
+    }
+
+    /* MARK: Finish the run, keeping it if it's the seed's best */
+    let run = DailyRun {
+      seed,
+      score: score_system.score,
+      time: self.time(&physics_system),
+      best_combo: score_system.best_combo,
+    };
+    let result = DailyResult {
+      run,
+      best: load_best_run(seed),
+    };
+
+    log::info(
+      "daily",
+      format!(
+        "finished daily {} with {} points in {:.2}s",
+        seed, result.run.score, result.run.time
+      ),
+    );
+    if result.new_best() {
+      write_best_run(&result.run);
+    }
+
+    Rc::new(Self {
+      cleared: true,
+      finished: Some(result),
+      ..*self
+    })
+  }
+}
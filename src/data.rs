@@ -0,0 +1,507 @@
+use std::{collections::HashMap, env::current_dir, fs, path::PathBuf, rc::Rc, time::SystemTime};
+
+use serde::{Deserialize, de::DeserializeOwned};
+
+use crate::{
+  collision::CollisionLayer,
+  combat::ModuleInstance,
+  ecs::{CapCategory, Enemy, Faction, LootEntry, OnHitStatus},
+  log,
+  save::SaveData,
+  system::System,
+};
+
+/* MARK: Enemy definitions */
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct EnemyProjectileDefinition {
+  pub damage: f32,
+  pub speed: f32,
+  #[serde(default)]
+  pub on_hit_status: Option<OnHitStatus>,
+  /* How many frames ahead of firing the attack is shown, none at zero */
+  #[serde(default)]
+  pub telegraph_frames: i32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EnemyDefinition {
+  pub health: f32,
+  pub damage: f32,
+  #[serde(default)]
+  pub loot: Vec<LootEntry>,
+  #[serde(default = "default_loot_despawn_frames")]
+  pub loot_despawn_frames: i32,
+  #[serde(default)]
+  pub knockback: f32,
+  #[serde(default)]
+  pub on_hit_status: Option<OnHitStatus>,
+  #[serde(default)]
+  pub projectile: EnemyProjectileDefinition,
+  /* How long the corpse lingers, at zero the enemy disappears as soon as it dies */
+  #[serde(default = "default_death_frames")]
+  pub death_frames: i32,
+  /* Picked out on the radar apart from ordinary enemies */
+  #[serde(default)]
+  pub boss: bool,
+  /* Chance for a spawn without affixes of its own to roll a random one */
+  #[serde(default)]
+  pub elite_chance: f32,
+  /* Wildlife is Neutral, enemies of another group fight the rest */
+  #[serde(default = "default_enemy_faction")]
+  pub faction: Faction,
+}
+
+fn default_enemy_faction() -> Faction {
+  Faction::Enemy(0)
+}
+
+fn default_death_frames() -> i32 {
+  30
+}
+
+fn default_loot_despawn_frames() -> i32 {
+  600
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EnemyDefinitions {
+  pub goblin: EnemyDefinition,
+  pub defender: EnemyDefinition,
+  pub seeker: EnemyDefinition,
+  pub seeker_generator: EnemyDefinition,
+  pub minelayer: EnemyDefinition,
+  pub swarmer: EnemyDefinition,
+  /* Keyed by script name */
+  #[serde(default)]
+  pub scripted: HashMap<String, EnemyDefinition>,
+}
+
+/* Used for scripted enemies which have no entry of their own */
+static SCRIPTED_ENEMY_FALLBACK: EnemyDefinition = EnemyDefinition {
+  health: 50.0,
+  damage: 10.0,
+  loot: Vec::new(),
+  loot_despawn_frames: 600,
+  knockback: 0.0,
+  on_hit_status: None,
+  projectile: EnemyProjectileDefinition {
+    damage: 5.0,
+    speed: 1.0,
+    on_hit_status: None,
+    telegraph_frames: 0,
+  },
+  death_frames: 30,
+  boss: false,
+  elite_chance: 0.0,
+  faction: Faction::Enemy(0),
+};
+
+impl EnemyDefinitions {
+  pub fn for_enemy(&self, enemy: &Enemy) -> &EnemyDefinition {
+    match enemy {
+      Enemy::Goblin(_) => &self.goblin,
+      Enemy::Defender(_) => &self.defender,
+      Enemy::Seeker(_) => &self.seeker,
+      Enemy::SeekerGenerator(_) => &self.seeker_generator,
+      Enemy::Minelayer(_) => &self.minelayer,
+      Enemy::Swarmer(_) => &self.swarmer,
+      Enemy::Scripted(scripted) => self
+        .scripted
+        .get(&scripted.name)
+        .unwrap_or(&SCRIPTED_ENEMY_FALLBACK),
+    }
+  }
+}
+
+/* MARK: Weapon definitions */
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExplosionDefinition {
+  pub radius: f32,
+  pub strength: f32,
+  pub damage: f32,
+  /* Whether the player is caught in their own blast */
+  #[serde(default)]
+  pub self_blast: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WeaponDefinition {
+  pub cooldown: f32,
+  pub damage: f32,
+  pub speed: f32,
+  #[serde(default)]
+  pub force_mod: f32,
+  #[serde(default)]
+  pub explosion: Option<ExplosionDefinition>,
+  #[serde(default)]
+  pub on_hit_status: Option<OnHitStatus>,
+  /* Damage is scaled by a random factor within this fraction either way */
+  #[serde(default)]
+  pub damage_variance: f32,
+  #[serde(default)]
+  pub crit_chance: f32,
+  #[serde(default = "default_crit_multiplier")]
+  pub crit_multiplier: f32,
+  /* Times a projectile reflects off reflective walls before breaking on one */
+  #[serde(default)]
+  pub max_bounces: i32,
+  /* Weapons without heat are only limited by their cooldown */
+  #[serde(default)]
+  pub heat: Option<HeatDefinition>,
+  /* Continuous collision detection for its shots, so fast ones can't skip through thin walls */
+  #[serde(default)]
+  pub ccd: bool,
+}
+
+/* Each shot builds heat, and a weapon that reaches its capacity stops firing until it has
+cooled all the way down */
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct HeatDefinition {
+  pub capacity: f32,
+  pub per_shot: f32,
+  /* Heat lost per frame */
+  pub cooling: f32,
+}
+
+fn default_crit_multiplier() -> f32 {
+  2.0
+}
+
+/* What each level a weapon module has been fused up adds, as fractions of its base stats */
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct LevelBonus {
+  #[serde(default)]
+  pub damage: f32,
+  /* Taken off the cooldown left after the previous levels */
+  #[serde(default)]
+  pub cooldown: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WeaponDefinitions {
+  pub plasma: WeaponDefinition,
+  pub missile: WeaponDefinition,
+  pub drone: WeaponDefinition,
+  /* Fired from mounted turrets in place of the player's own weapons */
+  pub turret: WeaponDefinition,
+  #[serde(default)]
+  pub levels: LevelBonus,
+}
+
+/* MARK: Fusion recipes */
+
+/* Two unequipped modules fused into one, given in either order */
+#[derive(Clone, Debug, Deserialize)]
+pub struct FusionRecipe {
+  pub inputs: [ModuleInstance; 2],
+  pub output: ModuleInstance,
+}
+
+/* What the two modules fuse into, if any recipe takes them */
+pub fn fusion_output(
+  recipes: &[FusionRecipe],
+  first: ModuleInstance,
+  second: ModuleInstance,
+) -> Option<ModuleInstance> {
+  recipes
+    .iter()
+    .find(|recipe| recipe.inputs == [first, second] || recipe.inputs == [second, first])
+    .map(|recipe| recipe.output)
+}
+
+/* MARK: Dialogue definitions */
+
+/* Where a camera pan comes to rest */
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum PanTarget {
+  /* The trigger or enemy that started the dialogue */
+  Source,
+  Player,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum DialogueStep {
+  Line {
+    speaker: String,
+    /* File stem of a png in assets/portraits */
+    #[serde(default)]
+    portrait: Option<String>,
+    text: String,
+  },
+  Pan {
+    target: PanTarget,
+    frames: i32,
+  },
+}
+
+/* Keyed by the name map objects and scripts refer to */
+pub type DialogueDefinitions = HashMap<String, Vec<DialogueStep>>;
+
+/* MARK: Log definitions */
+
+/* Collectible lore, listed in the database in file order */
+#[derive(Clone, Debug, Deserialize)]
+pub struct LogDefinition {
+  pub id: String,
+  pub title: String,
+  pub text: String,
+}
+
+/* MARK: Movement profile */
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct MovementUpgrade {
+  #[serde(default = "default_movement_mod")]
+  pub acceleration_mod: f32,
+  #[serde(default = "default_movement_mod")]
+  pub speed_limit_mod: f32,
+  #[serde(default)]
+  pub extra_boost_charges: i32,
+}
+
+fn default_movement_mod() -> f32 {
+  1.0
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MovementProfile {
+  pub acceleration: f32,
+  pub speed_limit: f32,
+  /* How sharply acceleration falls off approaching the speed limit, higher holds
+  full acceleration for longer */
+  pub acceleration_curve: f32,
+  #[serde(default)]
+  pub linear_damping: f32,
+  pub boost_charges: i32,
+  /* Frames for a single spent boost charge to come back */
+  pub boost_recharge_frames: f32,
+  /* Keyed by the name engine upgrade pickups give */
+  #[serde(default)]
+  pub upgrades: HashMap<String, MovementUpgrade>,
+}
+
+/* The profile with whichever upgrades the player has found applied */
+#[derive(Clone, Copy, Debug)]
+pub struct MovementStats {
+  pub acceleration: f32,
+  pub speed_limit: f32,
+  pub acceleration_curve: f32,
+  pub linear_damping: f32,
+  pub boost_charges: i32,
+  pub boost_recharge_frames: f32,
+}
+
+impl MovementProfile {
+  pub fn stats(&self, upgrades: &[String]) -> MovementStats {
+    let (acceleration_mod, speed_limit_mod, extra_boost_charges) = upgrades
+      .iter()
+      .filter_map(|upgrade| self.upgrades.get(upgrade))
+      .fold(
+        (1.0, 1.0, 0),
+        |(acceleration_mod, speed_limit_mod, extra_boost_charges), upgrade| {
+          (
+            acceleration_mod * upgrade.acceleration_mod,
+            speed_limit_mod * upgrade.speed_limit_mod,
+            extra_boost_charges + upgrade.extra_boost_charges,
+          )
+        },
+      );
+
+    MovementStats {
+      acceleration: self.acceleration * acceleration_mod,
+      speed_limit: self.speed_limit * speed_limit_mod,
+      acceleration_curve: self.acceleration_curve,
+      linear_damping: self.linear_damping,
+      boost_charges: self.boost_charges + extra_boost_charges,
+      boost_recharge_frames: self.boost_recharge_frames,
+    }
+  }
+}
+
+impl MovementStats {
+  pub fn with_speed_mod(&self, speed_mod: f32) -> Self {
+    Self {
+      acceleration: self.acceleration * speed_mod,
+      speed_limit: self.speed_limit * speed_mod,
+      ..*self
+    }
+  }
+
+  /* Fraction of full acceleration left at this speed along the direction of travel */
+  pub fn acceleration_taper(&self, speed: f32) -> f32 {
+    1.0
+      - (speed / self.speed_limit)
+        .clamp(0.0, 1.0)
+        .powf(self.acceleration_curve)
+  }
+}
+
+/* MARK: Entity limits */
+
+/* Most entities of each kind alive at once, so bullet-hell rooms can't bog down */
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct EntityLimits {
+  pub player_projectiles: usize,
+  pub enemy_projectiles: usize,
+  pub particles: usize,
+  pub debris: usize,
+}
+
+impl EntityLimits {
+  pub fn for_category(&self, category: CapCategory) -> usize {
+    match category {
+      CapCategory::PlayerProjectile => self.player_projectiles,
+      CapCategory::EnemyProjectile => self.enemy_projectiles,
+      CapCategory::Particle => self.particles,
+      CapCategory::Debris => self.debris,
+    }
+  }
+}
+
+/* MARK: Loading */
+
+/* Tests find the data beside the crate, wherever cargo was run from */
+fn data_root() -> PathBuf {
+  if cfg!(test) {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+  } else {
+    current_dir().unwrap()
+  }
+}
+
+fn data_read_path(file_name: &str) -> String {
+  data_root()
+    .join("assets")
+    .join("data")
+    .join(file_name)
+    .to_str()
+    .unwrap()
+    .to_string()
+}
+
+const ENEMIES_FILE_NAME: &str = "enemies.json";
+const WEAPONS_FILE_NAME: &str = "weapons.json";
+const DIALOGUE_FILE_NAME: &str = "dialogue.json";
+const LOGS_FILE_NAME: &str = "logs.json";
+const LIMITS_FILE_NAME: &str = "limits.json";
+const MOVEMENT_FILE_NAME: &str = "movement.json";
+const FUSION_FILE_NAME: &str = "fusion.json";
+const COLLISIONS_FILE_NAME: &str = "collisions.json";
+
+fn try_load_definitions<T: DeserializeOwned>(file_name: &str) -> Result<T, String> {
+  fs::read_to_string(data_read_path(file_name))
+    .map_err(|error| error.to_string())
+    .and_then(|raw_file| serde_json::from_str(&raw_file).map_err(|error| error.to_string()))
+    .map_err(|error| format!("{}: {}", file_name, error))
+}
+
+/* Each layer against the layers it touches */
+pub type CollisionDefinitions = HashMap<CollisionLayer, Vec<CollisionLayer>>;
+
+pub fn try_load_collisions() -> Result<CollisionDefinitions, String> {
+  try_load_definitions(COLLISIONS_FILE_NAME)
+}
+
+fn data_modified_time(file_name: &str) -> Option<SystemTime> {
+  fs::metadata(data_read_path(file_name))
+    .and_then(|metadata| metadata.modified())
+    .ok()
+}
+
+#[derive(Clone, Debug)]
+pub struct GameData {
+  pub enemies: EnemyDefinitions,
+  pub weapons: WeaponDefinitions,
+  pub dialogue: DialogueDefinitions,
+  pub logs: Vec<LogDefinition>,
+  pub limits: EntityLimits,
+  pub movement: MovementProfile,
+  pub fusion: Vec<FusionRecipe>,
+}
+
+pub fn try_load_game_data() -> Result<GameData, String> {
+  Ok(GameData {
+    enemies: try_load_definitions(ENEMIES_FILE_NAME)?,
+    weapons: try_load_definitions(WEAPONS_FILE_NAME)?,
+    dialogue: try_load_definitions(DIALOGUE_FILE_NAME)?,
+    logs: try_load_definitions(LOGS_FILE_NAME)?,
+    limits: try_load_definitions(LIMITS_FILE_NAME)?,
+    movement: try_load_definitions(MOVEMENT_FILE_NAME)?,
+    fusion: try_load_definitions(FUSION_FILE_NAME)?,
+  })
+}
+
+fn game_data_modified_time() -> Option<SystemTime> {
+  [
+    ENEMIES_FILE_NAME,
+    WEAPONS_FILE_NAME,
+    DIALOGUE_FILE_NAME,
+    LOGS_FILE_NAME,
+    LIMITS_FILE_NAME,
+    MOVEMENT_FILE_NAME,
+    FUSION_FILE_NAME,
+  ]
+  .iter()
+  .filter_map(|file_name| data_modified_time(file_name))
+  .max()
+}
+
+/* MARK: DataSystem */
+
+const HOT_RELOAD_DATA: bool = cfg!(debug_assertions);
+
+pub struct DataSystem {
+  pub data: Rc<GameData>,
+  pub data_modified: Option<SystemTime>,
+  pub reloaded: bool,
+}
+
+impl System for DataSystem {
+  type Input = SaveData;
+
+  fn start(_: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
+  where
+    Self: Sized,
+  {
+    Rc::new(Self {
+      data: Rc::new(try_load_game_data().expect("Game data could not be loaded")),
+      data_modified: game_data_modified_time(),
+      reloaded: false,
+    })
+  }
+
+  fn run(
+    &self,
+    _: &crate::system::ProcessContext<Self::Input>,
+  ) -> Rc<dyn System<Input = Self::Input>> {
+    if HOT_RELOAD_DATA
+      && let Some(modified) = game_data_modified_time()
+      && self
+        .data_modified
+        .is_some_and(|last_modified| last_modified != modified)
+    {
+      /* MARK: Hot reload the data files */
+      let data = try_load_game_data();
+
+      if let Err(error) = &data {
+        log::error("data", format!("failed to hot reload game data: {}", error));
+      }
+
+      return Rc::new(Self {
+        reloaded: data.is_ok(),
+        data: data.map(Rc::new).unwrap_or(Rc::clone(&self.data)),
+        data_modified: Some(modified),
+      });
+    }
+
+    Rc::new(Self {
+      data: Rc::clone(&self.data),
+      data_modified: self.data_modified,
+      reloaded: false,
+    })
+  }
+}
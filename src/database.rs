@@ -0,0 +1,69 @@
+use std::rc::Rc;
+
+use crate::{
+  data::LogDefinition,
+  physics::PhysicsSystem,
+  save::SaveData,
+  system::{Requirement, System},
+};
+
+/* What the database menu page shows for a single log */
+#[derive(Clone)]
+pub struct DatabaseEntry {
+  pub title: String,
+  pub text: String,
+  pub discovered: bool,
+}
+
+pub struct DatabaseSystem {
+  /* Ids of every log the player has picked up, in pickup order */
+  pub discovered: Vec<String>,
+}
+
+impl DatabaseSystem {
+  /* Undiscovered logs are still listed so the page doubles as a checklist */
+  pub fn entries(&self, logs: &[LogDefinition]) -> Vec<DatabaseEntry> {
+    logs
+      .iter()
+      .map(|log| DatabaseEntry {
+        title: log.title.clone(),
+        text: log.text.clone(),
+        discovered: self.discovered.contains(&log.id),
+      })
+      .collect()
+  }
+}
+
+impl System for DatabaseSystem {
+  type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![Requirement::of::<PhysicsSystem>()]
+  }
+
+  fn start(ctx: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
+  where
+    Self: Sized,
+  {
+    Rc::new(Self {
+      discovered: ctx.input.discovered_logs.clone(),
+    })
+  }
+
+  fn run(
+    &self,
+    ctx: &crate::system::ProcessContext<Self::Input>,
+  ) -> Rc<dyn System<Input = Self::Input>> {
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+
+    let new_logs = physics_system
+      .new_logs
+      .iter()
+      .filter(|log_id| !self.discovered.contains(log_id))
+      .cloned();
+
+    Rc::new(Self {
+      discovered: self.discovered.iter().cloned().chain(new_logs).collect(),
+    })
+  }
+}
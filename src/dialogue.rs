@@ -0,0 +1,241 @@
+use std::{collections::HashMap, env::current_dir, fs, path::Path, rc::Rc};
+
+use macroquad::texture::{Image, Texture2D};
+use rapier2d::na::Vector2;
+
+use crate::{
+  controls::ControlsSystem,
+  data::{DataSystem, DialogueStep, PanTarget},
+  ecs::DialogueTrigger,
+  enemy::EnemySystem,
+  log,
+  menu::MenuSystem,
+  physics::PhysicsSystem,
+  save::SaveData,
+  system::{Requirement, System},
+};
+
+/* Frames the cancel button has to be held to skip the rest of a dialogue */
+pub const DIALOGUE_SKIP_FRAMES: i32 = 60;
+
+/* Portraits are looked up by file stem, so `assets/portraits/drifter.png` is "drifter" */
+const PORTRAIT_DIRECTORY: &str = "assets/portraits";
+const PORTRAIT_EXTENSION: &str = "png";
+
+fn load_portraits() -> HashMap<String, Texture2D> {
+  let portrait_directory = Path::new(&current_dir().unwrap()).join(PORTRAIT_DIRECTORY);
+
+  fs::read_dir(portrait_directory)
+    .map(|entries| {
+      entries
+        .flatten()
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default()
+    .into_iter()
+    .filter(|path| {
+      path
+        .extension()
+        .is_some_and(|ext| ext == PORTRAIT_EXTENSION)
+    })
+    .filter_map(|path| {
+      let name = path.file_stem()?.to_str()?.to_string();
+      let bytes = fs::read(&path).ok()?;
+      let image = Image::from_file_with_format(&bytes, None).ok()?;
+      Some((name, Texture2D::from_image(&image)))
+    })
+    .collect()
+}
+
+#[derive(Clone)]
+pub struct ActiveDialogue {
+  pub steps: Rc<Vec<DialogueStep>>,
+  pub step: usize,
+  pub pan_frames_remaining: i32,
+  /* Where the trigger or enemy that started the dialogue was */
+  pub source_translation: Vector2<f32>,
+  /* Held by the camera from the first pan until the dialogue ends */
+  pub camera_focus: Option<Vector2<f32>>,
+  pub skip_held_frames: i32,
+}
+
+impl ActiveDialogue {
+  /* None once every step has played */
+  fn enter_step(self, step: usize, player_translation: Vector2<f32>) -> Option<Self> {
+    match self.steps.get(step)? {
+      DialogueStep::Pan { target, frames } => Some(Self {
+        step,
+        pan_frames_remaining: *frames,
+        camera_focus: Some(match target {
+          PanTarget::Source => self.source_translation,
+          PanTarget::Player => player_translation,
+        }),
+        ..self
+      }),
+      DialogueStep::Line { .. } => Some(Self { step, ..self }),
+    }
+  }
+
+  pub fn current_step(&self) -> &DialogueStep {
+    &self.steps[self.step]
+  }
+}
+
+pub struct DialogueSystem {
+  pub active: Option<ActiveDialogue>,
+  /* Each dialogue only ever plays once per save */
+  pub seen: Vec<String>,
+  pub portraits: Rc<HashMap<String, Texture2D>>,
+}
+
+impl DialogueSystem {
+  /* Player input is ignored and the simulation holds while this is true */
+  pub fn playing(&self) -> bool {
+    self.active.is_some()
+  }
+
+  pub fn camera_focus(&self) -> Option<Vector2<f32>> {
+    self.active.as_ref().and_then(|active| active.camera_focus)
+  }
+}
+
+impl System for DialogueSystem {
+  type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<PhysicsSystem>(),
+      Requirement::of::<ControlsSystem<_>>(),
+      Requirement::of::<MenuSystem<_>>(),
+      Requirement::of::<EnemySystem>(),
+      Requirement::of::<DataSystem>(),
+    ]
+  }
+
+  fn start(ctx: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
+  where
+    Self: Sized,
+  {
+    Rc::new(Self {
+      active: None,
+      seen: ctx.input.seen_dialogue.clone(),
+      portraits: Rc::new(load_portraits()),
+    })
+  }
+
+  fn run(
+    &self,
+    ctx: &crate::system::ProcessContext<Self::Input>,
+  ) -> Rc<dyn System<Input = Self::Input>> {
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+    let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
+    let menu_system = ctx.require::<MenuSystem<_>>().unwrap();
+
+    let player_translation =
+      *physics_system.rigid_body_set[physics_system.player_handle].translation();
+
+    if let Some(active) = self.active.as_ref() {
+      /* MARK: Hold while paused */
+      if !menu_system.active_menus.is_empty() || controls_system.simulation_frozen() {
+        return Rc::new(Self {
+          active: self.active.clone(),
+          seen: self.seen.clone(),
+          portraits: Rc::clone(&self.portraits),
+        });
+      }
+
+      /* MARK: Skip everything left on a long enough hold */
+      let skip_held_frames = if controls_system.menu_cancel {
+        active.skip_held_frames + 1
+      } else {
+        0
+      };
+
+      let active = ActiveDialogue {
+        skip_held_frames,
+        pan_frames_remaining: active.pan_frames_remaining - 1,
+        ..active.clone()
+      };
+
+      /* MARK: Lines wait for confirm, pans for their frames to run out */
+      let step_finished = match active.current_step() {
+        DialogueStep::Line { .. } => {
+          controls_system.pressed(|controls| controls.menu_confirm || controls.firing)
+        }
+        DialogueStep::Pan { .. } => active.pan_frames_remaining <= 0,
+      };
+
+      let active = if skip_held_frames >= DIALOGUE_SKIP_FRAMES {
+        None
+      } else if step_finished {
+        let next_step = active.step + 1;
+        active.enter_step(next_step, player_translation)
+      } else {
+        Some(active)
+      };
+
+      return Rc::new(Self {
+        active,
+        seen: self.seen.clone(),
+        portraits: Rc::clone(&self.portraits),
+      });
+    }
+
+    /* MARK: Find unseen triggers the player is inside of */
+    let triggered_by_map = physics_system
+      .entered_trigger(|trigger: &DialogueTrigger| !self.seen.contains(&trigger.dialogue))
+      .map(|(trigger, trigger_collider)| {
+        (
+          trigger.dialogue.clone(),
+          *physics_system.collider_set[trigger_collider].translation(),
+        )
+      });
+
+    /* MARK: Or a boss announcing its next phase */
+    let enemy_system = ctx.require::<EnemySystem>().unwrap();
+
+    let triggered = triggered_by_map.or_else(|| {
+      enemy_system.decisions.iter().find_map(|decision| {
+        let dialogue = decision.dialogue.as_ref()?;
+        let enemy_rigid_body = physics_system.rigid_body_set.get(decision.handle)?;
+
+        (!self.seen.contains(dialogue)).then(|| (dialogue.clone(), *enemy_rigid_body.translation()))
+      })
+    });
+
+    let Some((dialogue, source_translation)) = triggered else {
+      return Rc::new(Self {
+        active: None,
+        seen: self.seen.clone(),
+        portraits: Rc::clone(&self.portraits),
+      });
+    };
+
+    let data_system = ctx.require::<DataSystem>().unwrap();
+
+    let steps = data_system.data.dialogue.get(&dialogue);
+    if steps.is_none() {
+      log::warn("dialogue", format!("no dialogue named {}", dialogue));
+    }
+
+    let active = steps.and_then(|steps| {
+      ActiveDialogue {
+        steps: Rc::new(steps.clone()),
+        step: 0,
+        pan_frames_remaining: 0,
+        source_translation,
+        camera_focus: None,
+        skip_held_frames: 0,
+      }
+      .enter_step(0, player_translation)
+    });
+
+    /* Unknown dialogue is marked seen too, so it only warns once */
+    Rc::new(Self {
+      active,
+      seen: [self.seen.clone(), vec![dialogue]].concat(),
+      portraits: Rc::clone(&self.portraits),
+    })
+  }
+}
@@ -1,4 +1,7 @@
-use std::{any::Any, rc::Rc};
+use std::{
+  any::{Any, TypeId},
+  sync::Arc,
+};
 
 use rapier2d::{
   na::Vector2,
@@ -7,12 +10,17 @@ use rapier2d::{
     RigidBodyHandle, RigidBodySet,
   },
 };
-use rpds::{HashTrieSet, List};
+use rpds::{HashTrieMap, HashTrieMapSync, List};
+use serde::{Deserialize, Serialize};
 
 use crate::{
   combat::WeaponModuleKind,
-  enemy::{EnemyDefender, EnemyGoblin, EnemyGoblinState, EnemySeeker, EnemySeekerGenerator},
+  enemy::{
+    EnemyDefender, EnemyGoblin, EnemyGoblinState, EnemyMinelayer, EnemyScripted, EnemySeeker,
+    EnemySeekerGenerator, EnemySwarmer,
+  },
   load_map::{MapAbilityType, MapEnemyName},
+  tutorial::TutorialAction,
 };
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -88,85 +96,223 @@ impl EntityHandle {
 pub struct Entity {
   pub handle: EntityHandle,
   pub components: ComponentSet,
-  pub label: String,
 }
 
+impl Entity {
+  /* Drawn over the entity while debugging and never matched on, so it's free to change. Built
+  from what the entity is, its name or id and its activation where it has them */
+  pub fn debug_label(&self) -> String {
+    let components = &self.components;
+
+    let base = match (components.get::<Name>(), components.get::<EntityKind>()) {
+      (Some(name), _) => name.name.clone(),
+      (None, Some(kind)) if *kind == EntityKind::Wall => [
+        components.get::<Damageable>().map(|_| "D"),
+        components.get::<Damager>().map(|_| "H"),
+        components.get::<Magnetic>().map(|_| "M"),
+        components.get::<Reflective>().map(|_| "R"),
+      ]
+      .into_iter()
+      .flatten()
+      .collect(),
+      (None, Some(kind)) => kind.debug_label().to_string(),
+      (None, None) => String::new(),
+    };
+
+    [
+      Some(base),
+      components.get::<Id>().map(|id| id.id.to_string()),
+      components
+        .get::<Activator>()
+        .map(|activator| format!("{:.2}", activator.activation)),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join(" ")
+  }
+}
+
+/* What an entity is, for the renderer and tools to go by instead of its debug label */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum EntityKind {
+  Player,
+  Enemy,
+  PlayerProjectile,
+  EnemyProjectile,
+  Mine,
+  Drone,
+  Explosion,
+  Impact,
+  Debris,
+  Wall,
+  Block,
+  ItemPickup,
+  HealthPickup,
+  CreditsPickup,
+  PowerUpPickup,
+  AbilityPickup,
+  LogPickup,
+  EngineUpgradePickup,
+  SavePoint,
+  MapTransition,
+  TouchSensor,
+  TutorialTrigger,
+  DialogueTrigger,
+  Encounter,
+  MountedTurret,
+  ExplosiveBarrel,
+  GravitySource,
+  GravityZone,
+  FluidVolume,
+  MagneticWall,
+  Hazard,
+  MountPoint,
+  MountZone,
+  ChainSwitch,
+  ChainSegment,
+  MagnetAnchor,
+  Locomotor,
+  TensionSwitch,
+  And,
+  Or,
+  Gate,
+  Engine,
+}
+impl Component for EntityKind {}
+
+impl EntityKind {
+  fn debug_label(&self) -> &'static str {
+    match self {
+      EntityKind::Player => "player",
+      EntityKind::Enemy => "enemy",
+      EntityKind::PlayerProjectile => "p",
+      EntityKind::EnemyProjectile => "ep",
+      EntityKind::Mine => "mine",
+      EntityKind::Drone => "drone",
+      EntityKind::Explosion => "boom",
+      EntityKind::Impact => "impact",
+      EntityKind::Debris => "debris",
+      EntityKind::Wall => "",
+      EntityKind::Block => "g",
+      EntityKind::ItemPickup => "item",
+      EntityKind::HealthPickup => "health",
+      EntityKind::CreditsPickup => "credits",
+      EntityKind::PowerUpPickup => "power-up",
+      EntityKind::AbilityPickup => "ability",
+      EntityKind::LogPickup => "log",
+      EntityKind::EngineUpgradePickup => "upgrade",
+      EntityKind::SavePoint => "save",
+      EntityKind::MapTransition => "exit",
+      EntityKind::TouchSensor => "gt",
+      EntityKind::TutorialTrigger => "tutorial",
+      EntityKind::DialogueTrigger => "dialogue",
+      EntityKind::Encounter => "encounter",
+      EntityKind::MountedTurret => "turret",
+      EntityKind::ExplosiveBarrel => "barrel",
+      EntityKind::GravitySource => "grav",
+      EntityKind::GravityZone => "grav zone",
+      EntityKind::FluidVolume => "fluid",
+      EntityKind::MagneticWall => "M",
+      EntityKind::Hazard => "hazard",
+      EntityKind::MountPoint => "mount",
+      EntityKind::MountZone => "zone",
+      EntityKind::ChainSwitch => "switch",
+      /* Too many and too small to label */
+      EntityKind::ChainSegment | EntityKind::MagnetAnchor => "",
+      EntityKind::Locomotor => "locomotor",
+      EntityKind::TensionSwitch => "tension",
+      EntityKind::And => "and",
+      EntityKind::Or => "or",
+      EntityKind::Gate => "gate",
+      EntityKind::Engine => "engine",
+    }
+  }
+}
+
+/* Tells apart entities of the same kind, e.g. the two players or where map transitions lead */
+pub struct Name {
+  pub name: String,
+}
+impl Component for Name {}
+
+/* Kept in a persistent map by type, so a set with one component changed shares every other
+component, and most of the map, with the set it was made from. Shared with Arc so entity passes
+can be split across threads */
 #[derive(Clone)]
 pub struct ComponentSet {
-  components: Vec<Rc<dyn Component>>,
+  components: HashTrieMapSync<TypeId, Arc<dyn Component>>,
 }
 
 impl ComponentSet {
   pub fn new() -> Self {
     ComponentSet {
-      components: Vec::new(),
+      components: HashTrieMap::new_sync(),
     }
   }
 
+  /* Leaves a component that's already there alone */
   pub fn insert<Item>(&self, item: Item) -> Self
   where
     Item: Component,
   {
-    if self.components.iter().any(|component| {
-      (Rc::clone(component) as Rc<dyn Any>)
-        .downcast::<Item>()
-        .is_ok()
-    }) {
+    if self.components.contains_key(&TypeId::of::<Item>()) {
       return self.clone();
     }
-    return Self {
-      components: self
-        .components
-        .iter()
-        .cloned()
-        .chain([Rc::new(item) as Rc<dyn Component>])
-        .collect(),
-    };
+    self.with(item)
   }
 
   pub fn with<Item>(&self, item: Item) -> Self
   where
     Item: Component,
   {
-    let components: Vec<_> = self
-      .components
-      .iter()
-      .cloned()
-      .filter(|component| {
-        (Rc::clone(component) as Rc<dyn Any>)
-          .downcast::<Item>()
-          .is_err()
-      })
-      .collect();
+    Self {
+      components: self.components.insert(TypeId::of::<Item>(), Arc::new(item)),
+    }
+  }
 
-    return Self { components }.insert(item);
+  /* Replaces a component with what's made from it, and does nothing if there isn't one */
+  pub fn update<Item>(&self, update: impl FnOnce(&Item) -> Item) -> Self
+  where
+    Item: Component,
+  {
+    match self.get::<Item>() {
+      Some(item) => self.with(update(&item)),
+      None => self.clone(),
+    }
   }
 
-  pub fn get<Item>(&self) -> Option<Rc<Item>>
+  pub fn without<Item>(&self) -> Self
+  where
+    Item: Component,
+  {
+    Self {
+      components: self.components.remove(&TypeId::of::<Item>()),
+    }
+  }
+
+  pub fn get<Item>(&self) -> Option<Arc<Item>>
   where
     Item: Component,
   {
     self
       .components
-      .iter()
-      .find(|component| {
-        (Rc::clone(component) as Rc<dyn Any>)
-          .downcast::<Item>()
-          .is_ok()
-      })
+      .get(&TypeId::of::<Item>())
       .and_then(|component| {
-        (Rc::clone(component) as Rc<dyn Any>)
+        (Arc::clone(component) as Arc<dyn Any + Send + Sync>)
           .downcast::<Item>()
           .ok()
       })
   }
 }
 
-pub trait Component: Any {}
+pub trait Component: Any + Send + Sync {}
 
 pub struct Damageable {
   pub health: f32,
   pub max_health: f32,
+  #[allow(dead_code)]
   pub destroy_on_zero_health: bool,
   pub current_hitstun: f32,
   pub max_hitstun: f32,
@@ -175,9 +321,169 @@ impl Component for Damageable {}
 
 pub struct Damager {
   pub damage: f32,
+  /* Impulse applied to whatever this damages, zero for none */
+  pub knockback: f32,
+  pub on_hit_status: Option<OnHitStatus>,
+  /* Whoever fired this, never hurt by its own shots */
+  pub source: Option<EntityHandle>,
 }
 impl Component for Damager {}
 
+/* The side an entity fights for, read by the damage pass and by enemies picking targets.
+Shots and blasts take the side of whoever set them off, anything without one is Neutral */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Deserialize)]
+pub enum Faction {
+  Player,
+  /* Enemies in different groups fight each other, the same group only under friendly fire */
+  Enemy(i32),
+  /* Walls, wildlife and the like, hurting and hurt by everyone but sought out by no one */
+  #[default]
+  Neutral,
+}
+impl Component for Faction {}
+
+impl Faction {
+  pub fn of(components: &ComponentSet) -> Faction {
+    components
+      .get::<Faction>()
+      .map_or(Faction::default(), |faction| *faction)
+  }
+
+  pub fn hostile_to(&self, other: Faction, friendly_fire: bool) -> bool {
+    match (self, other) {
+      (Faction::Player, Faction::Player) => false,
+      (Faction::Enemy(group), Faction::Enemy(other_group)) => {
+        *group != other_group || friendly_fire
+      }
+      _ => true,
+    }
+  }
+
+  pub fn targets(&self, other: Faction) -> bool {
+    *self != Faction::Neutral && other != Faction::Neutral && self.hostile_to(other, false)
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+pub enum StatusEffectKind {
+  /* Magnitude is damage taken per frame */
+  Burn,
+  /* Magnitude multiplies movement force */
+  Slow,
+  /* Stops enemies from firing, magnitude is unused */
+  Emp,
+}
+
+/* Status applied to whatever a Damager hits */
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct OnHitStatus {
+  pub kind: StatusEffectKind,
+  pub frames: i32,
+  #[serde(default)]
+  pub magnitude: f32,
+}
+
+#[derive(Clone, Default)]
+pub struct StatusEffects {
+  pub effects: Vec<OnHitStatus>,
+}
+impl Component for StatusEffects {}
+
+impl StatusEffects {
+  /* Reapplying an effect refreshes it rather than stacking */
+  pub fn apply(&self, status: &OnHitStatus) -> Self {
+    Self {
+      effects: self
+        .effects
+        .iter()
+        .filter(|effect| effect.kind != status.kind)
+        .cloned()
+        .chain([*status])
+        .collect(),
+    }
+  }
+
+  pub fn tick(&self) -> Self {
+    Self {
+      effects: self
+        .effects
+        .iter()
+        .filter(|effect| effect.frames > 1)
+        .map(|effect| OnHitStatus {
+          frames: effect.frames - 1,
+          ..*effect
+        })
+        .collect(),
+    }
+  }
+
+  fn of_kind(&self, kind: StatusEffectKind) -> impl Iterator<Item = &OnHitStatus> {
+    self
+      .effects
+      .iter()
+      .filter(move |effect| effect.kind == kind)
+  }
+
+  pub fn damage_per_frame(&self) -> f32 {
+    self
+      .of_kind(StatusEffectKind::Burn)
+      .map(|effect| effect.magnitude)
+      .sum()
+  }
+
+  pub fn movement_mod(&self) -> f32 {
+    self
+      .of_kind(StatusEffectKind::Slow)
+      .map(|effect| effect.magnitude)
+      .product()
+  }
+
+  pub fn firing_disabled(&self) -> bool {
+    self.of_kind(StatusEffectKind::Emp).next().is_some()
+  }
+}
+
+/* What a hazard pool is filled with */
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum HazardKind {
+  Lava,
+  Acid,
+}
+
+impl HazardKind {
+  /* Reapplied every frame something is inside, so the effect lingers after leaving */
+  pub fn status(&self) -> OnHitStatus {
+    match self {
+      HazardKind::Lava => OnHitStatus {
+        kind: StatusEffectKind::Burn,
+        frames: 30,
+        magnitude: 0.5,
+      },
+      HazardKind::Acid => OnHitStatus {
+        kind: StatusEffectKind::Burn,
+        frames: 120,
+        magnitude: 0.2,
+      },
+    }
+  }
+}
+
+pub struct Hazard {
+  pub kind: HazardKind,
+}
+impl Component for Hazard {}
+
+/* Water or thin air, acting on every body inside of it */
+pub struct FluidVolume {
+  /* Fraction of velocity lost per frame */
+  pub drag: f32,
+  /* Upward acceleration applied per frame */
+  pub buoyancy: f32,
+  /* Scales the player's own movement while inside */
+  pub movement_scale: f32,
+}
+impl Component for FluidVolume {}
+
 pub struct DestroyOnCollision;
 impl Component for DestroyOnCollision {}
 
@@ -189,6 +495,10 @@ pub enum Enemy {
   Defender(EnemyDefender),
   Seeker(EnemySeeker),
   SeekerGenerator(EnemySeekerGenerator),
+  Minelayer(EnemyMinelayer),
+  Swarmer(EnemySwarmer),
+  /* Anything else named in map data */
+  Scripted(EnemyScripted),
 }
 impl Enemy {
   pub fn default_from_map(map_enemy: MapEnemyName) -> Enemy {
@@ -199,11 +509,96 @@ impl Enemy {
       MapEnemyName::Defender => Self::Defender(EnemyDefender { cooldown: 0 }),
       MapEnemyName::Seeker => Self::Seeker(EnemySeeker),
       MapEnemyName::SeekerGenerator => Self::SeekerGenerator(EnemySeekerGenerator { cooldown: 0 }),
+      MapEnemyName::Minelayer => Self::Minelayer(EnemyMinelayer { cooldown: 0 }),
+      MapEnemyName::Swarmer => Self::Swarmer(EnemySwarmer),
+      MapEnemyName::Scripted(name) => Self::Scripted(EnemyScripted {
+        name,
+        state: Default::default(),
+      }),
     }
   }
 }
 impl Component for Enemy {}
 
+pub struct Boss;
+impl Component for Boss {}
+
+/* Modifiers placed on or rolled onto an enemy spawn, on top of its definition */
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+pub enum EliteAffix {
+  /* Twice the health */
+  Armored,
+  Fast,
+  /* Blows up where it dies */
+  Explosive,
+  Regenerating,
+}
+
+impl EliteAffix {
+  pub const ALL: [EliteAffix; 4] = [
+    EliteAffix::Armored,
+    EliteAffix::Fast,
+    EliteAffix::Explosive,
+    EliteAffix::Regenerating,
+  ];
+}
+
+const ELITE_FAST_MOVEMENT_MOD: f32 = 1.6;
+const ELITE_REGENERATION_PER_FRAME: f32 = 0.05;
+
+#[derive(Clone)]
+pub struct Elite {
+  pub affixes: Vec<EliteAffix>,
+}
+impl Component for Elite {}
+
+impl Elite {
+  pub fn has(&self, affix: EliteAffix) -> bool {
+    self.affixes.contains(&affix)
+  }
+
+  pub fn movement_mod(&self) -> f32 {
+    if self.has(EliteAffix::Fast) {
+      ELITE_FAST_MOVEMENT_MOD
+    } else {
+      1.0
+    }
+  }
+
+  pub fn regeneration_per_frame(&self) -> f32 {
+    if self.has(EliteAffix::Regenerating) {
+      ELITE_REGENERATION_PER_FRAME
+    } else {
+      0.0
+    }
+  }
+}
+
+/* An enemy turned to the player's side by a hack, which self-destructs once frames_left
+runs out */
+#[derive(Clone, Copy, Debug)]
+pub struct Hacked {
+  pub frames_left: i32,
+  pub frames: i32,
+}
+impl Component for Hacked {}
+
+/* A player who just came into a room, who can't be hurt until frames_left runs out */
+#[derive(Clone, Copy, Debug)]
+pub struct SpawnProtection {
+  pub frames_left: i32,
+  pub frames: i32,
+}
+impl Component for SpawnProtection {}
+
+/* An enemy winding up an attack, shown ahead of time so it can be dodged on reaction */
+#[derive(Clone, Copy, Debug)]
+pub struct Telegraph {
+  pub frames_left: i32,
+  pub frames: i32,
+}
+impl Component for Telegraph {}
+
 pub struct GivesItemOnCollision {
   pub weapon_module_kind: WeaponModuleKind,
 }
@@ -218,17 +613,87 @@ impl Component for MapTransitionOnCollision {}
 pub struct SaveMenuOnCollision;
 impl Component for SaveMenuOnCollision {}
 
-pub struct DropHealthOnDestroy {
-  pub amount: f32,
-  pub chance: f32,
+/* A fixed gun the player can climb into, standing still while they fire it */
+pub struct MountedTurret;
+impl Component for MountedTurret {}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LootDrop {
+  Health(f32),
+  Credits(i32),
+  Module(WeaponModuleKind),
+  PowerUp(PowerUpKind),
+  /* Weighs the table towards dropping nothing at all */
+  Nothing,
+}
+
+/* Temporary boosts which run out after a while, or on leaving the room */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerUpKind {
+  RapidFire,
+  DamageBoost,
+  Speed,
+}
+
+impl PowerUpKind {
+  pub fn name(&self) -> &'static str {
+    match self {
+      PowerUpKind::RapidFire => "rapid_fire",
+      PowerUpKind::DamageBoost => "damage_boost",
+      PowerUpKind::Speed => "speed",
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct LootEntry {
+  pub weight: f32,
+  pub drop: LootDrop,
 }
-impl Component for DropHealthOnDestroy {}
+
+/* One entry is rolled by weight when the entity is destroyed */
+pub struct LootTable {
+  pub entries: Vec<LootEntry>,
+  /* How long the drop lies around before despawning, zero keeps it forever */
+  pub despawn_frames: i32,
+}
+impl Component for LootTable {}
+
+impl LootTable {
+  /* Roll is in [0, 1) */
+  pub fn pick(&self, roll: f32) -> Option<LootDrop> {
+    let total_weight = self.entries.iter().map(|entry| entry.weight).sum::<f32>();
+    let target = roll * total_weight;
+
+    self
+      .entries
+      .iter()
+      .scan(0.0, |cumulative_weight, entry| {
+        *cumulative_weight += entry.weight;
+        Some((*cumulative_weight, entry.drop))
+      })
+      .find(|(cumulative_weight, _)| target < *cumulative_weight)
+      .map(|(_, drop)| drop)
+  }
+}
+
+pub struct GivesCreditsOnCollision {
+  pub amount: i32,
+}
+impl Component for GivesCreditsOnCollision {}
 
 pub struct HealOnCollision {
   pub amount: f32,
 }
 impl Component for HealOnCollision {}
 
+pub struct GivesPowerUpOnCollision {
+  pub kind: PowerUpKind,
+}
+impl Component for GivesPowerUpOnCollision {}
+
 pub struct TouchSensor {
   pub target_activation: f32,
 }
@@ -240,9 +705,39 @@ pub struct GravitySource {
 }
 impl Component for GravitySource {}
 
+/* Pulls everything inside the same way, unlike a GravitySource's point */
+pub struct GravityZone {
+  pub acceleration: Vector2<f32>,
+  pub activator_id: Option<i32>,
+}
+impl Component for GravityZone {}
+
 pub struct Destroyed;
 impl Component for Destroyed {}
 
+/* Frames to keep a destroyed entity around as a corpse while its death plays out */
+pub struct DeathAnimation {
+  pub frames: i32,
+}
+impl Component for DeathAnimation {}
+
+/* A corpse, which no longer collides or damages and is removed once this runs out */
+pub struct Dying {
+  pub frames_remaining: i32,
+  pub frames: i32,
+}
+impl Component for Dying {}
+
+pub struct GivesLogOnCollision {
+  pub log_id: String,
+}
+impl Component for GivesLogOnCollision {}
+
+pub struct GivesEngineUpgradeOnCollision {
+  pub name: String,
+}
+impl Component for GivesEngineUpgradeOnCollision {}
+
 pub struct GiveAbilityOnCollision {
   pub ability_type: MapAbilityType,
 }
@@ -258,6 +753,12 @@ pub struct Switch {
 }
 impl Component for Switch {}
 
+/* Activation follows how hard an attached chain pulls, reaching full at full_force */
+pub struct TensionSwitch {
+  pub full_force: f32,
+}
+impl Component for TensionSwitch {}
+
 pub struct Locomotor {
   pub joint: ImpulseJointHandle,
   pub reverse_direction: bool,
@@ -267,6 +768,27 @@ impl Component for Locomotor {}
 pub struct ChainSegment;
 impl Component for ChainSegment {}
 
+pub struct Magnetic;
+impl Component for Magnetic {}
+
+/* Projectiles with bounces left are mirrored off of this instead of breaking */
+pub struct Reflective;
+impl Component for Reflective {}
+
+pub struct Bounces {
+  pub count: i32,
+  pub max: i32,
+}
+impl Component for Bounces {}
+
+/* On the anchor body the player is jointed to while magnetized */
+pub struct MagnetLatch {
+  pub surface: ColliderHandle,
+  /* Points away from the surface, towards the player */
+  pub normal: Vector2<f32>,
+}
+impl Component for MagnetLatch {}
+
 pub struct SimpleActivatable {
   pub activation: f32,
   pub activator_id: i32,
@@ -282,15 +804,96 @@ pub struct ExplodeOnCollision {
   pub strength: f32,
   pub radius: f32,
   pub damage: f32,
+  /* What the blast damages */
   pub interaction_groups: InteractionGroups,
+  /* What the blast pushes around, which can reach further than what it damages */
+  pub force_groups: InteractionGroups,
+  /* Whether the side that set it off is caught in it too, taking a fraction of the damage and a
+  hard shove, e.g. to rocket jump */
+  pub self_blast: bool,
 }
 impl Component for ExplodeOnCollision {}
 
+/* Damage from a blast that only reaches the side which set it off */
+pub struct SelfBlast;
+impl Component for SelfBlast {}
+
+/* Detonates once the entity is destroyed, whatever destroyed it */
+pub struct ExplodeOnDeath {
+  pub explosion: ExplodeOnCollision,
+}
+impl Component for ExplodeOnDeath {}
+
+/* Detonates the entity's ExplodeOnCollision once the player comes within radius,
+but only after arm_frames have passed */
+pub struct ProximityTrigger {
+  pub arm_frames: i32,
+  pub radius: f32,
+}
+impl Component for ProximityTrigger {}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImpactKind {
+  Spark,
+  Scorch,
+  Critical,
+}
+
+/* Companion orbiting the player, one per equipped drone module */
+pub struct Drone {
+  pub index: usize,
+  pub cooldown: f32,
+}
+impl Component for Drone {}
+
+/* Marks a projectile whose damage was rolled as a critical hit */
+pub struct CriticalHit;
+impl Component for CriticalHit {}
+
+/* Purely visual marker left where a projectile hit something */
+pub struct Impact {
+  pub normal: Vector2<f32>,
+  pub kind: ImpactKind,
+  pub lifetime: i32,
+}
+impl Component for Impact {}
+
 pub struct DestroyAfterFrames {
   pub frames: i32,
 }
 impl Component for DestroyAfterFrames {}
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CapCategory {
+  PlayerProjectile,
+  EnemyProjectile,
+  Particle,
+  Debris,
+}
+
+/* Counts against the cap for its category, over the cap the lowest priority
+and then oldest are destroyed first */
+pub struct Capped {
+  pub category: CapCategory,
+  pub priority: i32,
+  pub spawned_frame: i64,
+}
+impl Component for Capped {}
+
+/* Counts down through DestroyAfterFrames, blinking for the last warning_frames */
+pub struct DespawnTimer {
+  pub warning_frames: i32,
+}
+impl Component for DespawnTimer {}
+
+const DESPAWN_BLINK_FRAMES: i32 = 6;
+
+impl DespawnTimer {
+  pub fn hidden(&self, frames_remaining: i32) -> bool {
+    frames_remaining < self.warning_frames && (frames_remaining / DESPAWN_BLINK_FRAMES) % 2 == 1
+  }
+}
+
 pub struct And {
   pub activator_ids: (i32, i32),
 }
@@ -317,3 +920,44 @@ pub struct Id {
   pub id: i32,
 }
 impl Component for Id {}
+
+/* Stays destroyed when its room is revisited. Kept apart from Id since wall
+tile indices would collide with object ids */
+pub struct Persistent {
+  pub id: i32,
+}
+impl Component for Persistent {}
+
+pub struct MovableBlock;
+impl Component for MovableBlock {}
+
+pub struct TutorialTrigger {
+  pub action: TutorialAction,
+  pub text: String,
+}
+impl Component for TutorialTrigger {}
+
+pub struct DialogueTrigger {
+  pub dialogue: String,
+}
+impl Component for DialogueTrigger {}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EncounterState {
+  Waiting,
+  Fighting,
+  Cleared,
+}
+
+/* Holds its linked activators shut from when a player walks into the arena until every enemy
+that was inside it then has been destroyed, then hands out its rewards */
+#[derive(Clone)]
+pub struct Encounter {
+  pub id: i32,
+  pub state: EncounterState,
+  pub gate_ids: Vec<i32>,
+  pub closed_activation: f32,
+  pub enemies: Vec<EntityHandle>,
+  pub rewards: Vec<LootDrop>,
+}
+impl Component for Encounter {}
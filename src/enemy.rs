@@ -1,21 +1,38 @@
-use std::{f32::consts::PI, rc::Rc};
+use std::{collections::HashMap, f32::consts::PI, rc::Rc, sync::Arc};
 
-use macroquad::{prelude::rand, rand::RandGenerator};
+use macroquad::rand::RandGenerator;
 use rapier2d::{na::Vector2, prelude::*};
+use rayon::prelude::*;
 
 use crate::{
+  collision::{CollisionLayer, groups_except},
   combat::{Projectile, distance_projection_physics},
-  ecs::{ComponentSet, Enemy, Entity, EntityHandle},
-  load_map::{
-    COLLISION_GROUP_ENEMY_PROJECTILE, COLLISION_GROUP_PLAYER, COLLISION_GROUP_WALL, EnemySpawn,
-    MapEnemyName,
+  controls::ControlsSystem,
+  data::{DataSystem, EnemyDefinitions, EnemyProjectileDefinition},
+  ecs::{
+    ComponentSet, Damageable, Enemy, Entity, EntityHandle, ExplodeOnCollision, Faction,
+    ProximityTrigger, Telegraph,
   },
-  physics::PhysicsSystem,
+  load_map::{EnemySpawn, MapEnemyName},
+  physics::{PhysicsSystem, nearest_entity},
   save::SaveData,
-  system::System,
+  spatial::SpatialHash,
+  system::{Requirement, System},
   units::{PhysicsVector, UnitConvert, UnitConvert2, vec_zero},
 };
 
+#[cfg(feature = "scripting")]
+use crate::script::{ScriptRegistry, ScriptState, ScriptView};
+
+#[cfg(not(feature = "scripting"))]
+type ScriptState = ();
+
+/* Stationary entity left behind at the enemy's position */
+pub struct Mine {
+  pub collider: Collider,
+  pub component_set: ComponentSet,
+}
+
 pub struct EnemyDecisionEnemySpawn {
   pub enemy_spawn: EnemySpawn,
   pub initial_force: Vector2<f32>,
@@ -27,14 +44,30 @@ pub struct EnemyDecision {
   pub movement_force: Vector2<f32>,
   pub enemy: Enemy,
   pub enemies_to_spawn: Vec<EnemyDecisionEnemySpawn>,
+  pub mines: Vec<Mine>,
+  /* Scripted enemies can start a dialogue, see DialogueSystem */
+  pub dialogue: Option<String>,
+  /* Set while an attack is coming up, until the frame it's made */
+  pub telegraph: Option<Telegraph>,
 }
 
 pub struct EnemySystem {
   pub decisions: Vec<EnemyDecision>,
+  #[cfg(feature = "scripting")]
+  pub scripts: Rc<ScriptRegistry>,
 }
 
 impl System for EnemySystem {
   type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<PhysicsSystem>(),
+      Requirement::of::<DataSystem>(),
+      Requirement::of::<ControlsSystem<_>>(),
+    ]
+  }
+
   fn start(
     _: &crate::system::ProcessContext<Self::Input>,
   ) -> std::rc::Rc<dyn System<Input = Self::Input>>
@@ -43,6 +76,8 @@ impl System for EnemySystem {
   {
     Rc::new(Self {
       decisions: Vec::new(),
+      #[cfg(feature = "scripting")]
+      scripts: Rc::new(ScriptRegistry::load()),
     })
   }
 
@@ -50,34 +85,192 @@ impl System for EnemySystem {
     &self,
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> std::rc::Rc<dyn System<Input = Self::Input>> {
-    let physics_system = ctx.get::<PhysicsSystem>().unwrap();
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+    let data_system = ctx.require::<DataSystem>().unwrap();
+    let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
+
+    /* Enemies think in whole frames, their forces land once per frame of game time */
+    if !controls_system.whole_frame {
+      return Rc::new(Self {
+        decisions: Vec::new(),
+        #[cfg(feature = "scripting")]
+        scripts: Rc::clone(&self.scripts),
+      });
+    }
 
-    let rng = rand::RandGenerator::new();
-    rng.srand(physics_system.frame_count as u64);
+    let decisions = decide(
+      &physics_system,
+      &data_system.data.enemies,
+      #[cfg(feature = "scripting")]
+      &self.scripts,
+    );
 
-    let player_translation =
-      physics_system.rigid_body_set[physics_system.player_handle].translation();
+    Rc::new(Self {
+      decisions,
+      #[cfg(feature = "scripting")]
+      scripts: Rc::clone(&self.scripts),
+    })
+  }
+}
 
-    let enemy_behavior =
-      enemy_behavior_generator(player_translation, &physics_system.rigid_body_set, &rng);
+/* What every enemy does this frame, also run without an EnemySystem by the benchmark */
+pub fn decide(
+  physics_system: &PhysicsSystem,
+  enemy_definitions: &EnemyDefinitions,
+  #[cfg(feature = "scripting")] scripts: &ScriptRegistry,
+) -> Vec<EnemyDecision> {
+  let player_translations = [
+    Some(physics_system.player_handle),
+    physics_system.player_two_handle,
+  ]
+  .into_iter()
+  .flatten()
+  .map(|handle| *physics_system.rigid_body_set[handle].translation())
+  .collect::<Vec<_>>();
 
-    let decisions = physics_system
+  /* Swarmers only look at each other, so bucket them once per frame */
+  let swarm = SpatialHash::new(
+    SWARMER_NEIGHBOR_RADIUS,
+    physics_system
       .entities
       .iter()
-      .filter_map(enemy_behavior)
-      .collect::<Vec<_>>();
+      .filter_map(
+        |(handle, entity)| match (handle, entity.components.get::<Enemy>()) {
+          (EntityHandle::RigidBody(rigid_body_handle), Some(enemy))
+            if matches!(enemy.as_ref(), Enemy::Swarmer(_)) =>
+          {
+            let rigid_body = &physics_system.rigid_body_set[*rigid_body_handle];
+            Some((
+              *rigid_body.translation(),
+              (*rigid_body_handle, *rigid_body.linvel()),
+            ))
+          }
+          _ => None,
+        },
+      ),
+  );
 
-    Rc::new(Self { decisions })
-  }
+  /* Every enemy decides from the same snapshot, so they're worked out on rayon's threads and
+  collected back in snapshot order */
+  let snapshot = physics_system.entities.iter().collect::<Vec<_>>();
+  let (rigid_body_set, collider_set) =
+    (&physics_system.rigid_body_set, &physics_system.collider_set);
+  let (entities, entity_hash) = (&physics_system.entities, &*physics_system.entity_hash);
+
+  /* Enemies go for the nearest thing they target, rival enemies as well as the players */
+  let targets = snapshot
+    .par_iter()
+    .filter_map(|(handle, entity)| {
+      entity.components.get::<Enemy>()?;
+      let faction = Faction::of(&entity.components);
+      let translation = *handle.translation(rigid_body_set, collider_set);
+
+      let target = nearest_entity(
+        entities,
+        entity_hash,
+        translation,
+        ENEMY_TARGET_RANGE,
+        |other| {
+          other.handle != **handle
+            && other.components.get::<Damageable>().is_some()
+            && faction.targets(Faction::of(&other.components))
+        },
+      )?;
+
+      Some((
+        **handle,
+        *target.handle.translation(rigid_body_set, collider_set),
+      ))
+    })
+    .collect::<HashMap<_, _>>();
+
+  let enemy_behavior = enemy_behavior_generator(
+    &player_translations,
+    &targets,
+    rigid_body_set,
+    physics_system.frame_count,
+    enemy_definitions,
+    &swarm,
+    #[cfg(feature = "scripting")]
+    scripts,
+  );
+
+  /* The room's cycle can calm enemies down or stir them up */
+  let aggression = physics_system
+    .cycle_phase()
+    .map_or(1.0, |phase| phase.aggression);
+
+  /* Hacked enemies with nothing to fight keep up with the player without firing at them */
+  let holds_fire = |decision: &EnemyDecision| {
+    let handle = EntityHandle::RigidBody(decision.handle);
+    !targets.contains_key(&handle)
+      && entities
+        .get(&handle)
+        .is_some_and(|entity| Faction::of(&entity.components) == Faction::Player)
+  };
+
+  snapshot
+    .par_iter()
+    .filter_map(|&entry| enemy_behavior(entry))
+    .map(|decision| {
+      if holds_fire(&decision) {
+        EnemyDecision {
+          projectiles: vec![],
+          enemies_to_spawn: vec![],
+          mines: vec![],
+          telegraph: None,
+          ..decision
+        }
+      } else {
+        decision
+      }
+    })
+    .map(|decision| {
+      if aggression > 0.0 {
+        EnemyDecision {
+          movement_force: decision.movement_force * aggression,
+          ..decision
+        }
+      } else {
+        EnemyDecision {
+          movement_force: vec_zero(),
+          projectiles: vec![],
+          enemies_to_spawn: vec![],
+          mines: vec![],
+          telegraph: None,
+          ..decision
+        }
+      }
+    })
+    .collect::<Vec<_>>()
 }
 
-fn enemy_behavior_generator(
-  player_translation: &Vector2<f32>,
-  physics_rigid_bodies: &RigidBodySet,
-  rng: &RandGenerator,
-) -> impl Fn((&EntityHandle, &Rc<Entity>)) -> Option<EnemyDecision> {
-  |(&handle, entity)| {
+fn enemy_behavior_generator<'a>(
+  player_translations: &'a [Vector2<f32>],
+  targets: &'a HashMap<EntityHandle, Vector2<f32>>,
+  physics_rigid_bodies: &'a RigidBodySet,
+  frame_count: i64,
+  enemy_definitions: &'a EnemyDefinitions,
+  swarm: &'a SwarmHash,
+  #[cfg(feature = "scripting")] scripts: &'a ScriptRegistry,
+) -> impl Fn((&EntityHandle, &Arc<Entity>)) -> Option<EnemyDecision> + 'a {
+  move |(&handle, entity)| {
     if let EntityHandle::RigidBody(rigid_body_handle) = handle {
+      let rng = &enemy_rng(frame_count, rigid_body_handle);
+
+      /* Behaviors all chase the nearest player, unless something else they target is nearer */
+      let translation = physics_rigid_bodies[rigid_body_handle].translation();
+      let player_translation = targets.get(&handle).unwrap_or_else(|| {
+        player_translations
+          .iter()
+          .min_by(|a, b| {
+            (*a - translation)
+              .magnitude()
+              .total_cmp(&(*b - translation).magnitude())
+          })
+          .unwrap()
+      });
+
       entity
         .components
         .get::<Enemy>()
@@ -87,14 +280,37 @@ fn enemy_behavior_generator(
             player_translation,
             physics_rigid_bodies,
             rng,
+            enemy_definitions,
           ),
-          Enemy::Defender(defender) => defender.behavior(rigid_body_handle),
+          Enemy::Defender(defender) => defender.behavior(rigid_body_handle, enemy_definitions),
           Enemy::Seeker(seeker) => {
             seeker.behavior(rigid_body_handle, player_translation, physics_rigid_bodies)
           }
           Enemy::SeekerGenerator(seeker_generator) => {
             seeker_generator.behavior(rigid_body_handle, player_translation, physics_rigid_bodies)
           }
+          Enemy::Swarmer(swarmer) => swarmer.behavior(
+            rigid_body_handle,
+            player_translation,
+            physics_rigid_bodies,
+            swarm,
+          ),
+          Enemy::Minelayer(minelayer) => minelayer.behavior(
+            rigid_body_handle,
+            player_translation,
+            physics_rigid_bodies,
+            enemy_definitions,
+          ),
+          #[cfg(feature = "scripting")]
+          Enemy::Scripted(scripted) => scripted.behavior(
+            rigid_body_handle,
+            player_translation,
+            physics_rigid_bodies,
+            rng,
+            scripts,
+          ),
+          #[cfg(not(feature = "scripting"))]
+          Enemy::Scripted(scripted) => scripted.behavior(rigid_body_handle),
         })
     } else {
       None
@@ -102,11 +318,38 @@ fn enemy_behavior_generator(
   }
 }
 
-const ENEMY_GROUPS: InteractionGroups = InteractionGroups {
-  memberships: COLLISION_GROUP_ENEMY_PROJECTILE,
-  filter: COLLISION_GROUP_PLAYER.union(COLLISION_GROUP_WALL),
-  test_mode: InteractionTestMode::And,
-};
+/* Each enemy rolls from its own generator, seeded by the frame and its handle, so what it rolls
+doesn't depend on which thread decided for it or in what order */
+fn enemy_rng(frame_count: i64, handle: RigidBodyHandle) -> RandGenerator {
+  let (index, generation) = handle.into_raw_parts();
+  let rng = RandGenerator::new();
+  rng.srand(
+    (frame_count as u64).wrapping_mul(0x9e3779b97f4a7c15)
+      ^ ((generation as u64) << 32 | index as u64),
+  );
+  rng
+}
+
+/* Shown only once the attack is within the definition's telegraph window */
+fn telegraph(
+  frames_left: i32,
+  projectile_definition: &EnemyProjectileDefinition,
+) -> Option<Telegraph> {
+  (frames_left > 0 && frames_left <= projectile_definition.telegraph_frames).then_some(Telegraph {
+    frames_left,
+    frames: projectile_definition.telegraph_frames,
+  })
+}
+
+const ENEMY_TARGET_RANGE: f32 = 20.0;
+
+/* Enemy fire only lets enemies through in rooms with friendly fire, which widens it on spawn */
+fn enemy_projectile_groups() -> InteractionGroups {
+  groups_except(
+    CollisionLayer::EnemyProjectile,
+    CollisionLayer::Enemy.group(),
+  )
+}
 
 #[derive(Clone)]
 pub enum EnemyGoblinState {
@@ -133,8 +376,6 @@ const GOBLIN_STATE_ACCELERATING_INITIAL_FRAMES: i32 = 10;
 const GOBLIN_STATE_DECELERATING_INITIAL_FRAMES: i32 = 10;
 
 const GOBLIN_MOVE_FORCE: f32 = 0.2;
-const GOBLIN_PROJECTILE_SPEED: f32 = 1.0;
-const GOBLIN_PROJECTILE_DAMAGE: f32 = 5.0;
 
 impl EnemyGoblin {
   pub fn behavior(
@@ -143,7 +384,9 @@ impl EnemyGoblin {
     player_translation: &Vector2<f32>,
     rigid_body_set: &RigidBodySet,
     rng: &RandGenerator,
+    enemy_definitions: &EnemyDefinitions,
   ) -> EnemyDecision {
+    let projectile_definition = &enemy_definitions.goblin.projectile;
     match self.state {
       EnemyGoblinState::Shooting(frames_left) => {
         if frames_left > 0 {
@@ -154,6 +397,9 @@ impl EnemyGoblin {
             }),
             movement_force: vec_zero(),
             enemies_to_spawn: vec![],
+            mines: vec![],
+            dialogue: None,
+            telegraph: None,
             projectiles: vec![],
           }
         } else {
@@ -167,6 +413,9 @@ impl EnemyGoblin {
             }),
             movement_force: vec_zero(),
             enemies_to_spawn: vec![],
+            mines: vec![],
+            dialogue: None,
+            telegraph: None,
             projectiles: vec![],
           }
         }
@@ -180,6 +429,9 @@ impl EnemyGoblin {
             }),
             movement_force: vec_zero(),
             enemies_to_spawn: vec![],
+            mines: vec![],
+            dialogue: None,
+            telegraph: None,
             projectiles: vec![],
           }
         } else {
@@ -190,6 +442,9 @@ impl EnemyGoblin {
             }),
             movement_force: vec_zero(),
             enemies_to_spawn: vec![],
+            mines: vec![],
+            dialogue: None,
+            telegraph: None,
             projectiles: vec![],
           }
         }
@@ -203,6 +458,9 @@ impl EnemyGoblin {
             }),
             movement_force: direction.normalize() * GOBLIN_MOVE_FORCE,
             enemies_to_spawn: vec![],
+            mines: vec![],
+            dialogue: None,
+            telegraph: None,
             projectiles: vec![],
           }
         } else {
@@ -213,6 +471,9 @@ impl EnemyGoblin {
             }),
             movement_force: vec_zero(),
             enemies_to_spawn: vec![],
+            mines: vec![],
+            dialogue: None,
+            telegraph: None,
             projectiles: vec![],
           }
         }
@@ -228,6 +489,10 @@ impl EnemyGoblin {
             }),
             movement_force: -linvel.normalize() * GOBLIN_MOVE_FORCE,
             enemies_to_spawn: vec![],
+            mines: vec![],
+            dialogue: None,
+            /* The shot comes as soon as it has stopped */
+            telegraph: telegraph(frames_left, projectile_definition),
             projectiles: vec![],
           }
         } else {
@@ -238,18 +503,23 @@ impl EnemyGoblin {
             }),
             movement_force: vec_zero(),
             enemies_to_spawn: vec![],
+            mines: vec![],
+            dialogue: None,
+            telegraph: None,
             projectiles: vec![Projectile {
               collider: ColliderBuilder::ball(0.2)
-                .collision_groups(ENEMY_GROUPS)
+                .collision_groups(enemy_projectile_groups())
                 .build(),
-              damage: GOBLIN_PROJECTILE_DAMAGE,
+              damage: projectile_definition.damage,
+              on_hit_status: projectile_definition.on_hit_status,
               initial_impulse: PhysicsVector::from_vec(
                 (player_translation - rigid_body_set[handle].translation()).normalize()
-                  * GOBLIN_PROJECTILE_SPEED,
+                  * projectile_definition.speed,
               ),
               offset: PhysicsVector::zero(),
               force_mod: 0.0,
               component_set: ComponentSet::new(),
+              ccd: false,
             }],
           }
         }
@@ -263,21 +533,33 @@ pub struct EnemyDefender {
   pub cooldown: i32,
 }
 
+const DEFENDER_FIRE_COOLDOWN: i32 = 50;
+
 impl EnemyDefender {
-  pub fn behavior(&self, handle: RigidBodyHandle) -> EnemyDecision {
-    let should_fire_projectiles = self.cooldown % 50 == 0;
+  pub fn behavior(
+    &self,
+    handle: RigidBodyHandle,
+    enemy_definitions: &EnemyDefinitions,
+  ) -> EnemyDecision {
+    let projectile_definition = &enemy_definitions.defender.projectile;
+    let should_fire_projectiles = self.cooldown % DEFENDER_FIRE_COOLDOWN == 0;
     EnemyDecision {
       handle,
       movement_force: vec_zero(),
       projectiles: if should_fire_projectiles {
         let projectile = |offset: f32| Projectile {
           collider: ColliderBuilder::ball(0.2)
-            .collision_groups(ENEMY_GROUPS)
+            .collision_groups(enemy_projectile_groups())
             .build(),
-          damage: 5.0,
-          initial_impulse: distance_projection_physics(offset + self.cooldown as f32 / 120.0, 0.7),
+          damage: projectile_definition.damage,
+          on_hit_status: projectile_definition.on_hit_status,
+          initial_impulse: distance_projection_physics(
+            offset + self.cooldown as f32 / 120.0,
+            projectile_definition.speed,
+          ),
           offset: PhysicsVector::zero(),
           component_set: ComponentSet::new(),
+          ccd: false,
           force_mod: 0.0,
         };
         Vec::from([
@@ -293,6 +575,12 @@ impl EnemyDefender {
         cooldown: self.cooldown - 1,
       }),
       enemies_to_spawn: vec![],
+      mines: vec![],
+      dialogue: None,
+      telegraph: telegraph(
+        self.cooldown.rem_euclid(DEFENDER_FIRE_COOLDOWN),
+        projectile_definition,
+      ),
     }
   }
 }
@@ -323,13 +611,16 @@ impl EnemySeeker {
         vec_zero()
       } else {
         direction_to_player.normalize() * SEEKER_SPEED
-      }) - velocity_away_from_player.normalize() * SEEKER_SPEED * 0.3
+      }) - normalize_or_zero(velocity_away_from_player) * SEEKER_SPEED * 0.3
     };
     EnemyDecision {
       movement_force,
       handle,
       projectiles: vec![],
       enemies_to_spawn: vec![],
+      mines: vec![],
+      dialogue: None,
+      telegraph: None,
       enemy: Enemy::Seeker(Self),
     }
   }
@@ -369,6 +660,264 @@ impl EnemySeekerGenerator {
       } else {
         vec![]
       },
+      mines: vec![],
+      dialogue: None,
+      telegraph: None,
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct EnemySwarmer;
+
+type SwarmHash = SpatialHash<(RigidBodyHandle, Vector2<f32>)>;
+
+const SWARMER_NEIGHBOR_RADIUS: f32 = 3.0;
+const SWARMER_SPEED_CAP: f32 = 4.0;
+const SWARMER_MAX_FORCE: f32 = 0.25;
+const SWARMER_SEEK_WEIGHT: f32 = 0.15;
+const SWARMER_SEPARATION_WEIGHT: f32 = 0.3;
+const SWARMER_ALIGNMENT_WEIGHT: f32 = 0.05;
+const SWARMER_COHESION_WEIGHT: f32 = 0.05;
+
+fn normalize_or_zero(vector: Vector2<f32>) -> Vector2<f32> {
+  if vector.magnitude() > 0.0 {
+    vector.normalize()
+  } else {
+    vec_zero()
+  }
+}
+
+impl EnemySwarmer {
+  pub fn behavior(
+    &self,
+    handle: RigidBodyHandle,
+    player_translation: &Vector2<f32>,
+    physics_rigid_bodies: &RigidBodySet,
+    swarm: &SwarmHash,
+  ) -> EnemyDecision {
+    let self_rigid_body = &physics_rigid_bodies[handle];
+    let translation = *self_rigid_body.translation();
+    let linvel = *self_rigid_body.linvel();
+
+    let neighbors = swarm
+      .within(translation, SWARMER_NEIGHBOR_RADIUS)
+      .filter(|(_, (neighbor_handle, _))| *neighbor_handle != handle)
+      .collect::<Vec<_>>();
+
+    let flocking_force = if neighbors.is_empty() {
+      vec_zero()
+    } else {
+      let neighbor_count = neighbors.len() as f32;
+
+      /* Push away from neighbors, harder the closer they are */
+      let separation = neighbors
+        .iter()
+        .map(|(position, _)| {
+          let away = translation - position;
+          let distance = away.magnitude();
+          if distance > 0.0 {
+            away / (distance * distance)
+          } else {
+            vec_zero()
+          }
+        })
+        .sum::<Vector2<f32>>();
+
+      let alignment = neighbors
+        .iter()
+        .map(|(_, (_, velocity))| *velocity)
+        .sum::<Vector2<f32>>()
+        / neighbor_count
+        - linvel;
+
+      let cohesion = neighbors
+        .iter()
+        .map(|(position, _)| *position)
+        .sum::<Vector2<f32>>()
+        / neighbor_count
+        - translation;
+
+      normalize_or_zero(separation) * SWARMER_SEPARATION_WEIGHT
+        + normalize_or_zero(alignment) * SWARMER_ALIGNMENT_WEIGHT
+        + normalize_or_zero(cohesion) * SWARMER_COHESION_WEIGHT
+    };
+
+    let seek_force = if linvel.magnitude() >= SWARMER_SPEED_CAP {
+      vec_zero()
+    } else {
+      normalize_or_zero(player_translation - translation) * SWARMER_SEEK_WEIGHT
+    };
+
+    let movement_force = flocking_force + seek_force;
+
+    EnemyDecision {
+      movement_force: if movement_force.magnitude() > SWARMER_MAX_FORCE {
+        movement_force.normalize() * SWARMER_MAX_FORCE
+      } else {
+        movement_force
+      },
+      handle,
+      projectiles: vec![],
+      enemies_to_spawn: vec![],
+      mines: vec![],
+      dialogue: None,
+      telegraph: None,
+      enemy: Enemy::Swarmer(Self),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct EnemyMinelayer {
+  pub cooldown: i32,
+}
+
+const MINELAYER_MOVE_FORCE: f32 = 0.1;
+const MINELAYER_SPEED_CAP: f32 = 2.0;
+const MINELAYER_DROP_COOLDOWN: i32 = 150;
+const MINE_ARM_FRAMES: i32 = 60;
+const MINE_TRIGGER_RADIUS: f32 = 2.0;
+const MINE_EXPLOSION_RADIUS: f32 = 1.5;
+const MINE_EXPLOSION_STRENGTH: f32 = -0.5;
+
+impl EnemyMinelayer {
+  pub fn behavior(
+    &self,
+    handle: RigidBodyHandle,
+    player_translation: &Vector2<f32>,
+    physics_rigid_bodies: &RigidBodySet,
+    enemy_definitions: &EnemyDefinitions,
+  ) -> EnemyDecision {
+    let self_rigid_body = &physics_rigid_bodies[handle];
+    let direction_to_player = player_translation - self_rigid_body.translation();
+
+    let movement_force = if self_rigid_body.linvel().magnitude() >= MINELAYER_SPEED_CAP
+      || direction_to_player.magnitude() == 0.0
+    {
+      vec_zero()
+    } else {
+      direction_to_player.normalize() * MINELAYER_MOVE_FORCE
+    };
+
+    let should_drop_mine = self.cooldown % MINELAYER_DROP_COOLDOWN == 0;
+
+    EnemyDecision {
+      movement_force,
+      handle,
+      projectiles: vec![],
+      enemies_to_spawn: vec![],
+      mines: if should_drop_mine {
+        vec![Mine {
+          collider: ColliderBuilder::ball(0.25)
+            .collision_groups(enemy_projectile_groups())
+            .build(),
+          component_set: ComponentSet::new()
+            .insert(ExplodeOnCollision {
+              radius: MINE_EXPLOSION_RADIUS,
+              strength: MINE_EXPLOSION_STRENGTH,
+              damage: enemy_definitions.minelayer.projectile.damage,
+              interaction_groups: enemy_projectile_groups(),
+              force_groups: enemy_projectile_groups(),
+              self_blast: false,
+            })
+            .insert(ProximityTrigger {
+              arm_frames: MINE_ARM_FRAMES,
+              radius: MINE_TRIGGER_RADIUS,
+            }),
+        }]
+      } else {
+        vec![]
+      },
+      dialogue: None,
+      telegraph: None,
+      enemy: Enemy::Minelayer(Self {
+        cooldown: self.cooldown - 1,
+      }),
+    }
+  }
+}
+
+/* Behavior for enemies named in map data but driven by a script, see script.rs */
+#[derive(Clone)]
+pub struct EnemyScripted {
+  pub name: String,
+  #[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+  pub state: ScriptState,
+}
+
+#[cfg(feature = "scripting")]
+impl EnemyScripted {
+  pub fn behavior(
+    &self,
+    handle: RigidBodyHandle,
+    player_translation: &Vector2<f32>,
+    physics_rigid_bodies: &RigidBodySet,
+    rng: &RandGenerator,
+    scripts: &ScriptRegistry,
+  ) -> EnemyDecision {
+    let self_rigid_body = &physics_rigid_bodies[handle];
+    let decision = scripts.behavior(
+      &self.name,
+      &self.state,
+      ScriptView {
+        translation: *self_rigid_body.translation(),
+        linvel: *self_rigid_body.linvel(),
+        player_translation: *player_translation,
+        seed: rng.rand() as u64,
+      },
+    );
+
+    EnemyDecision {
+      handle,
+      movement_force: decision.movement_force,
+      projectiles: decision
+        .projectiles
+        .iter()
+        .map(|projectile| Projectile {
+          collider: ColliderBuilder::ball(projectile.radius)
+            .collision_groups(enemy_projectile_groups())
+            .build(),
+          damage: projectile.damage,
+          on_hit_status: None,
+          initial_impulse: PhysicsVector::from_vec(projectile.direction * projectile.speed),
+          offset: PhysicsVector::zero(),
+          force_mod: 0.0,
+          component_set: ComponentSet::new(),
+          ccd: false,
+        })
+        .collect(),
+      enemies_to_spawn: decision
+        .spawns
+        .into_iter()
+        .map(|spawn| EnemyDecisionEnemySpawn {
+          initial_force: spawn.initial_force,
+          enemy_spawn: EnemySpawn::new(&spawn.name, *self_rigid_body.translation()),
+        })
+        .collect(),
+      mines: vec![],
+      dialogue: decision.dialogue,
+      telegraph: None,
+      enemy: Enemy::Scripted(Self {
+        name: self.name.clone(),
+        state: decision.state,
+      }),
+    }
+  }
+}
+
+#[cfg(not(feature = "scripting"))]
+impl EnemyScripted {
+  pub fn behavior(&self, handle: RigidBodyHandle) -> EnemyDecision {
+    EnemyDecision {
+      handle,
+      movement_force: vec_zero(),
+      projectiles: vec![],
+      enemies_to_spawn: vec![],
+      mines: vec![],
+      dialogue: None,
+      telegraph: None,
+      enemy: Enemy::Scripted(self.clone()),
     }
   }
 }
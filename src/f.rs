@@ -1,6 +1,7 @@
 pub trait Monad<T> {
   type SelfType<A>: Monad<A>;
 
+  #[allow(dead_code)]
   fn bind<B, F>(self, transform: F) -> Self::SelfType<B>
   where
     F: Fn(&T) -> B;
@@ -13,10 +14,7 @@ impl<T> Monad<T> for Option<T> {
   where
     F: Fn(&T) -> B,
   {
-    match self {
-      Some(some) => Some(transform(&some)),
-      None => None,
-    }
+    self.map(|some| transform(&some))
   }
 }
 
@@ -43,9 +41,6 @@ where
 
 impl<T, E> MonadTranslate<T, Option<T>> for Result<T, E> {
   fn translate(self) -> Option<T> {
-    return match self {
-      Ok(ok) => Some(ok),
-      Err(_) => None,
-    };
+    return self.ok();
   }
 }
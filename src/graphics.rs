@@ -1,23 +1,52 @@
-use std::{marker::PhantomData, rc::Rc, thread::sleep, time::Duration};
+use std::{
+  collections::HashMap,
+  f32::consts::PI,
+  fs,
+  marker::PhantomData,
+  rc::Rc,
+  thread::sleep,
+  time::{self, Duration},
+};
 
+use chrono::{DateTime, Utc};
 use macroquad::prelude::*;
 use rapier2d::prelude::*;
 
 use crate::{
+  ability::{AbilitySystem, HACK_CHANNEL_FRAMES},
   camera::CameraSystem,
+  challenge::{ChallengeSystem, Medal},
   combat::{
-    CombatSystem, Direction, EQUIP_SLOTS_WIDTH, WeaponModule, WeaponModuleKind,
-    distance_projection_screen, get_reticle_pos, get_slot_positions, weapon_module_from_kind,
+    CombatSystem, Direction, EQUIP_SLOTS_HEIGHT, EQUIP_SLOTS_WIDTH, ModuleInstance, Weapon,
+    WeaponModule, WeaponModuleKind, distance_projection_screen, get_reticle_pos,
+    get_slot_positions, weapon_module_from_kind,
   },
   controls::ControlsSystem,
-  ecs::{Damageable, EntityHandle},
-  graphics_utils::{draw_collider, draw_label},
-  load_map::{MapSystem, physics_scalar_to_map},
+  daily::DailySystem,
+  data::DialogueStep,
+  dialogue::{ActiveDialogue, DIALOGUE_SKIP_FRAMES, DialogueSystem},
+  ecs::{
+    Boss, Damageable, DespawnTimer, DestroyAfterFrames, Dying, Elite, EliteAffix, Enemy, Entity,
+    EntityHandle, FluidVolume, GiveAbilityOnCollision, GivesEngineUpgradeOnCollision,
+    GivesItemOnCollision, Hacked, Hazard, HazardKind, Impact, ImpactKind, SpawnProtection,
+    Telegraph,
+  },
+  graphics_utils::{draw_collider, draw_joint, draw_label, joint_state_lines},
+  load_map::{ChallengeCondition, MapAbilityType, MapSystem, physics_scalar_to_map},
+  locale::{tr, tr_args, tr_count},
+  log,
   menu::{GameMenu, INVENTORY_WRAP_WIDTH, MainMenu, MenuSystem},
-  physics::PhysicsSystem,
-  save::SaveSystem,
-  system::System,
-  units::{PhysicsVector, ScreenVector, UnitConvert, UnitConvert2},
+  palette::{mix, palette},
+  physics::{PhysicsSystem, ROOM_FADE_FRAMES},
+  save::{Difficulty, SaveData, SavePreview, SaveSystem},
+  score::ScoreSystem,
+  settings::SettingsSystem,
+  speedrun::{SpeedrunSystem, format_run_time},
+  storage,
+  system::{Requirement, System},
+  text::{Align, draw_ui_text, draw_ui_text_aligned, line_height, wrap_text},
+  tutorial::TutorialSystem,
+  units::{PhysicsScalar, PhysicsVector, ScreenVector, UnitConvert, UnitConvert2},
 };
 
 const TARGET_FPS: f32 = 60.0;
@@ -28,32 +57,35 @@ const RETICLE_SIZE: f32 = 3.0;
 /* DEBUG OPTIONS */
 const SHOW_COLLIDERS: bool = true;
 const SHOW_SLOTS: bool = true;
+const SHOW_PROFILER: bool = cfg!(debug_assertions);
+/* Joints are drawn, and those of the body under the cursor have their state printed */
+const SHOW_JOINTS: bool = cfg!(debug_assertions);
 
-/* Colors */
-pub const COLOR_1: Color = Color {
-  r: 214.0 / 255.0,
-  g: 246.0 / 255.0,
-  b: 214.0 / 255.0,
-  a: 1.0,
-};
-pub const COLOR_2: Color = Color {
-  r: 107.0 / 255.0,
-  g: 165.0 / 255.0,
-  b: 107.0 / 255.0,
-  a: 1.0,
-};
-pub const COLOR_3: Color = Color {
-  r: 29.0 / 255.0,
-  g: 88.0 / 255.0,
-  b: 73.0 / 255.0,
-  a: 1.0,
-};
-pub const COLOR_4: Color = Color {
-  r: 0.0 / 255.0,
-  g: 18.0 / 255.0,
-  b: 25.0 / 255.0,
-  a: 1.0,
-};
+const LOCK_ON_MARKER_RADIUS: f32 = 14.0;
+
+/* Every this many frames of a predicted path gets a dot */
+const TRAJECTORY_DOT_SPACING: usize = 4;
+const TRAJECTORY_DOT_SIZE: f32 = 1.5;
+const AIM_LINE_THICKNESS: f32 = 1.0;
+/* Same as the player's ball */
+const GHOST_RADIUS: f32 = 0.25;
+
+const BOOST_PIP_RADIUS: f32 = 6.0;
+const BOOST_PIP_SPACING: f32 = 18.0;
+
+const HEAT_BAR_WIDTH: f32 = 80.0;
+const HEAT_BAR_HEIGHT: f32 = 6.0;
+const HEAT_BAR_SPACING: f32 = 12.0;
+
+const POWER_UP_LINE_HEIGHT: f32 = 24.0;
+/* Power-up timers are counted in frames but shown in seconds */
+const FRAMES_PER_SECOND: f32 = 60.0;
+
+const PROFILER_FONT_SIZE: f32 = 20.0;
+const PROFILER_LINE_HEIGHT: f32 = 20.0;
+
+/* How far from the cursor a body can be to have its joints inspected */
+const JOINT_INSPECT_RADIUS: f32 = 0.5;
 
 pub struct GraphicsSystem<Input>(PhantomData<Input>);
 
@@ -63,6 +95,26 @@ const MINI_MAP_TILE_HEIGHT: f32 = 2.0;
 impl<Input: Clone + Default + 'static> System for GraphicsSystem<Input> {
   type Input = Input;
 
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<MenuSystem<_>>(),
+      Requirement::of::<SaveSystem<_>>(),
+      Requirement::when::<SaveData, CameraSystem>(),
+      Requirement::when::<SaveData, CombatSystem>(),
+      Requirement::when::<SaveData, PhysicsSystem>(),
+      Requirement::when::<SaveData, MapSystem>(),
+      Requirement::when::<SaveData, ControlsSystem<_>>(),
+      Requirement::when::<SaveData, SettingsSystem>(),
+      Requirement::when::<SaveData, SpeedrunSystem>(),
+      Requirement::when::<SaveData, ScoreSystem>(),
+      Requirement::when::<SaveData, ChallengeSystem>(),
+      Requirement::when::<SaveData, DailySystem>(),
+      Requirement::when::<SaveData, TutorialSystem>(),
+      Requirement::when::<SaveData, DialogueSystem>(),
+      Requirement::when::<SaveData, AbilitySystem>(),
+    ]
+  }
+
   fn start(_: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
   where
     Self: Sized,
@@ -74,37 +126,118 @@ impl<Input: Clone + Default + 'static> System for GraphicsSystem<Input> {
     &self,
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> Rc<dyn System<Input = Self::Input>> {
+    let menu_system = ctx.require::<MenuSystem<_>>().unwrap();
+    let photo_mode = menu_system.in_photo_mode();
+
+    /* Before anything is drawn, so what's captured is the last frame */
+    if menu_system.take_screenshot {
+      save_screenshot();
+    }
+
     /* Background */
-    clear_background(COLOR_1);
+    clear_background(palette().color_1);
+
+    if !photo_mode {
+      draw_fps();
+    }
 
-    draw_fps();
+    /* Debug - per system timings from the last frame */
+    if SHOW_PROFILER && !photo_mode {
+      let profile_lines = ctx
+        .profile
+        .system_timings
+        .iter()
+        .map(|(name, elapsed_ms)| format!("{}: {:.2}ms", name, elapsed_ms))
+        .chain(
+          cfg!(feature = "count-allocations")
+            .then(|| format!("allocations: {}", ctx.profile.allocations)),
+        )
+        .chain(ctx.downcast::<SaveData>().into_iter().flat_map(|ctx| {
+          let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+          [
+            format!("entities: {}", physics_system.entities.size()),
+            format!("rigid bodies: {}", physics_system.rigid_body_set.len()),
+          ]
+        }));
+
+      profile_lines.enumerate().for_each(|(index, line)| {
+        draw_text(
+          &line,
+          screen_width() * 0.8,
+          PROFILER_LINE_HEIGHT * (index + 1) as f32,
+          PROFILER_FONT_SIZE,
+          palette().color_4,
+        );
+      });
+    }
 
     if let Some(ctx) = ctx.downcast::<_>() {
-      let camera_system = ctx.get::<CameraSystem>().unwrap();
-      let combat_system = ctx.get::<CombatSystem>().unwrap();
-      let physics_system = ctx.get::<PhysicsSystem>().unwrap();
-      let map_system = ctx.get::<MapSystem>().unwrap();
-      let controls_system = ctx.get::<ControlsSystem<_>>().unwrap();
+      let camera_system = ctx.require::<CameraSystem>().unwrap();
+      let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+
+      /* Photo mode zoom scales the world about the middle of the screen */
+      if camera_system.zoom != 1.0 {
+        set_camera(&Camera2D::from_display_rect(Rect::new(
+          screen_width() * (1.0 - 1.0 / camera_system.zoom) / 2.0,
+          screen_height() * (1.0 - 1.0 / camera_system.zoom) / 2.0,
+          screen_width() / camera_system.zoom,
+          screen_height() / camera_system.zoom,
+        )));
+      }
+
+      let player_handles = [
+        Some(physics_system.player_handle),
+        physics_system.player_two_handle,
+      ]
+      .into_iter()
+      .flatten()
+      .collect::<Vec<_>>();
+
+      /* Drops about to despawn blink out every few frames */
+      let blinking_handles = physics_system
+        .entities
+        .iter()
+        .filter(|(_, entity)| {
+          if let Some(despawn_timer) = entity.components.get::<DespawnTimer>()
+            && let Some(destroy_after_frames) = entity.components.get::<DestroyAfterFrames>()
+          {
+            despawn_timer.hidden(destroy_after_frames.frames)
+          } else {
+            false
+          }
+        })
+        .map(|(handle, _)| *handle)
+        .collect::<Vec<_>>();
 
       /* Debug */
       if SHOW_COLLIDERS {
         physics_system
           .collider_set
           .iter()
+          .filter(|(collider_handle, collider)| {
+            !collider
+              .parent()
+              .is_some_and(|parent| player_handles.contains(&parent))
+              && !blinking_handles.contains(&EntityHandle::Collider(*collider_handle))
+          })
           .for_each(|(_, collider)| {
             draw_collider(collider, camera_system.translation, None, None);
           });
 
         /* Draw entity labels */
         physics_system.entities.iter().for_each(|(handle, entity)| {
+          if blinking_handles.contains(handle) {
+            return;
+          }
+
           if let EntityHandle::RigidBody(rigid_body_handle) = handle {
             draw_label(
               PhysicsVector::from_vec(
                 *physics_system.rigid_body_set[*rigid_body_handle].translation(),
               ),
               camera_system.translation,
-              entity.label.clone(),
-              Some(COLOR_4),
+              entity.debug_label(),
+              Some(palette().color_4),
             );
           }
 
@@ -117,25 +250,342 @@ impl<Input: Clone + Default + 'static> System for GraphicsSystem<Input> {
                   *physics_system.collider_set[collider_handle].translation(),
                 ),
                 camera_system.translation,
-                entity.label.clone(),
-                Some(COLOR_2),
+                entity.debug_label(),
+                Some(palette().color_2),
               );
             })
         });
       }
 
+      if SHOW_JOINTS {
+        physics_system
+          .impulse_joint_set
+          .iter()
+          .for_each(|(_, joint)| {
+            draw_joint(
+              joint,
+              &physics_system.rigid_body_set,
+              camera_system.translation,
+            );
+          });
+
+        /* Inspect whichever jointed body is under the cursor */
+        let (mouse_x, mouse_y) = mouse_position();
+        let cursor = ScreenVector::from_vec(vector![mouse_x, mouse_y])
+          .into_pos(camera_system.translation)
+          .into_vec();
+
+        let inspected = physics_system.nearest_entity(cursor, JOINT_INSPECT_RADIUS, |entity| {
+          matches!(entity.handle, EntityHandle::RigidBody(body) if physics_system
+            .impulse_joint_set
+            .attached_joints(body)
+            .next()
+            .is_some())
+        });
+
+        if let Some(EntityHandle::RigidBody(body)) = inspected.map(|entity| entity.handle) {
+          physics_system
+            .impulse_joint_set
+            .attached_joints(body)
+            .flat_map(|(_, _, joint_handle, joint)| {
+              joint_state_lines(joint_handle, joint, &physics_system.rigid_body_set)
+            })
+            .enumerate()
+            .for_each(|(index, line)| {
+              draw_text(
+                &line,
+                mouse_x + PROFILER_LINE_HEIGHT,
+                mouse_y + PROFILER_LINE_HEIGHT * (index + 1) as f32,
+                PROFILER_FONT_SIZE,
+                palette().color_4,
+              );
+            });
+        }
+      }
+
+      /* Draw hazard pools, rippling so they stand out from walls */
+      physics_system.entities.iter().for_each(|(handle, entity)| {
+        if let Some(hazard) = entity.components.get::<Hazard>()
+          && let EntityHandle::Collider(collider_handle) = handle
+        {
+          let collider = &physics_system.collider_set[*collider_handle];
+          let ripple = ((get_time() as f32 * HAZARD_RIPPLE_SPEED + collider.translation().x).sin()
+            + 1.0)
+            / 2.0;
+
+          draw_collider(
+            collider,
+            camera_system.translation,
+            None,
+            Some(hazard_color(hazard.kind, ripple)),
+          );
+        }
+      });
+
+      /* Draw fluid volumes, thicker fluids more opaque */
+      physics_system.entities.iter().for_each(|(handle, entity)| {
+        if let Some(fluid_volume) = entity.components.get::<FluidVolume>()
+          && let EntityHandle::Collider(collider_handle) = handle
+        {
+          draw_collider(
+            &physics_system.collider_set[*collider_handle],
+            camera_system.translation,
+            None,
+            Some(
+              palette()
+                .fluid
+                .with_alpha(0.15 + 0.35 * fluid_volume.drag.clamp(0.0, 1.0)),
+            ),
+          );
+        }
+      });
+
+      /* Draw projectile impacts */
+      physics_system.entities.iter().for_each(|(handle, entity)| {
+        if let Some(impact) = entity.components.get::<Impact>()
+          && let EntityHandle::RigidBody(rigid_body_handle) = handle
+        {
+          let remaining = entity
+            .components
+            .get::<DestroyAfterFrames>()
+            .map(|destroy_after_frames| destroy_after_frames.frames as f32 / impact.lifetime as f32)
+            .unwrap_or(0.0);
+
+          draw_impact(
+            PhysicsVector::from_vec(
+              *physics_system.rigid_body_set[*rigid_body_handle].translation(),
+            )
+            .into_pos(camera_system.translation),
+            &impact,
+            remaining,
+          );
+        }
+      });
+
+      /* Draw corpses bursting apart and fading as their death plays out */
+      physics_system.entities.iter().for_each(|(handle, entity)| {
+        if let Some(dying) = entity.components.get::<Dying>() {
+          let progress = 1.0 - dying.frames_remaining as f32 / dying.frames as f32;
+          let screen_pos = PhysicsVector::from_vec(
+            *handle.translation(&physics_system.rigid_body_set, &physics_system.collider_set),
+          )
+          .into_pos(camera_system.translation);
+
+          draw_circle(
+            screen_pos.x(),
+            screen_pos.y(),
+            DEATH_BURST_RADIUS * progress,
+            palette().color_3.with_alpha(1.0 - progress),
+          );
+          draw_circle_lines(
+            screen_pos.x(),
+            screen_pos.y(),
+            DEATH_BURST_RADIUS * (0.5 + progress),
+            2.0,
+            palette().color_4.with_alpha(1.0 - progress),
+          );
+        }
+      });
+
+      /* Tint elites with a ring for each of their affixes */
+      physics_system.entities.iter().for_each(|(handle, entity)| {
+        if let Some(elite) = entity.components.get::<Elite>() {
+          let screen_pos = PhysicsVector::from_vec(
+            *handle.translation(&physics_system.rigid_body_set, &physics_system.collider_set),
+          )
+          .into_pos(camera_system.translation);
+
+          elite.affixes.iter().enumerate().for_each(|(index, affix)| {
+            draw_circle_lines(
+              screen_pos.x(),
+              screen_pos.y(),
+              ELITE_RING_RADIUS + ELITE_RING_SPACING * index as f32,
+              2.0,
+              elite_tint(*affix),
+            );
+          });
+        }
+      });
+
+      /* Fill in the enemy being hacked as the channel goes on */
+      let ability_system = ctx.require::<AbilitySystem>().unwrap();
+      if let Some(hack_target) = ability_system.hack_target
+        && physics_system.entities.contains_key(&hack_target)
+      {
+        let progress = ability_system.hack_channel / HACK_CHANNEL_FRAMES;
+        let screen_pos = PhysicsVector::from_vec(
+          *hack_target.translation(&physics_system.rigid_body_set, &physics_system.collider_set),
+        )
+        .into_pos(camera_system.translation);
+
+        draw_circle_lines(
+          screen_pos.x(),
+          screen_pos.y(),
+          HACK_RING_RADIUS,
+          2.0,
+          palette().color_1,
+        );
+        draw_circle(
+          screen_pos.x(),
+          screen_pos.y(),
+          HACK_RING_RADIUS * progress,
+          palette().color_1.with_alpha(0.5),
+        );
+      }
+
+      /* Mark hacked enemies with a ring that shrinks as their time runs out */
+      physics_system.entities.iter().for_each(|(handle, entity)| {
+        if let Some(hacked) = entity.components.get::<Hacked>() {
+          let remaining = hacked.frames_left as f32 / hacked.frames as f32;
+          let screen_pos = PhysicsVector::from_vec(
+            *handle.translation(&physics_system.rigid_body_set, &physics_system.collider_set),
+          )
+          .into_pos(camera_system.translation);
+
+          draw_circle_lines(
+            screen_pos.x(),
+            screen_pos.y(),
+            HACK_RING_RADIUS * remaining.max(0.0),
+            2.0,
+            palette().color_1,
+          );
+        }
+      });
+
+      /* Flash a ring closing in on enemies about to attack */
+      physics_system.entities.iter().for_each(|(handle, entity)| {
+        if let Some(telegraph) = entity.components.get::<Telegraph>()
+          && (telegraph.frames_left / TELEGRAPH_BLINK_FRAMES) % 2 == 0
+        {
+          let progress = 1.0 - telegraph.frames_left as f32 / telegraph.frames as f32;
+          let screen_pos = PhysicsVector::from_vec(
+            *handle.translation(&physics_system.rigid_body_set, &physics_system.collider_set),
+          )
+          .into_pos(camera_system.translation);
+
+          draw_circle_lines(
+            screen_pos.x(),
+            screen_pos.y(),
+            TELEGRAPH_RING_RADIUS * (2.0 - progress),
+            2.0,
+            palette().damage,
+          );
+        }
+      });
+
+      /* Draw the players, blinking while they are invulnerable after a hit or a spawn */
+      player_handles.iter().for_each(|&handle| {
+        let components = physics_system
+          .entities
+          .get(&EntityHandle::RigidBody(handle))
+          .map(|entity| &entity.components);
+        let hitstun = components
+          .and_then(|components| components.get::<Damageable>())
+          .map_or(0.0, |damageable| damageable.current_hitstun);
+        let protection = components.and_then(|components| components.get::<SpawnProtection>());
+
+        /* A ring closes in on the player as they come into the room */
+        if let Some(protection) = &protection {
+          let progress = (protection.frames - protection.frames_left) as f32 / SPAWN_RING_FRAMES;
+          if progress < 1.0 {
+            let screen_pos =
+              PhysicsVector::from_vec(*physics_system.rigid_body_set[handle].translation())
+                .into_pos(camera_system.translation);
+
+            draw_circle_lines(
+              screen_pos.x(),
+              screen_pos.y(),
+              SPAWN_RING_RADIUS * (1.0 - progress),
+              2.0,
+              Color {
+                a: 1.0 - progress,
+                ..palette().color_4
+              },
+            );
+          }
+        }
+
+        let blinking = |frames: i32| frames > 0 && (frames / PLAYER_BLINK_FRAMES) % 2 == 1;
+        if blinking(hitstun as i32)
+          || protection.is_some_and(|protection| blinking(protection.frames_left))
+        {
+          return;
+        }
+
+        let color = if handle == physics_system.player_handle {
+          palette().color_4
+        } else {
+          palette().color_3
+        };
+
+        physics_system.rigid_body_set[handle]
+          .colliders()
+          .iter()
+          .for_each(|&collider_handle| {
+            draw_collider(
+              &physics_system.collider_set[collider_handle],
+              camera_system.translation,
+              None,
+              Some(color),
+            );
+          });
+      });
+
+      set_default_camera();
+
+      /* Tint the room for its current cycle phase */
+      if let Some([r, g, b, a]) = physics_system.cycle_phase().and_then(|phase| phase.tint) {
+        draw_rectangle(
+          0.0,
+          0.0,
+          screen_width(),
+          screen_height(),
+          Color { r, g, b, a },
+        );
+      }
+
+      /* Fade the room out on the way through a map transition, and the next one back in */
+      let fade = match (&physics_system.room_exit, &physics_system.room_entry) {
+        (Some(room_exit), _) => 1.0 - room_exit.frames_left as f32 / ROOM_FADE_FRAMES as f32,
+        (None, Some(_)) => 1.0 - physics_system.frame_count as f32 / ROOM_FADE_FRAMES as f32,
+        (None, None) => 0.0,
+      };
+      if fade > 0.0 {
+        draw_rectangle(
+          0.0,
+          0.0,
+          screen_width(),
+          screen_height(),
+          Color {
+            a: fade,
+            ..palette().color_4
+          },
+        );
+      }
+    }
+
+    /* MARK: HUD, hidden while taking photos */
+    if let Some(ctx) = ctx.downcast::<_>()
+      && !photo_mode
+    {
+      let camera_system = ctx.require::<CameraSystem>().unwrap();
+      let combat_system = ctx.require::<CombatSystem>().unwrap();
+      let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+      let map_system = ctx.require::<MapSystem>().unwrap();
+      let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
+
       let player_physics_pos = PhysicsVector::from_vec(
         *physics_system.rigid_body_set[physics_system.player_handle].translation(),
       );
 
       /* Draw scuffed map overlay */
-      if controls_system.map {
-        let (_, current_world_map) = map_system
+      /* The daily arena isn't part of the world, so there's no map of it to draw */
+      if controls_system.map
+        && let Some((_, current_world_map)) = map_system
           .map_registry
           .iter()
           .find(|(map_name, _)| **map_name == map_system.current_map_name)
-          .unwrap();
-
+      {
         let player_x =
           (physics_scalar_to_map(player_physics_pos.data.0[0][0]) + current_world_map.x) / 8.0;
         let player_y = (current_world_map.height
@@ -159,7 +609,7 @@ impl<Input: Clone + Default + 'static> System for GraphicsSystem<Input> {
             x: center_x,
             y: center_y + 3.0,
           },
-          RED,
+          palette().map_marker,
         );
 
         map_system.map_registry.iter().for_each(|(_, world_map)| {
@@ -190,20 +640,114 @@ impl<Input: Clone + Default + 'static> System for GraphicsSystem<Input> {
               );
             });
         });
+
+        /* Mark challenge rooms with a ring for each step of the best medal earned there */
+        let challenge_system = ctx.require::<ChallengeSystem>().unwrap();
+
+        map_system
+          .map_registry
+          .iter()
+          .for_each(|(map_name, world_map)| {
+            let Some(medal) = challenge_system.medals.get(map_name) else {
+              return;
+            };
+
+            let x = ((world_map.x + world_map.width / 2.0) / 8.0 - player_x) * MINI_MAP_TILE_WIDTH
+              + center_x;
+            let y = ((world_map.y + world_map.height / 2.0) / 8.0 - player_y)
+              * MINI_MAP_TILE_HEIGHT
+              + center_y;
+
+            (0..=*medal as i32).for_each(|step| {
+              draw_circle_lines(
+                x,
+                y,
+                MEDAL_RING_RADIUS + MEDAL_RING_SPACING * step as f32,
+                2.0,
+                medal_color(*medal),
+              );
+            });
+          });
+      }
+
+      /* Highlight the locked on target */
+      if let Some(handle) = combat_system.lock_on_target
+        && physics_system.entities.contains_key(&handle)
+      {
+        let target_screen_pos = PhysicsVector::from_vec(
+          *handle.translation(&physics_system.rigid_body_set, &physics_system.collider_set),
+        )
+        .into_pos(camera_system.translation);
+
+        draw_circle_lines(
+          target_screen_pos.x(),
+          target_screen_pos.y(),
+          LOCK_ON_MARKER_RADIUS,
+          2.0,
+          palette().color_3,
+        );
       }
 
       /* Draw reticle */
       let player_screen_pos = player_physics_pos.into_pos(camera_system.translation);
 
+      if let Some(aim_line_end) = combat_system.aim_line {
+        let aim_line_end_screen_pos =
+          PhysicsVector::from_vec(aim_line_end).into_pos(camera_system.translation);
+
+        draw_line(
+          player_screen_pos.x(),
+          player_screen_pos.y(),
+          aim_line_end_screen_pos.x(),
+          aim_line_end_screen_pos.y(),
+          AIM_LINE_THICKNESS,
+          palette().damage.with_alpha(0.5),
+        );
+      }
+
       let reticle_pos = get_reticle_pos(combat_system.reticle_angle);
 
       draw_circle(
         player_screen_pos.x() + reticle_pos.x(),
         player_screen_pos.y() + reticle_pos.y(),
         RETICLE_SIZE,
-        COLOR_4,
+        palette().color_4,
       );
 
+      /* Dot out the predicted shot paths */
+      combat_system.trajectory_preview.iter().for_each(|path| {
+        path
+          .iter()
+          .step_by(TRAJECTORY_DOT_SPACING)
+          .for_each(|point| {
+            let dot_screen_pos =
+              PhysicsVector::from_vec(*point).into_pos(camera_system.translation);
+
+            draw_circle(
+              dot_screen_pos.x(),
+              dot_screen_pos.y(),
+              TRAJECTORY_DOT_SIZE,
+              palette().color_4.with_alpha(0.6),
+            );
+          });
+      });
+
+      /* Draw the co-op partner's reticle */
+      if let Some(player_two_handle) = physics_system.player_two_handle {
+        let player_two_screen_pos =
+          PhysicsVector::from_vec(*physics_system.rigid_body_set[player_two_handle].translation())
+            .into_pos(camera_system.translation);
+
+        let player_two_reticle_pos = get_reticle_pos(combat_system.player_two_reticle_angle);
+
+        draw_circle(
+          player_two_screen_pos.x() + player_two_reticle_pos.x(),
+          player_two_screen_pos.y() + player_two_reticle_pos.y(),
+          RETICLE_SIZE,
+          palette().color_3,
+        );
+      }
+
       /* DEBUG - Draw slots */
       if SHOW_SLOTS {
         let slot_positions = get_slot_positions(combat_system.reticle_angle);
@@ -217,16 +761,56 @@ impl<Input: Clone + Default + 'static> System for GraphicsSystem<Input> {
             slot_screen_pos.into_vec() + distance_projection_screen(slot.angle, 7.0).into_vec(),
           );
 
-          draw_circle(slot_screen_pos.x(), slot_screen_pos.y(), 2.0, COLOR_3);
+          draw_circle(
+            slot_screen_pos.x(),
+            slot_screen_pos.y(),
+            2.0,
+            palette().color_3,
+          );
           draw_circle(
             slot_next_screen_pos.x(),
             slot_next_screen_pos.y(),
             2.0,
-            COLOR_4,
+            palette().color_4,
           );
         });
       }
 
+      let settings_system = ctx.require::<SettingsSystem>().unwrap();
+
+      /* Faint stand-in for the player along the room's best run */
+      if settings_system.settings.ghost_replay
+        && let Some(ghost_translation) = ctx
+          .require::<ChallengeSystem>()
+          .unwrap()
+          .ghost_translation(&physics_system)
+      {
+        let screen_pos =
+          PhysicsVector::from_vec(ghost_translation).into_pos(camera_system.translation);
+
+        draw_circle(
+          screen_pos.x(),
+          screen_pos.y(),
+          *PhysicsScalar(GHOST_RADIUS).convert(),
+          palette().color_4.with_alpha(0.3),
+        );
+      }
+
+      if settings_system.settings.radar {
+        draw_radar(&physics_system, camera_system.translation);
+      }
+
+      /* Name the room's current cycle phase */
+      if let Some(phase) = physics_system.cycle_phase() {
+        draw_ui_text(
+          &phase.name,
+          screen_width() * 0.01,
+          screen_height() * 0.1,
+          30.0,
+          palette().color_4,
+        );
+      }
+
       /* Draw overlays */
       let player = physics_system
         .entities
@@ -235,7 +819,13 @@ impl<Input: Clone + Default + 'static> System for GraphicsSystem<Input> {
 
       let player_damageable = player.components.get::<Damageable>().unwrap();
 
-      draw_text(
+      /* Flash the screen edge for the first frames of the player's hitstun */
+      let frames_since_hit = player_damageable.max_hitstun - player_damageable.current_hitstun;
+      if player_damageable.current_hitstun > 0.0 && frames_since_hit < DAMAGE_FLASH_FRAMES {
+        draw_damage_flash(1.0 - frames_since_hit / DAMAGE_FLASH_FRAMES);
+      }
+
+      draw_ui_text(
         &format!(
           "{}/{}",
           player_damageable.health, player_damageable.max_health
@@ -243,24 +833,326 @@ impl<Input: Clone + Default + 'static> System for GraphicsSystem<Input> {
         screen_width() * 0.01,
         screen_height() * 0.9,
         40.0,
-        COLOR_4,
+        palette().color_4,
+      );
+
+      draw_ui_text(
+        &tr_args(
+          "hud.credits",
+          &[("credits", combat_system.credits.to_string())],
+        ),
+        screen_width() * 0.01,
+        screen_height() * 0.85,
+        30.0,
+        palette().color_2,
       );
+
+      /* Name the loadout just swapped to */
+      if combat_system.loadout_swap_frames > 0 {
+        draw_ui_text(
+          &tr_args(
+            "hud.loadout",
+            &[(
+              "name",
+              combat_system.loadout_presets[combat_system.active_loadout]
+                .name
+                .clone(),
+            )],
+          ),
+          screen_width() * 0.01,
+          screen_height() * 0.75,
+          30.0,
+          palette().color_4,
+        );
+      }
+
+      /* Prompt to mount a turret the player is on, or how to leave the one they're operating */
+      let turret_prompt = if physics_system.mounted_turret.is_some() {
+        Some("hud.turret_leave")
+      } else if physics_system.turret_in_range {
+        Some("hud.turret_mount")
+      } else {
+        None
+      };
+
+      if let Some(turret_prompt) = turret_prompt {
+        draw_ui_text_aligned(
+          &tr(turret_prompt),
+          screen_width() * 0.5,
+          screen_height() * 0.9,
+          30.0,
+          palette().color_4,
+          Align::Center,
+        );
+      }
+
+      /* Puzzle rooms say when there's something to rewind to */
+      if physics_system.puzzle && !physics_system.rewind_snapshots.is_empty() {
+        draw_ui_text_aligned(
+          &tr("hud.rewind"),
+          screen_width() * 0.5,
+          screen_height() * 0.95,
+          24.0,
+          palette().color_2,
+          Align::Center,
+        );
+      }
+
+      /* Boost charges as pips, the one recharging filling up as it comes back */
+      let ability_system = ctx.require::<AbilitySystem>().unwrap();
+
+      if ability_system.acquired_boost {
+        (0..ability_system.max_boost_charges).for_each(|pip| {
+          let x = screen_width() * 0.01 + BOOST_PIP_RADIUS + pip as f32 * BOOST_PIP_SPACING;
+          let y = screen_height() * 0.8;
+
+          if pip < ability_system.boost_charges {
+            draw_circle(x, y, BOOST_PIP_RADIUS, palette().color_2);
+          } else {
+            draw_circle_lines(x, y, BOOST_PIP_RADIUS, 1.0, palette().color_2);
+
+            if pip == ability_system.boost_charges {
+              draw_circle(
+                x,
+                y,
+                BOOST_PIP_RADIUS * ability_system.boost_recharge
+                  / ability_system.boost_recharge_frames,
+                palette().color_2,
+              );
+            }
+          }
+        });
+      }
+
+      /* One bar per weapon that builds heat, switching colour while it cools off an overheat */
+      combat_system
+        .current_weapons
+        .iter()
+        .filter_map(Weapon::heat)
+        .enumerate()
+        .for_each(|(index, (heat, overheated))| {
+          let x = screen_width() * 0.01;
+          let y = screen_height() * 0.7 - index as f32 * HEAT_BAR_SPACING;
+          let color = if overheated {
+            palette().color_4
+          } else {
+            palette().color_2
+          };
+
+          draw_rectangle(x, y, HEAT_BAR_WIDTH * heat, HEAT_BAR_HEIGHT, color);
+          draw_rectangle_lines(x, y, HEAT_BAR_WIDTH, HEAT_BAR_HEIGHT, 1.0, color);
+        });
+
+      /* Power-ups with the seconds they have left, stacked above the heat bars */
+      combat_system
+        .power_ups
+        .iter()
+        .enumerate()
+        .for_each(|(index, power_up)| {
+          draw_ui_text(
+            &tr_args(
+              "hud.power_up",
+              &[
+                ("name", tr(&format!("power_up.{}", power_up.kind.name()))),
+                (
+                  "seconds",
+                  (power_up.frames_left / FRAMES_PER_SECOND)
+                    .ceil()
+                    .to_string(),
+                ),
+              ],
+            ),
+            screen_width() * 0.01,
+            screen_height() * 0.65 - index as f32 * POWER_UP_LINE_HEIGHT,
+            24.0,
+            palette().color_4,
+          );
+        });
+
+      if let Some(player_two_damageable) = physics_system
+        .player_two_handle
+        .and_then(|handle| {
+          physics_system
+            .entities
+            .get(&EntityHandle::RigidBody(handle))
+        })
+        .and_then(|entity| entity.components.get::<Damageable>())
+      {
+        draw_ui_text(
+          &tr_args(
+            "hud.player_two",
+            &[
+              ("health", player_two_damageable.health.to_string()),
+              ("max_health", player_two_damageable.max_health.to_string()),
+              (
+                "loadout",
+                combat_system.loadout_presets[combat_system.player_two_loadout]
+                  .name
+                  .clone(),
+              ),
+            ],
+          ),
+          screen_width() * 0.01,
+          screen_height() * 0.95,
+          40.0,
+          palette().color_3,
+        );
+      }
+
+      /* Speedrun timer, with the latest split against the best */
+      let speedrun_system = ctx.require::<SpeedrunSystem>().unwrap();
+
+      if settings_system.settings.speedrun_timer {
+        draw_ui_text_aligned(
+          &format_run_time(speedrun_system.run.time),
+          screen_width() * 0.5,
+          screen_height() * 0.05,
+          40.0,
+          palette().color_4,
+          Align::Center,
+        );
+
+        if let Some((split, delta)) = speedrun_system.last_split_delta() {
+          draw_ui_text(
+            &format!(
+              "{} {}{:.2}",
+              split.label,
+              if delta > 0.0 { "+" } else { "" },
+              delta
+            ),
+            screen_width() * 0.45,
+            screen_height() * 0.09,
+            30.0,
+            palette().color_4,
+          );
+        }
+      }
+
+      /* Score in scored rooms, with the multiplier the combo is at */
+      let score_system = ctx.require::<ScoreSystem>().unwrap();
+
+      if physics_system.scored {
+        draw_ui_text_aligned(
+          &tr_args(
+            "hud.score",
+            &[
+              ("score", score_system.score.to_string()),
+              ("multiplier", score_system.multiplier().to_string()),
+            ],
+          ),
+          screen_width() * 0.95,
+          screen_height() * 0.05,
+          40.0,
+          if score_system.combo > 1 {
+            palette().color_1
+          } else {
+            palette().color_4
+          },
+          Align::Right,
+        );
+      }
+
+      /* Time spent in challenge rooms, against which medals are awarded on leaving */
+      let challenge_system = ctx.require::<ChallengeSystem>().unwrap();
+
+      if physics_system.challenge.is_some() {
+        draw_ui_text_aligned(
+          &format_run_time(challenge_system.time(&physics_system)),
+          screen_width() * 0.95,
+          screen_height() * 0.09,
+          30.0,
+          palette().color_4,
+          Align::Right,
+        );
+      }
+
+      /* Time the daily run has taken so far */
+      let daily_system = ctx.require::<DailySystem>().unwrap();
+
+      if daily_system.seed.is_some() {
+        draw_ui_text_aligned(
+          &format_run_time(daily_system.time(&physics_system)),
+          screen_width() * 0.95,
+          screen_height() * 0.09,
+          30.0,
+          palette().color_4,
+          Align::Right,
+        );
+      }
+
+      /* Tutorial prompt, with the glyph for whichever device was used last */
+      let tutorial_system = ctx.require::<TutorialSystem>().unwrap();
+
+      if let Some(prompt) = tutorial_system.active_prompt.as_ref() {
+        draw_ui_text(
+          &format!(
+            "[{}] {}",
+            prompt.action.glyph(controls_system.control_mode),
+            prompt.text
+          ),
+          screen_width() * 0.3,
+          screen_height() * 0.8,
+          40.0,
+          palette().color_4,
+        );
+      }
+
+      /* Dialogue text box */
+      let dialogue_system = ctx.require::<DialogueSystem>().unwrap();
+
+      if let Some(active) = dialogue_system.active.as_ref() {
+        draw_dialogue(active, &dialogue_system.portraits);
+      }
+
+      if controls_system.gamepad_disconnected {
+        draw_ui_text_aligned(
+          &tr("hud.controller_disconnected"),
+          screen_width() * 0.5,
+          screen_height() * 0.5,
+          40.0,
+          palette().color_4,
+          Align::Center,
+        );
+      }
+
+      if controls_system.debug_paused {
+        /* Kept clear of player two's health in the other corner */
+        draw_ui_text_aligned(
+          "debug paused - F5 resume, F6 step",
+          screen_width() * 0.99,
+          screen_height() * 0.95,
+          30.0,
+          palette().color_4,
+          Align::Right,
+        );
+      }
     }
 
     /* Draw the scuffed menu */
-    let menu_system = ctx.get::<MenuSystem<_>>().unwrap();
-    let save_system = ctx.get::<SaveSystem<_>>().unwrap();
-
-    menu_system
-      .active_main_menus
-      .iter()
-      .rev()
-      .for_each(|menu| draw_main_menu(menu, &save_system.available_save_data));
+    let save_system = ctx.require::<SaveSystem<_>>().unwrap();
+
+    menu_system.active_main_menus.iter().rev().for_each(|menu| {
+      draw_main_menu(
+        menu,
+        &save_system.available_save_data,
+        &save_system.thumbnails,
+        &save_system.previews,
+      )
+    });
+    /* Photo mode hides the pause menu it was opened from */
     menu_system
       .active_menus
       .iter()
+      .take(if photo_mode { 1 } else { usize::MAX })
       .rev()
-      .for_each(|menu| draw_menu(menu, &save_system.available_save_data));
+      .for_each(|menu| {
+        draw_menu(
+          menu,
+          &save_system.available_save_data,
+          &save_system.thumbnails,
+          &save_system.previews,
+        )
+      });
 
     /* Maintain target fps */
     let frame_time = get_frame_time();
@@ -275,127 +1167,672 @@ impl<Input: Clone + Default + 'static> System for GraphicsSystem<Input> {
   }
 }
 
-fn draw_main_menu(menu: &MainMenu, available_sava_data: &[String]) {
+const IMPACT_SPARK_COUNT: i32 = 4;
+const IMPACT_SPARK_SPREAD: f32 = 0.6;
+const IMPACT_SPARK_LENGTH: f32 = 12.0;
+const IMPACT_SCORCH_RADIUS: f32 = 4.0;
+const IMPACT_CRITICAL_SPARK_COUNT: i32 = 8;
+const IMPACT_CRITICAL_SPARK_LENGTH: f32 = 20.0;
+const IMPACT_CRITICAL_RING_RADIUS: f32 = 10.0;
+
+const DEATH_BURST_RADIUS: f32 = 24.0;
+
+const TELEGRAPH_RING_RADIUS: f32 = 16.0;
+
+const HACK_RING_RADIUS: f32 = 16.0;
+
+const MEDAL_RING_RADIUS: f32 = 4.0;
+const MEDAL_RING_SPACING: f32 = 3.0;
+
+/* Medals keep their metal colors whatever the palette, and are told apart by ring count too */
+fn medal_color(medal: Medal) -> Color {
+  match medal {
+    Medal::Bronze => Color::from_rgba(205, 127, 50, 255),
+    Medal::Silver => Color::from_rgba(192, 192, 192, 255),
+    Medal::Gold => Color::from_rgba(255, 200, 40, 255),
+  }
+}
+
+fn medal_key(medal: Medal) -> &'static str {
+  match medal {
+    Medal::Bronze => "medal.bronze",
+    Medal::Silver => "medal.silver",
+    Medal::Gold => "medal.gold",
+  }
+}
+
+const ELITE_RING_RADIUS: f32 = 12.0;
+const ELITE_RING_SPACING: f32 = 3.0;
+
+fn elite_tint(affix: EliteAffix) -> Color {
+  match affix {
+    EliteAffix::Armored => palette().color_2,
+    EliteAffix::Fast => palette().fluid,
+    EliteAffix::Explosive => palette().lava,
+    EliteAffix::Regenerating => palette().acid,
+  }
+}
+const TELEGRAPH_BLINK_FRAMES: i32 = 3;
+
+const PLAYER_BLINK_FRAMES: i32 = 4;
+const SPAWN_RING_RADIUS: f32 = 48.0;
+const SPAWN_RING_FRAMES: f32 = 20.0;
+const DAMAGE_FLASH_FRAMES: f32 = 12.0;
+const DAMAGE_FLASH_WIDTH: f32 = 24.0;
+
+const RADAR_RANGE: f32 = 30.0;
+/* How far in from the screen edge the markers sit */
+const RADAR_EDGE_MARGIN: f32 = 16.0;
+const RADAR_MARKER_SIZE: f32 = 6.0;
+const RADAR_BOSS_MARKER_SIZE: f32 = 10.0;
+
+#[derive(Clone, Copy)]
+enum RadarKind {
+  Enemy,
+  Boss,
+  Pickup,
+}
+
+fn radar_kind(entity: &Entity) -> Option<RadarKind> {
+  let components = &entity.components;
+
+  if components.get::<Dying>().is_some() {
+    None
+  } else if components.get::<Boss>().is_some() {
+    Some(RadarKind::Boss)
+  } else if components.get::<Enemy>().is_some() {
+    Some(RadarKind::Enemy)
+  } else if components.get::<GivesItemOnCollision>().is_some()
+    || components.get::<GiveAbilityOnCollision>().is_some()
+    || components.get::<GivesEngineUpgradeOnCollision>().is_some()
+  {
+    Some(RadarKind::Pickup)
+  } else {
+    None
+  }
+}
+
+/* The point on the screen's edge on the way from its middle to something off screen, with the
+angle it lies at. Nothing for what's already in view */
+fn radar_marker(screen_pos: Vec2) -> Option<(Vec2, f32)> {
+  let center = vec2(screen_width() / 2.0, screen_height() / 2.0);
+  let offset = screen_pos - center;
+
+  if offset.x.abs() <= center.x && offset.y.abs() <= center.y {
+    return None;
+  }
+
+  let scale = ((center.x - RADAR_EDGE_MARGIN) / offset.x.abs())
+    .min((center.y - RADAR_EDGE_MARGIN) / offset.y.abs());
+
+  Some((center + offset * scale, offset.y.atan2(offset.x)))
+}
+
+fn draw_radar_arrow(position: Vec2, angle: f32, size: f32, color: Color) {
+  let direction = Vec2::from_angle(angle);
+  let side = direction.perp() * size * 0.7;
+
+  draw_triangle(
+    position + direction * size,
+    position - direction * size + side,
+    position - direction * size - side,
+    color,
+  );
+}
+
+/* Edge markers for whatever worth knowing about is nearby but off screen */
+fn draw_radar(physics_system: &PhysicsSystem, camera_translation: Vector<f32>) {
+  let player_translation =
+    *physics_system.rigid_body_set[physics_system.player_handle].translation();
+
+  physics_system
+    .entities_within(player_translation, RADAR_RANGE)
+    .filter_map(|(translation, entity)| Some((translation, radar_kind(entity)?)))
+    .for_each(|(translation, kind)| {
+      let screen_pos = PhysicsVector::from_vec(translation).into_pos(camera_translation);
+      let Some((position, angle)) = radar_marker(vec2(screen_pos.x(), screen_pos.y())) else {
+        return;
+      };
+
+      match kind {
+        RadarKind::Enemy => {
+          draw_radar_arrow(position, angle, RADAR_MARKER_SIZE, palette().color_4);
+        }
+        RadarKind::Boss => {
+          draw_radar_arrow(position, angle, RADAR_BOSS_MARKER_SIZE, palette().damage);
+          draw_circle_lines(
+            position.x,
+            position.y,
+            RADAR_BOSS_MARKER_SIZE * 1.5,
+            2.0,
+            palette().color_4,
+          );
+        }
+        RadarKind::Pickup => {
+          draw_poly(
+            position.x,
+            position.y,
+            4,
+            RADAR_MARKER_SIZE,
+            0.0,
+            palette().color_3,
+          );
+        }
+      }
+    });
+}
+
+/* Bands along every edge of the screen, fading with intensity */
+fn draw_damage_flash(intensity: f32) {
+  let color = palette().damage.with_alpha(0.6 * intensity);
+
+  draw_rectangle(0.0, 0.0, screen_width(), DAMAGE_FLASH_WIDTH, color);
+  draw_rectangle(
+    0.0,
+    screen_height() - DAMAGE_FLASH_WIDTH,
+    screen_width(),
+    DAMAGE_FLASH_WIDTH,
+    color,
+  );
+  draw_rectangle(
+    0.0,
+    DAMAGE_FLASH_WIDTH,
+    DAMAGE_FLASH_WIDTH,
+    screen_height() - 2.0 * DAMAGE_FLASH_WIDTH,
+    color,
+  );
+  draw_rectangle(
+    screen_width() - DAMAGE_FLASH_WIDTH,
+    DAMAGE_FLASH_WIDTH,
+    DAMAGE_FLASH_WIDTH,
+    screen_height() - 2.0 * DAMAGE_FLASH_WIDTH,
+    color,
+  );
+}
+
+const HAZARD_RIPPLE_SPEED: f32 = 3.0;
+
+/* Ripple runs from 0 to 1 and brightens the pool */
+fn hazard_color(kind: HazardKind, ripple: f32) -> Color {
+  let palette = palette();
+
+  match kind {
+    HazardKind::Lava => mix(palette.lava, palette.lava_highlight, ripple),
+    HazardKind::Acid => mix(palette.acid, palette.acid_highlight, ripple),
+  }
+}
+
+fn draw_impact(screen_pos: ScreenVector, impact: &Impact, remaining: f32) {
+  match impact.kind {
+    ImpactKind::Scorch => draw_circle(
+      screen_pos.x(),
+      screen_pos.y(),
+      IMPACT_SCORCH_RADIUS,
+      palette().color_4.with_alpha(0.6 * remaining),
+    ),
+    ImpactKind::Spark => {
+      /* Screen y points down while physics y points up, so the angle flips */
+      let normal_angle = -impact.normal.y.atan2(impact.normal.x);
+      let length = IMPACT_SPARK_LENGTH * (1.0 - remaining * 0.5);
+
+      (0..IMPACT_SPARK_COUNT).for_each(|index| {
+        let angle = normal_angle
+          + IMPACT_SPARK_SPREAD * (index as f32 / (IMPACT_SPARK_COUNT - 1) as f32 - 0.5) * 2.0;
+        let end = distance_projection_screen(angle, length);
+
+        draw_line(
+          screen_pos.x(),
+          screen_pos.y(),
+          screen_pos.x() + end.x(),
+          screen_pos.y() + end.y(),
+          1.0,
+          palette().color_2.with_alpha(remaining),
+        );
+      });
+    }
+    ImpactKind::Critical => {
+      /* Sparks burst all the way around with a flash ring to read apart from a normal hit */
+      let length = IMPACT_CRITICAL_SPARK_LENGTH * (1.0 - remaining * 0.5);
+
+      (0..IMPACT_CRITICAL_SPARK_COUNT).for_each(|index| {
+        let angle = 2.0 * PI * index as f32 / IMPACT_CRITICAL_SPARK_COUNT as f32;
+        let end = distance_projection_screen(angle, length);
+
+        draw_line(
+          screen_pos.x(),
+          screen_pos.y(),
+          screen_pos.x() + end.x(),
+          screen_pos.y() + end.y(),
+          2.0,
+          palette().color_3.with_alpha(remaining),
+        );
+      });
+
+      draw_circle_lines(
+        screen_pos.x(),
+        screen_pos.y(),
+        IMPACT_CRITICAL_RING_RADIUS * (1.0 - remaining * 0.5),
+        1.0,
+        palette().color_2.with_alpha(remaining),
+      );
+    }
+  }
+}
+
+const DIALOGUE_BOX_HEIGHT: f32 = 160.0;
+const DIALOGUE_PORTRAIT_SIZE: f32 = 128.0;
+const DIALOGUE_PADDING: f32 = 16.0;
+
+/* Text box along the bottom of the screen, with the skip progress above it */
+fn draw_dialogue(active: &ActiveDialogue, portraits: &HashMap<String, Texture2D>) {
+  let box_y = screen_height() - DIALOGUE_BOX_HEIGHT - DIALOGUE_PADDING;
+
+  if let DialogueStep::Line {
+    speaker,
+    portrait,
+    text,
+  } = active.current_step()
+  {
+    draw_rectangle(
+      DIALOGUE_PADDING,
+      box_y,
+      screen_width() - 2.0 * DIALOGUE_PADDING,
+      DIALOGUE_BOX_HEIGHT,
+      palette().color_4,
+    );
+
+    let portrait_x = 2.0 * DIALOGUE_PADDING;
+    let portrait_y = box_y + DIALOGUE_PADDING;
+
+    /* Speakers without a portrait get a frame with their initial */
+    match portrait
+      .as_ref()
+      .and_then(|portrait| portraits.get(portrait))
+    {
+      Some(texture) => draw_texture_ex(
+        texture,
+        portrait_x,
+        portrait_y,
+        WHITE,
+        DrawTextureParams {
+          dest_size: Some(Vec2::new(DIALOGUE_PORTRAIT_SIZE, DIALOGUE_PORTRAIT_SIZE)),
+          ..Default::default()
+        },
+      ),
+      None => {
+        draw_rectangle_lines(
+          portrait_x,
+          portrait_y,
+          DIALOGUE_PORTRAIT_SIZE,
+          DIALOGUE_PORTRAIT_SIZE,
+          4.0,
+          palette().color_2,
+        );
+        draw_ui_text(
+          &speaker.chars().take(1).collect::<String>(),
+          portrait_x + DIALOGUE_PORTRAIT_SIZE * 0.35,
+          portrait_y + DIALOGUE_PORTRAIT_SIZE * 0.65,
+          60.0,
+          palette().color_1,
+        );
+      }
+    }
+
+    let text_x = portrait_x + DIALOGUE_PORTRAIT_SIZE + DIALOGUE_PADDING;
+
+    draw_ui_text(speaker, text_x, portrait_y + 30.0, 36.0, palette().color_2);
+    draw_ui_text(text, text_x, portrait_y + 75.0, 30.0, palette().color_1);
+  }
+
+  if active.skip_held_frames > 0 {
+    let skip_progress = active.skip_held_frames as f32 / DIALOGUE_SKIP_FRAMES as f32;
+
+    draw_ui_text(
+      &tr("hud.skipping"),
+      screen_width() - 220.0,
+      box_y - 20.0,
+      30.0,
+      palette().color_4,
+    );
+    draw_rectangle(
+      screen_width() - 220.0,
+      box_y - 12.0,
+      200.0 * skip_progress,
+      6.0,
+      palette().color_4,
+    );
+  }
+}
+
+const SCREENSHOT_DIR_NAME: &str = "screenshots";
+
+/* Errors are only logged, a missed picture shouldn't interrupt the game */
+fn save_screenshot() {
+  let screenshot_dir = storage::data_dir().join(SCREENSHOT_DIR_NAME);
+
+  if let Err(error) = fs::create_dir_all(&screenshot_dir) {
+    log::error(
+      "graphics",
+      format!("couldn't create {}: {}", screenshot_dir.display(), error),
+    );
+    return;
+  }
+
+  /* No colons, they aren't allowed in Windows file names */
+  let sys_time: DateTime<Utc> = time::SystemTime::now().into();
+  let screenshot_path = screenshot_dir.join(format!(
+    "screenshot_{}.png",
+    sys_time.format("%Y-%m-%d_%H-%M-%S%.3f")
+  ));
+
+  get_screen_data().export_png(screenshot_path.to_str().unwrap());
+  log::info("graphics", format!("saved {}", screenshot_path.display()));
+}
+
+/* Wrapped text widths, as fractions of the screen width */
+const DATABASE_WRAP_WIDTH: f32 = 0.6;
+const ERROR_WRAP_WIDTH: f32 = 0.5;
+
+const SAVE_THUMBNAIL_WIDTH: f32 = 80.0;
+
+/* Drawn to the left of the save's row, with the text baseline at `y` */
+fn draw_save_thumbnail(thumbnails: &HashMap<String, Texture2D>, save: &str, y: f32) {
+  if let Some(thumbnail) = thumbnails.get(save) {
+    let height = SAVE_THUMBNAIL_WIDTH * thumbnail.height() / thumbnail.width();
+
+    draw_texture_ex(
+      thumbnail,
+      screen_width() * 0.5 - SAVE_THUMBNAIL_WIDTH - 8.0,
+      y - height,
+      WHITE,
+      DrawTextureParams {
+        dest_size: Some(Vec2::new(SAVE_THUMBNAIL_WIDTH, height)),
+        ..Default::default()
+      },
+    );
+  }
+}
+
+const SAVE_PREVIEW_CELL_SIZE: f32 = 8.0;
+const SAVE_PREVIEW_ABILITY_RADIUS: f32 = 4.0;
+
+fn ability_key(ability: MapAbilityType) -> &'static str {
+  match ability {
+    MapAbilityType::Boost => "ability.boost",
+    MapAbilityType::Chain => "ability.chain",
+    MapAbilityType::Magnetize => "ability.magnetize",
+    MapAbilityType::Hack => "ability.hack",
+  }
+}
+
+/* The equipped module grid left of the thumbnail, weapons filled in and modifiers outlined,
+with a column of the save's abilities by their initials left of that */
+fn draw_save_preview(previews: &HashMap<String, SavePreview>, save: &str, y: f32) {
+  let Some(preview) = previews.get(save) else {
+    return;
+  };
+
+  let grid_width = SAVE_PREVIEW_CELL_SIZE * EQUIP_SLOTS_WIDTH as f32;
+  let grid_x = screen_width() * 0.5 - SAVE_THUMBNAIL_WIDTH - 16.0 - grid_width;
+  let grid_y = y - SAVE_PREVIEW_CELL_SIZE * EQUIP_SLOTS_HEIGHT as f32;
+
+  preview
+    .equipped_modules
+    .iter()
+    .enumerate()
+    .for_each(|(module_x, column)| {
+      column.iter().enumerate().for_each(|(module_y, module)| {
+        let x = grid_x + module_x as f32 * SAVE_PREVIEW_CELL_SIZE;
+        let y = grid_y + module_y as f32 * SAVE_PREVIEW_CELL_SIZE;
+
+        match module.map(|module| weapon_module_from_kind(module.kind)) {
+          Some(WeaponModule::Generator(_)) => draw_rectangle(
+            x + 1.0,
+            y + 1.0,
+            SAVE_PREVIEW_CELL_SIZE - 2.0,
+            SAVE_PREVIEW_CELL_SIZE - 2.0,
+            palette().color_1,
+          ),
+          Some(WeaponModule::Modulator(..)) => draw_rectangle_lines(
+            x + 1.0,
+            y + 1.0,
+            SAVE_PREVIEW_CELL_SIZE - 2.0,
+            SAVE_PREVIEW_CELL_SIZE - 2.0,
+            2.0,
+            palette().color_1,
+          ),
+          None => draw_rectangle(
+            x + 3.0,
+            y + 3.0,
+            SAVE_PREVIEW_CELL_SIZE - 6.0,
+            SAVE_PREVIEW_CELL_SIZE - 6.0,
+            palette().color_3,
+          ),
+        }
+      })
+    });
+
+  preview
+    .abilities
+    .iter()
+    .enumerate()
+    .for_each(|(index, ability)| {
+      let x = grid_x - 8.0 - SAVE_PREVIEW_ABILITY_RADIUS;
+      let y = grid_y + SAVE_PREVIEW_ABILITY_RADIUS + index as f32 * SAVE_PREVIEW_CELL_SIZE;
+
+      draw_circle(x, y, SAVE_PREVIEW_ABILITY_RADIUS, palette().color_1);
+      draw_ui_text_aligned(
+        &tr(ability_key(*ability))
+          .chars()
+          .take(1)
+          .collect::<String>(),
+        x - SAVE_PREVIEW_ABILITY_RADIUS - 2.0,
+        y + SAVE_PREVIEW_ABILITY_RADIUS,
+        12.0,
+        palette().color_1,
+        Align::Right,
+      );
+    });
+}
+
+/* Selected menu options are marked by dashes either side */
+fn option_text(key: &str, selected: bool) -> String {
+  if selected {
+    format!("-{}-", tr(key))
+  } else {
+    tr(key)
+  }
+}
+
+fn setting_text(key: &str, value: String) -> String {
+  format!("{} < {} >", tr(key), value)
+}
+
+fn on_off_text(on: bool) -> String {
+  tr(if on { "settings.on" } else { "settings.off" })
+}
+
+fn draw_main_menu(
+  menu: &MainMenu,
+  available_sava_data: &[String],
+  thumbnails: &HashMap<String, Texture2D>,
+  previews: &HashMap<String, SavePreview>,
+) {
   match menu.kind.clone() {
     /* MARK: Menu Main */
     crate::menu::MainMenuKind::Main(should_include_continue_option) => {
-      draw_rectangle(0.0, 0.0, screen_width(), screen_height(), COLOR_4);
+      draw_rectangle(0.0, 0.0, screen_width(), screen_height(), palette().color_4);
 
-      draw_text(
-        "LONGINUS",
+      draw_ui_text(
+        &tr("menu.title"),
         screen_width() * 0.2,
         screen_height() * 0.3,
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
 
-      draw_text(
-        &format!(
-          "{}{}{}",
-          if menu.cursor_position == vector![0, 0] {
-            "-"
-          } else {
-            ""
-          },
+      draw_ui_text(
+        &option_text(
           if should_include_continue_option {
-            "continue"
+            "menu.continue"
           } else {
-            "new_game"
+            "menu.new_game"
           },
-          if menu.cursor_position == vector![0, 0] {
-            "-"
-          } else {
-            ""
-          }
+          menu.cursor_position == vector![0, 0],
         ),
         screen_width() * 0.2,
         screen_height() * 0.6,
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
-      draw_text(
-        &format!(
-          "{}{}{}",
-          if menu.cursor_position == vector![0, 1] {
-            "-"
-          } else {
-            ""
-          },
+      draw_ui_text(
+        &option_text(
           if should_include_continue_option {
-            "new_game"
+            "menu.new_game"
           } else {
-            "load_game"
+            "menu.load_game"
           },
-          if menu.cursor_position == vector![0, 1] {
-            "-"
-          } else {
-            ""
-          }
+          menu.cursor_position == vector![0, 1],
         ),
         screen_width() * 0.2,
         screen_height() * 0.7,
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
       if should_include_continue_option {
-        draw_text(
-          if menu.cursor_position == vector![0, 2] {
-            "-load_game-"
-          } else {
-            "load_game"
-          },
+        draw_ui_text(
+          &option_text("menu.load_game", menu.cursor_position == vector![0, 2]),
           screen_width() * 0.2,
           screen_height() * 0.8,
           40.0,
-          COLOR_1,
+          palette().color_1,
         );
       }
+      let daily_row = if should_include_continue_option { 3 } else { 2 };
+      draw_ui_text(
+        &option_text("menu.daily", menu.cursor_position == vector![0, daily_row]),
+        screen_width() * 0.2,
+        screen_height() * (0.6 + 0.1 * daily_row as f32),
+        40.0,
+        palette().color_1,
+      );
+    }
+    crate::menu::MainMenuKind::MainLoadSave => {
+      draw_rectangle(
+        screen_width() * 0.45,
+        screen_height() * 0.45,
+        screen_width() * 0.5,
+        screen_height() * 0.5,
+        palette().color_2,
+      );
+      draw_ui_text(
+        &option_text("menu.cancel", menu.cursor_position == vector![0, 0]),
+        screen_width() * 0.5,
+        screen_height() * 0.5,
+        40.0,
+        palette().color_1,
+      );
+      available_sava_data
+        .iter()
+        .enumerate()
+        .for_each(|(index, save)| {
+          let y = screen_height() * (0.55 + (index as f32 * 0.05));
+
+          draw_save_thumbnail(thumbnails, save, y);
+          draw_save_preview(previews, save, y);
+          draw_ui_text(
+            &format!(
+              "{}{}",
+              if menu.cursor_position.y - 1 == index as i32 {
+                "-"
+              } else {
+                ""
+              },
+              save
+            ),
+            screen_width() * 0.5,
+            y,
+            40.0,
+            palette().color_1,
+          );
+        });
     }
-    crate::menu::MainMenuKind::MainLoadSave => {
+    crate::menu::MainMenuKind::NewGameDifficulty => {
       draw_rectangle(
         screen_width() * 0.45,
         screen_height() * 0.45,
         screen_width() * 0.5,
         screen_height() * 0.5,
-        COLOR_2,
+        palette().color_2,
       );
-      draw_text(
-        if menu.cursor_position == vector![0, 0] {
-          "-cancel-"
-        } else {
-          "cancel"
-        },
+      draw_ui_text(
+        &tr("menu.difficulty"),
         screen_width() * 0.5,
         screen_height() * 0.5,
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
-      available_sava_data
+      Difficulty::ALL
         .iter()
+        .map(|difficulty| format!("difficulty.{}", difficulty.name()))
+        .chain(["menu.cancel".to_string()])
         .enumerate()
-        .for_each(|(index, save)| {
-          draw_text(
-            &format!(
-              "{}{}",
-              if menu.cursor_position.y - 1 == index as i32 {
-                "-"
-              } else {
-                ""
-              },
-              save
-            ),
+        .for_each(|(index, option)| {
+          draw_ui_text(
+            &option_text(&option, menu.cursor_position.y == index as i32),
             screen_width() * 0.5,
             screen_height() * (0.55 + (index as f32 * 0.05)),
             40.0,
-            COLOR_1,
+            palette().color_1,
+          );
+        });
+    }
+    /* MARK: Menu Error */
+    crate::menu::MainMenuKind::Error(error) => {
+      draw_rectangle(
+        screen_width() * 0.2,
+        screen_height() * 0.3,
+        screen_width() * 0.6,
+        screen_height() * 0.4,
+        palette().color_2,
+      );
+      draw_ui_text(
+        &tr("error.title"),
+        screen_width() * 0.25,
+        screen_height() * 0.36,
+        40.0,
+        palette().color_1,
+      );
+      wrap_text(&error, 24.0, screen_width() * ERROR_WRAP_WIDTH)
+        .iter()
+        .enumerate()
+        .for_each(|(index, line)| {
+          draw_ui_text(
+            line,
+            screen_width() * 0.25,
+            screen_height() * 0.42 + index as f32 * line_height(24.0),
+            24.0,
+            palette().color_1,
           );
         });
+      draw_ui_text(
+        &option_text("error.return", true),
+        screen_width() * 0.25,
+        screen_height() * 0.66,
+        40.0,
+        palette().color_1,
+      );
     }
     _ => todo!("Unimplemented"),
   }
 }
 
-fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
+fn draw_menu(
+  menu: &GameMenu,
+  available_sava_data: &[String],
+  thumbnails: &HashMap<String, Texture2D>,
+  previews: &HashMap<String, SavePreview>,
+) {
   match menu.kind.clone() {
     /* MARK: Pause Main */
     crate::menu::GameMenuKind::PauseMain => {
@@ -404,41 +1841,291 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
         screen_height() * 0.1,
         screen_width() * 0.8,
         screen_height() * 0.8,
-        COLOR_3,
+        palette().color_3,
       );
 
-      draw_text(
-        if menu.cursor_position == vector![0, 0] {
-          "-resume-"
-        } else {
-          "resume"
-        },
+      draw_ui_text(
+        &option_text("pause.resume", menu.cursor_position == vector![0, 0]),
+        screen_width() * 0.2,
+        screen_height() * 0.55,
+        40.0,
+        palette().color_1,
+      );
+      draw_ui_text(
+        &option_text("pause.load_game", menu.cursor_position == vector![0, 1]),
         screen_width() * 0.2,
         screen_height() * 0.6,
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
-      draw_text(
-        if menu.cursor_position == vector![0, 1] {
-          "-load game-"
-        } else {
-          "load game"
-        },
+      draw_ui_text(
+        &option_text("pause.settings", menu.cursor_position == vector![0, 2]),
         screen_width() * 0.2,
         screen_height() * 0.65,
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
-      draw_text(
-        if menu.cursor_position == vector![0, 2] {
-          "-quit to menu-"
-        } else {
-          "quit to menu"
-        },
+      draw_ui_text(
+        &option_text("pause.database", menu.cursor_position == vector![0, 3]),
+        screen_width() * 0.2,
+        screen_height() * 0.7,
+        40.0,
+        palette().color_1,
+      );
+      draw_ui_text(
+        &option_text("pause.photo_mode", menu.cursor_position == vector![0, 4]),
+        screen_width() * 0.2,
+        screen_height() * 0.75,
+        40.0,
+        palette().color_1,
+      );
+      draw_ui_text(
+        &option_text("pause.suspend", menu.cursor_position == vector![0, 5]),
+        screen_width() * 0.2,
+        screen_height() * 0.8,
+        40.0,
+        palette().color_1,
+      );
+      draw_ui_text(
+        &option_text("pause.quit", menu.cursor_position == vector![0, 6]),
         screen_width() * 0.2,
+        screen_height() * 0.85,
+        40.0,
+        palette().color_1,
+      );
+    }
+    /* MARK: Photo Mode */
+    crate::menu::GameMenuKind::PhotoMode(capturing) => {
+      if !capturing {
+        draw_ui_text(
+          &tr("photo_mode.hint"),
+          screen_width() * 0.01,
+          screen_height() * 0.97,
+          24.0,
+          palette().color_4,
+        );
+      }
+    }
+    /* MARK: Pause Database */
+    crate::menu::GameMenuKind::PauseDatabase(entries) => {
+      draw_rectangle(
+        screen_width() * 0.45,
+        screen_height() * 0.15,
+        screen_width() * 0.5,
+        screen_height() * 0.8,
+        palette().color_2,
+      );
+
+      let discovered_count = entries.iter().filter(|entry| entry.discovered).count();
+      let completion = if entries.is_empty() {
+        100.0
+      } else {
+        100.0 * discovered_count as f32 / entries.len() as f32
+      };
+
+      draw_ui_text(
+        &tr_count(
+          "database.title",
+          entries.len(),
+          &[
+            ("found", discovered_count.to_string()),
+            ("percent", format!("{:.0}", completion)),
+          ],
+        ),
+        screen_width() * 0.5,
+        screen_height() * 0.2,
+        40.0,
+        palette().color_1,
+      );
+      entries.iter().enumerate().for_each(|(index, entry)| {
+        let title = if entry.discovered {
+          entry.title.clone()
+        } else {
+          tr("database.undiscovered")
+        };
+
+        draw_ui_text(
+          &if menu.cursor_position.y == index as i32 {
+            format!("-{}-", title)
+          } else {
+            title
+          },
+          screen_width() * 0.5,
+          screen_height() * (0.25 + (index as f32 * 0.05)),
+          40.0,
+          palette().color_1,
+        );
+      });
+    }
+    /* MARK: Pause Database Entry */
+    crate::menu::GameMenuKind::PauseDatabaseEntry(entry) => {
+      draw_rectangle(
+        screen_width() * 0.15,
+        screen_height() * 0.15,
+        screen_width() * 0.7,
         screen_height() * 0.7,
+        palette().color_3,
+      );
+      draw_ui_text(
+        &entry.title,
+        screen_width() * 0.2,
+        screen_height() * 0.25,
+        40.0,
+        palette().color_1,
+      );
+      wrap_text(&entry.text, 30.0, screen_width() * DATABASE_WRAP_WIDTH)
+        .iter()
+        .enumerate()
+        .for_each(|(index, line)| {
+          draw_ui_text(
+            line,
+            screen_width() * 0.2,
+            screen_height() * 0.33 + index as f32 * line_height(30.0),
+            30.0,
+            palette().color_1,
+          );
+        });
+    }
+    /* MARK: Pause Settings */
+    crate::menu::GameMenuKind::PauseSettings(settings) => {
+      draw_rectangle(
+        screen_width() * 0.45,
+        screen_height() * 0.15,
+        screen_width() * 0.5,
+        screen_height() * 0.84,
+        palette().color_2,
+      );
+      draw_ui_text(
+        &tr("settings.title"),
+        screen_width() * 0.5,
+        screen_height() * 0.2,
+        40.0,
+        palette().color_1,
+      );
+      [
+        setting_text(
+          "settings.aim_assist",
+          tr(&format!("aim_assist.{}", settings.aim_assist.name())),
+        ),
+        setting_text(
+          "settings.log_level",
+          tr(&format!("log_level.{}", settings.log_level.name())),
+        ),
+        setting_text("settings.log_to_file", on_off_text(settings.log_to_file)),
+        setting_text(
+          "settings.speedrun_timer",
+          on_off_text(settings.speedrun_timer),
+        ),
+        setting_text("settings.coop", on_off_text(settings.coop)),
+        setting_text(
+          "settings.palette",
+          tr(&format!("palette.{}", settings.palette.name())),
+        ),
+        setting_text(
+          "settings.high_contrast",
+          on_off_text(settings.high_contrast),
+        ),
+        setting_text(
+          "settings.text_size",
+          tr(&format!("text_size.{}", settings.ui_scale.name())),
+        ),
+        setting_text("settings.language", settings.language.name().to_string()),
+        setting_text("settings.toggle_fire", on_off_text(settings.toggle_fire)),
+        setting_text("settings.sticky_aim", on_off_text(settings.sticky_aim)),
+        setting_text(
+          "settings.boost_binding",
+          tr(&format!("boost_binding.{}", settings.boost_binding.name())),
+        ),
+        setting_text(
+          "settings.game_speed",
+          format!("{}%", settings.game_speed.percent()),
+        ),
+        setting_text(
+          "settings.trajectory_preview",
+          on_off_text(settings.trajectory_preview),
+        ),
+        setting_text("settings.radar", on_off_text(settings.radar)),
+        setting_text("settings.aim_line", on_off_text(settings.aim_line)),
+        setting_text("settings.ghost_replay", on_off_text(settings.ghost_replay)),
+        setting_text(
+          "settings.physics_quality",
+          tr(&format!(
+            "physics_quality.{}",
+            settings.physics_quality.name()
+          )),
+        ),
+        tr("settings.assist"),
+        tr("settings.change_controller"),
+        tr("settings.done"),
+      ]
+      .iter()
+      .enumerate()
+      .for_each(|(index, option)| {
+        draw_ui_text(
+          &if menu.cursor_position.y == index as i32 {
+            format!("-{}-", option)
+          } else {
+            option.to_string()
+          },
+          screen_width() * 0.5,
+          screen_height() * (0.25 + (index as f32 * 0.035)),
+          40.0,
+          palette().color_1,
+        );
+      });
+    }
+    /* MARK: Pause Assist */
+    crate::menu::GameMenuKind::PauseAssist(settings) => {
+      draw_rectangle(
+        screen_width() * 0.45,
+        screen_height() * 0.15,
+        screen_width() * 0.5,
+        screen_height() * 0.5,
+        palette().color_2,
+      );
+      draw_ui_text(
+        &tr("assist.title"),
+        screen_width() * 0.5,
+        screen_height() * 0.2,
         40.0,
-        COLOR_1,
+        palette().color_1,
+      );
+      [
+        setting_text(
+          "assist.damage_taken",
+          format!("{}%", settings.assist.damage_taken.percent()),
+        ),
+        setting_text(
+          "assist.infinite_boost",
+          on_off_text(settings.assist.infinite_boost),
+        ),
+        setting_text(
+          "assist.no_knockback",
+          on_off_text(settings.assist.no_knockback),
+        ),
+        tr("settings.done"),
+      ]
+      .iter()
+      .enumerate()
+      .for_each(|(index, option)| {
+        draw_ui_text(
+          &if menu.cursor_position.y == index as i32 {
+            format!("-{}-", option)
+          } else {
+            option.to_string()
+          },
+          screen_width() * 0.5,
+          screen_height() * (0.25 + (index as f32 * 0.045)),
+          40.0,
+          palette().color_1,
+        );
+      });
+      draw_ui_text(
+        &tr("assist.note"),
+        screen_width() * 0.5,
+        screen_height() * 0.6,
+        30.0,
+        palette().color_1,
       );
     }
     /* MARK: Pause Load Save */
@@ -448,24 +2135,24 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
         screen_height() * 0.45,
         screen_width() * 0.5,
         screen_height() * 0.5,
-        COLOR_2,
+        palette().color_2,
       );
-      draw_text(
-        if menu.cursor_position == vector![0, 0] {
-          "-cancel"
-        } else {
-          "cancel"
-        },
+      draw_ui_text(
+        &option_text("menu.cancel", menu.cursor_position == vector![0, 0]),
         screen_width() * 0.5,
         screen_height() * 0.5,
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
       available_sava_data
         .iter()
         .enumerate()
         .for_each(|(index, save)| {
-          draw_text(
+          let y = screen_height() * (0.55 + (index as f32 * 0.05));
+
+          draw_save_thumbnail(thumbnails, save, y);
+          draw_save_preview(previews, save, y);
+          draw_ui_text(
             &format!(
               "{}{}",
               if menu.cursor_position.y - 1 == index as i32 {
@@ -476,9 +2163,9 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
               save
             ),
             screen_width() * 0.5,
-            screen_height() * (0.55 + (index as f32 * 0.05)),
+            y,
             40.0,
-            COLOR_1,
+            palette().color_1,
           );
         });
     }
@@ -489,38 +2176,106 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
         screen_height() * 0.1,
         screen_width() * 0.8,
         screen_height() * 0.8,
-        COLOR_3,
+        palette().color_3,
       );
 
-      draw_text(
-        "inventory",
+      draw_ui_text(
+        &tr("inventory.title"),
         screen_width() * 0.2,
         screen_height() * 0.4,
         80.0,
-        COLOR_1,
+        palette().color_1,
       );
 
-      draw_text(
-        if menu.cursor_position == vector![0, 0] {
-          "-edit-"
-        } else {
-          "edit"
-        },
+      draw_ui_text(
+        &option_text("inventory.edit", menu.cursor_position == vector![0, 0]),
         screen_width() * 0.2,
         screen_height() * 0.6,
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
-      draw_text(
-        if menu.cursor_position == vector![1, 0] {
-          "-close-"
-        } else {
-          "close"
-        },
-        screen_width() * 0.5,
+      draw_ui_text(
+        &option_text("inventory.fuse", menu.cursor_position == vector![1, 0]),
+        screen_width() * 0.4,
+        screen_height() * 0.6,
+        40.0,
+        palette().color_1,
+      );
+      draw_ui_text(
+        &option_text("inventory.close", menu.cursor_position == vector![2, 0]),
+        screen_width() * 0.6,
         screen_height() * 0.6,
         40.0,
-        COLOR_1,
+        palette().color_1,
+      );
+    }
+    /* MARK: Inventory fuse */
+    crate::menu::GameMenuKind::InventoryFuse(selection) => {
+      draw_rectangle(
+        screen_width() * 0.45,
+        screen_height() * 0.4,
+        screen_width() * 0.5,
+        screen_height() * 0.5,
+        palette().color_2,
+      );
+
+      draw_ui_text(
+        &tr("fuse.title"),
+        0.5 * screen_width(),
+        0.45 * screen_height(),
+        40.0,
+        palette().color_1,
+      );
+
+      (0..selection.unequipped_modules.len() as i32).for_each(|index| {
+        let x = index % INVENTORY_WRAP_WIDTH;
+        let y = index / INVENTORY_WRAP_WIDTH;
+        let color = if selection.first_picked == Some(index as usize) {
+          palette().color_4
+        } else if menu.cursor_position == vector![x, y] {
+          palette().color_3
+        } else {
+          palette().color_2
+        };
+
+        draw_rectangle(
+          (0.5 + (x as f32 * 0.05)) * screen_width(),
+          (0.5 + (y as f32 * 0.05)) * screen_height(),
+          0.05 * screen_width(),
+          0.05 * screen_height(),
+          color,
+        );
+      });
+
+      selection
+        .unequipped_modules
+        .iter()
+        .enumerate()
+        .for_each(|(index, &module)| {
+          draw_module_symbol(
+            module,
+            (index as i32 % INVENTORY_WRAP_WIDTH) as f32 * 0.05,
+            (index as i32 / INVENTORY_WRAP_WIDTH) as f32 * 0.05,
+          );
+        });
+
+      let hint = match (selection.first_picked, selection.preview) {
+        (None, _) => tr("fuse.pick"),
+        (Some(_), Some(preview)) => tr_args(
+          "fuse.result",
+          &[
+            ("module", debug_module_symbol(preview.kind).to_string()),
+            ("level", preview.level.to_string()),
+          ],
+        ),
+        (Some(_), None) => tr("fuse.no_recipe"),
+      };
+      draw_ui_text(
+        &hint,
+        0.5 * screen_width(),
+        0.85 * screen_height(),
+        30.0,
+        palette().color_1,
       );
     }
     /* MARK: Inventory pick slot */
@@ -530,19 +2285,18 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
         screen_height() * 0.4,
         screen_width() * 0.5,
         screen_height() * 0.5,
-        COLOR_2,
+        palette().color_2,
       );
 
-      draw_text(
-        if menu.cursor_position.x == 0 && menu.cursor_position.y == -1 {
-          "-confirm-"
-        } else {
-          "confirm"
-        },
+      draw_ui_text(
+        &option_text(
+          "inventory.confirm",
+          menu.cursor_position.x == 0 && menu.cursor_position.y == -1,
+        ),
         0.5 * screen_width(),
         0.45 * screen_height(),
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
 
       (0..4).for_each(|x| {
@@ -552,7 +2306,7 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
             (0.5 + (y as f32 * 0.05)) * screen_height(),
             0.05 * screen_width(),
             0.05 * screen_height(),
-            COLOR_3,
+            palette().color_3,
           );
 
           draw_rectangle(
@@ -560,7 +2314,7 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
             (0.51 + (y as f32 * 0.05)) * screen_height(),
             0.03 * screen_width(),
             0.03 * screen_height(),
-            COLOR_2,
+            palette().color_2,
           );
         })
       });
@@ -571,7 +2325,7 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
           (0.5 + (menu.cursor_position.y as f32 * 0.05)) * screen_height(),
           0.05 * screen_width(),
           0.05 * screen_height(),
-          COLOR_3,
+          palette().color_3,
         );
 
         let hovering_module = if menu.cursor_position.x < EQUIP_SLOTS_WIDTH {
@@ -588,16 +2342,24 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
         };
 
         if let Some(hovering_module) = hovering_module {
-          debug_module_text(hovering_module)
+          module_description(hovering_module.kind)
+            .into_iter()
+            .chain((hovering_module.level > 0).then(|| {
+              tr_args(
+                "inventory.level",
+                &[("level", hovering_module.level.to_string())],
+              )
+            }))
+            .collect::<Vec<_>>()
             .iter()
             .enumerate()
             .for_each(|(index, text)| {
-              draw_text(
+              draw_ui_text(
                 text,
                 0.5 * screen_width(),
                 (0.8 + (index as f32 * 0.02)) * screen_height(),
                 25.0,
-                COLOR_1,
+                palette().color_1,
               );
             });
         }
@@ -608,20 +2370,14 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
         .iter()
         .enumerate()
         .for_each(|(index, &equipped_module)| {
-          if let Some(module_kind) = equipped_module {
+          if let Some(module) = equipped_module {
             let module_x = (index as i32 % EQUIP_SLOTS_WIDTH) as f32 * 0.05;
             let module_y = (index as i32 / EQUIP_SLOTS_WIDTH) as f32 * 0.05;
 
-            draw_text(
-              debug_module_symbol(module_kind),
-              (0.5113 + (module_x)) * screen_width(),
-              (0.535 + (module_y)) * screen_height(),
-              30.0,
-              COLOR_1,
-            );
+            draw_module_symbol(module, module_x, module_y);
 
             if let WeaponModule::Modulator(_, attachment_points) =
-              weapon_module_from_kind(module_kind)
+              weapon_module_from_kind(module.kind)
             {
               attachment_points
                 .iter()
@@ -632,7 +2388,7 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
                       (0.51 + module_y) * screen_height(),
                       0.01 * screen_width(),
                       0.005 * screen_height(),
-                      COLOR_4,
+                      palette().color_4,
                     );
                   }
                   Direction::Down => {
@@ -641,7 +2397,7 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
                       (0.535 + module_y) * screen_height(),
                       0.01 * screen_width(),
                       0.005 * screen_height(),
-                      COLOR_4,
+                      palette().color_4,
                     );
                   }
                   Direction::Left => {
@@ -650,7 +2406,7 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
                       (0.52 + module_y) * screen_height(),
                       0.005 * screen_width(),
                       0.01 * screen_height(),
-                      COLOR_4,
+                      palette().color_4,
                     );
                   }
                   Direction::Right => {
@@ -659,7 +2415,7 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
                       (0.52 + module_y) * screen_height(),
                       0.005 * screen_width(),
                       0.01 * screen_height(),
-                      COLOR_4,
+                      palette().color_4,
                     );
                   }
                 });
@@ -671,20 +2427,14 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
         .unequipped_modules
         .iter()
         .enumerate()
-        .for_each(|(index, &unequipped_module_kind)| {
+        .for_each(|(index, &unequipped_module)| {
           let module_x = (EQUIP_SLOTS_WIDTH + (index as i32 % INVENTORY_WRAP_WIDTH)) as f32 * 0.05;
           let module_y = (index as i32 / INVENTORY_WRAP_WIDTH) as f32 * 0.05;
 
-          draw_text(
-            debug_module_symbol(unequipped_module_kind),
-            (0.5113 + (module_x)) * screen_width(),
-            (0.535 + (module_y)) * screen_height(),
-            30.0,
-            COLOR_1,
-          );
+          draw_module_symbol(unequipped_module, module_x, module_y);
 
           if let WeaponModule::Modulator(_, attachment_points) =
-            weapon_module_from_kind(unequipped_module_kind)
+            weapon_module_from_kind(unequipped_module.kind)
           {
             attachment_points
               .iter()
@@ -695,7 +2445,7 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
                     (0.51 + module_y) * screen_height(),
                     0.01 * screen_width(),
                     0.005 * screen_height(),
-                    COLOR_4,
+                    palette().color_4,
                   );
                 }
                 Direction::Down => {
@@ -704,7 +2454,7 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
                     (0.535 + module_y) * screen_height(),
                     0.01 * screen_width(),
                     0.005 * screen_height(),
-                    COLOR_4,
+                    palette().color_4,
                   );
                 }
                 Direction::Left => {
@@ -713,7 +2463,7 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
                     (0.52 + module_y) * screen_height(),
                     0.005 * screen_width(),
                     0.01 * screen_height(),
-                    COLOR_4,
+                    palette().color_4,
                   );
                 }
                 Direction::Right => {
@@ -722,7 +2472,7 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
                     (0.52 + module_y) * screen_height(),
                     0.005 * screen_width(),
                     0.01 * screen_height(),
-                    COLOR_4,
+                    palette().color_4,
                   );
                 }
               });
@@ -736,31 +2486,31 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
         screen_height() * 0.45,
         screen_width() * 0.4,
         screen_height() * 0.1,
-        COLOR_2,
+        palette().color_2,
       );
 
-      draw_text(
-        "Cancel",
+      draw_ui_text(
+        &tr("save_confirm.cancel"),
         0.4 * screen_width(),
         0.5 * screen_height(),
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
 
-      draw_text(
-        "Save",
+      draw_ui_text(
+        &tr("save_confirm.save"),
         0.6 * screen_width(),
         0.5 * screen_height(),
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
 
-      draw_text(
+      draw_ui_text(
         "-",
         (0.4 + (menu.cursor_position.x as f32 * 0.2)) * screen_width(),
         0.53 * screen_height(),
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
     }
     crate::menu::GameMenuKind::ModulePickupConfirm(weapon_module_kind) => {
@@ -769,34 +2519,32 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
         screen_height() * 0.4,
         screen_width() * 0.4,
         screen_height() * 0.15,
-        COLOR_2,
+        palette().color_2,
       );
 
-      draw_text(
-        &format!(
-          "{} {} aquired",
+      draw_ui_text(
+        &tr_args(
           match weapon_module_from_kind(weapon_module_kind) {
-            WeaponModule::Generator(_) => {
-              "Weapon"
-            }
-            WeaponModule::Modulator(_, _) => {
-              "Modifier"
-            }
+            WeaponModule::Generator(_) => "pickup.weapon",
+            WeaponModule::Modulator(_, _) => "pickup.modifier",
           },
-          debug_module_symbol(weapon_module_kind)
+          &[(
+            "module",
+            debug_module_symbol(weapon_module_kind).to_string(),
+          )],
         ),
         0.4 * screen_width(),
         0.45 * screen_height(),
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
 
-      draw_text(
-        "-edit-",
+      draw_ui_text(
+        &option_text("inventory.edit", true),
         0.4 * screen_width(),
         0.5 * screen_height(),
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
     }
     crate::menu::GameMenuKind::AbilityPickupConfirm(ability) => {
@@ -805,87 +2553,244 @@ fn draw_menu(menu: &GameMenu, available_sava_data: &[String]) {
         screen_height() * 0.4,
         screen_width() * 0.4,
         screen_height() * 0.15,
-        COLOR_2,
+        palette().color_2,
       );
 
-      draw_text(
-        &format!(
-          "Ability {} aquired",
-          match ability {
-            crate::load_map::MapAbilityType::Boost => "BOOST",
-            crate::load_map::MapAbilityType::Chain => "CHAIN",
-          },
-        ),
+      draw_ui_text(
+        &tr_args("pickup.ability", &[("ability", tr(ability_key(ability)))]),
         0.4 * screen_width(),
         0.45 * screen_height(),
         40.0,
-        COLOR_1,
+        palette().color_1,
       );
 
-      draw_text(
-        "-close-",
+      draw_ui_text(
+        &option_text("inventory.close", true),
         0.4 * screen_width(),
         0.5 * screen_height(),
         40.0,
-        COLOR_1,
+        palette().color_1,
+      );
+    }
+    crate::menu::GameMenuKind::ScoreResults(results) => {
+      draw_rectangle(
+        screen_width() * 0.3,
+        screen_height() * 0.3,
+        screen_width() * 0.4,
+        screen_height() * 0.35,
+        palette().color_2,
+      );
+
+      [
+        tr("results.title"),
+        tr_args("results.score", &[("score", results.score.to_string())]),
+        tr_args(
+          "results.best_combo",
+          &[("combo", results.best_combo.to_string())],
+        ),
+        if results.new_high_score() {
+          tr("results.new_high_score")
+        } else {
+          tr_args(
+            "results.high_score",
+            &[("score", results.high_score.unwrap_or(0).to_string())],
+          )
+        },
+      ]
+      .iter()
+      .enumerate()
+      .for_each(|(index, line)| {
+        draw_ui_text(
+          line,
+          0.35 * screen_width(),
+          (0.36 + 0.06 * index as f32) * screen_height(),
+          40.0,
+          palette().color_1,
+        );
+      });
+
+      draw_ui_text(
+        &option_text("inventory.close", true),
+        0.35 * screen_width(),
+        0.61 * screen_height(),
+        40.0,
+        palette().color_1,
+      );
+    }
+    crate::menu::GameMenuKind::ChallengeResults(result) => {
+      draw_rectangle(
+        screen_width() * 0.3,
+        screen_height() * 0.3,
+        screen_width() * 0.4,
+        screen_height() * 0.35,
+        palette().color_2,
+      );
+
+      let medal_line = match result.medal {
+        Some(medal) if Some(medal) > result.best => {
+          tr_args("challenge.new_best", &[("medal", tr(medal_key(medal)))])
+        }
+        Some(medal) => tr_args("challenge.medal", &[("medal", tr(medal_key(medal)))]),
+        None => tr("challenge.no_medal"),
+      };
+
+      [
+        tr("challenge.title"),
+        tr_args("challenge.time", &[("time", format_run_time(result.time))]),
+        medal_line,
+      ]
+      .into_iter()
+      .chain(
+        result
+          .failed_conditions
+          .iter()
+          .map(|condition| match condition {
+            ChallengeCondition::NoDamage => tr("challenge.failed.no_damage"),
+            ChallengeCondition::AllKills => tr("challenge.failed.all_kills"),
+          }),
+      )
+      .enumerate()
+      .for_each(|(index, line)| {
+        draw_ui_text(
+          &line,
+          0.35 * screen_width(),
+          (0.36 + 0.05 * index as f32) * screen_height(),
+          if index == 0 { 40.0 } else { 30.0 },
+          result
+            .medal
+            .filter(|_| index == 2)
+            .map_or(palette().color_1, medal_color),
+        );
+      });
+
+      draw_ui_text(
+        &option_text("inventory.close", true),
+        0.35 * screen_width(),
+        0.61 * screen_height(),
+        40.0,
+        palette().color_1,
+      );
+    }
+    crate::menu::GameMenuKind::DailyResults(result) => {
+      draw_rectangle(
+        screen_width() * 0.3,
+        screen_height() * 0.3,
+        screen_width() * 0.4,
+        screen_height() * 0.35,
+        palette().color_2,
+      );
+
+      [
+        tr_args("daily.title", &[("seed", result.run.seed.to_string())]),
+        tr_args("results.score", &[("score", result.run.score.to_string())]),
+        tr_args("daily.time", &[("time", format_run_time(result.run.time))]),
+        tr_args(
+          "results.best_combo",
+          &[("combo", result.run.best_combo.to_string())],
+        ),
+        match &result.best {
+          Some(best) if !result.new_best() => tr_args(
+            "daily.best",
+            &[
+              ("score", best.score.to_string()),
+              ("time", format_run_time(best.time)),
+            ],
+          ),
+          _ => tr("daily.new_best"),
+        },
+      ]
+      .iter()
+      .enumerate()
+      .for_each(|(index, line)| {
+        draw_ui_text(
+          line,
+          0.35 * screen_width(),
+          (0.36 + 0.05 * index as f32) * screen_height(),
+          if index == 0 { 40.0 } else { 30.0 },
+          palette().color_1,
+        );
+      });
+
+      draw_ui_text(
+        &option_text("daily.finish", true),
+        0.35 * screen_width(),
+        0.61 * screen_height(),
+        40.0,
+        palette().color_1,
       );
     }
     crate::menu::GameMenuKind::GameOver => {
-      draw_rectangle(0.0, 0.0, screen_width(), screen_height(), COLOR_4);
+      draw_rectangle(0.0, 0.0, screen_width(), screen_height(), palette().color_4);
 
-      draw_text(
-        "GAME OVER",
-        0.4 * screen_width(),
+      draw_ui_text_aligned(
+        &tr("game_over"),
+        0.5 * screen_width(),
         0.6 * screen_height(),
         40.0,
-        COLOR_1,
+        palette().color_1,
+        Align::Center,
       );
     }
   }
 }
 
+/* A module's symbol in its inventory cell, with how far it's been levelled up under it */
+fn draw_module_symbol(module: ModuleInstance, module_x: f32, module_y: f32) {
+  draw_ui_text(
+    debug_module_symbol(module.kind),
+    (0.5113 + module_x) * screen_width(),
+    (0.535 + module_y) * screen_height(),
+    30.0,
+    palette().color_1,
+  );
+
+  if module.level > 0 {
+    draw_ui_text(
+      &format!("+{}", module.level),
+      (0.5113 + module_x) * screen_width(),
+      (0.55 + module_y) * screen_height(),
+      20.0,
+      palette().color_4,
+    );
+  }
+}
+
 fn debug_module_symbol(module_kind: WeaponModuleKind) -> &'static str {
   match module_kind {
     WeaponModuleKind::Plasma => "PLAS",
     WeaponModuleKind::Missile => "MISL",
+    WeaponModuleKind::Drone => "DRON",
     WeaponModuleKind::DoubleDamage75Freq => "D75F",
     WeaponModuleKind::DoubleFreq75Damage => "F75D",
     WeaponModuleKind::Front2Slot => "2FSL",
     WeaponModuleKind::FortyFiveSlot => "45SL",
     WeaponModuleKind::SideSlot => "SDSL",
     WeaponModuleKind::MirrorSlot => "RVSL",
+    WeaponModuleKind::CritChance75Freq => "C75F",
+    WeaponModuleKind::CritChance50Freq => "C50F",
+    WeaponModuleKind::HeatSink => "HSNK",
+    WeaponModuleKind::Overcharge => "OVCH",
   }
 }
 
-fn debug_module_text(module_kind: WeaponModuleKind) -> Vec<&'static str> {
-  match module_kind {
-    WeaponModuleKind::Plasma => vec!["weapon; shoots moderately fast with moderate damage"],
-    WeaponModuleKind::Missile => {
-      vec![
-        "weapon; shoots slowly and accelerates after firing, with high damage",
-        "and an explosion on impact",
-      ]
-    }
-    WeaponModuleKind::DoubleDamage75Freq => {
-      vec!["modifier; doubles damage but reduces frequency by 25%"]
-    }
-    WeaponModuleKind::DoubleFreq75Damage => {
-      vec!["modifier; doubles frequency but reduces damage by 25%"]
-    }
-    WeaponModuleKind::Front2Slot => {
-      vec!["modifier; allows weapon to fire from the front two projectile slots"]
-    }
-    WeaponModuleKind::FortyFiveSlot => {
-      vec!["modifier; allows weapon to fire from the front diagonal projectile slots"]
-    }
-    WeaponModuleKind::SideSlot => {
-      vec!["modifier; allows weapon to fire from the side projectile slots"]
-    }
-    WeaponModuleKind::MirrorSlot => {
-      vec![
-        "modifier; allows weapon to fire from the reverse equivalents of any",
-        "front slots it currently fires from",
-      ]
-    }
-  }
+/* A module's description in the inventory, each line of its table entry on a line of its own */
+fn module_description(module_kind: WeaponModuleKind) -> Vec<String> {
+  tr(match module_kind {
+    WeaponModuleKind::Plasma => "module.plasma",
+    WeaponModuleKind::Missile => "module.missile",
+    WeaponModuleKind::Drone => "module.drone",
+    WeaponModuleKind::DoubleDamage75Freq => "module.double_damage_75_freq",
+    WeaponModuleKind::DoubleFreq75Damage => "module.double_freq_75_damage",
+    WeaponModuleKind::Front2Slot => "module.front_2_slot",
+    WeaponModuleKind::FortyFiveSlot => "module.forty_five_slot",
+    WeaponModuleKind::SideSlot => "module.side_slot",
+    WeaponModuleKind::MirrorSlot => "module.mirror_slot",
+    WeaponModuleKind::CritChance75Freq => "module.crit_chance_75_freq",
+    WeaponModuleKind::CritChance50Freq => "module.crit_chance_50_freq",
+    WeaponModuleKind::HeatSink => "module.heat_sink",
+    WeaponModuleKind::Overcharge => "module.overcharge",
+  })
+  .lines()
+  .map(str::to_string)
+  .collect()
 }
@@ -2,7 +2,7 @@ use macroquad::prelude::*;
 use rapier2d::{na::Vector2, prelude::*};
 
 use crate::{
-  graphics::{COLOR_2, COLOR_3, COLOR_4},
+  palette::palette,
   units::{PhysicsScalar, PhysicsVector, ScreenVector, UnitConvert, UnitConvert2},
 };
 
@@ -18,7 +18,7 @@ pub fn draw_label(
     screen_translation.x(),
     screen_translation.y(),
     20.0,
-    color.unwrap_or(COLOR_4),
+    color.unwrap_or(palette().color_4),
   );
 }
 
@@ -62,7 +62,7 @@ pub fn draw_collider(
         DrawRectangleParams {
           offset: Vec2 { x: 0.5, y: 0.5 },
           rotation: -rotation,
-          color: color.unwrap_or(COLOR_3).with_alpha(alpha),
+          color: color.unwrap_or(palette().color_3).with_alpha(alpha),
         },
       );
 
@@ -72,7 +72,7 @@ pub fn draw_collider(
           top_left.x(),
           top_left.y(),
           40.0,
-          color.unwrap_or(COLOR_4).with_alpha(alpha),
+          color.unwrap_or(palette().color_4).with_alpha(alpha),
         );
       };
     }
@@ -83,7 +83,7 @@ pub fn draw_collider(
       translation.x(),
       translation.y(),
       *PhysicsScalar(ball.radius).convert(),
-      color.unwrap_or(COLOR_2).with_alpha(alpha),
+      color.unwrap_or(palette().color_2).with_alpha(alpha),
     );
 
     if let Some(label) = label.as_ref() {
@@ -92,7 +92,7 @@ pub fn draw_collider(
         translation.x(),
         translation.y(),
         40.0,
-        color.unwrap_or(COLOR_4).with_alpha(alpha),
+        color.unwrap_or(palette().color_4).with_alpha(alpha),
       );
     };
   }
@@ -126,10 +126,171 @@ pub fn draw_collider(
             top_left.y,
             dimensions.x,
             dimensions.y,
-            color.unwrap_or(COLOR_3).with_alpha(alpha),
+            color.unwrap_or(palette().color_3).with_alpha(alpha),
           );
         }
       }
     });
   }
 }
+
+const JOINT_ANCHOR_RADIUS: f32 = 3.0;
+/* Length of the rays showing revolute limits and motor targets */
+const JOINT_ANGLE_RAY_LENGTH: f32 = 0.5;
+
+fn draw_physics_line(
+  from: Vector2<f32>,
+  to: Vector2<f32>,
+  camera_position: Vector2<f32>,
+  color: Color,
+) {
+  let from = PhysicsVector::from_vec(from).into_pos(camera_position);
+  let to = PhysicsVector::from_vec(to).into_pos(camera_position);
+
+  draw_line(from.x(), from.y(), to.x(), to.y(), 1.0, color);
+}
+
+fn joint_anchors(
+  joint: &ImpulseJoint,
+  rigid_body_set: &RigidBodySet,
+) -> (Vector2<f32>, Vector2<f32>) {
+  (
+    (rigid_body_set[joint.body1].position() * joint.data.local_anchor1()).coords,
+    (rigid_body_set[joint.body2].position() * joint.data.local_anchor2()).coords,
+  )
+}
+
+/* Anchors tied back to their bodies, with prismatic limits drawn along the axis and revolute
+limits as rays, and the motor target marked on either */
+pub fn draw_joint(
+  joint: &ImpulseJoint,
+  rigid_body_set: &RigidBodySet,
+  camera_position: Vector2<f32>,
+) {
+  let body1 = &rigid_body_set[joint.body1];
+  let body2 = &rigid_body_set[joint.body2];
+  let (anchor1, anchor2) = joint_anchors(joint, rigid_body_set);
+
+  draw_physics_line(
+    *body1.translation(),
+    anchor1,
+    camera_position,
+    palette().color_3,
+  );
+  draw_physics_line(
+    *body2.translation(),
+    anchor2,
+    camera_position,
+    palette().color_3,
+  );
+  draw_physics_line(anchor1, anchor2, camera_position, palette().color_4);
+
+  [anchor1, anchor2].into_iter().for_each(|anchor| {
+    let anchor = PhysicsVector::from_vec(anchor).into_pos(camera_position);
+    draw_circle_lines(
+      anchor.x(),
+      anchor.y(),
+      JOINT_ANCHOR_RADIUS,
+      1.0,
+      palette().color_4,
+    );
+  });
+
+  let frame_rotation = body1.rotation() * joint.data.local_frame1.rotation;
+
+  if joint.data.as_prismatic().is_some() {
+    let axis = frame_rotation * vector![1.0, 0.0];
+
+    if let Some(limits) = joint.data.limits(JointAxis::LinX) {
+      draw_physics_line(
+        anchor1 + axis * limits.min,
+        anchor1 + axis * limits.max,
+        camera_position,
+        palette().color_2,
+      );
+    }
+
+    if let Some(motor) = joint.data.motor(JointAxis::LinX) {
+      let target =
+        PhysicsVector::from_vec(anchor1 + axis * motor.target_pos).into_pos(camera_position);
+      draw_circle(
+        target.x(),
+        target.y(),
+        JOINT_ANCHOR_RADIUS,
+        palette().color_2,
+      );
+    }
+  }
+
+  if joint.data.as_revolute().is_some() {
+    let ray = |angle: f32| {
+      anchor1 + Rotation::new(frame_rotation.angle() + angle) * vector![JOINT_ANGLE_RAY_LENGTH, 0.0]
+    };
+
+    if let Some(limits) = joint.data.limits(JointAxis::AngX) {
+      draw_physics_line(anchor1, ray(limits.min), camera_position, palette().color_2);
+      draw_physics_line(anchor1, ray(limits.max), camera_position, palette().color_2);
+    }
+
+    if let Some(motor) = joint.data.motor(JointAxis::AngX) {
+      draw_physics_line(
+        anchor1,
+        ray(motor.target_pos),
+        camera_position,
+        palette().color_4,
+      );
+    }
+  }
+}
+
+/* What the inspector prints for a joint */
+pub fn joint_state_lines(
+  handle: ImpulseJointHandle,
+  joint: &ImpulseJoint,
+  rigid_body_set: &RigidBodySet,
+) -> Vec<String> {
+  let kind = if joint.data.as_prismatic().is_some() {
+    "prismatic"
+  } else if joint.data.as_revolute().is_some() {
+    "revolute"
+  } else {
+    "generic"
+  };
+
+  let (anchor1, anchor2) = joint_anchors(joint, rigid_body_set);
+  let free_axis = if kind == "revolute" {
+    JointAxis::AngX
+  } else {
+    JointAxis::LinX
+  };
+
+  [
+    Some(format!(
+      "{} {:?}: {:?} -> {:?}",
+      kind,
+      handle.into_raw_parts(),
+      joint.body1.into_raw_parts(),
+      joint.body2.into_raw_parts()
+    )),
+    Some(format!(
+      "  stretch {:.3} impulse ({:.3}, {:.3}, {:.3})",
+      (anchor1 - anchor2).magnitude(),
+      joint.impulses.x,
+      joint.impulses.y,
+      joint.impulses.z
+    )),
+    joint
+      .data
+      .limits(free_axis)
+      .map(|limits| format!("  limits [{:.2}, {:.2}]", limits.min, limits.max)),
+    joint.data.motor(free_axis).map(|motor| {
+      format!(
+        "  motor target {:.2} vel {:.2} impulse {:.3}",
+        motor.target_pos, motor.target_vel, motor.impulse
+      )
+    }),
+  ]
+  .into_iter()
+  .flatten()
+  .collect()
+}
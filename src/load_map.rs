@@ -1,52 +1,298 @@
 use std::{
-  env::{current_dir, current_exe},
+  env::current_dir,
   f32::consts::PI,
   fs,
-  path::Path,
+  path::{Path, PathBuf},
   rc::Rc,
+  sync::mpsc::{self, Receiver, TryRecvError},
+  thread,
+  time::SystemTime,
 };
 
+use itertools::Itertools;
+use macroquad::rand::RandGenerator;
 use rapier2d::{
   na::{Unit, Vector2},
   prelude::*,
 };
 use rpds::HashTrieMap;
-use serde::Deserialize;
-use serde_literals::lit_str;
+use serde::{
+  Deserialize, Serialize,
+  de::{DeserializeOwned, IntoDeserializer},
+};
+use serde_json::{Map as JsonMap, Value};
 
 use crate::{
+  collision::{CollisionLayer, groups, groups_except, interactions},
   combat::{WeaponModuleKind, distance_projection_physics},
-  ecs::{ComponentSet, Damageable, Damager, DropHealthOnDestroy, Enemy},
+  contact_rules::one_way,
+  crash::record_event,
+  data::EnemyDefinitions,
+  ecs::{
+    Boss, ComponentSet, Damageable, Damager, DeathAnimation, Elite, EliteAffix, Enemy,
+    ExplodeOnCollision, ExplodeOnDeath, Faction, HazardKind, LootDrop, LootEntry, LootTable,
+    Persistent,
+  },
   f::MonadTranslate,
+  log,
   physics::PhysicsSystem,
-  save::SaveData,
-  system::System,
+  save::{Difficulty, SaveData},
+  system::{Requirement, System},
+  tutorial::TutorialAction,
   units::{PhysicsScalar, PhysicsVector, UnitConvert2, vec_zero},
 };
 
+/* MARK: Tiled map format */
+
+const TILED_FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
+const TILED_FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
+const TILED_FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
+const TILED_ROTATED_HEXAGONAL_120_FLAG: u32 = 0x10000000;
+
+/* Tiles from this tileset are the only ones which produce walls */
+const COLLIDER_TILESET_NAME: &str = "colliders";
+
+fn map_warning(message: String) {
+  log::warn("map", message);
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct TilesetReference {
+  firstgid: u32,
+  #[serde(default)]
+  source: Option<String>,
+  #[serde(default)]
+  name: Option<String>,
+}
+
+impl TilesetReference {
+  fn is_collider_tileset(&self) -> bool {
+    self
+      .source
+      .as_ref()
+      .and_then(|source| Path::new(source).file_stem())
+      .and_then(|file_stem| file_stem.to_str())
+      .or(self.name.as_deref())
+      .is_some_and(|name| name == COLLIDER_TILESET_NAME)
+  }
+}
+
 #[derive(Clone, Debug, Deserialize)]
-enum TileLayerName {
-  Colliders,
+#[serde(untagged)]
+enum TileData {
+  Csv(Vec<u32>),
+  Encoded(String),
 }
 
 #[derive(Clone, Debug, Deserialize)]
 struct TileLayer {
-  data: Vec<i32>,
+  name: String,
+  #[serde(default)]
+  data: Option<TileData>,
+  #[serde(default)]
   height: i32,
+  #[serde(default)]
   width: i32,
-  name: TileLayerName,
 }
 
-lit_str!(EnemySpawnTemplatePath, "templates/EnemySpawn.tx");
+#[derive(Clone, Debug, Deserialize)]
+struct ObjectLayer {
+  objects: Vec<Value>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct GroupLayer {
+  layers: Vec<Layer>,
+}
 
 #[derive(Clone, Debug, Deserialize)]
-#[serde(untagged)]
-enum EnemySpawnTemplate {
-  #[serde(with = "EnemySpawnTemplatePath")]
-  Path,
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Layer {
+  TileLayer(TileLayer),
+  ObjectGroup(ObjectLayer),
+  Group(GroupLayer),
+  #[serde(other)]
+  Unknown,
+}
+
+impl Layer {
+  fn flatten(&self) -> Vec<&Layer> {
+    match self {
+      Layer::Group(group) => group.layers.iter().flat_map(Layer::flatten).collect(),
+      layer => vec![layer],
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Template {
+  object: JsonMap<String, Value>,
+}
+
+fn xml_unescape(value: &str) -> String {
+  value
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&amp;", "&")
+}
+
+/* Reads the attributes of the first `<tag ...>` element found in the given xml */
+fn xml_attributes(xml: &str) -> Vec<(String, String)> {
+  let mut attributes = Vec::new();
+  let mut rest = xml;
+
+  while let Some(equals_index) = rest.find("=\"") {
+    let key = rest[..equals_index]
+      .rsplit(|c: char| c.is_whitespace())
+      .next()
+      .unwrap_or("")
+      .to_string();
+    let value_start = equals_index + 2;
+    let Some(value_length) = rest[value_start..].find('"') else {
+      break;
+    };
+    attributes.push((
+      key,
+      xml_unescape(&rest[value_start..value_start + value_length]),
+    ));
+    rest = &rest[value_start + value_length + 1..];
+  }
+
+  attributes
+}
+
+fn xml_elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+  xml
+    .match_indices(&format!("<{} ", tag))
+    .filter_map(|(start, _)| {
+      xml[start..]
+        .find('>')
+        .map(|end| &xml[start + tag.len() + 1..start + end])
+    })
+    .collect()
+}
+
+fn property_value_from_str(property_type: Option<&str>, value: &str) -> Value {
+  match property_type {
+    Some("int") | Some("object") => value.parse::<i64>().map(Value::from).ok(),
+    Some("float") => value.parse::<f64>().map(Value::from).ok(),
+    Some("bool") => value.parse::<bool>().map(Value::from).ok(),
+    _ => None,
+  }
+  .unwrap_or(Value::from(value))
+}
+
+fn load_template(template_path: &Path) -> Option<Template> {
+  let xml = fs::read_to_string(template_path).ok()?;
+
+  let object_element = xml_elements(&xml, "object").into_iter().next()?;
+
+  let object = xml_attributes(object_element)
+    .into_iter()
+    .map(|(key, value)| match key.as_str() {
+      "x" | "y" | "width" | "height" | "rotation" => {
+        (key, property_value_from_str(Some("float"), &value))
+      }
+      _ => (key, Value::from(value)),
+    })
+    .chain([(
+      "properties".to_string(),
+      Value::Array(
+        xml_elements(&xml, "property")
+          .into_iter()
+          .map(|property_element| {
+            let attributes = xml_attributes(property_element);
+            let attribute = |name: &str| {
+              attributes
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+            };
+            serde_json::json!({
+              "name": attribute("name").unwrap_or_default(),
+              "value": property_value_from_str(
+                attribute("type").as_deref(),
+                &attribute("value").unwrap_or_default(),
+              ),
+            })
+          })
+          .collect(),
+      ),
+    )])
+    .collect();
+
+  Some(Template { object })
+}
+
+/* Converts a Tiled `[{ name, type, value }]` property list into a `{ name: value }` object */
+fn properties_as_object(properties: Option<&Value>) -> JsonMap<String, Value> {
+  properties
+    .and_then(Value::as_array)
+    .into_iter()
+    .flatten()
+    .filter_map(|property| {
+      Some((
+        property.get("name")?.as_str()?.to_string(),
+        property.get("value")?.clone(),
+      ))
+    })
+    .collect()
+}
+
+/* Merges the object with its template (if any) and resolves its type, returning the type name
+ * and an object whose properties are keyed by name */
+fn normalize_object(object: &Value, map_directory: &Path) -> Option<(String, Value)> {
+  let object = object.as_object()?;
+
+  let template = object
+    .get("template")
+    .and_then(Value::as_str)
+    .map(|template_path| {
+      load_template(&map_directory.join(template_path)).unwrap_or_else(|| {
+        map_warning(format!("could not load object template {}", template_path));
+        Template::default()
+      })
+    })
+    .unwrap_or_default();
+
+  let properties = properties_as_object(template.object.get("properties"))
+    .into_iter()
+    .chain(properties_as_object(object.get("properties")))
+    .collect::<JsonMap<_, _>>();
+
+  let merged = template
+    .object
+    .into_iter()
+    .chain(object.clone())
+    .chain([("properties".to_string(), Value::Object(properties))])
+    .collect::<JsonMap<_, _>>();
+
+  let object_type = ["type", "class"]
+    .iter()
+    .filter_map(|key| merged.get(*key).and_then(Value::as_str))
+    .find(|object_type| !object_type.is_empty())
+    .map(|object_type| object_type.split_whitespace().collect::<String>());
+
+  match object_type {
+    Some(object_type) => Some((object_type, Value::Object(merged))),
+    None => {
+      map_warning(format!(
+        "object {} has no type and will be skipped",
+        merged.get("id").unwrap_or(&Value::Null)
+      ));
+      None
+    }
+  }
 }
 
+/* Scripted enemies are named with this and then their script, as in "Scripted:drifter", so a
+misspelled built in enemy fails to load rather than quietly becoming an idle scripted one */
+const SCRIPTED_ENEMY_PREFIX: &str = "Scripted:";
+
 #[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
 pub enum MapEnemyName {
   /* Dragonspawn */
   Goblin,
@@ -54,14 +300,59 @@ pub enum MapEnemyName {
   Defender,
   Seeker,
   SeekerGenerator,
+  Minelayer,
+  Swarmer,
+  /* Named after its behavior script */
+  Scripted(String),
+}
+
+impl TryFrom<String> for MapEnemyName {
+  type Error = String;
+
+  fn try_from(name: String) -> Result<Self, Self::Error> {
+    match name.as_str() {
+      "Goblin" => Ok(Self::Goblin),
+      "Defender" => Ok(Self::Defender),
+      "Seeker" => Ok(Self::Seeker),
+      "SeekerGenerator" => Ok(Self::SeekerGenerator),
+      "Minelayer" => Ok(Self::Minelayer),
+      "Swarmer" => Ok(Self::Swarmer),
+      _ => name
+        .strip_prefix(SCRIPTED_ENEMY_PREFIX)
+        .filter(|script| !script.is_empty())
+        .map(|script| Self::Scripted(script.to_string()))
+        .ok_or_else(|| {
+          format!(
+            "unknown enemy {}, scripted enemies are named {}<script>",
+            name, SCRIPTED_ENEMY_PREFIX
+          )
+        }),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MapEnemySpawnProperties {
+  /* Once killed, never respawns when the room is revisited */
+  #[serde(default)]
+  persistent: bool,
+  /* Comma separated affixes, as in "Armored, Fast" */
+  #[serde(default)]
+  elite: String,
+  /* Enemies in different groups fight each other */
+  #[serde(default)]
+  group: Option<i32>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 struct MapEnemySpawn {
+  id: i32,
   x: f32,
   y: f32,
   name: MapEnemyName,
-  template: EnemySpawnTemplate,
+  #[serde(default)]
+  properties: MapEnemySpawnProperties,
 }
 
 impl MapEnemySpawn {
@@ -70,30 +361,33 @@ impl MapEnemySpawn {
       self.x * 0.125 * TILE_DIMENSION_PHYSICS,
       (map_height - self.y) * 0.125 * TILE_DIMENSION_PHYSICS
     ]);
-    EnemySpawn::new(&self.name, translation.into_vec())
+    EnemySpawn {
+      persistent_id: self.properties.persistent.then_some(self.id),
+      group: self.properties.group,
+      affixes: self
+        .properties
+        .elite
+        .split(',')
+        .map(str::trim)
+        .filter(|affix| !affix.is_empty())
+        .filter_map(|affix| {
+          EliteAffix::deserialize(affix.into_deserializer())
+            .inspect_err(|_: &serde::de::value::Error| {
+              map_warning(format!("enemy {} has a bad elite affix {}", self.id, affix))
+            })
+            .ok()
+        })
+        .collect(),
+      ..EnemySpawn::new(&self.name, translation.into_vec())
+    }
   }
 }
 
-lit_str!(PlayerSpawnTemplatePath, "templates/Player Spawn.tx");
-
-#[derive(Clone, Debug, Deserialize)]
-#[serde(untagged)]
-enum PlayerSpawnTemplate {
-  #[serde(with = "PlayerSpawnTemplatePath")]
-  Path,
-}
-
 #[derive(Clone, Debug, Deserialize)]
 struct MapPlayerSpawn {
   id: i32,
   x: f32,
   y: f32,
-  template: PlayerSpawnTemplate,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-pub enum MapItemPickupClass {
-  ItemPickup,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -102,28 +396,12 @@ struct MapItemPickup {
   x: f32,
   y: f32,
   name: WeaponModuleKind,
-  #[serde(rename = "type")]
-  _class: MapItemPickupClass,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapMapTransitionTargetClass {
-  TargetPlayerSpawn,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-struct MapMapTransitionTarget {
-  name: MapMapTransitionTargetClass,
-  value: i32,
-}
-
-lit_str!(MapTransitionTemplatePath, "templates/MapTransition.tx");
-
-#[derive(Clone, Debug, Deserialize)]
-#[serde(untagged)]
-enum MapTransitionTemplate {
-  #[serde(with = "MapTransitionTemplatePath")]
-  Path,
+#[serde(rename_all = "PascalCase")]
+struct MapMapTransitionProperties {
+  target_player_spawn: i32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -132,40 +410,37 @@ struct MapMapTransition {
   y: f32,
   width: f32,
   height: f32,
+  #[serde(default)]
+  rotation: f32,
   name: String,
-  properties: (MapMapTransitionTarget,),
-  template: MapTransitionTemplate,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-enum MapSpawnPointTargetClass {
-  PlayerSpawnId,
+  properties: MapMapTransitionProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapSpawnPointTarget {
-  #[serde(rename = "name")]
-  _name: MapSpawnPointTargetClass,
-  value: i32,
+#[serde(rename_all = "PascalCase")]
+struct MapSavePointProperties {
+  player_spawn_id: i32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapSavePointClass {
-  SavePoint,
+struct MapSavePoint {
+  x: f32,
+  y: f32,
+  properties: MapSavePointProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapSavePoint {
+struct MapMountedTurret {
+  id: i32,
   x: f32,
   y: f32,
-  properties: (MapSpawnPointTarget,),
-  #[serde(rename = "type")]
-  _class: MapSavePointClass,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapBlockClass {
-  Block,
+struct MapExplosiveBarrel {
+  id: i32,
+  x: f32,
+  y: f32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -175,367 +450,361 @@ struct MapBlock {
   y: f32,
   width: f32,
   height: f32,
-  #[serde(rename = "type")]
-  _class: MapBlockClass,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-enum MapObject1IdClass {
-  Object1Id,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-struct MapObject1Id {
-  name: MapObject1IdClass,
-  value: i32,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-enum MapObject1LocalXClass {
-  Object1LocalX,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-struct MapObject1LocalX {
-  name: MapObject1LocalXClass,
-  value: f32,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-enum MapObject1LocalYClass {
-  Object1LocalY,
+  #[serde(default)]
+  rotation: f32,
+  #[serde(default)]
+  properties: MapBlockProperties,
 }
 
-#[derive(Clone, Debug, Deserialize)]
-struct MapObject1LocalY {
-  name: MapObject1LocalYClass,
-  value: f32,
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MapBlockProperties {
+  /* Solid only from the block's top side, so it can be pushed through from below */
+  #[serde(default)]
+  one_way: bool,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapObject2IdClass {
-  Object2Id,
+#[serde(rename_all = "PascalCase")]
+struct MapGlueProperties {
+  object1_id: i32,
+  object1_local_x: f32,
+  object1_local_y: f32,
+  object2_id: Option<i32>,
+  object2_local_x: Option<f32>,
+  object2_local_y: Option<f32>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapObject2Id {
-  name: MapObject2IdClass,
-  value: i32,
+struct MapGlue {
+  properties: MapGlueProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapObject2LocalXClass {
-  Object2LocalX,
+#[serde(rename_all = "PascalCase")]
+struct MapTouchSensorProperties {
+  target_activation: f32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapObject2LocalX {
-  name: MapObject2LocalXClass,
-  value: f32,
+struct MapTouchSensor {
+  id: i32,
+  x: f32,
+  y: f32,
+  width: f32,
+  height: f32,
+  #[serde(default)]
+  rotation: f32,
+  properties: MapTouchSensorProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapObject2LocalYClass {
-  Object2LocalY,
+#[serde(rename_all = "PascalCase")]
+struct MapGravitySourceProperties {
+  activator_id: Option<i32>,
+  radius: f32,
+  strength: f32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapObject2LocalY {
-  name: MapObject2LocalYClass,
-  value: f32,
+struct MapGravitySource {
+  x: f32,
+  y: f32,
+  properties: MapGravitySourceProperties,
 }
 
+/* Acceleration is in map directions, so positive Y falls down the screen */
 #[derive(Clone, Debug, Deserialize)]
-enum MapGlueClass {
-  Glue,
+#[serde(rename_all = "PascalCase")]
+struct MapGravityZoneProperties {
+  activator_id: Option<i32>,
+  #[serde(default)]
+  acceleration_x: f32,
+  #[serde(default)]
+  acceleration_y: f32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-#[serde(untagged)]
-enum MapGlueMapObjects {
-  MultiObject(
-    (
-      MapObject1Id,
-      MapObject1LocalX,
-      MapObject1LocalY,
-      MapObject2Id,
-      MapObject2LocalX,
-      MapObject2LocalY,
-    ),
-  ),
-  SingleObject((MapObject1Id, MapObject1LocalX, MapObject1LocalY)),
+struct MapGravityZone {
+  x: f32,
+  y: f32,
+  width: f32,
+  height: f32,
+  #[serde(default)]
+  rotation: f32,
+  properties: MapGravityZoneProperties,
 }
 
-#[derive(Clone, Debug, Deserialize)]
-struct MapGlue {
-  properties: MapGlueMapObjects,
-  #[serde(rename = "type")]
-  _class: MapGlueClass,
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum MapAbilityType {
+  Boost,
+  Chain,
+  Magnetize,
+  Hack,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapTouchSensorTargetActivationClass {
-  TargetActivation,
+struct MapAbilityPickup {
+  x: f32,
+  y: f32,
+  name: MapAbilityType,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapTouchSensorTargetActivation {
-  #[serde(rename = "name")]
-  _name: MapTouchSensorTargetActivationClass,
-  value: f32,
+#[serde(rename_all = "PascalCase")]
+struct MapHazardProperties {
+  kind: HazardKind,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapTouchSensor {
-  id: i32,
+struct MapHazard {
   x: f32,
   y: f32,
   width: f32,
   height: f32,
-  properties: (MapTouchSensorTargetActivation,),
-}
-
-#[derive(Clone, Debug, Deserialize)]
-enum MapGravitySourceRadiusClass {
-  Radius,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-struct MapGravitySourceRadius {
-  #[serde(rename = "name")]
-  _name: MapGravitySourceRadiusClass,
-  value: f32,
+  #[serde(default)]
+  rotation: f32,
+  properties: MapHazardProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapGravitySourceStrengthClass {
-  Strength,
+#[serde(rename_all = "PascalCase")]
+struct MapFluidVolumeProperties {
+  #[serde(default)]
+  drag: f32,
+  #[serde(default)]
+  buoyancy: f32,
+  #[serde(default = "default_movement_scale")]
+  movement_scale: f32,
 }
 
-#[derive(Clone, Debug, Deserialize)]
-struct MapGravitySourceStrength {
-  #[serde(rename = "name")]
-  _name: MapGravitySourceStrengthClass,
-  value: f32,
+fn default_movement_scale() -> f32 {
+  1.0
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapGravitySourceClass {
-  GravitySource,
+struct MapFluidVolume {
+  x: f32,
+  y: f32,
+  width: f32,
+  height: f32,
+  #[serde(default)]
+  rotation: f32,
+  properties: MapFluidVolumeProperties,
 }
 
+/* A wall the player can latch onto with Magnetize */
 #[derive(Clone, Debug, Deserialize)]
-struct MapGravitySource {
+struct MapMagneticWall {
   x: f32,
   y: f32,
-  properties: (
-    Option<MapActivatorId>,
-    MapGravitySourceRadius,
-    MapGravitySourceStrength,
-  ),
-  #[serde(rename = "type")]
-  _class: MapGravitySourceClass,
+  width: f32,
+  height: f32,
+  #[serde(default)]
+  rotation: f32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapAbilityPickupClass {
-  AbilityPickup,
-}
-
-#[derive(Clone, Copy, Debug, Deserialize)]
-pub enum MapAbilityType {
-  Boost,
-  Chain,
+#[serde(rename_all = "PascalCase")]
+struct MapLogEntryProperties {
+  log_id: String,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapAbilityPickup {
+struct MapLogEntry {
   x: f32,
   y: f32,
-  name: MapAbilityType,
-  #[serde(rename = "type")]
-  _class: MapAbilityPickupClass,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-enum MapInitialActivationClass {
-  InitialActivation,
+  properties: MapLogEntryProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapInitialActivation {
-  #[serde(rename = "name")]
-  _name: MapInitialActivationClass,
-  value: f32,
+#[serde(rename_all = "PascalCase")]
+struct MapEngineUpgradeProperties {
+  name: String,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapChainSwitchClass {
-  ChainSwitch,
+struct MapEngineUpgrade {
+  x: f32,
+  y: f32,
+  properties: MapEngineUpgradeProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapRotationClass {
-  Rotation,
+#[serde(rename_all = "PascalCase")]
+struct MapChainSwitchProperties {
+  initial_activation: f32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapActivatorIdClass {
-  ActivatorId,
+struct MapChainSwitch {
+  id: i32,
+  x: f32,
+  y: f32,
+  #[serde(default)]
+  rotation: f32,
+  properties: MapChainSwitchProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapActivatorId {
-  #[serde(rename = "name")]
-  _name: MapActivatorIdClass,
-  value: i32,
+struct MapMountPoint {
+  id: i32,
+  x: f32,
+  y: f32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapActivator1IdClass {
-  Activator1Id,
+#[serde(rename_all = "PascalCase")]
+struct MapTensionSwitchProperties {
+  full_force: f32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapActivator1Id {
-  #[serde(rename = "name")]
-  _name: MapActivator1IdClass,
-  value: i32,
+struct MapTensionSwitch {
+  id: i32,
+  x: f32,
+  y: f32,
+  properties: MapTensionSwitchProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapActivator2IdClass {
-  Activator2Id,
+#[serde(rename_all = "PascalCase")]
+struct MapTwoActivatorProperties {
+  activator1_id: i32,
+  activator2_id: i32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapActivator2Id {
-  #[serde(rename = "name")]
-  _name: MapActivator2IdClass,
-  value: i32,
+struct MapOr {
+  id: i32,
+  x: f32,
+  y: f32,
+  properties: MapTwoActivatorProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapChainSwitch {
+struct MapAnd {
   id: i32,
   x: f32,
   y: f32,
-  rotation: f32,
-  properties: (MapInitialActivation,),
-  #[serde(rename = "type")]
-  _class: MapChainSwitchClass,
+  properties: MapTwoActivatorProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapMountPointClass {
-  MountPoint,
+#[serde(rename_all = "PascalCase")]
+struct MapGateProperties {
+  activator_id: i32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapMountPoint {
+struct MapGate {
   id: i32,
   x: f32,
   y: f32,
-  #[serde(rename = "type")]
-  _class: MapMountPointClass,
+  properties: MapGateProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapOrClass {
-  Or,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-struct MapOr {
-  id: i32,
+struct MapPoint {
   x: f32,
   y: f32,
-  properties: (MapActivator1Id, MapActivator2Id),
-  #[serde(rename = "type")]
-  _class: MapOrClass,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapAndClass {
-  And,
+#[serde(rename_all = "PascalCase")]
+struct MapLocomotorProperties {
+  activator_id: i32,
+  #[serde(default)]
+  reverse_direction: bool,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapAnd {
+struct MapLocomotor {
   id: i32,
   x: f32,
   y: f32,
-  properties: (MapActivator1Id, MapActivator2Id),
-  #[serde(rename = "type")]
-  _class: MapAndClass,
+  polyline: [MapPoint; 2],
+  properties: MapLocomotorProperties,
 }
 
-#[derive(Clone, Debug, Deserialize)]
-enum MapGateClass {
-  Gate,
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MapEngineProperties {
+  activator_id: Option<i32>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapGate {
+struct MapEngine {
   id: i32,
   x: f32,
   y: f32,
-  properties: (MapActivatorId,),
-  #[serde(rename = "type")]
-  _class: MapGateClass,
+  #[serde(default)]
+  properties: MapEngineProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapReverseDirectionClass {
-  ReverseDirection,
+#[serde(rename_all = "PascalCase")]
+struct MapTutorialTriggerProperties {
+  action: TutorialAction,
+  text: String,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapReverseDirection {
-  #[serde(rename = "name")]
-  _name: MapReverseDirectionClass,
-  value: bool,
+#[serde(rename_all = "PascalCase")]
+struct MapDialogueTriggerProperties {
+  dialogue: String,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapPoint {
+struct MapDialogueTrigger {
   x: f32,
   y: f32,
+  width: f32,
+  height: f32,
+  #[serde(default)]
+  rotation: f32,
+  properties: MapDialogueTriggerProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-enum MapLocomotorClass {
-  Locomotor,
+#[serde(rename_all = "PascalCase")]
+struct MapEncounterProperties {
+  /* Comma separated ids of the activators held shut during the fight */
+  gate_ids: String,
+  #[serde(default)]
+  closed_activation: f32,
+  #[serde(default)]
+  reward_credits: i32,
+  #[serde(default)]
+  reward_health: f32,
+  reward_module: Option<WeaponModuleKind>,
 }
 
+/* The area is both what the player walks into and the arena whose enemies are fought */
 #[derive(Clone, Debug, Deserialize)]
-struct MapLocomotor {
+struct MapEncounter {
   id: i32,
   x: f32,
   y: f32,
-  polyline: [MapPoint; 2],
-  properties: (MapActivatorId, MapReverseDirection),
-  #[serde(rename = "type")]
-  _class: MapLocomotorClass,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-enum MapEngineClass {
-  Engine,
+  width: f32,
+  height: f32,
+  #[serde(default)]
+  rotation: f32,
+  properties: MapEncounterProperties,
 }
 
 #[derive(Clone, Debug, Deserialize)]
-struct MapEngine {
-  id: i32,
+struct MapTutorialTrigger {
   x: f32,
   y: f32,
-  properties: (Option<MapActivatorId>,),
-  #[serde(rename = "type")]
-  _class: MapEngineClass,
+  width: f32,
+  height: f32,
+  #[serde(default)]
+  rotation: f32,
+  properties: MapTutorialTriggerProperties,
 }
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(untagged)]
+#[derive(Clone, Debug)]
 enum Object {
   EnemySpawn(MapEnemySpawn),
   PlayerSpawn(MapPlayerSpawn),
@@ -545,31 +814,109 @@ enum Object {
   Block(MapBlock),
   TouchSensor(MapTouchSensor),
   GravitySource(MapGravitySource),
+  GravityZone(MapGravityZone),
   AbilityPickup(MapAbilityPickup),
+  LogEntry(MapLogEntry),
+  EngineUpgrade(MapEngineUpgrade),
+  Hazard(MapHazard),
+  FluidVolume(MapFluidVolume),
+  MagneticWall(MapMagneticWall),
   ChainSwitch(MapChainSwitch),
   MountPoint(MapMountPoint),
+  TensionSwitch(MapTensionSwitch),
   Or(MapOr),
   And(MapAnd),
   Gate(MapGate),
   Locomotor(MapLocomotor),
   Glue(MapGlue),
   Engine(MapEngine),
+  TutorialTrigger(MapTutorialTrigger),
+  DialogueTrigger(MapDialogueTrigger),
+  Encounter(MapEncounter),
+  ExplosiveBarrel(MapExplosiveBarrel),
+  MountedTurret(MapMountedTurret),
 }
 
-#[derive(Clone, Debug, Deserialize)]
-enum ObjectLayerName {
-  Entities,
+fn deser_object<T: DeserializeOwned>(
+  object: Value,
+  wrap: fn(T) -> Object,
+) -> Result<Object, serde_json::Error> {
+  serde_json::from_value(object).map(wrap)
 }
 
-#[derive(Clone, Debug, Deserialize)]
-struct ObjectLayer {
-  objects: Vec<Object>,
-  name: ObjectLayerName,
+impl Object {
+  fn from_tiled(object_type: &str, object: Value) -> Option<Object> {
+    let id = object.get("id").cloned().unwrap_or(Value::Null);
+
+    let object = match object_type {
+      "EnemySpawn" => deser_object(object, Object::EnemySpawn),
+      "PlayerSpawn" => deser_object(object, Object::PlayerSpawn),
+      "ItemPickup" => deser_object(object, Object::ItemPickup),
+      "MapTransition" => deser_object(object, Object::MapTransition),
+      "SavePoint" => deser_object(object, Object::SavePoint),
+      "Block" => deser_object(object, Object::Block),
+      "TouchSensor" => deser_object(object, Object::TouchSensor),
+      "GravitySource" => deser_object(object, Object::GravitySource),
+      "GravityZone" => deser_object(object, Object::GravityZone),
+      "AbilityPickup" => deser_object(object, Object::AbilityPickup),
+      "LogEntry" => deser_object(object, Object::LogEntry),
+      "EngineUpgrade" => deser_object(object, Object::EngineUpgrade),
+      "Hazard" => deser_object(object, Object::Hazard),
+      "FluidVolume" => deser_object(object, Object::FluidVolume),
+      "MagneticWall" => deser_object(object, Object::MagneticWall),
+      "ChainSwitch" => deser_object(object, Object::ChainSwitch),
+      "MountPoint" => deser_object(object, Object::MountPoint),
+      "TensionSwitch" => deser_object(object, Object::TensionSwitch),
+      "Or" => deser_object(object, Object::Or),
+      "And" => deser_object(object, Object::And),
+      "Gate" => deser_object(object, Object::Gate),
+      "Locomotor" => deser_object(object, Object::Locomotor),
+      "Glue" => deser_object(object, Object::Glue),
+      "Engine" => deser_object(object, Object::Engine),
+      "TutorialTrigger" => deser_object(object, Object::TutorialTrigger),
+      "DialogueTrigger" => deser_object(object, Object::DialogueTrigger),
+      "Encounter" => deser_object(object, Object::Encounter),
+      "ExplosiveBarrel" => deser_object(object, Object::ExplosiveBarrel),
+      "MountedTurret" => deser_object(object, Object::MountedTurret),
+      _ => {
+        map_warning(format!(
+          "unknown object type {} on object {}",
+          object_type, id
+        ));
+        return None;
+      }
+    };
+
+    object
+      .inspect_err(|err| {
+        map_warning(format!(
+          "skipping malformed {} object {}: {}",
+          object_type, id, err
+        ))
+      })
+      .ok()
+  }
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct RawMap {
-  layers: (TileLayer, ObjectLayer),
+  width: i32,
+  height: i32,
+  #[serde(default = "default_tile_dimension")]
+  tilewidth: i32,
+  #[serde(default = "default_tile_dimension")]
+  tileheight: i32,
+  #[serde(default)]
+  tilesets: Vec<TilesetReference>,
+  layers: Vec<Layer>,
+  #[serde(default)]
+  properties: Value,
+  #[serde(skip)]
+  directory: PathBuf,
+}
+
+fn default_tile_dimension() -> i32 {
+  MAP_TILE_DIMENSION
 }
 
 #[derive(Deserialize)]
@@ -607,21 +954,52 @@ pub struct WorldMapWithTiles {
   pub y: f32,
 }
 
-fn deser_map(raw: &str) -> RawMap {
-  serde_json::from_str(raw).expect("JSON was not well-formatted")
+fn parse_map(raw: &str, file_path: &str) -> Result<RawMap, serde_json::Error> {
+  serde_json::from_str(raw).map(|raw_map| RawMap {
+    directory: Path::new(file_path)
+      .parent()
+      .map(Path::to_path_buf)
+      .unwrap_or_default(),
+    ..raw_map
+  })
 }
 
-pub const COLLISION_GROUP_WALL: Group = Group::GROUP_1;
-pub const COLLISION_GROUP_PLAYER: Group = Group::GROUP_2;
-pub const COLLISION_GROUP_PLAYER_PROJECTILE: Group = Group::GROUP_3;
-pub const COLLISION_GROUP_ENEMY: Group = Group::GROUP_4;
-pub const COLLISION_GROUP_ENEMY_PROJECTILE: Group = Group::GROUP_5;
-pub const COLLISION_GROUP_PLAYER_INTERACTIBLE: Group = Group::GROUP_6;
-pub const COLLISION_GROUP_CHAIN: Group = Group::GROUP_7;
+fn deser_map(raw: &str, file_path: &str) -> RawMap {
+  parse_map(raw, file_path).expect("JSON was not well-formatted")
+}
+
+/* Barrels are solid like walls, and are the only walls a barrel's blast reaches */
+pub fn barrel_groups() -> InteractionGroups {
+  groups(CollisionLayer::Wall)
+}
+
+/* Built walls, as opposed to blocks and barrels, which barrel blasts pass over */
+fn built_wall_groups() -> InteractionGroups {
+  groups_except(CollisionLayer::Wall, CollisionLayer::Blast.group())
+}
+
+/* Pushes the players as well as everything a player's blast reaches */
+pub fn player_self_blast_force_groups() -> InteractionGroups {
+  InteractionGroups {
+    memberships: CollisionLayer::Explosion
+      .group()
+      .union(CollisionLayer::SelfBlast.group()),
+    filter: interactions(CollisionLayer::Explosion).union(interactions(CollisionLayer::SelfBlast)),
+    test_mode: InteractionTestMode::And,
+  }
+}
 
+/* For scene queries that should only be stopped by walls */
+pub const WALL_QUERY_GROUPS: InteractionGroups = InteractionGroups {
+  memberships: Group::all(),
+  filter: CollisionLayer::Wall.group(),
+  test_mode: InteractionTestMode::And,
+};
+
+/* Gravity reaches every body but walls */
 pub const GRAVITY_INTERACTION_GROUPS: InteractionGroups = InteractionGroups {
   memberships: Group::all(),
-  filter: COLLISION_GROUP_WALL.complement(),
+  filter: CollisionLayer::Wall.group().complement(),
   test_mode: InteractionTestMode::And,
 };
 
@@ -630,7 +1008,22 @@ pub struct EnemySpawn {
   pub name: Enemy,
   pub collider: Collider,
   pub rigid_body: RigidBody,
-}
+  /* Only set for map placed enemies which stay dead */
+  pub persistent_id: Option<i32>,
+  /* Placed on the map, otherwise rolled from the enemy's elite chance */
+  pub affixes: Vec<EliteAffix>,
+  /* Overrides the group of the enemy's faction */
+  pub group: Option<i32>,
+}
+
+const ELITE_ARMORED_HEALTH_MOD: f32 = 2.0;
+const ELITE_EXPLOSION_RADIUS: f32 = 2.0;
+const ELITE_EXPLOSION_STRENGTH: f32 = -0.5;
+const ELITE_EXPLOSION_GROUPS: InteractionGroups = InteractionGroups {
+  memberships: CollisionLayer::EnemyProjectile.group(),
+  filter: CollisionLayer::Player.group(),
+  test_mode: InteractionTestMode::And,
+};
 
 impl EnemySpawn {
   pub fn new(name: &MapEnemyName, translation: Vector2<f32>) -> Self {
@@ -640,6 +1033,9 @@ impl EnemySpawn {
       MapEnemyName::Defender => RigidBodyBuilder::fixed(),
       MapEnemyName::Seeker => RigidBodyBuilder::dynamic(),
       MapEnemyName::SeekerGenerator => RigidBodyBuilder::fixed(),
+      MapEnemyName::Minelayer => RigidBodyBuilder::dynamic(),
+      MapEnemyName::Swarmer => RigidBodyBuilder::dynamic(),
+      MapEnemyName::Scripted(_) => RigidBodyBuilder::dynamic(),
     };
     let mut rigid_body = rigid_body_builder.translation(translation).build();
     rigid_body.wake_up(true);
@@ -647,65 +1043,98 @@ impl EnemySpawn {
       name: Enemy::default_from_map(name.clone()),
       collider,
       rigid_body,
+      persistent_id: None,
+      affixes: Vec::new(),
+      group: None,
     }
   }
 
-  pub fn into_entity_components(&self) -> ComponentSet {
-    match self.name {
-      Enemy::Goblin(_) => ComponentSet::new()
-        .insert(Damageable {
-          health: 50.0,
-          max_health: 50.0,
-          destroy_on_zero_health: true,
-          current_hitstun: 0.0,
-          max_hitstun: 0.0,
-        })
-        .insert(Damager { damage: 10.0 })
-        .insert(DropHealthOnDestroy {
-          amount: 15.0,
-          chance: 0.5,
-        }),
-      Enemy::Defender(_) => ComponentSet::new()
-        .insert(Damageable {
-          health: 100.0,
-          max_health: 100.0,
-          destroy_on_zero_health: true,
-          current_hitstun: 0.0,
-          max_hitstun: 0.0,
-        })
-        .insert(Damager { damage: 10.0 })
-        .insert(DropHealthOnDestroy {
-          amount: 20.0,
-          chance: 0.4,
-        }),
-      Enemy::Seeker(_) => ComponentSet::new()
-        .insert(Damageable {
-          health: 30.0,
-          max_health: 30.0,
-          destroy_on_zero_health: true,
-          current_hitstun: 0.0,
-          max_hitstun: 0.0,
-        })
-        .insert(Damager { damage: 25.0 })
-        .insert(DropHealthOnDestroy {
-          amount: 10.0,
-          chance: 0.5,
-        }),
-      Enemy::SeekerGenerator(_) => ComponentSet::new()
-        .insert(Damageable {
-          health: 120.0,
-          max_health: 120.0,
-          destroy_on_zero_health: true,
-          current_hitstun: 0.0,
-          max_hitstun: 0.0,
-        })
-        .insert(Damager { damage: 10.0 })
-        .insert(DropHealthOnDestroy {
-          amount: 35.0,
-          chance: 0.7,
-        }),
-    }
-    .insert(self.name.clone())
+  /* elite_roll is uniform in 0..1, both deciding whether an elite is rolled and which */
+  pub fn to_entity_components(
+    &self,
+    enemies: &EnemyDefinitions,
+    difficulty: Difficulty,
+    elite_roll: f32,
+  ) -> ComponentSet {
+    let definition = enemies.for_enemy(&self.name);
+    let affixes = if !self.affixes.is_empty() {
+      self.affixes.clone()
+    } else if elite_roll < definition.elite_chance {
+      let index = (elite_roll / definition.elite_chance * EliteAffix::ALL.len() as f32) as usize;
+      vec![EliteAffix::ALL[index.min(EliteAffix::ALL.len() - 1)]]
+    } else {
+      Vec::new()
+    };
+    let damage = definition.damage * difficulty.enemy_damage_mod();
+    let health = if affixes.contains(&EliteAffix::Armored) {
+      definition.health * difficulty.enemy_health_mod() * ELITE_ARMORED_HEALTH_MOD
+    } else {
+      definition.health * difficulty.enemy_health_mod()
+    };
+    let components = match self.persistent_id {
+      Some(id) => ComponentSet::new().insert(Persistent { id }),
+      None => ComponentSet::new(),
+    };
+    let components = if definition.boss {
+      components.insert(Boss)
+    } else {
+      components
+    };
+    let components = if affixes.contains(&EliteAffix::Explosive) {
+      components.insert(ExplodeOnDeath {
+        explosion: ExplodeOnCollision {
+          strength: ELITE_EXPLOSION_STRENGTH,
+          radius: ELITE_EXPLOSION_RADIUS,
+          damage,
+          interaction_groups: ELITE_EXPLOSION_GROUPS,
+          force_groups: ELITE_EXPLOSION_GROUPS,
+          self_blast: false,
+        },
+      })
+    } else {
+      components
+    };
+    let components = if affixes.is_empty() {
+      components
+    } else {
+      components.insert(Elite { affixes })
+    };
+    components
+      .insert(Damageable {
+        health,
+        max_health: health,
+        destroy_on_zero_health: true,
+        current_hitstun: 0.0,
+        max_hitstun: 0.0,
+      })
+      .insert(Damager {
+        damage,
+        knockback: definition.knockback,
+        on_hit_status: definition.on_hit_status,
+        source: None,
+      })
+      .insert(match (definition.faction, self.group) {
+        (Faction::Enemy(_), Some(group)) => Faction::Enemy(group),
+        (faction, _) => faction,
+      })
+      .insert(LootTable {
+        entries: definition
+          .loot
+          .iter()
+          .map(|entry| match entry.drop {
+            LootDrop::Health(amount) => LootEntry {
+              weight: entry.weight * difficulty.health_drop_chance_mod(),
+              drop: LootDrop::Health(amount * difficulty.health_drop_amount_mod()),
+            },
+            _ => *entry,
+          })
+          .collect(),
+        despawn_frames: definition.loot_despawn_frames,
+      })
+      .insert(DeathAnimation {
+        frames: definition.death_frames,
+      })
+      .insert(self.name.clone())
   }
 }
 
@@ -742,6 +1171,21 @@ pub struct Block {
   pub collider: Collider,
 }
 
+/* The player steps onto the sensor and presses interact to take it over */
+#[derive(Clone)]
+pub struct MountedTurret {
+  pub id: i32,
+  pub collider: Collider,
+}
+
+/* Blows up once shot down, setting off any other barrels caught in the blast */
+#[derive(Clone)]
+pub struct ExplosiveBarrel {
+  pub id: i32,
+  pub translation: Vector<f32>,
+  pub collider: Collider,
+}
+
 #[derive(Clone)]
 pub struct TouchSensor {
   pub collider: Collider,
@@ -756,12 +1200,50 @@ pub struct GravitySource {
   pub activator_id: Option<i32>,
 }
 
+#[derive(Clone)]
+pub struct GravityZone {
+  pub collider: Collider,
+  pub acceleration: Vector2<f32>,
+  pub activator_id: Option<i32>,
+}
+
 #[derive(Clone)]
 pub struct AbilityPickup {
   pub collider: Collider,
   pub ability_type: MapAbilityType,
 }
 
+#[derive(Clone)]
+pub struct HazardZone {
+  pub collider: Collider,
+  pub kind: HazardKind,
+}
+
+#[derive(Clone)]
+pub struct MagneticWall {
+  pub collider: Collider,
+}
+
+#[derive(Clone)]
+pub struct FluidZone {
+  pub collider: Collider,
+  pub drag: f32,
+  pub buoyancy: f32,
+  pub movement_scale: f32,
+}
+
+#[derive(Clone)]
+pub struct LogEntry {
+  pub collider: Collider,
+  pub log_id: String,
+}
+
+#[derive(Clone)]
+pub struct EngineUpgradePickup {
+  pub collider: Collider,
+  pub name: String,
+}
+
 #[derive(Clone)]
 pub struct ChainSwitch {
   pub id: i32,
@@ -779,6 +1261,16 @@ pub struct MountPoint {
   pub knob: Collider,
 }
 
+/* A mount point fixed in place that activates by being pulled on */
+#[derive(Clone)]
+pub struct TensionSwitch {
+  pub id: i32,
+  pub rigid_body: RigidBody,
+  pub zone: Collider,
+  pub knob: Collider,
+  pub full_force: f32,
+}
+
 #[derive(Clone)]
 pub struct Or {
   pub rigid_body: RigidBody,
@@ -810,9 +1302,12 @@ pub struct Locomotor {
   pub activator_id: i32,
 }
 
+/* The id of the entity glued to and where on it, with no id meaning the world */
+type GlueAttachment<Id> = (Id, Vector2<f32>);
+
 #[derive(Clone)]
 pub struct Glue {
-  pub attachments: ((i32, Vector2<f32>), (Option<i32>, Vector2<f32>)),
+  pub attachments: (GlueAttachment<i32>, GlueAttachment<Option<i32>>),
 }
 
 #[derive(Clone)]
@@ -822,11 +1317,44 @@ pub struct Engine {
   pub rigid_body: RigidBody,
 }
 
+#[derive(Clone)]
+pub struct TutorialTrigger {
+  pub collider: Collider,
+  pub action: TutorialAction,
+  pub text: String,
+}
+
+#[derive(Clone)]
+pub struct DialogueTrigger {
+  pub collider: Collider,
+  pub dialogue: String,
+}
+
+#[derive(Clone)]
+pub struct Encounter {
+  pub id: i32,
+  pub collider: Collider,
+  pub gate_ids: Vec<i32>,
+  pub closed_activation: f32,
+  pub rewards: Vec<LootDrop>,
+}
+
 #[derive(Clone)]
 pub struct Wall {
+  /* The tile index, which is stable for as long as the map layout is */
+  pub id: i32,
   pub collider: Collider,
   pub damaging: Option<f32>,
   pub damageable: Option<f32>,
+  pub magnetic: bool,
+  pub reflective: bool,
+}
+
+impl Wall {
+  /* Plain walls are merged into a single compound collider instead of being entities */
+  pub fn is_static(&self) -> bool {
+    self.damaging.is_none() && self.damageable.is_none() && !self.magnetic && !self.reflective
+  }
 }
 
 fn collider_from_enemy_name(name: MapEnemyName) -> Collider {
@@ -835,17 +1363,15 @@ fn collider_from_enemy_name(name: MapEnemyName) -> Collider {
     MapEnemyName::Defender => ColliderBuilder::cuboid(0.5, 0.5),
     MapEnemyName::Seeker => ColliderBuilder::cuboid(0.2, 0.2).mass(1.0),
     MapEnemyName::SeekerGenerator => ColliderBuilder::cuboid(0.7, 0.7),
+    MapEnemyName::Minelayer => ColliderBuilder::cuboid(0.4, 0.4),
+    MapEnemyName::Swarmer => ColliderBuilder::ball(0.2).mass(0.5),
+    MapEnemyName::Scripted(_) => ColliderBuilder::cuboid(0.5, 0.5),
   };
 
-  let collision_groups = InteractionGroups {
-    memberships: COLLISION_GROUP_ENEMY,
-    filter: COLLISION_GROUP_PLAYER
-      .union(COLLISION_GROUP_PLAYER_PROJECTILE)
-      .union(COLLISION_GROUP_WALL),
-    ..Default::default()
-  };
-
-  collider_builder.collision_groups(collision_groups).build()
+  /* Enemy fire only lets enemies through in rooms with friendly fire */
+  collider_builder
+    .collision_groups(groups(CollisionLayer::Enemy))
+    .build()
 }
 
 #[derive(Clone)]
@@ -858,15 +1384,27 @@ pub enum MapComponent {
   Block(Block),
   TouchSensor(TouchSensor),
   GravitySource(GravitySource),
+  GravityZone(GravityZone),
   AbilityPickup(AbilityPickup),
+  LogEntry(LogEntry),
+  EngineUpgrade(EngineUpgradePickup),
+  Hazard(HazardZone),
+  FluidVolume(FluidZone),
+  MagneticWall(MagneticWall),
   ChainSwitch(ChainSwitch),
   MountPoint(MountPoint),
+  TensionSwitch(TensionSwitch),
   Or(Or),
   And(And),
   Gate(Gate),
   Locomotor(Locomotor),
   Glue(Glue),
   Engine(Engine),
+  TutorialTrigger(TutorialTrigger),
+  DialogueTrigger(DialogueTrigger),
+  Encounter(Encounter),
+  ExplosiveBarrel(ExplosiveBarrel),
+  MountedTurret(MountedTurret),
 }
 
 fn map_scalar_to_physics(scalar: f32) -> PhysicsScalar {
@@ -902,70 +1440,63 @@ impl Object {
             .into_vec(),
           )
           .sensor(true)
-          .collision_groups(InteractionGroups {
-            memberships: COLLISION_GROUP_PLAYER_INTERACTIBLE,
-            filter: COLLISION_GROUP_PLAYER,
-            ..Default::default()
-          })
+          .collision_groups(groups(CollisionLayer::PlayerInteractible))
           .build(),
       }),
 
       Object::MapTransition(map_transition) => MapComponent::MapTransition(MapTransition {
-        target_player_spawn_id: map_transition.properties.0.value,
+        target_player_spawn_id: map_transition.properties.target_player_spawn,
         map_name: map_transition.name.clone(),
         collider: cuboid_collider_from_map(
           map_transition.x,
           map_transition.y,
           map_transition.width,
           map_transition.height,
+          map_transition.rotation,
           map_height,
         )
         .sensor(true)
-        .collision_groups(InteractionGroups {
-          memberships: COLLISION_GROUP_PLAYER_INTERACTIBLE,
-          filter: COLLISION_GROUP_PLAYER,
-          ..Default::default()
-        })
+        .collision_groups(groups(CollisionLayer::PlayerInteractible))
         .build(),
       }),
 
       Object::SavePoint(save_point) => MapComponent::SavePoint(SavePoint {
-        player_spawn_id: save_point.properties.0.value,
+        player_spawn_id: save_point.properties.player_spawn_id,
         collider: ColliderBuilder::ball(1.0)
           .translation(vector![
             *map_scalar_to_physics(save_point.x),
             *map_scalar_to_physics(map_height - save_point.y)
           ])
           .sensor(true)
-          .collision_groups(InteractionGroups {
-            memberships: COLLISION_GROUP_PLAYER_INTERACTIBLE,
-            filter: COLLISION_GROUP_PLAYER,
-            ..Default::default()
-          })
+          .collision_groups(groups(CollisionLayer::PlayerInteractible))
           .build(),
       }),
 
       Object::Block(block) => MapComponent::Block(Block {
         id: block.id,
-        rigid_body: RigidBodyBuilder::dynamic()
-          .translation(physics_translation_from_map(
-            block.x,
-            block.y,
-            block.width,
-            block.height,
-            map_height,
-          ))
-          .build(),
-        collider: ColliderBuilder::cuboid(
-          *map_scalar_to_physics(block.width / 2.0),
-          *map_scalar_to_physics(block.height / 2.0),
-        )
-        .collision_groups(InteractionGroups {
-          memberships: COLLISION_GROUP_WALL,
-          filter: !COLLISION_GROUP_WALL,
-          ..Default::default()
-        })
-        .build(),
+        rigid_body: {
+          let (center_x, center_y) =
+            rotated_center_from_map(block.x, block.y, block.width, block.height, block.rotation);
+          RigidBodyBuilder::dynamic()
+            .translation(physics_translation_from_map(
+              center_x, center_y, 0.0, 0.0, map_height,
+            ))
+            .rotation(-block.rotation * PI / 180.0)
+            .build()
+        },
+        collider: {
+          let collider = ColliderBuilder::cuboid(
+            *map_scalar_to_physics(block.width / 2.0),
+            *map_scalar_to_physics(block.height / 2.0),
+          )
+          .collision_groups(groups(CollisionLayer::Wall));
+          if block.properties.one_way {
+            one_way(collider)
+          } else {
+            collider
+          }
+          .build()
+        },
       }),
 
       Object::TouchSensor(touch_sensor) => MapComponent::TouchSensor(TouchSensor {
@@ -974,21 +1505,18 @@ impl Object {
           touch_sensor.y,
           touch_sensor.width,
           touch_sensor.height,
+          touch_sensor.rotation,
           map_height,
         )
         .sensor(true)
-        .collision_groups(InteractionGroups {
-          memberships: COLLISION_GROUP_PLAYER_INTERACTIBLE,
-          filter: COLLISION_GROUP_PLAYER,
-          ..Default::default()
-        })
+        .collision_groups(groups(CollisionLayer::PlayerInteractible))
         .build(),
-        target_activation: touch_sensor.properties.0.value,
+        target_activation: touch_sensor.properties.target_activation,
         id: touch_sensor.id,
       }),
 
       Object::GravitySource(gravity_source) => MapComponent::GravitySource(GravitySource {
-        collider: ColliderBuilder::ball(gravity_source.properties.1.value)
+        collider: ColliderBuilder::ball(gravity_source.properties.radius)
           .translation(physics_translation_from_map(
             gravity_source.x,
             gravity_source.y,
@@ -999,12 +1527,27 @@ impl Object {
           .sensor(true)
           .collision_groups(GRAVITY_INTERACTION_GROUPS)
           .build(),
-        strength: gravity_source.properties.2.value,
-        activator_id: gravity_source
-          .properties
-          .0
-          .as_ref()
-          .map(|activator_id| activator_id.value),
+        strength: gravity_source.properties.strength,
+        activator_id: gravity_source.properties.activator_id,
+      }),
+
+      Object::GravityZone(gravity_zone) => MapComponent::GravityZone(GravityZone {
+        collider: cuboid_collider_from_map(
+          gravity_zone.x,
+          gravity_zone.y,
+          gravity_zone.width,
+          gravity_zone.height,
+          gravity_zone.rotation,
+          map_height,
+        )
+        .sensor(true)
+        .collision_groups(GRAVITY_INTERACTION_GROUPS)
+        .build(),
+        acceleration: vector![
+          gravity_zone.properties.acceleration_x,
+          -gravity_zone.properties.acceleration_y
+        ],
+        activator_id: gravity_zone.properties.activator_id,
       }),
 
       Object::AbilityPickup(ability_pickup) => MapComponent::AbilityPickup(AbilityPickup {
@@ -1018,24 +1561,94 @@ impl Object {
             map_height,
           ))
           .sensor(true)
-          .collision_groups(InteractionGroups {
-            memberships: COLLISION_GROUP_PLAYER_INTERACTIBLE,
-            filter: COLLISION_GROUP_PLAYER,
-            ..Default::default()
-          })
+          .collision_groups(groups(CollisionLayer::PlayerInteractible))
+          .build(),
+      }),
+
+      Object::LogEntry(log_entry) => MapComponent::LogEntry(LogEntry {
+        log_id: log_entry.properties.log_id.clone(),
+        collider: ColliderBuilder::ball(0.5)
+          .translation(physics_translation_from_map(
+            log_entry.x,
+            log_entry.y,
+            0.0,
+            0.0,
+            map_height,
+          ))
+          .sensor(true)
+          .collision_groups(groups(CollisionLayer::PlayerInteractible))
+          .build(),
+      }),
+
+      Object::EngineUpgrade(engine_upgrade) => MapComponent::EngineUpgrade(EngineUpgradePickup {
+        name: engine_upgrade.properties.name.clone(),
+        collider: ColliderBuilder::ball(0.5)
+          .translation(physics_translation_from_map(
+            engine_upgrade.x,
+            engine_upgrade.y,
+            0.0,
+            0.0,
+            map_height,
+          ))
+          .sensor(true)
+          .collision_groups(groups(CollisionLayer::PlayerInteractible))
           .build(),
       }),
 
+      Object::Hazard(hazard) => MapComponent::Hazard(HazardZone {
+        collider: cuboid_collider_from_map(
+          hazard.x,
+          hazard.y,
+          hazard.width,
+          hazard.height,
+          hazard.rotation,
+          map_height,
+        )
+        .sensor(true)
+        .collision_groups(groups(CollisionLayer::Hazard))
+        .build(),
+        kind: hazard.properties.kind,
+      }),
+
+      /* Acts on everything gravity does, without the walls */
+      Object::FluidVolume(fluid_volume) => MapComponent::FluidVolume(FluidZone {
+        collider: cuboid_collider_from_map(
+          fluid_volume.x,
+          fluid_volume.y,
+          fluid_volume.width,
+          fluid_volume.height,
+          fluid_volume.rotation,
+          map_height,
+        )
+        .sensor(true)
+        .collision_groups(GRAVITY_INTERACTION_GROUPS)
+        .build(),
+        drag: fluid_volume.properties.drag,
+        buoyancy: fluid_volume.properties.buoyancy,
+        movement_scale: fluid_volume.properties.movement_scale,
+      }),
+
+      Object::MagneticWall(magnetic_wall) => MapComponent::MagneticWall(MagneticWall {
+        collider: cuboid_collider_from_map(
+          magnetic_wall.x,
+          magnetic_wall.y,
+          magnetic_wall.width,
+          magnetic_wall.height,
+          magnetic_wall.rotation,
+          map_height,
+        )
+        .collision_groups(built_wall_groups())
+        .build(),
+      }),
+
       Object::ChainSwitch(chain_switch) => {
         let center_position =
           physics_translation_from_map(chain_switch.x, chain_switch.y, 0.0, 0.0, map_height);
 
-        let switch_half_limits = 1.0; // TODO: load from map
-
         let rotation_vec =
           distance_projection_physics(chain_switch.rotation * PI / 180.0, 1.0).into_vec();
 
-        let initial_activation = chain_switch.properties.0.value;
+        let initial_activation = chain_switch.properties.initial_activation;
 
         let knob_position = center_position + (2.0 * initial_activation - 1.0) * rotation_vec;
 
@@ -1050,11 +1663,7 @@ impl Object {
               map_height,
             ))
             .sensor(true)
-            .collision_groups(InteractionGroups {
-              memberships: COLLISION_GROUP_PLAYER_INTERACTIBLE,
-              filter: COLLISION_GROUP_PLAYER,
-              ..Default::default()
-            })
+            .collision_groups(groups(CollisionLayer::PlayerInteractible))
             .build(),
           switch_center: RigidBodyBuilder::dynamic()
             .lock_translations()
@@ -1081,15 +1690,11 @@ impl Object {
             .build(),
           zone: ColliderBuilder::ball(10.0)
             .sensor(true)
-            .collision_groups(InteractionGroups {
-              memberships: COLLISION_GROUP_PLAYER_INTERACTIBLE,
-              filter: COLLISION_GROUP_PLAYER,
-              ..Default::default()
-            })
+            .collision_groups(groups(CollisionLayer::PlayerInteractible))
             .build(),
           knob: ColliderBuilder::ball(0.1)
             .collision_groups(InteractionGroups {
-              memberships: COLLISION_GROUP_WALL,
+              memberships: CollisionLayer::Wall.group(),
               filter: Group::empty(),
               ..Default::default()
             })
@@ -1098,8 +1703,33 @@ impl Object {
         })
       }
 
+      Object::TensionSwitch(tension_switch) => MapComponent::TensionSwitch(TensionSwitch {
+        rigid_body: RigidBodyBuilder::fixed()
+          .translation(physics_translation_from_map(
+            tension_switch.x,
+            tension_switch.y,
+            0.0,
+            0.0,
+            map_height,
+          ))
+          .build(),
+        zone: ColliderBuilder::ball(10.0)
+          .sensor(true)
+          .collision_groups(groups(CollisionLayer::PlayerInteractible))
+          .build(),
+        knob: ColliderBuilder::ball(0.1)
+          .collision_groups(InteractionGroups {
+            memberships: CollisionLayer::Wall.group(),
+            filter: Group::empty(),
+            ..Default::default()
+          })
+          .build(),
+        id: tension_switch.id,
+        full_force: tension_switch.properties.full_force,
+      }),
+
       Object::Or(or) => MapComponent::Or(Or {
-        activator_ids: (or.properties.0.value, or.properties.1.value),
+        activator_ids: (or.properties.activator1_id, or.properties.activator2_id),
         rigid_body: RigidBodyBuilder::dynamic()
           .translation(physics_translation_from_map(
             or.x, or.y, 0.0, 0.0, map_height,
@@ -1109,7 +1739,7 @@ impl Object {
       }),
 
       Object::And(and) => MapComponent::And(And {
-        activator_ids: (and.properties.0.value, and.properties.1.value),
+        activator_ids: (and.properties.activator1_id, and.properties.activator2_id),
         rigid_body: RigidBodyBuilder::dynamic()
           .translation(physics_translation_from_map(
             and.x, and.y, 0.0, 0.0, map_height,
@@ -1120,7 +1750,7 @@ impl Object {
 
       Object::Gate(gate) => MapComponent::Gate(Gate {
         id: gate.id,
-        activator_id: gate.properties.0.value,
+        activator_id: gate.properties.activator_id,
         rigid_body: RigidBodyBuilder::dynamic()
           .translation(physics_translation_from_map(
             gate.x, gate.y, 0.0, 0.0, map_height,
@@ -1146,7 +1776,7 @@ impl Object {
         let axis = top_left - bottom_right;
         let axis_len = axis.magnitude();
 
-        let reverse_direction = locomotor.properties.1.value;
+        let reverse_direction = locomotor.properties.reverse_direction;
 
         let knob_base = RigidBodyBuilder::dynamic().lock_rotations();
 
@@ -1166,78 +1796,142 @@ impl Object {
             knob_base.translation(top_left).build()
           },
           reverse_direction,
-          activator_id: locomotor.properties.0.value,
+          activator_id: locomotor.properties.activator_id,
         })
       }
 
-      Object::Glue(glue) => MapComponent::Glue(Glue {
-        attachments: match &glue.properties {
-          MapGlueMapObjects::MultiObject((
-            object_1_id,
-            object_1_x,
-            object_1_y,
-            object_2_id,
-            object_2_x,
-            object_2_y,
-          )) => (
-            (
-              object_1_id.value,
-              physics_translation_from_map(
-                object_1_x.value,
-                object_1_y.value,
-                0.0,
-                0.0,
-                map_height,
-              ),
-            ),
-            (
-              Some(object_2_id.value),
-              physics_translation_from_map(
-                object_2_x.value,
-                object_2_y.value,
-                0.0,
-                0.0,
-                map_height,
-              ),
-            ),
-          ),
-          MapGlueMapObjects::SingleObject((object_1_id, object_1_x, object_1_y)) => (
-            (
-              object_1_id.value,
-              physics_translation_from_map(
-                object_1_x.value,
-                object_1_y.value,
-                0.0,
-                0.0,
-                map_height,
-              ),
-            ),
-            (
-              None,
-              physics_translation_from_map(
-                object_1_x.value,
-                object_1_y.value,
-                0.0,
-                0.0,
-                map_height,
+      Object::Glue(glue) => {
+        let properties = &glue.properties;
+        let object_1_translation = physics_translation_from_map(
+          properties.object1_local_x,
+          properties.object1_local_y,
+          0.0,
+          0.0,
+          map_height,
+        );
+        MapComponent::Glue(Glue {
+          attachments: (
+            (properties.object1_id, object_1_translation),
+            match properties.object2_id {
+              Some(object2_id) => (
+                Some(object2_id),
+                physics_translation_from_map(
+                  properties.object2_local_x.unwrap_or(0.0),
+                  properties.object2_local_y.unwrap_or(0.0),
+                  0.0,
+                  0.0,
+                  map_height,
+                ),
               ),
-            ),
+              None => (None, object_1_translation),
+            },
           ),
-        },
-      }),
+        })
+      }
       Object::Engine(engine) => MapComponent::Engine(Engine {
         id: engine.id,
-        activator_id: engine
-          .properties
-          .0
-          .as_ref()
-          .map(|activator_id| activator_id.value),
+        activator_id: engine.properties.activator_id,
         rigid_body: RigidBodyBuilder::fixed()
           .translation(physics_translation_from_map(
             engine.x, engine.y, 0.0, 0.0, map_height,
           ))
           .build(),
       }),
+
+      Object::TutorialTrigger(tutorial_trigger) => MapComponent::TutorialTrigger(TutorialTrigger {
+        collider: cuboid_collider_from_map(
+          tutorial_trigger.x,
+          tutorial_trigger.y,
+          tutorial_trigger.width,
+          tutorial_trigger.height,
+          tutorial_trigger.rotation,
+          map_height,
+        )
+        .sensor(true)
+        .collision_groups(groups(CollisionLayer::PlayerInteractible))
+        .build(),
+        action: tutorial_trigger.properties.action,
+        text: tutorial_trigger.properties.text.clone(),
+      }),
+
+      Object::DialogueTrigger(dialogue_trigger) => MapComponent::DialogueTrigger(DialogueTrigger {
+        collider: cuboid_collider_from_map(
+          dialogue_trigger.x,
+          dialogue_trigger.y,
+          dialogue_trigger.width,
+          dialogue_trigger.height,
+          dialogue_trigger.rotation,
+          map_height,
+        )
+        .sensor(true)
+        .collision_groups(groups(CollisionLayer::PlayerInteractible))
+        .build(),
+        dialogue: dialogue_trigger.properties.dialogue.clone(),
+      }),
+
+      Object::ExplosiveBarrel(barrel) => MapComponent::ExplosiveBarrel(ExplosiveBarrel {
+        id: barrel.id,
+        translation: physics_translation_from_map(barrel.x, barrel.y, 0.0, 0.0, map_height),
+        collider: ColliderBuilder::ball(BARREL_RADIUS)
+          .collision_groups(barrel_groups())
+          .build(),
+      }),
+
+      Object::MountedTurret(turret) => MapComponent::MountedTurret(MountedTurret {
+        id: turret.id,
+        collider: ColliderBuilder::ball(TURRET_RADIUS)
+          .translation(physics_translation_from_map(
+            turret.x, turret.y, 0.0, 0.0, map_height,
+          ))
+          .sensor(true)
+          .collision_groups(groups(CollisionLayer::PlayerInteractible))
+          .build(),
+      }),
+
+      Object::Encounter(encounter) => {
+        let properties = &encounter.properties;
+
+        MapComponent::Encounter(Encounter {
+          id: encounter.id,
+          collider: cuboid_collider_from_map(
+            encounter.x,
+            encounter.y,
+            encounter.width,
+            encounter.height,
+            encounter.rotation,
+            map_height,
+          )
+          .sensor(true)
+          .collision_groups(groups(CollisionLayer::PlayerInteractible))
+          .build(),
+          gate_ids: properties
+            .gate_ids
+            .split(',')
+            .map(str::trim)
+            .filter(|gate_id| !gate_id.is_empty())
+            .filter_map(|gate_id| {
+              gate_id
+                .parse()
+                .inspect_err(|_| {
+                  map_warning(format!(
+                    "encounter {} has a bad gate id {}",
+                    encounter.id, gate_id
+                  ))
+                })
+                .ok()
+            })
+            .collect(),
+          closed_activation: properties.closed_activation,
+          rewards: [
+            (properties.reward_credits > 0).then_some(LootDrop::Credits(properties.reward_credits)),
+            (properties.reward_health > 0.0).then_some(LootDrop::Health(properties.reward_health)),
+            properties.reward_module.map(LootDrop::Module),
+          ]
+          .into_iter()
+          .flatten()
+          .collect(),
+        })
+      }
     }
   }
 }
@@ -1255,43 +1949,65 @@ fn physics_translation_from_map(
   ]
 }
 
+/* Tiled rotates rectangles clockwise (in degrees) around their top left corner */
+fn rotated_center_from_map(
+  translation_map_x: f32,
+  translation_map_y: f32,
+  translation_map_width: f32,
+  translation_map_height: f32,
+  rotation: f32,
+) -> (f32, f32) {
+  let radians = rotation * PI / 180.0;
+  let half_width = translation_map_width / 2.0;
+  let half_height = translation_map_height / 2.0;
+  (
+    translation_map_x + half_width * radians.cos() - half_height * radians.sin(),
+    translation_map_y + half_width * radians.sin() + half_height * radians.cos(),
+  )
+}
+
 fn cuboid_collider_from_map(
   translation_map_x: f32,
   translation_map_y: f32,
   translation_map_width: f32,
   translation_map_height: f32,
+  rotation: f32,
   map_height: f32,
 ) -> ColliderBuilder {
+  let (center_x, center_y) = rotated_center_from_map(
+    translation_map_x,
+    translation_map_y,
+    translation_map_width,
+    translation_map_height,
+    rotation,
+  );
   ColliderBuilder::cuboid(
     *map_scalar_to_physics(translation_map_width / 2.0),
     *map_scalar_to_physics(translation_map_height / 2.0),
   )
   .translation(physics_translation_from_map(
-    translation_map_x,
-    translation_map_y,
-    translation_map_width,
-    translation_map_height,
-    map_height,
+    center_x, center_y, 0.0, 0.0, map_height,
   ))
-}
-
-impl ObjectLayer {
-  pub fn into(&self, map_height: f32) -> Vec<MapComponent> {
-    self
-      .objects
-      .iter()
-      .map(|object| object.into(map_height))
-      .collect()
-  }
+  .rotation(-rotation * PI / 180.0)
 }
 
 pub const TILE_DIMENSION_PHYSICS: f32 = 0.8;
+const MAP_TILE_DIMENSION: i32 = 8;
 
 const EMPTY: i32 = 0;
 const WALL_COLLIDER: i32 = 1;
 const WALL_DESTRUCTIBLE: i32 = 2;
 const WALL_DAMAGING: i32 = 3;
-const WALL: [i32; 3] = [WALL_COLLIDER, WALL_DESTRUCTIBLE, WALL_DAMAGING];
+const WALL: [i32; 5] = [
+  WALL_COLLIDER,
+  WALL_DESTRUCTIBLE,
+  WALL_DAMAGING,
+  WALL_MAGNETIC,
+  WALL_REFLECTIVE,
+];
+const HAZARD_LAVA: i32 = 4;
+const WALL_MAGNETIC: i32 = 5;
+const WALL_REFLECTIVE: i32 = 6;
 
 #[derive(Clone)]
 pub enum MapTile {
@@ -1306,61 +2022,155 @@ pub fn translation_vector_from_index(index: i32, map_dimensions: Vector2<i32>) -
 }
 
 const DESTRUCTIBLE_WALL_HEALTH: f32 = 1.0;
+const BARREL_RADIUS: f32 = 0.4;
+const TURRET_RADIUS: f32 = 0.6;
 const DAMAGING_WALL_DAMAGE: f32 = 10.0;
 
-impl TileLayer {
-  pub fn into(&self) -> Vec<MapTile> {
-    self
-      .data
-      .iter()
-      .enumerate()
-      .filter_map(|(uindex, tile_data)| {
-        let index = uindex.try_into().unwrap();
-        if WALL.contains(tile_data) {
-          let collider =
-            ColliderBuilder::cuboid(TILE_DIMENSION_PHYSICS / 2.0, TILE_DIMENSION_PHYSICS / 2.0)
-              .translation(translation_vector_from_index(
-                index,
-                vector![self.width, self.height],
-              ))
-              .collision_groups(InteractionGroups {
-                memberships: COLLISION_GROUP_WALL,
-                filter: COLLISION_GROUP_PLAYER
-                  .union(COLLISION_GROUP_PLAYER_PROJECTILE)
-                  .union(COLLISION_GROUP_ENEMY)
-                  .union(COLLISION_GROUP_ENEMY_PROJECTILE),
-                ..Default::default()
-              })
-              .build();
-
-          let damageable = if *tile_data == WALL_DESTRUCTIBLE {
-            Some(DESTRUCTIBLE_WALL_HEALTH)
-          } else {
-            None
-          };
+fn walls_from_tiles(tiles: &[i32], map_dimensions: Vector2<i32>) -> Vec<MapTile> {
+  tiles
+    .iter()
+    .enumerate()
+    .filter_map(|(uindex, tile_data)| {
+      let index = uindex.try_into().unwrap();
+      if WALL.contains(tile_data) {
+        let collider =
+          ColliderBuilder::cuboid(TILE_DIMENSION_PHYSICS / 2.0, TILE_DIMENSION_PHYSICS / 2.0)
+            .translation(translation_vector_from_index(index, map_dimensions))
+            .collision_groups(built_wall_groups())
+            .build();
+
+        let damageable = if *tile_data == WALL_DESTRUCTIBLE {
+          Some(DESTRUCTIBLE_WALL_HEALTH)
+        } else {
+          None
+        };
 
-          let damaging = if *tile_data == WALL_DAMAGING {
-            Some(DAMAGING_WALL_DAMAGE)
-          } else {
-            None
-          };
+        let damaging = if *tile_data == WALL_DAMAGING {
+          Some(DAMAGING_WALL_DAMAGE)
+        } else {
+          None
+        };
+
+        return Some(MapTile::Wall(Wall {
+          id: index,
+          collider,
+          damageable,
+          damaging,
+          magnetic: *tile_data == WALL_MAGNETIC,
+          reflective: *tile_data == WALL_REFLECTIVE,
+        }));
+      }
+      if *tile_data != EMPTY && *tile_data != HAZARD_LAVA {
+        map_warning(format!("unaccounted wall {} at tile {}", tile_data, index));
+      }
+      None
+    })
+    .collect()
+}
+
+/* Hazard tiles don't collide, they are sensors filled with lava */
+fn hazards_from_tiles(tiles: &[i32], map_dimensions: Vector2<i32>) -> Vec<HazardZone> {
+  tiles
+    .iter()
+    .enumerate()
+    .filter(|(_, tile_data)| **tile_data == HAZARD_LAVA)
+    .map(|(uindex, _)| HazardZone {
+      collider: ColliderBuilder::cuboid(TILE_DIMENSION_PHYSICS / 2.0, TILE_DIMENSION_PHYSICS / 2.0)
+        .translation(translation_vector_from_index(
+          uindex.try_into().unwrap(),
+          map_dimensions,
+        ))
+        .sensor(true)
+        .collision_groups(groups(CollisionLayer::Hazard))
+        .build(),
+      kind: HazardKind::Lava,
+    })
+    .collect()
+}
 
-          return Some(MapTile::Wall(Wall {
-            collider,
-            damageable,
-            damaging,
-          }));
-        }
-        if *tile_data == EMPTY {
-          return None;
-        }
-        todo!("unaccounted wall {}", tile_data);
-      })
-      .collect()
+/* MARK: Map cycle */
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CyclePhase {
+  pub name: String,
+  /* Frame within the period that this phase begins on */
+  pub start: i64,
+  #[serde(default = "default_cycle_multiplier")]
+  pub gravity_multiplier: f32,
+  /* Scales enemy movement, at zero enemies also stop attacking */
+  #[serde(default = "default_cycle_multiplier")]
+  pub aggression: f32,
+  /* RGBA laid over the room while the phase lasts */
+  #[serde(default)]
+  pub tint: Option<[f32; 4]>,
+}
+
+fn default_cycle_multiplier() -> f32 {
+  1.0
+}
+
+/* Set with a `Cycle` map property, either a class value or a JSON string */
+#[derive(Clone, Debug, Deserialize)]
+pub struct MapCycle {
+  pub period: i64,
+  pub phases: Vec<CyclePhase>,
+}
+
+impl MapCycle {
+  pub fn phase_at(&self, frame: i64) -> Option<&CyclePhase> {
+    let time = frame.rem_euclid(self.period.max(1));
+
+    /* Before the first phase starts the last one is still running from the previous period */
+    self
+      .phases
+      .iter()
+      .filter(|phase| phase.start <= time)
+      .max_by_key(|phase| phase.start)
+      .or(self.phases.iter().max_by_key(|phase| phase.start))
+  }
+}
+
+/* MARK: Challenge rooms */
+
+/* Set with a `ChallengeRoom` map property, either a class value or a JSON string. Leaving the
+room through an exit within a medal's time, with every condition met, earns that medal */
+#[derive(Clone, Debug, Deserialize)]
+pub struct ChallengeRoom {
+  /* Seconds to reach an exit in for each medal */
+  pub gold: f32,
+  pub silver: f32,
+  pub bronze: f32,
+  #[serde(default)]
+  pub conditions: Vec<ChallengeCondition>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum ChallengeCondition {
+  /* The player leaves without having lost any health */
+  NoDamage,
+  /* No enemies are left in the room */
+  AllKills,
+}
+
+fn parse_class_property<T: DeserializeOwned>(value: &Value) -> Result<T, serde_json::Error> {
+  match value {
+    Value::String(raw) => serde_json::from_str(raw),
+    _ => serde_json::from_value(value.clone()),
   }
 }
 
 pub struct Map {
+  pub cycle: Option<MapCycle>,
+  /* Set with a `FriendlyFire` map property, enemy fire then hurts other enemies */
+  pub friendly_fire: bool,
+  /* Set with a `Scored` map property, kills then build a score and combo */
+  pub scored: bool,
+  /* Set with a `Streamed` map property, streamed rooms next to each other in the world are
+  played as one continuous area */
+  pub streamed: bool,
+  /* Set with a `Puzzle` map property, the world can then be rewound a few seconds */
+  pub puzzle: bool,
+  pub challenge: Option<ChallengeRoom>,
   pub colliders: Vec<MapTile>,
   pub player_spawns: Vec<PlayerSpawn>,
   pub enemy_spawns: Vec<EnemySpawn>,
@@ -1370,28 +2180,137 @@ pub struct Map {
   pub blocks: Vec<Block>,
   pub touch_sensors: Vec<TouchSensor>,
   pub gravity_sources: Vec<GravitySource>,
+  pub gravity_zones: Vec<GravityZone>,
   pub ability_pickups: Vec<AbilityPickup>,
+  pub log_entries: Vec<LogEntry>,
+  pub engine_upgrades: Vec<EngineUpgradePickup>,
+  pub hazards: Vec<HazardZone>,
+  pub fluid_volumes: Vec<FluidZone>,
+  pub magnetic_walls: Vec<MagneticWall>,
   pub chain_switches: Vec<ChainSwitch>,
   pub mount_points: Vec<MountPoint>,
+  pub tension_switches: Vec<TensionSwitch>,
   pub ands: Vec<And>,
   pub ors: Vec<Or>,
   pub gates: Vec<Gate>,
   pub locomotors: Vec<Locomotor>,
   pub glues: Vec<Glue>,
   pub engines: Vec<Engine>,
+  pub tutorial_triggers: Vec<TutorialTrigger>,
+  pub dialogue_triggers: Vec<DialogueTrigger>,
+  pub encounters: Vec<Encounter>,
+  pub explosive_barrels: Vec<ExplosiveBarrel>,
+  pub mounted_turrets: Vec<MountedTurret>,
 }
 
 impl RawMap {
-  pub fn as_map(&self) -> Map {
-    let tile_layer = &self.layers.0;
+  fn tileset_for_gid(&self, gid: u32) -> Option<&TilesetReference> {
+    self
+      .tilesets
+      .iter()
+      .filter(|tileset| tileset.firstgid <= gid)
+      .max_by_key(|tileset| tileset.firstgid)
+  }
 
-    let colliders = tile_layer.into();
+  fn flattened_layers(&self) -> Vec<&Layer> {
+    self.layers.iter().flat_map(Layer::flatten).collect()
+  }
 
-    let entities_layer = &self.layers.1;
+  /* Merges every tile layer into a single grid of wall kinds, with later layers drawn on top */
+  pub fn collider_tiles(&self) -> Vec<i32> {
+    self
+      .flattened_layers()
+      .into_iter()
+      .filter_map(|layer| match layer {
+        Layer::TileLayer(tile_layer) => Some(tile_layer),
+        _ => None,
+      })
+      .fold(
+        vec![EMPTY; (self.width * self.height) as usize],
+        |tiles, tile_layer| {
+          let data = match &tile_layer.data {
+            Some(TileData::Csv(data)) => data,
+            Some(TileData::Encoded(encoded)) => {
+              map_warning(format!(
+                "tile layer {} uses encoded data ({} bytes), which is unsupported",
+                tile_layer.name,
+                encoded.len()
+              ));
+              return tiles;
+            }
+            None => {
+              map_warning(format!(
+                "tile layer {} has no data (infinite maps are unsupported)",
+                tile_layer.name
+              ));
+              return tiles;
+            }
+          };
 
-    let map_height = tile_layer.height as f32 * 8.0;
+          if tile_layer.width != self.width || tile_layer.height != self.height {
+            map_warning(format!(
+              "tile layer {} does not match the map dimensions",
+              tile_layer.name
+            ));
+            return tiles;
+          }
 
-    let converted_entities = entities_layer.into(map_height);
+          tiles
+            .iter()
+            .zip(data)
+            .map(|(&tile, &gid)| {
+              let gid = gid
+                & !(TILED_FLIPPED_HORIZONTALLY_FLAG
+                  | TILED_FLIPPED_VERTICALLY_FLAG
+                  | TILED_FLIPPED_DIAGONALLY_FLAG
+                  | TILED_ROTATED_HEXAGONAL_120_FLAG);
+
+              if gid == 0 {
+                return tile;
+              }
+
+              match self.tileset_for_gid(gid) {
+                Some(tileset) if tileset.is_collider_tileset() => {
+                  (gid - tileset.firstgid) as i32 + WALL_COLLIDER
+                }
+                _ => tile,
+              }
+            })
+            .collect()
+        },
+      )
+  }
+
+  fn objects(&self) -> Vec<Object> {
+    self
+      .flattened_layers()
+      .into_iter()
+      .flat_map(|layer| match layer {
+        Layer::ObjectGroup(object_layer) => object_layer.objects.iter().collect(),
+        _ => vec![],
+      })
+      .filter_map(|object| normalize_object(object, &self.directory))
+      .filter_map(|(object_type, object)| Object::from_tiled(&object_type, object))
+      .collect()
+  }
+
+  pub fn as_map(&self) -> Map {
+    if self.tilewidth != MAP_TILE_DIMENSION || self.tileheight != MAP_TILE_DIMENSION {
+      map_warning(format!(
+        "map tiles are {}x{}, expected {}x{}",
+        self.tilewidth, self.tileheight, MAP_TILE_DIMENSION, MAP_TILE_DIMENSION
+      ));
+    }
+
+    let colliders = walls_from_tiles(&self.collider_tiles(), vector![self.width, self.height]);
+
+    let map_height = (self.height * self.tileheight) as f32;
+
+    let converted_entities = self
+      .objects()
+      .iter()
+      .map(|object| object.into(map_height))
+      .collect::<Vec<_>>();
 
     let enemy_spawns: Vec<EnemySpawn> = converted_entities
       .iter()
@@ -1493,6 +2412,82 @@ impl RawMap {
       })
       .collect::<Vec<_>>();
 
+    let log_entries = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::LogEntry(log_entry) = object {
+          Some(log_entry)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let engine_upgrades = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::EngineUpgrade(engine_upgrade) = object {
+          Some(engine_upgrade)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let hazards = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::Hazard(hazard) = object {
+          Some(hazard)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .chain(hazards_from_tiles(
+        &self.collider_tiles(),
+        vector![self.width, self.height],
+      ))
+      .collect::<Vec<_>>();
+
+    let gravity_zones = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::GravityZone(gravity_zone) = object {
+          Some(gravity_zone)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let fluid_volumes = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::FluidVolume(fluid_volume) = object {
+          Some(fluid_volume)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let magnetic_walls = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::MagneticWall(magnetic_wall) = object {
+          Some(magnetic_wall)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
     let chain_switches = converted_entities
       .iter()
       .flat_map(|object| {
@@ -1517,6 +2512,18 @@ impl RawMap {
       .cloned()
       .collect::<Vec<_>>();
 
+    let tension_switches = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::TensionSwitch(tension_switch) = object {
+          Some(tension_switch)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
     let ands = converted_entities
       .iter()
       .flat_map(|object| {
@@ -1589,7 +2596,109 @@ impl RawMap {
       .cloned()
       .collect::<Vec<_>>();
 
+    let tutorial_triggers = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::TutorialTrigger(tutorial_trigger) = object {
+          Some(tutorial_trigger)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let dialogue_triggers = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::DialogueTrigger(dialogue_trigger) = object {
+          Some(dialogue_trigger)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let encounters = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::Encounter(encounter) = object {
+          Some(encounter)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let explosive_barrels = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::ExplosiveBarrel(barrel) = object {
+          Some(barrel)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let mounted_turrets = converted_entities
+      .iter()
+      .flat_map(|object| {
+        if let MapComponent::MountedTurret(turret) = object {
+          Some(turret)
+        } else {
+          None
+        }
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let cycle = properties_as_object(Some(&self.properties))
+      .get("Cycle")
+      .and_then(|cycle| {
+        parse_class_property(cycle)
+          .map_err(|err| map_warning(format!("could not parse map cycle: {}", err)))
+          .ok()
+      });
+
+    let challenge = properties_as_object(Some(&self.properties))
+      .get("ChallengeRoom")
+      .and_then(|challenge| {
+        parse_class_property(challenge)
+          .map_err(|err| map_warning(format!("could not parse challenge room: {}", err)))
+          .ok()
+      });
+
+    let friendly_fire = properties_as_object(Some(&self.properties))
+      .get("FriendlyFire")
+      .and_then(Value::as_bool)
+      .unwrap_or(false);
+
+    let scored = properties_as_object(Some(&self.properties))
+      .get("Scored")
+      .and_then(Value::as_bool)
+      .unwrap_or(false);
+
+    let streamed = properties_as_object(Some(&self.properties))
+      .get("Streamed")
+      .and_then(Value::as_bool)
+      .unwrap_or(false);
+
+    let puzzle = properties_as_object(Some(&self.properties))
+      .get("Puzzle")
+      .and_then(Value::as_bool)
+      .unwrap_or(false);
+
     Map {
+      cycle,
+      friendly_fire,
+      scored,
+      streamed,
+      puzzle,
+      challenge,
       colliders,
       enemy_spawns,
       player_spawns,
@@ -1599,15 +2708,27 @@ impl RawMap {
       blocks,
       touch_sensors,
       gravity_sources,
+      gravity_zones,
       ability_pickups,
+      log_entries,
+      engine_upgrades,
+      hazards,
+      fluid_volumes,
+      magnetic_walls,
       chain_switches,
       mount_points,
+      tension_switches,
       ands,
       ors,
       gates,
       locomotors,
       glues,
       engines,
+      tutorial_triggers,
+      dialogue_triggers,
+      encounters,
+      explosive_barrels,
+      mounted_turrets,
     }
   }
 }
@@ -1616,35 +2737,461 @@ pub fn load_raw(file_path: &str) -> Option<RawMap> {
   fs::read_to_string(file_path)
     .translate()
     .as_ref()
-    .map(|raw_file| deser_map(raw_file))
+    .map(|raw_file| deser_map(raw_file, file_path))
+}
+
+/* Unlike load_raw, tolerates a file that is mid-write or malformed, for use during hot reload */
+fn try_load_raw(file_path: &str) -> Option<RawMap> {
+  fs::read_to_string(file_path)
+    .ok()
+    .and_then(|raw_file| parse_map(&raw_file, file_path).ok())
+}
+
+fn map_modified_time(file_path: &str) -> Option<SystemTime> {
+  fs::metadata(file_path)
+    .and_then(|metadata| metadata.modified())
+    .ok()
 }
 
 pub fn load(file_path: &str) -> Option<Map> {
   load_raw(file_path).as_ref().map(RawMap::as_map)
 }
 
+/* Like load, but reports what went wrong rather than panicking on a malformed file */
+pub fn try_load(file_path: &str) -> Result<Map, String> {
+  let raw_file = fs::read_to_string(file_path).map_err(|error| error.to_string())?;
+
+  parse_map(&raw_file, file_path)
+    .map(|map_raw| map_raw.as_map())
+    .map_err(|error| error.to_string())
+}
+
 pub fn load_world() -> Option<World> {
+  try_load_world().ok()
+}
+
+pub fn try_load_world() -> Result<World, String> {
   let world_path = Path::new(&current_dir().unwrap())
     .join("assets/maps/CL.world")
     .to_str()
     .unwrap()
     .to_string();
 
-  fs::read_to_string(world_path)
-    .ok()
-    .as_ref()
-    .map(|raw_file| serde_json::from_str(raw_file).expect("JSON was not well-formatted"))
+  let raw_file =
+    fs::read_to_string(&world_path).map_err(|error| format!("{world_path}: {error}"))?;
+
+  serde_json::from_str(&raw_file).map_err(|error| format!("{world_path}: {error}"))
+}
+
+/* Everything MapSystem reads when a game starts, so a broken asset is caught before it panics */
+pub fn check_maps_for(save_data: &SaveData) -> Result<(), String> {
+  try_load_world()?;
+
+  if let Some(seed) = save_data.daily_seed {
+    return try_load_daily(seed).map(|_| ());
+  }
+
+  save_data
+    .visited_maps
+    .iter()
+    .chain([&save_data.map_name])
+    .try_for_each(|map_name| {
+      try_load(&map_read_path(map_name))
+        .map(|_| ())
+        .map_err(|error| format!("{map_name}: {error}"))
+    })
+}
+
+/* MARK: Daily arena */
+
+pub const DAILY_MAP_NAME: &str = "daily";
+/* Room templates, `start.json` first and then arena rooms named `arena_*.json` */
+const DAILY_TEMPLATES_DIRECTORY: &str = "assets/maps/daily";
+const DAILY_ARENA_ROOMS: usize = 4;
+/* Each room's object ids are moved up by this much so rooms don't point at each other's */
+const DAILY_ROOM_ID_STRIDE: i64 = 10000;
+
+fn daily_template_paths() -> Result<(PathBuf, Vec<PathBuf>), String> {
+  let directory = current_dir().unwrap().join(DAILY_TEMPLATES_DIRECTORY);
+
+  let arena_paths = fs::read_dir(&directory)
+    .map_err(|error| format!("{}: {}", DAILY_TEMPLATES_DIRECTORY, error))?
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.path())
+    .filter(|path| {
+      path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .is_some_and(|file_name| file_name.starts_with("arena_") && file_name.ends_with(".json"))
+    })
+    /* Sorted so the same seed always picks the same rooms */
+    .sorted()
+    .collect::<Vec<_>>();
+
+  if arena_paths.is_empty() {
+    return Err(format!("{} has no arena rooms", DAILY_TEMPLATES_DIRECTORY));
+  }
+
+  Ok((directory.join("start.json"), arena_paths))
+}
+
+fn try_load_raw_template(path: &Path) -> Result<RawMap, String> {
+  let file_path = path.to_str().unwrap();
+  let raw_file = fs::read_to_string(path).map_err(|error| format!("{file_path}: {error}"))?;
+
+  parse_map(&raw_file, file_path).map_err(|error| format!("{file_path}: {error}"))
+}
+
+/* Moves an object's id, and the ids it points at, into its room's range */
+fn offset_object_ids(object: &Value, offset: i64) -> Value {
+  let offset_id = |value: &Value| match value.as_i64() {
+    Some(id) => Value::from(id + offset),
+    None => value.clone(),
+  };
+
+  let Some(object) = object.as_object() else {
+    return object.clone();
+  };
+
+  let properties = object
+    .get("properties")
+    .and_then(Value::as_array)
+    .map(|properties| {
+      properties
+        .iter()
+        .map(|property| {
+          let name = property.get("name").and_then(Value::as_str).unwrap_or("");
+          let value = property.get("value").unwrap_or(&Value::Null);
+
+          let value = if name.ends_with("Ids")
+            && let Some(ids) = value.as_str()
+          {
+            Value::from(
+              ids
+                .split(',')
+                .map(|id| match id.trim().parse::<i64>() {
+                  Ok(id) => (id + offset).to_string(),
+                  Err(_) => id.to_string(),
+                })
+                .join(","),
+            )
+          } else if name.ends_with("Id") {
+            offset_id(value)
+          } else {
+            value.clone()
+          };
+
+          let mut property = property.as_object().cloned().unwrap_or_default();
+          property.insert("value".to_string(), value);
+          Value::Object(property)
+        })
+        .collect::<Vec<_>>()
+    });
+
+  let mut object = object.clone();
+  if let Some(id) = object.get("id") {
+    object.insert("id".to_string(), offset_id(id));
+  }
+  if let Some(properties) = properties {
+    object.insert("properties".to_string(), Value::from(properties));
+  }
+  Value::Object(object)
+}
+
+impl RawMap {
+  /* Lays the rooms out left to right as a single room, walled in at either end. Rooms have to
+  be the same height, and ids only ever point within the room they're in */
+  fn stitch(rooms: &[RawMap], properties: Value, directory: PathBuf) -> Result<RawMap, String> {
+    let height = rooms.first().map_or(0, |room| room.height);
+    if let Some(room) = rooms.iter().find(|room| room.height != height) {
+      return Err(format!(
+        "daily rooms must all be {} tiles high, found one {} high",
+        height, room.height
+      ));
+    }
+
+    let width = rooms.iter().map(|room| room.width).sum::<i32>();
+    let room_tiles = rooms.iter().map(RawMap::collider_tiles).collect::<Vec<_>>();
+
+    /* Walls go back to being gids of a lone collider tileset */
+    let data = (0..height)
+      .flat_map(|row| {
+        rooms
+          .iter()
+          .zip(&room_tiles)
+          .flat_map(move |(room, tiles)| {
+            tiles[(row * room.width) as usize..((row + 1) * room.width) as usize].to_vec()
+          })
+          .collect::<Vec<_>>()
+      })
+      .enumerate()
+      .map(|(index, tile)| {
+        let column = index as i32 % width;
+        let tile = if column == 0 || column == width - 1 {
+          WALL_COLLIDER
+        } else {
+          tile
+        };
+
+        if tile == EMPTY {
+          0
+        } else {
+          (tile - WALL_COLLIDER + 1) as u32
+        }
+      })
+      .collect::<Vec<_>>();
+
+    let objects = rooms
+      .iter()
+      .enumerate()
+      .scan(0, |room_x, (index, room)| {
+        let offset_x = *room_x;
+        *room_x += room.width * room.tilewidth;
+
+        Some(
+          room
+            .flattened_layers()
+            .into_iter()
+            .flat_map(|layer| match layer {
+              Layer::ObjectGroup(object_layer) => object_layer.objects.clone(),
+              _ => vec![],
+            })
+            .map(|object| {
+              let mut object = offset_object_ids(&object, index as i64 * DAILY_ROOM_ID_STRIDE);
+              if let Some(x) = object.get("x").and_then(Value::as_f64) {
+                object["x"] = Value::from(x + offset_x as f64);
+              }
+              object
+            })
+            .collect::<Vec<_>>(),
+        )
+      })
+      .flatten()
+      .collect::<Vec<_>>();
+
+    Ok(RawMap {
+      width,
+      height,
+      tilewidth: MAP_TILE_DIMENSION,
+      tileheight: MAP_TILE_DIMENSION,
+      tilesets: vec![TilesetReference {
+        firstgid: 1,
+        source: None,
+        name: Some(COLLIDER_TILESET_NAME.to_string()),
+      }],
+      layers: vec![
+        Layer::TileLayer(TileLayer {
+          name: "colliders".to_string(),
+          data: Some(TileData::Csv(data)),
+          height,
+          width,
+        }),
+        Layer::ObjectGroup(ObjectLayer { objects }),
+      ],
+      properties,
+      directory,
+    })
+  }
+}
+
+/* The day's arena, the start room followed by arena rooms picked by the seed. It's always
+scored, whatever the start room says */
+pub fn try_load_daily(seed: u64) -> Result<Map, String> {
+  let (start_path, arena_paths) = daily_template_paths()?;
+
+  let rng = RandGenerator::new();
+  rng.srand(seed);
+
+  let rooms = [start_path]
+    .into_iter()
+    .chain((0..DAILY_ARENA_ROOMS).map(|_| arena_paths[rng.gen_range(0, arena_paths.len())].clone()))
+    .map(|path| try_load_raw_template(path.as_path()))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let properties = rooms[0]
+    .properties
+    .as_array()
+    .into_iter()
+    .flatten()
+    .filter(|property| property.get("name").and_then(Value::as_str) != Some("Scored"))
+    .cloned()
+    .chain([serde_json::json!({ "name": "Scored", "type": "bool", "value": true })])
+    .collect::<Vec<_>>();
+
+  RawMap::stitch(
+    &rooms,
+    Value::from(properties),
+    current_dir().unwrap().join(DAILY_TEMPLATES_DIRECTORY),
+  )
+  .map(|raw_map| raw_map.as_map())
+}
+
+/* MARK: Preloading */
+
+/* Everything a transition needs from disk, parsed ahead of time */
+pub struct PreparedMap {
+  pub tiles: Vec<i32>,
+  pub map: Rc<Map>,
+  pub modified: Option<SystemTime>,
+}
+
+/* Map holds script state which can't cross threads, so the background thread
+stops at the raw parse and the Map is built once the result arrives */
+type ParsedMap = (Vec<i32>, RawMap, Option<SystemTime>);
+
+#[derive(Clone)]
+pub enum Preload {
+  Pending(Rc<Receiver<Option<ParsedMap>>>),
+  Ready(Rc<PreparedMap>),
+}
+
+type Preloads = Rc<HashTrieMap<String, Preload>>;
+
+fn prepare((tiles, map_raw, modified): ParsedMap) -> Rc<PreparedMap> {
+  Rc::new(PreparedMap {
+    tiles,
+    map: Rc::new(map_raw.as_map()),
+    modified,
+  })
+}
+
+fn spawn_preload(map_name: &String) -> Preload {
+  let (sender, receiver) = mpsc::channel();
+  let map_path = map_read_path(map_name);
+
+  thread::spawn(move || {
+    let modified = map_modified_time(&map_path);
+    let parsed =
+      try_load_raw(&map_path).map(|map_raw| (map_raw.collider_tiles(), map_raw, modified));
+    /* The receiver is gone if the player already left for another room */
+    let _ = sender.send(parsed);
+  });
+
+  Preload::Pending(Rc::new(receiver))
+}
+
+/* Keeps preloads for maps reachable from this one and starts any that are missing */
+fn preloads_for_neighbours(map: &Map, preloads: &Preloads) -> Preloads {
+  Rc::new(
+    map
+      .map_transitions
+      .iter()
+      .map(|map_transition| &map_transition.map_name)
+      .unique()
+      .map(|map_name| {
+        (
+          map_name.clone(),
+          preloads
+            .get(map_name)
+            .cloned()
+            .unwrap_or_else(|| spawn_preload(map_name)),
+        )
+      })
+      .collect(),
+  )
+}
+
+/* Moves finished background parses into the cache, dropping any that failed */
+fn poll_preloads(preloads: &Preloads) -> Preloads {
+  Rc::new(
+    preloads
+      .iter()
+      .filter_map(|(map_name, preload)| match preload {
+        Preload::Pending(receiver) => match receiver.try_recv() {
+          Ok(parsed) => parsed.map(|parsed| (map_name.clone(), Preload::Ready(prepare(parsed)))),
+          Err(TryRecvError::Empty) => Some((map_name.clone(), preload.clone())),
+          Err(TryRecvError::Disconnected) => None,
+        },
+        Preload::Ready(_) => Some((map_name.clone(), preload.clone())),
+      })
+      .collect(),
+  )
+}
+
+fn load_prepared(map_name: &String) -> Rc<PreparedMap> {
+  let map_path = map_read_path(map_name);
+  let map_raw = load_raw(&map_path).unwrap();
+
+  prepare((
+    map_raw.collider_tiles(),
+    map_raw,
+    map_modified_time(&map_path),
+  ))
+}
+
+/* Falls back to the filesystem when the neighbour was never preloaded, or has
+been edited since while hot reloading */
+fn take_prepared(map_name: &String, preloads: &Preloads) -> Rc<PreparedMap> {
+  let prepared = match preloads.get(map_name) {
+    Some(Preload::Ready(prepared)) => Some(Rc::clone(prepared)),
+    /* Still parsing, waiting on it is no slower than starting over */
+    Some(Preload::Pending(receiver)) => receiver.recv().ok().flatten().map(prepare),
+    None => None,
+  }
+  .filter(|prepared| {
+    !HOT_RELOAD_MAPS || prepared.modified == map_modified_time(&map_read_path(map_name))
+  });
+
+  prepared.unwrap_or_else(|| {
+    log::debug("map", format!("{} was not preloaded", map_name));
+    load_prepared(map_name)
+  })
+}
+
+/* MARK: Streaming */
+
+/* Streamed rooms come in once the player is this close to them, in map pixels */
+const STREAM_RANGE: f32 = 256.0;
+
+/* A streamed room next to the one the physics world was built for, placed `offset` away from
+it in physics space */
+#[derive(Clone)]
+pub struct Chunk {
+  pub map: Rc<Map>,
+  pub offset: Vector<f32>,
+}
+
+/* Streamed rooms in range of the player, keyed by map name */
+pub type Chunks = Rc<HashTrieMap<String, Chunk>>;
+
+type MapRegistry = Rc<HashTrieMap<String, WorldMapWithTiles>>;
+
+fn world_map_named<'a>(world: &'a World, map_name: &str) -> Option<&'a WorldMap> {
+  world
+    .maps
+    .iter()
+    .find(|world_map| world_map.file_name == format!("{}.json", map_name))
+}
+
+/* Distance from a point to the nearest edge of a room, zero inside of it */
+fn distance_to_world_map(world_map: &WorldMap, x: f32, y: f32) -> f32 {
+  let dx = (world_map.x - x)
+    .max(x - (world_map.x + world_map.width))
+    .max(0.0);
+  let dy = (world_map.y - y)
+    .max(y - (world_map.y + world_map.height))
+    .max(0.0);
+  dx.hypot(dy)
 }
 
 pub struct MapSystem {
-  pub map: Option<Map>,
+  pub map: Option<Rc<Map>>,
   pub world: Rc<World>,
   pub current_map_name: String,
   pub target_player_spawn_id: i32,
-  pub map_registry: Rc<HashTrieMap<String, WorldMapWithTiles>>,
+  pub map_registry: MapRegistry,
+  pub current_map_modified: Option<SystemTime>,
+  pub hot_reloaded: bool,
+  pub preloads: Preloads,
+  /* Whether the current map streams its neighbours in rather than loading them */
+  pub streaming: bool,
+  pub chunks: Chunks,
 }
 
-fn map_read_path(map_name: &String) -> String {
+/* Only watch map files for changes in debug builds */
+const HOT_RELOAD_MAPS: bool = cfg!(debug_assertions);
+
+pub fn map_read_path(map_name: &String) -> String {
   Path::new(&current_dir().unwrap())
     .join(format!("assets/maps/{map_name}.json"))
     .to_str()
@@ -1652,8 +3199,100 @@ fn map_read_path(map_name: &String) -> String {
     .to_string()
 }
 
+impl MapSystem {
+  /* Preloads streamed rooms coming into range of the player, handing over those which are
+  ready as chunks and counting them as visited */
+  fn stream(
+    &self,
+    physics_system: &PhysicsSystem,
+    preloads: Preloads,
+  ) -> (Preloads, Chunks, MapRegistry) {
+    let Some(anchor) = world_map_named(&self.world, &self.current_map_name) else {
+      return (
+        preloads,
+        Rc::new(HashTrieMap::new()),
+        Rc::clone(&self.map_registry),
+      );
+    };
+
+    /* Physics space starts at the bottom left of the room the world was built for */
+    let player_translation =
+      physics_system.rigid_body_set[physics_system.player_handle].translation();
+    let player_x = anchor.x + physics_scalar_to_map(PhysicsScalar(player_translation.x));
+    let player_y =
+      anchor.y + anchor.height - physics_scalar_to_map(PhysicsScalar(player_translation.y));
+
+    let in_range = self
+      .world
+      .maps
+      .iter()
+      .filter(|world_map| world_map.file_name != anchor.file_name)
+      .filter(|world_map| distance_to_world_map(world_map, player_x, player_y) <= STREAM_RANGE)
+      .filter_map(|world_map| {
+        Some((
+          world_map.file_name.strip_suffix(".json")?.to_string(),
+          world_map,
+        ))
+      })
+      .collect::<Vec<_>>();
+
+    let preloads = in_range.iter().fold(preloads, |preloads, (map_name, _)| {
+      if preloads.contains_key(map_name) {
+        return preloads;
+      }
+      Rc::new(preloads.insert(map_name.clone(), spawn_preload(map_name)))
+    });
+
+    let streamed = in_range
+      .iter()
+      .filter_map(|(map_name, world_map)| match preloads.get(map_name) {
+        Some(Preload::Ready(prepared)) if prepared.map.streamed => {
+          Some((map_name, world_map, prepared))
+        }
+        _ => None,
+      })
+      .collect::<Vec<_>>();
+
+    let chunks = streamed
+      .iter()
+      .map(|(map_name, world_map, prepared)| {
+        (
+          map_name.to_string(),
+          Chunk {
+            map: Rc::clone(&prepared.map),
+            offset: vector![
+              *map_scalar_to_physics(world_map.x - anchor.x),
+              *map_scalar_to_physics(anchor.y + anchor.height - (world_map.y + world_map.height))
+            ],
+          },
+        )
+      })
+      .collect();
+
+    let map_registry = streamed
+      .iter()
+      .filter(|(map_name, _, _)| !self.map_registry.contains_key(*map_name))
+      .fold(
+        self.map_registry.as_ref().clone(),
+        |map_registry, (map_name, world_map, prepared)| {
+          map_registry.insert(
+            map_name.to_string(),
+            world_map.with_tiles(prepared.tiles.clone()),
+          )
+        },
+      );
+
+    (preloads, Rc::new(chunks), Rc::new(map_registry))
+  }
+}
+
 impl System for MapSystem {
   type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![Requirement::of::<PhysicsSystem>()]
+  }
+
   fn start(
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> std::rc::Rc<dyn System<Input = Self::Input>>
@@ -1670,7 +3309,7 @@ impl System for MapSystem {
         .iter()
         .map(|map_name| {
           let map_raw = load_raw(&map_read_path(map_name)).unwrap();
-          let tiles = map_raw.layers.0.data.clone();
+          let tiles = map_raw.collider_tiles();
 
           let world_map = world
             .maps
@@ -1683,13 +3322,29 @@ impl System for MapSystem {
         .collect::<HashTrieMap<_, _>>(),
     );
 
-    let map = load(&map_read_path(&save_data.map_name));
+    /* The daily arena isn't part of the world, it's put together from its seed each time */
+    let map = match save_data.daily_seed {
+      Some(seed) => try_load_daily(seed).ok(),
+      None => load(&map_read_path(&save_data.map_name)),
+    }
+    .map(Rc::new);
+    let preloads = map
+      .as_ref()
+      .map(|map| preloads_for_neighbours(map, &Rc::new(HashTrieMap::new())))
+      .unwrap_or_default();
+    let streaming = map.as_ref().is_some_and(|map| map.streamed);
+
     Rc::new(Self {
       world,
       map,
       map_registry,
       current_map_name: save_data.map_name.clone(),
       target_player_spawn_id: save_data.player_spawn_id,
+      current_map_modified: map_modified_time(&map_read_path(&save_data.map_name)),
+      hot_reloaded: false,
+      preloads,
+      streaming,
+      chunks: Rc::new(HashTrieMap::new()),
     })
   }
 
@@ -1697,11 +3352,17 @@ impl System for MapSystem {
     &self,
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> std::rc::Rc<dyn System<Input = Self::Input>> {
-    let physics_system = ctx.get::<PhysicsSystem>().unwrap();
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+
+    let current_map_path = map_read_path(&self.current_map_name);
+
+    let preloads = poll_preloads(&self.preloads);
 
     if let Some((map_name, id)) = physics_system.load_new_map.as_ref() {
-      let map_raw = load_raw(&map_read_path(map_name)).unwrap();
-      let tiles = map_raw.layers.0.data.clone();
+      record_event(format!("entered {} at spawn {}", map_name, id));
+      log::info("map", format!("loading {} at spawn {}", map_name, id));
+
+      let prepared = take_prepared(map_name, &preloads);
 
       let world_map = self
         .world
@@ -1710,27 +3371,163 @@ impl System for MapSystem {
         .find(|&world_map| world_map.file_name == format!("{}.json", map_name))
         .unwrap();
 
-      let map_registry = Rc::new(
-        self
-          .map_registry
-          .insert(map_name.to_string(), world_map.with_tiles(tiles)),
-      );
+      let map_registry = Rc::new(self.map_registry.insert(
+        map_name.to_string(),
+        world_map.with_tiles(prepared.tiles.clone()),
+      ));
 
       Rc::new(Self {
-        map: Some(map_raw.as_map()),
+        map: Some(Rc::clone(&prepared.map)),
         map_registry,
         current_map_name: map_name.clone(),
         target_player_spawn_id: *id,
         world: Rc::clone(&self.world),
+        current_map_modified: prepared.modified,
+        hot_reloaded: false,
+        preloads: preloads_for_neighbours(&prepared.map, &preloads),
+        streaming: prepared.map.streamed,
+        chunks: Rc::new(HashTrieMap::new()),
+      })
+    } else if HOT_RELOAD_MAPS
+      && let Some(modified) = map_modified_time(&current_map_path)
+      && self
+        .current_map_modified
+        .is_some_and(|last_modified| last_modified != modified)
+    {
+      /* MARK: Hot reload the current map */
+      let map_raw = try_load_raw(&current_map_path);
+
+      if map_raw.is_none() {
+        log::error(
+          "map",
+          format!("failed to hot reload map {}", self.current_map_name),
+        );
+      }
+
+      let map_registry = match map_raw.as_ref().and_then(|map_raw| {
+        self
+          .world
+          .maps
+          .iter()
+          .find(|&world_map| world_map.file_name == format!("{}.json", self.current_map_name))
+          .map(|world_map| world_map.with_tiles(map_raw.collider_tiles()))
+      }) {
+        Some(world_map) => Rc::new(
+          self
+            .map_registry
+            .insert(self.current_map_name.clone(), world_map),
+        ),
+        None => Rc::clone(&self.map_registry),
+      };
+
+      let map = map_raw.as_ref().map(RawMap::as_map).map(Rc::new);
+
+      Rc::new(Self {
+        streaming: map.as_ref().map_or(self.streaming, |map| map.streamed),
+        chunks: Rc::new(HashTrieMap::new()),
+        preloads: match map.as_ref() {
+          Some(map) => preloads_for_neighbours(map, &preloads),
+          None => preloads,
+        },
+        map,
+        map_registry,
+        current_map_name: self.current_map_name.clone(),
+        target_player_spawn_id: self.target_player_spawn_id,
+        world: Rc::clone(&self.world),
+        current_map_modified: Some(modified),
+        hot_reloaded: map_raw.is_some(),
       })
     } else {
+      /* MARK: Stream in the rooms around the player */
+      let (preloads, chunks, map_registry) = if self.streaming {
+        self.stream(&physics_system, preloads)
+      } else {
+        (
+          preloads,
+          Rc::clone(&self.chunks),
+          Rc::clone(&self.map_registry),
+        )
+      };
+
       Rc::new(Self {
         current_map_name: self.current_map_name.clone(),
         map: None,
-        map_registry: Rc::clone(&self.map_registry),
+        map_registry,
         target_player_spawn_id: self.target_player_spawn_id,
         world: Rc::clone(&self.world),
+        current_map_modified: self.current_map_modified,
+        hot_reloaded: false,
+        preloads,
+        streaming: self.streaming,
+        chunks,
       })
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fixture_path(file_name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+      .join("tests/fixtures/tiled")
+      .join(file_name)
+      .to_str()
+      .unwrap()
+      .to_string()
+  }
+
+  #[test]
+  fn tiled_maps_import_walls_from_every_collider_tile_layer() {
+    let map = try_load(&fixture_path("importer.tmj")).unwrap();
+
+    /* Decoration tiles make no walls, and flipped collider tiles are still walls */
+    let wall_ids = map
+      .colliders
+      .iter()
+      .map(|MapTile::Wall(wall)| wall.id)
+      .collect::<Vec<_>>();
+    assert_eq!(wall_ids, vec![8, 9, 10]);
+  }
+
+  #[test]
+  fn tiled_maps_import_objects_and_skip_unknown_ones() {
+    let map = try_load(&fixture_path("importer.tmj")).unwrap();
+
+    assert_eq!(
+      map
+        .player_spawns
+        .iter()
+        .map(|player_spawn| player_spawn.id)
+        .collect::<Vec<_>>(),
+      vec![1]
+    );
+
+    let [enemy_spawn] = map.enemy_spawns.as_slice() else {
+      panic!("expected one enemy spawn");
+    };
+    assert!(matches!(enemy_spawn.name, Enemy::Goblin(_)));
+    assert_eq!(enemy_spawn.persistent_id, Some(3));
+  }
+
+  #[test]
+  fn tiled_map_transitions_take_what_they_leave_out_from_their_template() {
+    let map = try_load(&fixture_path("importer.tmj")).unwrap();
+
+    let [map_transition] = map.map_transitions.as_slice() else {
+      panic!("expected one map transition");
+    };
+    assert_eq!(map_transition.map_name, "Elsewhere");
+    assert_eq!(map_transition.target_player_spawn_id, 7);
+
+    /* Only the height was overridden, the width is still the template's */
+    let half_extents = map_transition
+      .collider
+      .shape()
+      .as_cuboid()
+      .unwrap()
+      .half_extents;
+    assert!((half_extents.y - 2.0 * half_extents.x).abs() < 1e-5);
+  }
+}
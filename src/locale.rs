@@ -0,0 +1,136 @@
+use std::{
+  collections::HashMap,
+  env::current_dir,
+  fs,
+  sync::{
+    OnceLock,
+    atomic::{AtomicU8, Ordering},
+  },
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::log;
+
+/* One flat json table of keyed strings per language, named by its code */
+const LANG_DIR: &str = "assets/lang";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub enum Language {
+  #[default]
+  English,
+  Spanish,
+}
+
+impl Language {
+  pub const ALL: [Language; 2] = [Language::English, Language::Spanish];
+
+  fn code(&self) -> &'static str {
+    match self {
+      Language::English => "en",
+      Language::Spanish => "es",
+    }
+  }
+
+  /* Written in the language itself so it can be found from any other */
+  pub fn name(&self) -> &'static str {
+    match self {
+      Language::English => "english",
+      Language::Spanish => "español",
+    }
+  }
+
+  pub fn cycle(&self, step: i32) -> Self {
+    let index = Language::ALL
+      .iter()
+      .position(|language| language == self)
+      .unwrap() as i32;
+
+    Language::ALL[(index + step).rem_euclid(Language::ALL.len() as i32) as usize]
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+  Text(String),
+  /* Picked between by count, both languages so far only split singular from the rest */
+  Plural { one: String, other: String },
+}
+
+type Table = HashMap<String, Entry>;
+
+/* Like the palette, strings are looked up from all over. Tables are indexed by Language */
+static TABLES: OnceLock<Vec<Table>> = OnceLock::new();
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::English as u8);
+
+fn load_table(language: &Language) -> Table {
+  let table_path = current_dir()
+    .unwrap()
+    .join(LANG_DIR)
+    .join(format!("{}.json", language.code()));
+
+  fs::read_to_string(&table_path)
+    .map_err(|error| error.to_string())
+    .and_then(|raw| serde_json::from_str(&raw).map_err(|error| error.to_string()))
+    .unwrap_or_else(|error| {
+      log::warn(
+        "locale",
+        format!("couldn't load {}: {}", table_path.display(), error),
+      );
+      Table::new()
+    })
+}
+
+pub fn load_languages() {
+  let _ = TABLES.set(Language::ALL.iter().map(load_table).collect());
+}
+
+pub fn configure(language: Language) {
+  LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+fn language() -> Language {
+  Language::ALL[LANGUAGE.load(Ordering::Relaxed) as usize]
+}
+
+/* Missing keys fall back to English, so a partial translation still reads */
+fn lookup(key: &str) -> Option<&'static Entry> {
+  let tables = TABLES.get()?;
+
+  tables[language() as usize]
+    .get(key)
+    .or_else(|| tables[Language::English as usize].get(key))
+}
+
+/* Replaces each {name} with its value */
+fn fill(text: &str, args: &[(&str, String)]) -> String {
+  args.iter().fold(text.to_string(), |text, (name, value)| {
+    text.replace(&format!("{{{}}}", name), value)
+  })
+}
+
+/* The key itself is shown when no table has it, so gaps are easy to spot */
+pub fn tr(key: &str) -> String {
+  match lookup(key) {
+    Some(Entry::Text(text)) => text.clone(),
+    Some(Entry::Plural { other, .. }) => other.clone(),
+    None => key.to_string(),
+  }
+}
+
+pub fn tr_args(key: &str, args: &[(&str, String)]) -> String {
+  fill(&tr(key), args)
+}
+
+/* Picks the plural form for `count`, which is also available to the text as {count} */
+pub fn tr_count(key: &str, count: usize, args: &[(&str, String)]) -> String {
+  let text = match lookup(key) {
+    Some(Entry::Plural { one, .. }) if count == 1 => one.clone(),
+    Some(Entry::Plural { other, .. }) => other.clone(),
+    Some(Entry::Text(text)) => text.clone(),
+    None => key.to_string(),
+  };
+
+  fill(&fill(&text, &[("count", count.to_string())]), args)
+}
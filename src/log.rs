@@ -0,0 +1,113 @@
+use std::{
+  fs::{self, File},
+  io::Write,
+  path::Path,
+  sync::{
+    Mutex,
+    atomic::{AtomicU8, Ordering},
+  },
+  time,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::save::save_data_path;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, PartialOrd, Debug)]
+pub enum LogLevel {
+  Error,
+  #[default]
+  Warn,
+  Info,
+  Debug,
+}
+
+impl LogLevel {
+  pub const ALL: [LogLevel; 4] = [
+    LogLevel::Error,
+    LogLevel::Warn,
+    LogLevel::Info,
+    LogLevel::Debug,
+  ];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      LogLevel::Error => "error",
+      LogLevel::Warn => "warn",
+      LogLevel::Info => "info",
+      LogLevel::Debug => "debug",
+    }
+  }
+
+  pub fn cycle(&self, step: i32) -> Self {
+    let index = LogLevel::ALL
+      .iter()
+      .position(|level| level == self)
+      .unwrap() as i32;
+
+    LogLevel::ALL[(index + step).rem_euclid(LogLevel::ALL.len() as i32) as usize]
+  }
+}
+
+/* Logging is called from anywhere, including outside of the game loop, so the
+configuration lives in statics rather than a system */
+static VERBOSITY: AtomicU8 = AtomicU8::new(LogLevel::Warn as u8);
+static FILE_SINK: Mutex<Option<File>> = Mutex::new(None);
+
+const LOG_DIR_NAME: &str = "logs";
+
+fn timestamp() -> String {
+  let sys_time: DateTime<Utc> = time::SystemTime::now().into();
+  sys_time.format("%+").to_string()
+}
+
+fn open_file_sink() -> Option<File> {
+  let log_dir = save_data_path(LOG_DIR_NAME);
+  fs::create_dir_all(&log_dir).ok()?;
+  File::create(Path::new(&log_dir).join(format!("{}.log", timestamp()))).ok()
+}
+
+/* Keeps the current file open if the sink is already enabled */
+pub fn configure(verbosity: LogLevel, file_sink: bool) {
+  VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+
+  if let Ok(mut sink) = FILE_SINK.lock() {
+    if !file_sink {
+      *sink = None;
+    } else if sink.is_none() {
+      *sink = open_file_sink();
+    }
+  }
+}
+
+pub fn log(level: LogLevel, target: &str, message: String) {
+  if level as u8 > VERBOSITY.load(Ordering::Relaxed) {
+    return;
+  }
+
+  let line = format!("[{} {}] {}", level.name(), target, message);
+  println!("{}", line);
+
+  if let Ok(mut sink) = FILE_SINK.lock()
+    && let Some(file) = sink.as_mut()
+  {
+    let _ = writeln!(file, "{} {}", timestamp(), line);
+  }
+}
+
+pub fn error(target: &str, message: String) {
+  log(LogLevel::Error, target, message);
+}
+
+pub fn warn(target: &str, message: String) {
+  log(LogLevel::Warn, target, message);
+}
+
+pub fn info(target: &str, message: String) {
+  log(LogLevel::Info, target, message);
+}
+
+pub fn debug(target: &str, message: String) {
+  log(LogLevel::Debug, target, message);
+}
@@ -1,43 +1,75 @@
 use macroquad::prelude::*;
-use std::rc::Rc;
+use std::{env, process, rc::Rc};
 
 use crate::ability::AbilitySystem;
+use crate::benchmark::BENCHMARK_FLAG;
 use crate::camera::CameraSystem;
+use crate::challenge::ChallengeSystem;
 use crate::combat::CombatSystem;
-use crate::controls::ControlsSystem;
+use crate::controls::{ControlsSystem, CoopControlsSystem};
+use crate::daily::DailySystem;
+use crate::data::DataSystem;
+use crate::database::DatabaseSystem;
+use crate::dialogue::DialogueSystem;
 use crate::enemy::EnemySystem;
 use crate::graphics::GraphicsSystem;
 use crate::load_map::MapSystem;
+use crate::map_validation::VALIDATE_MAPS_FLAG;
 use crate::menu::{MenuSystem, QuitDecision};
 use crate::physics::PhysicsSystem;
-use crate::save::{SaveData, SaveSystem, load_save};
-use crate::system::{Process, System};
+use crate::procgen::GENERATE_MAP_FLAG;
+use crate::save::{SaveData, SaveSystem};
+use crate::score::ScoreSystem;
+use crate::settings::SettingsSystem;
+use crate::speedrun::SpeedrunSystem;
+use crate::storage::DATA_DIR_FLAG;
+use crate::system::{Process, Step, SubProcess, System};
+use crate::tutorial::TutorialSystem;
 
 mod ability;
+mod activation;
+mod benchmark;
 mod camera;
+mod challenge;
+mod collision;
 mod combat;
+mod contact_rules;
 mod controls;
+mod crash;
+mod daily;
+mod data;
+mod database;
+mod dialogue;
 mod ecs;
 mod enemy;
 mod f;
 mod graphics;
 mod graphics_utils;
 mod load_map;
+mod locale;
+mod log;
+mod map_validation;
 mod menu;
+mod palette;
 mod physics;
+mod procgen;
+mod profiler;
 mod save;
+mod score;
+#[cfg(feature = "scripting")]
+mod script;
+mod settings;
+mod spatial;
+mod speedrun;
+mod storage;
 mod system;
+mod text;
+mod tutorial;
 mod units;
 
 #[derive(Clone, Default)]
 pub struct Start;
 
-enum State {
-  MainMenu,
-  Game(SaveData),
-  Exit,
-}
-
 fn window_conf() -> Conf {
   Conf {
     window_title: "Longinus".to_string(),
@@ -47,56 +79,84 @@ fn window_conf() -> Conf {
   }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-  let mut state = State::MainMenu;
+/* Runs on top of the main menu, which resumes with the game's QuitDecision */
+fn game_process(save_data: SaveData) -> SubProcess {
+  crash::record_event(format!("started game on {}", save_data.map_name));
+
+  Process::new(&save_data)
+    .add_system(SaveSystem::start)
+    .add_system(SettingsSystem::start)
+    .add_system(DataSystem::start)
+    .add_system(CombatSystem::start)
+    .add_system(MapSystem::start)
+    .add_system(CameraSystem::start)
+    .add_system(PhysicsSystem::start)
+    .add_system(DatabaseSystem::start)
+    .add_system(SpeedrunSystem::start)
+    .add_system(ScoreSystem::start)
+    .add_system(ChallengeSystem::start)
+    .add_system(DailySystem::start)
+    .add_system(TutorialSystem::start)
+    .add_system(ControlsSystem::start)
+    .add_system(CoopControlsSystem::start)
+    .add_system(MenuSystem::start)
+    .add_system(EnemySystem::start)
+    .add_system(DialogueSystem::start)
+    .add_system(AbilitySystem::start)
+    .add_system(GraphicsSystem::start)
+    .sub_process(|ctx| {
+      ctx
+        .require::<MenuSystem<_>>()
+        .unwrap()
+        .quit_decision
+        .clone()
+    })
+}
+
+fn main() {
+  if let Some(data_dir) = env::args().skip_while(|arg| arg != DATA_DIR_FLAG).nth(1) {
+    storage::set_data_dir(&data_dir);
+  }
+
+  /* Checks the maps and exits without ever opening a window */
+  if env::args().any(|arg| arg == VALIDATE_MAPS_FLAG) {
+    process::exit(if map_validation::run() { 0 } else { 1 });
+  }
 
-  loop {
-    state = match state {
-      State::MainMenu => {
-        let save_data = Process::new(&Start)
-          .add_system(ControlsSystem::start)
-          .add_system(SaveSystem::start)
-          .add_system(MenuSystem::start)
-          .add_system(GraphicsSystem::start)
-          .start()
-          .run_move(|ctx| {
-            ctx
-              .get::<MenuSystem<_>>()
-              .unwrap()
-              .save_to_load
-              .as_ref()
-              .map(load_save)
-          })
-          .await;
-        State::Game(save_data)
-      }
-      State::Game(save_data) => {
-        let quit_decision = &Rc::new(
-          Process::new(&save_data)
-            .add_system(SaveSystem::start)
-            .add_system(CombatSystem::start)
-            .add_system(MapSystem::start)
-            .add_system(CameraSystem::start)
-            .add_system(PhysicsSystem::start)
-            .add_system(ControlsSystem::start)
-            .add_system(MenuSystem::start)
-            .add_system(EnemySystem::start)
-            .add_system(AbilitySystem::start)
-            .add_system(GraphicsSystem::start)
-            .start(),
-        )
-        .run(|ctx| ctx.get::<MenuSystem<_>>().unwrap().quit_decision.clone())
-        .await;
-        match quit_decision {
-          QuitDecision::LoadSave(save_to_load) => {
-            State::Game(load_save(&menu::SaveToLoad::SaveData(save_to_load.clone())))
-          }
-          QuitDecision::ToMainMenu => State::MainMenu,
-          QuitDecision::ToDesktop => State::Exit,
-        }
-      }
-      State::Exit => break,
-    };
+  /* Writes out a generated room for curating, again without a window */
+  if env::args().any(|arg| arg == GENERATE_MAP_FLAG) {
+    process::exit(if procgen::run() { 0 } else { 1 });
   }
+
+  /* Times a headless run through a stress room, for comparing performance across changes */
+  if env::args().any(|arg| arg == BENCHMARK_FLAG) {
+    process::exit(if benchmark::run() { 0 } else { 1 });
+  }
+
+  macroquad::Window::from_config(window_conf(), run());
+}
+
+async fn run() {
+  crash::install_panic_handler();
+  text::load_fonts();
+  locale::load_languages();
+
+  Rc::new(
+    Process::new(&Start)
+      .add_system(ControlsSystem::start)
+      .add_system(SaveSystem::start)
+      .add_system(MenuSystem::start)
+      .add_system(GraphicsSystem::start)
+      .start(),
+  )
+  .run_stack(|ctx| {
+    let menu_system = ctx.require::<MenuSystem<_>>().unwrap();
+
+    match (&menu_system.quit_decision, &menu_system.game_to_start) {
+      (Some(QuitDecision::ToDesktop), _) => Step::Exit(()),
+      (_, Some(save_data)) => Step::Push(game_process(save_data.clone())),
+      _ => Step::Continue,
+    }
+  })
+  .await;
 }
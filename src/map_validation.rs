@@ -0,0 +1,279 @@
+use std::{
+  collections::{HashMap, HashSet},
+  env::current_dir,
+  fs,
+};
+
+use itertools::Itertools;
+
+use crate::{
+  load_map::{Map, map_read_path, try_load},
+  save::{SaveData, initital_save_file_path},
+};
+
+pub const VALIDATE_MAPS_FLAG: &str = "--validate-maps";
+
+pub struct MapIssue {
+  pub map_name: String,
+  pub message: String,
+}
+
+/* Every map directly in assets/maps, skipping the old ones and editor backups */
+fn map_names() -> Vec<String> {
+  fs::read_dir(current_dir().unwrap().join("assets/maps"))
+    .map(|entries| {
+      entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+          path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        })
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .sorted()
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+/* Ids of everything in the map that other objects can point at */
+fn entity_ids(map: &Map) -> Vec<i32> {
+  map
+    .player_spawns
+    .iter()
+    .map(|player_spawn| player_spawn.id)
+    .chain(map.item_pickups.iter().map(|item_pickup| item_pickup.id))
+    .chain(map.blocks.iter().map(|block| block.id))
+    .chain(map.mount_points.iter().map(|mount_point| mount_point.id))
+    .chain(map.locomotors.iter().map(|locomotor| locomotor.id))
+    .chain(activator_ids(map))
+    .collect()
+}
+
+/* Ids of whatever carries an activation for others to read */
+fn activator_ids(map: &Map) -> Vec<i32> {
+  map
+    .touch_sensors
+    .iter()
+    .map(|touch_sensor| touch_sensor.id)
+    .chain(
+      map
+        .chain_switches
+        .iter()
+        .map(|chain_switch| chain_switch.id),
+    )
+    .chain(
+      map
+        .tension_switches
+        .iter()
+        .map(|tension_switch| tension_switch.id),
+    )
+    .chain(map.ands.iter().map(|and| and.id))
+    .chain(map.ors.iter().map(|or| or.id))
+    .chain(map.gates.iter().map(|gate| gate.id))
+    .chain(map.engines.iter().map(|engine| engine.id))
+    .collect()
+}
+
+/* Activator ids each object reads from, named for the report */
+fn activator_references(map: &Map) -> Vec<(&'static str, i32)> {
+  map
+    .gravity_sources
+    .iter()
+    .filter_map(|gravity_source| gravity_source.activator_id)
+    .map(|id| ("gravity source", id))
+    .chain(
+      map
+        .gravity_zones
+        .iter()
+        .filter_map(|gravity_zone| gravity_zone.activator_id)
+        .map(|id| ("gravity zone", id)),
+    )
+    .chain(
+      map
+        .ands
+        .iter()
+        .flat_map(|and| [("and", and.activator_ids.0), ("and", and.activator_ids.1)]),
+    )
+    .chain(
+      map
+        .ors
+        .iter()
+        .flat_map(|or| [("or", or.activator_ids.0), ("or", or.activator_ids.1)]),
+    )
+    .chain(map.gates.iter().map(|gate| ("gate", gate.activator_id)))
+    .chain(
+      map
+        .locomotors
+        .iter()
+        .map(|locomotor| ("locomotor", locomotor.activator_id)),
+    )
+    .chain(
+      map
+        .engines
+        .iter()
+        .filter_map(|engine| engine.activator_id)
+        .map(|id| ("engine", id)),
+    )
+    .collect()
+}
+
+pub fn validate_map(map: &Map, maps: &HashMap<String, Map>) -> Vec<String> {
+  let ids = entity_ids(map);
+  let activators = activator_ids(map).into_iter().collect::<HashSet<_>>();
+
+  let duplicate_ids = ids
+    .iter()
+    .duplicates()
+    .map(|id| format!("entity id {} is used more than once", id));
+
+  let dangling_activators = activator_references(map)
+    .into_iter()
+    .filter(|(_, id)| !activators.contains(id))
+    .map(|(kind, id)| format!("{} reads activator {} which doesn't exist", kind, id));
+
+  let dangling_transitions = map.map_transitions.iter().filter_map(|map_transition| {
+    match maps.get(&map_transition.map_name) {
+      None => Some(format!(
+        "transition points at missing map {}",
+        map_transition.map_name
+      )),
+      Some(target) => (!target
+        .player_spawns
+        .iter()
+        .any(|player_spawn| player_spawn.id == map_transition.target_player_spawn_id))
+      .then(|| {
+        format!(
+          "transition points at missing spawn {} in {}",
+          map_transition.target_player_spawn_id, map_transition.map_name
+        )
+      }),
+    }
+  });
+
+  let dangling_save_points = map
+    .save_points
+    .iter()
+    .filter(|save_point| {
+      !map
+        .player_spawns
+        .iter()
+        .any(|player_spawn| player_spawn.id == save_point.player_spawn_id)
+    })
+    .map(|save_point| {
+      format!(
+        "save point respawns at missing spawn {}",
+        save_point.player_spawn_id
+      )
+    });
+
+  duplicate_ids
+    .chain(dangling_activators)
+    .chain(dangling_transitions)
+    .chain(dangling_save_points)
+    .collect()
+}
+
+/* Spawns nothing can send the player to, neither a transition, a save point nor a new game */
+fn unreachable_spawns(
+  maps: &HashMap<String, Map>,
+  initial_spawn: Option<(String, i32)>,
+) -> Vec<MapIssue> {
+  let reachable = maps
+    .iter()
+    .flat_map(|(map_name, map)| {
+      map
+        .map_transitions
+        .iter()
+        .map(|map_transition| {
+          (
+            map_transition.map_name.clone(),
+            map_transition.target_player_spawn_id,
+          )
+        })
+        .chain(
+          map
+            .save_points
+            .iter()
+            .map(|save_point| (map_name.clone(), save_point.player_spawn_id)),
+        )
+        .collect::<Vec<_>>()
+    })
+    .chain(initial_spawn)
+    .collect::<HashSet<_>>();
+
+  maps
+    .iter()
+    .sorted_by_key(|(map_name, _)| *map_name)
+    .flat_map(|(map_name, map)| {
+      map
+        .player_spawns
+        .iter()
+        .filter(|player_spawn| !reachable.contains(&(map_name.clone(), player_spawn.id)))
+        .map(|player_spawn| MapIssue {
+          map_name: map_name.clone(),
+          message: format!("player spawn {} can't be reached", player_spawn.id),
+        })
+        .collect::<Vec<_>>()
+    })
+    .collect()
+}
+
+pub fn validate_maps() -> Vec<MapIssue> {
+  let (maps, load_issues): (Vec<_>, Vec<_>) = map_names()
+    .into_iter()
+    .map(|map_name| match try_load(&map_read_path(&map_name)) {
+      Ok(map) => Ok((map_name, map)),
+      Err(error) => Err(MapIssue {
+        map_name,
+        message: format!("failed to load: {}", error),
+      }),
+    })
+    .partition_result();
+
+  let maps = maps.into_iter().collect::<HashMap<_, _>>();
+
+  let initial_spawn = fs::read_to_string(initital_save_file_path())
+    .ok()
+    .and_then(|raw| serde_json::from_str::<SaveData>(&raw).ok())
+    .map(|save_data| (save_data.map_name, save_data.player_spawn_id));
+
+  let map_issues = maps
+    .iter()
+    .sorted_by_key(|(map_name, _)| *map_name)
+    .flat_map(|(map_name, map)| {
+      validate_map(map, &maps)
+        .into_iter()
+        .map(|message| MapIssue {
+          map_name: map_name.clone(),
+          message,
+        })
+        .collect::<Vec<_>>()
+    })
+    .collect::<Vec<_>>();
+
+  load_issues
+    .into_iter()
+    .chain(map_issues)
+    .chain(unreachable_spawns(&maps, initial_spawn))
+    .collect()
+}
+
+/* Prints the report, returning whether every map passed */
+pub fn run() -> bool {
+  let issues = validate_maps();
+
+  issues
+    .iter()
+    .for_each(|issue| println!("{}: {}", issue.map_name, issue.message));
+
+  println!(
+    "{} issue{} found across {} maps",
+    issues.len(),
+    if issues.len() == 1 { "" } else { "s" },
+    map_names().len()
+  );
+
+  issues.is_empty()
+}
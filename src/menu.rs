@@ -6,18 +6,25 @@ use rapier2d::prelude::*;
 use rapier2d::{na::Vector2, parry::utils::hashmap::HashMap};
 
 use crate::Start;
+use crate::challenge::{ChallengeResult, ChallengeSystem};
 use crate::combat::Direction;
+use crate::daily::{DailyResult, DailySystem, daily_seed};
+use crate::data::{DataSystem, FusionRecipe, fusion_output};
+use crate::database::{DatabaseEntry, DatabaseSystem};
 use crate::ecs::{Destroyed, EntityHandle};
 use crate::load_map::MapAbilityType;
+use crate::log;
 use crate::physics::PhysicsSystem;
-use crate::save::{SaveData, SaveSystem};
+use crate::save::{Difficulty, SaveData, SaveSystem, load_game};
+use crate::score::{RoomResults, ScoreSystem};
+use crate::settings::{Assist, Settings, SettingsSystem};
 use crate::{
   combat::{
-    CombatSystem, EQUIP_SLOTS_HEIGHT, EQUIP_SLOTS_WIDTH, EquippedModules, UnequippedModules,
-    WeaponModuleKind,
+    CombatSystem, EQUIP_SLOTS_HEIGHT, EQUIP_SLOTS_WIDTH, EquippedModules, ModuleInstance,
+    UnequippedModules, WeaponModuleKind,
   },
   controls::ControlsSystem,
-  system::System,
+  system::{Requirement, System},
 };
 
 #[derive(Clone)]
@@ -26,15 +33,35 @@ pub struct InventoryUpdateData {
   pub unequipped_modules: UnequippedModules,
 }
 
+#[derive(Clone)]
+pub struct FuseSelection {
+  pub unequipped_modules: UnequippedModules,
+  /* The index of the module picked to fuse first */
+  pub first_picked: Option<usize>,
+  /* What the first pick and the module under the cursor fuse into */
+  pub preview: Option<ModuleInstance>,
+}
+
 #[derive(Clone)]
 pub enum GameMenuKind {
   PauseMain,
   PauseLoadSave,
+  PauseSettings(Settings),
+  /* A page of the settings being edited, handed back to them once done */
+  PauseAssist(Settings),
+  PauseDatabase(Rc<Vec<DatabaseEntry>>),
+  PauseDatabaseEntry(DatabaseEntry),
+  /* True for the frame drawn without the hint, ready to be captured */
+  PhotoMode(bool),
   InventoryMain,
-  InventoryPickSlot(Option<WeaponModuleKind>, InventoryUpdateData),
+  InventoryPickSlot(Option<ModuleInstance>, InventoryUpdateData),
+  InventoryFuse(FuseSelection),
   SaveConfirm(i32),
   ModulePickupConfirm(WeaponModuleKind),
   AbilityPickupConfirm(MapAbilityType),
+  ScoreResults(RoomResults),
+  ChallengeResults(ChallengeResult),
+  DailyResults(DailyResult),
   GameOver,
 }
 
@@ -50,7 +77,11 @@ pub type GameMenu = Menu<GameMenuKind>;
 pub enum MainMenuKind {
   Main(bool),
   MainLoadSave,
+  #[allow(dead_code)]
   MainLoadSaveConfirm,
+  NewGameDifficulty,
+  /* Starting a game failed, holds what went wrong */
+  Error(String),
 }
 
 pub type MainMenu = Menu<MainMenuKind>;
@@ -68,8 +99,12 @@ struct MenuInput {
 
 #[derive(Clone, Debug)]
 pub enum SaveToLoad {
-  Initial,
+  Initial(Difficulty),
   SaveData(String),
+  /* A daily arena run with the given seed */
+  Daily(u64),
+  /* Left by save & quit, gone once it's loaded */
+  Suspended,
 }
 
 #[derive(Clone, Default)]
@@ -78,13 +113,46 @@ pub struct MenuSystem<Input> {
   pub active_main_menus: Vec<MainMenu>,
   pub inventory_update: Option<InventoryUpdateData>,
   pub save_point_confirmed_id: Option<i32>,
-  pub save_to_load: Option<SaveToLoad>,
+  /* Set on the frame the player suspends, which saves the room as is before quitting */
+  pub suspend_confirmed: bool,
+  /* The chosen save, once it and its maps were read without error */
+  pub game_to_start: Option<SaveData>,
   pub quit_decision: Option<QuitDecision>,
+  pub settings_update: Option<Settings>,
+  /* Lets the next gamepad to press a button take control */
+  pub release_controller: bool,
+  /* Set for the frame after photo mode drew its capture frame */
+  pub take_screenshot: bool,
   phantom: PhantomData<Input>,
 }
 
+impl<Input> MenuSystem<Input> {
+  pub fn in_photo_mode(&self) -> bool {
+    self
+      .active_menus
+      .first()
+      .is_some_and(|menu| matches!(menu.kind, GameMenuKind::PhotoMode(_)))
+  }
+}
+
 impl<Input: Clone + Default + 'static> System for MenuSystem<Input> {
   type Input = Input;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<ControlsSystem<_>>(),
+      Requirement::of::<SaveSystem<_>>(),
+      Requirement::when::<SaveData, CombatSystem>(),
+      Requirement::when::<SaveData, SettingsSystem>(),
+      Requirement::when::<SaveData, PhysicsSystem>(),
+      Requirement::when::<SaveData, DataSystem>(),
+      Requirement::when::<SaveData, DatabaseSystem>(),
+      Requirement::when::<SaveData, ScoreSystem>(),
+      Requirement::when::<SaveData, ChallengeSystem>(),
+      Requirement::when::<SaveData, DailySystem>(),
+    ]
+  }
+
   fn start(
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> std::rc::Rc<dyn System<Input = Self::Input>>
@@ -92,12 +160,12 @@ impl<Input: Clone + Default + 'static> System for MenuSystem<Input> {
     Self: Sized,
   {
     if ctx.downcast::<Start>().is_some() {
-      let save_system = ctx.get::<SaveSystem<_>>().unwrap();
+      let save_system = ctx.require::<SaveSystem<_>>().unwrap();
 
       return Rc::new(Self {
         active_main_menus: vec![MainMenu {
           cursor_position: vector![0, 0],
-          kind: MainMenuKind::Main(!save_system.available_save_data.is_empty()),
+          kind: MainMenuKind::Main(save_system.can_continue()),
         }],
         ..Default::default()
       });
@@ -113,7 +181,34 @@ impl<Input: Clone + Default + 'static> System for MenuSystem<Input> {
     &self,
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> Rc<dyn System<Input = Self::Input>> {
-    let controls_system = ctx.get::<ControlsSystem<_>>().unwrap();
+    /* Back from a game, reopen the main menu or pass the decision along */
+    if ctx.downcast::<Start>().is_some()
+      && let Some(quit_decision) = ctx.resumed_with::<QuitDecision>()
+    {
+      let save_system = ctx.require::<SaveSystem<_>>().unwrap();
+      let (quit_decision, save_to_load) = match quit_decision.as_ref() {
+        QuitDecision::ToDesktop => (Some(QuitDecision::ToDesktop), None),
+        QuitDecision::LoadSave(save) => (None, Some(SaveToLoad::SaveData(save.clone()))),
+        QuitDecision::ToMainMenu => (None, None),
+      };
+
+      let (active_main_menus, game_to_start) = start_game(
+        save_to_load.as_ref(),
+        vec![MainMenu {
+          cursor_position: vector![0, 0],
+          kind: MainMenuKind::Main(save_system.can_continue()),
+        }],
+      );
+
+      return Rc::new(Self {
+        active_main_menus,
+        game_to_start,
+        quit_decision,
+        ..Default::default()
+      });
+    }
+
+    let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
 
     if controls_system.last_frame.is_none() {
       return Rc::new(self.clone());
@@ -133,24 +228,36 @@ impl<Input: Clone + Default + 'static> System for MenuSystem<Input> {
       inventory: controls_system.inventory && !(last_frame.inventory),
     };
 
-    let save_system = ctx.get::<SaveSystem<_>>().unwrap();
+    let save_system = ctx.require::<SaveSystem<_>>().unwrap();
 
     if let Some(ctx) = ctx.downcast::<SaveData>() {
-      let combat_system = ctx.get::<CombatSystem>().unwrap();
+      let combat_system = ctx.require::<CombatSystem>().unwrap();
+      let settings_system = ctx.require::<SettingsSystem>().unwrap();
 
       if !self.active_menus.is_empty() {
+        let data_system = ctx.require::<DataSystem>().unwrap();
+        let database_system = ctx.require::<DatabaseSystem>().unwrap();
+
         let NextMenuUpdate {
           menus: next_menus,
           inventory_update,
           save_point_confirmed_id,
-          save_to_load,
+          suspend_confirmed,
           quit_decision,
+          settings_update,
+          release_controller,
+          take_screenshot,
         } = next_menus(
           &self.active_menus[0],
           &input,
-          &combat_system.unequipped_modules,
-          &combat_system.equipped_modules,
-          &save_system.available_save_data,
+          &MenuData {
+            unequipped_modules: &combat_system.unequipped_modules,
+            equipped_modules: &combat_system.equipped_modules,
+            available_saves: &save_system.available_save_data,
+            settings: &settings_system.settings,
+            database_entries: &database_system.entries(&data_system.data.logs),
+            fusion_recipes: &data_system.data.fusion,
+          },
         );
         return Rc::new(Self {
           active_menus: next_menus
@@ -160,16 +267,28 @@ impl<Input: Clone + Default + 'static> System for MenuSystem<Input> {
             .collect(),
           inventory_update,
           save_point_confirmed_id,
-          save_to_load,
+          suspend_confirmed,
           quit_decision,
+          settings_update,
+          release_controller,
+          take_screenshot,
           ..Default::default()
         });
       }
 
-      let physics_system = ctx.get::<PhysicsSystem>().unwrap();
+      let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+      let score_system = ctx.require::<ScoreSystem>().unwrap();
+      let challenge_system = ctx.require::<ChallengeSystem>().unwrap();
+      let daily_system = ctx.require::<DailySystem>().unwrap();
 
       return Rc::new(Self {
-        active_menus: open_menu(&input, physics_system),
+        active_menus: open_menu(
+          &input,
+          physics_system,
+          score_system.results.as_ref(),
+          challenge_system.result.as_ref(),
+          daily_system.finished.as_ref(),
+        ),
         ..Default::default()
       });
     }
@@ -182,15 +301,21 @@ impl<Input: Clone + Default + 'static> System for MenuSystem<Input> {
         &self.active_main_menus[0],
         &input,
         &save_system.available_save_data,
+        save_system.suspend_save_available,
       );
 
-      return Rc::new(Self {
-        active_main_menus: next_menus
+      let (active_main_menus, game_to_start) = start_game(
+        save_to_load.as_ref(),
+        next_menus
           .iter()
           .chain(self.active_main_menus.clone()[1..].iter())
           .cloned()
           .collect(),
-        save_to_load,
+      );
+
+      return Rc::new(Self {
+        active_main_menus,
+        game_to_start,
         ..Default::default()
       });
     }
@@ -199,7 +324,13 @@ impl<Input: Clone + Default + 'static> System for MenuSystem<Input> {
   }
 }
 
-fn open_menu(input: &MenuInput, physics_system: Rc<PhysicsSystem>) -> Vec<GameMenu> {
+fn open_menu(
+  input: &MenuInput,
+  physics_system: Rc<PhysicsSystem>,
+  score_results: Option<&RoomResults>,
+  challenge_result: Option<&ChallengeResult>,
+  daily_result: Option<&DailyResult>,
+) -> Vec<GameMenu> {
   if physics_system
     .entities
     .get(&EntityHandle::RigidBody(physics_system.player_handle))
@@ -259,8 +390,26 @@ fn open_menu(input: &MenuInput, physics_system: Rc<PhysicsSystem>) -> Vec<GameMe
       cursor_position: vector![0, 0],
     });
 
+  let score_results = score_results.map(|results| GameMenu {
+    kind: GameMenuKind::ScoreResults(results.clone()),
+    cursor_position: vector![0, 0],
+  });
+
+  let challenge_result = challenge_result.map(|result| GameMenu {
+    kind: GameMenuKind::ChallengeResults(result.clone()),
+    cursor_position: vector![0, 0],
+  });
+
+  let daily_result = daily_result.map(|result| GameMenu {
+    kind: GameMenuKind::DailyResults(result.clone()),
+    cursor_position: vector![0, 0],
+  });
+
   save_confirm
     .into_iter()
+    .chain(score_results)
+    .chain(challenge_result)
+    .chain(daily_result)
     .chain(inventory_main)
     .chain(pause_main)
     .chain(ability_pickup_confirm)
@@ -268,6 +417,32 @@ fn open_menu(input: &MenuInput, physics_system: Rc<PhysicsSystem>) -> Vec<GameMe
     .collect()
 }
 
+/* Loads the chosen save, opening the error screen over the menus instead if it or its maps
+are missing or corrupt */
+fn start_game(
+  save_to_load: Option<&SaveToLoad>,
+  menus: Vec<MainMenu>,
+) -> (Vec<MainMenu>, Option<SaveData>) {
+  match save_to_load.map(load_game) {
+    Some(Ok(save_data)) => (menus, Some(save_data)),
+    Some(Err(error)) => {
+      log::error("menu", format!("couldn't start the game: {}", error));
+
+      (
+        [MainMenu {
+          cursor_position: vector![0, 0],
+          kind: MainMenuKind::Error(error),
+        }]
+        .into_iter()
+        .chain(menus)
+        .collect(),
+        None,
+      )
+    }
+    None => (menus, None),
+  }
+}
+
 #[derive(Default)]
 struct NextMainMenuUpdate {
   menus: Vec<MainMenu>,
@@ -278,6 +453,7 @@ fn next_main_menus(
   current_menu: &MainMenu,
   input: &MenuInput,
   available_saves: &[String],
+  suspend_save_available: bool,
 ) -> NextMainMenuUpdate {
   if !(input.up || input.down || input.left || input.right || input.confirm || input.cancel) {
     return NextMainMenuUpdate {
@@ -286,11 +462,13 @@ fn next_main_menus(
     };
   }
 
-  match current_menu.kind {
+  match &current_menu.kind {
     MainMenuKind::Main(should_include_continue_option) => {
+      let should_include_continue_option = *should_include_continue_option;
       let (menus, save_to_load) = menu_main(
         current_menu.cursor_position,
         available_saves,
+        suspend_save_available,
         input,
         should_include_continue_option,
       );
@@ -307,6 +485,22 @@ fn next_main_menus(
         save_to_load: save_to_load.map(SaveToLoad::SaveData),
       }
     }
+    MainMenuKind::NewGameDifficulty => {
+      let (menus, difficulty) = menu_new_game_difficulty(current_menu.cursor_position, input);
+      NextMainMenuUpdate {
+        menus,
+        save_to_load: difficulty.map(SaveToLoad::Initial),
+      }
+    }
+    /* The only option is returning to the menu underneath */
+    MainMenuKind::Error(_) => NextMainMenuUpdate {
+      menus: if input.confirm || input.cancel {
+        vec![]
+      } else {
+        vec![current_menu.clone()]
+      },
+      ..Default::default()
+    },
     _ => todo!("Unimplemented"),
   }
 }
@@ -316,17 +510,45 @@ struct NextMenuUpdate {
   menus: Vec<GameMenu>,
   inventory_update: Option<InventoryUpdateData>,
   save_point_confirmed_id: Option<i32>,
-  save_to_load: Option<SaveToLoad>,
+  suspend_confirmed: bool,
   quit_decision: Option<QuitDecision>,
+  settings_update: Option<Settings>,
+  release_controller: bool,
+  take_screenshot: bool,
 }
 
-fn next_menus(
-  current_menu: &GameMenu,
-  input: &MenuInput,
-  unequipped_modules: &UnequippedModules,
-  equipped_modules: &EquippedModules,
-  available_saves: &Vec<String>,
-) -> NextMenuUpdate {
+/* What the menus show from the rest of the game */
+struct MenuData<'a> {
+  unequipped_modules: &'a UnequippedModules,
+  equipped_modules: &'a EquippedModules,
+  available_saves: &'a [String],
+  settings: &'a Settings,
+  database_entries: &'a [DatabaseEntry],
+  fusion_recipes: &'a [FusionRecipe],
+}
+
+fn next_menus(current_menu: &GameMenu, input: &MenuInput, data: &MenuData) -> NextMenuUpdate {
+  let MenuData {
+    unequipped_modules,
+    equipped_modules,
+    available_saves,
+    settings,
+    database_entries,
+    fusion_recipes,
+  } = *data;
+
+  /* The capture frame has been drawn, grab it and bring the hint back */
+  if let GameMenuKind::PhotoMode(true) = current_menu.kind {
+    return NextMenuUpdate {
+      menus: vec![GameMenu {
+        kind: GameMenuKind::PhotoMode(false),
+        cursor_position: current_menu.cursor_position,
+      }],
+      take_screenshot: true,
+      ..Default::default()
+    };
+  }
+
   if !(input.up || input.down || input.left || input.right || input.confirm || input.cancel) {
     return NextMenuUpdate {
       menus: vec![current_menu.clone()],
@@ -365,13 +587,49 @@ fn next_menus(
 
   match current_menu.kind.clone() {
     GameMenuKind::PauseMain => {
-      let (menus, quit_decision) = pause_main(current_menu.cursor_position, input);
+      let (menus, quit_decision) = pause_main(
+        current_menu.cursor_position,
+        input,
+        settings,
+        database_entries,
+      );
       NextMenuUpdate {
         menus,
-        quit_decision,
+        suspend_confirmed: quit_decision == Some(PauseQuit::Suspend),
+        quit_decision: quit_decision.map(|_| QuitDecision::ToMainMenu),
+        ..Default::default()
+      }
+    }
+    GameMenuKind::PauseSettings(edited_settings) => {
+      let (menus, settings_update, release_controller) =
+        pause_settings(current_menu.cursor_position, input, edited_settings);
+      NextMenuUpdate {
+        menus,
+        settings_update,
+        release_controller,
         ..Default::default()
       }
     }
+    GameMenuKind::PauseAssist(edited_settings) => NextMenuUpdate {
+      menus: pause_assist(current_menu.cursor_position, input, edited_settings),
+      ..Default::default()
+    },
+    GameMenuKind::PauseDatabase(entries) => NextMenuUpdate {
+      menus: pause_database(current_menu.cursor_position, input, entries),
+      ..Default::default()
+    },
+    GameMenuKind::PauseDatabaseEntry(entry) => NextMenuUpdate {
+      menus: pause_database_entry(input, entry),
+      ..Default::default()
+    },
+    /* Moving the camera is left to CameraSystem, confirm takes a picture */
+    GameMenuKind::PhotoMode(_) => NextMenuUpdate {
+      menus: vec![GameMenu {
+        kind: GameMenuKind::PhotoMode(input.confirm),
+        cursor_position: current_menu.cursor_position,
+      }],
+      ..Default::default()
+    },
     GameMenuKind::PauseLoadSave => {
       let (menus, save_to_load) =
         pause_load_game(current_menu.cursor_position, input, available_saves);
@@ -390,6 +648,20 @@ fn next_menus(
       ),
       ..Default::default()
     },
+    GameMenuKind::InventoryFuse(selection) => {
+      let (menus, inventory_update) = inventory_fuse(
+        current_menu.cursor_position,
+        input,
+        &selection,
+        equipped_modules,
+        fusion_recipes,
+      );
+      NextMenuUpdate {
+        menus,
+        inventory_update,
+        ..Default::default()
+      }
+    }
     GameMenuKind::InventoryPickSlot(currently_holding, inventory_update) => {
       let (menus, inventory_update) = inventory_pick_slot(
         current_menu.cursor_position,
@@ -426,6 +698,22 @@ fn next_menus(
       menus: ability_pickup_confirm(input, ability_type),
       ..Default::default()
     },
+    GameMenuKind::ScoreResults(results) => NextMenuUpdate {
+      menus: score_results(input, results),
+      ..Default::default()
+    },
+    GameMenuKind::ChallengeResults(result) => NextMenuUpdate {
+      menus: challenge_results(input, result),
+      ..Default::default()
+    },
+    GameMenuKind::DailyResults(result) => {
+      let (quit_decision, menus) = daily_results(input, result);
+      NextMenuUpdate {
+        menus,
+        quit_decision,
+        ..Default::default()
+      }
+    }
     GameMenuKind::GameOver => {
       let (quit_decision, menus) = game_over(input);
       NextMenuUpdate {
@@ -440,6 +728,7 @@ fn next_menus(
 fn menu_main(
   cursor_position: Vector2<i32>,
   available_saves: &[String],
+  suspend_save_available: bool,
   input: &MenuInput,
   should_include_continue_option: bool,
 ) -> (Vec<MainMenu>, Option<SaveToLoad>) {
@@ -447,7 +736,7 @@ fn menu_main(
     cursor_position,
     0,
     0,
-    if should_include_continue_option { 2 } else { 1 },
+    if should_include_continue_option { 3 } else { 2 },
     input,
     None,
   );
@@ -475,12 +764,23 @@ fn menu_main(
   } else {
     cursor_position == vector![0, 1]
   };
+  let daily = if should_include_continue_option {
+    cursor_position == vector![0, 3]
+  } else {
+    cursor_position == vector![0, 2]
+  };
+
+  /* A save & quit is dropped once any other game is started, so one still around is newest */
+  if continue_game && suspend_save_available {
+    log::debug("menu", "resuming the suspended game".to_string());
+    return (vec![], Some(SaveToLoad::Suspended));
+  }
 
   if continue_game {
     let most_recent_save = available_saves
       .iter()
       .fold("", |init, elem| if *init > **elem { init } else { elem });
-    println!("{}", most_recent_save);
+    log::debug("menu", format!("continuing from {}", most_recent_save));
     return (
       vec![],
       Some(SaveToLoad::SaveData(most_recent_save.to_string())),
@@ -488,7 +788,19 @@ fn menu_main(
   }
 
   if new_game {
-    return (vec![], Some(SaveToLoad::Initial));
+    return (
+      vec![
+        MainMenu {
+          cursor_position: NEW_GAME_DEFAULT_DIFFICULTY_CURSOR,
+          kind: MainMenuKind::NewGameDifficulty,
+        },
+        MainMenu {
+          cursor_position,
+          kind: MainMenuKind::Main(should_include_continue_option),
+        },
+      ],
+      None,
+    );
   }
 
   if load_game {
@@ -507,12 +819,55 @@ fn menu_main(
     );
   }
 
+  if daily {
+    return (vec![], Some(SaveToLoad::Daily(daily_seed())));
+  }
+
   todo!("Unhandled cursor positon {}", cursor_position);
 }
 
+/* Rows are the difficulties in order, followed by cancel */
+const NEW_GAME_DEFAULT_DIFFICULTY_CURSOR: Vector2<i32> = vector![0, 1];
+
+fn menu_new_game_difficulty(
+  cursor_position: Vector2<i32>,
+  input: &MenuInput,
+) -> (Vec<MainMenu>, Option<Difficulty>) {
+  let cursor_position = handle_cursor_movement(
+    cursor_position,
+    0,
+    0,
+    Difficulty::ALL.len() as i32,
+    input,
+    None,
+  );
+
+  if input.cancel {
+    return (vec![], None);
+  }
+
+  /* No change if confirm is not input */
+  if !input.confirm {
+    return (
+      vec![MainMenu {
+        cursor_position,
+        kind: MainMenuKind::NewGameDifficulty,
+      }],
+      None,
+    );
+  }
+
+  match Difficulty::ALL.get(cursor_position.y as usize) {
+    Some(difficulty) => (vec![], Some(*difficulty)),
+    /* Cancel */
+    None => (vec![], None),
+  }
+}
+
 #[derive(Clone)]
 pub enum QuitDecision {
   ToMainMenu,
+  #[allow(dead_code)]
   ToDesktop,
   LoadSave(String),
 }
@@ -552,11 +907,20 @@ fn menu_load_game(
   (vec![], Some(available_saves[save_index_to_load].clone()))
 }
 
+/* Both go back to the main menu, suspending saves the room as it is first */
+#[derive(PartialEq)]
+enum PauseQuit {
+  ToMainMenu,
+  Suspend,
+}
+
 fn pause_main(
   cursor_position: Vector2<i32>,
   input: &MenuInput,
-) -> (Vec<GameMenu>, Option<QuitDecision>) {
-  let cursor_position = handle_cursor_movement(cursor_position, 0, 0, 2, input, None);
+  settings: &Settings,
+  database_entries: &[DatabaseEntry],
+) -> (Vec<GameMenu>, Option<PauseQuit>) {
+  let cursor_position = handle_cursor_movement(cursor_position, 0, 0, 6, input, None);
 
   /* No change if confirm is not input */
   if !input.confirm {
@@ -572,7 +936,11 @@ fn pause_main(
   /* Transition to next menu */
   let cancel = cursor_position == vector![0, 0];
   let load_game = cursor_position == vector![0, 1];
-  let quit_to_menu = cursor_position == vector![0, 2];
+  let open_settings = cursor_position == vector![0, 2];
+  let open_database = cursor_position == vector![0, 3];
+  let open_photo_mode = cursor_position == vector![0, 4];
+  let suspend = cursor_position == vector![0, 5];
+  let quit_to_menu = cursor_position == vector![0, 6];
 
   if cancel {
     return (vec![], None);
@@ -594,17 +962,257 @@ fn pause_main(
     );
   }
 
+  if open_settings {
+    return (
+      vec![
+        Menu {
+          cursor_position: vector![0, 0],
+          kind: GameMenuKind::PauseSettings(*settings),
+        },
+        Menu {
+          cursor_position,
+          kind: GameMenuKind::PauseMain,
+        },
+      ],
+      None,
+    );
+  }
+
+  if open_database {
+    return (
+      vec![
+        Menu {
+          cursor_position: vector![0, 0],
+          kind: GameMenuKind::PauseDatabase(Rc::new(database_entries.to_vec())),
+        },
+        Menu {
+          cursor_position,
+          kind: GameMenuKind::PauseMain,
+        },
+      ],
+      None,
+    );
+  }
+
+  if open_photo_mode {
+    return (
+      vec![
+        Menu {
+          cursor_position: vector![0, 0],
+          kind: GameMenuKind::PhotoMode(false),
+        },
+        Menu {
+          cursor_position,
+          kind: GameMenuKind::PauseMain,
+        },
+      ],
+      None,
+    );
+  }
+
+  if suspend {
+    return (vec![], Some(PauseQuit::Suspend));
+  }
+
   if quit_to_menu {
-    return (vec![], Some(QuitDecision::ToMainMenu));
+    return (vec![], Some(PauseQuit::ToMainMenu));
   }
 
   todo!("Unhandled cursor positon {}", cursor_position);
 }
 
+/* Rows of the settings page, top to bottom. The assist page comes back to its row */
+const AIM_ASSIST_CURSOR: Vector2<i32> = vector![0, 0];
+const LOG_LEVEL_CURSOR: Vector2<i32> = vector![0, 1];
+const LOG_TO_FILE_CURSOR: Vector2<i32> = vector![0, 2];
+const SPEEDRUN_TIMER_CURSOR: Vector2<i32> = vector![0, 3];
+const COOP_CURSOR: Vector2<i32> = vector![0, 4];
+const PALETTE_CURSOR: Vector2<i32> = vector![0, 5];
+const HIGH_CONTRAST_CURSOR: Vector2<i32> = vector![0, 6];
+const UI_SCALE_CURSOR: Vector2<i32> = vector![0, 7];
+const LANGUAGE_CURSOR: Vector2<i32> = vector![0, 8];
+const TOGGLE_FIRE_CURSOR: Vector2<i32> = vector![0, 9];
+const STICKY_AIM_CURSOR: Vector2<i32> = vector![0, 10];
+const BOOST_BINDING_CURSOR: Vector2<i32> = vector![0, 11];
+const GAME_SPEED_CURSOR: Vector2<i32> = vector![0, 12];
+const TRAJECTORY_PREVIEW_CURSOR: Vector2<i32> = vector![0, 13];
+const RADAR_CURSOR: Vector2<i32> = vector![0, 14];
+const AIM_LINE_CURSOR: Vector2<i32> = vector![0, 15];
+const GHOST_REPLAY_CURSOR: Vector2<i32> = vector![0, 16];
+const PHYSICS_QUALITY_CURSOR: Vector2<i32> = vector![0, 17];
+const OPEN_ASSIST_CURSOR: Vector2<i32> = vector![0, 18];
+const CHANGE_CONTROLLER_CURSOR: Vector2<i32> = vector![0, 19];
+const SETTINGS_DONE_CURSOR: Vector2<i32> = vector![0, 20];
+
+fn pause_settings(
+  cursor_position: Vector2<i32>,
+  input: &MenuInput,
+  settings: Settings,
+) -> (Vec<GameMenu>, Option<Settings>, bool) {
+  let cursor_position =
+    handle_cursor_movement(cursor_position, 0, 0, SETTINGS_DONE_CURSOR.y, input, None);
+
+  let aim_assist_row = cursor_position == AIM_ASSIST_CURSOR;
+  let log_level_row = cursor_position == LOG_LEVEL_CURSOR;
+  let log_to_file_row = cursor_position == LOG_TO_FILE_CURSOR;
+  let speedrun_timer_row = cursor_position == SPEEDRUN_TIMER_CURSOR;
+  let coop_row = cursor_position == COOP_CURSOR;
+  let palette_row = cursor_position == PALETTE_CURSOR;
+  let high_contrast_row = cursor_position == HIGH_CONTRAST_CURSOR;
+  let ui_scale_row = cursor_position == UI_SCALE_CURSOR;
+  let language_row = cursor_position == LANGUAGE_CURSOR;
+  let toggle_fire_row = cursor_position == TOGGLE_FIRE_CURSOR;
+  let sticky_aim_row = cursor_position == STICKY_AIM_CURSOR;
+  let boost_binding_row = cursor_position == BOOST_BINDING_CURSOR;
+  let game_speed_row = cursor_position == GAME_SPEED_CURSOR;
+  let trajectory_preview_row = cursor_position == TRAJECTORY_PREVIEW_CURSOR;
+  let radar_row = cursor_position == RADAR_CURSOR;
+  let aim_line_row = cursor_position == AIM_LINE_CURSOR;
+  let ghost_replay_row = cursor_position == GHOST_REPLAY_CURSOR;
+  let physics_quality_row = cursor_position == PHYSICS_QUALITY_CURSOR;
+  let open_assist = cursor_position == OPEN_ASSIST_CURSOR;
+  let change_controller = cursor_position == CHANGE_CONTROLLER_CURSOR;
+  let done = cursor_position == SETTINGS_DONE_CURSOR;
+
+  /* Changes only apply once confirmed with done, cancelling discards them */
+  if done && input.confirm {
+    return (vec![], Some(settings), false);
+  }
+
+  if open_assist && input.confirm {
+    return (
+      vec![GameMenu {
+        cursor_position: vector![0, 0],
+        kind: GameMenuKind::PauseAssist(settings),
+      }],
+      None,
+      false,
+    );
+  }
+
+  let step = if done || open_assist || change_controller {
+    0
+  } else if input.left {
+    -1
+  } else if input.right || input.confirm {
+    1
+  } else {
+    0
+  };
+
+  (
+    vec![GameMenu {
+      cursor_position,
+      kind: GameMenuKind::PauseSettings(Settings {
+        aim_assist: if aim_assist_row {
+          settings.aim_assist.cycle(step)
+        } else {
+          settings.aim_assist
+        },
+        log_level: if log_level_row {
+          settings.log_level.cycle(step)
+        } else {
+          settings.log_level
+        },
+        log_to_file: settings.log_to_file != (log_to_file_row && step != 0),
+        speedrun_timer: settings.speedrun_timer != (speedrun_timer_row && step != 0),
+        coop: settings.coop != (coop_row && step != 0),
+        palette: if palette_row {
+          settings.palette.cycle(step)
+        } else {
+          settings.palette
+        },
+        high_contrast: settings.high_contrast != (high_contrast_row && step != 0),
+        ui_scale: if ui_scale_row {
+          settings.ui_scale.cycle(step)
+        } else {
+          settings.ui_scale
+        },
+        language: if language_row {
+          settings.language.cycle(step)
+        } else {
+          settings.language
+        },
+        toggle_fire: settings.toggle_fire != (toggle_fire_row && step != 0),
+        sticky_aim: settings.sticky_aim != (sticky_aim_row && step != 0),
+        boost_binding: if boost_binding_row {
+          settings.boost_binding.cycle(step)
+        } else {
+          settings.boost_binding
+        },
+        game_speed: if game_speed_row {
+          settings.game_speed.cycle(step)
+        } else {
+          settings.game_speed
+        },
+        trajectory_preview: settings.trajectory_preview != (trajectory_preview_row && step != 0),
+        radar: settings.radar != (radar_row && step != 0),
+        aim_line: settings.aim_line != (aim_line_row && step != 0),
+        ghost_replay: settings.ghost_replay != (ghost_replay_row && step != 0),
+        physics_quality: if physics_quality_row {
+          settings.physics_quality.cycle(step)
+        } else {
+          settings.physics_quality
+        },
+        assist: settings.assist,
+      }),
+    }],
+    None,
+    change_controller && input.confirm,
+  )
+}
+
+fn pause_assist(
+  cursor_position: Vector2<i32>,
+  input: &MenuInput,
+  settings: Settings,
+) -> Vec<GameMenu> {
+  let cursor_position = handle_cursor_movement(cursor_position, 0, 0, 3, input, None);
+
+  let damage_taken_row = cursor_position == vector![0, 0];
+  let infinite_boost_row = cursor_position == vector![0, 1];
+  let no_knockback_row = cursor_position == vector![0, 2];
+  let done = cursor_position == vector![0, 3];
+
+  /* Back to the assist row of the settings page, still unconfirmed */
+  if done && input.confirm {
+    return vec![GameMenu {
+      cursor_position: OPEN_ASSIST_CURSOR,
+      kind: GameMenuKind::PauseSettings(settings),
+    }];
+  }
+
+  let step = if done {
+    0
+  } else if input.left {
+    -1
+  } else if input.right || input.confirm {
+    1
+  } else {
+    0
+  };
+
+  vec![GameMenu {
+    cursor_position,
+    kind: GameMenuKind::PauseAssist(Settings {
+      assist: Assist {
+        damage_taken: if damage_taken_row {
+          settings.assist.damage_taken.cycle(step)
+        } else {
+          settings.assist.damage_taken
+        },
+        infinite_boost: settings.assist.infinite_boost != (infinite_boost_row && step != 0),
+        no_knockback: settings.assist.no_knockback != (no_knockback_row && step != 0),
+      },
+      ..settings
+    }),
+  }]
+}
+
 fn pause_load_game(
   cursor_position: Vector2<i32>,
   input: &MenuInput,
-  available_saves: &Vec<String>,
+  available_saves: &[String],
 ) -> (Vec<GameMenu>, Option<String>) {
   let cursor_position = handle_cursor_movement(
     cursor_position,
@@ -636,8 +1244,52 @@ fn pause_load_game(
   return (vec![], Some(available_saves[save_index_to_load].clone()));
 }
 
+/* Rows are the logs in definition order, only discovered ones can be opened */
+fn pause_database(
+  cursor_position: Vector2<i32>,
+  input: &MenuInput,
+  entries: Rc<Vec<DatabaseEntry>>,
+) -> Vec<GameMenu> {
+  let cursor_position = handle_cursor_movement(
+    cursor_position,
+    0,
+    0,
+    (entries.len() as i32 - 1).max(0),
+    input,
+    None,
+  );
+
+  let database = GameMenu {
+    cursor_position,
+    kind: GameMenuKind::PauseDatabase(Rc::clone(&entries)),
+  };
+
+  match entries.get(cursor_position.y as usize) {
+    Some(entry) if input.confirm && entry.discovered => vec![
+      GameMenu {
+        cursor_position: vector![0, 0],
+        kind: GameMenuKind::PauseDatabaseEntry(entry.clone()),
+      },
+      database,
+    ],
+    _ => vec![database],
+  }
+}
+
+fn pause_database_entry(input: &MenuInput, entry: DatabaseEntry) -> Vec<GameMenu> {
+  if input.confirm {
+    vec![]
+  } else {
+    vec![GameMenu {
+      cursor_position: vector![0, 0],
+      kind: GameMenuKind::PauseDatabaseEntry(entry),
+    }]
+  }
+}
+
 const EDIT_CURSOR: Vector2<i32> = vector![0, 0];
-const CLOSE_CURSOR: Vector2<i32> = vector![1, 0];
+const FUSE_CURSOR: Vector2<i32> = vector![1, 0];
+const CLOSE_CURSOR: Vector2<i32> = vector![2, 0];
 
 fn inventory_main(
   cursor_position: Vector2<i32>,
@@ -645,7 +1297,24 @@ fn inventory_main(
   unequipped_modules: &UnequippedModules,
   equipped_modules: &EquippedModules,
 ) -> Vec<GameMenu> {
-  let cursor_position = handle_cursor_movement(cursor_position, 0, 1, 0, input, None);
+  let cursor_position = handle_cursor_movement(cursor_position, 0, 2, 0, input, None);
+
+  if cursor_position == FUSE_CURSOR && input.confirm {
+    return vec![
+      GameMenu {
+        cursor_position: vector![0, 0],
+        kind: GameMenuKind::InventoryFuse(FuseSelection {
+          unequipped_modules: unequipped_modules.clone(),
+          first_picked: None,
+          preview: None,
+        }),
+      },
+      GameMenu {
+        cursor_position,
+        kind: GameMenuKind::InventoryMain,
+      },
+    ];
+  }
 
   if cursor_position == EDIT_CURSOR && input.confirm {
     return vec![
@@ -654,7 +1323,7 @@ fn inventory_main(
         kind: GameMenuKind::InventoryPickSlot(
           None,
           InventoryUpdateData {
-            equipped_modules: equipped_modules.clone(),
+            equipped_modules: *equipped_modules,
             unequipped_modules: unequipped_modules.clone(),
           },
         ),
@@ -681,7 +1350,7 @@ pub const INVENTORY_WRAP_WIDTH: i32 = 5;
 fn inventory_pick_slot(
   cursor_position: Vector2<i32>,
   input: &MenuInput,
-  currently_holding: Option<WeaponModuleKind>,
+  currently_holding: Option<ModuleInstance>,
   inventory_update: &InventoryUpdateData,
 ) -> (Vec<GameMenu>, Option<InventoryUpdateData>) {
   let unequipped_modules_count: i32 = inventory_update
@@ -748,8 +1417,7 @@ fn inventory_pick_slot(
           cursor_position,
           kind: GameMenuKind::InventoryPickSlot(
             inventory_update.equipped_modules.data.0[cursor_position.y as usize]
-              [cursor_position.x as usize]
-              .clone(),
+              [cursor_position.x as usize],
             InventoryUpdateData {
               equipped_modules: EquippedModules::from_iterator(
                 inventory_update
@@ -759,7 +1427,7 @@ fn inventory_pick_slot(
                   .enumerate()
                   .map(|(index, value)| {
                     if index as i32 == cursor_position.x + (cursor_position.y * EQUIP_SLOTS_WIDTH) {
-                      currently_holding.clone()
+                      currently_holding
                     } else {
                       value
                     }
@@ -785,7 +1453,6 @@ fn inventory_pick_slot(
             .flat_map(|(index, module)| {
               if index == accessing_index {
                 currently_holding
-                  .clone()
                   .map(|currently_holding| vec![currently_holding])
                   .unwrap_or(vec![])
               } else {
@@ -815,7 +1482,7 @@ fn inventory_pick_slot(
               .get(accessing_index)
               .cloned(),
             InventoryUpdateData {
-              equipped_modules: inventory_update.equipped_modules.clone(),
+              equipped_modules: inventory_update.equipped_modules,
               unequipped_modules: updated_unequipped_modules,
             },
           ),
@@ -830,7 +1497,7 @@ fn inventory_pick_slot(
     return (
       vec![],
       Some(InventoryUpdateData {
-        equipped_modules: inventory_update.equipped_modules.clone(),
+        equipped_modules: inventory_update.equipped_modules,
         unequipped_modules: currently_holding
           .map(|currently_holding| {
             inventory_update
@@ -854,6 +1521,94 @@ fn inventory_pick_slot(
   );
 }
 
+/* Two unequipped modules are picked in turn and fused as soon as the second is picked, if a
+recipe takes them. Picking the first again puts it back */
+fn inventory_fuse(
+  cursor_position: Vector2<i32>,
+  input: &MenuInput,
+  selection: &FuseSelection,
+  equipped_modules: &EquippedModules,
+  fusion_recipes: &[FusionRecipe],
+) -> (Vec<GameMenu>, Option<InventoryUpdateData>) {
+  let modules = &selection.unequipped_modules;
+  let last_row = (modules.len() as i32 - 1).max(0) / INVENTORY_WRAP_WIDTH;
+  let cursor_position = handle_cursor_movement(
+    cursor_position,
+    0,
+    INVENTORY_WRAP_WIDTH - 1,
+    last_row,
+    input,
+    None,
+  );
+  let index = (cursor_position.x + cursor_position.y * INVENTORY_WRAP_WIDTH) as usize;
+
+  let fused = |first_picked: Option<usize>| {
+    let first = first_picked.filter(|&first| first != index)?;
+    fusion_output(fusion_recipes, modules[first], *modules.get(index)?)
+  };
+
+  let unchanged = |first_picked: Option<usize>| {
+    (
+      vec![GameMenu {
+        cursor_position,
+        kind: GameMenuKind::InventoryFuse(FuseSelection {
+          unequipped_modules: modules.clone(),
+          first_picked,
+          preview: fused(first_picked),
+        }),
+      }],
+      None,
+    )
+  };
+
+  if !input.confirm || index >= modules.len() {
+    return unchanged(selection.first_picked);
+  }
+
+  let Some(first) = selection.first_picked.filter(|&first| first != index) else {
+    return unchanged(if selection.first_picked.is_some() {
+      None
+    } else {
+      Some(index)
+    });
+  };
+
+  let Some(output) = fused(Some(first)) else {
+    return unchanged(Some(first));
+  };
+
+  log::info(
+    "menu",
+    format!(
+      "fused {:?} and {:?} into {:?}",
+      modules[first], modules[index], output
+    ),
+  );
+
+  let unequipped_modules = modules
+    .iter()
+    .enumerate()
+    .filter(|&(module_index, _)| module_index != first && module_index != index)
+    .map(|(_, &module)| module)
+    .chain([output])
+    .collect::<Vec<_>>();
+
+  (
+    vec![GameMenu {
+      cursor_position: vector![0, 0],
+      kind: GameMenuKind::InventoryFuse(FuseSelection {
+        unequipped_modules: unequipped_modules.clone(),
+        first_picked: None,
+        preview: None,
+      }),
+    }],
+    Some(InventoryUpdateData {
+      equipped_modules: *equipped_modules,
+      unequipped_modules,
+    }),
+  )
+}
+
 fn save_confirm(
   cursor_position: Vector2<i32>,
   input: &MenuInput,
@@ -917,6 +1672,43 @@ fn ability_pickup_confirm(input: &MenuInput, ability_type: MapAbilityType) -> Ve
   }
 }
 
+fn score_results(input: &MenuInput, results: RoomResults) -> Vec<GameMenu> {
+  if input.confirm {
+    vec![]
+  } else {
+    vec![GameMenu {
+      cursor_position: vector![0, 0],
+      kind: GameMenuKind::ScoreResults(results),
+    }]
+  }
+}
+
+fn challenge_results(input: &MenuInput, result: ChallengeResult) -> Vec<GameMenu> {
+  if input.confirm {
+    vec![]
+  } else {
+    vec![GameMenu {
+      cursor_position: vector![0, 0],
+      kind: GameMenuKind::ChallengeResults(result),
+    }]
+  }
+}
+
+/* The run is over once the arena is cleared, so confirming leaves for the main menu */
+fn daily_results(input: &MenuInput, result: DailyResult) -> (Option<QuitDecision>, Vec<GameMenu>) {
+  if input.confirm {
+    (Some(QuitDecision::ToMainMenu), vec![])
+  } else {
+    (
+      None,
+      vec![GameMenu {
+        cursor_position: vector![0, 0],
+        kind: GameMenuKind::DailyResults(result),
+      }],
+    )
+  }
+}
+
 fn game_over(input: &MenuInput) -> (Option<QuitDecision>, Vec<GameMenu>) {
   if input.confirm {
     (Some(QuitDecision::ToMainMenu), vec![])
@@ -954,22 +1746,23 @@ fn menu_input_to_direction(input: &MenuInput) -> HashSet<Direction> {
   .collect()
 }
 
+/* Where the cursor goes instead when moved in a direction from a position */
+type CursorOverrides = HashMap<Vector2<i32>, HashMap<Direction, Vector2<i32>>>;
+
 fn handle_cursor_movement(
   cursor_position: Vector2<i32>,
   min_x_inclusive: i32,
   max_x_inclusive: i32,
   max_y_inclusive: i32,
   input: &MenuInput,
-  overrides: Option<&HashMap<Vector2<i32>, HashMap<Direction, Vector2<i32>>>>,
+  overrides: Option<&CursorOverrides>,
 ) -> Vector2<i32> {
   let override_movement = overrides
-    .map(|overrides| overrides.get(&cursor_position))
-    .flatten()
+    .and_then(|overrides| overrides.get(&cursor_position))
     .map(|direction_map| {
       menu_input_to_direction(input)
         .iter()
-        .map(|input_direction| direction_map.get(input_direction))
-        .flatten()
+        .filter_map(|input_direction| direction_map.get(input_direction))
         .cloned()
         .collect::<Vec<_>>()
     })
@@ -978,17 +1771,20 @@ fn handle_cursor_movement(
     .cloned()
     .collect::<Vec<_>>();
 
-  if override_movement.len() > 0 {
+  if !override_movement.is_empty() {
     return override_movement[0];
   }
 
   /* Debug - warn console if multiple conflicting cursor overrides are found */
   if override_movement.len() > 1 {
-    println!(
-      "Conflicting cursor movement overrides! Found {} for cursor position {} {}",
-      override_movement.len(),
-      cursor_position.x,
-      cursor_position.y
+    log::warn(
+      "menu",
+      format!(
+        "conflicting cursor movement overrides, found {} for cursor position {} {}",
+        override_movement.len(),
+        cursor_position.x,
+        cursor_position.y
+      ),
     );
   }
 
@@ -1020,3 +1816,36 @@ fn handle_cursor_movement(
     },
   ];
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn confirm() -> MenuInput {
+    MenuInput {
+      up: false,
+      down: false,
+      left: false,
+      right: false,
+      confirm: true,
+      cancel: false,
+      inventory: false,
+      pause: false,
+    }
+  }
+
+  /* Rows added above the assist row used to leave done pointing at whatever took its place */
+  #[test]
+  fn leaving_the_assist_page_returns_to_its_row() {
+    let settings = Settings::default();
+
+    let (menus, _, _) = pause_settings(OPEN_ASSIST_CURSOR, &confirm(), settings);
+    assert!(matches!(menus[0].kind, GameMenuKind::PauseAssist(_)));
+
+    let menus = pause_assist(vector![0, 3], &confirm(), settings);
+    assert_eq!(menus[0].cursor_position, OPEN_ASSIST_CURSOR);
+
+    let (menus, _, _) = pause_settings(menus[0].cursor_position, &confirm(), settings);
+    assert!(matches!(menus[0].kind, GameMenuKind::PauseAssist(_)));
+  }
+}
@@ -0,0 +1,152 @@
+use std::sync::RwLock;
+
+use macroquad::color::{BLACK, Color, RED, WHITE};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub enum PaletteKind {
+  #[default]
+  Classic,
+  Deuteranopia,
+  Protanopia,
+  Tritanopia,
+}
+
+impl PaletteKind {
+  pub const ALL: [PaletteKind; 4] = [
+    PaletteKind::Classic,
+    PaletteKind::Deuteranopia,
+    PaletteKind::Protanopia,
+    PaletteKind::Tritanopia,
+  ];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      PaletteKind::Classic => "classic",
+      PaletteKind::Deuteranopia => "deuteranopia",
+      PaletteKind::Protanopia => "protanopia",
+      PaletteKind::Tritanopia => "tritanopia",
+    }
+  }
+
+  pub fn cycle(&self, step: i32) -> Self {
+    let index = PaletteKind::ALL
+      .iter()
+      .position(|kind| kind == self)
+      .unwrap() as i32;
+
+    PaletteKind::ALL[(index + step).rem_euclid(PaletteKind::ALL.len() as i32) as usize]
+  }
+
+  fn palette(&self) -> Palette {
+    match self {
+      PaletteKind::Classic => CLASSIC,
+      PaletteKind::Deuteranopia => DEUTERANOPIA,
+      PaletteKind::Protanopia => PROTANOPIA,
+      PaletteKind::Tritanopia => TRITANOPIA,
+    }
+  }
+}
+
+#[derive(Clone, Copy)]
+pub struct Palette {
+  /* Shades from lightest to darkest, the background through to the player */
+  pub color_1: Color,
+  pub color_2: Color,
+  pub color_3: Color,
+  pub color_4: Color,
+  pub damage: Color,
+  /* Hazards ripple between their base and highlight */
+  pub lava: Color,
+  pub lava_highlight: Color,
+  pub acid: Color,
+  pub acid_highlight: Color,
+  pub fluid: Color,
+  pub map_marker: Color,
+}
+
+const CLASSIC: Palette = Palette {
+  color_1: Color::from_rgba(214, 246, 214, 255),
+  color_2: Color::from_rgba(107, 165, 107, 255),
+  color_3: Color::from_rgba(29, 88, 73, 255),
+  color_4: Color::from_rgba(0, 18, 25, 255),
+  damage: Color::new(0.9, 0.1, 0.1, 1.0),
+  lava: Color::new(0.8, 0.2, 0.05, 1.0),
+  lava_highlight: Color::new(1.0, 0.5, 0.05, 1.0),
+  acid: Color::new(0.4, 0.75, 0.1, 1.0),
+  acid_highlight: Color::new(0.6, 1.0, 0.1, 1.0),
+  fluid: Color::new(0.2, 0.45, 0.9, 1.0),
+  map_marker: RED,
+};
+
+/* Red and green read alike, so shades run through blue and hazards are told apart by
+brightness as much as by hue */
+const DEUTERANOPIA: Palette = Palette {
+  color_1: Color::from_rgba(222, 235, 247, 255),
+  color_2: Color::from_rgba(107, 160, 204, 255),
+  color_3: Color::from_rgba(33, 90, 150, 255),
+  color_4: Color::from_rgba(8, 30, 60, 255),
+  damage: Color::from_rgba(230, 159, 0, 255),
+  lava: Color::from_rgba(213, 94, 0, 255),
+  lava_highlight: Color::from_rgba(240, 140, 60, 255),
+  acid: Color::from_rgba(240, 228, 66, 255),
+  acid_highlight: Color::from_rgba(255, 245, 157, 255),
+  fluid: Color::from_rgba(204, 121, 167, 255),
+  map_marker: Color::from_rgba(240, 228, 66, 255),
+};
+
+/* As deuteranopia, with reds darkened further so damage leans on orange */
+const PROTANOPIA: Palette = Palette {
+  damage: Color::from_rgba(255, 176, 0, 255),
+  lava: Color::from_rgba(230, 120, 0, 255),
+  lava_highlight: Color::from_rgba(255, 170, 60, 255),
+  ..DEUTERANOPIA
+};
+
+/* Blue and green read alike, so acid and fluid move away from both */
+const TRITANOPIA: Palette = Palette {
+  lava: Color::from_rgba(213, 94, 0, 255),
+  lava_highlight: Color::from_rgba(240, 140, 60, 255),
+  acid: Color::from_rgba(204, 121, 167, 255),
+  acid_highlight: Color::from_rgba(235, 170, 205, 255),
+  fluid: Color::from_rgba(120, 120, 120, 255),
+  ..CLASSIC
+};
+
+pub fn mix(from: Color, to: Color, amount: f32) -> Color {
+  Color::new(
+    from.r + (to.r - from.r) * amount,
+    from.g + (to.g - from.g) * amount,
+    from.b + (to.b - from.b) * amount,
+    from.a + (to.a - from.a) * amount,
+  )
+}
+
+impl Palette {
+  /* Pushes the shades apart, leaving the hazard and marker colors alone */
+  fn high_contrast(self) -> Self {
+    Self {
+      color_1: WHITE,
+      color_3: mix(self.color_3, BLACK, 0.5),
+      color_4: BLACK,
+      ..self
+    }
+  }
+}
+
+/* Read while drawing from anywhere, so like logging it lives in a static rather than a system */
+static PALETTE: RwLock<Palette> = RwLock::new(CLASSIC);
+
+pub fn configure(kind: PaletteKind, high_contrast: bool) {
+  if let Ok(mut palette) = PALETTE.write() {
+    *palette = if high_contrast {
+      kind.palette().high_contrast()
+    } else {
+      kind.palette()
+    };
+  }
+}
+
+pub fn palette() -> Palette {
+  PALETTE.read().map(|palette| *palette).unwrap_or(CLASSIC)
+}
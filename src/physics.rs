@@ -1,44 +1,73 @@
-use itertools::Itertools;
 use macroquad::prelude::rand;
 use rapier2d::{
   na::{Isometry2, OPoint},
+  parry::query::{ShapeCastHit, ShapeCastOptions, intersection_test},
   prelude::*,
 };
 use rpds::{HashTrieMap, List, list};
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+  cell::RefCell,
+  cmp::Reverse,
+  collections::{HashMap, HashSet, VecDeque},
+  f32::consts::PI,
+  iter,
+  rc::Rc,
+  sync::Arc,
+  time::Instant,
+};
 
 use crate::{
   ability::AbilitySystem,
-  combat::{CombatSystem, WeaponModuleKind},
-  controls::{ControlsSystem, angle_from_vec},
+  activation::{
+    Entities, logic_passes, touch_sensor_pass, update_components, update_components_with,
+  },
+  collision::{CollisionLayer, groups},
+  combat::{CombatSystem, DroneSpec, Projectile, WeaponModuleKind},
+  contact_rules::{ContactRules, pass_through_allies},
+  controls::{ControlsSystem, CoopControlsSystem, angle_from_vec},
+  data::{DataSystem, EnemyDefinitions, EntityLimits, MovementStats},
+  database::DatabaseSystem,
+  dialogue::DialogueSystem,
   ecs::{
-    Activator, And, ChainMountArea, ChainSegment, ComponentSet, Damageable, Damager,
-    DestroyAfterFrames, DestroyOnCollision, Destroyed, DropHealthOnDestroy, Engine, Entity,
-    EntityHandle, ExplodeOnCollision, Gate, GiveAbilityOnCollision, GivesItemOnCollision,
-    GravitySource, HealOnCollision, Id, Locomotor, MapTransitionOnCollision, Or,
-    SaveMenuOnCollision, SimpleActivatable, Switch, TouchSensor,
+    Activator, And, Bounces, CapCategory, Capped, ChainMountArea, ChainSegment, Component,
+    ComponentSet, CriticalHit, Damageable, Damager, DeathAnimation, DespawnTimer,
+    DestroyAfterFrames, DestroyOnCollision, Destroyed, DialogueTrigger, Drone, Dying, Elite,
+    Encounter, EncounterState, Enemy, Engine, Entity, EntityHandle, EntityKind, ExplodeOnCollision,
+    ExplodeOnDeath, Faction, FluidVolume, Gate, GiveAbilityOnCollision, GivesCreditsOnCollision,
+    GivesEngineUpgradeOnCollision, GivesItemOnCollision, GivesLogOnCollision,
+    GivesPowerUpOnCollision, GravitySource, GravityZone, Hacked, Hazard, HealOnCollision, Id,
+    Impact, ImpactKind, Locomotor, LootDrop, LootTable, MagnetLatch, Magnetic,
+    MapTransitionOnCollision, MountedTurret, MovableBlock, Name, Or, Persistent, PowerUpKind,
+    ProximityTrigger, Reflective, SaveMenuOnCollision, SelfBlast, SimpleActivatable,
+    SpawnProtection, StatusEffects, Switch, Telegraph, TensionSwitch, TouchSensor, TutorialTrigger,
   },
-  enemy::EnemySystem,
+  enemy::{EnemyDecision, EnemySystem},
   load_map::{
-    COLLISION_GROUP_CHAIN, COLLISION_GROUP_ENEMY, COLLISION_GROUP_ENEMY_PROJECTILE,
-    COLLISION_GROUP_PLAYER, COLLISION_GROUP_PLAYER_INTERACTIBLE, COLLISION_GROUP_WALL, Map,
-    MapAbilityType, MapSystem, MapTile,
+    ChallengeRoom, Chunks, CyclePhase, ExplosiveBarrel, Map, MapAbilityType, MapCycle, MapSystem,
+    MapTile, WALL_QUERY_GROUPS,
   },
+  log,
   menu::MenuSystem,
-  save::SaveData,
-  system::System,
+  save::{Difficulty, MapState, SaveData, SuspendedEntity, SuspendedRoom},
+  settings::{Assist, SettingsSystem},
+  spatial::SpatialHash,
+  system::{Requirement, System},
   units::{PhysicsVector, UnitConvert2},
 };
 
-const PLAYER_SPEED_LIMIT: f32 = 2.5;
-const PLAYER_ACCELERATION_MOD: f32 = 0.25;
+/* How far from a magnetic surface the player can latch on */
+const MAGNET_RANGE: f32 = 0.6;
+/* The latch is remade once the nearest surface turns further than this, e.g. around corners */
+const MAGNET_RELATCH_ALIGNMENT: f32 = 0.99;
 
 const CHAIN_SEGMENT_LENGTH: f32 = 0.5;
 const CHAIN_SEGMENT_HEIGHT: f32 = 0.05;
+/* Damage worth of enemy fire the chain deflects before snapping */
+const CHAIN_DURABILITY: f32 = 30.0;
+/* How far apart a joint's anchors can be pulled before the chain snaps */
+const CHAIN_BREAK_STRETCH: f32 = 0.4;
 pub const CHAIN_ANGULAR_DAMPING: f32 = 1.0;
 
-pub const ENGINE_MAX_SPEED: f32 = 0.005;
-
 pub struct PhysicsSystem {
   pub rigid_body_set: RigidBodySet,
   pub collider_set: ColliderSet,
@@ -51,31 +80,804 @@ pub struct PhysicsSystem {
   pub multibody_joint_set: MultibodyJointSet,
   pub ccd_solver: CCDSolver,
   pub player_handle: RigidBodyHandle,
-  pub entities: HashTrieMap<EntityHandle, Rc<Entity>>,
-  pub new_weapon_modules: List<(i32, WeaponModuleKind)>,
+  /* Only set while the co-op partner is alive in this room */
+  pub player_two_handle: Option<RigidBodyHandle>,
+  /* Stays set after the partner dies so they sit out the rest of the room */
+  pub player_two_joined: bool,
+  pub entities: Entities,
+  /* Ids are only set for pickups placed in the map, not dropped loot */
+  pub new_weapon_modules: List<(Option<i32>, WeaponModuleKind)>,
   pub new_abilities: List<MapAbilityType>,
+  pub new_logs: List<String>,
+  pub new_engine_upgrades: List<String>,
+  pub new_credits: i32,
+  pub new_power_ups: List<PowerUpKind>,
+  /* Enemies killed this frame, for scoring */
+  pub new_kills: i32,
+  /* Set on the frame an encounter in the room is won */
+  pub encounter_cleared: bool,
   pub frame_count: i64,
+  /* Frames counted at game speed, which cycles and orbits follow */
+  pub simulated_frames: f64,
   pub load_new_map: Option<(String, i32)>,
   pub save_point_contact: Option<i32>,
   pub save_point_contact_last_frame: Option<i32>,
   pub mount_points_in_range: List<RigidBodyHandle>,
+  /* The turret the player is operating, locked in place on top of it */
+  pub mounted_turret: Option<EntityHandle>,
+  /* Whether the player is standing on a turret they could mount */
+  pub turret_in_range: bool,
+  /* Furthest any chain joint has been pulled apart this frame */
+  pub chain_tension: f32,
+  /* Enemy fire the chain can still deflect before it breaks */
+  pub chain_durability: f32,
+  /* Set on the frame the chain snaps on its own rather than being released */
+  pub chain_broke: bool,
+  pub map_name: String,
+  /* Every room's persistent state, the current room's only as of when it was entered
+  apart from what has been destroyed since */
+  pub map_states: Rc<HashTrieMap<String, MapState>>,
+  pub cycle: Option<Rc<MapCycle>>,
+  pub friendly_fire: bool,
+  pub scored: bool,
+  pub challenge: Option<Rc<ChallengeRoom>>,
+  /* Every entity bucketed by translation as of the end of the last frame */
+  pub entity_hash: Rc<SpatialHash<EntityHandle>>,
+  /* Neighbouring streamed rooms currently simulated alongside this one, by map name */
+  pub streamed_chunks: Rc<HashTrieMap<String, StreamedChunk>>,
+  /* Only puzzle rooms can be rewound, and only they keep snapshots */
+  pub puzzle: bool,
+  /* Oldest first, at most REWIND_SNAPSHOTS of them */
+  pub rewind_snapshots: Rc<VecDeque<Rc<RewindSnapshot>>>,
+  /* Milliseconds each stage took last frame, empty when the stages didn't run */
+  pub stage_timings: Rc<Vec<(&'static str, f32)>>,
+  /* Set while the room fades out on the way through a map transition */
+  pub room_exit: Option<RoomExit>,
+  /* How the player came into this room, if through a map transition */
+  pub room_entry: Option<RoomEntry>,
+}
+
+/* Rooms fade out over this long before a map transition loads the next one, and fade back in
+over as long after */
+pub const ROOM_FADE_FRAMES: i32 = 15;
+
+/* A map transition the player has walked into, loaded once frames_left runs out */
+#[derive(Clone, Debug)]
+pub struct RoomExit {
+  pub map_name: String,
+  pub target_player_spawn_id: i32,
+  pub velocity: Vector<f32>,
+  pub frames_left: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct RoomEntry {
+  pub from_map_name: String,
+  pub velocity: Vector<f32>,
+}
+
+/* How many of the slowest stages a slow frame names */
+const SLOW_STAGES_LOGGED: usize = 3;
+
+/* A snapshot is kept this often in puzzle rooms, half a second apart */
+const REWIND_INTERVAL_FRAMES: i64 = 30;
+/* Rewinding goes back to the oldest, about three seconds */
+const REWIND_SNAPSHOTS: usize = 6;
+
+/* The world as it was at the start of a frame. Entities and room states are persistent so
+keeping them is cheap, the physics sets are copied outright */
+pub struct RewindSnapshot {
+  rigid_body_set: RigidBodySet,
+  collider_set: ColliderSet,
+  island_manager: IslandManager,
+  broad_phase: DefaultBroadPhase,
+  narrow_phase: NarrowPhase,
+  impulse_joint_set: ImpulseJointSet,
+  multibody_joint_set: MultibodyJointSet,
+  ccd_solver: CCDSolver,
+  entities: Entities,
+  player_two_handle: Option<RigidBodyHandle>,
+  map_states: Rc<HashTrieMap<String, MapState>>,
+  streamed_chunks: Rc<HashTrieMap<String, StreamedChunk>>,
+}
+
+impl RewindSnapshot {
+  fn of(physics_system: &PhysicsSystem) -> Self {
+    Self {
+      rigid_body_set: physics_system.rigid_body_set.clone(),
+      collider_set: physics_system.collider_set.clone(),
+      island_manager: physics_system.island_manager.clone(),
+      broad_phase: physics_system.broad_phase.clone(),
+      narrow_phase: physics_system.narrow_phase.clone(),
+      impulse_joint_set: physics_system.impulse_joint_set.clone(),
+      multibody_joint_set: physics_system.multibody_joint_set.clone(),
+      ccd_solver: physics_system.ccd_solver.clone(),
+      entities: physics_system.entities.clone(),
+      player_two_handle: physics_system.player_two_handle,
+      map_states: Rc::clone(&physics_system.map_states),
+      streamed_chunks: Rc::clone(&physics_system.streamed_chunks),
+    }
+  }
+}
+
+/* What a streamed room added to the world, so it can be taken out again once out of range */
+#[derive(Clone)]
+pub struct StreamedChunk {
+  pub walls: ColliderHandle,
+  pub entities: Vec<EntityHandle>,
+}
+
+/* Roughly the range gameplay queries look over, so most only visit a few cells */
+const ENTITY_HASH_CELL_SIZE: f32 = 4.0;
+
+fn build_entity_hash(
+  entities: &Entities,
+  rigid_body_set: &RigidBodySet,
+  collider_set: &ColliderSet,
+) -> Rc<SpatialHash<EntityHandle>> {
+  Rc::new(SpatialHash::new(
+    ENTITY_HASH_CELL_SIZE,
+    entities
+      .keys()
+      .map(|handle| (*handle.translation(rigid_body_set, collider_set), *handle)),
+  ))
+}
+
+/* Apart from PhysicsSystem so it can be shared between threads, which the system can't be */
+pub fn nearest_entity<'a>(
+  entities: &'a Entities,
+  entity_hash: &SpatialHash<EntityHandle>,
+  center: Vector<f32>,
+  radius: f32,
+  predicate: impl Fn(&Entity) -> bool,
+) -> Option<&'a Arc<Entity>> {
+  entity_hash
+    .nearest(center, radius, |handle| {
+      entities.get(handle).is_some_and(|entity| predicate(entity))
+    })
+    .and_then(|(_, handle)| entities.get(handle))
+}
+
+impl PhysicsSystem {
+  pub fn entities_within(
+    &self,
+    center: Vector<f32>,
+    radius: f32,
+  ) -> impl Iterator<Item = (Vector<f32>, &Arc<Entity>)> {
+    self
+      .entity_hash
+      .within(center, radius)
+      .filter_map(|(translation, handle)| Some((*translation, self.entities.get(handle)?)))
+  }
+
+  /* MARK: Scene queries against the broad phase as of the last step */
+
+  fn query_pipeline<'a>(&'a self, filter: QueryFilter<'a>) -> QueryPipeline<'a> {
+    self.broad_phase.as_query_pipeline(
+      self.narrow_phase.query_dispatcher(),
+      &self.rigid_body_set,
+      &self.collider_set,
+      filter,
+    )
+  }
+
+  pub fn raycast(
+    &self,
+    origin: Vector<f32>,
+    direction: Vector<f32>,
+    max_distance: f32,
+    filter: QueryFilter,
+  ) -> Option<(ColliderHandle, RayIntersection)> {
+    /* A zero direction doesn't point anywhere to hit anything */
+    let direction = direction.try_normalize(0.0)?;
+
+    self.query_pipeline(filter).cast_ray_and_get_normal(
+      &Ray::new(origin.into(), direction),
+      max_distance,
+      true,
+    )
+  }
+
+  /* Sweeps the shape from translation over the whole of velocity */
+  pub fn shapecast(
+    &self,
+    shape: &dyn Shape,
+    translation: Vector<f32>,
+    velocity: Vector<f32>,
+    filter: QueryFilter,
+  ) -> Option<(ColliderHandle, ShapeCastHit)> {
+    self.query_pipeline(filter).cast_shape(
+      &Isometry::translation(translation.x, translation.y),
+      &velocity,
+      shape,
+      ShapeCastOptions::with_max_time_of_impact(1.0),
+    )
+  }
+
+  /* Everything overlapping the collider, apart from itself */
+  pub fn overlapping(
+    &self,
+    collider_handle: ColliderHandle,
+    filter: QueryFilter,
+  ) -> Vec<ColliderHandle> {
+    let collider = &self.collider_set[collider_handle];
+    let query_pipeline = self.query_pipeline(filter.exclude_collider(collider_handle));
+
+    query_pipeline
+      .intersect_shape(*collider.position(), collider.shape())
+      .map(|(handle, _)| handle)
+      .collect()
+  }
+
+  /* The first trigger of this kind the player is inside of that `unseen` lets through, with its
+  collider. Map triggers that only ever fire once look themselves up through this */
+  pub fn entered_trigger<Trigger: Component>(
+    &self,
+    unseen: impl Fn(&Trigger) -> bool,
+  ) -> Option<(Arc<Trigger>, ColliderHandle)> {
+    let player_colliders = self.rigid_body_set[self.player_handle].colliders();
+
+    self.entities.iter().find_map(|(handle, entity)| {
+      let trigger = entity
+        .components
+        .get::<Trigger>()
+        .filter(|trigger| unseen(trigger))?;
+
+      let EntityHandle::Collider(trigger_collider) = handle else {
+        return None;
+      };
+
+      self
+        .overlapping(*trigger_collider, QueryFilter::new())
+        .iter()
+        .any(|collider| player_colliders.contains(collider))
+        .then_some((trigger, *trigger_collider))
+    })
+  }
+
+  pub fn nearest_entity(
+    &self,
+    center: Vector<f32>,
+    radius: f32,
+    predicate: impl Fn(&Entity) -> bool,
+  ) -> Option<&Arc<Entity>> {
+    nearest_entity(&self.entities, &self.entity_hash, center, radius, predicate)
+  }
+
+  /* Where a projectile would be each frame if fired now, steered only by its own force and the
+  room's gravity, and cut short at the first wall. Cheap enough to run every frame, though it
+  drifts from the real flight as it ignores everything else it could hit */
+  pub fn predict_path(
+    &self,
+    projectile: &Projectile,
+    translation: Vector<f32>,
+    linvel: Vector<f32>,
+    frames: usize,
+  ) -> Vec<Vector<f32>> {
+    let mass = projectile.collider.mass();
+    let dt = self.integration_parameters.dt;
+    let cycle_multiplier = self
+      .cycle_phase()
+      .map_or(1.0, |phase| phase.gravity_multiplier);
+
+    let thrust = projectile
+      .initial_impulse
+      .into_vec()
+      .try_normalize(f32::EPSILON)
+      .map_or(Vector::zeros(), |direction| {
+        direction * projectile.force_mod / mass
+      });
+
+    let gravity_sources = self
+      .entities
+      .iter()
+      .filter_map(|(handle, entity)| {
+        let gravity_source = entity.components.get::<GravitySource>()?;
+        let EntityHandle::Collider(collider_handle) = handle else {
+          return None;
+        };
+        let strength = gravity_activation(&self.entities, gravity_source.activator_id)
+          * gravity_source.strength
+          * cycle_multiplier;
+        Some((&self.collider_set[*collider_handle], strength))
+      })
+      .collect::<Vec<_>>();
+
+    let gravity_zones = self
+      .entities
+      .iter()
+      .filter_map(|(handle, entity)| {
+        let gravity_zone = entity.components.get::<GravityZone>()?;
+        let EntityHandle::Collider(collider_handle) = handle else {
+          return None;
+        };
+        let acceleration = gravity_zone.acceleration
+          * gravity_activation(&self.entities, gravity_zone.activator_id)
+          * cycle_multiplier;
+        Some((&self.collider_set[*collider_handle], acceleration))
+      })
+      .collect::<Vec<_>>();
+
+    let contains = |collider: &Collider, point: Vector<f32>| {
+      collider
+        .shape()
+        .contains_point(collider.position(), &point.into())
+    };
+
+    (0..frames)
+      .scan(
+        (
+          translation,
+          linvel + projectile.initial_impulse.into_vec() / mass,
+        ),
+        |(position, velocity), _| {
+          /* The same per frame impulses apply_gravity_sources gives, as velocity */
+          let pull = gravity_sources
+            .iter()
+            .filter(|(collider, _)| contains(collider, *position))
+            .map(|(collider, strength)| {
+              let distance_vec = collider.translation() - *position;
+              distance_vec * *strength / distance_vec.magnitude_squared() / mass
+            })
+            .chain(
+              gravity_zones
+                .iter()
+                .filter(|(collider, _)| contains(collider, *position))
+                .map(|(_, acceleration)| *acceleration),
+            )
+            .fold(Vector::zeros(), |total, pull| total + pull);
+
+          *velocity += thrust * dt + pull;
+
+          let step = *velocity * dt;
+          let blocked = step.magnitude() > f32::EPSILON
+            && self
+              .raycast(
+                *position,
+                step,
+                step.magnitude(),
+                QueryFilter::new().groups(WALL_QUERY_GROUPS),
+              )
+              .is_some();
+
+          if blocked {
+            return None;
+          }
+
+          *position += step;
+          Some(*position)
+        },
+      )
+      .collect()
+  }
+
+  /* Rooms without a cycle have no phase, which behaves like the defaults */
+  pub fn cycle_phase(&self) -> Option<&CyclePhase> {
+    self
+      .cycle
+      .as_ref()
+      .and_then(|cycle| cycle.phase_at(self.simulated_frames as i64))
+  }
+
+  /* The persistent state store with the current room's live gates and blocks folded in */
+  pub fn map_states(&self) -> HashTrieMap<String, MapState> {
+    let recorded = self
+      .map_states
+      .get(&self.map_name)
+      .cloned()
+      .unwrap_or_default();
+
+    let gate_activations = self
+      .entities
+      .values()
+      .filter_map(|entity| {
+        let gate = entity.components.get::<Gate>()?;
+        let id = entity.components.get::<Id>()?;
+        Some((id.id, gate.highest_historical_activation))
+      })
+      .collect();
+
+    let block_translations = self
+      .entities
+      .iter()
+      .filter_map(|(handle, entity)| {
+        entity.components.get::<MovableBlock>()?;
+        let id = entity.components.get::<Id>()?;
+        let translation = handle.translation(&self.rigid_body_set, &self.collider_set);
+        Some((id.id, (translation.x, translation.y)))
+      })
+      .collect();
+
+    self.map_states.insert(
+      self.map_name.clone(),
+      MapState {
+        gate_activations,
+        block_translations,
+        ..recorded
+      },
+    )
+  }
+
+  /* The system as the stages left the world, a frame on from this one */
+  pub fn with_frame(
+    &self,
+    frame: FrameState,
+    time_scale: f32,
+    rewind_snapshots: Rc<VecDeque<Rc<RewindSnapshot>>>,
+  ) -> Self {
+    let entity_hash =
+      build_entity_hash(&frame.entities, &frame.rigid_body_set, &frame.collider_set);
+
+    Self {
+      rigid_body_set: frame.rigid_body_set,
+      collider_set: frame.collider_set,
+      integration_parameters: self.integration_parameters,
+      physics_pipeline: Rc::clone(&self.physics_pipeline),
+      island_manager: frame.island_manager,
+      broad_phase: frame.broad_phase,
+      narrow_phase: frame.narrow_phase,
+      impulse_joint_set: frame.impulse_joint_set,
+      multibody_joint_set: frame.multibody_joint_set,
+      ccd_solver: frame.ccd_solver,
+      player_handle: self.player_handle,
+      player_two_handle: frame.player_two_handle,
+      player_two_joined: frame.player_two_joined,
+      entities: frame.entities,
+      new_weapon_modules: frame.new_weapon_modules,
+      new_abilities: frame.new_abilities,
+      new_logs: frame.new_logs,
+      new_engine_upgrades: frame.new_engine_upgrades,
+      new_credits: frame.new_credits,
+      new_power_ups: frame.new_power_ups,
+      new_kills: frame.new_kills,
+      encounter_cleared: frame.encounter_cleared,
+      frame_count: self.frame_count + 1,
+      simulated_frames: self.simulated_frames + time_scale as f64,
+      load_new_map: frame.load_new_map,
+      save_point_contact: frame.save_point_contact,
+      save_point_contact_last_frame: self.save_point_contact,
+      mount_points_in_range: frame.mount_points_in_range,
+      mounted_turret: frame.mounted_turret,
+      turret_in_range: frame.turret_in_range,
+      chain_tension: frame.chain_tension,
+      chain_durability: frame.chain_durability,
+      chain_broke: frame.chain_broke,
+      map_name: self.map_name.clone(),
+      map_states: frame.map_states,
+      cycle: self.cycle.clone(),
+      friendly_fire: self.friendly_fire,
+      scored: self.scored,
+      challenge: self.challenge.clone(),
+      entity_hash,
+      streamed_chunks: frame.streamed_chunks,
+      puzzle: self.puzzle,
+      rewind_snapshots,
+      stage_timings: Rc::new(vec![]),
+      room_exit: None,
+      room_entry: self.room_entry.clone(),
+    }
+  }
+
+  /* Everything placed in this room that's still around, as it is right now. Whatever was
+  spawned since, shots and drops and the like, isn't kept */
+  pub fn suspended_room(&self) -> SuspendedRoom {
+    let streamed_entities = self
+      .streamed_chunks
+      .values()
+      .flat_map(|chunk| chunk.entities.iter())
+      .collect::<HashSet<_>>();
+
+    let player_body = &self.rigid_body_set[self.player_handle];
+
+    SuspendedRoom {
+      player_translation: (player_body.translation().x, player_body.translation().y),
+      player_velocity: (player_body.linvel().x, player_body.linvel().y),
+      entities: self
+        .entities
+        .iter()
+        .filter(|(handle, entity)| {
+          !streamed_entities.contains(handle) && entity.components.get::<Destroyed>().is_none()
+        })
+        .filter_map(|(handle, entity)| {
+          let (kind, id) = suspend_key(entity)?;
+          let moving_body = match handle {
+            EntityHandle::RigidBody(rigid_body_handle) => {
+              Some(&self.rigid_body_set[*rigid_body_handle]).filter(|body| body.is_dynamic())
+            }
+            EntityHandle::Collider(_) => None,
+          };
+
+          Some(SuspendedEntity {
+            kind,
+            id,
+            translation: moving_body.map(|body| (body.translation().x, body.translation().y)),
+            velocity: moving_body.map(|body| (body.linvel().x, body.linvel().y)),
+            health: entity
+              .components
+              .get::<Damageable>()
+              .map(|damageable| damageable.health),
+            activation: entity
+              .components
+              .get::<Activator>()
+              .map(|activator| activator.activation),
+          })
+        })
+        .collect(),
+    }
+  }
+}
+
+/* How an entity placed in the map is found again once the map is reloaded */
+fn suspend_key(entity: &Entity) -> Option<(EntityKind, i32)> {
+  let kind = *entity.components.get::<EntityKind>()?;
+  let id = match entity.components.get::<Id>() {
+    Some(id) => id.id,
+    None => entity.components.get::<Persistent>()?.id,
+  };
+  Some((kind, id))
+}
+
+/* The parts of a physics world that bodies are taken out of */
+struct PhysicsSets<'a> {
+  rigid_body_set: &'a mut RigidBodySet,
+  collider_set: &'a mut ColliderSet,
+  island_manager: &'a mut IslandManager,
+  impulse_joint_set: &'a mut ImpulseJointSet,
+  multibody_joint_set: &'a mut MultibodyJointSet,
+}
+
+/* Puts a freshly loaded room back the way it was suspended. The player is already where they
+were left, and anything placed in the map that isn't in the suspended room had been destroyed */
+fn restore_suspended_room(
+  suspended_room: &SuspendedRoom,
+  entities: Entities,
+  player_handle: RigidBodyHandle,
+  sets: PhysicsSets,
+) -> Entities {
+  let PhysicsSets {
+    rigid_body_set,
+    collider_set,
+    island_manager,
+    impulse_joint_set,
+    multibody_joint_set,
+  } = sets;
+  let (x, y) = suspended_room.player_velocity;
+  rigid_body_set[player_handle].set_linvel(vector![x, y], true);
+
+  let suspended_entities = suspended_room
+    .entities
+    .iter()
+    .map(|suspended| ((suspended.kind, suspended.id), suspended))
+    .collect::<HashMap<_, _>>();
+
+  entities
+    .iter()
+    .filter_map(|(&handle, entity)| {
+      let Some(key) = suspend_key(entity) else {
+        return Some((handle, Arc::clone(entity)));
+      };
+
+      let Some(suspended) = suspended_entities.get(&key) else {
+        match handle {
+          EntityHandle::RigidBody(rigid_body_handle) => {
+            rigid_body_set.remove(
+              rigid_body_handle,
+              island_manager,
+              collider_set,
+              impulse_joint_set,
+              multibody_joint_set,
+              true,
+            );
+          }
+          EntityHandle::Collider(collider_handle) => {
+            collider_set.remove(collider_handle, island_manager, rigid_body_set, true);
+          }
+        }
+        return None;
+      };
+
+      if let EntityHandle::RigidBody(rigid_body_handle) = handle {
+        let body = &mut rigid_body_set[rigid_body_handle];
+        if let Some((x, y)) = suspended.translation {
+          body.set_translation(vector![x, y], true);
+        }
+        if let Some((x, y)) = suspended.velocity {
+          body.set_linvel(vector![x, y], true);
+        }
+      }
+
+      let components = match (entity.components.get::<Damageable>(), suspended.health) {
+        (Some(damageable), Some(health)) => entity.components.with(Damageable {
+          health,
+          ..*damageable
+        }),
+        _ => entity.components.clone(),
+      };
+      let components = match suspended.activation {
+        Some(activation) if components.get::<Activator>().is_some() => {
+          components.with(Activator { activation })
+        }
+        _ => components,
+      };
+
+      Some((handle, Arc::new(Entity { handle, components })))
+    })
+    .collect()
 }
 
 const PLAYER_MAX_HITSTUN: f32 = 100.0;
 
-fn load_new_map(
+/* Where the co-op partner appears relative to the first player */
+const PLAYER_TWO_OFFSET: Vector<f32> = vector![0.6, 0.0];
+
+/* How far from a door a player coming through it stands */
+const ENTRY_CLEARANCE: f32 = 0.5;
+
+/* Players can't be hurt for this long after coming into a room */
+const SPAWN_PROTECTION_FRAMES: i32 = 90;
+
+/* A spawn point inside of something is moved out along rings of this spacing, up to the
+furthest distance, before giving up and leaving the player where they were */
+const SPAWN_NUDGE_STEP: f32 = 0.25;
+const SPAWN_NUDGE_MAX_DISTANCE: f32 = 4.0;
+const SPAWN_NUDGE_DIRECTIONS: usize = 16;
+
+/* Dropped loot starts blinking this long before it despawns */
+const LOOT_DESPAWN_WARNING_FRAMES: i32 = 120;
+
+/* Room rng is seeded by the room, so its elites roll the same way on every visit */
+fn room_seed(map_name: &str) -> u64 {
+  map_name.bytes().fold(0u64, |seed, byte| {
+    seed.wrapping_mul(31).wrapping_add(byte as u64)
+  })
+}
+
+/* Everything about a room that decides which enemies spawn in it, and where */
+struct EnemySpawns<'a> {
+  map: &'a Map,
+  map_name: &'a str,
+  map_state: &'a MapState,
+  offset: Vector<f32>,
+  enemy_definitions: &'a EnemyDefinitions,
+  difficulty: Difficulty,
+}
+
+fn spawn_enemies(
+  spawns: &EnemySpawns,
+  rigid_body_set: &mut RigidBodySet,
+  collider_set: &mut ColliderSet,
+) -> Vec<Entity> {
+  let EnemySpawns {
+    map,
+    map_name,
+    map_state,
+    offset,
+    enemy_definitions,
+    difficulty,
+  } = *spawns;
+  let rng = rand::RandGenerator::new();
+  rng.srand(room_seed(map_name));
+  map
+    .enemy_spawns
+    .iter()
+    .filter(|enemy_spawn| {
+      enemy_spawn
+        .persistent_id
+        .is_none_or(|id| !map_state.killed_enemies.contains(&id))
+    })
+    .map(|enemy_spawn| {
+      let mut rigid_body = enemy_spawn.rigid_body.clone();
+      rigid_body.set_translation(rigid_body.translation() + offset, false);
+      let handle = rigid_body_set.insert(rigid_body);
+      collider_set.insert_with_parent(enemy_spawn.collider.clone(), handle, rigid_body_set);
+      Entity {
+        handle: EntityHandle::RigidBody(handle),
+        components: enemy_spawn
+          .to_entity_components(enemy_definitions, difficulty, rng.gen_range(0.0, 1.0))
+          .insert(EntityKind::Enemy),
+      }
+    })
+    .collect()
+}
+
+fn spawn_map_transitions(
   map: &Map,
-  map_name: &str,
-  acquired_modules: &[(String, i32)],
-  target_player_spawn_id: i32,
-  player_health: f32,
-  player_max_health: f32,
-  boost_acquired: bool,
-  chain_acquired: bool,
-) -> Rc<PhysicsSystem> {
+  offset: Vector<f32>,
+  collider_set: &mut ColliderSet,
+) -> Vec<Entity> {
+  map
+    .map_transitions
+    .iter()
+    .map(|map_transition| {
+      let mut collider = map_transition.collider.clone();
+      collider.set_translation(collider.translation() + offset);
+      Entity {
+        handle: EntityHandle::Collider(collider_set.insert(collider)),
+        components: ComponentSet::new()
+          .insert(MapTransitionOnCollision {
+            map_name: map_transition.map_name.clone(),
+            target_player_spawn_id: map_transition.target_player_spawn_id,
+          })
+          .insert(EntityKind::MapTransition)
+          .insert(Name {
+            name: map_transition.map_name.clone(),
+          }),
+      }
+    })
+    .collect()
+}
+
+/* Every plain wall still standing in the room, as one fixed compound collider */
+fn static_walls(map: &Map, map_state: &MapState, offset: Vector<f32>) -> Collider {
+  let shapes = map
+    .colliders
+    .iter()
+    .filter_map(|map_tile| match map_tile {
+      MapTile::Wall(wall) if wall.is_static() && !map_state.destroyed_walls.contains(&wall.id) => {
+        Some((
+          Isometry2::new(wall.collider.translation() + offset, 0.0),
+          SharedShape::new(*wall.collider.shape().as_cuboid().unwrap()),
+        ))
+      }
+      MapTile::Wall(_) => None,
+    })
+    .collect::<Vec<_>>();
+  ColliderBuilder::compound(shapes)
+    .collision_groups(groups(CollisionLayer::Wall))
+    .build()
+}
+
+/* Everything a room is built from when it's loaded into a fresh physics world */
+pub struct MapLoad<'a> {
+  pub map: &'a Map,
+  pub map_name: &'a str,
+  pub map_states: Rc<HashTrieMap<String, MapState>>,
+  pub acquired_modules: &'a [(String, i32)],
+  pub target_player_spawn_id: i32,
+  pub player_health: f32,
+  pub player_max_health: f32,
+  pub boost_acquired: bool,
+  pub chain_acquired: bool,
+  pub magnetize_acquired: bool,
+  pub hack_acquired: bool,
+  pub engine_upgrades: &'a [String],
+  pub discovered_logs: &'a [String],
+  /* Puts the player here rather than at the spawn point */
+  pub player_translation_override: Option<Vector<f32>>,
+  pub entry: Option<&'a RoomEntry>,
+  /* The co-op partner's health, if there is one */
+  pub player_two_health: Option<f32>,
+  pub enemy_definitions: &'a EnemyDefinitions,
+  pub difficulty: Difficulty,
+  pub suspended_room: Option<&'a SuspendedRoom>,
+}
+
+pub fn load_new_map(load: MapLoad) -> Rc<PhysicsSystem> {
+  let MapLoad {
+    map,
+    map_name,
+    map_states,
+    acquired_modules,
+    target_player_spawn_id,
+    player_health,
+    player_max_health,
+    boost_acquired,
+    chain_acquired,
+    magnetize_acquired,
+    hack_acquired,
+    engine_upgrades,
+    discovered_logs,
+    player_translation_override,
+    entry,
+    player_two_health,
+    enemy_definitions,
+    difficulty,
+    suspended_room,
+  } = load;
   let mut rigid_body_set = RigidBodySet::new();
   let mut collider_set = ColliderSet::new();
-  let multibody_joint_set = MultibodyJointSet::new();
+  let mut multibody_joint_set = MultibodyJointSet::new();
+  let map_state = map_states.get(map_name).cloned().unwrap_or_default();
   let mut impulse_joint_set = ImpulseJointSet::new();
 
   let player_spawn = map
@@ -85,49 +887,58 @@ fn load_new_map(
     .unwrap();
 
   /* MARK: Create the player. */
-  let mut player_rigid_body = RigidBodyBuilder::dynamic()
-    .translation(player_spawn.translation.into_vec())
-    .build();
-  player_rigid_body.wake_up(true);
-  let player_collider = &ColliderBuilder::ball(0.25)
-    .collision_groups(InteractionGroups {
-      memberships: COLLISION_GROUP_PLAYER,
-      filter: COLLISION_GROUP_WALL
-        .union(COLLISION_GROUP_ENEMY)
-        .union(COLLISION_GROUP_ENEMY_PROJECTILE)
-        .union(COLLISION_GROUP_PLAYER_INTERACTIBLE),
-      ..Default::default()
-    })
-    .build();
-  let player_handle = rigid_body_set.insert(player_rigid_body);
-  collider_set.insert_with_parent(player_collider.clone(), player_handle, &mut rigid_body_set);
-
-  let player = Entity {
-    handle: EntityHandle::RigidBody(player_handle),
-    components: ComponentSet::new().insert(Damageable {
-      health: player_health,
-      max_health: player_max_health,
-      destroy_on_zero_health: false,
-      current_hitstun: 0.0,
-      max_hitstun: PLAYER_MAX_HITSTUN,
-    }),
-    label: "player".to_string(),
-  };
+  let spawn_translation = player_spawn.translation.into_vec();
+  let entry_door = entry.and_then(|entry| entry_door(map, &entry.from_map_name, spawn_translation));
+  let player_translation = player_translation_override
+    .unwrap_or(entry_door.map_or(spawn_translation, |(translation, _)| translation));
+
+  let (player_handle, player) = spawn_player(
+    player_translation,
+    player_health,
+    player_max_health,
+    "player",
+    &mut collider_set,
+    &mut rigid_body_set,
+  );
+
+  /* MARK: Bring the co-op partner along */
+  let player_two = player_two_health.map(|player_two_health| {
+    spawn_player(
+      player_translation + PLAYER_TWO_OFFSET,
+      player_two_health,
+      player_max_health,
+      "player two",
+      &mut collider_set,
+      &mut rigid_body_set,
+    )
+  });
+  let player_two_handle = player_two.as_ref().map(|(handle, _)| *handle);
+
+  /* Keep the player moving the way they came in, unless that's back out of the door */
+  if let Some(entry) = entry
+    && player_translation_override.is_none()
+  {
+    let away_from_door = entry_door.map_or(entry.velocity, |(translation, door_center)| {
+      translation - door_center
+    });
+    if entry.velocity.dot(&away_from_door) > 0.0 {
+      rigid_body_set[player_handle].set_linvel(entry.velocity, true);
+    }
+  }
 
   /* MARK: Spawn enemies. */
-  let enemies = map
-    .enemy_spawns
-    .iter()
-    .map(|enemy_spawn| {
-      let handle = rigid_body_set.insert(enemy_spawn.rigid_body.clone());
-      collider_set.insert_with_parent(enemy_spawn.collider.clone(), handle, &mut rigid_body_set);
-      Entity {
-        handle: EntityHandle::RigidBody(handle),
-        components: enemy_spawn.into_entity_components(),
-        label: "enemy".to_string(),
-      }
-    })
-    .collect::<Vec<_>>();
+  let enemies = spawn_enemies(
+    &EnemySpawns {
+      map,
+      map_name,
+      map_state: &map_state,
+      offset: vector![0.0, 0.0],
+      enemy_definitions,
+      difficulty,
+    },
+    &mut rigid_body_set,
+    &mut collider_set,
+  );
 
   /* MARK: Spawn item pickups. */
   let item_pickups = map
@@ -143,25 +954,14 @@ fn load_new_map(
             weapon_module_kind: item_pickup.weapon_module_kind,
           })
           .insert(Id { id: item_pickup.id })
-          .insert(DestroyOnCollision),
-        label: "item".to_string(),
+          .insert(DestroyOnCollision)
+          .insert(EntityKind::ItemPickup),
       }
     })
     .collect::<Vec<_>>();
 
   /* MARK: Spawn map transitions. */
-  let map_transitions = map
-    .map_transitions
-    .iter()
-    .map(|map_transition| Entity {
-      handle: EntityHandle::Collider(collider_set.insert(map_transition.collider.clone())),
-      components: ComponentSet::new().insert(MapTransitionOnCollision {
-        map_name: map_transition.map_name.clone(),
-        target_player_spawn_id: map_transition.target_player_spawn_id,
-      }),
-      label: map_transition.map_name.clone(),
-    })
-    .collect::<Vec<_>>();
+  let map_transitions = spawn_map_transitions(map, vector![0.0, 0.0], &mut collider_set);
 
   /* MARK: Spawn save points. */
   let save_points = map
@@ -174,8 +974,8 @@ fn load_new_map(
         .insert(Id {
           id: save_point.player_spawn_id,
         })
-        .insert(HealOnCollision { amount: 9999.0 }),
-      label: "save".to_string(),
+        .insert(HealOnCollision { amount: 9999.0 })
+        .insert(EntityKind::SavePoint),
     })
     .collect::<Vec<_>>();
 
@@ -184,7 +984,15 @@ fn load_new_map(
     .blocks
     .iter()
     .map(|block| {
-      let rigid_body_handle = rigid_body_set.insert(block.rigid_body.clone());
+      let mut rigid_body = block.rigid_body.clone();
+      if let Some((_, (x, y))) = map_state
+        .block_translations
+        .iter()
+        .find(|(id, _)| *id == block.id)
+      {
+        rigid_body.set_translation(vector![*x, *y], true);
+      }
+      let rigid_body_handle = rigid_body_set.insert(rigid_body);
       collider_set.insert_with_parent(
         block.collider.clone(),
         rigid_body_handle,
@@ -192,8 +1000,10 @@ fn load_new_map(
       );
       Entity {
         handle: EntityHandle::RigidBody(rigid_body_handle),
-        components: ComponentSet::new().insert(Id { id: block.id }),
-        label: format!("g{}", block.id),
+        components: ComponentSet::new()
+          .insert(Id { id: block.id })
+          .insert(MovableBlock)
+          .insert(EntityKind::Block),
       }
     })
     .collect::<Vec<_>>();
@@ -211,34 +1021,121 @@ fn load_new_map(
         .insert(Activator { activation: 0.0 })
         .insert(Id {
           id: touch_sensor.id,
-        }),
-      label: "gt".to_string(),
+        })
+        .insert(EntityKind::TouchSensor),
     })
     .collect::<Vec<_>>();
 
-  /* MARK: Spawn gravity sources */
-  let gravity_sources = map
-    .gravity_sources
+  /* MARK: Spawn tutorial triggers */
+  let tutorial_triggers = map
+    .tutorial_triggers
     .iter()
-    .map(|gravity_source| Entity {
-      handle: EntityHandle::Collider(collider_set.insert(gravity_source.collider.clone())),
-      components: ComponentSet::new().insert(GravitySource {
-        strength: gravity_source.strength,
-        activator_id: gravity_source.activator_id,
-      }),
-      label: "grav".to_string(),
+    .map(|tutorial_trigger| Entity {
+      handle: EntityHandle::Collider(collider_set.insert(tutorial_trigger.collider.clone())),
+      components: ComponentSet::new()
+        .insert(TutorialTrigger {
+          action: tutorial_trigger.action,
+          text: tutorial_trigger.text.clone(),
+        })
+        .insert(EntityKind::TutorialTrigger),
     })
     .collect::<Vec<_>>();
 
-  /* Spawn ability pickups */
-  let ability_pickups = map
-    .ability_pickups
+  /* MARK: Spawn dialogue triggers */
+  let dialogue_triggers = map
+    .dialogue_triggers
     .iter()
-    .filter_map(|ability_pickup| {
-      let should_spawn_entity = match ability_pickup.ability_type {
-        MapAbilityType::Boost => !boost_acquired,
-        MapAbilityType::Chain => !chain_acquired,
-      };
+    .map(|dialogue_trigger| Entity {
+      handle: EntityHandle::Collider(collider_set.insert(dialogue_trigger.collider.clone())),
+      components: ComponentSet::new()
+        .insert(DialogueTrigger {
+          dialogue: dialogue_trigger.dialogue.clone(),
+        })
+        .insert(EntityKind::DialogueTrigger),
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Spawn encounters, apart from those already cleared */
+  let encounters = map
+    .encounters
+    .iter()
+    .filter(|encounter| !map_state.cleared_encounters.contains(&encounter.id))
+    .map(|encounter| Entity {
+      handle: EntityHandle::Collider(collider_set.insert(encounter.collider.clone())),
+      components: ComponentSet::new()
+        .insert(Encounter {
+          id: encounter.id,
+          state: EncounterState::Waiting,
+          gate_ids: encounter.gate_ids.clone(),
+          closed_activation: encounter.closed_activation,
+          enemies: Vec::new(),
+          rewards: encounter.rewards.clone(),
+        })
+        .insert(EntityKind::Encounter),
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Spawn explosive barrels */
+  let explosive_barrels = map
+    .explosive_barrels
+    .iter()
+    .map(|barrel| spawn_explosive_barrel(barrel, &mut collider_set, &mut rigid_body_set))
+    .collect::<Vec<_>>();
+
+  /* MARK: Spawn mounted turrets */
+  let mounted_turrets = map
+    .mounted_turrets
+    .iter()
+    .map(|turret| Entity {
+      handle: EntityHandle::Collider(collider_set.insert(turret.collider.clone())),
+      components: ComponentSet::new()
+        .insert(MountedTurret)
+        .insert(Id { id: turret.id })
+        .insert(EntityKind::MountedTurret),
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Spawn gravity sources */
+  let gravity_sources = map
+    .gravity_sources
+    .iter()
+    .map(|gravity_source| Entity {
+      handle: EntityHandle::Collider(collider_set.insert(gravity_source.collider.clone())),
+      components: ComponentSet::new()
+        .insert(GravitySource {
+          strength: gravity_source.strength,
+          activator_id: gravity_source.activator_id,
+        })
+        .insert(EntityKind::GravitySource),
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Spawn gravity zones */
+  let gravity_zones = map
+    .gravity_zones
+    .iter()
+    .map(|gravity_zone| Entity {
+      handle: EntityHandle::Collider(collider_set.insert(gravity_zone.collider.clone())),
+      components: ComponentSet::new()
+        .insert(GravityZone {
+          acceleration: gravity_zone.acceleration,
+          activator_id: gravity_zone.activator_id,
+        })
+        .insert(EntityKind::GravityZone),
+    })
+    .collect::<Vec<_>>();
+
+  /* Spawn ability pickups */
+  let ability_pickups = map
+    .ability_pickups
+    .iter()
+    .filter_map(|ability_pickup| {
+      let should_spawn_entity = match ability_pickup.ability_type {
+        MapAbilityType::Boost => !boost_acquired,
+        MapAbilityType::Chain => !chain_acquired,
+        MapAbilityType::Magnetize => !magnetize_acquired,
+        MapAbilityType::Hack => !hack_acquired,
+      };
 
       if should_spawn_entity {
         Some(Entity {
@@ -247,8 +1144,8 @@ fn load_new_map(
             .insert(GiveAbilityOnCollision {
               ability_type: ability_pickup.ability_type,
             })
-            .insert(DestroyOnCollision),
-          label: "ability".to_string(),
+            .insert(DestroyOnCollision)
+            .insert(EntityKind::AbilityPickup),
         })
       } else {
         None
@@ -256,6 +1153,78 @@ fn load_new_map(
     })
     .collect::<Vec<_>>();
 
+  /* MARK: Spawn log entries */
+  let log_entries = map
+    .log_entries
+    .iter()
+    .filter(|log_entry| !discovered_logs.contains(&log_entry.log_id))
+    .map(|log_entry| Entity {
+      handle: EntityHandle::Collider(collider_set.insert(log_entry.collider.clone())),
+      components: ComponentSet::new()
+        .insert(GivesLogOnCollision {
+          log_id: log_entry.log_id.clone(),
+        })
+        .insert(DestroyOnCollision)
+        .insert(EntityKind::LogPickup),
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Spawn engine upgrades */
+  let engine_upgrade_pickups = map
+    .engine_upgrades
+    .iter()
+    .filter(|engine_upgrade| !engine_upgrades.contains(&engine_upgrade.name))
+    .map(|engine_upgrade| Entity {
+      handle: EntityHandle::Collider(collider_set.insert(engine_upgrade.collider.clone())),
+      components: ComponentSet::new()
+        .insert(GivesEngineUpgradeOnCollision {
+          name: engine_upgrade.name.clone(),
+        })
+        .insert(DestroyOnCollision)
+        .insert(EntityKind::EngineUpgradePickup),
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Spawn fluid volumes */
+  let fluid_volumes = map
+    .fluid_volumes
+    .iter()
+    .map(|fluid_volume| Entity {
+      handle: EntityHandle::Collider(collider_set.insert(fluid_volume.collider.clone())),
+      components: ComponentSet::new()
+        .insert(FluidVolume {
+          drag: fluid_volume.drag,
+          buoyancy: fluid_volume.buoyancy,
+          movement_scale: fluid_volume.movement_scale,
+        })
+        .insert(EntityKind::FluidVolume),
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Spawn magnetic walls */
+  let magnetic_walls = map
+    .magnetic_walls
+    .iter()
+    .map(|magnetic_wall| Entity {
+      handle: EntityHandle::Collider(collider_set.insert(magnetic_wall.collider.clone())),
+      components: ComponentSet::new()
+        .insert(Magnetic)
+        .insert(EntityKind::MagneticWall),
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Spawn hazards */
+  let hazards = map
+    .hazards
+    .iter()
+    .map(|hazard| Entity {
+      handle: EntityHandle::Collider(collider_set.insert(hazard.collider.clone())),
+      components: ComponentSet::new()
+        .insert(Hazard { kind: hazard.kind })
+        .insert(EntityKind::Hazard),
+    })
+    .collect::<Vec<_>>();
+
   /* MARK: Spawn chain switches */
   let chain_switches = map
     .chain_switches
@@ -283,8 +1252,9 @@ fn load_new_map(
       [
         Entity {
           handle: EntityHandle::Collider(collider_set.insert(chain_switch.collider.clone())),
-          components: ComponentSet::new().insert(ChainMountArea { target_mount_body }),
-          label: "mount".to_string(),
+          components: ComponentSet::new()
+            .insert(ChainMountArea { target_mount_body })
+            .insert(EntityKind::MountZone),
         },
         Entity {
           handle: EntityHandle::RigidBody(target_mount_body),
@@ -295,8 +1265,8 @@ fn load_new_map(
             .insert(Id {
               id: chain_switch.id,
             })
-            .insert(Activator { activation: 0.0 }),
-          label: "switch".to_string(),
+            .insert(Activator { activation: 0.0 })
+            .insert(EntityKind::ChainSwitch),
         },
       ]
     })
@@ -332,8 +1302,8 @@ fn load_new_map(
           .insert(SimpleActivatable {
             activation: 0.0,
             activator_id: locomotor.activator_id,
-          }),
-        label: "locomotor".to_string(),
+          })
+          .insert(EntityKind::Locomotor),
       }
     })
     .collect::<Vec<_>>();
@@ -359,24 +1329,69 @@ fn load_new_map(
       let mount_point_entity = {
         Entity {
           handle: EntityHandle::RigidBody(mount_point_handle),
-          components: ComponentSet::new().insert(Id { id: mount_point.id }),
-          label: "mount".to_string(),
+          components: ComponentSet::new()
+            .insert(Id { id: mount_point.id })
+            .insert(EntityKind::MountPoint),
         }
       };
 
       [
         Entity {
           handle: EntityHandle::Collider(zone_handle),
-          components: ComponentSet::new().insert(ChainMountArea {
-            target_mount_body: mount_point_handle,
-          }),
-          label: "zone".to_string(),
+          components: ComponentSet::new()
+            .insert(ChainMountArea {
+              target_mount_body: mount_point_handle,
+            })
+            .insert(EntityKind::MountZone),
         },
         mount_point_entity,
       ]
     })
     .collect::<Vec<_>>();
 
+  /* MARK: Spawn tension switches. */
+  let tension_switches = map
+    .tension_switches
+    .iter()
+    .flat_map(|tension_switch| {
+      let switch_handle = rigid_body_set.insert(tension_switch.rigid_body.clone());
+      let zone_handle = collider_set.insert_with_parent(
+        tension_switch.zone.clone(),
+        switch_handle,
+        &mut rigid_body_set,
+      );
+
+      collider_set.insert_with_parent(
+        tension_switch.knob.clone(),
+        switch_handle,
+        &mut rigid_body_set,
+      );
+
+      [
+        Entity {
+          handle: EntityHandle::Collider(zone_handle),
+          components: ComponentSet::new()
+            .insert(ChainMountArea {
+              target_mount_body: switch_handle,
+            })
+            .insert(EntityKind::MountZone),
+        },
+        Entity {
+          handle: EntityHandle::RigidBody(switch_handle),
+          components: ComponentSet::new()
+            .insert(TensionSwitch {
+              full_force: tension_switch.full_force,
+            })
+            .insert(Id {
+              id: tension_switch.id,
+            })
+            .insert(Activator { activation: 0.0 })
+            .insert(EntityKind::TensionSwitch),
+        },
+      ]
+    })
+    .collect::<Vec<_>>();
+
   /* MARK: Spawn ands. */
   let ands = map
     .ands
@@ -391,8 +1406,8 @@ fn load_new_map(
             activator_ids: (and.activator_ids.0, and.activator_ids.1),
           })
           .insert(Activator { activation: 0.0 })
-          .insert(Id { id: and.id }),
-        label: "and".to_string(),
+          .insert(Id { id: and.id })
+          .insert(EntityKind::And),
       }
     })
     .collect::<Vec<_>>();
@@ -411,8 +1426,8 @@ fn load_new_map(
             activator_ids: (or.activator_ids.0, or.activator_ids.1),
           })
           .insert(Activator { activation: 0.0 })
-          .insert(Id { id: or.id }),
-        label: "or".to_string(),
+          .insert(Id { id: or.id })
+          .insert(EntityKind::Or),
       }
     })
     .collect::<Vec<_>>();
@@ -423,17 +1438,23 @@ fn load_new_map(
     .iter()
     .map(|gate| {
       let gate_handle = rigid_body_set.insert(gate.rigid_body.clone());
+      let activation = map_state
+        .gate_activations
+        .iter()
+        .find(|(id, _)| *id == gate.id)
+        .map(|(_, activation)| *activation)
+        .unwrap_or(0.0);
 
       Entity {
         handle: EntityHandle::RigidBody(gate_handle),
         components: ComponentSet::new()
           .insert(Gate {
             activator_id: gate.activator_id,
-            highest_historical_activation: 0.0,
+            highest_historical_activation: activation,
           })
-          .insert(Activator { activation: 0.0 })
-          .insert(Id { id: gate.id }),
-        label: "gate".to_string(),
+          .insert(Activator { activation })
+          .insert(Id { id: gate.id })
+          .insert(EntityKind::Gate),
       }
     })
     .collect::<Vec<_>>();
@@ -453,8 +1474,8 @@ fn load_new_map(
             currently_increasing: true,
           })
           .insert(Activator { activation: 0.0 })
-          .insert(Id { id: engine.id }),
-        label: "engine".to_string(),
+          .insert(Id { id: engine.id })
+          .insert(EntityKind::Engine),
       }
     })
     .collect::<Vec<_>>();
@@ -464,17 +1485,17 @@ fn load_new_map(
     .colliders
     .iter()
     .map(|map_tile| match map_tile {
+      MapTile::Wall(wall) if map_state.destroyed_walls.contains(&wall.id) => None,
       MapTile::Wall(wall) => {
-        if wall.damaging.is_none() && wall.damageable.is_none() {
-          (
-            Some((
-              Isometry2::new(*wall.collider.translation(), 0.0),
-              SharedShape::new(*wall.collider.shape().as_cuboid().unwrap()),
-            )),
-            None,
-          )
+        if wall.is_static() {
+          None
         } else {
-          let damager = wall.damaging.map(|damaging| Damager { damage: damaging });
+          let damager = wall.damaging.map(|damaging| Damager {
+            damage: damaging,
+            knockback: 0.0,
+            on_hit_status: None,
+            source: None,
+          });
           let damageable = wall.damageable.map(|damageable| Damageable {
             health: damageable,
             max_health: damageable,
@@ -489,20 +1510,26 @@ fn load_new_map(
             &mut rigid_body_set,
           );
 
-          let label = format!(
-            "{}{}",
-            if damageable.is_some() { "D" } else { "" },
-            if damager.is_some() { "H" } else { "" },
-          );
-
-          let component_set = ComponentSet::new();
+          let component_set = ComponentSet::new().insert(EntityKind::Wall);
+          let component_set = if wall.magnetic {
+            component_set.insert(Magnetic)
+          } else {
+            component_set
+          };
+          let component_set = if wall.reflective {
+            component_set.insert(Reflective)
+          } else {
+            component_set
+          };
           let component_set = if let Some(damager) = damager {
             component_set.insert(damager)
           } else {
             component_set
           };
           let component_set = if let Some(damageable) = damageable {
-            component_set.insert(damageable)
+            component_set
+              .insert(damageable)
+              .insert(Persistent { id: wall.id })
           } else {
             component_set
           };
@@ -510,61 +1537,58 @@ fn load_new_map(
           let entity = Entity {
             handle: EntityHandle::RigidBody(rigid_body_handle),
             components: component_set,
-            label,
           };
-          (None, Some(entity))
+          Some(entity)
         }
       }
     })
     .collect::<Vec<_>>();
 
-  let static_walls = map_tiles
-    .iter()
-    .cloned()
-    .filter_map(|(static_wall, _)| static_wall)
-    .collect::<Vec<_>>();
-  collider_set.insert(ColliderBuilder::compound(static_walls).collision_groups(
-    InteractionGroups {
-      memberships: COLLISION_GROUP_WALL,
-      filter: Group::all(),
-      ..Default::default()
-    },
-  ));
+  collider_set.insert(static_walls(map, &map_state, vector![0.0, 0.0]));
 
-  let interactive_walls = map_tiles
-    .iter()
-    .cloned()
-    .filter_map(|(_, interactive_wall)| interactive_wall)
-    .collect::<Vec<_>>();
+  let interactive_walls = map_tiles.into_iter().flatten().collect::<Vec<_>>();
 
   /* MARK: Create other structures necessary for the simulation. */
   let integration_parameters = IntegrationParameters::default();
   let physics_pipeline = Rc::new(RefCell::new(PhysicsPipeline::new()));
-  let island_manager = IslandManager::new();
+  let mut island_manager = IslandManager::new();
   let broad_phase = DefaultBroadPhase::new();
   let narrow_phase = NarrowPhase::new();
   let ccd_solver: CCDSolver = CCDSolver::new();
   let entities = [player]
     .iter()
     .cloned()
+    .chain(player_two.map(|(_, entity)| entity))
     .chain(enemies)
     .chain(interactive_walls)
     .chain(blocks)
     .chain(item_pickups)
     .chain(ability_pickups)
+    .chain(log_entries)
+    .chain(engine_upgrade_pickups)
+    .chain(hazards)
+    .chain(fluid_volumes)
+    .chain(magnetic_walls)
     .chain(map_transitions)
     .chain(save_points)
     .chain(touch_sensors)
+    .chain(tutorial_triggers)
+    .chain(dialogue_triggers)
+    .chain(encounters)
+    .chain(explosive_barrels)
+    .chain(mounted_turrets)
     .chain(gravity_sources)
+    .chain(gravity_zones)
     .chain(chain_switches)
     .chain(locomotors)
     .chain(mount_points)
+    .chain(tension_switches)
     .chain(ands)
     .chain(ors)
     .chain(gates)
     .chain(engines)
-    .map(|entity| (entity.handle, Rc::new(entity)))
-    .collect::<HashTrieMap<_, _>>();
+    .map(|entity| (entity.handle, Arc::new(entity)))
+    .collect::<Entities>();
 
   /* MARK: Spawn glues. */
   map.glues.iter().for_each(|glue| {
@@ -624,6 +1648,34 @@ fn load_new_map(
     );
   });
 
+  /* MARK: Put a suspended room back as it was left */
+  let entities = match suspended_room {
+    Some(suspended_room) => restore_suspended_room(
+      suspended_room,
+      entities,
+      player_handle,
+      PhysicsSets {
+        rigid_body_set: &mut rigid_body_set,
+        collider_set: &mut collider_set,
+        island_manager: &mut island_manager,
+        impulse_joint_set: &mut impulse_joint_set,
+        multibody_joint_set: &mut multibody_joint_set,
+      },
+    ),
+    None => entities,
+  };
+
+  /* MARK: Move the players out of anything they came in inside of */
+  [Some(player_handle), player_two_handle]
+    .into_iter()
+    .flatten()
+    .for_each(|handle| {
+      let translation = clear_spawn_translation(handle, &rigid_body_set, &collider_set);
+      rigid_body_set[handle].set_translation(translation, true);
+    });
+
+  let entity_hash = build_entity_hash(&entities, &rigid_body_set, &collider_set);
+
   Rc::new(PhysicsSystem {
     rigid_body_set,
     collider_set,
@@ -636,48 +1688,167 @@ fn load_new_map(
     multibody_joint_set,
     ccd_solver,
     player_handle,
+    player_two_handle,
+    player_two_joined: player_two_handle.is_some(),
     entities,
     frame_count: 0,
+    simulated_frames: 0.0,
     new_weapon_modules: list![],
     new_abilities: list![],
+    new_logs: list![],
+    new_engine_upgrades: list![],
+    new_credits: 0,
+    new_power_ups: list![],
+    new_kills: 0,
+    encounter_cleared: false,
     load_new_map: None,
     save_point_contact: None,
     save_point_contact_last_frame: None,
     mount_points_in_range: list![],
+    mounted_turret: None,
+    turret_in_range: false,
+    chain_tension: 0.0,
+    chain_durability: 0.0,
+    chain_broke: false,
+    map_name: map_name.to_string(),
+    entity_hash,
+    map_states,
+    cycle: map.cycle.clone().map(Rc::new),
+    friendly_fire: map.friendly_fire,
+    puzzle: map.puzzle,
+    rewind_snapshots: Rc::new(VecDeque::new()),
+    stage_timings: Rc::new(vec![]),
+    room_exit: None,
+    room_entry: entry.cloned(),
+    scored: map.scored,
+    challenge: map.challenge.clone().map(Rc::new),
+    streamed_chunks: Rc::new(HashTrieMap::new()),
   })
 }
 
 impl System for PhysicsSystem {
   type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<MapSystem>(),
+      Requirement::of::<CombatSystem>(),
+      Requirement::of::<AbilitySystem>(),
+      Requirement::of::<DataSystem>(),
+      Requirement::of::<ControlsSystem<_>>(),
+      Requirement::of::<CoopControlsSystem>(),
+      Requirement::of::<MenuSystem<_>>(),
+      Requirement::of::<EnemySystem>(),
+      Requirement::of::<DialogueSystem>(),
+      Requirement::of::<DatabaseSystem>(),
+      Requirement::of::<SettingsSystem>(),
+    ]
+  }
+
+  /* The slowest stages and how crowded the room is */
+  fn diagnostics(&self) -> Vec<String> {
+    let mut stage_timings = self.stage_timings.as_ref().clone();
+    stage_timings.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    let count_of = |kinds: &[EntityKind]| {
+      self
+        .entities
+        .values()
+        .filter(|entity| {
+          entity
+            .components
+            .get::<EntityKind>()
+            .is_some_and(|kind| kinds.contains(&kind))
+        })
+        .count()
+    };
+
+    vec![
+      format!(
+        "slowest stages: {}",
+        stage_timings
+          .iter()
+          .take(SLOW_STAGES_LOGGED)
+          .map(|(name, elapsed_ms)| format!("{} {:.1}ms", name, elapsed_ms))
+          .collect::<Vec<_>>()
+          .join(", ")
+      ),
+      format!(
+        "{} in {}: {} entities, {} enemies, {} projectiles",
+        self.frame_count,
+        self.map_name,
+        self.entities.size(),
+        count_of(&[EntityKind::Enemy]),
+        count_of(&[EntityKind::PlayerProjectile, EntityKind::EnemyProjectile])
+      ),
+    ]
+  }
+
+  fn snapshot(&self) -> Option<serde_json::Value> {
+    Some(serde_json::json!({
+      "map_name": self.map_name,
+      "frame_count": self.frame_count,
+      "stage_timings": self.stage_timings.as_ref(),
+      "room": self.suspended_room(),
+    }))
+  }
+
   fn start(ctx: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
   where
     Self: Sized,
   {
-    let map_system = ctx.get::<MapSystem>().unwrap();
+    let map_system = ctx.require::<MapSystem>().unwrap();
     let map = map_system.map.as_ref().unwrap();
 
-    let combat_system = ctx.get::<CombatSystem>().unwrap();
+    let combat_system = ctx.require::<CombatSystem>().unwrap();
+    let data_system = ctx.require::<DataSystem>().unwrap();
 
-    load_new_map(
+    load_new_map(MapLoad {
       map,
-      &map_system.current_map_name,
-      &combat_system.acquired_items,
-      map_system.target_player_spawn_id,
-      ctx.input.player_health,
-      ctx.input.player_max_health,
-      ctx.input.acquired_boost,
-      ctx.input.acquired_chain,
-    )
+      map_name: &map_system.current_map_name,
+      map_states: Rc::new(
+        ctx
+          .input
+          .map_states
+          .iter()
+          .map(|(map_name, map_state)| (map_name.clone(), map_state.clone()))
+          .collect(),
+      ),
+      acquired_modules: &combat_system.acquired_items,
+      target_player_spawn_id: map_system.target_player_spawn_id,
+      player_health: ctx.input.player_health,
+      player_max_health: ctx.input.player_max_health,
+      boost_acquired: ctx.input.acquired_boost,
+      chain_acquired: ctx.input.acquired_chain,
+      magnetize_acquired: ctx.input.acquired_magnetize,
+      hack_acquired: ctx.input.acquired_hack,
+      engine_upgrades: &ctx.input.engine_upgrades,
+      discovered_logs: &ctx.input.discovered_logs,
+      player_translation_override: ctx.input.suspended_room.as_ref().map(|suspended_room| {
+        let (x, y) = suspended_room.player_translation;
+        vector![x, y]
+      }),
+      entry: None,
+      /* The partner joins once their gamepad is claimed */
+      player_two_health: None,
+      enemy_definitions: &data_system.data.enemies,
+      difficulty: ctx.input.difficulty,
+      suspended_room: ctx.input.suspended_room.as_ref(),
+    })
   }
 
   fn run(
     &self,
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> Rc<dyn System<Input = Self::Input>> {
-    let map_system = ctx.get::<MapSystem>().unwrap();
+    let map_system = ctx.require::<MapSystem>().unwrap();
 
-    let combat_system = ctx.get::<CombatSystem>().unwrap();
-    let ability_system = ctx.get::<AbilitySystem>().unwrap();
+    let combat_system = ctx.require::<CombatSystem>().unwrap();
+    let ability_system = ctx.require::<AbilitySystem>().unwrap();
+    let data_system = ctx.require::<DataSystem>().unwrap();
+    let database_system = ctx.require::<DatabaseSystem>().unwrap();
+
+    let coop_controls_system = ctx.require::<CoopControlsSystem>().unwrap();
 
     if let Some(map) = map_system.map.as_ref() {
       let player_entity = self
@@ -686,1302 +1857,5613 @@ impl System for PhysicsSystem {
         .unwrap();
       let player_damageable = player_entity.components.get::<Damageable>().unwrap();
 
-      return load_new_map(
+      /* A partner who went down in the last room comes back at full health */
+      let player_two_health = coop_controls_system.gamepad.map(|_| {
+        self
+          .player_two_handle
+          .and_then(|handle| self.entities.get(&EntityHandle::RigidBody(handle)))
+          .and_then(|entity| entity.components.get::<Damageable>())
+          .map(|damageable| damageable.health)
+          .unwrap_or(player_damageable.max_health)
+      });
+
+      return load_new_map(MapLoad {
         map,
-        &map_system.current_map_name,
-        &combat_system.acquired_items,
-        map_system.target_player_spawn_id,
-        player_damageable.health,
-        player_damageable.max_health,
-        ability_system.acquired_boost,
-        ability_system.acquired_chain,
-      );
+        map_name: &map_system.current_map_name,
+        /* Leaving a room is when its state is recorded */
+        map_states: Rc::new(self.map_states()),
+        acquired_modules: &combat_system.acquired_items,
+        target_player_spawn_id: map_system.target_player_spawn_id,
+        player_health: player_damageable.health,
+        player_max_health: player_damageable.max_health,
+        boost_acquired: ability_system.acquired_boost,
+        chain_acquired: ability_system.acquired_chain,
+        magnetize_acquired: ability_system.acquired_magnetize,
+        hack_acquired: ability_system.acquired_hack,
+        engine_upgrades: &ability_system.engine_upgrades,
+        discovered_logs: &database_system.discovered,
+        /* Keep the player where they were if the map was only hot reloaded */
+        player_translation_override: if map_system.hot_reloaded {
+          Some(*self.rigid_body_set[self.player_handle].translation())
+        } else {
+          None
+        },
+        entry: self
+          .room_exit
+          .as_ref()
+          .filter(|_| !map_system.hot_reloaded)
+          .map(|room_exit| RoomEntry {
+            from_map_name: self.map_name.clone(),
+            velocity: room_exit.velocity,
+          })
+          .as_ref(),
+        player_two_health,
+        enemy_definitions: &data_system.data.enemies,
+        difficulty: ctx.input.difficulty,
+        suspended_room: None,
+      });
     }
 
-    let mut physics_pipeline = self.physics_pipeline.as_ref().borrow_mut();
-    let mut island_manager = self.island_manager.clone();
-    let mut broad_phase = self.broad_phase.clone();
-    let mut narrow_phase = self.narrow_phase.clone();
-    let mut impulse_joint_set = self.impulse_joint_set.clone();
-    let mut multibody_joint_set = self.multibody_joint_set.clone();
-    let mut ccd_solver = self.ccd_solver.clone();
-    let rigid_body_set = &mut self.rigid_body_set.clone();
-    let mut collider_set = self.collider_set.clone();
-
-    let entities = self.entities.clone();
-
-    /* MARK: Don't do physics if currently in menu */
-    let menu_system = ctx.get::<MenuSystem<_>>().unwrap();
+    /* MARK: Don't do physics if currently in menu, dialogue or frozen for debugging */
+    let menu_system = ctx.require::<MenuSystem<_>>().unwrap();
+    let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
+    let dialogue_system = ctx.require::<DialogueSystem>().unwrap();
 
-    if !menu_system.active_menus.is_empty() {
+    if !menu_system.active_menus.is_empty()
+      || controls_system.simulation_frozen()
+      || dialogue_system.playing()
+      || self.room_exit.is_some()
+    {
       return Rc::new(Self {
-        rigid_body_set: rigid_body_set.clone(),
-        collider_set,
+        rigid_body_set: self.rigid_body_set.clone(),
+        collider_set: self.collider_set.clone(),
         integration_parameters: self.integration_parameters,
         physics_pipeline: Rc::clone(&self.physics_pipeline),
-        island_manager,
-        broad_phase,
-        narrow_phase,
-        impulse_joint_set,
-        multibody_joint_set,
-        ccd_solver,
+        island_manager: self.island_manager.clone(),
+        broad_phase: self.broad_phase.clone(),
+        narrow_phase: self.narrow_phase.clone(),
+        impulse_joint_set: self.impulse_joint_set.clone(),
+        multibody_joint_set: self.multibody_joint_set.clone(),
+        ccd_solver: self.ccd_solver.clone(),
         player_handle: self.player_handle,
+        player_two_handle: self.player_two_handle,
+        player_two_joined: self.player_two_joined,
         entities: self.entities.clone(),
         frame_count: self.frame_count + 1,
+        simulated_frames: self.simulated_frames + controls_system.time_scale as f64,
         new_weapon_modules: list![],
         new_abilities: list![],
-        load_new_map: None,
+        new_logs: list![],
+        new_engine_upgrades: list![],
+        new_credits: 0,
+        new_power_ups: list![],
+        new_kills: 0,
+        encounter_cleared: false,
+        /* The next room is loaded on the last frame of the fade */
+        load_new_map: self
+          .room_exit
+          .as_ref()
+          .filter(|room_exit| room_exit.frames_left == 1)
+          .map(|room_exit| (room_exit.map_name.clone(), room_exit.target_player_spawn_id)),
         save_point_contact: self.save_point_contact,
         save_point_contact_last_frame: self.save_point_contact_last_frame,
         mount_points_in_range: list![],
+        mounted_turret: self.mounted_turret,
+        turret_in_range: self.turret_in_range,
+        chain_tension: self.chain_tension,
+        chain_durability: self.chain_durability,
+        chain_broke: false,
+        map_name: self.map_name.clone(),
+        map_states: Rc::clone(&self.map_states),
+        cycle: self.cycle.clone(),
+        friendly_fire: self.friendly_fire,
+        scored: self.scored,
+        challenge: self.challenge.clone(),
+        entity_hash: Rc::clone(&self.entity_hash),
+        streamed_chunks: Rc::clone(&self.streamed_chunks),
+        puzzle: self.puzzle,
+        rewind_snapshots: Rc::clone(&self.rewind_snapshots),
+        stage_timings: Rc::new(vec![]),
+        /* Kept once the fade is over, for the next room to be entered from */
+        room_exit: self.room_exit.as_ref().map(|room_exit| RoomExit {
+          frames_left: (room_exit.frames_left - 1).max(0),
+          ..room_exit.clone()
+        }),
+        room_entry: self.room_entry.clone(),
       });
     }
 
-    /* MARK: Move the player */
-    let controls_system = ctx.get::<ControlsSystem<_>>().unwrap();
+    /* MARK: Run every stage over this frame */
+    let enemy_system = ctx.require::<EnemySystem>().unwrap();
+    let settings_system = ctx.require::<SettingsSystem>().unwrap();
+    let drone_specs = combat_system.drone_specs();
 
-    let next_player_impulse =
-      player_movement_impulse(controls_system, &rigid_body_set[self.player_handle]);
+    let inputs = FrameInputs {
+      frame_count: self.frame_count,
+      simulated_frames: self.simulated_frames,
+      time_scale: controls_system.time_scale,
+      whole_frame: controls_system.whole_frame,
+      player_handle: self.player_handle,
+      map_name: &self.map_name,
+      left_stick: controls_system.left_stick,
+      coop_gamepad_claimed: coop_controls_system.gamepad.is_some(),
+      coop_left_stick: coop_controls_system.left_stick,
+      saved_player_two_health: ctx.input.player_two_health,
+      boost_force: ability_system.boost_force,
+      interact: controls_system.pressed(|controls| controls.interact),
+      dismount: controls_system.pressed(|controls| controls.menu_cancel),
+      projectiles: &combat_system.new_projectiles,
+      player_two_projectiles: &combat_system.player_two_projectiles,
+      drone_specs: &drone_specs,
+      enemy_decisions: &enemy_system.decisions,
+      enemy_definitions: &data_system.data.enemies,
+      entity_limits: data_system.data.limits,
+      movement: data_system
+        .data
+        .movement
+        .stats(&ability_system.engine_upgrades)
+        .with_speed_mod(combat_system.speed_mod()),
+      difficulty: ctx.input.difficulty,
+      chain_to_mount_point: ability_system.chain_to_mount_point,
+      kill_chain: ability_system.kill_chain,
+      magnetize: ability_system.magnetize,
+      hack: ability_system.hack,
+      assist: settings_system.settings.assist,
+      physics_pipeline: &self.physics_pipeline,
+      /* A slowed down frame steps the world over less time */
+      integration_parameters: settings_system.settings.physics_quality.apply(
+        IntegrationParameters {
+          dt: self.integration_parameters.dt * controls_system.time_scale,
+          ..self.integration_parameters
+        },
+      ),
+      cycle_phase: self.cycle_phase(),
+      friendly_fire: self.friendly_fire,
+      chunks: Rc::clone(&map_system.chunks),
+    };
 
-    rigid_body_set[self.player_handle].apply_impulse(next_player_impulse, true);
+    /* MARK: Rewind puzzle rooms to the oldest snapshot, or keep one every so often */
+    let rewind_to = self
+      .rewind_snapshots
+      .front()
+      .filter(|_| self.puzzle && controls_system.pressed(|controls| controls.rewind));
+
+    let rewind_snapshots = if rewind_to.is_some() {
+      log::info("physics", "rewound".to_string());
+      Rc::new(VecDeque::new())
+    } else if self.puzzle && self.frame_count % REWIND_INTERVAL_FRAMES == 0 {
+      let mut rewind_snapshots = self.rewind_snapshots.as_ref().clone();
+      if rewind_snapshots.len() == REWIND_SNAPSHOTS {
+        rewind_snapshots.pop_front();
+      }
+      rewind_snapshots.push_back(Rc::new(RewindSnapshot::of(self)));
+      Rc::new(rewind_snapshots)
+    } else {
+      Rc::clone(&self.rewind_snapshots)
+    };
 
-    /* MARK: Perform boost */
-    let player_mass = rigid_body_set[self.player_handle].mass();
+    let initial_frame = match rewind_to {
+      Some(snapshot) => FrameState::from_snapshot(self, snapshot),
+      None => FrameState::from_system(self),
+    };
 
-    if let Some(boost_force) = ability_system.boost_force {
-      rigid_body_set[self.player_handle].apply_impulse(boost_force * player_mass, true);
-    }
+    let (frame, stage_timings) = PHYSICS_STAGES.iter().fold(
+      (initial_frame, Vec::with_capacity(PHYSICS_STAGES.len())),
+      |(frame, mut stage_timings), (name, stage)| {
+        let started = Instant::now();
+        let frame = stage(frame, &inputs);
+        stage_timings.push((*name, started.elapsed().as_secs_f32() * 1000.0));
+        (frame, stage_timings)
+      },
+    );
 
-    /* MARK: Gravity source behavior */
-    entities.iter().for_each(|(handle, entity)| {
-      if let Some(gravity_source) = entity.components.get::<GravitySource>()
-        && let EntityHandle::Collider(collider_handle) = handle
-      {
-        let strength = if let Some(target_activator_id) = gravity_source.activator_id
-          && let Some((_, entity)) = entities.iter().find(|(_, entity)| {
-            if let Some(id) = entity.components.get::<Id>()
-              && id.id == target_activator_id
-            {
-              true
-            } else {
-              false
-            }
-          })
-          && let Some(activator) = entity.components.get::<Activator>()
-        {
-          activator.activation
-        } else {
-          1.0
-        } * gravity_source.strength;
+    /* MARK: Fade out before loading the room the player walked into */
+    let room_exit = frame
+      .load_new_map
+      .as_ref()
+      .map(|(map_name, target_player_spawn_id)| RoomExit {
+        map_name: map_name.clone(),
+        target_player_spawn_id: *target_player_spawn_id,
+        velocity: *frame.rigid_body_set[self.player_handle].linvel(),
+        frames_left: ROOM_FADE_FRAMES,
+      });
 
-        narrow_phase
-          .intersection_pairs_with(*collider_handle)
-          .filter_map(|(collider1, collider2, colliding)| {
-            if colliding {
-              [collider1, collider2]
-                .iter()
-                .find(|other_handle| **other_handle != *collider_handle)
-                .cloned()
-            } else {
-              None
-            }
-          })
-          .for_each(|other_handle| {
-            let distance_vec = collider_set[*collider_handle].translation()
-              - collider_set[other_handle].translation();
+    Rc::new(Self {
+      stage_timings: Rc::new(stage_timings),
+      load_new_map: None,
+      room_exit,
+      ..self.with_frame(frame, controls_system.time_scale, rewind_snapshots)
+    })
+  }
+}
 
-            let distance_squared = distance_vec.magnitude_squared();
-            let gravity_intensity = strength / distance_squared;
+/* MARK: Stages */
 
-            if let Some(rigid_body_handle) = collider_set[other_handle].parent() {
-              rigid_body_set[rigid_body_handle]
-                .apply_impulse(distance_vec * gravity_intensity, true);
-            }
-          });
-      }
-    });
+/* Everything the stages read from other systems, gathered up front so a stage
+can be run without a ProcessContext */
+pub struct FrameInputs<'a> {
+  pub frame_count: i64,
+  pub simulated_frames: f64,
+  /* Per frame forces and float counters are scaled by this */
+  pub time_scale: f32,
+  /* Whether counters kept in whole frames advance this frame */
+  pub whole_frame: bool,
+  pub player_handle: RigidBodyHandle,
+  pub map_name: &'a str,
+  pub left_stick: PhysicsVector,
+  pub coop_gamepad_claimed: bool,
+  pub coop_left_stick: PhysicsVector,
+  /* Health the partner had when the game was saved, used when they first join */
+  pub saved_player_two_health: Option<f32>,
+  pub boost_force: Option<Vector<f32>>,
+  /* Pressed this frame, mounting and leaving turrets */
+  pub interact: bool,
+  pub dismount: bool,
+  pub projectiles: &'a [Projectile],
+  pub player_two_projectiles: &'a [Projectile],
+  pub drone_specs: &'a [DroneSpec],
+  pub enemy_decisions: &'a [EnemyDecision],
+  pub enemy_definitions: &'a EnemyDefinitions,
+  pub entity_limits: EntityLimits,
+  pub movement: MovementStats,
+  pub difficulty: Difficulty,
+  pub chain_to_mount_point: Option<RigidBodyHandle>,
+  pub kill_chain: bool,
+  pub magnetize: bool,
+  /* The enemy a hack was completed on this frame */
+  pub hack: Option<EntityHandle>,
+  /* Only ever applied to the players */
+  pub assist: Assist,
+  pub physics_pipeline: &'a RefCell<PhysicsPipeline>,
+  pub integration_parameters: IntegrationParameters,
+  pub cycle_phase: Option<&'a CyclePhase>,
+  /* Enemy fire hurts other enemies in this room */
+  pub friendly_fire: bool,
+  /* Streamed rooms in range of the player, positioned relative to this one */
+  pub chunks: Chunks,
+}
 
-    /* MARK: Fire all weapons */
-    let new_projectiles = combat_system
-      .new_projectiles
-      .iter()
-      .map(|projectile| {
-        let handle = rigid_body_set.insert(RigidBodyBuilder::dynamic().translation(
-          *rigid_body_set[self.player_handle].translation() + projectile.offset.into_vec(),
-        ));
-        collider_set.insert_with_parent(projectile.collider.clone(), handle, rigid_body_set);
+impl FrameInputs<'_> {
+  /* How far counters kept in whole frames move this frame */
+  fn whole_frames(&self) -> i32 {
+    i32::from(self.whole_frame)
+  }
+}
+
+/* The world and this frame's results as they are handed from stage to stage */
+pub struct FrameState {
+  pub rigid_body_set: RigidBodySet,
+  pub collider_set: ColliderSet,
+  pub island_manager: IslandManager,
+  pub broad_phase: DefaultBroadPhase,
+  pub narrow_phase: NarrowPhase,
+  pub impulse_joint_set: ImpulseJointSet,
+  pub multibody_joint_set: MultibodyJointSet,
+  pub ccd_solver: CCDSolver,
+  pub entities: Entities,
+  pub player_two_handle: Option<RigidBodyHandle>,
+  pub player_two_joined: bool,
+  pub new_weapon_modules: List<(Option<i32>, WeaponModuleKind)>,
+  pub new_abilities: List<MapAbilityType>,
+  pub new_logs: List<String>,
+  pub new_engine_upgrades: List<String>,
+  pub new_credits: i32,
+  pub new_power_ups: List<PowerUpKind>,
+  pub new_kills: i32,
+  pub encounter_cleared: bool,
+  pub load_new_map: Option<(String, i32)>,
+  pub save_point_contact: Option<i32>,
+  pub mount_points_in_range: List<RigidBodyHandle>,
+  pub mounted_turret: Option<EntityHandle>,
+  pub turret_in_range: bool,
+  pub chain_tension: f32,
+  pub chain_durability: f32,
+  pub chain_broke: bool,
+  pub map_states: Rc<HashTrieMap<String, MapState>>,
+  pub streamed_chunks: Rc<HashTrieMap<String, StreamedChunk>>,
+}
+
+impl FrameState {
+  pub fn from_system(physics_system: &PhysicsSystem) -> Self {
+    Self {
+      rigid_body_set: physics_system.rigid_body_set.clone(),
+      collider_set: physics_system.collider_set.clone(),
+      island_manager: physics_system.island_manager.clone(),
+      broad_phase: physics_system.broad_phase.clone(),
+      narrow_phase: physics_system.narrow_phase.clone(),
+      impulse_joint_set: physics_system.impulse_joint_set.clone(),
+      multibody_joint_set: physics_system.multibody_joint_set.clone(),
+      ccd_solver: physics_system.ccd_solver.clone(),
+      entities: physics_system.entities.clone(),
+      player_two_handle: physics_system.player_two_handle,
+      player_two_joined: physics_system.player_two_joined,
+      new_weapon_modules: list![],
+      new_abilities: list![],
+      new_logs: list![],
+      new_engine_upgrades: list![],
+      new_credits: 0,
+      new_power_ups: list![],
+      new_kills: 0,
+      encounter_cleared: false,
+      load_new_map: None,
+      save_point_contact: None,
+      mount_points_in_range: list![],
+      mounted_turret: physics_system.mounted_turret,
+      turret_in_range: false,
+      chain_tension: 0.0,
+      chain_durability: physics_system.chain_durability,
+      chain_broke: false,
+      map_states: Rc::clone(&physics_system.map_states),
+      streamed_chunks: Rc::clone(&physics_system.streamed_chunks),
+    }
+  }
 
-        let rbs_clone = rigid_body_set.clone();
-        let player_velocity = rbs_clone[self.player_handle].linvel();
-        rigid_body_set[handle].set_linvel(*player_velocity, true);
+  /* Picks the frame up from a rewind snapshot instead of where the last frame left off */
+  fn from_snapshot(physics_system: &PhysicsSystem, snapshot: &RewindSnapshot) -> Self {
+    Self {
+      rigid_body_set: snapshot.rigid_body_set.clone(),
+      collider_set: snapshot.collider_set.clone(),
+      island_manager: snapshot.island_manager.clone(),
+      broad_phase: snapshot.broad_phase.clone(),
+      narrow_phase: snapshot.narrow_phase.clone(),
+      impulse_joint_set: snapshot.impulse_joint_set.clone(),
+      multibody_joint_set: snapshot.multibody_joint_set.clone(),
+      ccd_solver: snapshot.ccd_solver.clone(),
+      entities: snapshot.entities.clone(),
+      player_two_handle: snapshot.player_two_handle,
+      map_states: Rc::clone(&snapshot.map_states),
+      streamed_chunks: Rc::clone(&snapshot.streamed_chunks),
+      ..Self::from_system(physics_system)
+    }
+  }
 
-        rigid_body_set[handle].apply_impulse(projectile.initial_impulse.into_vec(), true);
-        rigid_body_set[handle].add_force(
-          projectile.initial_impulse.into_vec().normalize() * projectile.force_mod,
+  /* Takes an entity's body and colliders out of the world */
+  fn remove_body(&mut self, handle: EntityHandle) {
+    match handle {
+      EntityHandle::RigidBody(rigid_body_handle) => {
+        self.rigid_body_set.remove(
+          rigid_body_handle,
+          &mut self.island_manager,
+          &mut self.collider_set,
+          &mut self.impulse_joint_set,
+          &mut self.multibody_joint_set,
+          true,
+        );
+      }
+      EntityHandle::Collider(collider_handle) => {
+        self.collider_set.remove(
+          collider_handle,
+          &mut self.island_manager,
+          &mut self.rigid_body_set,
           true,
         );
+      }
+    }
+  }
+}
 
-        let handle = EntityHandle::RigidBody(handle);
+pub type PhysicsStage = fn(FrameState, &FrameInputs) -> FrameState;
+
+/* Run in this order every frame. Each stage only sees what earlier stages and
+the previous frame produced, so a new gameplay pass is a function slotted in here */
+pub const PHYSICS_STAGES: &[(&str, PhysicsStage)] = &[
+  ("stream", stream_chunks),
+  ("status", tick_status_effects),
+  ("hacks", run_hacks),
+  ("hazards", apply_hazards),
+  ("magnet", update_magnet),
+  ("players", move_players),
+  ("gravity", apply_gravity_sources),
+  ("fluids", apply_fluid_volumes),
+  ("turrets", operate_turrets),
+  ("spawn", spawn_player_projectiles),
+  ("ai-apply", apply_enemy_decisions),
+  ("deflect", deflect_with_chain),
+  ("damage", resolve_damage),
+  ("pickups", collect_pickups),
+  ("chain", update_chain),
+  ("activation", calculate_activation),
+  ("encounters", run_encounters),
+  ("caps", enforce_entity_caps),
+  ("cleanup", clean_up_destroyed),
+  ("step", step_physics),
+];
+
+/* Brings streamed rooms that came into range into the world and takes out the ones that left it.
+Their kills aren't remembered, so a room's enemies are back once it has streamed out and in again */
+fn stream_chunks(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  /* MARK: Remove chunks out of range */
+  let (streamed_chunks, entities) = frame.streamed_chunks.clone().iter().fold(
+    (Rc::clone(&frame.streamed_chunks), frame.entities.clone()),
+    |(streamed_chunks, entities), (map_name, streamed_chunk)| {
+      if inputs.chunks.contains_key(map_name) {
+        return (streamed_chunks, entities);
+      }
+      frame.collider_set.remove(
+        streamed_chunk.walls,
+        &mut frame.island_manager,
+        &mut frame.rigid_body_set,
+        true,
+      );
+      /* Entities killed since the chunk streamed in are already gone */
+      let entities = streamed_chunk
+        .entities
+        .iter()
+        .fold(entities, |entities, handle| {
+          if !entities.contains_key(handle) {
+            return entities;
+          }
+          frame.remove_body(*handle);
+          entities.remove(handle)
+        });
+      (Rc::new(streamed_chunks.remove(map_name)), entities)
+    },
+  );
 
-        (
-          handle,
-          Rc::new(Entity {
-            handle,
-            components: projectile
-              .component_set
-              .insert(DestroyOnCollision)
-              .insert(Damager {
-                damage: projectile.damage,
-              }),
-            label: "p".to_string(),
-          }),
-        )
-      })
-      .collect::<HashTrieMap<_, _>>();
+  /* MARK: Add chunks that came into range */
+  let (streamed_chunks, entities) = inputs.chunks.iter().fold(
+    (streamed_chunks, entities),
+    |(streamed_chunks, entities), (map_name, chunk)| {
+      if streamed_chunks.contains_key(map_name) {
+        return (streamed_chunks, entities);
+      }
+      let map_state = frame.map_states.get(map_name).cloned().unwrap_or_default();
+      let walls = frame
+        .collider_set
+        .insert(static_walls(&chunk.map, &map_state, chunk.offset));
+      let enemies = spawn_enemies(
+        &EnemySpawns {
+          map: &chunk.map,
+          map_name,
+          map_state: &map_state,
+          offset: chunk.offset,
+          enemy_definitions: inputs.enemy_definitions,
+          difficulty: inputs.difficulty,
+        },
+        &mut frame.rigid_body_set,
+        &mut frame.collider_set,
+      );
+      let map_transitions =
+        spawn_map_transitions(&chunk.map, chunk.offset, &mut frame.collider_set);
+      let new_entities = enemies
+        .into_iter()
+        .map(|entity| Entity {
+          components: entity.components.without::<Persistent>(),
+          ..entity
+        })
+        .chain(map_transitions)
+        .collect::<Vec<_>>();
+      let streamed_chunk = StreamedChunk {
+        walls,
+        entities: new_entities.iter().map(|entity| entity.handle).collect(),
+      };
+      let entities = new_entities.into_iter().fold(entities, |entities, entity| {
+        entities.insert(entity.handle, Arc::new(entity))
+      });
+      (
+        Rc::new(streamed_chunks.insert(map_name.clone(), streamed_chunk)),
+        entities,
+      )
+    },
+  );
 
-    let entities = entities.into_iter().chain(new_projectiles.iter());
+  FrameState {
+    entities,
+    streamed_chunks,
+    ..frame
+  }
+}
 
-    /* MARK: Carry out enemy behavior */
-    let enemy_system = ctx.get::<EnemySystem>().unwrap();
+fn tick_status_effects(frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  /* The inputs hold the pipeline's RefCell, so only what the pass reads goes to the threads */
+  let (time_scale, whole_frame) = (inputs.time_scale, inputs.whole_frame);
+  let entities = update_components(&frame.entities, |entity| {
+    let status_effects = entity.components.get::<StatusEffects>();
+    let elite = entity.components.get::<Elite>();
+    if status_effects.is_none() && elite.is_none() {
+      return None;
+    }
 
-    let entities = entities
-      .flat_map(|(_, entity)| {
-        let relevant_decision = enemy_system
-          .decisions
-          .iter()
-          .find(|&decision| EntityHandle::RigidBody(decision.handle) == entity.handle);
-        if relevant_decision.is_none() {
-          return vec![(entity.handle, entity.clone())];
-        }
-        let relevant_decision = relevant_decision.unwrap();
+    /* Regenerating elites heal against whatever is burning them */
+    let damage_per_frame = (status_effects
+      .as_ref()
+      .map_or(0.0, |status_effects| status_effects.damage_per_frame())
+      - elite.map_or(0.0, |elite| elite.regeneration_per_frame()))
+      * time_scale;
 
-        if let EntityHandle::RigidBody(rigid_body_handle) = entity.handle {
-          rigid_body_set[rigid_body_handle].apply_impulse(relevant_decision.movement_force, true);
-        }
+    let components = match status_effects {
+      Some(status_effects) if whole_frame => entity.components.with(status_effects.tick()),
+      _ => entity.components.clone(),
+    };
+    Some(match entity.components.get::<Damageable>() {
+      Some(damageable) if damage_per_frame != 0.0 => components.with(Damageable {
+        health: (damageable.health - damage_per_frame).min(damageable.max_health),
+        ..*damageable
+      }),
+      _ => components,
+    })
+  });
 
-        let new_projectiles = if let EntityHandle::RigidBody(rigid_body_handle) = entity.handle {
-          relevant_decision
-            .projectiles
-            .iter()
-            .map(|projectile| {
-              let handle = rigid_body_set.insert(RigidBodyBuilder::dynamic().translation(
-                *rigid_body_set[rigid_body_handle].translation() + projectile.offset.into_vec(),
-              ));
-              collider_set.insert_with_parent(projectile.collider.clone(), handle, rigid_body_set);
-
-              let rbs_clone = rigid_body_set.clone();
-              let enemy_velocity = rbs_clone[rigid_body_handle].linvel();
-              rigid_body_set[handle].set_linvel(*enemy_velocity, true);
-
-              rigid_body_set[handle].apply_impulse(projectile.initial_impulse.into_vec(), true);
-
-              (
-                EntityHandle::RigidBody(handle),
-                Rc::new(Entity {
-                  handle: EntityHandle::RigidBody(handle),
-                  components: ComponentSet::new()
-                    .insert(DestroyOnCollision)
-                    .insert(Damager {
-                      damage: projectile.damage,
-                    }),
-                  label: "ep".to_string(),
-                }),
-              )
-            })
-            .collect::<HashMap<_, _>>()
-        } else {
-          HashMap::new()
-        };
+  FrameState { entities, ..frame }
+}
 
-        [(
-          entity.handle,
-          Rc::new(Entity {
-            components: entity.components.with(relevant_decision.enemy.clone()),
-            ..entity.as_ref().clone()
-          }),
-        )]
-        .into_iter()
-        .chain(new_projectiles)
-        .chain(
-          relevant_decision
-            .enemies_to_spawn
-            .iter()
-            .map(|enemy_to_spawn| {
-              let handle = rigid_body_set.insert(enemy_to_spawn.enemy_spawn.rigid_body.clone());
-              collider_set.insert_with_parent(
-                enemy_to_spawn.enemy_spawn.collider.clone(),
-                handle,
-                rigid_body_set,
-              );
-              rigid_body_set[handle].apply_impulse(enemy_to_spawn.initial_force, true);
-              (
-                EntityHandle::RigidBody(handle),
-                Rc::new(Entity {
-                  handle: EntityHandle::RigidBody(handle),
-                  components: enemy_to_spawn.enemy_spawn.into_entity_components(),
-                  label: "child enemy".to_string(),
-                }),
-              )
-            }),
-        )
-        .collect()
+/* Frames a hacked enemy fights for the player before it self-destructs */
+const HACK_DURATION_FRAMES: i32 = 600;
+const HACK_SELF_DESTRUCT_RADIUS: f32 = 2.0;
+const HACK_SELF_DESTRUCT_STRENGTH: f32 = -0.5;
+const HACK_SELF_DESTRUCT_DAMAGE: f32 = 20.0;
+
+const BARREL_HEALTH: f32 = 10.0;
+const BARREL_EXPLOSION_RADIUS: f32 = 2.5;
+const BARREL_EXPLOSION_STRENGTH: f32 = -1.0;
+const BARREL_EXPLOSION_DAMAGE: f32 = 30.0;
+
+/* Explodes on being destroyed rather than on contact, so it takes shooting down or a
+neighbour's blast to set one off */
+fn spawn_explosive_barrel(
+  barrel: &ExplosiveBarrel,
+  collider_set: &mut ColliderSet,
+  rigid_body_set: &mut RigidBodySet,
+) -> Entity {
+  let rigid_body_handle =
+    rigid_body_set.insert(RigidBodyBuilder::fixed().translation(barrel.translation));
+  collider_set.insert_with_parent(barrel.collider.clone(), rigid_body_handle, rigid_body_set);
+
+  Entity {
+    handle: EntityHandle::RigidBody(rigid_body_handle),
+    components: ComponentSet::new()
+      .insert(Id { id: barrel.id })
+      .insert(Damageable {
+        health: BARREL_HEALTH,
+        max_health: BARREL_HEALTH,
+        destroy_on_zero_health: true,
+        current_hitstun: 0.0,
+        max_hitstun: 0.0,
+      })
+      .insert(ExplodeOnDeath {
+        explosion: ExplodeOnCollision {
+          strength: BARREL_EXPLOSION_STRENGTH,
+          radius: BARREL_EXPLOSION_RADIUS,
+          damage: BARREL_EXPLOSION_DAMAGE,
+          interaction_groups: groups(CollisionLayer::Blast),
+          force_groups: groups(CollisionLayer::Blast),
+          self_blast: false,
+        },
+      })
+      .insert(EntityKind::ExplosiveBarrel),
+  }
+}
+
+fn run_hacks(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  /* MARK: Turn the enemy a hack was just completed on */
+  let entities = match inputs
+    .hack
+    .and_then(|handle| Some((handle, frame.entities.get(&handle)?)))
+  {
+    Some((handle, entity)) => {
+      /* It stops body blocking the player now that they're on the same side */
+      if let EntityHandle::RigidBody(body_handle) = handle
+        && let Some(body) = frame.rigid_body_set.get(body_handle)
+      {
+        body
+          .colliders()
+          .to_vec()
+          .into_iter()
+          .for_each(|collider_handle| {
+            pass_through_allies(&mut frame.collider_set[collider_handle], Faction::Player)
+          });
+      }
+
+      let components = entity
+        .components
+        .with(Faction::Player)
+        .with(Hacked {
+          frames_left: HACK_DURATION_FRAMES,
+          frames: HACK_DURATION_FRAMES,
+        })
+        .with(ExplodeOnDeath {
+          explosion: ExplodeOnCollision {
+            strength: HACK_SELF_DESTRUCT_STRENGTH,
+            radius: HACK_SELF_DESTRUCT_RADIUS,
+            damage: HACK_SELF_DESTRUCT_DAMAGE,
+            interaction_groups: groups(CollisionLayer::Explosion),
+            force_groups: groups(CollisionLayer::Explosion),
+            self_blast: false,
+          },
+        });
+      frame.entities.insert(
+        handle,
+        Arc::new(Entity {
+          components,
+          ..entity.as_ref().clone()
+        }),
+      )
+    }
+    None => frame.entities.clone(),
+  };
+
+  /* MARK: Count down hacked enemies, self-destructing the ones that run out */
+  let whole_frames = inputs.whole_frames();
+  let entities = update_components(&entities, |entity| {
+    let frames_left = entity.components.get::<Hacked>()?.frames_left - whole_frames;
+    let components = entity.components.update(|hacked: &Hacked| Hacked {
+      frames_left,
+      ..*hacked
+    });
+
+    Some(if frames_left <= 0 {
+      components.with(Destroyed)
+    } else {
+      components
+    })
+  });
+
+  FrameState { entities, ..frame }
+}
+
+fn apply_hazards(frame: FrameState, _: &FrameInputs) -> FrameState {
+  let rigid_body_set = &frame.rigid_body_set;
+  let narrow_phase = &frame.narrow_phase;
+
+  let hazards = frame
+    .entities
+    .iter()
+    .filter_map(|(handle, entity)| {
+      let hazard = entity.components.get::<Hazard>()?;
+      let EntityHandle::Collider(collider_handle) = handle else {
+        return None;
+      };
+      Some((*collider_handle, hazard.kind))
+    })
+    .collect::<Vec<_>>();
+
+  if hazards.is_empty() {
+    return frame;
+  }
+
+  /* MARK: Keep the hazard's status on anything damageable inside of it */
+  let entities = update_components(&frame.entities, |entity| {
+    entity.components.get::<Damageable>()?;
+
+    let entity_colliders = entity.handle.colliders(rigid_body_set);
+    if entity.components.get::<SpawnProtection>().is_some() {
+      return None;
+    }
+
+    let statuses = hazards
+      .iter()
+      .filter(|(hazard_collider, _)| {
+        entity_colliders.iter().any(|&entity_collider| {
+          narrow_phase
+            .intersection_pair(*hazard_collider, *entity_collider)
+            .unwrap_or(false)
+        })
       })
-      .collect::<HashTrieMap<_, _>>();
+      .map(|(_, kind)| kind.status())
+      .collect::<Vec<_>>();
+
+    if statuses.is_empty() {
+      return None;
+    }
+
+    let status_effects = entity
+      .components
+      .get::<StatusEffects>()
+      .map(|status_effects| status_effects.as_ref().clone())
+      .unwrap_or_default();
 
-    /* MARK: Spawn explosions for entities marked as explode on collision */
-    let entities = entities
+    let status_effects = statuses
       .iter()
-      .flat_map(|(handle, entity)| {
-        if let Some(explode_on_collision) = entity.components.get::<ExplodeOnCollision>()
-          && handle
-            .colliders(rigid_body_set)
-            .iter()
-            .any(|&&collider_handle| {
-              let collider = &collider_set[collider_handle];
+      .fold(status_effects, |status_effects, status| {
+        status_effects.apply(status)
+      });
 
-              if collider.is_sensor() {
-                narrow_phase
-                  .intersection_pairs_with(collider_handle)
-                  .any(|(_, _, is_intersecting)| is_intersecting)
-              } else {
-                narrow_phase
-                  .contact_pairs_with(collider_handle)
-                  .any(|contact_pair| contact_pair.has_any_active_contact)
-              }
-            })
-        {
-          let explosion = spawn_explosion(
-            *handle.translation(rigid_body_set, &collider_set),
-            explode_on_collision.as_ref(),
-            &mut collider_set,
-            rigid_body_set,
-          );
+    Some(if entity.components.get::<StatusEffects>().is_some() {
+      entity.components.with(status_effects)
+    } else {
+      entity.components.insert(status_effects)
+    })
+  });
 
-          vec![
-            (*handle, Rc::clone(entity)),
-            (explosion.handle, explosion.into()),
-          ]
+  FrameState { entities, ..frame }
+}
+
+fn move_players(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  /* MARK: Join or drop the co-op partner */
+  let player_two_down = frame.player_two_handle.is_some_and(|player_two_handle| {
+    frame
+      .entities
+      .get(&EntityHandle::RigidBody(player_two_handle))
+      .and_then(|entity| entity.components.get::<Damageable>())
+      .is_some_and(|damageable| damageable.health <= 0.0)
+  });
+
+  let (entities, player_two_handle, player_two_joined) =
+    match (frame.player_two_handle, inputs.coop_gamepad_claimed) {
+      (Some(player_two_handle), true) if !player_two_down => {
+        (frame.entities, Some(player_two_handle), true)
+      }
+      (Some(player_two_handle), coop_gamepad_claimed) => {
+        frame.rigid_body_set.remove(
+          player_two_handle,
+          &mut frame.island_manager,
+          &mut frame.collider_set,
+          &mut frame.impulse_joint_set,
+          &mut frame.multibody_joint_set,
+          true,
+        );
+        (
+          frame
+            .entities
+            .remove(&EntityHandle::RigidBody(player_two_handle)),
+          None,
+          coop_gamepad_claimed,
+        )
+      }
+      (None, true) if !frame.player_two_joined => {
+        let player_max_health = frame
+          .entities
+          .get(&EntityHandle::RigidBody(inputs.player_handle))
+          .and_then(|entity| entity.components.get::<Damageable>())
+          .unwrap()
+          .max_health;
+
+        let (player_two_handle, player_two) = spawn_player(
+          frame.rigid_body_set[inputs.player_handle].translation() + PLAYER_TWO_OFFSET,
+          inputs.saved_player_two_health.unwrap_or(player_max_health),
+          player_max_health,
+          "player two",
+          &mut frame.collider_set,
+          &mut frame.rigid_body_set,
+        );
+        /* Beside player one could be inside of a wall */
+        let translation = clear_spawn_translation(
+          player_two_handle,
+          &frame.rigid_body_set,
+          &frame.collider_set,
+        );
+        frame.rigid_body_set[player_two_handle].set_translation(translation, true);
+        (
+          frame
+            .entities
+            .insert(player_two.handle, Arc::new(player_two)),
+          Some(player_two_handle),
+          true,
+        )
+      }
+      (None, coop_gamepad_claimed) => (
+        frame.entities,
+        None,
+        frame.player_two_joined && coop_gamepad_claimed,
+      ),
+    };
+
+  let rigid_body_set = &mut frame.rigid_body_set;
+
+  let movement_mod = |handle: RigidBodyHandle| {
+    entities
+      .get(&EntityHandle::RigidBody(handle))
+      .and_then(|entity| entity.components.get::<StatusEffects>())
+      .map(|status_effects| status_effects.movement_mod())
+      .unwrap_or(1.0)
+      * fluid_volumes_around(&entities, &frame.narrow_phase, &frame.collider_set, handle)
+        .iter()
+        .map(|fluid_volume| fluid_volume.movement_scale)
+        .product::<f32>()
+  };
+
+  /* MARK: Move the player, relative to the surface while magnetized */
+  let left_stick = match entities
+    .values()
+    .find_map(|entity| entity.components.get::<MagnetLatch>())
+  {
+    Some(latch) => {
+      let tangent = vector![latch.normal.y, -latch.normal.x];
+      PhysicsVector::from_vec(
+        tangent * inputs.left_stick.x() + latch.normal * inputs.left_stick.y(),
+      )
+    }
+    None => inputs.left_stick,
+  };
+
+  let next_player_impulse = player_movement_impulse(
+    left_stick,
+    &rigid_body_set[inputs.player_handle],
+    &inputs.movement,
+  ) * movement_mod(inputs.player_handle)
+    * inputs.time_scale;
+
+  rigid_body_set[inputs.player_handle].set_linear_damping(inputs.movement.linear_damping);
+  rigid_body_set[inputs.player_handle].apply_impulse(next_player_impulse, true);
+
+  if let Some(player_two_handle) = player_two_handle {
+    let next_player_two_impulse = player_movement_impulse(
+      inputs.coop_left_stick,
+      &rigid_body_set[player_two_handle],
+      &inputs.movement,
+    ) * movement_mod(player_two_handle)
+      * inputs.time_scale;
+
+    rigid_body_set[player_two_handle].set_linear_damping(inputs.movement.linear_damping);
+    rigid_body_set[player_two_handle].apply_impulse(next_player_two_impulse, true);
+  }
+
+  /* MARK: Perform boost */
+  let player_mass = rigid_body_set[inputs.player_handle].mass();
+
+  if let Some(boost_force) = inputs.boost_force {
+    rigid_body_set[inputs.player_handle].apply_impulse(boost_force * player_mass, true);
+  }
+
+  FrameState {
+    entities,
+    player_two_handle,
+    player_two_joined,
+    ..frame
+  }
+}
+
+/* Firing from a turret is up to combat, here the player is only held on top of it. Leaving
+is on cancel, or when the turret is no longer there */
+fn operate_turrets(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  let turret_in_range = frame.entities.iter().find_map(|(handle, entity)| {
+    entity.components.get::<MountedTurret>()?;
+    let EntityHandle::Collider(turret_collider) = handle else {
+      return None;
+    };
+
+    frame
+      .narrow_phase
+      .intersection_pairs_with(*turret_collider)
+      .any(|(collider1, collider2, colliding)| {
+        let other_handle = if collider1 == *turret_collider {
+          collider2
         } else {
-          vec![(*handle, Rc::clone(entity))]
-        }
+          collider1
+        };
+        colliding && frame.collider_set[other_handle].parent() == Some(inputs.player_handle)
       })
-      .collect::<HashTrieMap<_, _>>();
+      .then_some(*handle)
+  });
 
-    /* MARK: Damage all entities colliding with damagers */
-    let entities = entities.iter().map(map_damageable_damage_taken(
-      rigid_body_set,
-      &narrow_phase,
-      &collider_set,
-      &entities,
-    ));
+  let mounted_turret = match frame.mounted_turret {
+    Some(handle) if inputs.dismount || !frame.entities.contains_key(&handle) => None,
+    None if inputs.interact => turret_in_range,
+    mounted_turret => mounted_turret,
+  };
 
-    /* MARK: Destroy all marked to be destroyed on this frame */
-    let entities = entities.map(|(handle, entity)| {
-      if let Some(destroy_after_frames) = entity.components.get::<DestroyAfterFrames>() {
-        if destroy_after_frames.frames > 0 {
-          (
-            handle,
-            Rc::new(Entity {
-              components: entity.components.with(DestroyAfterFrames {
-                frames: destroy_after_frames.frames - 1,
-              }),
-              ..entity.as_ref().clone()
-            }),
-          )
-        } else {
-          (
-            handle,
-            Rc::new(Entity {
-              components: entity.components.with(Destroyed),
-              ..entity.as_ref().clone()
-            }),
-          )
-        }
-      } else {
-        (handle, entity)
-      }
+  if let Some(handle) = mounted_turret {
+    let translation = *handle.translation(&frame.rigid_body_set, &frame.collider_set);
+    let player_body = &mut frame.rigid_body_set[inputs.player_handle];
+    player_body.set_translation(translation, true);
+    player_body.set_linvel(vector![0.0, 0.0], true);
+    player_body.reset_forces(true);
+  }
+
+  FrameState {
+    mounted_turret,
+    turret_in_range: turret_in_range.is_some(),
+    ..frame
+  }
+}
+
+fn apply_gravity_sources(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  let cycle_multiplier = inputs
+    .cycle_phase
+    .map_or(1.0, |phase| phase.gravity_multiplier);
+
+  let entities = &frame.entities;
+  let collider_set = &frame.collider_set;
+  let rigid_body_set = &mut frame.rigid_body_set;
+
+  /* Explosions carry their pull on a body of their own, map sources are bare colliders */
+  let gravity_sources = entities
+    .iter()
+    .filter_map(|(handle, entity)| {
+      let gravity_source = entity.components.get::<GravitySource>()?;
+      let strength = gravity_activation(entities, gravity_source.activator_id)
+        * gravity_source.strength
+        * cycle_multiplier
+        * inputs.time_scale;
+
+      Some(
+        handle
+          .colliders(rigid_body_set)
+          .into_iter()
+          .map(move |&collider_handle| (collider_handle, strength))
+          .collect::<Vec<_>>(),
+      )
+    })
+    .flatten()
+    .collect::<Vec<_>>();
+
+  gravity_sources
+    .into_iter()
+    .for_each(|(collider_handle, strength)| {
+      let source_parent = collider_set[collider_handle].parent();
+
+      frame
+        .narrow_phase
+        .intersection_pairs_with(collider_handle)
+        .filter_map(|(collider1, collider2, colliding)| {
+          if colliding {
+            [collider1, collider2]
+              .iter()
+              .find(|other_handle| **other_handle != collider_handle)
+              .cloned()
+          } else {
+            None
+          }
+        })
+        .for_each(|other_handle| {
+          let distance_vec =
+            collider_set[collider_handle].translation() - collider_set[other_handle].translation();
+
+          let distance_squared = distance_vec.magnitude_squared();
+          let gravity_intensity = strength / distance_squared;
+
+          if let Some(rigid_body_handle) = collider_set[other_handle].parent()
+            && Some(rigid_body_handle) != source_parent
+          {
+            rigid_body_set[rigid_body_handle].apply_impulse(distance_vec * gravity_intensity, true);
+          }
+        });
     });
 
-    /* MARK: Destroy all entities with 0 health marked as such */
-    let entities = entities.map(|(handle, entity)| {
-      if let Some(damageable) = entity.components.get::<Damageable>()
-        && damageable.health <= 0.0
-      {
+  /* MARK: Zones push every body inside in one direction */
+  entities.iter().for_each(|(handle, entity)| {
+    if let Some(gravity_zone) = entity.components.get::<GravityZone>()
+      && let EntityHandle::Collider(collider_handle) = handle
+    {
+      let acceleration = gravity_zone.acceleration
+        * gravity_activation(entities, gravity_zone.activator_id)
+        * cycle_multiplier
+        * inputs.time_scale;
+
+      frame
+        .narrow_phase
+        .intersection_pairs_with(*collider_handle)
+        .filter_map(|(collider1, collider2, colliding)| {
+          let other_handle = if collider1 == *collider_handle {
+            collider2
+          } else {
+            collider1
+          };
+          if colliding {
+            collider_set[other_handle].parent()
+          } else {
+            None
+          }
+        })
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .for_each(|rigid_body_handle| {
+          let rigid_body = &mut rigid_body_set[rigid_body_handle];
+          let mass = rigid_body.mass();
+          rigid_body.apply_impulse(acceleration * mass, true);
+        });
+    }
+  });
+
+  frame
+}
+
+/* Full strength without an activator, otherwise scaled by its activation */
+fn gravity_activation(entities: &Entities, activator_id: Option<i32>) -> f32 {
+  let Some(target_activator_id) = activator_id else {
+    return 1.0;
+  };
+
+  entities
+    .iter()
+    .find(|(_, entity)| {
+      entity
+        .components
+        .get::<Id>()
+        .is_some_and(|id| id.id == target_activator_id)
+    })
+    .and_then(|(_, entity)| entity.components.get::<Activator>())
+    .map_or(1.0, |activator| activator.activation)
+}
+
+/* The closest point on any magnetic surface in range, with the normal towards `point` */
+fn nearest_magnetic_surface(
+  frame: &FrameState,
+  point: Vector<f32>,
+) -> Option<(ColliderHandle, Vector<f32>)> {
+  frame
+    .entities
+    .iter()
+    .filter(|(_, entity)| entity.components.get::<Magnetic>().is_some())
+    .flat_map(|(handle, _)| match handle {
+      EntityHandle::Collider(collider_handle) => vec![*collider_handle],
+      EntityHandle::RigidBody(rigid_body_handle) => frame.rigid_body_set[*rigid_body_handle]
+        .colliders()
+        .to_vec(),
+    })
+    .filter_map(|collider_handle| {
+      let collider = &frame.collider_set[collider_handle];
+      let projection = collider
+        .shape()
+        .project_point(collider.position(), &point.into(), true);
+      let offset = point - projection.point.coords;
+      let distance = offset.magnitude();
+
+      (!projection.is_inside && distance > 0.0 && distance <= MAGNET_RANGE)
+        .then(|| (collider_handle, offset / distance, distance))
+    })
+    .min_by(|(_, _, distance_a), (_, _, distance_b)| distance_a.total_cmp(distance_b))
+    .map(|(collider_handle, normal, _)| (collider_handle, normal))
+}
+
+/* While magnetized, a prismatic joint keeps the player at a fixed distance from the
+surface and only lets them slide along it */
+fn update_magnet(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  let latch = frame.entities.iter().find_map(|(&handle, entity)| {
+    entity
+      .components
+      .get::<MagnetLatch>()
+      .map(|latch| (handle, latch))
+  });
+
+  let player_translation = *frame.rigid_body_set[inputs.player_handle].translation();
+  let surface = if inputs.magnetize {
+    nearest_magnetic_surface(&frame, player_translation)
+  } else {
+    None
+  };
+
+  let keep_latch = match (&latch, surface) {
+    (Some((_, latch)), Some((surface, normal))) => {
+      latch.surface == surface && latch.normal.dot(&normal) >= MAGNET_RELATCH_ALIGNMENT
+    }
+    (None, None) => true,
+    _ => false,
+  };
+
+  if keep_latch {
+    return frame;
+  }
+
+  /* MARK: Let go of the old surface */
+  let entities = match latch
+    .as_ref()
+    .and_then(|(handle, _)| frame.entities.get(handle).map(|entity| (*handle, entity)))
+  {
+    Some((handle, entity)) => frame.entities.insert(
+      handle,
+      Arc::new(Entity {
+        components: entity.components.insert(Destroyed),
+        ..entity.as_ref().clone()
+      }),
+    ),
+    None => frame.entities.clone(),
+  };
+
+  /* MARK: Latch onto the new one */
+  let entities = match surface {
+    Some((surface, normal)) => {
+      let anchor_handle = frame
+        .rigid_body_set
+        .insert(RigidBodyBuilder::fixed().translation(player_translation));
+      let tangent = UnitVector::new_normalize(vector![normal.y, -normal.x]);
+
+      frame.impulse_joint_set.insert(
+        anchor_handle,
+        inputs.player_handle,
+        PrismaticJointBuilder::new(tangent),
+        true,
+      );
+
+      let handle = EntityHandle::RigidBody(anchor_handle);
+      entities.insert(
+        handle,
+        Arc::new(Entity {
+          handle,
+          components: ComponentSet::new()
+            .insert(MagnetLatch { surface, normal })
+            .insert(EntityKind::MagnetAnchor),
+        }),
+      )
+    }
+    None => entities,
+  };
+
+  FrameState { entities, ..frame }
+}
+
+/* Every fluid volume one of the body's colliders is inside of */
+fn fluid_volumes_around(
+  entities: &Entities,
+  narrow_phase: &NarrowPhase,
+  collider_set: &ColliderSet,
+  rigid_body_handle: RigidBodyHandle,
+) -> Vec<Arc<FluidVolume>> {
+  entities
+    .iter()
+    .filter_map(|(handle, entity)| {
+      let fluid_volume = entity.components.get::<FluidVolume>()?;
+      let EntityHandle::Collider(fluid_collider) = handle else {
+        return None;
+      };
+
+      narrow_phase
+        .intersection_pairs_with(*fluid_collider)
+        .any(|(collider1, collider2, colliding)| {
+          let other_handle = if collider1 == *fluid_collider {
+            collider2
+          } else {
+            collider1
+          };
+          colliding && collider_set[other_handle].parent() == Some(rigid_body_handle)
+        })
+        .then_some(fluid_volume)
+    })
+    .collect()
+}
+
+/* Drag and buoyancy act on top of the global gravity vector, which stays as is */
+fn apply_fluid_volumes(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  let collider_set = &frame.collider_set;
+  let rigid_body_set = &mut frame.rigid_body_set;
+
+  let bodies_inside = frame
+    .entities
+    .iter()
+    .filter_map(|(handle, entity)| {
+      entity.components.get::<FluidVolume>()?;
+      let EntityHandle::Collider(fluid_collider) = handle else {
+        return None;
+      };
+      Some(*fluid_collider)
+    })
+    .flat_map(|fluid_collider| {
+      frame
+        .narrow_phase
+        .intersection_pairs_with(fluid_collider)
+        .filter_map(|(collider1, collider2, colliding)| {
+          let other_handle = if collider1 == fluid_collider {
+            collider2
+          } else {
+            collider1
+          };
+          if colliding {
+            collider_set[other_handle].parent()
+          } else {
+            None
+          }
+        })
+        .collect::<Vec<_>>()
+    })
+    .collect::<HashSet<_>>();
+
+  /* MARK: Slow and lift each body once, however many volumes overlap it */
+  bodies_inside.into_iter().for_each(|rigid_body_handle| {
+    let fluid_volumes = fluid_volumes_around(
+      &frame.entities,
+      &frame.narrow_phase,
+      collider_set,
+      rigid_body_handle,
+    );
+
+    let rigid_body = &mut rigid_body_set[rigid_body_handle];
+    if !rigid_body.is_dynamic() {
+      return;
+    }
+
+    /* Velocity kept over a slowed down frame compounds the same as over a whole one */
+    let drag = fluid_volumes
+      .iter()
+      .fold(1.0_f32, |kept, fluid_volume| {
+        kept * (1.0 - fluid_volume.drag.clamp(0.0, 1.0))
+      })
+      .powf(inputs.time_scale);
+    let buoyancy = fluid_volumes
+      .iter()
+      .map(|fluid_volume| fluid_volume.buoyancy)
+      .sum::<f32>()
+      * inputs.time_scale;
+
+    let mass = rigid_body.mass();
+    let velocity_change = -rigid_body.linvel() * (1.0 - drag) + vector![0.0, buoyancy];
+
+    rigid_body.apply_impulse(velocity_change * mass, true);
+  });
+
+  frame
+}
+
+fn spawn_player_projectiles(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  let collider_set = &mut frame.collider_set;
+  let rigid_body_set = &mut frame.rigid_body_set;
+
+  /* MARK: Fire all weapons */
+  let player_translation = *rigid_body_set[inputs.player_handle].translation();
+  let player_velocity = *rigid_body_set[inputs.player_handle].linvel();
+
+  let new_projectiles = inputs
+    .projectiles
+    .iter()
+    .map(|projectile| {
+      spawn_player_projectile(
+        projectile,
+        player_translation + projectile.offset.into_vec(),
+        player_velocity,
+        inputs.frame_count,
+        collider_set,
+        rigid_body_set,
+      )
+    })
+    .collect::<Vec<_>>();
+
+  let new_player_two_projectiles = frame
+    .player_two_handle
+    .into_iter()
+    .flat_map(|player_two_handle| {
+      let player_two_translation = *rigid_body_set[player_two_handle].translation();
+      let player_two_velocity = *rigid_body_set[player_two_handle].linvel();
+
+      inputs
+        .player_two_projectiles
+        .iter()
+        .map(|projectile| {
+          spawn_player_projectile(
+            projectile,
+            player_two_translation + projectile.offset.into_vec(),
+            player_two_velocity,
+            inputs.frame_count,
+            collider_set,
+            rigid_body_set,
+          )
+        })
+        .collect::<Vec<_>>()
+    })
+    .collect::<Vec<_>>();
+
+  let entities = frame
+    .entities
+    .iter()
+    .map(|(&handle, entity)| (handle, Arc::clone(entity)))
+    .chain(new_projectiles)
+    .chain(new_player_two_projectiles)
+    .collect::<Vec<_>>();
+
+  /* MARK: Keep drones in orbit and fire at the nearest enemy */
+  let drone_specs = inputs.drone_specs;
+
+  let drone_orbit_translation = |index: usize| {
+    let angle = inputs.simulated_frames as f32 * DRONE_ORBIT_SPEED
+      + 2.0 * PI * index as f32 / drone_specs.len() as f32;
+    player_translation + vector![angle.cos(), angle.sin()] * DRONE_ORBIT_RADIUS
+  };
+
+  /* Drones leave hacked enemies alone */
+  let enemy_translations = entities
+    .iter()
+    .filter(|(_, entity)| {
+      entity.components.get::<Enemy>().is_some()
+        && Faction::Player.hostile_to(Faction::of(&entity.components), false)
+    })
+    .map(|(handle, _)| *handle.translation(rigid_body_set, collider_set))
+    .collect::<Vec<_>>();
+
+  let nearest_enemy = |translation: Vector<f32>| {
+    enemy_translations
+      .iter()
+      .map(|enemy_translation| enemy_translation - translation)
+      .filter(|offset| offset.magnitude() <= DRONE_RANGE)
+      .min_by(|a, b| a.magnitude().total_cmp(&b.magnitude()))
+  };
+
+  let existing_drone_indices = entities
+    .iter()
+    .filter_map(|(_, entity)| entity.components.get::<Drone>().map(|drone| drone.index))
+    .collect::<HashSet<_>>();
+
+  let new_drones = (0..drone_specs.len())
+    .filter(|index| !existing_drone_indices.contains(index))
+    .map(|index| {
+      spawn_drone(
+        index,
+        drone_orbit_translation(index),
+        collider_set,
+        rigid_body_set,
+      )
+    })
+    .collect::<Vec<_>>();
+
+  let entities = entities
+    .into_iter()
+    .flat_map(|(handle, entity)| {
+      let Some(drone) = entity.components.get::<Drone>() else {
+        return vec![(handle, entity)];
+      };
+      let EntityHandle::RigidBody(rigid_body_handle) = handle else {
+        return vec![(handle, entity)];
+      };
+
+      /* Drones past the number currently equipped have been unequipped */
+      let Some(drone_spec) = drone_specs.get(drone.index) else {
+        return vec![(
+          handle,
+          Arc::new(Entity {
+            components: entity.components.with(Destroyed),
+            ..entity.as_ref().clone()
+          }),
+        )];
+      };
+
+      let translation = *rigid_body_set[rigid_body_handle].translation();
+      rigid_body_set[rigid_body_handle]
+        .set_next_kinematic_translation(drone_orbit_translation(drone.index));
+
+      if drone.cooldown <= 0.0
+        && let Some(enemy_offset) = nearest_enemy(translation)
+      {
+        let projectile = Projectile {
+          initial_impulse: PhysicsVector::from_vec(enemy_offset.normalize() * drone_spec.speed),
+          ..drone_spec.projectile.clone()
+        };
+
+        vec![
+          (
+            handle,
+            Arc::new(Entity {
+              components: entity.components.with(Drone {
+                cooldown: drone_spec.cooldown,
+                ..*drone
+              }),
+              ..entity.as_ref().clone()
+            }),
+          ),
+          spawn_player_projectile(
+            &projectile,
+            translation,
+            vector![0.0, 0.0],
+            inputs.frame_count,
+            collider_set,
+            rigid_body_set,
+          ),
+        ]
+      } else {
+        vec![(
+          handle,
+          Arc::new(Entity {
+            components: entity.components.with(Drone {
+              cooldown: (drone.cooldown - inputs.time_scale).max(0.0),
+              ..*drone
+            }),
+            ..entity.as_ref().clone()
+          }),
+        )]
+      }
+    })
+    .chain(new_drones)
+    .collect::<Entities>();
+
+  FrameState { entities, ..frame }
+}
+
+fn apply_enemy_decisions(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  let collider_set = &mut frame.collider_set;
+  let rigid_body_set = &mut frame.rigid_body_set;
+  let rng = rand::RandGenerator::new();
+  rng.srand(inputs.frame_count as u64);
+
+  /* Enemy fire only needs to reach enemies when one of them can be hurt by it, under friendly
+  fire or with rival groups or wildlife sharing the room */
+  let enemy_fire_hits_enemies = inputs.friendly_fire
+    || frame
+      .entities
+      .values()
+      .filter(|entity| entity.components.get::<Enemy>().is_some())
+      .map(|entity| Faction::of(&entity.components))
+      .collect::<HashSet<_>>()
+      .len()
+      > 1;
+
+  let entities = frame
+    .entities
+    .iter()
+    .flat_map(|(_, entity)| {
+      let relevant_decision = inputs
+        .enemy_decisions
+        .iter()
+        .find(|&decision| EntityHandle::RigidBody(decision.handle) == entity.handle);
+      if relevant_decision.is_none() {
+        return vec![(entity.handle, entity.clone())];
+      }
+      let relevant_decision = relevant_decision.unwrap();
+
+      let status_effects = entity.components.get::<StatusEffects>();
+      let movement_mod = status_effects
+        .as_ref()
+        .map(|status_effects| status_effects.movement_mod())
+        .unwrap_or(1.0)
+        * entity
+          .components
+          .get::<Elite>()
+          .map_or(1.0, |elite| elite.movement_mod());
+      let firing_disabled = status_effects
+        .as_ref()
+        .is_some_and(|status_effects| status_effects.firing_disabled());
+      let faction = Faction::of(&entity.components);
+
+      if let EntityHandle::RigidBody(rigid_body_handle) = entity.handle {
+        rigid_body_set[rigid_body_handle]
+          .apply_impulse(relevant_decision.movement_force * movement_mod, true);
+      }
+
+      let new_projectiles = if let EntityHandle::RigidBody(rigid_body_handle) = entity.handle
+        && !firing_disabled
+      {
+        relevant_decision
+          .projectiles
+          .iter()
+          .map(|projectile| {
+            let mut collider = projectile.collider.clone();
+
+            /* Shots that can hit enemies leave from outside the shooter, not to hit it at once */
+            let clearance = if enemy_fire_hits_enemies {
+              let groups = collider.collision_groups();
+              collider.set_collision_groups(InteractionGroups {
+                filter: groups.filter.union(CollisionLayer::Enemy.group()),
+                ..groups
+              });
+
+              let shooter_radius = rigid_body_set[rigid_body_handle]
+                .colliders()
+                .iter()
+                .map(|&collider_handle| {
+                  collider_set[collider_handle]
+                    .shape()
+                    .compute_local_bounding_sphere()
+                    .radius
+                })
+                .fold(0.0, f32::max);
+              let shot_radius = collider.shape().compute_local_bounding_sphere().radius;
+
+              projectile
+                .initial_impulse
+                .into_vec()
+                .try_normalize(f32::EPSILON)
+                .map_or(vector![0.0, 0.0], |direction| {
+                  direction * (shooter_radius + shot_radius)
+                })
+            } else {
+              vector![0.0, 0.0]
+            };
+
+            let handle = rigid_body_set.insert(
+              RigidBodyBuilder::dynamic()
+                .translation(
+                  *rigid_body_set[rigid_body_handle].translation()
+                    + projectile.offset.into_vec()
+                    + clearance,
+                )
+                .ccd_enabled(projectile.ccd),
+            );
+            collider_set.insert_with_parent(collider, handle, rigid_body_set);
+
+            let enemy_velocity = *rigid_body_set[rigid_body_handle].linvel();
+            rigid_body_set[handle].set_linvel(enemy_velocity, true);
+
+            rigid_body_set[handle].apply_impulse(projectile.initial_impulse.into_vec(), true);
+
+            (
+              EntityHandle::RigidBody(handle),
+              Arc::new(Entity {
+                handle: EntityHandle::RigidBody(handle),
+                components: projectile
+                  .component_set
+                  .insert(DestroyOnCollision)
+                  .insert(Damager {
+                    damage: projectile.damage,
+                    knockback: 0.0,
+                    on_hit_status: projectile.on_hit_status,
+                    source: Some(entity.handle),
+                  })
+                  .insert(faction)
+                  .insert(Capped {
+                    category: CapCategory::EnemyProjectile,
+                    priority: 0,
+                    spawned_frame: inputs.frame_count,
+                  })
+                  .insert(EntityKind::EnemyProjectile),
+              }),
+            )
+          })
+          .collect::<HashMap<_, _>>()
+      } else {
+        HashMap::new()
+      };
+
+      let new_mines = if let EntityHandle::RigidBody(rigid_body_handle) = entity.handle
+        && !firing_disabled
+      {
+        relevant_decision
+          .mines
+          .iter()
+          .map(|mine| {
+            let handle = rigid_body_set.insert(
+              RigidBodyBuilder::fixed()
+                .translation(*rigid_body_set[rigid_body_handle].translation()),
+            );
+            collider_set.insert_with_parent(mine.collider.clone(), handle, rigid_body_set);
+
+            (
+              EntityHandle::RigidBody(handle),
+              Arc::new(Entity {
+                handle: EntityHandle::RigidBody(handle),
+                /* Mines shape the fight, so stray shots are culled before them */
+                components: mine
+                  .component_set
+                  .insert(DestroyOnCollision)
+                  .insert(faction)
+                  .insert(Capped {
+                    category: CapCategory::EnemyProjectile,
+                    priority: 1,
+                    spawned_frame: inputs.frame_count,
+                  })
+                  .insert(EntityKind::Mine),
+              }),
+            )
+          })
+          .collect::<HashMap<_, _>>()
+      } else {
+        HashMap::new()
+      };
+
+      [(
+        entity.handle,
+        Arc::new(Entity {
+          components: match relevant_decision.telegraph {
+            Some(telegraph) => entity.components.with(telegraph),
+            None => entity.components.without::<Telegraph>(),
+          }
+          .with(relevant_decision.enemy.clone()),
+          ..entity.as_ref().clone()
+        }),
+      )]
+      .into_iter()
+      .chain(new_projectiles)
+      .chain(new_mines)
+      .chain(
+        relevant_decision
+          .enemies_to_spawn
+          .iter()
+          .map(|enemy_to_spawn| {
+            let handle = rigid_body_set.insert(enemy_to_spawn.enemy_spawn.rigid_body.clone());
+            collider_set.insert_with_parent(
+              enemy_to_spawn.enemy_spawn.collider.clone(),
+              handle,
+              rigid_body_set,
+            );
+            rigid_body_set[handle].apply_impulse(enemy_to_spawn.initial_force, true);
+            (
+              EntityHandle::RigidBody(handle),
+              Arc::new(Entity {
+                handle: EntityHandle::RigidBody(handle),
+                /* Spawned enemies fight for the side of their parent */
+                components: enemy_to_spawn
+                  .enemy_spawn
+                  .to_entity_components(
+                    inputs.enemy_definitions,
+                    inputs.difficulty,
+                    rng.gen_range(0.0, 1.0),
+                  )
+                  .with(faction)
+                  .insert(EntityKind::Enemy),
+              }),
+            )
+          }),
+      )
+      .collect()
+    })
+    .collect::<Entities>();
+
+  FrameState { entities, ..frame }
+}
+
+/* Enemy projectiles crossing the chain are bounced off of it and turned against enemies */
+fn deflect_with_chain(mut frame: FrameState, _: &FrameInputs) -> FrameState {
+  let collider_set = &mut frame.collider_set;
+  let rigid_body_set = &mut frame.rigid_body_set;
+
+  let deflected = frame
+    .entities
+    .iter()
+    .filter(|(_, entity)| entity.components.get::<ChainSegment>().is_some())
+    .flat_map(|(handle, _)| handle.colliders(rigid_body_set).into_iter().copied())
+    .flat_map(|segment_collider| {
+      let segment_normal = collider_set[segment_collider].rotation() * vector![0.0, 1.0];
+
+      frame
+        .narrow_phase
+        .intersection_pairs_with(segment_collider)
+        .filter_map(|(collider1, collider2, colliding)| {
+          let other_handle = if collider1 == segment_collider {
+            collider2
+          } else {
+            collider1
+          };
+          let other = &collider_set[other_handle];
+
+          if !colliding
+            || !other
+              .collision_groups()
+              .memberships
+              .contains(CollisionLayer::EnemyProjectile.group())
+          {
+            return None;
+          }
+
+          let projectile_handle = other.parent()?;
+          frame
+            .entities
+            .get(&EntityHandle::RigidBody(projectile_handle))?
+            .components
+            .get::<DestroyOnCollision>()?;
+
+          Some((projectile_handle, segment_normal))
+        })
+        .collect::<Vec<_>>()
+    })
+    .collect::<HashMap<_, _>>();
+
+  /* Every deflected shot wears the chain down by the damage it would have dealt */
+  let chain_durability = frame.chain_durability
+    - deflected
+      .keys()
+      .filter_map(|&projectile_handle| {
+        frame
+          .entities
+          .get(&EntityHandle::RigidBody(projectile_handle))?
+          .components
+          .get::<Damager>()
+      })
+      .map(|damager| damager.damage)
+      .sum::<f32>();
+
+  deflected
+    .into_iter()
+    .for_each(|(projectile_handle, segment_normal)| {
+      let projectile = &mut rigid_body_set[projectile_handle];
+      let velocity = *projectile.linvel();
+      projectile.set_linvel(
+        velocity - 2.0 * velocity.dot(&segment_normal) * segment_normal,
+        true,
+      );
+
+      projectile
+        .colliders()
+        .to_vec()
+        .into_iter()
+        .for_each(|collider_handle| {
+          collider_set[collider_handle]
+            .set_collision_groups(groups(CollisionLayer::PlayerProjectile));
+        });
+    });
+
+  FrameState {
+    chain_durability,
+    ..frame
+  }
+}
+
+fn resolve_damage(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  let collider_set = &mut frame.collider_set;
+  let rigid_body_set = &mut frame.rigid_body_set;
+  let narrow_phase = &frame.narrow_phase;
+
+  let reflective_colliders = frame
+    .entities
+    .iter()
+    .filter(|(_, entity)| entity.components.get::<Reflective>().is_some())
+    .flat_map(|(handle, _)| handle.colliders(rigid_body_set).into_iter().copied())
+    .collect::<HashSet<_>>();
+
+  /* MARK: Spawn explosions for entities marked as explode on collision */
+  let entities = frame
+    .entities
+    .iter()
+    .flat_map(|(handle, entity)| {
+      /* Mines sit harmless until they arm, even when touched */
+      let armed = entity
+        .components
+        .get::<ProximityTrigger>()
+        .is_none_or(|proximity_trigger| proximity_trigger.arm_frames <= 0);
+
+      if let Some(explode_on_collision) = entity.components.get::<ExplodeOnCollision>()
+        && armed
+        && handle
+          .colliders(rigid_body_set)
+          .iter()
+          .any(|&&collider_handle| {
+            let collider = &collider_set[collider_handle];
+
+            if collider.is_sensor() {
+              narrow_phase
+                .intersection_pairs_with(collider_handle)
+                .any(|(_, _, is_intersecting)| is_intersecting)
+            } else {
+              narrow_phase
+                .contact_pairs_with(collider_handle)
+                .any(|contact_pair| contact_pair.has_any_active_contact)
+            }
+          })
+      {
+        let explosion = spawn_explosion(
+          *handle.translation(rigid_body_set, collider_set),
+          explode_on_collision.as_ref(),
+          Faction::of(&entity.components),
+          collider_set,
+          rigid_body_set,
+        );
+
+        [(*handle, Arc::clone(entity))]
+          .into_iter()
+          .chain(
+            explosion
+              .into_iter()
+              .map(|blast| (blast.handle, blast.into())),
+          )
+          .collect()
+      } else {
+        vec![(*handle, Arc::clone(entity))]
+      }
+    })
+    .collect::<Entities>();
+
+  /* MARK: Detonate armed proximity triggers near either player */
+  let player_translations = [Some(inputs.player_handle), frame.player_two_handle]
+    .into_iter()
+    .flatten()
+    .map(|handle| *rigid_body_set[handle].translation())
+    .collect::<Vec<_>>();
+
+  let entities = entities
+    .iter()
+    .flat_map(|(handle, entity)| {
+      if let Some(proximity_trigger) = entity.components.get::<ProximityTrigger>() {
+        if proximity_trigger.arm_frames > 0 {
+          return vec![(
+            *handle,
+            Arc::new(Entity {
+              components: entity.components.with(ProximityTrigger {
+                arm_frames: proximity_trigger.arm_frames - inputs.whole_frames(),
+                ..*proximity_trigger
+              }),
+              ..entity.as_ref().clone()
+            }),
+          )];
+        }
+
+        let translation = *handle.translation(rigid_body_set, collider_set);
+
+        if let Some(explode_on_collision) = entity.components.get::<ExplodeOnCollision>()
+          && player_translations.iter().any(|player_translation| {
+            (translation - player_translation).magnitude() <= proximity_trigger.radius
+          })
+        {
+          let explosion = spawn_explosion(
+            translation,
+            explode_on_collision.as_ref(),
+            Faction::of(&entity.components),
+            collider_set,
+            rigid_body_set,
+          );
+
+          return [(
+            *handle,
+            Arc::new(Entity {
+              components: entity.components.with(Destroyed),
+              ..entity.as_ref().clone()
+            }),
+          )]
+          .into_iter()
+          .chain(
+            explosion
+              .into_iter()
+              .map(|blast| (blast.handle, blast.into())),
+          )
+          .collect();
+        }
+      }
+
+      vec![(*handle, Arc::clone(entity))]
+    })
+    .collect::<Entities>();
+
+  /* MARK: Damage all entities colliding with damagers */
+  let player_handles = [Some(inputs.player_handle), frame.player_two_handle]
+    .into_iter()
+    .flatten()
+    .map(EntityHandle::RigidBody)
+    .collect::<Vec<_>>();
+
+  let whole_frames = inputs.whole_frames();
+  let (entities, knockbacks) = update_components_with(
+    &entities,
+    damage_taken(
+      rigid_body_set,
+      narrow_phase,
+      collider_set,
+      &entities,
+      inputs.time_scale,
+      whole_frames,
+      inputs.friendly_fire,
+      inputs.assist,
+      &player_handles,
+    ),
+  );
+
+  knockbacks.into_iter().for_each(|(handle, knockback)| {
+    if let EntityHandle::RigidBody(rigid_body_handle) = handle
+      && knockback.magnitude() > 0.0
+    {
+      rigid_body_set[rigid_body_handle].apply_impulse(knockback, true);
+    }
+  });
+
+  /* MARK: Destroy all marked to be destroyed on this frame */
+  let entities = update_components(&entities, |entity| {
+    let destroy_after_frames = entity.components.get::<DestroyAfterFrames>()?;
+
+    if destroy_after_frames.frames > 0 {
+      return Some(entity.components.with(DestroyAfterFrames {
+        frames: destroy_after_frames.frames - whole_frames,
+      }));
+    }
+
+    return Some(entity.components.with(Destroyed));
+  });
+
+  /* MARK: Destroy all entities with 0 health marked as such */
+  let entities = update_components(&entities, |entity| {
+    let damageable = entity.components.get::<Damageable>()?;
+
+    return (damageable.health <= 0.0).then(|| entity.components.with(Destroyed));
+  });
+
+  /* MARK: Reflect projectiles flying into reflective walls while they have bounces left */
+  let entities = entities
+    .iter()
+    .map(|(&handle, entity)| (handle, Arc::clone(entity)))
+    .map(|(handle, entity)| {
+      let Some(bounces) = entity.components.get::<Bounces>() else {
+        return (handle, entity);
+      };
+      let EntityHandle::RigidBody(rigid_body_handle) = entity.handle else {
+        return (handle, entity);
+      };
+
+      let velocity = *rigid_body_set[rigid_body_handle].linvel();
+      let incoming_normal = reflective_contacts(
+        &entity.handle,
+        &reflective_colliders,
+        narrow_phase,
+        rigid_body_set,
+      )
+      .into_iter()
+      .map(|(_, normal)| normal)
+      .find(|normal| velocity.dot(normal) < 0.0);
+
+      match incoming_normal {
+        Some(normal) if bounces.count < bounces.max => {
+          rigid_body_set[rigid_body_handle]
+            .set_linvel(velocity - 2.0 * velocity.dot(&normal) * normal, true);
+          (
+            handle,
+            Arc::new(Entity {
+              components: entity.components.with(Bounces {
+                count: bounces.count + 1,
+                ..*bounces
+              }),
+              ..entity.as_ref().clone()
+            }),
+          )
+        }
+        _ => (handle, entity),
+      }
+    })
+    .collect::<Vec<_>>()
+    .into_iter();
+
+  /* MARK: Destroy colliding entities marked as destroy on collision */
+  let entities = entities
+    .map(|(handle, entity)| {
+      /* Reflective walls a bounced projectile is already flying away from don't break it */
+      let deflected_by = match (entity.components.get::<Bounces>(), entity.handle) {
+        (Some(_), EntityHandle::RigidBody(rigid_body_handle)) => {
+          let velocity = *rigid_body_set[rigid_body_handle].linvel();
+          reflective_contacts(
+            &entity.handle,
+            &reflective_colliders,
+            narrow_phase,
+            rigid_body_set,
+          )
+          .into_iter()
+          .filter(|(_, normal)| velocity.dot(normal) >= 0.0)
+          .map(|(collider_handle, _)| collider_handle)
+          .collect::<Vec<_>>()
+        }
+        _ => vec![],
+      };
+
+      let entity_destroyed = !(entity.components.get::<DestroyOnCollision>().is_none()
+        || entity
+          .handle
+          .colliders(rigid_body_set)
+          .iter()
+          .flat_map(|&&collider_handle| {
+            let collider = &collider_set[collider_handle];
+
+            if collider.is_sensor() {
+              narrow_phase
+                .intersection_pairs_with(collider_handle)
+                .flat_map(|(collider1, collider2, is_intersecting)| {
+                  if is_intersecting {
+                    vec![collider1, collider2]
+                  } else {
+                    vec![]
+                  }
+                })
+                .collect::<Vec<_>>()
+            } else {
+              narrow_phase
+                .contact_pairs_with(collider_handle)
+                .flat_map(|contact_pair| {
+                  if contact_pair.has_any_active_contact {
+                    vec![contact_pair.collider1, contact_pair.collider2]
+                  } else {
+                    vec![]
+                  }
+                })
+                .collect::<Vec<_>>()
+            }
+          })
+          .filter(|collider_handle| {
+            !entity
+              .handle
+              .colliders(rigid_body_set)
+              .contains(&collider_handle)
+              && !collider_set[*collider_handle].is_sensor()
+              && !deflected_by.contains(collider_handle)
+          })
+          .count()
+          == 0);
+
+      if entity_destroyed {
+        (
+          handle,
+          Arc::new(Entity {
+            components: entity.components.with(Destroyed),
+            ..entity.as_ref().clone()
+          }),
+        )
+      } else {
+        (handle, entity)
+      }
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Spawn impacts where projectiles were destroyed on collision */
+  let entities = entities
+    .into_iter()
+    .flat_map(|(handle, entity)| {
+      if entity.components.get::<DestroyOnCollision>().is_some()
+        && entity.components.get::<Destroyed>().is_some()
+        && let Some(impact) = spawn_impact(
+          &entity.handle,
+          entity.components.get::<CriticalHit>().is_some(),
+          inputs.frame_count,
+          narrow_phase,
+          collider_set,
+          rigid_body_set,
+        )
+      {
+        vec![(handle, entity), (impact.handle, impact.into())]
+      } else {
+        vec![(handle, entity)]
+      }
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Blow up destroyed entities marked to explode on death */
+  let entities = entities
+    .into_iter()
+    .flat_map(|(handle, entity)| {
+      if entity.components.get::<Destroyed>().is_some()
+        && let Some(explode_on_death) = entity.components.get::<ExplodeOnDeath>()
+      {
+        let explosion = spawn_explosion(
+          *handle.translation(rigid_body_set, collider_set),
+          &explode_on_death.explosion,
+          Faction::of(&entity.components),
+          collider_set,
+          rigid_body_set,
+        );
+
+        [(handle, entity)]
+          .into_iter()
+          .chain(
+            explosion
+              .into_iter()
+              .map(|blast| (blast.handle, blast.into())),
+          )
+          .collect()
+      } else {
+        vec![(handle, entity)]
+      }
+    })
+    .collect::<Vec<_>>();
+
+  let rng = rand::RandGenerator::new();
+  rng.srand(inputs.frame_count as u64);
+
+  /* MARK: Scatter debris from destroyed walls */
+  let entities = entities
+    .into_iter()
+    .flat_map(|(handle, entity)| {
+      if entity.components.get::<Destroyed>().is_none() {
+        return vec![(handle, entity)];
+      }
+
+      /* Wall bodies sit at the origin with their collider placed in the room */
+      let Some(translation) = handle
+        .colliders(rigid_body_set)
+        .into_iter()
+        .map(|&collider_handle| &collider_set[collider_handle])
+        .find(|collider| {
+          collider
+            .collision_groups()
+            .memberships
+            .contains(CollisionLayer::Wall.group())
+        })
+        .map(|collider| *collider.translation())
+      else {
+        return vec![(handle, entity)];
+      };
+
+      [(handle, entity)]
+        .into_iter()
+        .chain((0..DEBRIS_PIECES).map(|_| {
+          let debris = spawn_debris(
+            translation,
+            inputs.frame_count,
+            &rng,
+            collider_set,
+            rigid_body_set,
+          );
+          (debris.handle, debris.into())
+        }))
+        .collect()
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Roll the loot tables of destroyed entities */
+  let entities = entities
+    .into_iter()
+    .flat_map(|(handle, entity)| {
+      if entity.components.get::<Destroyed>().is_none() {
+        return vec![(handle, entity)];
+      }
+
+      let Some(loot_table) = entity.components.get::<LootTable>() else {
+        return vec![(handle, entity)];
+      };
+      let Some(drop) = loot_table.pick(rng.gen_range(0.0, 1.0)) else {
+        return vec![(handle, entity)];
+      };
+
+      let translation = *entity.handle.translation(rigid_body_set, collider_set);
+
+      match spawn_loot(drop, loot_table.despawn_frames, translation, collider_set) {
+        Some(loot) => vec![(handle, entity), (loot.handle, loot.into())],
+        None => vec![(handle, entity)],
+      }
+    })
+    .collect::<Entities>();
+
+  FrameState { entities, ..frame }
+}
+
+fn spawn_loot(
+  drop: LootDrop,
+  despawn_frames: i32,
+  translation: Vector<f32>,
+  collider_set: &mut ColliderSet,
+) -> Option<Entity> {
+  let components = match drop {
+    LootDrop::Health(amount) => ComponentSet::new()
+      .insert(HealOnCollision { amount })
+      .insert(EntityKind::HealthPickup),
+    LootDrop::Credits(amount) => ComponentSet::new()
+      .insert(GivesCreditsOnCollision { amount })
+      .insert(EntityKind::CreditsPickup),
+    LootDrop::Module(weapon_module_kind) => ComponentSet::new()
+      .insert(GivesItemOnCollision { weapon_module_kind })
+      .insert(EntityKind::ItemPickup),
+    LootDrop::PowerUp(kind) => ComponentSet::new()
+      .insert(GivesPowerUpOnCollision { kind })
+      .insert(EntityKind::PowerUpPickup),
+    LootDrop::Nothing => return None,
+  };
+
+  let handle = collider_set.insert(
+    ColliderBuilder::ball(0.31)
+      .collision_groups(groups(CollisionLayer::PlayerInteractible))
+      .sensor(true)
+      .translation(translation)
+      .build(),
+  );
+
+  let components = components.insert(DestroyOnCollision);
+  let components = if despawn_frames > 0 {
+    components
+      .insert(DestroyAfterFrames {
+        frames: despawn_frames,
+      })
+      .insert(DespawnTimer {
+        warning_frames: LOOT_DESPAWN_WARNING_FRAMES.min(despawn_frames),
+      })
+  } else {
+    components
+  };
+
+  Some(Entity {
+    handle: EntityHandle::Collider(handle),
+    components,
+  })
+}
+
+fn collect_pickups(frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  let rigid_body_set = &frame.rigid_body_set;
+  let narrow_phase = &frame.narrow_phase;
+  let entities = &frame.entities;
+
+  let touching_player = |handle: &EntityHandle| {
+    handle
+      .colliders(rigid_body_set)
+      .iter()
+      .any(|&entity_collider_handle| {
+        rigid_body_set[inputs.player_handle]
+          .colliders()
+          .iter()
+          .any(|player_collider| {
+            narrow_phase
+              .intersection_pair(*entity_collider_handle, *player_collider)
+              .unwrap_or(false)
+          })
+      })
+  };
+
+  /* MARK: Give items on collision */
+  let new_weapon_modules = entities.iter().fold(list![], |acc, (handle, entity)| {
+    if let Some(gives_item) = entity.components.get::<GivesItemOnCollision>()
+      && touching_player(handle)
+    {
+      let id = entity.components.get::<Id>().map(|id| id.id);
+      acc.push_front((id, gives_item.weapon_module_kind))
+    } else {
+      acc
+    }
+  });
+
+  /* MARK: Give credits on collision */
+  let new_credits = entities
+    .iter()
+    .filter(|(handle, _)| touching_player(handle))
+    .filter_map(|(_, entity)| entity.components.get::<GivesCreditsOnCollision>())
+    .map(|gives_credits| gives_credits.amount)
+    .sum();
+
+  /* MARK: Give power-ups on collision */
+  let new_power_ups = entities.iter().fold(list![], |acc, (handle, entity)| {
+    if let Some(gives_power_up) = entity.components.get::<GivesPowerUpOnCollision>()
+      && touching_player(handle)
+    {
+      acc.push_front(gives_power_up.kind)
+    } else {
+      acc
+    }
+  });
+
+  /* MARK: Give abilities on collision */
+  let new_abilities = entities.iter().fold(list![], |acc, (handle, entity)| {
+    if let Some(gives_ability) = entity.components.get::<GiveAbilityOnCollision>()
+      && touching_player(handle)
+    {
+      acc.push_front(gives_ability.ability_type)
+    } else {
+      acc
+    }
+  });
+
+  /* MARK: Discover logs on collision */
+  let new_logs = entities.iter().fold(list![], |acc, (handle, entity)| {
+    if let Some(gives_log) = entity.components.get::<GivesLogOnCollision>()
+      && touching_player(handle)
+    {
+      acc.push_front(gives_log.log_id.clone())
+    } else {
+      acc
+    }
+  });
+
+  /* MARK: Give engine upgrades on collision */
+  let new_engine_upgrades = entities.iter().fold(list![], |acc, (handle, entity)| {
+    if let Some(gives_engine_upgrade) = entity.components.get::<GivesEngineUpgradeOnCollision>()
+      && touching_player(handle)
+    {
+      acc.push_front(gives_engine_upgrade.name.clone())
+    } else {
+      acc
+    }
+  });
+
+  /* MARK: Load new map */
+  let load_new_map = entities.iter().find_map(|(handle, entity)| {
+    if handle
+      .colliders(rigid_body_set)
+      .iter()
+      .all(|&collider_handle| {
+        narrow_phase
+          .intersection_pairs_with(*collider_handle)
+          .filter(|(_, _, colliding)| *colliding)
+          .count()
+          == 0
+      })
+    {
+      return None;
+    }
+
+    entity
+      .components
+      .get::<MapTransitionOnCollision>()
+      /* Rooms already streamed in are walked into rather than loaded */
+      .filter(|map_transition_on_collision| {
+        map_transition_on_collision.map_name != inputs.map_name
+          && !inputs
+            .chunks
+            .contains_key(&map_transition_on_collision.map_name)
+      })
+      .map(|map_transition_on_collision| {
+        (
+          map_transition_on_collision.map_name.clone(),
+          map_transition_on_collision.target_player_spawn_id,
+        )
+      })
+  });
+
+  /* MARK: Save point interaction */
+  let save_point_contact = entities.iter().find_map(|(handle, entity)| {
+    if handle
+      .colliders(rigid_body_set)
+      .iter()
+      .any(|&collider_handle| {
+        narrow_phase
+          .intersection_pairs_with(*collider_handle)
+          .filter(|(_, _, colliding)| *colliding)
+          .count()
+          > 0
+      })
+      && entity.components.get::<SaveMenuOnCollision>().is_some()
+      && let Some(id) = entity.components.get::<Id>()
+    {
+      Some(id.id)
+    } else {
+      None
+    }
+  });
+
+  /* MARK: Heal from sensor collision mark as such */
+  let entities = entities
+    .iter()
+    .map(|(&handle, entity)| {
+      let damageable = entity.components.get::<Damageable>();
+
+      if damageable.is_none() {
+        return (handle, Arc::clone(entity));
+      }
+      let damageable = damageable.unwrap();
+
+      let healing_sensors = entity
+        .handle
+        .colliders(rigid_body_set)
+        .into_iter()
+        .flat_map(|&collider_handle| {
+          narrow_phase
+            .intersection_pairs_with(collider_handle)
+            .flat_map(|(collider1, collider2, has_active_contact)| {
+              if !has_active_contact {
+                Vec::new()
+              } else {
+                [collider1, collider2]
+                  .iter()
+                  .cloned()
+                  .filter(|&handle| collider_handle != handle)
+                  .collect::<Vec<_>>()
+              }
+            })
+            .collect::<Vec<_>>()
+        })
+        .flat_map(|collider_handle| {
+          entities
+            .iter()
+            .find(|(handle, _)| {
+              handle
+                .colliders(rigid_body_set)
+                .iter()
+                .any(|&handle| *handle == collider_handle)
+            })
+            .and_then(|(_, entity)| entity.components.get::<HealOnCollision>())
+        });
+
+      let incoming_healing = healing_sensors.fold(0.0, |sum, healing| sum + healing.amount);
+
+      (
+        handle,
+        Entity {
+          components: entity.components.with(Damageable {
+            health: (damageable.health + incoming_healing).min(damageable.max_health),
+            ..*damageable
+          }),
+          ..entity.as_ref().clone()
+        }
+        .into(),
+      )
+    })
+    .collect::<Entities>();
+
+  FrameState {
+    entities,
+    new_weapon_modules,
+    new_abilities,
+    new_logs,
+    new_engine_upgrades,
+    new_credits,
+    new_power_ups,
+    load_new_map,
+    save_point_contact,
+    ..frame
+  }
+}
+
+fn update_chain(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  let collider_set = &mut frame.collider_set;
+  let rigid_body_set = &mut frame.rigid_body_set;
+  let impulse_joint_set = &mut frame.impulse_joint_set;
+
+  /* MARK: Initiate chain on selected mount point */
+  let chain_entities = inputs.chain_to_mount_point.map(|mount_point| {
+    let player_translation = *rigid_body_set[inputs.player_handle].translation();
+
+    let vector_to_mount_point = rigid_body_set[mount_point].translation() - player_translation;
+    let distance_to_mount_point = vector_to_mount_point.magnitude();
+    let num_chain_segments = (distance_to_mount_point / CHAIN_SEGMENT_LENGTH).floor();
+    let initial_chain_segment_length = distance_to_mount_point % CHAIN_SEGMENT_LENGTH;
+
+    let unit_to_mount_point = vector_to_mount_point.normalize();
+
+    let rotation_angle = -angle_from_vec(PhysicsVector::from_vec(unit_to_mount_point));
+
+    let initial_chain_segment_handle = rigid_body_set.insert(
+      RigidBodyBuilder::dynamic()
+        .translation(
+          player_translation + (unit_to_mount_point * initial_chain_segment_length / 2.0),
+        )
+        .rotation(rotation_angle)
+        .angular_damping(CHAIN_ANGULAR_DAMPING)
+        .build(),
+    );
+    collider_set.insert_with_parent(
+      ColliderBuilder::cuboid(
+        initial_chain_segment_length / 2.0,
+        CHAIN_SEGMENT_HEIGHT / 2.0,
+      )
+      .sensor(true)
+      .collision_groups(groups(CollisionLayer::Chain)),
+      initial_chain_segment_handle,
+      rigid_body_set,
+    );
+
+    let chain_segment_handles = (0..num_chain_segments as i32)
+      .map(|chain_segment_index| {
+        let chain_segment_handle = rigid_body_set.insert(
+          RigidBodyBuilder::dynamic()
+            .translation(
+              player_translation
+                + (unit_to_mount_point
+                  * (initial_chain_segment_length
+                    + (CHAIN_SEGMENT_LENGTH * (chain_segment_index as f32 + 0.5)))),
+            )
+            .rotation(rotation_angle)
+            .angular_damping(CHAIN_ANGULAR_DAMPING)
+            .build(),
+        );
+        collider_set.insert_with_parent(
+          ColliderBuilder::cuboid(CHAIN_SEGMENT_LENGTH / 2.0, CHAIN_SEGMENT_HEIGHT / 2.0)
+            .sensor(true)
+            .collision_groups(groups(CollisionLayer::Chain)),
+          chain_segment_handle,
+          rigid_body_set,
+        );
+        chain_segment_handle
+      })
+      .collect::<List<_>>();
+
+    impulse_joint_set.insert(
+      inputs.player_handle,
+      initial_chain_segment_handle,
+      RevoluteJointBuilder::new()
+        .local_anchor1(vector![0.0, 0.0].into())
+        .local_anchor2(
+          vector![
+            -initial_chain_segment_length / 2.0,
+            CHAIN_SEGMENT_HEIGHT / 2.0
+          ]
+          .into(),
+        )
+        .contacts_enabled(false)
+        .build(),
+      true,
+    );
+
+    let left_segment_anchor: OPoint<_, _> =
+      vector![CHAIN_SEGMENT_LENGTH / 2.0, CHAIN_SEGMENT_HEIGHT / 2.0].into();
+
+    let right_segment_anchor: OPoint<_, _> =
+      vector![-CHAIN_SEGMENT_LENGTH / 2.0, CHAIN_SEGMENT_HEIGHT / 2.0].into();
+
+    chain_segment_handles
+      .first()
+      .map(|&standard_segment_handle| {
+        impulse_joint_set.insert(
+          initial_chain_segment_handle,
+          standard_segment_handle,
+          RevoluteJointBuilder::new()
+            .local_anchor1(
+              vector![
+                initial_chain_segment_length / 2.0,
+                CHAIN_SEGMENT_HEIGHT / 2.0,
+              ]
+              .into(),
+            )
+            .local_anchor2(right_segment_anchor),
+          //              .limits(CHAIN_SEGMENT_LIMITS),
+          true,
+        )
+      });
+
+    chain_segment_handles
+      .iter()
+      .reduce(|&segment_a_handle, segment_b_handle| {
+        impulse_joint_set.insert(
+          segment_a_handle,
+          *segment_b_handle,
+          RevoluteJointBuilder::new()
+            .local_anchor1(left_segment_anchor)
+            .local_anchor2(right_segment_anchor),
+          //              .limits(CHAIN_SEGMENT_LIMITS),
+          true,
+        );
+        segment_b_handle
+      });
+
+    if let Some(&last_segment_handle) = chain_segment_handles
+      .push_front(initial_chain_segment_handle)
+      .last()
+    {
+      impulse_joint_set.insert(
+        last_segment_handle,
+        mount_point,
+        RevoluteJointBuilder::new()
+          .local_anchor1(left_segment_anchor)
+          .local_anchor2(vector![0.0, 0.0].into()),
+        true,
+      );
+    };
+
+    chain_segment_handles
+      .push_front(initial_chain_segment_handle)
+      .iter()
+      .map(|&handle| {
+        let handle = EntityHandle::RigidBody(handle);
+        (
+          handle,
+          Arc::new(Entity {
+            handle,
+            components: ComponentSet::new()
+              .insert(ChainSegment)
+              .insert(EntityKind::ChainSegment),
+          }),
+        )
+      })
+      .collect::<Entities>()
+  });
+
+  let entities = if let Some(chain_entities) = chain_entities {
+    frame
+      .entities
+      .into_iter()
+      .chain(&chain_entities)
+      .map(|(&handle, entity)| (handle, Arc::clone(entity)))
+      .collect::<Entities>()
+  } else {
+    frame.entities
+  };
+
+  let chain_durability = if inputs.chain_to_mount_point.is_some() {
+    CHAIN_DURABILITY
+  } else {
+    frame.chain_durability
+  };
+
+  /* MARK: Measure chain tension */
+  let chain_segment_handles = entities
+    .iter()
+    .filter(|(_, entity)| {
+      entity.components.get::<ChainSegment>().is_some()
+        && entity.components.get::<Destroyed>().is_none()
+    })
+    .filter_map(|(handle, _)| match handle {
+      EntityHandle::RigidBody(rigid_body_handle) => Some(*rigid_body_handle),
+      _ => None,
+    })
+    .collect::<HashSet<_>>();
+
+  let chain_tension = impulse_joint_set
+    .iter()
+    .filter(|(_, joint)| {
+      chain_segment_handles.contains(&joint.body1) || chain_segment_handles.contains(&joint.body2)
+    })
+    .map(|(_, joint)| {
+      let anchor1 = rigid_body_set[joint.body1].position() * joint.data.local_anchor1();
+      let anchor2 = rigid_body_set[joint.body2].position() * joint.data.local_anchor2();
+
+      (anchor1 - anchor2).magnitude()
+    })
+    .fold(0.0, f32::max);
+
+  let chain_broke = !chain_segment_handles.is_empty()
+    && (chain_durability <= 0.0 || chain_tension > CHAIN_BREAK_STRETCH);
+
+  /* MARK: Kill chain */
+  let entities = if inputs.kill_chain || chain_broke {
+    entities
+      .into_iter()
+      .map(|(&handle, entity)| {
+        if entity.components.get::<ChainSegment>().is_some() {
+          return (
+            handle,
+            Arc::new(Entity {
+              handle,
+              components: entity.components.with(Destroyed),
+            }),
+          );
+        }
+
+        (handle, Arc::clone(entity))
+      })
+      .collect::<Entities>()
+  } else {
+    entities
+  };
+
+  /* MARK: Spark where the chain snapped */
+  let entities = if chain_broke {
+    chain_segment_handles
+      .iter()
+      .map(|&segment_handle| {
+        let segment = &rigid_body_set[segment_handle];
+        let normal = segment.rotation() * vector![0.0, 1.0];
+        let translation = *segment.translation();
+
+        let handle = EntityHandle::RigidBody(
+          rigid_body_set.insert(RigidBodyBuilder::fixed().translation(translation)),
+        );
+
+        Entity {
+          handle,
+          components: ComponentSet::new()
+            .insert(Impact {
+              normal,
+              kind: ImpactKind::Spark,
+              lifetime: IMPACT_SPARK_FRAMES,
+            })
+            .insert(DestroyAfterFrames {
+              frames: IMPACT_SPARK_FRAMES,
+            })
+            .insert(Capped {
+              category: CapCategory::Particle,
+              priority: 0,
+              spawned_frame: inputs.frame_count,
+            })
+            .insert(EntityKind::Impact),
+        }
+      })
+      .collect::<Vec<_>>()
+      .into_iter()
+      .fold(entities, |entities, impact| {
+        entities.insert(impact.handle, Arc::new(impact))
+      })
+  } else {
+    entities
+  };
+
+  FrameState {
+    entities,
+    chain_tension,
+    chain_durability,
+    chain_broke,
+    ..frame
+  }
+}
+
+fn calculate_activation(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  let rigid_body_set = &frame.rigid_body_set;
+  let impulse_joint_set = &mut frame.impulse_joint_set;
+
+  /* MARK: Calculate activation for chain switches */
+  let entities = update_components(&frame.entities, |entity| {
+    let switch = entity.components.get::<Switch>()?;
+    entity.components.get::<Activator>()?;
+
+    let joint = impulse_joint_set.get(switch.joint).unwrap();
+    let prismatic = joint.data.as_prismatic().unwrap();
+
+    let activation = ((rigid_body_set[joint.body1].translation()
+      - rigid_body_set[joint.body2].translation())
+    .dot(&prismatic.local_axis1())
+      + 1.0)
+      / 2.0;
+
+    Some(entity.components.with(Activator { activation }))
+  });
+
+  /* Push each switch on towards the end it's nearer, once every activation is known */
+  entities.values().for_each(|entity| {
+    if let Some(switch) = entity.components.get::<Switch>()
+      && let Some(activator) = entity.components.get::<Activator>()
+    {
+      let joint = impulse_joint_set.get_mut(switch.joint, true).unwrap();
+      let prismatic = joint.data.as_prismatic_mut().unwrap();
+      let limits = prismatic.limits().unwrap();
+
+      prismatic.set_motor_position(
+        if activator.activation > 0.5 {
+          limits.min
+        } else {
+          limits.max
+        },
+        3.2,
+        2.0,
+      );
+
+      prismatic.set_motor_velocity(
+        if activator.activation > 0.5 {
+          -1.0
+        } else {
+          1.0
+        },
+        1.0,
+      );
+    }
+  });
+
+  /* MARK: Calculate activation for tension switches */
+  /* The joint impulses are still the ones the last step solved */
+  let dt = inputs.integration_parameters.dt;
+  let entities = update_components(&entities, |entity| {
+    let tension_switch = entity.components.get::<TensionSwitch>()?;
+    let EntityHandle::RigidBody(switch_handle) = entity.handle else {
+      return None;
+    };
+    let force = impulse_joint_set
+      .attached_joints(switch_handle)
+      .map(|(_, _, _, joint)| joint.impulses.xy().magnitude())
+      .sum::<f32>()
+      / dt;
+
+    Some(entity.components.with(Activator {
+      activation: (force / tension_switch.full_force).min(1.0),
+    }))
+  });
+
+  /* MARK: Calculate activation for touch sensors */
+  let entities = touch_sensor_pass(&entities, |handle| {
+    !handle
+      .intersecting_with_colliders(rigid_body_set, &frame.narrow_phase)
+      .is_empty()
+  });
+
+  /* MARK: Calculate activation for engines, SimpleActivatables, Ands, Ors and Gates */
+  let entities = logic_passes(&entities, inputs.time_scale);
+
+  /* MARK: Locomotor behavior */
+  entities.iter().for_each(|(_, entity)| {
+    if let Some(locomotor) = entity.components.get::<Locomotor>()
+      && let Some(activatable) = entity.components.get::<SimpleActivatable>()
+      && let Some(joint) = impulse_joint_set.get_mut(locomotor.joint, true)
+    {
+      let prismatic = joint.data.as_prismatic_mut().unwrap();
+
+      let prismatic_limits = prismatic.limits().unwrap();
+
+      let prismatic_limit_magnitude = prismatic_limits.max - prismatic_limits.min;
+
+      let target_ratio = activatable.activation * prismatic_limit_magnitude;
+
+      let motor_position = if locomotor.reverse_direction {
+        prismatic_limits.min + target_ratio
+      } else {
+        prismatic_limits.max - target_ratio
+      };
+
+      prismatic.set_motor_position(motor_position, 500.0, 400.0);
+    }
+  });
+
+  FrameState { entities, ..frame }
+}
+
+/* Gap between the rewards of a cleared encounter, laid out in a row */
+const ENCOUNTER_REWARD_SPACING: f32 = 0.8;
+
+fn run_encounters(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  let collider_set = &mut frame.collider_set;
+  let rigid_body_set = &frame.rigid_body_set;
+  let narrow_phase = &frame.narrow_phase;
+
+  let player_colliders = [Some(inputs.player_handle), frame.player_two_handle]
+    .into_iter()
+    .flatten()
+    .flat_map(|handle| rigid_body_set[handle].colliders().to_vec())
+    .collect::<Vec<_>>();
+
+  let alive = |handle: &EntityHandle| {
+    frame.entities.get(handle).is_some_and(|entity| {
+      entity.components.get::<Destroyed>().is_none() && entity.components.get::<Dying>().is_none()
+    })
+  };
+
+  /* MARK: Start fights the player walks into and finish those with no enemies left */
+  let updated = frame
+    .entities
+    .iter()
+    .filter_map(|(handle, entity)| {
+      let encounter = entity.components.get::<Encounter>()?;
+      let EntityHandle::Collider(collider_handle) = handle else {
+        return None;
+      };
+
+      let state = match encounter.state {
+        EncounterState::Waiting
+          if player_colliders.iter().any(|player_collider| {
+            narrow_phase
+              .intersection_pair(*collider_handle, *player_collider)
+              .unwrap_or(false)
+          }) =>
+        {
+          EncounterState::Fighting
+        }
+        EncounterState::Fighting if !encounter.enemies.iter().any(alive) => EncounterState::Cleared,
+        _ => return None,
+      };
+
+      /* Whatever is in the arena as the fight starts has to be beaten */
+      let enemies = if state == EncounterState::Fighting {
+        let arena = &collider_set[*collider_handle];
+
+        frame
+          .entities
+          .iter()
+          .filter(|(enemy_handle, enemy)| {
+            enemy.components.get::<Enemy>().is_some() && alive(enemy_handle)
+          })
+          .filter(|(enemy_handle, _)| {
+            arena.shape().contains_point(
+              arena.position(),
+              &(*enemy_handle.translation(rigid_body_set, collider_set)).into(),
+            )
+          })
+          .map(|(enemy_handle, _)| *enemy_handle)
+          .collect()
+      } else {
+        encounter.enemies.clone()
+      };
+
+      Some((
+        *handle,
+        Encounter {
+          state,
+          enemies,
+          ..encounter.as_ref().clone()
+        },
+      ))
+    })
+    .collect::<Vec<_>>();
+
+  /* MARK: Hand out the rewards of cleared encounters and remember them */
+  let cleared = updated
+    .iter()
+    .filter(|(_, encounter)| encounter.state == EncounterState::Cleared)
+    .collect::<Vec<_>>();
+
+  let rewards = cleared
+    .iter()
+    .flat_map(|(handle, encounter)| {
+      let center = *handle.translation(rigid_body_set, collider_set);
+      let first_offset = (encounter.rewards.len() as f32 - 1.0) / 2.0;
+
+      encounter
+        .rewards
+        .iter()
+        .enumerate()
+        .map(move |(index, drop)| {
+          (
+            *drop,
+            center
+              + vector![
+                (index as f32 - first_offset) * ENCOUNTER_REWARD_SPACING,
+                0.0
+              ],
+          )
+        })
+        .collect::<Vec<_>>()
+    })
+    .collect::<Vec<_>>()
+    .into_iter()
+    .filter_map(|(drop, translation)| spawn_loot(drop, 0, translation, collider_set))
+    .collect::<Vec<_>>();
+
+  let map_states = cleared.iter().fold(
+    Rc::clone(&frame.map_states),
+    |map_states, (_, encounter)| {
+      let map_state = map_states.get(inputs.map_name).cloned().unwrap_or_default();
+      Rc::new(map_states.insert(
+        inputs.map_name.to_string(),
+        MapState {
+          cleared_encounters: [map_state.cleared_encounters, vec![encounter.id]].concat(),
+          ..map_state
+        },
+      ))
+    },
+  );
+
+  let entities = updated
+    .iter()
+    .fold(frame.entities.clone(), |entities, (handle, encounter)| {
+      let entity = Arc::clone(&entities[handle]);
+      entities.insert(
+        *handle,
+        Arc::new(Entity {
+          components: entity.components.with(encounter.clone()),
+          ..entity.as_ref().clone()
+        }),
+      )
+    });
+
+  let entities = rewards.into_iter().fold(entities, |entities, reward| {
+    entities.insert(reward.handle, Arc::new(reward))
+  });
+
+  /* MARK: Hold the doors of fights in progress shut, over whatever opened them */
+  let closed = entities
+    .values()
+    .filter_map(|entity| entity.components.get::<Encounter>())
+    .filter(|encounter| encounter.state == EncounterState::Fighting)
+    .flat_map(|encounter| {
+      encounter
+        .gate_ids
+        .iter()
+        .map(|gate_id| (*gate_id, encounter.closed_activation))
+        .collect::<Vec<_>>()
+    })
+    .collect::<HashMap<_, _>>();
+
+  let entities = entities
+    .iter()
+    .map(|(&handle, entity)| {
+      if let Some(id) = entity.components.get::<Id>()
+        && let Some(activation) = closed.get(&id.id)
+        && entity.components.get::<Activator>().is_some()
+      {
+        (
+          handle,
+          Arc::new(Entity {
+            components: entity.components.with(Activator {
+              activation: *activation,
+            }),
+            ..entity.as_ref().clone()
+          }),
+        )
+      } else {
+        (handle, Arc::clone(entity))
+      }
+    })
+    .collect();
+
+  FrameState {
+    entities,
+    map_states,
+    encounter_cleared: !cleared.is_empty(),
+    ..frame
+  }
+}
+
+fn enforce_entity_caps(frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  /* MARK: Mark whatever is over each cap, least important and oldest first */
+  let over_cap = [
+    CapCategory::PlayerProjectile,
+    CapCategory::EnemyProjectile,
+    CapCategory::Particle,
+    CapCategory::Debris,
+  ]
+  .into_iter()
+  .flat_map(|category| {
+    let mut capped = frame
+      .entities
+      .iter()
+      .filter(|(_, entity)| entity.components.get::<Destroyed>().is_none())
+      .filter_map(|(handle, entity)| {
+        let capped = entity.components.get::<Capped>()?;
+        (capped.category == category).then_some((*handle, capped))
+      })
+      .collect::<Vec<_>>();
+
+    capped.sort_by_key(|(_, capped)| (Reverse(capped.priority), Reverse(capped.spawned_frame)));
+
+    capped
+      .into_iter()
+      .skip(inputs.entity_limits.for_category(category))
+      .map(|(handle, _)| handle)
+      .collect::<Vec<_>>()
+  })
+  .collect::<Vec<_>>();
+
+  let entities = over_cap
+    .into_iter()
+    .fold(frame.entities.clone(), |entities, handle| {
+      let entity = Arc::clone(&entities[&handle]);
+      entities.insert(
+        handle,
+        Arc::new(Entity {
+          components: entity.components.insert(Destroyed),
+          ..entity.as_ref().clone()
+        }),
+      )
+    });
+
+  FrameState { entities, ..frame }
+}
+
+fn clean_up_destroyed(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  /* MARK: Count the enemies killed this frame, leaving out the player's own hacked ones */
+  let new_kills = frame
+    .entities
+    .values()
+    .filter(|entity| {
+      entity.components.get::<Destroyed>().is_some()
+        && entity.components.get::<Enemy>().is_some()
+        && Faction::Player.hostile_to(Faction::of(&entity.components), false)
+    })
+    .count() as i32;
+
+  /* MARK: Remember persistent entities destroyed this frame */
+  let map_states = frame
+    .entities
+    .values()
+    .filter(|entity| entity.components.get::<Destroyed>().is_some())
+    .filter_map(|entity| {
+      entity
+        .components
+        .get::<Persistent>()
+        .map(|persistent| (persistent.id, entity.components.get::<Enemy>().is_some()))
+    })
+    .fold(Rc::clone(&frame.map_states), |map_states, (id, enemy)| {
+      let map_state = map_states.get(inputs.map_name).cloned().unwrap_or_default();
+      let map_state = if enemy {
+        MapState {
+          killed_enemies: [map_state.killed_enemies, vec![id]].concat(),
+          ..map_state
+        }
+      } else {
+        MapState {
+          destroyed_walls: [map_state.destroyed_walls, vec![id]].concat(),
+          ..map_state
+        }
+      };
+      Rc::new(map_states.insert(inputs.map_name.to_string(), map_state))
+    });
+
+  /* MARK: Leave corpses for entities with a death animation, removing them once it ends */
+  let entities = frame
+    .entities
+    .iter()
+    .map(|(&handle, entity)| {
+      let components = &entity.components;
+      let components = match (
+        components.get::<Dying>(),
+        components.get::<Destroyed>(),
+        components.get::<DeathAnimation>(),
+      ) {
+        (Some(dying), _, _) if dying.frames_remaining > 0 => components.with(Dying {
+          frames_remaining: dying.frames_remaining - inputs.whole_frames(),
+          ..*dying
+        }),
+        (Some(_), _, _) => components.insert(Destroyed),
+        (None, Some(_), Some(death_animation))
+          if death_animation.frames > 0
+            && handle != EntityHandle::RigidBody(inputs.player_handle) =>
+        {
+          handle
+            .colliders(&frame.rigid_body_set)
+            .into_iter()
+            .copied()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .for_each(|collider_handle| frame.collider_set[collider_handle].set_enabled(false));
+
+          components
+            .without::<Destroyed>()
+            .without::<Enemy>()
+            .without::<Damager>()
+            .without::<Damageable>()
+            .insert(Dying {
+              frames_remaining: death_animation.frames,
+              frames: death_animation.frames,
+            })
+        }
+        _ => return (handle, Arc::clone(entity)),
+      };
+
+      (
+        handle,
+        Arc::new(Entity {
+          components,
+          ..entity.as_ref().clone()
+        }),
+      )
+    })
+    .collect::<Entities>();
+
+  /* MARK: Remove destroyed entities */
+  let entities = entities
+    .into_iter()
+    .filter_map(|(&handle, entity)| {
+      if entity.components.get::<Destroyed>().is_none()
+        || handle == EntityHandle::RigidBody(inputs.player_handle)
+      {
+        return Some((handle, Arc::clone(entity)));
+      }
+
+      frame.remove_body(entity.handle);
+      None
+    })
+    .collect::<Entities>();
+
+  /* MARK: Find all mount points in range */
+  let mount_points_in_range = entities
+    .iter()
+    .flat_map(|(handle, entity)| {
+      entity
+        .components
+        .get::<ChainMountArea>()
+        .into_iter()
+        .filter_map(|chain_mount_activation| {
+          if !handle
+            .intersecting_with_colliders(&frame.rigid_body_set, &frame.narrow_phase)
+            .is_empty()
+          {
+            Some(chain_mount_activation.target_mount_body)
+          } else {
+            None
+          }
+        })
+    })
+    .collect::<List<_>>();
+
+  FrameState {
+    entities,
+    mount_points_in_range,
+    map_states,
+    new_kills,
+    ..frame
+  }
+}
+
+fn step_physics(mut frame: FrameState, inputs: &FrameInputs) -> FrameState {
+  inputs.physics_pipeline.borrow_mut().step(
+    &vector![0.0, 0.0],
+    &inputs.integration_parameters,
+    &mut frame.island_manager,
+    &mut frame.broad_phase,
+    &mut frame.narrow_phase,
+    &mut frame.rigid_body_set,
+    &mut frame.collider_set,
+    &mut frame.impulse_joint_set,
+    &mut frame.multibody_joint_set,
+    &mut frame.ccd_solver,
+    &ContactRules,
+    &(),
+  );
+
+  frame
+}
+
+fn player_movement_impulse(
+  left_stick: PhysicsVector,
+  player: &RigidBody,
+  movement: &MovementStats,
+) -> Vector<f32> {
+  let attempted_acceleration = left_stick.into_vec() * movement.acceleration;
+  let player_mass = player.mass();
+  let player_velocity = player.linvel();
+  let velocity_change = attempted_acceleration * player_mass;
+
+  /* Braking and turning always get the full acceleration */
+  let safe_acceleration_x = if attempted_acceleration.x == 0.0
+    || velocity_change.x.signum() != player_velocity.x.signum()
+  {
+    attempted_acceleration.x
+  } else {
+    attempted_acceleration.x * movement.acceleration_taper(player_velocity.x.abs())
+  };
+
+  let safe_acceleration_y = if attempted_acceleration.y == 0.0
+    || velocity_change.y.signum() != player_velocity.y.signum()
+  {
+    attempted_acceleration.y
+  } else {
+    attempted_acceleration.y * movement.acceleration_taper(player_velocity.y.abs())
+  };
+
+  vector![safe_acceleration_x, safe_acceleration_y]
+}
+
+/* Pushes the damaged entity away from the damager, along the contact normal when
+there is one and otherwise away from the damager's center (e.g. sensors) */
+fn knockback_direction(
+  handle: &EntityHandle,
+  damager_collider_handle: ColliderHandle,
+  rigid_body_set: &RigidBodySet,
+  narrow_phase: &NarrowPhase,
+  collider_set: &ColliderSet,
+) -> Vector<f32> {
+  let contact_normal = handle
+    .colliders(rigid_body_set)
+    .iter()
+    .find_map(|&&collider_handle| {
+      let contact_pair = narrow_phase.contact_pair(collider_handle, damager_collider_handle)?;
+      let manifold = contact_pair
+        .manifolds
+        .iter()
+        .find(|manifold| !manifold.data.solver_contacts.is_empty())?;
+
+      /* The manifold normal points from collider1 towards collider2 */
+      Some(if contact_pair.collider1 == damager_collider_handle {
+        manifold.data.normal
+      } else {
+        -manifold.data.normal
+      })
+    });
+
+  let direction = contact_normal.unwrap_or(
+    handle.translation(rigid_body_set, collider_set)
+      - collider_set[damager_collider_handle].translation(),
+  );
+
+  if direction.magnitude() > 0.0 {
+    direction.normalize()
+  } else {
+    vector![0.0, 0.0]
+  }
+}
+
+/* Works out the damage each entity takes without touching the world, handing back the
+knockback impulse for the caller to apply */
+#[allow(clippy::too_many_arguments)]
+fn damage_taken<'a>(
+  rigid_body_set: &'a RigidBodySet,
+  narrow_phase: &'a NarrowPhase,
+  collider_set: &'a ColliderSet,
+  entities: &'a Entities,
+  time_scale: f32,
+  whole_frames: i32,
+  friendly_fire: bool,
+  player_assist: Assist,
+  player_handles: &'a [EntityHandle],
+) -> impl Fn(&Entity) -> Option<(ComponentSet, Vector<f32>)> + Sync + 'a {
+  move |entity| {
+    let handle = entity.handle;
+    let damageable = entity.components.get::<Damageable>()?;
+
+    let decrement_hitstun = |damageable: &Damageable| Damageable {
+      current_hitstun: damageable.current_hitstun - time_scale,
+      ..*damageable
+    };
+
+    if let Some(protection) = entity.components.get::<SpawnProtection>() {
+      let frames_left = protection.frames_left - whole_frames;
+      let components = if frames_left <= 0 {
+        entity.components.without::<SpawnProtection>()
+      } else {
+        entity.components.with(SpawnProtection {
+          frames_left,
+          ..*protection
+        })
+      };
+
+      return Some((components, vector![0.0, 0.0]));
+    }
+
+    if damageable.current_hitstun > 0.0 {
+      return Some((
+        entity.components.update(decrement_hitstun),
+        vector![0.0, 0.0],
+      ));
+    }
+
+    let faction = Faction::of(&entity.components);
+
+    let damagers = entity
+      .handle
+      .intersecting_with_colliders(rigid_body_set, narrow_phase)
+      .into_iter()
+      .flat_map(|&collider_handle| {
+        collider_set[collider_handle]
+          .parent()
+          .and_then(|rigid_body_handle| entities.get(&EntityHandle::RigidBody(rigid_body_handle)))
+          .and_then(|entity| {
+            let damager = entity.components.get::<Damager>()?;
+            let damager_faction = Faction::of(&entity.components);
+            let reaches = if entity.components.get::<SelfBlast>().is_some() {
+              damager_faction == faction
+            } else {
+              damager_faction.hostile_to(faction, friendly_fire)
+            };
+
+            (damager.source != Some(handle) && reaches).then_some(damager)
+          })
+          .map(|damager| (collider_handle, damager))
+      })
+      .collect::<Vec<_>>();
+
+    let incoming_damage = damagers
+      .iter()
+      .fold(0.0, |sum, (_, damager)| sum + damager.damage);
+
+    if incoming_damage == 0.0 {
+      if damageable.current_hitstun > 0.0 {
+        return Some((
+          entity.components.update(decrement_hitstun),
+          vector![0.0, 0.0],
+        ));
+      }
+
+      return None;
+    }
+
+    /* Assist mode softens hits on the players and nothing else */
+    let assist = if player_handles.contains(&handle) {
+      player_assist
+    } else {
+      Assist::default()
+    };
+
+    let knockback = damagers
+      .iter()
+      .filter(|(_, damager)| damager.knockback != 0.0 && !assist.no_knockback)
+      .map(|(damager_collider_handle, damager)| {
+        knockback_direction(
+          &handle,
+          *damager_collider_handle,
+          rigid_body_set,
+          narrow_phase,
+          collider_set,
+        ) * damager.knockback
+      })
+      .fold(vector![0.0, 0.0], |sum, impulse| sum + impulse);
+
+    let components = entity.components.with(Damageable {
+      health: damageable.health - incoming_damage * assist.damage_taken.factor(),
+      current_hitstun: damageable.max_hitstun,
+      ..*damageable
+    });
+
+    let on_hit_statuses = damagers
+      .iter()
+      .filter_map(|(_, damager)| damager.on_hit_status)
+      .collect::<Vec<_>>();
+
+    let components = if on_hit_statuses.is_empty() {
+      components
+    } else {
+      let status_effects = entity
+        .components
+        .get::<StatusEffects>()
+        .map(|status_effects| status_effects.as_ref().clone())
+        .unwrap_or_default();
+
+      let status_effects = on_hit_statuses
+        .iter()
+        .fold(status_effects, |status_effects, status| {
+          status_effects.apply(status)
+        });
+
+      if entity.components.get::<StatusEffects>().is_some() {
+        components.with(status_effects)
+      } else {
+        components.insert(status_effects)
+      }
+    };
+
+    Some((components, knockback))
+  }
+}
+
+const IMPACT_SPARK_FRAMES: i32 = 10;
+const IMPACT_SCORCH_FRAMES: i32 = 300;
+const IMPACT_CRITICAL_FRAMES: i32 = 20;
+
+/* Finds where the entity touched something this frame and leaves a short lived
+marker there, oriented away from the surface it hit */
+/* Reflective colliders in contact with the entity, each with the normal pointing out of it */
+fn reflective_contacts(
+  handle: &EntityHandle,
+  reflective_colliders: &HashSet<ColliderHandle>,
+  narrow_phase: &NarrowPhase,
+  rigid_body_set: &RigidBodySet,
+) -> Vec<(ColliderHandle, Vector<f32>)> {
+  handle
+    .colliders(rigid_body_set)
+    .iter()
+    .flat_map(|&&collider_handle| {
+      narrow_phase
+        .contact_pairs_with(collider_handle)
+        .filter(|contact_pair| contact_pair.has_any_active_contact)
+        .filter_map(move |contact_pair| {
+          let manifold = contact_pair
+            .manifolds
+            .iter()
+            .find(|manifold| !manifold.data.solver_contacts.is_empty())?;
+
+          /* The manifold normal points from collider1 towards collider2 */
+          let (other_handle, normal) = if contact_pair.collider1 == collider_handle {
+            (contact_pair.collider2, -manifold.data.normal)
+          } else {
+            (contact_pair.collider1, manifold.data.normal)
+          };
+
+          reflective_colliders
+            .contains(&other_handle)
+            .then_some((other_handle, normal))
+        })
+    })
+    .collect()
+}
+
+fn spawn_impact(
+  handle: &EntityHandle,
+  critical: bool,
+  frame_count: i64,
+  narrow_phase: &NarrowPhase,
+  collider_set: &ColliderSet,
+  rigid_body_set: &mut RigidBodySet,
+) -> Option<Entity> {
+  let (point, normal, hit_wall) =
+    handle
+      .colliders(rigid_body_set)
+      .iter()
+      .find_map(|&&collider_handle| {
+        narrow_phase
+          .contact_pairs_with(collider_handle)
+          .filter(|contact_pair| contact_pair.has_any_active_contact)
+          .find_map(|contact_pair| {
+            let manifold = contact_pair
+              .manifolds
+              .iter()
+              .find(|manifold| !manifold.data.solver_contacts.is_empty())?;
+
+            /* The manifold normal points from collider1 towards collider2 */
+            let (other_handle, normal) = if contact_pair.collider1 == collider_handle {
+              (contact_pair.collider2, -manifold.data.normal)
+            } else {
+              (contact_pair.collider1, manifold.data.normal)
+            };
+
+            let hit_wall = collider_set[other_handle]
+              .collision_groups()
+              .memberships
+              .contains(CollisionLayer::Wall.group());
+
+            Some((
+              manifold.data.solver_contacts[0].point.coords,
+              normal,
+              hit_wall,
+            ))
+          })
+      })?;
+
+  let kind = if hit_wall {
+    ImpactKind::Scorch
+  } else if critical {
+    ImpactKind::Critical
+  } else {
+    ImpactKind::Spark
+  };
+
+  let frames = match kind {
+    ImpactKind::Scorch => IMPACT_SCORCH_FRAMES,
+    ImpactKind::Spark => IMPACT_SPARK_FRAMES,
+    ImpactKind::Critical => IMPACT_CRITICAL_FRAMES,
+  };
+
+  let rigid_body_handle = rigid_body_set.insert(RigidBodyBuilder::fixed().translation(point));
+
+  Some(Entity {
+    handle: EntityHandle::RigidBody(rigid_body_handle),
+    components: ComponentSet::new()
+      .insert(Impact {
+        normal,
+        kind,
+        lifetime: frames,
+      })
+      .insert(DestroyAfterFrames { frames })
+      .insert(Capped {
+        category: CapCategory::Particle,
+        priority: (kind == ImpactKind::Critical) as i32,
+        spawned_frame: frame_count,
+      })
+      .insert(EntityKind::Impact),
+  })
+}
+
+/* Where a player coming in from the given room stands: just inside from the door back to it
+nearest the spawn point, on the line between the two. Also gives the center of that door */
+fn entry_door(
+  map: &Map,
+  from_map_name: &str,
+  spawn_translation: Vector<f32>,
+) -> Option<(Vector<f32>, Vector<f32>)> {
+  let distance_to = |collider: &Collider| (collider.translation() - spawn_translation).magnitude();
+  let door = map
+    .map_transitions
+    .iter()
+    .filter(|map_transition| map_transition.map_name == from_map_name)
+    .min_by(|a, b| distance_to(&a.collider).total_cmp(&distance_to(&b.collider)))?;
+
+  let door_center = *door.collider.translation();
+  let distance = distance_to(&door.collider);
+  if distance == 0.0 {
+    return None;
+  }
+  let direction = (door_center - spawn_translation) / distance;
+
+  let time_of_impact = door.collider.shape().cast_ray(
+    door.collider.position(),
+    &Ray::new(Point::from(spawn_translation), direction),
+    distance,
+    true,
+  )?;
+
+  Some((
+    spawn_translation + direction * (time_of_impact - ENTRY_CLEARANCE).max(0.0),
+    door_center,
+  ))
+}
+
+/* Whether the player would be inside of a wall, enemy or hazard at the given translation. Sensors
+the player is meant to stand in, like save points and transitions, don't count */
+fn spawn_blocked(
+  player_handle: RigidBodyHandle,
+  translation: Vector<f32>,
+  rigid_body_set: &RigidBodySet,
+  collider_set: &ColliderSet,
+) -> bool {
+  let player_collider = &collider_set[rigid_body_set[player_handle].colliders()[0]];
+  let position = Isometry::new(translation, 0.0);
+
+  collider_set.iter().any(|(_, collider)| {
+    collider.parent() != Some(player_handle)
+      && collider
+        .collision_groups()
+        .test(player_collider.collision_groups())
+      && !collider
+        .collision_groups()
+        .memberships
+        .contains(CollisionLayer::PlayerInteractible.group())
+      && intersection_test(
+        &position,
+        player_collider.shape(),
+        collider.position(),
+        collider.shape(),
+      )
+      .unwrap_or(false)
+  })
+}
+
+/* The nearest translation to the player's own that they aren't stuck inside of anything at */
+fn clear_spawn_translation(
+  player_handle: RigidBodyHandle,
+  rigid_body_set: &RigidBodySet,
+  collider_set: &ColliderSet,
+) -> Vector<f32> {
+  let translation = *rigid_body_set[player_handle].translation();
+  let rings = (SPAWN_NUDGE_MAX_DISTANCE / SPAWN_NUDGE_STEP) as usize;
+
+  iter::once(translation)
+    .chain((1..=rings).flat_map(|ring| {
+      (0..SPAWN_NUDGE_DIRECTIONS).map(move |direction| {
+        let angle = direction as f32 / SPAWN_NUDGE_DIRECTIONS as f32 * 2.0 * PI;
+        translation + vector![angle.cos(), angle.sin()] * ring as f32 * SPAWN_NUDGE_STEP
+      })
+    }))
+    .find(|&candidate| !spawn_blocked(player_handle, candidate, rigid_body_set, collider_set))
+    .unwrap_or_else(|| {
+      log::warn(
+        "physics",
+        "couldn't find anywhere clear to spawn the player".to_string(),
+      );
+      translation
+    })
+}
+
+fn spawn_player(
+  translation: Vector<f32>,
+  health: f32,
+  max_health: f32,
+  name: &str,
+  collider_set: &mut ColliderSet,
+  rigid_body_set: &mut RigidBodySet,
+) -> (RigidBodyHandle, Entity) {
+  let mut player_rigid_body = RigidBodyBuilder::dynamic().translation(translation).build();
+  player_rigid_body.wake_up(true);
+  let mut player_collider = ColliderBuilder::ball(0.25)
+    .collision_groups(groups(CollisionLayer::Player))
+    .build();
+  pass_through_allies(&mut player_collider, Faction::Player);
+  let player_handle = rigid_body_set.insert(player_rigid_body);
+  collider_set.insert_with_parent(player_collider, player_handle, rigid_body_set);
+
+  (
+    player_handle,
+    Entity {
+      handle: EntityHandle::RigidBody(player_handle),
+      components: ComponentSet::new()
+        .insert(Damageable {
+          health,
+          max_health,
+          destroy_on_zero_health: false,
+          current_hitstun: 0.0,
+          max_hitstun: PLAYER_MAX_HITSTUN,
+        })
+        .insert(Faction::Player)
+        .insert(EntityKind::Player)
+        .insert(SpawnProtection {
+          frames_left: SPAWN_PROTECTION_FRAMES,
+          frames: SPAWN_PROTECTION_FRAMES,
+        })
+        .insert(Name {
+          name: name.to_string(),
+        }),
+    },
+  )
+}
+
+fn spawn_player_projectile(
+  projectile: &Projectile,
+  translation: Vector<f32>,
+  linvel: Vector<f32>,
+  frame_count: i64,
+  collider_set: &mut ColliderSet,
+  rigid_body_set: &mut RigidBodySet,
+) -> (EntityHandle, Arc<Entity>) {
+  let handle = rigid_body_set.insert(
+    RigidBodyBuilder::dynamic()
+      .translation(translation)
+      .ccd_enabled(projectile.ccd),
+  );
+  collider_set.insert_with_parent(projectile.collider.clone(), handle, rigid_body_set);
+
+  rigid_body_set[handle].set_linvel(linvel, true);
+
+  rigid_body_set[handle].apply_impulse(projectile.initial_impulse.into_vec(), true);
+  rigid_body_set[handle].add_force(
+    projectile.initial_impulse.into_vec().normalize() * projectile.force_mod,
+    true,
+  );
+
+  let handle = EntityHandle::RigidBody(handle);
+
+  (
+    handle,
+    Arc::new(Entity {
+      handle,
+      components: projectile
+        .component_set
+        .insert(DestroyOnCollision)
+        .insert(Damager {
+          damage: projectile.damage,
+          knockback: 0.0,
+          on_hit_status: projectile.on_hit_status,
+          source: None,
+        })
+        .insert(Faction::Player)
+        .insert(Capped {
+          category: CapCategory::PlayerProjectile,
+          priority: 0,
+          spawned_frame: frame_count,
+        })
+        .insert(EntityKind::PlayerProjectile),
+    }),
+  )
+}
+
+const DRONE_ORBIT_RADIUS: f32 = 1.5;
+/* Radians per frame */
+const DRONE_ORBIT_SPEED: f32 = 0.03;
+const DRONE_RANGE: f32 = 8.0;
+
+fn spawn_drone(
+  index: usize,
+  translation: Vector<f32>,
+  collider_set: &mut ColliderSet,
+  rigid_body_set: &mut RigidBodySet,
+) -> (EntityHandle, Arc<Entity>) {
+  let rigid_body_handle =
+    rigid_body_set.insert(RigidBodyBuilder::kinematic_position_based().translation(translation));
+  /* The drone itself never touches anything, it only fires */
+  collider_set.insert_with_parent(
+    ColliderBuilder::ball(0.2)
+      .collision_groups(InteractionGroups {
+        memberships: Group::NONE,
+        filter: Group::NONE,
+        ..Default::default()
+      })
+      .sensor(true),
+    rigid_body_handle,
+    rigid_body_set,
+  );
+
+  let handle = EntityHandle::RigidBody(rigid_body_handle);
+
+  (
+    handle,
+    Arc::new(Entity {
+      handle,
+      components: ComponentSet::new()
+        .insert(Drone {
+          index,
+          cooldown: 0.0,
+        })
+        .insert(EntityKind::Drone),
+    }),
+  )
+}
+
+const DEBRIS_PIECES: usize = 6;
+const DEBRIS_FRAMES: i32 = 90;
+const DEBRIS_SIZE: f32 = 0.08;
+/* Top speed a piece of debris flies off at, in a random direction */
+const DEBRIS_SPEED: f32 = 4.0;
+
+fn spawn_debris(
+  translation: Vector<f32>,
+  frame_count: i64,
+  rng: &rand::RandGenerator,
+  collider_set: &mut ColliderSet,
+  rigid_body_set: &mut RigidBodySet,
+) -> Entity {
+  let offset = vector![rng.gen_range(-0.2, 0.2), rng.gen_range(-0.2, 0.2)];
+  let velocity = Rotation::new(rng.gen_range(0.0, 2.0 * PI))
+    * vector![rng.gen_range(0.25, 1.0) * DEBRIS_SPEED, 0.0];
+
+  let rigid_body_handle = rigid_body_set.insert(
+    RigidBodyBuilder::dynamic()
+      .translation(translation + offset)
+      .linvel(velocity)
+      .angvel(rng.gen_range(-10.0, 10.0))
+      .linear_damping(1.0),
+  );
+  collider_set.insert_with_parent(
+    ColliderBuilder::cuboid(DEBRIS_SIZE, DEBRIS_SIZE)
+      .collision_groups(groups(CollisionLayer::Debris)),
+    rigid_body_handle,
+    rigid_body_set,
+  );
+
+  Entity {
+    handle: EntityHandle::RigidBody(rigid_body_handle),
+    components: ComponentSet::new()
+      .insert(DestroyAfterFrames {
+        frames: DEBRIS_FRAMES,
+      })
+      .insert(DespawnTimer {
+        warning_frames: DEBRIS_FRAMES / 3,
+      })
+      .insert(Capped {
+        category: CapCategory::Debris,
+        priority: 0,
+        spawned_frame: frame_count,
+      })
+      .insert(EntityKind::Debris),
+  }
+}
+
+const EXPLOSION_KNOCKBACK: f32 = 0.5;
+/* Fraction of its damage a blast does to the side that set it off */
+const SELF_BLAST_DAMAGE: f32 = 0.25;
+const SELF_BLAST_KNOCKBACK: f32 = 4.0;
+
+/* The blast that damages and the one that pushes are separate bodies, so each can reach its own
+groups. Blasts that catch their own side get a third, doing the reduced self damage */
+fn spawn_explosion(
+  translation: Vector<f32>,
+  explosion: &ExplodeOnCollision,
+  faction: Faction,
+  collider_set: &mut ColliderSet,
+  rigid_body_set: &mut RigidBodySet,
+) -> Vec<Entity> {
+  let mut blast = |interaction_groups: InteractionGroups, components: ComponentSet| {
+    let rigid_body_handle =
+      rigid_body_set.insert(RigidBodyBuilder::dynamic().translation(translation));
+    collider_set.insert_with_parent(
+      ColliderBuilder::ball(explosion.radius)
+        .collision_groups(interaction_groups)
+        .enabled(true)
+        .sensor(true),
+      rigid_body_handle,
+      rigid_body_set,
+    );
+
+    Entity {
+      handle: EntityHandle::RigidBody(rigid_body_handle),
+      components: components
+        .insert(DestroyAfterFrames { frames: 5 })
+        .insert(EntityKind::Explosion),
+    }
+  };
+
+  let damage = blast(
+    explosion.interaction_groups,
+    ComponentSet::new()
+      .insert(Damager {
+        damage: explosion.damage,
+        knockback: EXPLOSION_KNOCKBACK,
+        on_hit_status: None,
+        source: None,
+      })
+      .insert(faction),
+  );
+  let force = blast(
+    explosion.force_groups,
+    ComponentSet::new().insert(GravitySource {
+      strength: explosion.strength,
+      activator_id: None,
+    }),
+  );
+
+  if explosion.self_blast {
+    let self_damage = blast(
+      groups(CollisionLayer::SelfBlast),
+      ComponentSet::new()
+        .insert(Damager {
+          damage: explosion.damage * SELF_BLAST_DAMAGE,
+          knockback: SELF_BLAST_KNOCKBACK,
+          on_hit_status: None,
+          source: None,
+        })
+        .insert(faction)
+        .insert(SelfBlast),
+    );
+    vec![damage, force, self_damage]
+  } else {
+    vec![damage, force]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::OnceLock;
+
+  use super::*;
+  use crate::{
+    combat::{WeaponModule, WeaponModuleKind, get_slot_positions, weapon_module_from_kind},
+    data::{GameData, try_load_game_data},
+    ecs::{
+      Bounces, EliteAffix, HazardKind, LootEntry, MagnetLatch, Magnetic, OnHitStatus, Reflective,
+      StatusEffectKind,
+    },
+    enemy::EnemySwarmer,
+    load_map::{EnemySpawn, MapEnemyName, barrel_groups},
+  };
+
+  /* MARK: Harness */
+
+  fn stage(name: &str) -> PhysicsStage {
+    PHYSICS_STAGES
+      .iter()
+      .find(|(stage_name, _)| *stage_name == name)
+      .map(|(_, stage)| *stage)
+      .unwrap()
+  }
+
+  /* A world holding only the player, at the origin with full health and past their spawn
+  protection */
+  fn frame_with_player() -> (FrameState, RigidBodyHandle) {
+    let mut rigid_body_set = RigidBodySet::new();
+    let mut collider_set = ColliderSet::new();
+
+    let (player_handle, player) = spawn_player(
+      vector![0.0, 0.0],
+      100.0,
+      100.0,
+      "player",
+      &mut collider_set,
+      &mut rigid_body_set,
+    );
+
+    (
+      FrameState {
+        rigid_body_set,
+        collider_set,
+        island_manager: IslandManager::new(),
+        broad_phase: DefaultBroadPhase::new(),
+        narrow_phase: NarrowPhase::new(),
+        impulse_joint_set: ImpulseJointSet::new(),
+        multibody_joint_set: MultibodyJointSet::new(),
+        ccd_solver: CCDSolver::new(),
+        entities: HashTrieMap::new_sync().insert(
+          player.handle,
+          Arc::new(Entity {
+            components: player.components.without::<SpawnProtection>(),
+            ..player
+          }),
+        ),
+        player_two_handle: None,
+        player_two_joined: false,
+        new_weapon_modules: list![],
+        new_abilities: list![],
+        new_logs: list![],
+        new_engine_upgrades: list![],
+        new_credits: 0,
+        new_power_ups: list![],
+        new_kills: 0,
+        encounter_cleared: false,
+        load_new_map: None,
+        save_point_contact: None,
+        mount_points_in_range: list![],
+        mounted_turret: None,
+        turret_in_range: false,
+        chain_tension: 0.0,
+        chain_durability: 0.0,
+        chain_broke: false,
+        map_states: Rc::new(HashTrieMap::new()),
+        streamed_chunks: Rc::new(HashTrieMap::new()),
+      },
+      player_handle,
+    )
+  }
+
+  /* Read from the crate's own assets, once for every test */
+  fn game_data() -> &'static GameData {
+    static GAME_DATA: OnceLock<GameData> = OnceLock::new();
+    GAME_DATA.get_or_init(|| try_load_game_data().unwrap())
+  }
+
+  thread_local! {
+    /* Each test gets a thread of its own, and with it a pipeline of its own */
+    static PIPELINE: &'static RefCell<PhysicsPipeline> =
+      Box::leak(Box::new(RefCell::new(PhysicsPipeline::new())));
+  }
+
+  /* A frame where nothing is held or fired, tests override what they need with struct update
+  syntax */
+  fn inputs(player_handle: RigidBodyHandle) -> FrameInputs<'static> {
+    FrameInputs {
+      frame_count: 0,
+      simulated_frames: 0.0,
+      time_scale: 1.0,
+      whole_frame: true,
+      player_handle,
+      map_name: "test",
+      left_stick: PhysicsVector::from_vec(vector![0.0, 0.0]),
+      coop_gamepad_claimed: false,
+      coop_left_stick: PhysicsVector::from_vec(vector![0.0, 0.0]),
+      saved_player_two_health: None,
+      boost_force: None,
+      interact: false,
+      dismount: false,
+      projectiles: &[],
+      player_two_projectiles: &[],
+      drone_specs: &[],
+      enemy_decisions: &[],
+      enemy_definitions: &game_data().enemies,
+      entity_limits: EntityLimits {
+        player_projectiles: 100,
+        enemy_projectiles: 100,
+        particles: 100,
+        debris: 100,
+      },
+      movement: MovementStats {
+        acceleration: 0.25,
+        speed_limit: 2.5,
+        acceleration_curve: 8.0,
+        linear_damping: 0.0,
+        boost_charges: 2,
+        boost_recharge_frames: 240.0,
+      },
+      difficulty: Difficulty::default(),
+      chain_to_mount_point: None,
+      kill_chain: false,
+      magnetize: false,
+      hack: None,
+      assist: Assist::default(),
+      physics_pipeline: PIPELINE.with(|pipeline| *pipeline),
+      integration_parameters: IntegrationParameters::default(),
+      cycle_phase: None,
+      friendly_fire: false,
+      chunks: Rc::new(HashTrieMap::new()),
+    }
+  }
+
+  /* Builds up a test frame and runs stages on it */
+  trait TestFrame {
+    fn with_entity(self, handle: EntityHandle, components: ComponentSet) -> Self;
+    fn insert_body(&mut self, translation: Vector<f32>) -> RigidBodyHandle;
+    fn insert_sensor(&mut self, translation: Vector<f32>) -> ColliderHandle;
+    fn components(&self, handle: EntityHandle) -> ComponentSet;
+    fn run(self, stage_name: &str, inputs: &FrameInputs) -> Self;
+  }
+
+  impl TestFrame for FrameState {
+    fn with_entity(self, handle: EntityHandle, components: ComponentSet) -> Self {
+      FrameState {
+        entities: self
+          .entities
+          .insert(handle, Arc::new(Entity { handle, components })),
+        ..self
+      }
+    }
+
+    fn insert_body(&mut self, translation: Vector<f32>) -> RigidBodyHandle {
+      let handle = self
+        .rigid_body_set
+        .insert(RigidBodyBuilder::dynamic().translation(translation).build());
+      self.collider_set.insert_with_parent(
+        ColliderBuilder::ball(0.25).build(),
+        handle,
+        &mut self.rigid_body_set,
+      );
+      handle
+    }
+
+    fn insert_sensor(&mut self, translation: Vector<f32>) -> ColliderHandle {
+      self.collider_set.insert(
+        ColliderBuilder::ball(1.0)
+          .sensor(true)
+          .translation(translation)
+          .build(),
+      )
+    }
+
+    fn components(&self, handle: EntityHandle) -> ComponentSet {
+      self.entities.get(&handle).unwrap().components.clone()
+    }
+
+    fn run(self, stage_name: &str, inputs: &FrameInputs) -> Self {
+      stage(stage_name)(self, inputs)
+    }
+  }
+
+  /* MARK: The stages themselves */
+
+  mod pipeline {
+    use super::*;
+
+    #[test]
+    fn stages_run_in_pipeline_order() {
+      let names = PHYSICS_STAGES
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+
+      assert_eq!(names.first(), Some(&"stream"));
+      assert_eq!(names.last(), Some(&"step"));
+      assert!(
+        names.iter().position(|name| *name == "ai-apply")
+          < names.iter().position(|name| *name == "damage")
+      );
+      assert!(
+        names.iter().position(|name| *name == "activation")
+          < names.iter().position(|name| *name == "cleanup")
+      );
+    }
+  }
+
+  /* MARK: The status stage */
+
+  mod status {
+    use super::*;
+
+    #[test]
+    fn status_stage_burns_and_ticks_down() {
+      let (frame, player_handle) = frame_with_player();
+      let handle = EntityHandle::RigidBody(player_handle);
+      let components = frame.components(handle);
+
+      let frame = frame.with_entity(
+        handle,
+        components.with(StatusEffects {
+          effects: vec![OnHitStatus {
+            kind: StatusEffectKind::Burn,
+            frames: 2,
+            magnitude: 3.0,
+          }],
+        }),
+      );
+
+      let frame = frame.run("status", &inputs(player_handle));
+      let components = frame.components(handle);
+
+      assert_eq!(components.get::<Damageable>().unwrap().health, 97.0);
+      assert_eq!(
+        components.get::<StatusEffects>().unwrap().effects[0].frames,
+        1
+      );
+    }
+
+    #[test]
+    fn status_stage_regenerates_elites_up_to_full_health() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let damageable = |health| Damageable {
+        health,
+        max_health: 50.0,
+        destroy_on_zero_health: true,
+        current_hitstun: 0.0,
+        max_hitstun: 0.0,
+      };
+      let elite = || Elite {
+        affixes: vec![EliteAffix::Regenerating],
+      };
+      let hurt_handle = EntityHandle::RigidBody(frame.insert_body(vector![5.0, 0.0]));
+      let healthy_handle = EntityHandle::RigidBody(frame.insert_body(vector![-5.0, 0.0]));
+      let frame = frame.with_entity(
+        hurt_handle,
+        ComponentSet::new().insert(elite()).insert(damageable(10.0)),
+      );
+      let frame = frame.with_entity(
+        healthy_handle,
+        ComponentSet::new().insert(elite()).insert(damageable(50.0)),
+      );
+
+      let frame = frame.run("status", &inputs);
+
+      let health = |handle| frame.components(handle).get::<Damageable>().unwrap().health;
+      assert!(health(hurt_handle) > 10.0);
+      assert_eq!(health(healthy_handle), 50.0);
+    }
+  }
+
+  /* MARK: The hacks stage */
+
+  mod hacks {
+    use super::*;
+
+    #[test]
+    fn hacks_stage_turns_the_target_until_it_self_destructs() {
+      let (mut frame, player_handle) = frame_with_player();
+
+      let enemy_handle = EntityHandle::RigidBody(frame.insert_body(vector![3.0, 0.0]));
+      let frame = frame.with_entity(
+        enemy_handle,
+        ComponentSet::new()
+          .insert(Faction::Enemy(0))
+          .insert(Damageable {
+            health: 20.0,
+            max_health: 20.0,
+            destroy_on_zero_health: true,
+            current_hitstun: 0.0,
+            max_hitstun: 0.0,
+          }),
+      );
+
+      let frame = frame.run(
+        "hacks",
+        &FrameInputs {
+          hack: Some(enemy_handle),
+          ..inputs(player_handle)
+        },
+      );
+
+      let components = frame.components(enemy_handle);
+      assert_eq!(Faction::of(&components), Faction::Player);
+      assert!(components.get::<Hacked>().is_some());
+      assert!(components.get::<Destroyed>().is_none());
+
+      let frame = frame.with_entity(
+        enemy_handle,
+        components.with(Hacked {
+          frames_left: 1,
+          frames: HACK_DURATION_FRAMES,
+        }),
+      );
+      let frame = frame.run("hacks", &inputs(player_handle));
+
+      assert!(frame.components(enemy_handle).get::<Destroyed>().is_some());
+    }
+
+    #[test]
+    fn hacked_enemies_stop_touching_the_player() {
+      let (mut frame, player_handle) = frame_with_player();
+
+      let enemy_body = frame.insert_body(vector![0.4, 0.0]);
+      let enemy_handle = EntityHandle::RigidBody(enemy_body);
+      let frame = frame.with_entity(enemy_handle, ComponentSet::new().insert(Faction::Enemy(0)));
+      let touching = |frame: &FrameState| {
+        frame
+          .narrow_phase
+          .contact_pair(
+            frame.rigid_body_set[player_handle].colliders()[0],
+            frame.rigid_body_set[enemy_body].colliders()[0],
+          )
+          .is_some_and(|pair| pair.has_any_active_contact)
+      };
+
+      let frame = frame.run("step", &inputs(player_handle));
+      assert!(touching(&frame));
+
+      let frame = frame.run(
+        "hacks",
+        &FrameInputs {
+          hack: Some(enemy_handle),
+          ..inputs(player_handle)
+        },
+      );
+      let frame = frame.run("step", &inputs(player_handle));
+      assert!(!touching(&frame));
+    }
+  }
+
+  /* MARK: The hazards stage */
+
+  mod hazards {
+    use super::*;
+
+    #[test]
+    fn hazards_stage_burns_entities_inside() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let hazard = frame.insert_sensor(vector![0.5, 0.0]);
+      let frame = frame.with_entity(
+        EntityHandle::Collider(hazard),
+        ComponentSet::new().insert(Hazard {
+          kind: HazardKind::Lava,
+        }),
+      );
+
+      /* Stepping once finds the intersection the hazard acts on */
+      let frame = frame.run("step", &inputs);
+      let frame = frame.run("hazards", &inputs);
+      let components = frame.components(EntityHandle::RigidBody(player_handle));
+
+      assert!(
+        components
+          .get::<StatusEffects>()
+          .unwrap()
+          .damage_per_frame()
+          > 0.0
+      );
+    }
+  }
+
+  /* MARK: The magnet stage */
+
+  mod magnet {
+    use super::*;
+
+    #[test]
+    fn magnet_stage_latches_while_held() {
+      let (mut frame, player_handle) = frame_with_player();
+      let magnetized = FrameInputs {
+        magnetize: true,
+        ..inputs(player_handle)
+      };
+
+      let wall = frame.collider_set.insert(
+        ColliderBuilder::cuboid(0.1, 1.0)
+          .translation(vector![0.5, 0.0])
+          .build(),
+      );
+      let frame = frame.with_entity(
+        EntityHandle::Collider(wall),
+        ComponentSet::new().insert(Magnetic),
+      );
+
+      let latched = |frame: &FrameState| {
+        frame
+          .entities
+          .values()
+          .filter(|entity| {
+            entity.components.get::<MagnetLatch>().is_some()
+              && entity.components.get::<Destroyed>().is_none()
+          })
+          .count()
+      };
+
+      let frame = frame.run("magnet", &magnetized);
+      assert_eq!(latched(&frame), 1);
+      assert_eq!(frame.impulse_joint_set.len(), 1);
+
+      let frame = frame.run("magnet", &magnetized);
+      assert_eq!(latched(&frame), 1);
+
+      let frame = frame.run("magnet", &inputs(player_handle));
+      assert_eq!(latched(&frame), 0);
+    }
+  }
+
+  /* MARK: The players stage */
+
+  mod players {
+    use super::*;
+
+    #[test]
+    fn players_stage_moves_the_player_and_joins_the_partner() {
+      let (frame, player_handle) = frame_with_player();
+
+      let frame = frame.run(
+        "players",
+        &FrameInputs {
+          left_stick: PhysicsVector::from_vec(vector![1.0, 0.0]),
+          coop_gamepad_claimed: true,
+          ..inputs(player_handle)
+        },
+      );
+
+      assert!(frame.rigid_body_set[player_handle].linvel().x > 0.0);
+
+      let player_two_handle = frame.player_two_handle.unwrap();
+      assert!(frame.player_two_joined);
+      assert!(
+        frame
+          .entities
+          .contains_key(&EntityHandle::RigidBody(player_two_handle))
+      );
+
+      /* Dropping the gamepad removes the partner */
+      let frame = frame.run("players", &inputs(player_handle));
+
+      assert_eq!(frame.player_two_handle, None);
+      assert!(!frame.rigid_body_set.contains(player_two_handle));
+    }
+
+    #[test]
+    fn players_stage_scales_movement_with_game_speed() {
+      let velocity_at = |time_scale: f32| {
+        let (frame, player_handle) = frame_with_player();
+        let frame = frame.run(
+          "players",
+          &FrameInputs {
+            left_stick: PhysicsVector::from_vec(vector![1.0, 0.0]),
+            time_scale,
+            ..inputs(player_handle)
+          },
+        );
+        frame.rigid_body_set[player_handle].linvel().x
+      };
+
+      assert!((velocity_at(0.5) - velocity_at(1.0) * 0.5).abs() < 1e-4);
+    }
+  }
+
+  /* MARK: The gravity stage */
+
+  mod gravity {
+    use super::*;
+
+    #[test]
+    fn gravity_stage_pulls_bodies_inside_the_source() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let source = frame.insert_sensor(vector![0.5, 0.0]);
+      let frame = frame.with_entity(
+        EntityHandle::Collider(source),
+        ComponentSet::new().insert(GravitySource {
+          strength: 1.0,
+          activator_id: None,
+        }),
+      );
+
+      /* Stepping once finds the intersection the source acts on */
+      let frame = frame.run("step", &inputs);
+      let velocity_before = frame.rigid_body_set[player_handle].linvel().x;
+      let frame = frame.run("gravity", &inputs);
+
+      assert!(frame.rigid_body_set[player_handle].linvel().x > velocity_before);
+    }
+
+    #[test]
+    fn gravity_stage_pushes_bodies_inside_a_zone() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let zone = frame.insert_sensor(vector![0.5, 0.0]);
+      let frame = frame.with_entity(
+        EntityHandle::Collider(zone),
+        ComponentSet::new().insert(GravityZone {
+          acceleration: vector![0.0, -1.0],
+          activator_id: None,
+        }),
+      );
+
+      let frame = frame.run("step", &inputs);
+      let velocity_before = *frame.rigid_body_set[player_handle].linvel();
+      let frame = frame.run("gravity", &inputs);
+      let velocity_after = *frame.rigid_body_set[player_handle].linvel();
+
+      assert!((velocity_after.y - (velocity_before.y - 1.0)).abs() < 1e-4);
+      assert_eq!(velocity_after.x, velocity_before.x);
+    }
+
+    #[test]
+    fn gravity_stage_follows_the_cycle_phase() {
+      let (mut frame, player_handle) = frame_with_player();
+
+      let calm = CyclePhase {
+        name: "calm".to_string(),
+        start: 0,
+        gravity_multiplier: 0.0,
+        aggression: 1.0,
+        tint: None,
+      };
+      let inputs = FrameInputs {
+        cycle_phase: Some(&calm),
+        ..inputs(player_handle)
+      };
+
+      let source = frame.insert_sensor(vector![0.5, 0.0]);
+      let frame = frame.with_entity(
+        EntityHandle::Collider(source),
+        ComponentSet::new().insert(GravitySource {
+          strength: 1.0,
+          activator_id: None,
+        }),
+      );
+
+      let frame = frame.run("step", &inputs);
+      let velocity_before = frame.rigid_body_set[player_handle].linvel().x;
+      let frame = frame.run("gravity", &inputs);
+
+      assert_eq!(
+        frame.rigid_body_set[player_handle].linvel().x,
+        velocity_before
+      );
+    }
+  }
+
+  /* MARK: The fluids stage */
+
+  mod fluids {
+    use super::*;
+
+    #[test]
+    fn fluids_stage_drags_and_lifts_bodies_inside() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let fluid = frame.insert_sensor(vector![0.5, 0.0]);
+      let frame = frame.with_entity(
+        EntityHandle::Collider(fluid),
+        ComponentSet::new().insert(FluidVolume {
+          drag: 0.5,
+          buoyancy: 0.1,
+          movement_scale: 0.5,
+        }),
+      );
+
+      /* Stepping once finds the intersection the volume acts on */
+      let mut frame = frame.run("step", &inputs);
+      frame.rigid_body_set[player_handle].set_linvel(vector![2.0, 0.0], true);
+      let frame = frame.run("fluids", &inputs);
+
+      let velocity = frame.rigid_body_set[player_handle].linvel();
+      assert!((velocity.x - 1.0).abs() < 1e-4);
+      assert!(velocity.y > 0.0);
+    }
+  }
+
+  /* MARK: The turrets stage */
+
+  mod turrets {
+    use super::*;
+
+    #[test]
+    fn turrets_stage_holds_the_player_on_their_turret_until_they_leave() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let turret = EntityHandle::Collider(frame.insert_sensor(vector![5.0, 0.0]));
+      let frame = frame.with_entity(turret, ComponentSet::new().insert(MountedTurret));
+      let mut frame = FrameState {
+        mounted_turret: Some(turret),
+        ..frame
+      };
+      frame.rigid_body_set[player_handle].set_linvel(vector![3.0, 0.0], true);
+
+      let frame = frame.run("turrets", &inputs);
+
+      assert_eq!(frame.mounted_turret, Some(turret));
+      assert_eq!(
+        *frame.rigid_body_set[player_handle].translation(),
+        vector![5.0, 0.0]
+      );
+      assert_eq!(
+        *frame.rigid_body_set[player_handle].linvel(),
+        vector![0.0, 0.0]
+      );
+
+      let frame = frame.run(
+        "turrets",
+        &FrameInputs {
+          dismount: true,
+          ..inputs
+        },
+      );
+
+      assert_eq!(frame.mounted_turret, None);
+    }
+  }
+
+  /* MARK: The spawn stage */
+
+  mod spawn {
+    use super::*;
+
+    #[test]
+    fn spawn_stage_spawns_fired_projectiles() {
+      let (frame, player_handle) = frame_with_player();
+      let entity_count = frame.entities.size();
+
+      let projectile = Projectile {
+        collider: ColliderBuilder::ball(0.1).build(),
+        offset: PhysicsVector::from_vec(vector![1.0, 0.0]),
+        initial_impulse: PhysicsVector::from_vec(vector![0.1, 0.0]),
+        force_mod: 1.0,
+        damage: 1.0,
+        on_hit_status: None,
+        component_set: ComponentSet::new(),
+        ccd: false,
+      };
+
+      let frame = frame.run(
+        "spawn",
+        &FrameInputs {
+          projectiles: &[projectile],
+          ..inputs(player_handle)
+        },
+      );
+
+      assert_eq!(frame.entities.size(), entity_count + 1);
+      assert_eq!(frame.rigid_body_set.len(), 2);
+    }
+  }
+
+  /* MARK: The ai-apply stage */
+
+  mod ai_apply {
+    use super::*;
+
+    #[test]
+    fn ai_apply_stage_applies_enemy_decisions() {
+      let (mut frame, player_handle) = frame_with_player();
+
+      let enemy_handle = frame.insert_body(vector![5.0, 0.0]);
+      let frame = frame.with_entity(
+        EntityHandle::RigidBody(enemy_handle),
+        ComponentSet::new().insert(Enemy::Swarmer(EnemySwarmer)),
+      );
+
+      let decisions = [EnemyDecision {
+        handle: enemy_handle,
+        projectiles: vec![],
+        movement_force: vector![0.0, 1.0],
+        enemy: Enemy::Swarmer(EnemySwarmer),
+        enemies_to_spawn: vec![],
+        mines: vec![],
+        dialogue: None,
+        telegraph: None,
+      }];
+
+      let frame = frame.run(
+        "ai-apply",
+        &FrameInputs {
+          enemy_decisions: &decisions,
+          ..inputs(player_handle)
+        },
+      );
+
+      assert!(frame.rigid_body_set[enemy_handle].linvel().y > 0.0);
+    }
+
+    #[test]
+    fn ai_apply_stage_keeps_telegraphs_in_step_with_decisions() {
+      let (mut frame, player_handle) = frame_with_player();
+
+      let enemy_handle = frame.insert_body(vector![5.0, 0.0]);
+      let frame = frame.with_entity(
+        EntityHandle::RigidBody(enemy_handle),
+        ComponentSet::new().insert(Enemy::Swarmer(EnemySwarmer)),
+      );
+
+      let decision = |telegraph| EnemyDecision {
+        handle: enemy_handle,
+        projectiles: vec![],
+        movement_force: vector![0.0, 0.0],
+        enemy: Enemy::Swarmer(EnemySwarmer),
+        enemies_to_spawn: vec![],
+        mines: vec![],
+        dialogue: None,
+        telegraph,
+      };
+
+      let winding_up = [decision(Some(Telegraph {
+        frames_left: 5,
+        frames: 20,
+      }))];
+      let frame = frame.run(
+        "ai-apply",
+        &FrameInputs {
+          enemy_decisions: &winding_up,
+          ..inputs(player_handle)
+        },
+      );
+
+      let telegraph = frame
+        .components(EntityHandle::RigidBody(enemy_handle))
+        .get::<Telegraph>()
+        .map(|telegraph| telegraph.frames_left);
+      assert_eq!(telegraph, Some(5));
+
+      let fired = [decision(None)];
+      let frame = frame.run(
+        "ai-apply",
+        &FrameInputs {
+          enemy_decisions: &fired,
+          ..inputs(player_handle)
+        },
+      );
+
+      assert!(
+        frame
+          .components(EntityHandle::RigidBody(enemy_handle))
+          .get::<Telegraph>()
+          .is_none()
+      );
+    }
+  }
+
+  /* MARK: The deflect stage */
+
+  mod deflect {
+    use super::*;
+
+    #[test]
+    fn deflect_stage_turns_enemy_projectiles_crossing_the_chain() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let segment = frame
+        .rigid_body_set
+        .insert(RigidBodyBuilder::dynamic().translation(vector![3.0, 0.0]));
+      frame.collider_set.insert_with_parent(
+        ColliderBuilder::cuboid(CHAIN_SEGMENT_LENGTH / 2.0, CHAIN_SEGMENT_HEIGHT / 2.0)
+          .sensor(true)
+          .collision_groups(groups(CollisionLayer::Chain)),
+        segment,
+        &mut frame.rigid_body_set,
+      );
+      let frame = frame.with_entity(
+        EntityHandle::RigidBody(segment),
+        ComponentSet::new().insert(ChainSegment),
+      );
+
+      let mut frame = frame;
+      let projectile = frame
+        .rigid_body_set
+        .insert(RigidBodyBuilder::dynamic().translation(vector![3.0, 0.05]));
+      let projectile_collider = frame.collider_set.insert_with_parent(
+        ColliderBuilder::ball(0.1).collision_groups(InteractionGroups {
+          memberships: CollisionLayer::EnemyProjectile.group(),
+          filter: CollisionLayer::Player
+            .group()
+            .union(CollisionLayer::Chain.group()),
+          ..Default::default()
+        }),
+        projectile,
+        &mut frame.rigid_body_set,
+      );
+      let frame = frame.with_entity(
+        EntityHandle::RigidBody(projectile),
+        ComponentSet::new().insert(DestroyOnCollision),
+      );
+
+      let mut frame = frame.run("step", &inputs);
+      frame.rigid_body_set[projectile].set_linvel(vector![0.0, -1.0], true);
+      let frame = frame.run("deflect", &inputs);
+
+      assert!(frame.rigid_body_set[projectile].linvel().y > 0.0);
+      assert!(
+        frame.collider_set[projectile_collider]
+          .collision_groups()
+          .memberships
+          .contains(CollisionLayer::PlayerProjectile.group())
+      );
+    }
+  }
+
+  /* MARK: The damage stage */
+
+  mod damage {
+    use super::*;
+
+    #[test]
+    fn damage_stage_spares_players_until_their_spawn_protection_runs_out() {
+      let (mut frame, player_handle) = frame_with_player();
+      let handle = EntityHandle::RigidBody(player_handle);
+
+      let enemy_handle = EntityHandle::RigidBody(frame.insert_body(vector![0.4, 0.0]));
+      let frame = frame.with_entity(
+        enemy_handle,
+        ComponentSet::new()
+          .insert(Faction::Enemy(0))
+          .insert(Damager {
+            damage: 10.0,
+            knockback: 0.0,
+            on_hit_status: None,
+            source: None,
+          }),
+      );
+      let components = frame.components(handle);
+      let frame = frame.with_entity(
+        handle,
+        components.with(SpawnProtection {
+          frames_left: 1,
+          frames: SPAWN_PROTECTION_FRAMES,
+        }),
+      );
+      let health =
+        |frame: &FrameState| frame.components(handle).get::<Damageable>().unwrap().health;
+
+      let frame = frame.run("step", &inputs(player_handle));
+      let frame = frame.run("damage", &inputs(player_handle));
+      assert_eq!(health(&frame), 100.0);
+      assert!(frame.components(handle).get::<SpawnProtection>().is_none());
+
+      let frame = frame.run("damage", &inputs(player_handle));
+      assert_eq!(health(&frame), 90.0);
+    }
+
+    #[test]
+    fn damage_stage_reflects_projectiles_with_bounces_left() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let projectile = frame.insert_body(vector![5.0, 5.0]);
+      let wall = frame.collider_set.insert(
+        ColliderBuilder::cuboid(0.1, 1.0)
+          .translation(vector![5.34, 5.0])
+          .build(),
+      );
+      let frame = frame.with_entity(
+        EntityHandle::Collider(wall),
+        ComponentSet::new().insert(Reflective),
+      );
+      let frame = frame.with_entity(
+        EntityHandle::RigidBody(projectile),
+        ComponentSet::new()
+          .insert(DestroyOnCollision)
+          .insert(Bounces { count: 0, max: 1 }),
+      );
+
+      /* Stepping once finds the contact with the wall */
+      let mut frame = frame.run("step", &inputs);
+      frame.rigid_body_set[projectile].set_linvel(vector![1.0, 0.0], true);
+      let frame = frame.run("damage", &inputs);
+
+      let components = frame.components(EntityHandle::RigidBody(projectile));
+      assert!(frame.rigid_body_set[projectile].linvel().x < 0.0);
+      assert_eq!(components.get::<Bounces>().unwrap().count, 1);
+      assert!(components.get::<Destroyed>().is_none());
+
+      /* Out of bounces, the next hit breaks it */
+      let mut frame = frame;
+      frame.rigid_body_set[projectile].set_linvel(vector![1.0, 0.0], true);
+      let frame = frame.run("damage", &inputs);
+
+      let components = frame.components(EntityHandle::RigidBody(projectile));
+      assert!(components.get::<Destroyed>().is_some());
+    }
+
+    #[test]
+    fn damage_stage_destroys_expired_and_dead_entities() {
+      let (mut frame, player_handle) = frame_with_player();
+
+      let expiring_handle = EntityHandle::Collider(frame.insert_sensor(vector![9.0, 0.0]));
+      let dead_handle = EntityHandle::RigidBody(frame.insert_body(vector![-9.0, 0.0]));
+
+      let frame = frame.with_entity(
+        expiring_handle,
+        ComponentSet::new().insert(DestroyAfterFrames { frames: 0 }),
+      );
+      let frame = frame.with_entity(
+        dead_handle,
+        ComponentSet::new().insert(Damageable {
+          health: 0.0,
+          max_health: 10.0,
+          destroy_on_zero_health: true,
+          current_hitstun: 0.0,
+          max_hitstun: 0.0,
+        }),
+      );
+
+      let frame = frame.run("damage", &inputs(player_handle));
+
+      assert!(
+        frame
+          .components(expiring_handle)
+          .get::<Destroyed>()
+          .is_some()
+      );
+      assert!(frame.components(dead_handle).get::<Destroyed>().is_some());
+      assert!(
+        frame
+          .components(EntityHandle::RigidBody(player_handle))
+          .get::<Destroyed>()
+          .is_none()
+      );
+    }
+
+    #[test]
+    fn damage_stage_lets_missile_explosions_hurt_nearby_enemies() {
+      let game_data = game_data();
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let enemy_spawn = EnemySpawn::new(&MapEnemyName::Goblin, vector![5.0, 0.0]);
+      let enemy_handle = frame.rigid_body_set.insert(enemy_spawn.rigid_body.clone());
+      frame.collider_set.insert_with_parent(
+        enemy_spawn.collider.clone(),
+        enemy_handle,
+        &mut frame.rigid_body_set,
+      );
+      let enemy_handle = EntityHandle::RigidBody(enemy_handle);
+      let frame = frame.with_entity(
+        enemy_handle,
+        enemy_spawn.to_entity_components(&game_data.enemies, Difficulty::default(), 1.0),
+      );
+
+      /* Only the blast does damage, the missile itself carries no damager here */
+      let WeaponModule::Generator(missile) = weapon_module_from_kind(WeaponModuleKind::Missile)
+      else {
+        unreachable!()
+      };
+      let projectile = missile(&game_data.weapons)
+        .aimed_projectiles(&get_slot_positions(0.0))
+        .remove(0);
+      let mut frame = frame;
+      let missile_handle = frame
+        .rigid_body_set
+        .insert(RigidBodyBuilder::dynamic().translation(vector![5.0, 0.6]));
+      frame.collider_set.insert_with_parent(
+        projectile.collider,
+        missile_handle,
+        &mut frame.rigid_body_set,
+      );
+      let frame = frame.with_entity(
+        EntityHandle::RigidBody(missile_handle),
+        projectile.component_set,
+      );
+
+      let frame = frame.run("step", &inputs);
+      let frame = frame.run("damage", &inputs);
+      let frame = frame.run("step", &inputs);
+      let frame = frame.run("damage", &inputs);
+
+      let damageable = frame.components(enemy_handle).get::<Damageable>().unwrap();
+      assert!(damageable.health < damageable.max_health);
+    }
+
+    #[test]
+    fn damage_stage_scatters_debris_where_a_wall_broke() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let wall_handle = frame.rigid_body_set.insert(RigidBodyBuilder::fixed());
+      frame.collider_set.insert_with_parent(
+        ColliderBuilder::cuboid(0.5, 0.5)
+          .translation(vector![4.0, 0.0])
+          .collision_groups(InteractionGroups {
+            memberships: CollisionLayer::Wall.group(),
+            filter: CollisionLayer::Debris.group(),
+            ..Default::default()
+          }),
+        wall_handle,
+        &mut frame.rigid_body_set,
+      );
+      let frame = frame.with_entity(
+        EntityHandle::RigidBody(wall_handle),
+        ComponentSet::new().insert(Damageable {
+          health: 0.0,
+          max_health: 1.0,
+          destroy_on_zero_health: true,
+          current_hitstun: 0.0,
+          max_hitstun: 0.0,
+        }),
+      );
+
+      let frame = frame.run("damage", &inputs);
+
+      let debris = frame
+        .entities
+        .values()
+        .filter(|entity| entity.components.get::<Capped>().is_some())
+        .map(|entity| {
+          *entity
+            .handle
+            .translation(&frame.rigid_body_set, &frame.collider_set)
+        })
+        .collect::<Vec<_>>();
+      assert_eq!(debris.len(), DEBRIS_PIECES);
+      assert!(
+        debris
+          .iter()
+          .all(|translation| (translation - vector![4.0, 0.0]).magnitude() < 0.5)
+      );
+    }
+
+    #[test]
+    fn damage_stage_sets_off_barrels_caught_in_another_barrels_blast() {
+      let (frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let barrel_at = |frame: FrameState, id: i32, translation: Vector<f32>| {
+        let mut frame = frame;
+        let barrel = spawn_explosive_barrel(
+          &ExplosiveBarrel {
+            id,
+            translation,
+            collider: ColliderBuilder::ball(0.4)
+              .collision_groups(barrel_groups())
+              .build(),
+          },
+          &mut frame.collider_set,
+          &mut frame.rigid_body_set,
+        );
+        let handle = barrel.handle;
         (
+          FrameState {
+            entities: frame.entities.insert(handle, Arc::new(barrel)),
+            ..frame
+          },
           handle,
-          Rc::new(Entity {
-            components: entity.components.with(Destroyed),
-            ..entity.as_ref().clone()
-          }),
         )
-      } else {
-        (handle, entity)
-      }
-    });
+      };
+      let (frame, first) = barrel_at(frame, 1, vector![10.0, 0.0]);
+      let (frame, second) = barrel_at(frame, 2, vector![11.5, 0.0]);
+      let (frame, out_of_reach) = barrel_at(frame, 3, vector![20.0, 0.0]);
+
+      /* Shot down */
+      let components = frame.components(first);
+      let frame = frame.with_entity(
+        first,
+        components.with(Damageable {
+          health: 0.0,
+          ..*components.get::<Damageable>().unwrap()
+        }),
+      );
 
-    /* MARK: Destroy colliding entities marked as destroy on collision */
-    let entities = entities
-      .map(|(handle, entity)| {
-        let entity_destroyed = !(entity.components.get::<DestroyOnCollision>().is_none()
-          || entity
-            .handle
-            .colliders(rigid_body_set)
-            .iter()
-            .flat_map(|&&collider_handle| {
-              let collider = &collider_set[collider_handle];
-
-              if collider.is_sensor() {
-                narrow_phase
-                  .intersection_pairs_with(collider_handle)
-                  .flat_map(|(collider1, collider2, is_intersecting)| {
-                    if is_intersecting {
-                      vec![collider1, collider2]
-                    } else {
-                      vec![]
-                    }
-                  })
-                  .collect::<Vec<_>>()
-              } else {
-                narrow_phase
-                  .contact_pairs_with(collider_handle)
-                  .flat_map(|contact_pair| {
-                    if contact_pair.has_any_active_contact {
-                      vec![contact_pair.collider1, contact_pair.collider2]
-                    } else {
-                      vec![]
-                    }
-                  })
-                  .collect::<Vec<_>>()
-              }
-            })
-            .filter(|collider_handle| {
-              EntityHandle::Collider(*collider_handle) != entity.handle
-                && !collider_set[*collider_handle].is_sensor()
-            })
-            .count()
-            == 0);
+      let frame = (0..3).fold(frame, |frame, _| {
+        let frame = frame.run("damage", &inputs);
+        let frame = frame.run("cleanup", &inputs);
+        frame.run("step", &inputs)
+      });
 
-        if entity_destroyed {
-          (
-            handle,
-            Rc::new(Entity {
-              components: entity.components.with(Destroyed),
-              ..entity.as_ref().clone()
-            }),
-          )
-        } else {
-          (handle, entity)
-        }
-      })
-      .collect::<Vec<_>>();
+      assert!(!frame.entities.contains_key(&first));
+      assert!(!frame.entities.contains_key(&second));
+      assert!(frame.entities.contains_key(&out_of_reach));
+    }
 
-    let rng = rand::RandGenerator::new();
-    rng.srand(self.frame_count as u64);
+    #[test]
+    fn damage_stage_catches_the_player_in_their_own_missile_blast() {
+      let game_data = game_data();
+      let (frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      /* A wall beside the player for the missile to go off against */
+      let mut frame = frame;
+      frame.collider_set.insert(
+        ColliderBuilder::cuboid(0.5, 0.5)
+          .translation(vector![1.5, 0.0])
+          .collision_groups(InteractionGroups::all())
+          .build(),
+      );
 
-    /* MARK: Drop health pickups from entities with 0 health marked as such */
-    let entities = entities
-      .into_iter()
-      .flat_map(|(handle, entity)| {
-        if entity.components.get::<Destroyed>().is_none()
-          || entity.components.get::<DropHealthOnDestroy>().is_none()
-        {
-          return vec![(handle, entity)];
-        };
-        let drop_health = entity.components.get::<DropHealthOnDestroy>().unwrap();
+      let WeaponModule::Generator(missile) = weapon_module_from_kind(WeaponModuleKind::Missile)
+      else {
+        unreachable!()
+      };
+      let projectile = missile(&game_data.weapons)
+        .aimed_projectiles(&get_slot_positions(0.0))
+        .remove(0);
+      let missile_handle = frame
+        .rigid_body_set
+        .insert(RigidBodyBuilder::dynamic().translation(vector![0.8, 0.0]));
+      frame.collider_set.insert_with_parent(
+        projectile.collider,
+        missile_handle,
+        &mut frame.rigid_body_set,
+      );
+      let frame = frame.with_entity(
+        EntityHandle::RigidBody(missile_handle),
+        projectile.component_set.insert(Faction::Player),
+      );
 
-        let random = rng.gen_range(0.0, 1.0);
-        let should_drop_health = random < drop_health.chance;
+      let frame = frame.run("step", &inputs);
+      let frame = frame.run("damage", &inputs);
+      let frame = frame.run("step", &inputs);
+      let frame = frame.run("damage", &inputs);
 
-        if !should_drop_health {
-          return vec![(handle, entity)];
-        }
+      let damageable = frame
+        .components(EntityHandle::RigidBody(player_handle))
+        .get::<Damageable>()
+        .unwrap();
+      let explosion_damage = game_data.weapons.missile.explosion.as_ref().unwrap().damage;
+      assert_eq!(
+        damageable.max_health - damageable.health,
+        explosion_damage * SELF_BLAST_DAMAGE
+      );
+      assert!(frame.rigid_body_set[player_handle].linvel().x < 0.0);
+    }
 
-        let new_handle = collider_set.insert(
-          ColliderBuilder::ball(0.31)
-            .collision_groups(InteractionGroups {
-              memberships: COLLISION_GROUP_PLAYER_INTERACTIBLE,
-              filter: COLLISION_GROUP_PLAYER,
-              ..Default::default()
+    #[test]
+    fn damage_stage_lets_enemy_fire_hurt_rivals_and_allies_only_under_friendly_fire() {
+      let health_after_shot = |friendly_fire: bool, shot_group: i32, own_shot: bool| {
+        let (mut frame, player_handle) = frame_with_player();
+        let inputs = FrameInputs {
+          friendly_fire,
+          ..inputs(player_handle)
+        };
+
+        let enemy_handle = EntityHandle::RigidBody(frame.insert_body(vector![5.0, 0.0]));
+        let shot_handle = EntityHandle::RigidBody(frame.insert_body(vector![5.3, 0.0]));
+        let frame = frame.with_entity(
+          enemy_handle,
+          ComponentSet::new()
+            .insert(Enemy::Swarmer(EnemySwarmer))
+            .insert(Faction::Enemy(0))
+            .insert(Damageable {
+              health: 20.0,
+              max_health: 20.0,
+              destroy_on_zero_health: true,
+              current_hitstun: 0.0,
+              max_hitstun: 0.0,
+            }),
+        );
+        let frame = frame.with_entity(
+          shot_handle,
+          ComponentSet::new()
+            .insert(Damager {
+              damage: 5.0,
+              knockback: 0.0,
+              on_hit_status: None,
+              source: own_shot.then_some(enemy_handle),
             })
-            .sensor(true)
-            .translation(*entity.handle.translation(rigid_body_set, &collider_set))
-            .build(),
+            .insert(Faction::Enemy(shot_group)),
         );
-        vec![
-          (handle, entity),
-          (
-            EntityHandle::Collider(new_handle),
-            Entity {
-              handle: EntityHandle::Collider(new_handle),
-              components: ComponentSet::new()
-                .insert(DestroyOnCollision)
-                .insert(HealOnCollision {
-                  amount: drop_health.amount,
-                }),
-              label: "health".to_string(),
-            }
-            .into(),
-          ),
-        ]
-      })
-      .collect::<HashTrieMap<_, _>>();
 
-    /* MARK: Give items on collision */
-    let new_weapon_modules = entities.iter().fold(list![], |acc, (handle, entity)| {
-      if let Some(gives_item) = entity.components.get::<GivesItemOnCollision>()
-        && let Some(id) = entity.components.get::<Id>()
-        && handle
-          .colliders(rigid_body_set)
-          .iter()
-          .any(|&entity_collider_handle| {
-            rigid_body_set[self.player_handle]
-              .colliders()
-              .iter()
-              .any(|player_collider| {
-                narrow_phase
-                  .intersection_pair(*entity_collider_handle, *player_collider)
-                  .unwrap_or(false)
-              })
-          })
-      {
-        acc.push_front((id.id, gives_item.weapon_module_kind))
-      } else {
-        acc
-      }
-    });
+        let frame = frame.run("step", &inputs);
+        let frame = frame.run("damage", &inputs);
 
-    /* MARK: Give abilities on collision */
-    let new_abilities = entities.iter().fold(list![], |acc, (handle, entity)| {
-      if let Some(gives_ability) = entity.components.get::<GiveAbilityOnCollision>()
-        && handle
-          .colliders(rigid_body_set)
-          .iter()
-          .any(|&entity_collider_handle| {
-            rigid_body_set[self.player_handle]
-              .colliders()
-              .iter()
-              .any(|player_collider| {
-                narrow_phase
-                  .intersection_pair(*entity_collider_handle, *player_collider)
-                  .unwrap_or(false)
-              })
-          })
-      {
-        acc.push_front(gives_ability.ability_type)
-      } else {
-        acc
-      }
-    });
+        frame
+          .components(enemy_handle)
+          .get::<Damageable>()
+          .unwrap()
+          .health
+      };
 
-    /* MARK: Load new map */
-    let load_new_map = entities.iter().find_map(|(handle, entity)| {
-      if handle
-        .colliders(rigid_body_set)
-        .iter()
-        .all(|&collider_handle| {
-          narrow_phase
-            .intersection_pairs_with(*collider_handle)
-            .filter(|(_, _, colliding)| *colliding)
-            .count()
-            == 0
-        })
-      {
-        return None;
-      }
+      assert_eq!(health_after_shot(false, 0, false), 20.0);
+      assert_eq!(health_after_shot(true, 0, false), 15.0);
+      assert_eq!(health_after_shot(true, 0, true), 20.0);
+      assert_eq!(health_after_shot(false, 1, false), 15.0);
+    }
 
-      entity
-        .components
-        .get::<MapTransitionOnCollision>()
-        .map(|map_transition_on_collision| {
-          (
-            map_transition_on_collision.map_name.clone(),
-            map_transition_on_collision.target_player_spawn_id,
-          )
-        })
-    });
+    #[test]
+    fn damage_stage_drops_loot_from_destroyed_entities() {
+      let (mut frame, player_handle) = frame_with_player();
+
+      let dead_handle = EntityHandle::RigidBody(frame.insert_body(vector![-9.0, 0.0]));
+      let frame = frame.with_entity(
+        dead_handle,
+        ComponentSet::new().insert(Destroyed).insert(LootTable {
+          entries: vec![
+            LootEntry {
+              weight: 1.0,
+              drop: LootDrop::Credits(5),
+            },
+            LootEntry {
+              weight: 0.0,
+              drop: LootDrop::Nothing,
+            },
+          ],
+          despawn_frames: 60,
+        }),
+      );
 
-    /* MARK: Save point interaction */
-    let save_point_contact = entities.iter().find_map(|(handle, entity)| {
-      if handle
-        .colliders(rigid_body_set)
-        .iter()
-        .any(|&collider_handle| {
-          narrow_phase
-            .intersection_pairs_with(*collider_handle)
-            .filter(|(_, _, colliding)| *colliding)
-            .count()
-            > 0
-        })
-        && entity.components.get::<SaveMenuOnCollision>().is_some()
-        && let Some(id) = entity.components.get::<Id>()
-      {
-        Some(id.id)
-      } else {
-        None
-      }
-    });
+      let frame = frame.run("damage", &inputs(player_handle));
 
-    /* MARK: Heal from sensor collision mark as such */
-    let entities = entities
-      .iter()
-      .map(|(&handle, entity)| {
-        let damageable = entity.components.get::<Damageable>();
+      let credits = frame
+        .entities
+        .values()
+        .find(|entity| entity.components.get::<GivesCreditsOnCollision>().is_some())
+        .unwrap();
+      assert_eq!(
+        credits
+          .components
+          .get::<GivesCreditsOnCollision>()
+          .unwrap()
+          .amount,
+        5
+      );
+      assert_eq!(
+        credits
+          .components
+          .get::<DestroyAfterFrames>()
+          .unwrap()
+          .frames,
+        60
+      );
+    }
 
-        if damageable.is_none() {
-          return (handle, Rc::clone(entity));
-        }
-        let damageable = damageable.unwrap();
+    #[test]
+    fn mines_only_explode_on_contact_once_armed() {
+      let entity_count = |arm_frames: i32| {
+        let (mut frame, player_handle) = frame_with_player();
+        let mine_handle = frame.rigid_body_set.insert(
+          RigidBodyBuilder::dynamic()
+            .translation(vector![0.3, 0.0])
+            .build(),
+        );
+        frame.collider_set.insert_with_parent(
+          ColliderBuilder::ball(0.25)
+            .collision_groups(groups(CollisionLayer::EnemyProjectile))
+            .build(),
+          mine_handle,
+          &mut frame.rigid_body_set,
+        );
+        let frame = frame.with_entity(
+          EntityHandle::RigidBody(mine_handle),
+          ComponentSet::new()
+            .insert(ExplodeOnCollision {
+              radius: 1.5,
+              strength: -0.5,
+              damage: 10.0,
+              interaction_groups: groups(CollisionLayer::EnemyProjectile),
+              force_groups: groups(CollisionLayer::EnemyProjectile),
+              self_blast: false,
+            })
+            .insert(ProximityTrigger {
+              arm_frames,
+              radius: 0.0,
+            }),
+        );
 
-        let healing_sensors = entity
-          .handle
-          .colliders(rigid_body_set)
-          .into_iter()
-          .flat_map(|&collider_handle| {
-            narrow_phase
-              .intersection_pairs_with(collider_handle)
-              .flat_map(|(collider1, collider2, has_active_contact)| {
-                if !has_active_contact {
-                  Vec::new()
-                } else {
-                  [collider1, collider2]
-                    .iter()
-                    .cloned()
-                    .filter(|&handle| collider_handle != handle)
-                    .collect::<Vec<_>>()
-                }
-              })
-              .collect::<Vec<_>>()
-          })
-          .flat_map(|collider_handle| {
-            entities
-              .iter()
-              .find(|(handle, _)| {
-                handle
-                  .colliders(rigid_body_set)
-                  .iter()
-                  .any(|&handle| *handle == collider_handle)
-              })
-              .and_then(|(_, entity)| entity.components.get::<HealOnCollision>())
-          });
+        let inputs = inputs(player_handle);
+        let frame = frame.run("step", &inputs);
+        frame.run("damage", &inputs).entities.size()
+      };
 
-        let incoming_healing = healing_sensors.fold(0.0, |sum, healing| sum + healing.amount);
+      assert_eq!(entity_count(60), 2);
+      assert!(entity_count(0) > 2);
+    }
+  }
 
-        (
-          handle,
-          Entity {
-            components: entity.components.with(Damageable {
-              health: (damageable.health + incoming_healing).min(damageable.max_health),
-              ..*damageable
-            }),
-            ..entity.as_ref().clone()
-          }
-          .into(),
-        )
-      })
-      .collect::<HashTrieMap<_, _>>();
+  /* MARK: The pickups stage */
 
-    /* MARK: Initiate chain on selected mount point */
-    let chain_entities = ability_system.chain_to_mount_point.map(|mount_point| {
-      let player_translation = *rigid_body_set[self.player_handle].translation();
+  mod pickups {
+    use super::*;
 
-      let vector_to_mount_point = rigid_body_set[mount_point].translation() - player_translation;
-      let distance_to_mount_point = vector_to_mount_point.magnitude();
+    #[test]
+    fn pickups_stage_finds_save_point_contact() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
 
-      let num_chain_segments = (distance_to_mount_point / CHAIN_SEGMENT_LENGTH).floor();
-      let initial_chain_segment_length = distance_to_mount_point % CHAIN_SEGMENT_LENGTH;
+      let save_point = frame.insert_sensor(vector![0.0, 0.0]);
+      let frame = frame.with_entity(
+        EntityHandle::Collider(save_point),
+        ComponentSet::new()
+          .insert(SaveMenuOnCollision)
+          .insert(Id { id: 7 }),
+      );
 
-      let unit_to_mount_point = vector_to_mount_point.normalize();
+      let frame = frame.run("step", &inputs);
+      let frame = frame.run("pickups", &inputs);
 
-      let rotation_angle = -angle_from_vec(PhysicsVector::from_vec(unit_to_mount_point));
+      assert_eq!(frame.save_point_contact, Some(7));
+    }
+  }
 
-      let initial_chain_segment_handle = rigid_body_set.insert(
-        RigidBodyBuilder::dynamic()
-          .translation(
-            player_translation + (unit_to_mount_point * initial_chain_segment_length / 2.0),
-          )
-          .rotation(rotation_angle)
-          .angular_damping(CHAIN_ANGULAR_DAMPING)
-          .build(),
-      );
-      collider_set.insert_with_parent(
-        ColliderBuilder::cuboid(
-          initial_chain_segment_length / 2.0,
-          CHAIN_SEGMENT_HEIGHT / 2.0,
-        )
-        .collision_groups(InteractionGroups {
-          memberships: COLLISION_GROUP_CHAIN,
-          filter: Group::empty(),
-          ..Default::default()
-        }),
-        initial_chain_segment_handle,
-        rigid_body_set,
-      );
+  /* MARK: The chain stage */
 
-      let chain_segment_handles = (0..num_chain_segments as i32)
-        .map(|chain_segment_index| {
-          let chain_segment_handle = rigid_body_set.insert(
-            RigidBodyBuilder::dynamic()
-              .translation(
-                player_translation
-                  + (unit_to_mount_point
-                    * (initial_chain_segment_length
-                      + (CHAIN_SEGMENT_LENGTH * (chain_segment_index as f32 + 0.5)))),
-              )
-              .rotation(rotation_angle)
-              .angular_damping(CHAIN_ANGULAR_DAMPING)
-              .build(),
-          );
-          collider_set.insert_with_parent(
-            ColliderBuilder::cuboid(CHAIN_SEGMENT_LENGTH / 2.0, CHAIN_SEGMENT_HEIGHT / 2.0)
-              .collision_groups(InteractionGroups {
-                memberships: COLLISION_GROUP_CHAIN,
-                filter: Group::empty(),
-                ..Default::default()
-              }),
-            chain_segment_handle,
-            rigid_body_set,
-          );
-          chain_segment_handle
-        })
-        .collect::<List<_>>();
+  mod chain {
+    use super::*;
 
-      impulse_joint_set.insert(
-        self.player_handle,
-        initial_chain_segment_handle,
-        RevoluteJointBuilder::new()
-          .local_anchor1(vector![0.0, 0.0].into())
-          .local_anchor2(
-            vector![
-              -initial_chain_segment_length / 2.0,
-              CHAIN_SEGMENT_HEIGHT / 2.0
-            ]
-            .into(),
-          )
-          .contacts_enabled(false)
-          .build(),
-        true,
+    #[test]
+    fn chain_stage_marks_segments_destroyed_on_kill() {
+      let (mut frame, player_handle) = frame_with_player();
+
+      let segment_handle = EntityHandle::RigidBody(frame.insert_body(vector![1.0, 0.0]));
+      let frame = frame.with_entity(segment_handle, ComponentSet::new().insert(ChainSegment));
+
+      let frame = frame.run(
+        "chain",
+        &FrameInputs {
+          kill_chain: true,
+          ..inputs(player_handle)
+        },
       );
 
-      let left_segment_anchor: OPoint<_, _> =
-        vector![CHAIN_SEGMENT_LENGTH / 2.0, CHAIN_SEGMENT_HEIGHT / 2.0].into();
-
-      let right_segment_anchor: OPoint<_, _> =
-        vector![-CHAIN_SEGMENT_LENGTH / 2.0, CHAIN_SEGMENT_HEIGHT / 2.0].into();
-
-      chain_segment_handles
-        .first()
-        .map(|&standard_segment_handle| {
-          impulse_joint_set.insert(
-            initial_chain_segment_handle,
-            standard_segment_handle,
-            RevoluteJointBuilder::new()
-              .local_anchor1(
-                vector![
-                  initial_chain_segment_length / 2.0,
-                  CHAIN_SEGMENT_HEIGHT / 2.0,
-                ]
-                .into(),
-              )
-              .local_anchor2(right_segment_anchor),
-            //              .limits(CHAIN_SEGMENT_LIMITS),
-            true,
-          )
-        });
+      assert!(
+        frame
+          .components(segment_handle)
+          .get::<Destroyed>()
+          .is_some()
+      );
+    }
 
-      chain_segment_handles
-        .iter()
-        .reduce(|&segment_a_handle, segment_b_handle| {
-          impulse_joint_set.insert(
-            segment_a_handle,
-            *segment_b_handle,
-            RevoluteJointBuilder::new()
-              .local_anchor1(left_segment_anchor)
-              .local_anchor2(right_segment_anchor),
-            //              .limits(CHAIN_SEGMENT_LIMITS),
-            true,
-          );
-          segment_b_handle
-        });
+    #[test]
+    fn chain_stage_snaps_a_worn_out_chain() {
+      let (mut frame, player_handle) = frame_with_player();
 
-      if let Some(&last_segment_handle) = chain_segment_handles
-        .push_front(initial_chain_segment_handle)
-        .last()
-      {
-        impulse_joint_set.insert(
-          last_segment_handle,
-          mount_point,
-          RevoluteJointBuilder::new()
-            .local_anchor1(left_segment_anchor)
-            .local_anchor2(vector![0.0, 0.0].into()),
-          true,
-        );
-      };
+      let segment_handle = EntityHandle::RigidBody(frame.insert_body(vector![1.0, 0.0]));
+      let frame = frame.with_entity(segment_handle, ComponentSet::new().insert(ChainSegment));
 
-      chain_segment_handles
-        .push_front(initial_chain_segment_handle)
-        .iter()
-        .map(|&handle| {
-          let handle = EntityHandle::RigidBody(handle);
-          (
-            handle,
-            Rc::new(Entity {
-              handle,
-              components: ComponentSet::new().insert(ChainSegment),
-              label: "".to_string(),
-            }),
-          )
-        })
-        .collect::<HashTrieMap<_, _>>()
-    });
+      let frame = FrameState {
+        chain_durability: 0.0,
+        ..frame
+      }
+      .run("chain", &inputs(player_handle));
+
+      assert!(frame.chain_broke);
+      assert!(
+        frame
+          .components(segment_handle)
+          .get::<Destroyed>()
+          .is_some()
+      );
+    }
+  }
 
-    let entities = if let Some(chain_entities) = chain_entities {
-      entities
-        .into_iter()
-        .chain(&chain_entities)
-        .map(|(&handle, entity)| (handle, Rc::clone(entity)))
-        .collect::<HashTrieMap<_, _>>()
-    } else {
-      entities
-    };
+  /* MARK: The activation stage */
 
-    /* MARK: Kill chain */
-    let entities = if ability_system.kill_chain {
-      entities
-        .into_iter()
-        .map(|(&handle, entity)| {
-          if entity.components.get::<ChainSegment>().is_some() {
-            return (
-              handle,
-              Rc::new(Entity {
-                handle,
-                label: entity.label.clone(),
-                components: entity.components.with(Destroyed),
-              }),
-            );
-          }
+  mod activation {
+    use super::*;
 
-          (handle, Rc::clone(entity))
-        })
-        .collect::<HashTrieMap<_, _>>()
-    } else {
-      entities
-    };
+    #[test]
+    fn activation_stage_activates_touched_sensors() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
 
-    /* MARK: Calculate activation for chain switches */
-    let entities = entities
-      .into_iter()
-      .map(|(&handle, entity)| {
-        if let Some(switch) = entity.components.get::<Switch>()
-          && entity.components.get::<Activator>().is_some()
-        {
-          let joint = impulse_joint_set.get_mut(switch.joint, true).unwrap();
-          let prismatic = joint.data.as_prismatic_mut().unwrap();
+      let sensor_handle = EntityHandle::Collider(frame.insert_sensor(vector![0.0, 0.0]));
+      let frame = frame.with_entity(
+        sensor_handle,
+        ComponentSet::new()
+          .insert(TouchSensor {
+            target_activation: 1.0,
+          })
+          .insert(Activator { activation: 0.0 }),
+      );
 
-          let activation = ((rigid_body_set[joint.body1].translation()
-            - rigid_body_set[joint.body2].translation())
-          .dot(&prismatic.local_axis1())
-            + 1.0)
-            / 2.0;
+      let frame = frame.run("step", &inputs);
+      let frame = frame.run("activation", &inputs);
 
-          let limits = prismatic.limits().unwrap();
+      assert_eq!(
+        frame
+          .components(sensor_handle)
+          .get::<Activator>()
+          .unwrap()
+          .activation,
+        1.0
+      );
+    }
 
-          prismatic.set_motor_position(
-            if activation > 0.5 {
-              limits.min
-            } else {
-              limits.max
-            },
-            3.2,
-            2.0,
-          );
+    #[test]
+    fn activation_stage_activates_tension_switches_by_the_pull_on_them() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
 
-          prismatic.set_motor_velocity(if activation > 0.5 { -1.0 } else { 1.0 }, 1.0);
+      let switch_handle = frame.insert_body(vector![2.0, 0.0]);
+      let joint_handle = frame.impulse_joint_set.insert(
+        player_handle,
+        switch_handle,
+        RevoluteJointBuilder::new(),
+        true,
+      );
 
-          (
-            handle,
-            Rc::new(Entity {
-              handle,
-              label: entity.label.clone(),
-              components: entity.components.with(Activator { activation }),
-            }),
-          )
-        } else {
-          (handle, Rc::clone(entity))
-        }
-      })
-      .collect::<HashTrieMap<_, _>>();
+      /* Half the full force, as the solver would have left it */
+      frame
+        .impulse_joint_set
+        .get_mut(joint_handle, false)
+        .unwrap()
+        .impulses = vector![5.0 * inputs.integration_parameters.dt, 0.0, 0.0];
 
-    /* MARK: Calculate activation for touch sensors */
-    let entities = entities
-      .into_iter()
-      .map(|(&handle, entity)| {
-        if let Some(touch_sensor) = entity.components.get::<TouchSensor>()
-          && let Some(activator) = entity.components.get::<Activator>()
-        {
-          let activation = if !handle
-            .intersecting_with_colliders(rigid_body_set, &narrow_phase)
-            .is_empty()
-          {
-            touch_sensor.target_activation
-          } else {
-            0.0
-          };
-          (
-            handle,
-            Rc::new(Entity {
-              handle,
-              label: entity.label.clone(),
-              components: entity.components.with(Activator { activation }),
-            }),
-          )
-        } else {
-          (handle, Rc::clone(entity))
-        }
-      })
-      .collect::<HashTrieMap<_, _>>();
+      let frame = frame.with_entity(
+        EntityHandle::RigidBody(switch_handle),
+        ComponentSet::new()
+          .insert(TensionSwitch { full_force: 10.0 })
+          .insert(Activator { activation: 0.0 }),
+      );
 
-    /* MARK: Calculate activation for engines */
+      let frame = frame.run("activation", &inputs);
 
-    let entities = entities
-      .into_iter()
-      .map(|(&handle, entity)| {
-        if let Some(engine) = entity.components.get::<Engine>()
-          && let Some(activator) = entity.components.get::<Activator>()
-          && let Some(incoming_activation) = engine
-            .activator_id
-            .map(|activator_id| {
-              entities.iter().find_map(|(_, entity)| {
-                if let Some(activator) = entity.components.get::<Activator>()
-                  && let Some(id) = entity.components.get::<Id>()
-                  && activator_id == id.id
-                {
-                  Some(activator.activation)
-                } else {
-                  None
-                }
-              })
-            })
-            .unwrap_or(Some(0.0))
-        {
-          let activation_change = ENGINE_MAX_SPEED * incoming_activation;
+      let activation = frame
+        .components(EntityHandle::RigidBody(switch_handle))
+        .get::<Activator>()
+        .unwrap()
+        .activation;
 
-          let (activation, currently_increasing) = if engine.currently_increasing {
-            if activator.activation >= 1.0 {
-              (activator.activation - activation_change, false)
-            } else {
-              (activator.activation + activation_change, true)
-            }
-          } else {
-            if activator.activation <= 0.0 {
-              (activator.activation + activation_change, true)
-            } else {
-              (activator.activation - activation_change, false)
-            }
-          };
+      assert!((activation - 0.5).abs() < 1e-4);
+    }
+  }
 
-          (
-            handle,
-            Rc::new(Entity {
-              handle,
-              label: format!("engine {}", activation),
-              components: entity
-                .components
-                .with(Engine {
-                  activator_id: engine.activator_id,
-                  currently_increasing,
-                })
-                .with(Activator { activation }),
-            }),
-          )
-        } else {
-          (handle, Rc::clone(entity))
-        }
-      })
-      .collect::<HashTrieMap<_, _>>();
+  /* MARK: The encounters stage */
+
+  mod encounters {
+    use super::*;
+
+    #[test]
+    fn encounters_stage_holds_gates_shut_until_the_arena_is_cleared() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
+
+      let arena_handle = EntityHandle::Collider(frame.insert_sensor(vector![0.0, 0.0]));
+      let enemy_handle = EntityHandle::RigidBody(frame.insert_body(vector![0.5, 0.0]));
+      let gate_handle = EntityHandle::RigidBody(frame.insert_body(vector![5.0, 0.0]));
+      let frame = frame.with_entity(
+        arena_handle,
+        ComponentSet::new().insert(Encounter {
+          id: 3,
+          state: EncounterState::Waiting,
+          gate_ids: vec![7],
+          closed_activation: 0.0,
+          enemies: vec![],
+          rewards: vec![LootDrop::Credits(10)],
+        }),
+      );
+      let frame = frame.with_entity(
+        enemy_handle,
+        ComponentSet::new().insert(Enemy::Swarmer(EnemySwarmer)),
+      );
+      let frame = frame.with_entity(
+        gate_handle,
+        ComponentSet::new()
+          .insert(Id { id: 7 })
+          .insert(Activator { activation: 1.0 }),
+      );
 
-    /* MARK: Calculate SimpleActivatable activation */
-    let entities = entities
-      .iter()
-      .map(|(&handle, entity)| {
-        if let Some(activatable) = entity.components.get::<SimpleActivatable>()
-          && let Some(activation) = entities.iter().find_map(|(_, entity)| {
-            if let Some(activator) = entity.components.get::<Activator>()
-              && let Some(id) = entity.components.get::<Id>()
-              && activatable.activator_id == id.id
-            {
-              Some(activator.activation)
-            } else {
-              None
-            }
-          })
-        {
-          (
-            handle,
-            Rc::new(Entity {
-              handle,
-              components: entity.components.with(SimpleActivatable {
-                activation,
-                activator_id: activatable.activator_id,
-              }),
-              label: entity.label.clone(),
-            }),
-          )
-        } else {
-          (handle, Rc::clone(entity))
-        }
-      })
-      .collect::<HashTrieMap<_, _>>();
+      let frame = frame.run("step", &inputs);
+      let frame = frame.run("encounters", &inputs);
+
+      let encounter = frame.components(arena_handle).get::<Encounter>().unwrap();
+      assert_eq!(encounter.state, EncounterState::Fighting);
+      assert_eq!(encounter.enemies, vec![enemy_handle]);
+      assert_eq!(
+        frame
+          .components(gate_handle)
+          .get::<Activator>()
+          .unwrap()
+          .activation,
+        0.0
+      );
 
-    /* MARK: Calculate And activation */
-    let entities = entities
-      .iter()
-      .map(|(&handle, entity)| {
-        if let Some(and) = entity.components.get::<And>()
-          && let Some((activation_1, activation_2)) = entities
-            .iter()
-            .filter_map(|(_, entity)| {
-              if let Some(activator) = entity.components.get::<Activator>()
-                && let Some(id) = entity.components.get::<Id>()
-                && (and.activator_ids.0 == id.id || and.activator_ids.1 == id.id)
-              {
-                Some(activator.activation)
-              } else {
-                None
-              }
-            })
-            .collect_tuple()
-        {
-          let activation = (activation_1 + activation_2) / 2.0;
-          (
+      let components = frame.components(enemy_handle);
+      let frame = frame.with_entity(enemy_handle, components.insert(Destroyed));
+      let frame = frame.run("encounters", &inputs);
+
+      assert_eq!(
+        frame
+          .components(arena_handle)
+          .get::<Encounter>()
+          .unwrap()
+          .state,
+        EncounterState::Cleared
+      );
+      assert_eq!(
+        frame.map_states.get("test").unwrap().cleared_encounters,
+        vec![3]
+      );
+      assert!(
+        frame
+          .entities
+          .values()
+          .any(|entity| entity.components.get::<GivesCreditsOnCollision>().is_some())
+      );
+    }
+  }
+
+  /* MARK: The caps stage */
+
+  mod caps {
+    use super::*;
+
+    #[test]
+    fn caps_stage_destroys_the_oldest_low_priority_entities_over_the_cap() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = FrameInputs {
+        entity_limits: EntityLimits {
+          player_projectiles: 2,
+          enemy_projectiles: 2,
+          particles: 2,
+          debris: 2,
+        },
+        ..inputs(player_handle)
+      };
+
+      let [oldest, newest, important] =
+        [(0, 0, 3.0), (0, 5, 6.0), (1, 0, 9.0)].map(|(priority, spawned_frame, x)| {
+          let handle = EntityHandle::Collider(frame.insert_sensor(vector![x, 0.0]));
+          (handle, priority, spawned_frame)
+        });
+      let frame = [oldest, newest, important].into_iter().fold(
+        frame,
+        |frame, (handle, priority, spawned_frame)| {
+          frame.with_entity(
             handle,
-            Rc::new(Entity {
-              handle,
-              label: format!("and {}", activation),
-              components: entity.components.with(Activator { activation }),
+            ComponentSet::new().insert(Capped {
+              category: CapCategory::PlayerProjectile,
+              priority,
+              spawned_frame,
             }),
           )
-        } else {
-          (handle, Rc::clone(entity))
-        }
-      })
-      .collect::<HashTrieMap<_, _>>();
+        },
+      );
 
-    /* MARK: Calculate Or activation */
-    let entities = entities
-      .iter()
-      .map(|(&handle, entity)| {
-        if let Some(or) = entity.components.get::<Or>()
-          && let Some((activation_1, activation_2)) = entities
-            .iter()
-            .filter_map(|(_, entity)| {
-              if let Some(activator) = entity.components.get::<Activator>()
-                && let Some(id) = entity.components.get::<Id>()
-                && (or.activator_ids.0 == id.id || or.activator_ids.1 == id.id)
-              {
-                Some(activator.activation)
-              } else {
-                None
-              }
-            })
-            .collect_tuple()
-        {
-          let activation = (activation_1 + activation_2).min(1.0);
-          (
-            handle,
-            Rc::new(Entity {
-              handle,
-              label: format!("or {}", activation),
+      let frame = frame.run("caps", &inputs);
 
-              components: entity.components.with(Activator { activation }),
-            }),
-          )
-        } else {
-          (handle, Rc::clone(entity))
-        }
-      })
-      .collect::<HashTrieMap<_, _>>();
+      let destroyed = |(handle, _, _): (EntityHandle, i32, i64)| {
+        frame.components(handle).get::<Destroyed>().is_some()
+      };
+      assert!(destroyed(oldest));
+      assert!(!destroyed(newest));
+      assert!(!destroyed(important));
+    }
+  }
 
-    /* MARK: Calculate Gate activation */
-    let entities = entities
-      .iter()
-      .map(|(&handle, entity)| {
-        if let Some(gate) = entity.components.get::<Gate>()
-          && let Some(incoming_activation) = entities.iter().find_map(|(_, entity)| {
-            if let Some(activator) = entity.components.get::<Activator>()
-              && let Some(id) = entity.components.get::<Id>()
-              && gate.activator_id == id.id
-            {
-              Some(activator.activation)
-            } else {
-              None
-            }
-          })
-        {
-          let activation = incoming_activation.max(gate.highest_historical_activation);
-          (
-            handle,
-            Rc::new(Entity {
-              handle,
-              label: format!("gate {}", activation),
+  /* MARK: The cleanup stage */
 
-              components: entity.components.with(Activator { activation }).with(Gate {
-                activator_id: gate.activator_id,
-                highest_historical_activation: activation,
-              }),
-            }),
-          )
-        } else {
-          (handle, Rc::clone(entity))
-        }
-      })
-      .collect::<HashTrieMap<_, _>>();
+  mod cleanup {
+    use super::*;
 
-    /* MARK: Locomotor behavior */
-    entities.iter().for_each(|(_, entity)| {
-      if let Some(locomotor) = entity.components.get::<Locomotor>()
-        && let Some(activatable) = entity.components.get::<SimpleActivatable>()
-        && let Some(joint) = impulse_joint_set.get_mut(locomotor.joint, true)
-      {
-        let prismatic = joint.data.as_prismatic_mut().unwrap();
+    #[test]
+    fn cleanup_stage_keeps_corpses_until_their_death_plays_out() {
+      let (mut frame, player_handle) = frame_with_player();
+      let inputs = inputs(player_handle);
 
-        let prismatic_limits = prismatic.limits().unwrap();
+      let body = frame.insert_body(vector![2.0, 0.0]);
+      let handle = EntityHandle::RigidBody(body);
+      let frame = frame.with_entity(
+        handle,
+        ComponentSet::new()
+          .insert(Destroyed)
+          .insert(DeathAnimation { frames: 2 }),
+      );
 
-        let prismatic_limit_magnitude = prismatic_limits.max - prismatic_limits.min;
+      let frame = frame.run("cleanup", &inputs);
 
-        let target_ratio = activatable.activation * prismatic_limit_magnitude;
+      let components = frame.components(handle);
+      assert!(components.get::<Dying>().is_some());
+      assert!(components.get::<Destroyed>().is_none());
+      let collider = frame.rigid_body_set[body].colliders()[0];
+      assert!(!frame.collider_set[collider].is_enabled());
 
-        let motor_position = if locomotor.reverse_direction {
-          prismatic_limits.min + target_ratio
-        } else {
-          prismatic_limits.max - target_ratio
-        };
+      let frame = (0..3).fold(frame, |frame, _| frame.run("cleanup", &inputs));
 
-        prismatic.set_motor_position(motor_position, 500.0, 400.0);
-      }
-    });
+      assert!(frame.entities.get(&handle).is_none());
+      assert!(frame.rigid_body_set.get(body).is_none());
+    }
 
-    /* MARK: Remove destroyed entities */
-    let entities = entities
-      .into_iter()
-      .filter_map(|(&handle, entity)| {
-        if entity.components.get::<Destroyed>().is_none()
-          || handle == EntityHandle::RigidBody(self.player_handle)
-        {
-          return Some((handle, Rc::clone(entity)));
-        }
+    #[test]
+    fn cleanup_stage_removes_destroyed_and_remembers_persistent() {
+      let (mut frame, player_handle) = frame_with_player();
+
+      let enemy_handle = frame.insert_body(vector![5.0, 0.0]);
+      let frame = frame.with_entity(
+        EntityHandle::RigidBody(enemy_handle),
+        ComponentSet::new()
+          .insert(Enemy::Swarmer(EnemySwarmer))
+          .insert(Persistent { id: 3 })
+          .insert(Destroyed),
+      );
 
-        match entity.handle {
-          EntityHandle::RigidBody(rigid_body_handle) => {
-            rigid_body_set.remove(
-              rigid_body_handle,
-              &mut island_manager,
-              &mut collider_set,
-              &mut impulse_joint_set,
-              &mut multibody_joint_set,
-              true,
-            );
-          }
-          EntityHandle::Collider(collider_handle) => {
-            collider_set.remove(collider_handle, &mut island_manager, rigid_body_set, true);
-          }
-        }
-        None
-      })
-      .collect::<HashTrieMap<_, _>>();
+      let frame = frame.run("cleanup", &inputs(player_handle));
 
-    /* MARK: Find all mount points in range */
-    let mount_points_in_range = entities
-      .iter()
-      .flat_map(|(handle, entity)| {
-        entity
-          .components
-          .get::<ChainMountArea>()
-          .into_iter()
-          .filter_map(|chain_mount_activation| {
-            if !handle
-              .intersecting_with_colliders(rigid_body_set, &narrow_phase)
-              .is_empty()
-            {
-              Some(chain_mount_activation.target_mount_body)
-            } else {
-              None
-            }
-          })
-      })
-      .collect::<List<_>>();
+      assert!(
+        !frame
+          .entities
+          .contains_key(&EntityHandle::RigidBody(enemy_handle))
+      );
+      assert!(!frame.rigid_body_set.contains(enemy_handle));
+      assert_eq!(
+        frame.map_states.get("test").unwrap().killed_enemies,
+        vec![3]
+      );
+    }
 
-    /* MARK: Step physics */
-    physics_pipeline.step(
-      &vector![0.0, 0.0],
-      &self.integration_parameters,
-      &mut island_manager,
-      &mut broad_phase,
-      &mut narrow_phase,
-      rigid_body_set,
-      &mut collider_set,
-      &mut impulse_joint_set,
-      &mut multibody_joint_set,
-      &mut ccd_solver,
-      &(),
-      &(),
-    );
+    #[test]
+    fn cleanup_stage_counts_kills_but_not_hacked_enemies() {
+      let (mut frame, player_handle) = frame_with_player();
+
+      let killed_handle = EntityHandle::RigidBody(frame.insert_body(vector![5.0, 0.0]));
+      let hacked_handle = EntityHandle::RigidBody(frame.insert_body(vector![-5.0, 0.0]));
+      let frame = frame.with_entity(
+        killed_handle,
+        ComponentSet::new()
+          .insert(Enemy::Swarmer(EnemySwarmer))
+          .insert(Faction::Enemy(0))
+          .insert(Destroyed),
+      );
+      let frame = frame.with_entity(
+        hacked_handle,
+        ComponentSet::new()
+          .insert(Enemy::Swarmer(EnemySwarmer))
+          .insert(Faction::Player)
+          .insert(Destroyed),
+      );
 
-    Rc::new(Self {
-      rigid_body_set: rigid_body_set.clone(),
-      collider_set,
-      integration_parameters: self.integration_parameters,
-      physics_pipeline: Rc::clone(&self.physics_pipeline),
-      island_manager,
-      broad_phase,
-      narrow_phase,
-      impulse_joint_set,
-      multibody_joint_set,
-      ccd_solver,
-      player_handle: self.player_handle,
-      entities,
-      new_weapon_modules,
-      new_abilities,
-      frame_count: self.frame_count + 1,
-      load_new_map,
-      save_point_contact,
-      save_point_contact_last_frame: self.save_point_contact,
-      mount_points_in_range,
-    })
+      let frame = frame.run("cleanup", &inputs(player_handle));
+
+      assert_eq!(frame.new_kills, 1);
+    }
   }
-}
 
-fn player_movement_impulse(
-  controls_system: Rc<ControlsSystem<SaveData>>,
-  player: &RigidBody,
-) -> Vector<f32> {
-  let attempted_acceleration = controls_system.left_stick.into_vec() * PLAYER_ACCELERATION_MOD;
-  let player_mass = player.mass();
-  let player_velocity = player.linvel();
-  let velocity_change = attempted_acceleration * player_mass;
+  /* MARK: The step stage */
 
-  let safe_acceleration_x = if attempted_acceleration.x == 0.0
-    || velocity_change.x.signum() != player_velocity.x.signum()
-    || player_velocity.x.abs() < PLAYER_SPEED_LIMIT
-  {
-    attempted_acceleration.x
-  } else {
-    0.0
-  };
+  mod step {
+    use super::*;
 
-  let safe_acceleration_y = if attempted_acceleration.y == 0.0
-    || velocity_change.y.signum() != player_velocity.y.signum()
-    || player_velocity.y.abs() < PLAYER_SPEED_LIMIT
-  {
-    attempted_acceleration.y
-  } else {
-    0.0
-  };
+    #[test]
+    fn step_stage_integrates_velocity() {
+      let (mut frame, player_handle) = frame_with_player();
 
-  vector![safe_acceleration_x, safe_acceleration_y]
-}
+      frame.rigid_body_set[player_handle].set_linvel(vector![1.0, 0.0], true);
 
-fn map_damageable_damage_taken(
-  rigid_body_set: &RigidBodySet,
-  narrow_phase: &NarrowPhase,
-  collider_set: &ColliderSet,
-  entities: &HashTrieMap<EntityHandle, Rc<Entity>>,
-) -> impl Fn((&EntityHandle, &Rc<Entity>)) -> (EntityHandle, Rc<Entity>) {
-  |(&handle, entity)| {
-    let damageable = entity.components.get::<Damageable>();
+      let frame = frame.run("step", &inputs(player_handle));
 
-    if damageable.is_none() {
-      return (handle, Rc::clone(entity));
+      assert!(frame.rigid_body_set[player_handle].translation().x > 0.0);
     }
-    let damageable = damageable.unwrap();
+  }
 
-    if damageable.current_hitstun > 0.0 {
-      return (
-        handle,
-        Rc::new(Entity {
-          components: entity.components.with(Damageable {
-            current_hitstun: damageable.current_hitstun - 1.0,
-            ..*damageable
-          }),
-          ..entity.as_ref().clone()
-        }),
+  /* MARK: Loading into a room */
+
+  mod rooms {
+    use super::*;
+
+    #[test]
+    fn players_spawning_inside_a_wall_are_moved_out() {
+      let (mut frame, player_handle) = frame_with_player();
+      frame.collider_set.insert(
+        ColliderBuilder::cuboid(0.5, 0.5)
+          .collision_groups(groups(CollisionLayer::Wall))
+          .build(),
       );
-    }
 
-    let damagers = entity
-      .handle
-      .intersecting_with_colliders(rigid_body_set, narrow_phase)
-      .into_iter()
-      .flat_map(|&collider_handle| {
-        collider_set[collider_handle]
-          .parent()
-          .and_then(|rigid_body_handle| entities.get(&EntityHandle::RigidBody(rigid_body_handle)))
-          .and_then(|entity| entity.components.get::<Damager>())
-      })
-      .collect::<Vec<_>>();
+      let translation =
+        clear_spawn_translation(player_handle, &frame.rigid_body_set, &frame.collider_set);
 
-    let incoming_damage = damagers
-      .iter()
-      .fold(0.0, |sum, damager| sum + damager.damage);
+      assert!(translation.magnitude() >= 0.75);
+      assert!(translation.magnitude() <= 1.0);
+    }
 
-    if incoming_damage == 0.0 {
-      if damageable.current_hitstun > 0.0 {
-        return (
-          handle,
-          Rc::new(Entity {
-            components: entity.components.with(Damageable {
-              current_hitstun: damageable.current_hitstun - 1.0,
-              ..*damageable
-            }),
-            ..entity.as_ref().clone()
+    #[test]
+    fn restoring_a_suspended_room_puts_back_what_was_left_and_drops_the_rest() {
+      let (mut frame, player_handle) = frame_with_player();
+
+      let barrel = EntityHandle::RigidBody(frame.insert_body(vector![0.0, 3.0]));
+      let frame = frame.with_entity(
+        barrel,
+        ComponentSet::new()
+          .insert(EntityKind::ExplosiveBarrel)
+          .insert(Id { id: 1 })
+          .insert(Damageable {
+            health: 10.0,
+            max_health: 10.0,
+            destroy_on_zero_health: true,
+            current_hitstun: 0.0,
+            max_hitstun: 0.0,
           }),
-        );
-      }
+      );
+      let mut frame = frame;
+      let destroyed = EntityHandle::Collider(frame.insert_sensor(vector![5.0, 0.0]));
+      let mut frame = frame.with_entity(
+        destroyed,
+        ComponentSet::new()
+          .insert(EntityKind::TouchSensor)
+          .insert(Id { id: 2 }),
+      );
+
+      let entities = restore_suspended_room(
+        &SuspendedRoom {
+          player_translation: (0.0, 0.0),
+          player_velocity: (1.0, 0.0),
+          entities: vec![SuspendedEntity {
+            kind: EntityKind::ExplosiveBarrel,
+            id: 1,
+            translation: Some((2.0, 3.0)),
+            velocity: None,
+            health: Some(4.0),
+            activation: None,
+          }],
+        },
+        frame.entities.clone(),
+        player_handle,
+        PhysicsSets {
+          rigid_body_set: &mut frame.rigid_body_set,
+          collider_set: &mut frame.collider_set,
+          island_manager: &mut frame.island_manager,
+          impulse_joint_set: &mut frame.impulse_joint_set,
+          multibody_joint_set: &mut frame.multibody_joint_set,
+        },
+      );
 
-      return (handle, Rc::clone(entity));
+      let EntityHandle::RigidBody(barrel_body) = barrel else {
+        unreachable!()
+      };
+      assert_eq!(
+        *frame.rigid_body_set[barrel_body].translation(),
+        vector![2.0, 3.0]
+      );
+      assert_eq!(
+        entities
+          .get(&barrel)
+          .and_then(|entity| entity.components.get::<Damageable>())
+          .map(|damageable| damageable.health),
+        Some(4.0)
+      );
+      assert!(!entities.contains_key(&destroyed));
+      assert!(entities.contains_key(&EntityHandle::RigidBody(player_handle)));
+      assert_eq!(
+        *frame.rigid_body_set[player_handle].linvel(),
+        vector![1.0, 0.0]
+      );
     }
 
-    (
-      handle,
-      Rc::new(Entity {
-        components: entity.components.with(Damageable {
-          health: damageable.health - incoming_damage,
-          current_hitstun: damageable.max_hitstun,
-          ..*damageable
-        }),
-        ..entity.as_ref().clone()
-      }),
-    )
-  }
-}
+    #[test]
+    fn players_enter_just_inside_the_door_back_to_the_room_they_left() {
+      let mut map = crate::procgen::generate(1, &crate::procgen::Constraints::default())
+        .unwrap()
+        .map;
+      map.map_transitions = vec![crate::load_map::MapTransition {
+        map_name: "west".to_string(),
+        collider: ColliderBuilder::cuboid(0.5, 2.0)
+          .translation(vector![-5.0, 0.0])
+          .sensor(true)
+          .build(),
+        target_player_spawn_id: 0,
+      }];
 
-fn spawn_explosion(
-  translation: Vector<f32>,
-  explosion: &ExplodeOnCollision,
-  collider_set: &mut ColliderSet,
-  rigid_body_set: &mut RigidBodySet,
-) -> Entity {
-  let rigid_body_handle =
-    rigid_body_set.insert(RigidBodyBuilder::dynamic().translation(translation));
-  collider_set.insert_with_parent(
-    ColliderBuilder::ball(explosion.radius)
-      .collision_groups(explosion.interaction_groups)
-      .enabled(true)
-      .sensor(true),
-    rigid_body_handle,
-    rigid_body_set,
-  );
+      let (translation, door_center) = entry_door(&map, "west", vector![0.0, 0.0]).unwrap();
 
-  Entity {
-    handle: EntityHandle::RigidBody(rigid_body_handle),
-    components: ComponentSet::new()
-      .insert(Damager {
-        damage: explosion.damage,
-      })
-      .insert(GravitySource {
-        strength: explosion.strength,
-        activator_id: None,
-      })
-      .insert(DestroyAfterFrames { frames: 5 }),
-    label: "boom".to_string(),
+      assert_eq!(door_center, vector![-5.0, 0.0]);
+      assert!((translation - vector![-4.5 + ENTRY_CLEARANCE, 0.0]).magnitude() < 1e-4);
+      assert!(entry_door(&map, "east", vector![0.0, 0.0]).is_none());
+    }
   }
 }
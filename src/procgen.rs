@@ -0,0 +1,534 @@
+use std::{collections::HashMap, env, fs};
+
+use macroquad::rand::RandGenerator;
+use serde::{Deserialize, de::IntoDeserializer};
+use serde_json::{Value, json};
+
+use crate::{
+  combat::WeaponModuleKind,
+  load_map::{Map, RawMap},
+  map_validation::validate_map,
+};
+
+/* `--generate-map <seed> <output>` writes a generated room and exits, optionally shaped by
+the constraint flags below */
+pub const GENERATE_MAP_FLAG: &str = "--generate-map";
+/* As in `--map-size 40x25`, in tiles */
+const MAP_SIZE_FLAG: &str = "--map-size";
+const ENEMY_BUDGET_FLAG: &str = "--enemy-budget";
+/* Comma separated module kinds, as in `--pickups Plasma,Missile` */
+const PICKUPS_FLAG: &str = "--pickups";
+
+const TILE: i32 = 8;
+
+/* What each enemy costs out of a room's enemy budget */
+const ENEMY_COSTS: [(&str, i32); 6] = [
+  ("Goblin", 1),
+  ("Swarmer", 1),
+  ("Defender", 2),
+  ("Seeker", 2),
+  ("Minelayer", 3),
+  ("SeekerGenerator", 4),
+];
+
+/* Enemies never spawn closer than this many tiles to the player */
+const ENEMY_SPAWN_CLEARANCE: i32 = 8;
+
+/* Each required pickup sits in a vault along the top wall, closed off by a door that slides
+up out of the way once its touch sensor elsewhere in the room is touched */
+const VAULT_WIDTH: i32 = 9;
+const VAULT_HEIGHT: i32 = 6;
+const VAULT_SPACING: i32 = 1;
+/* Rows under the vaults left open so the doors can always be reached */
+const VAULT_APRON: i32 = 2;
+
+const MIN_MAP_WIDTH: i32 = 20;
+const MIN_MAP_HEIGHT: i32 = VAULT_HEIGHT + VAULT_APRON + 6;
+
+#[derive(Clone, Debug)]
+pub struct Constraints {
+  pub width: i32,
+  pub height: i32,
+  pub enemy_budget: i32,
+  /* Every one of these is placed in the room */
+  pub pickups: Vec<WeaponModuleKind>,
+}
+
+impl Default for Constraints {
+  fn default() -> Self {
+    Self {
+      width: 40,
+      height: 25,
+      enemy_budget: 8,
+      pickups: vec![],
+    }
+  }
+}
+
+/* The room both as Tiled JSON, for writing out and curating, and as the Map it loads as */
+pub struct GeneratedMap {
+  pub raw: Value,
+  pub map: Map,
+}
+
+struct Grid {
+  width: i32,
+  height: i32,
+  walls: Vec<bool>,
+  /* Kept clear of obstacles and enemies */
+  reserved: Vec<bool>,
+}
+
+impl Grid {
+  fn index(&self, x: i32, y: i32) -> usize {
+    (y * self.width + x) as usize
+  }
+
+  fn is_wall(&self, x: i32, y: i32) -> bool {
+    x < 0 || y < 0 || x >= self.width || y >= self.height || self.walls[self.index(x, y)]
+  }
+
+  fn set_wall(&mut self, x: i32, y: i32, wall: bool) {
+    let index = self.index(x, y);
+    self.walls[index] = wall;
+  }
+
+  fn reserve(&mut self, x: i32, y: i32, width: i32, height: i32) {
+    (y..y + height).for_each(|y| {
+      (x..x + width).for_each(|x| {
+        let index = self.index(x, y);
+        self.reserved[index] = true;
+      })
+    });
+  }
+
+  fn is_free(&self, x: i32, y: i32, width: i32, height: i32) -> bool {
+    (y..y + height)
+      .all(|y| (x..x + width).all(|x| !self.is_wall(x, y) && !self.reserved[self.index(x, y)]))
+  }
+
+  /* Every open tile reachable from the given one, doors counting as open */
+  fn reachable_from(&self, x: i32, y: i32) -> Vec<bool> {
+    let mut reached = vec![false; self.walls.len()];
+    let mut frontier = vec![(x, y)];
+
+    while let Some((x, y)) = frontier.pop() {
+      if self.is_wall(x, y) || reached[self.index(x, y)] {
+        continue;
+      }
+      reached[self.index(x, y)] = true;
+      frontier.extend([(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]);
+    }
+
+    reached
+  }
+
+  fn is_connected(&self, x: i32, y: i32) -> bool {
+    let reached = self.reachable_from(x, y);
+    self
+      .walls
+      .iter()
+      .zip(reached)
+      .all(|(wall, reached)| *wall || reached)
+  }
+}
+
+/* Hands out Tiled object ids in order */
+struct Objects {
+  objects: Vec<Value>,
+}
+
+impl Objects {
+  fn add(&mut self, object: Value) -> i32 {
+    let id = self.objects.len() as i32 + 1;
+    let mut object = object;
+    object["id"] = Value::from(id);
+    self.objects.push(object);
+    id
+  }
+}
+
+fn property(name: &str, kind: &str, value: Value) -> Value {
+  json!({ "name": name, "type": kind, "value": value })
+}
+
+fn random_tile(rng: &RandGenerator, grid: &Grid, width: i32, height: i32) -> (i32, i32) {
+  (
+    rng.gen_range(1, grid.width - width),
+    rng.gen_range(VAULT_HEIGHT + VAULT_APRON, grid.height - height),
+  )
+}
+
+/* Puts down a vault for the pickup, wiring its door to a touch sensor in the main room */
+fn add_vault(
+  rng: &RandGenerator,
+  grid: &mut Grid,
+  objects: &mut Objects,
+  vault_x: i32,
+  pickup: WeaponModuleKind,
+) -> Result<(), String> {
+  let bottom = VAULT_HEIGHT - 1;
+  (1..VAULT_HEIGHT).for_each(|y| {
+    grid.set_wall(vault_x, y, true);
+    grid.set_wall(vault_x + VAULT_WIDTH - 1, y, true);
+  });
+  (vault_x..vault_x + VAULT_WIDTH)
+    .filter(|x| *x > vault_x + 2 || *x == vault_x)
+    .for_each(|x| grid.set_wall(x, bottom, true));
+  grid.reserve(vault_x, 0, VAULT_WIDTH, VAULT_HEIGHT + VAULT_APRON);
+
+  objects.add(json!({
+    "name": pickup,
+    "type": "ItemPickup",
+    "x": (vault_x + VAULT_WIDTH - 3) * TILE + TILE / 2,
+    "y": 2 * TILE + TILE / 2,
+    "width": 0,
+    "height": 0,
+  }));
+
+  let (sensor_x, sensor_y) = (0..100)
+    .map(|_| random_tile(rng, grid, 2, 2))
+    .find(|(x, y)| grid.is_free(*x, *y, 2, 2))
+    .ok_or("no room left for a vault's touch sensor")?;
+  grid.reserve(sensor_x, sensor_y, 2, 2);
+
+  let sensor_id = objects.add(json!({
+    "name": "Touch Sensor",
+    "type": "TouchSensor",
+    "x": sensor_x * TILE,
+    "y": sensor_y * TILE,
+    "width": 2 * TILE,
+    "height": 2 * TILE,
+    "properties": [property("TargetActivation", "float", Value::from(1.0))],
+  }));
+  let gate_id = objects.add(json!({
+    "name": "Gate",
+    "type": "Gate",
+    "x": sensor_x * TILE,
+    "y": sensor_y * TILE,
+    "properties": [property("ActivatorId", "object", Value::from(sensor_id))],
+  }));
+
+  /* The door fills the gap in the vault's floor and rises into the vault to open it */
+  let door_id = objects.add(json!({
+    "name": "Block",
+    "type": "Block",
+    "x": (vault_x + 1) * TILE,
+    "y": bottom * TILE,
+    "width": 2 * TILE,
+    "height": TILE,
+  }));
+  let locomotor_id = objects.add(json!({
+    "name": "Locomotor",
+    "type": "Locomotor",
+    "x": (vault_x + 2) * TILE,
+    "y": bottom * TILE + TILE / 2,
+    "polyline": [{ "x": 0, "y": 0 }, { "x": 0, "y": -3 * TILE }],
+    "properties": [
+      property("ActivatorId", "object", Value::from(gate_id)),
+      property("ReverseDirection", "bool", Value::from(false)),
+    ],
+  }));
+  objects.add(json!({
+    "name": "Glue",
+    "type": "Glue",
+    "x": (vault_x + 2) * TILE,
+    "y": bottom * TILE + TILE / 2,
+    "properties": [
+      property("Object1Id", "object", Value::from(locomotor_id)),
+      property("Object1LocalX", "float", Value::from(0.0)),
+      property("Object1LocalY", "float", Value::from(0.0)),
+      property("Object2Id", "object", Value::from(door_id)),
+      property("Object2LocalX", "float", Value::from(0.0)),
+      property("Object2LocalY", "float", Value::from(0.0)),
+    ],
+  }));
+
+  Ok(())
+}
+
+fn tiled_json(grid: &Grid, objects: Vec<Value>) -> Value {
+  let next_object_id = objects.len() + 1;
+
+  json!({
+    "compressionlevel": -1,
+    "width": grid.width,
+    "height": grid.height,
+    "infinite": false,
+    "orientation": "orthogonal",
+    "renderorder": "right-down",
+    "tilewidth": TILE,
+    "tileheight": TILE,
+    /* Written for assets/maps, where rooms are curated into the game */
+    "tilesets": [{ "firstgid": 1, "source": "tilesets/colliders.tsx" }],
+    "layers": [
+      {
+        "id": 1,
+        "name": "Colliders",
+        "type": "tilelayer",
+        "width": grid.width,
+        "height": grid.height,
+        "x": 0,
+        "y": 0,
+        "opacity": 1,
+        "visible": true,
+        "data": grid.walls.iter().map(|wall| *wall as u32).collect::<Vec<_>>(),
+      },
+      {
+        "id": 2,
+        "name": "Entities",
+        "type": "objectgroup",
+        "draworder": "topdown",
+        "x": 0,
+        "y": 0,
+        "opacity": 1,
+        "visible": true,
+        "objects": objects,
+      },
+    ],
+    "nextlayerid": 3,
+    "nextobjectid": next_object_id,
+    "type": "map",
+    "version": "1.10",
+  })
+}
+
+/* A walled room with the pickups asked for in vaults, cover in between and enemies up to the
+budget, every part of it reachable from the player spawn. The same seed and constraints always
+give the same room */
+pub fn generate(seed: u64, constraints: &Constraints) -> Result<GeneratedMap, String> {
+  let Constraints {
+    width,
+    height,
+    enemy_budget,
+    pickups,
+  } = constraints;
+  let (width, height) = (*width, *height);
+
+  if width < MIN_MAP_WIDTH || height < MIN_MAP_HEIGHT {
+    return Err(format!(
+      "rooms must be at least {}x{} tiles",
+      MIN_MAP_WIDTH, MIN_MAP_HEIGHT
+    ));
+  }
+  /* Vaults share their outer walls with the room's */
+  if pickups.len() as i32 * (VAULT_WIDTH + VAULT_SPACING) - VAULT_SPACING > width {
+    return Err(format!(
+      "a {} tile wide room only fits {} pickups",
+      width,
+      (width + VAULT_SPACING) / (VAULT_WIDTH + VAULT_SPACING)
+    ));
+  }
+
+  let rng = RandGenerator::new();
+  rng.srand(seed);
+
+  let mut grid = Grid {
+    width,
+    height,
+    walls: (0..width * height)
+      .map(|index| {
+        let (x, y) = (index % width, index / width);
+        x == 0 || y == 0 || x == width - 1 || y == height - 1
+      })
+      .collect(),
+    reserved: vec![false; (width * height) as usize],
+  };
+  let mut objects = Objects { objects: vec![] };
+
+  /* MARK: Player spawn */
+  let (spawn_x, spawn_y) = (2, height - 4);
+  grid.reserve(spawn_x - 1, spawn_y - 1, 3, 3);
+  objects.add(json!({
+    "name": "Player Spawn",
+    "type": "PlayerSpawn",
+    "x": spawn_x * TILE + TILE / 2,
+    "y": spawn_y * TILE + TILE / 2,
+  }));
+
+  /* MARK: Vaults */
+  pickups.iter().enumerate().try_for_each(|(index, pickup)| {
+    add_vault(
+      &rng,
+      &mut grid,
+      &mut objects,
+      index as i32 * (VAULT_WIDTH + VAULT_SPACING),
+      *pickup,
+    )
+  })?;
+
+  /* MARK: Cover, only kept where it doesn't cut anything off */
+  (0..width * height / 40).for_each(|_| {
+    let (cover_width, cover_height) = (rng.gen_range(2, 6), rng.gen_range(2, 5));
+    let (x, y) = random_tile(&rng, &grid, cover_width, cover_height);
+
+    if !grid.is_free(x - 1, y - 1, cover_width + 2, cover_height + 2) {
+      return;
+    }
+
+    let cover = (y..y + cover_height)
+      .flat_map(|y| (x..x + cover_width).map(move |x| (x, y)))
+      .collect::<Vec<_>>();
+    cover.iter().for_each(|(x, y)| grid.set_wall(*x, *y, true));
+
+    if !grid.is_connected(spawn_x, spawn_y) {
+      cover.iter().for_each(|(x, y)| grid.set_wall(*x, *y, false));
+    }
+  });
+
+  /* MARK: Enemies, up to the budget */
+  let mut budget = *enemy_budget;
+  while ENEMY_COSTS.iter().any(|(_, cost)| *cost <= budget) {
+    let (name, cost) = ENEMY_COSTS[rng.gen_range(0, ENEMY_COSTS.len())];
+    if cost > budget {
+      continue;
+    }
+
+    let Some((x, y)) = (0..100)
+      .map(|_| random_tile(&rng, &grid, 1, 1))
+      .find(|(x, y)| {
+        grid.is_free(*x, *y, 1, 1)
+          && (x - spawn_x).abs().max((y - spawn_y).abs()) >= ENEMY_SPAWN_CLEARANCE
+      })
+    else {
+      break;
+    };
+    grid.reserve(x, y, 1, 1);
+    budget -= cost;
+
+    objects.add(json!({
+      "name": name,
+      "type": "EnemySpawn",
+      "x": x * TILE + TILE / 2,
+      "y": y * TILE + TILE / 2,
+    }));
+  }
+
+  /* MARK: Check the room loads and holds together */
+  let raw = tiled_json(&grid, objects.objects);
+  let map = serde_json::from_value::<RawMap>(raw.clone())
+    .map_err(|error| error.to_string())?
+    .as_map();
+
+  let issues = validate_map(&map, &HashMap::new());
+  if !issues.is_empty() {
+    return Err(issues.join(", "));
+  }
+
+  Ok(GeneratedMap { raw, map })
+}
+
+pub fn flag_value(flag: &str) -> Option<String> {
+  env::args().skip_while(|arg| arg != flag).nth(1)
+}
+
+fn constraints_from_args() -> Result<Constraints, String> {
+  let defaults = Constraints::default();
+
+  let (width, height) = match flag_value(MAP_SIZE_FLAG) {
+    Some(size) => size
+      .split_once('x')
+      .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+      .ok_or(format!("{} expects a size like 40x25", MAP_SIZE_FLAG))?,
+    None => (defaults.width, defaults.height),
+  };
+
+  let enemy_budget = match flag_value(ENEMY_BUDGET_FLAG) {
+    Some(budget) => budget
+      .parse()
+      .map_err(|_| format!("{} expects a number", ENEMY_BUDGET_FLAG))?,
+    None => defaults.enemy_budget,
+  };
+
+  let pickups = flag_value(PICKUPS_FLAG)
+    .unwrap_or_default()
+    .split(',')
+    .map(str::trim)
+    .filter(|pickup| !pickup.is_empty())
+    .map(|pickup| {
+      WeaponModuleKind::deserialize(pickup.into_deserializer())
+        .map_err(|_: serde::de::value::Error| format!("unknown module {}", pickup))
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+
+  Ok(Constraints {
+    width,
+    height,
+    enemy_budget,
+    pickups,
+  })
+}
+
+/* Generates the room asked for on the command line and writes it out, returning whether it
+succeeded */
+pub fn run() -> bool {
+  let result = flag_value(GENERATE_MAP_FLAG)
+    .and_then(|seed| seed.parse::<u64>().ok())
+    .ok_or(format!("{} expects a seed", GENERATE_MAP_FLAG))
+    .and_then(|seed| {
+      let output = env::args()
+        .skip_while(|arg| arg != GENERATE_MAP_FLAG)
+        .nth(2)
+        .ok_or(format!("{} expects an output path", GENERATE_MAP_FLAG))?;
+
+      let generated = generate(seed, &constraints_from_args()?)?;
+      fs::write(
+        &output,
+        serde_json::to_string_pretty(&generated.raw).unwrap(),
+      )
+      .map_err(|error| format!("{}: {}", output, error))?;
+
+      Ok((output, generated.map))
+    });
+
+  match result {
+    Ok((output, map)) => {
+      println!(
+        "wrote {} with {} enemies and {} pickups",
+        output,
+        map.enemy_spawns.len(),
+        map.item_pickups.len()
+      );
+      true
+    }
+    Err(error) => {
+      println!("couldn't generate a map: {}", error);
+      false
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SEEDS: u64 = 50;
+
+  #[test]
+  fn generated_maps_are_valid_and_complete() {
+    let constraints = Constraints {
+      pickups: vec![WeaponModuleKind::Plasma, WeaponModuleKind::Missile],
+      ..Default::default()
+    };
+
+    (0..SEEDS).for_each(|seed| {
+      let generated =
+        generate(seed, &constraints).unwrap_or_else(|error| panic!("seed {}: {}", seed, error));
+
+      assert_eq!(generated.map.player_spawns.len(), 1, "seed {}", seed);
+      assert_eq!(generated.map.item_pickups.len(), 2, "seed {}", seed);
+      assert_eq!(generated.map.gates.len(), 2, "seed {}", seed);
+      assert!(!generated.map.enemy_spawns.is_empty(), "seed {}", seed);
+    });
+  }
+
+  #[test]
+  fn generation_is_deterministic() {
+    let constraints = Constraints::default();
+
+    assert_eq!(
+      generate(7, &constraints).unwrap().raw,
+      generate(7, &constraints).unwrap().raw
+    );
+  }
+}
@@ -0,0 +1,157 @@
+#[cfg(feature = "count-allocations")]
+use std::alloc::{GlobalAlloc, Layout, System as SystemAllocator};
+use std::{
+  env, fs,
+  rc::Rc,
+  sync::atomic::{AtomicUsize, Ordering},
+  time,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{log, storage, system::System};
+
+/* Frames that take longer than two frames' worth at 60fps are logged with what took the time */
+const SLOW_FRAME_MS: f32 = 1000.0 / 30.0;
+
+/* Also write each slow frame out to slow_frames/ in the data directory, with every system's
+snapshot, for looking into afterwards */
+pub const DUMP_SLOW_FRAMES_FLAG: &str = "--dump-slow-frames";
+const SLOW_FRAMES_DIR_NAME: &str = "slow_frames";
+
+/* Counts every allocation so the profiler overlay can show allocations per frame. Each one
+costs an atomic add, so it's only installed with the count-allocations feature */
+#[cfg(feature = "count-allocations")]
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "count-allocations")]
+unsafe impl GlobalAlloc for CountingAllocator {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    unsafe { SystemAllocator.alloc(layout) }
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    unsafe { SystemAllocator.dealloc(ptr, layout) }
+  }
+
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    unsafe { SystemAllocator.realloc(ptr, layout, new_size) }
+  }
+}
+
+#[cfg(feature = "count-allocations")]
+#[global_allocator]
+static GLOBAL_ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/* Always 0 without the count-allocations feature */
+pub fn allocation_count() -> usize {
+  ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/* Measurements from the most recently completed frame */
+#[derive(Clone, Default)]
+pub struct FrameProfile {
+  /* System name and milliseconds spent in its run */
+  pub system_timings: Vec<(&'static str, f32)>,
+  pub allocations: usize,
+}
+
+impl FrameProfile {
+  pub fn frame_ms(&self) -> f32 {
+    self
+      .system_timings
+      .iter()
+      .map(|(_, elapsed_ms)| elapsed_ms)
+      .sum()
+  }
+}
+
+#[derive(Serialize)]
+struct SlowFrame<'a> {
+  frame_ms: f32,
+  allocations: usize,
+  system_timings: &'a [(&'static str, f32)],
+  diagnostics: Vec<(&'static str, Vec<String>)>,
+  snapshots: Vec<(&'static str, Value)>,
+}
+
+fn timestamp() -> String {
+  let sys_time: DateTime<Utc> = time::SystemTime::now().into();
+  sys_time.format("%Y-%m-%dT%H-%M-%S%.3f").to_string()
+}
+
+fn dump_slow_frame(slow_frame: &SlowFrame) -> Result<String, String> {
+  let dir = storage::data_path(SLOW_FRAMES_DIR_NAME);
+  fs::create_dir_all(&dir).map_err(|error| error.to_string())?;
+
+  let path = storage::data_path(&format!("{}/{}.json", SLOW_FRAMES_DIR_NAME, timestamp()));
+  let contents = serde_json::to_string_pretty(slow_frame).map_err(|error| error.to_string())?;
+  fs::write(&path, contents).map_err(|error| error.to_string())?;
+  Ok(path)
+}
+
+/* Logs a frame that went over budget, slowest systems first, along with whatever the systems
+have to say about it */
+pub fn report_slow_frame<Input: Clone + 'static>(
+  profile: &FrameProfile,
+  systems: &[Rc<dyn System<Input = Input>>],
+) {
+  let frame_ms = profile.frame_ms();
+  if frame_ms <= SLOW_FRAME_MS {
+    return;
+  }
+
+  let mut system_timings = profile.system_timings.clone();
+  system_timings.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+  log::info(
+    "profiler",
+    format!(
+      "slow frame, {:.1}ms: {}",
+      frame_ms,
+      system_timings
+        .iter()
+        .map(|(name, elapsed_ms)| format!("{} {:.1}ms", name, elapsed_ms))
+        .collect::<Vec<_>>()
+        .join(", ")
+    ),
+  );
+
+  let diagnostics = systems
+    .iter()
+    .map(|system| (system.name(), system.diagnostics()))
+    .filter(|(_, lines)| !lines.is_empty())
+    .collect::<Vec<_>>();
+
+  diagnostics.iter().for_each(|(name, lines)| {
+    lines
+      .iter()
+      .for_each(|line| log::info("profiler", format!("{}: {}", name, line)))
+  });
+
+  if !env::args().any(|arg| arg == DUMP_SLOW_FRAMES_FLAG) {
+    return;
+  }
+
+  let slow_frame = SlowFrame {
+    frame_ms,
+    allocations: profile.allocations,
+    system_timings: &profile.system_timings,
+    diagnostics,
+    snapshots: systems
+      .iter()
+      .filter_map(|system| Some((system.name(), system.snapshot()?)))
+      .collect(),
+  };
+
+  match dump_slow_frame(&slow_frame) {
+    Ok(path) => log::info("profiler", format!("wrote {}", path)),
+    Err(error) => log::warn("profiler", format!("couldn't write slow frame: {}", error)),
+  }
+}
@@ -1,36 +1,229 @@
-use std::{env::current_dir, fs, marker::PhantomData, path::Path, rc::Rc, time};
+use std::{
+  collections::HashMap,
+  env::current_dir,
+  fs::{self, File},
+  io::{self, Write},
+  marker::PhantomData,
+  path::Path,
+  rc::Rc,
+  time,
+};
 
 use chrono::{DateTime, Utc};
+use macroquad::{
+  color::BLACK,
+  texture::{Image, Texture2D, get_screen_data},
+};
 use serde::{Deserialize, Serialize};
 
 use crate::{
   ability::AbilitySystem,
+  challenge::{ChallengeGhost, ChallengeSystem, Medal},
   combat::{
-    CombatSystem, EQUIP_SLOTS_HEIGHT, EQUIP_SLOTS_WIDTH, UnequippedModules, WeaponModuleKind,
+    CombatSystem, EQUIP_SLOTS_HEIGHT, EQUIP_SLOTS_WIDTH, ModuleInstance, UnequippedModules,
   },
-  ecs::{Damageable, EntityHandle},
-  load_map::MapSystem,
-  menu::{MenuSystem, SaveToLoad},
+  crash::{record_event, record_snapshot},
+  database::DatabaseSystem,
+  dialogue::DialogueSystem,
+  ecs::{Damageable, EntityHandle, EntityKind},
+  load_map::{DAILY_MAP_NAME, MapAbilityType, MapSystem, check_maps_for},
+  log,
+  menu::{MenuSystem, QuitDecision, SaveToLoad},
   physics::PhysicsSystem,
-  system::System,
+  settings::SettingsSystem,
+  speedrun::{RunTimer, SpeedrunSystem},
+  storage,
+  system::{Requirement, System},
+  tutorial::{TutorialAction, TutorialSystem},
 };
 
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub enum Difficulty {
+  Easy,
+  #[default]
+  Normal,
+  Hard,
+}
+
+impl Difficulty {
+  pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+  pub fn enemy_health_mod(&self) -> f32 {
+    match self {
+      Difficulty::Easy => 0.75,
+      Difficulty::Normal => 1.0,
+      Difficulty::Hard => 1.5,
+    }
+  }
+
+  pub fn enemy_damage_mod(&self) -> f32 {
+    match self {
+      Difficulty::Easy => 0.5,
+      Difficulty::Normal => 1.0,
+      Difficulty::Hard => 1.5,
+    }
+  }
+
+  pub fn health_drop_amount_mod(&self) -> f32 {
+    match self {
+      Difficulty::Easy => 1.25,
+      Difficulty::Normal => 1.0,
+      Difficulty::Hard => 0.75,
+    }
+  }
+
+  pub fn health_drop_chance_mod(&self) -> f32 {
+    match self {
+      Difficulty::Easy => 1.5,
+      Difficulty::Normal => 1.0,
+      Difficulty::Hard => 0.5,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Difficulty::Easy => "easy",
+      Difficulty::Normal => "normal",
+      Difficulty::Hard => "hard",
+    }
+  }
+}
+
+/* A named layout of equipped modules that can be swapped to in game */
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct LoadoutPreset {
+  pub name: String,
+  pub equipped_modules:
+    [[Option<ModuleInstance>; EQUIP_SLOTS_HEIGHT as usize]; EQUIP_SLOTS_WIDTH as usize],
+}
+
+/* What the player has changed in a room that should survive leaving it, keyed by entity Id */
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct MapState {
+  #[serde(default)]
+  pub destroyed_walls: Vec<i32>,
+  #[serde(default)]
+  pub killed_enemies: Vec<i32>,
+  /* Gates latch, so only their highest activation needs keeping */
+  #[serde(default)]
+  pub gate_activations: Vec<(i32, f32)>,
+  #[serde(default)]
+  pub block_translations: Vec<(i32, (f32, f32))>,
+  /* Cleared encounters stay open and don't hand out their rewards again */
+  #[serde(default)]
+  pub cleared_encounters: Vec<i32>,
+}
+
+/* The room as it was left by suspending, put back over the freshly loaded room on resume */
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct SuspendedRoom {
+  pub player_translation: (f32, f32),
+  pub player_velocity: (f32, f32),
+  /* Map placed entities that were still around, any missing had been destroyed */
+  pub entities: Vec<SuspendedEntity>,
+}
+
+/* Found again after the reload by its kind and Id, or Persistent id for walls and enemies */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SuspendedEntity {
+  pub kind: EntityKind,
+  pub id: i32,
+  /* Only kept for bodies that move, fixed ones are wherever the map put them */
+  pub translation: Option<(f32, f32)>,
+  pub velocity: Option<(f32, f32)>,
+  pub health: Option<f32>,
+  pub activation: Option<f32>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct SaveData {
   pub player_spawn_id: i32,
   pub map_name: String,
   pub unequipped_modules: UnequippedModules,
   pub equipped_modules:
-    [[Option<WeaponModuleKind>; EQUIP_SLOTS_HEIGHT as usize]; EQUIP_SLOTS_WIDTH as usize],
+    [[Option<ModuleInstance>; EQUIP_SLOTS_HEIGHT as usize]; EQUIP_SLOTS_WIDTH as usize],
   pub acquired_items: Vec<(String, i32)>,
+  /* The active preset is kept in step with the equipped modules */
+  #[serde(default)]
+  pub loadout_presets: Vec<LoadoutPreset>,
+  #[serde(default)]
+  pub active_loadout: usize,
+  #[serde(default)]
+  pub credits: i32,
   pub player_health: f32,
   pub player_max_health: f32,
   pub acquired_boost: bool,
   pub acquired_chain: bool,
+  #[serde(default)]
+  pub acquired_magnetize: bool,
+  #[serde(default)]
+  pub acquired_hack: bool,
+  #[serde(default)]
+  pub engine_upgrades: Vec<String>,
   pub visited_maps: Vec<String>,
+  /* Saves from before difficulty settings existed are Normal */
+  #[serde(default)]
+  pub difficulty: Difficulty,
+  /* Keyed by map name, rooms the player never changed have no entry */
+  #[serde(default)]
+  pub map_states: HashMap<String, MapState>,
+  #[serde(default)]
+  pub run_timer: RunTimer,
+  #[serde(default)]
+  pub shown_tutorials: Vec<TutorialAction>,
+  #[serde(default)]
+  pub seen_dialogue: Vec<String>,
+  #[serde(default)]
+  pub discovered_logs: Vec<String>,
+  /* Only set when a co-op partner was in the game when saving */
+  #[serde(default)]
+  pub player_two_health: Option<f32>,
+  /* Which of the loadout presets the co-op partner fights with */
+  #[serde(default)]
+  pub player_two_loadout: usize,
+  /* Set for good once assist mode has been used on this playthrough */
+  #[serde(default)]
+  pub assist_used: bool,
+  /* Best medal earned in each challenge room */
+  #[serde(default)]
+  pub medals: HashMap<String, Medal>,
+  /* Fastest clean run through each challenge room, replayed as a ghost */
+  #[serde(default)]
+  pub challenge_ghosts: HashMap<String, ChallengeGhost>,
+  /* Only set for a daily arena run, which plays the arena assembled from this seed */
+  #[serde(default)]
+  pub daily_seed: Option<u64>,
+  /* Only set by suspending mid-room, resuming puts the player back exactly where they were */
+  #[serde(default)]
+  pub suspended_room: Option<SuspendedRoom>,
+}
+
+/* Enough of a save to tell it apart from the others in the load menu */
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct SavePreview {
+  pub equipped_modules:
+    [[Option<ModuleInstance>; EQUIP_SLOTS_HEIGHT as usize]; EQUIP_SLOTS_WIDTH as usize],
+  pub abilities: Vec<MapAbilityType>,
+}
+
+impl From<&SaveData> for SavePreview {
+  fn from(save_data: &SaveData) -> Self {
+    Self {
+      equipped_modules: save_data.equipped_modules,
+      abilities: [
+        (save_data.acquired_boost, MapAbilityType::Boost),
+        (save_data.acquired_chain, MapAbilityType::Chain),
+        (save_data.acquired_magnetize, MapAbilityType::Magnetize),
+        (save_data.acquired_hack, MapAbilityType::Hack),
+      ]
+      .into_iter()
+      .filter_map(|(acquired, ability)| acquired.then_some(ability))
+      .collect(),
+    }
+  }
 }
 
-fn initital_save_file_path() -> String {
+pub fn initital_save_file_path() -> String {
   Path::new(&current_dir().unwrap())
     .join("assets")
     .join("save_initial.json")
@@ -40,58 +233,370 @@ fn initital_save_file_path() -> String {
     .to_string()
 }
 
-fn save_data_path(save_filename: &str) -> String {
+/* The fixed loadout every daily arena run starts with */
+fn daily_save_file_path() -> String {
   Path::new(&current_dir().unwrap())
-    .join("storage")
-    .join(save_filename)
+    .join("assets")
+    .join("save_daily.json")
     .as_os_str()
     .to_str()
     .unwrap()
     .to_string()
 }
 
-fn save_dir_path() -> String {
-  Path::new(&current_dir().unwrap())
-    .join("storage")
-    .as_os_str()
-    .to_str()
-    .unwrap()
-    .to_string()
+pub fn save_data_path(save_filename: &str) -> String {
+  storage::data_path(save_filename)
 }
 
-pub fn load_save(save_to_load: &SaveToLoad) -> SaveData {
-  serde_json::from_str(
-    &fs::read_to_string(match save_to_load {
-      SaveToLoad::Initial => initital_save_file_path(),
-      SaveToLoad::SaveData(path) => save_data_path(path),
+fn save_dir_path() -> &'static Path {
+  storage::data_dir()
+}
+
+/* Crash logs share the storage directory, so only files with this prefix are saves */
+const SAVE_PREFIX: &str = "save_";
+
+/* Progress is snapshotted this often for the crash handler's emergency save */
+const SNAPSHOT_INTERVAL_FRAMES: i64 = 60;
+
+/* Thumbnails sit next to their save as `<save>.png` */
+const THUMBNAIL_EXTENSION: &str = ".png";
+const THUMBNAIL_WIDTH: u16 = 160;
+
+fn thumbnail_path(save_filename: &str) -> String {
+  save_data_path(&format!("{}{}", save_filename, THUMBNAIL_EXTENSION))
+}
+
+/* Previews sit next to their save as `<save>.preview`, so the load menu doesn't have to read
+every save in full */
+const PREVIEW_EXTENSION: &str = ".preview";
+
+fn preview_path(save_filename: &str) -> String {
+  save_data_path(&format!("{}{}", save_filename, PREVIEW_EXTENSION))
+}
+
+/* Saves from before checksums were written into the save itself keep theirs in `<save>.sum` */
+const CHECKSUM_EXTENSION: &str = ".sum";
+/* Writes land here first and are renamed over the real file once complete */
+const TEMP_EXTENSION: &str = ".tmp";
+/* Each save is written twice, a save that doesn't check out is read from `<save>.bak` instead */
+const BACKUP_EXTENSION: &str = ".bak";
+
+/* Written by save & quit and deleted as soon as it's resumed, so it can't be reloaded to undo
+what happened after. Lacks SAVE_PREFIX as well to stay out of the load menu */
+const SUSPEND_SAVE_NAME: &str = "suspend_save";
+
+/* Files kept beside a save that aren't saves themselves */
+const SIDE_FILE_EXTENSIONS: [&str; 5] = [
+  THUMBNAIL_EXTENSION,
+  PREVIEW_EXTENSION,
+  CHECKSUM_EXTENSION,
+  TEMP_EXTENSION,
+  BACKUP_EXTENSION,
+];
+
+fn checksum_path(path: &str) -> String {
+  format!("{}{}", path, CHECKSUM_EXTENSION)
+}
+
+fn backup_path(path: &str) -> String {
+  format!("{}{}", path, BACKUP_EXTENSION)
+}
+
+/* FNV-1a, only there to catch torn or clobbered files, not tampering */
+fn checksum(bytes: &[u8]) -> String {
+  let hash = bytes.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+    (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+  });
+
+  format!("{:016x}", hash)
+}
+
+fn is_checksum(line: &str) -> bool {
+  line.len() == 16 && line.chars().all(|char| char.is_ascii_hexdigit())
+}
+
+/* A crash part way through leaves either the old file or the new one, never half of each. The
+data is synced before the rename and the rename before returning, otherwise a power loss can
+keep the rename but not what it points to */
+fn write_atomically(path: &str, contents: &[u8]) -> io::Result<()> {
+  let temp_path = format!("{}{}", path, TEMP_EXTENSION);
+  let mut temp_file = File::create(&temp_path)?;
+  temp_file.write_all(contents)?;
+  temp_file.sync_all()?;
+  drop(temp_file);
+
+  fs::rename(&temp_path, path)?;
+  sync_parent_dir(path)
+}
+
+/* Directories can only be opened and synced like this on unix, elsewhere the rename is as
+durable as the platform makes it */
+fn sync_parent_dir(path: &str) -> io::Result<()> {
+  match Path::new(path).parent() {
+    Some(parent) if cfg!(unix) => {
+      let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+      } else {
+        parent
+      };
+      File::open(parent)?.sync_all()
+    }
+    _ => Ok(()),
+  }
+}
+
+/* The checksum heads the file it covers, so the two land in a single rename and can't end up
+from different writes */
+fn write_verified(path: &str, contents: &[u8]) -> io::Result<()> {
+  write_atomically(
+    path,
+    &[checksum(contents).as_bytes(), b"\n", contents].concat(),
+  )
+}
+
+/* Files from before checksums were written inline are checked against their `.sum`, or read
+unverified when they don't have one */
+fn read_verified(path: &str) -> Result<String, String> {
+  let raw_file = fs::read_to_string(path).map_err(|error| format!("{path}: {error}"))?;
+  let corrupt = || Err(format!("{path}: checksum mismatch, the file is corrupt"));
+
+  match raw_file.split_once('\n') {
+    Some((expected, contents)) if is_checksum(expected) => {
+      if expected == checksum(contents.as_bytes()) {
+        Ok(contents.to_string())
+      } else {
+        corrupt()
+      }
+    }
+    _ => match fs::read_to_string(checksum_path(path)) {
+      Ok(expected) if expected.trim() != checksum(raw_file.as_bytes()) => corrupt(),
+      _ => Ok(raw_file),
+    },
+  }
+}
+
+fn read_save(path: &str) -> Result<SaveData, String> {
+  let raw_file = read_verified(path)?;
+  serde_json::from_str(&raw_file).map_err(|error| format!("{path}: {error}"))
+}
+
+/* Falls back to the save's own backup, never to another save's */
+fn read_save_or_backup(path: &str) -> Result<SaveData, String> {
+  read_save(path).or_else(|error| {
+    log::warn("save", format!("{}, falling back to its backup", error));
+    /* Report what was wrong with the save asked for, not with the backup */
+    read_save(&backup_path(path)).map_err(|_| error)
+  })
+}
+
+/* Keeps the save about to be overwritten as its backup, so a bad save can be rolled back. A
+damaged one is left alone rather than replacing a backup that's still good */
+fn rotate_backup(path: &str) {
+  if !Path::new(path).exists() {
+    return;
+  }
+
+  if let Err(error) = read_verified(path).and_then(|contents| {
+    write_verified(&backup_path(path), contents.as_bytes()).map_err(|error| error.to_string())
+  }) {
+    log::warn("save", format!("couldn't back up {}: {}", path, error));
+  }
+}
+
+pub fn write_save(save_filename: &str, save_data: &SaveData) -> io::Result<()> {
+  fs::create_dir_all(save_dir_path())?;
+  let path = save_data_path(save_filename);
+  let contents = serde_json::to_string_pretty(save_data)?;
+
+  rotate_backup(&path);
+  write_verified(&path, contents.as_bytes())
+}
+
+/* Nearest neighbour downscale of whatever was last drawn to the screen */
+fn capture_thumbnail() -> Image {
+  let screen = get_screen_data();
+  let scale = screen.width as f32 / THUMBNAIL_WIDTH as f32;
+  let height = ((screen.height as f32 / scale) as u16).max(1);
+
+  let mut thumbnail = Image::gen_image_color(THUMBNAIL_WIDTH, height, BLACK);
+
+  (0..height as u32).for_each(|y| {
+    (0..THUMBNAIL_WIDTH as u32).for_each(|x| {
+      thumbnail.set_pixel(
+        x,
+        y,
+        screen.get_pixel(
+          ((x as f32 * scale) as u32).min(screen.width as u32 - 1),
+          ((y as f32 * scale) as u32).min(screen.height as u32 - 1),
+        ),
+      );
+    })
+  });
+
+  thumbnail
+}
+
+fn load_thumbnail(save_filename: &str) -> Option<Texture2D> {
+  let bytes = fs::read(thumbnail_path(save_filename)).ok()?;
+  let image = Image::from_file_with_format(&bytes, None).ok()?;
+  Some(Texture2D::from_image(&image))
+}
+
+/* Saves from before previews existed are read in full instead */
+fn load_preview(save_filename: &str) -> Option<SavePreview> {
+  fs::read_to_string(preview_path(save_filename))
+    .ok()
+    .and_then(|raw_file| serde_json::from_str(&raw_file).ok())
+    .or_else(|| {
+      read_save(&save_data_path(save_filename))
+        .ok()
+        .map(|save_data| SavePreview::from(&save_data))
     })
-    .unwrap(),
+}
+
+fn write_preview(save_filename: &str, save_data: &SaveData) -> io::Result<()> {
+  write_atomically(
+    &preview_path(save_filename),
+    serde_json::to_string(&SavePreview::from(save_data))?.as_bytes(),
   )
-  .expect("JSON was not well-formatted")
+}
+
+fn discard_suspend_save() {
+  let path = save_data_path(SUSPEND_SAVE_NAME);
+
+  [path.clone(), checksum_path(&path), backup_path(&path)]
+    .iter()
+    .filter(|path| Path::new(path).exists())
+    .for_each(|path| {
+      if let Err(error) = fs::remove_file(path) {
+        log::error("save", format!("couldn't delete {}: {}", path, error));
+      }
+    });
+}
+
+/* Gone whether or not it could be read, a broken one would otherwise be offered forever */
+fn take_suspend_save() -> Result<SaveData, String> {
+  let save_data = read_save_or_backup(&save_data_path(SUSPEND_SAVE_NAME));
+  discard_suspend_save();
+
+  save_data
+}
+
+pub fn load_save(save_to_load: &SaveToLoad) -> Result<SaveData, String> {
+  let save_data = match save_to_load {
+    SaveToLoad::Initial(_) => read_save(&initital_save_file_path())?,
+    SaveToLoad::Daily(_) => read_save(&daily_save_file_path())?,
+    SaveToLoad::Suspended => take_suspend_save()?,
+    SaveToLoad::SaveData(save_filename) => read_save_or_backup(&save_data_path(save_filename))?,
+  };
+
+  Ok(match save_to_load {
+    SaveToLoad::Initial(difficulty) => SaveData {
+      difficulty: *difficulty,
+      ..save_data
+    },
+    SaveToLoad::Daily(seed) => SaveData {
+      map_name: DAILY_MAP_NAME.to_string(),
+      daily_seed: Some(*seed),
+      ..save_data
+    },
+    SaveToLoad::SaveData(_) | SaveToLoad::Suspended => save_data,
+  })
+}
+
+/* The save along with a check that the maps it starts in can be loaded */
+pub fn load_game(save_to_load: &SaveToLoad) -> Result<SaveData, String> {
+  let save_data = load_save(save_to_load)?;
+  check_maps_for(&save_data)?;
+
+  /* Starting over or loading another save abandons a save & quit, or Continue would keep
+  resuming it over whatever was played since */
+  if let SaveToLoad::Initial(_) | SaveToLoad::SaveData(_) = save_to_load {
+    discard_suspend_save();
+  }
+
+  Ok(save_data)
 }
 
 pub struct SaveSystem<Input> {
   pub available_save_data: Vec<String>,
+  /* Continue resumes this ahead of the latest save */
+  pub suspend_save_available: bool,
+  /* Keyed by save file name, saves without a thumbnail have no entry */
+  pub thumbnails: HashMap<String, Texture2D>,
+  /* Keyed by save file name, saves that can't be read at all have no entry */
+  pub previews: HashMap<String, SavePreview>,
   phantom: PhantomData<Input>,
 }
 
+impl<Input> SaveSystem<Input> {
+  pub fn can_continue(&self) -> bool {
+    self.suspend_save_available || !self.available_save_data.is_empty()
+  }
+}
+
 impl<Input: Clone + 'static> System for SaveSystem<Input> {
   type Input = Input;
 
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::when::<SaveData, MenuSystem<_>>(),
+      Requirement::when::<SaveData, MapSystem>(),
+      Requirement::when::<SaveData, CombatSystem>(),
+      Requirement::when::<SaveData, PhysicsSystem>(),
+      Requirement::when::<SaveData, AbilitySystem>(),
+      Requirement::when::<SaveData, SpeedrunSystem>(),
+      Requirement::when::<SaveData, ChallengeSystem>(),
+      Requirement::when::<SaveData, TutorialSystem>(),
+      Requirement::when::<SaveData, DialogueSystem>(),
+      Requirement::when::<SaveData, DatabaseSystem>(),
+      Requirement::when::<SaveData, SettingsSystem>(),
+    ]
+  }
+
   fn start(
     _: &crate::system::ProcessContext<Self::Input>,
   ) -> std::rc::Rc<dyn System<Input = Self::Input>>
   where
     Self: Sized,
   {
+    /* First run, or someone cleared it out */
+    if let Err(error) = fs::create_dir_all(save_dir_path()) {
+      log::error(
+        "save",
+        format!("couldn't create the save directory: {}", error),
+      );
+    }
+
     let mut available_save_data = fs::read_dir(save_dir_path())
-      .unwrap()
+      .into_iter()
+      .flatten()
       .flatten()
       .flat_map(|dir_entry| dir_entry.file_name().into_string())
+      .filter(|file_name| {
+        file_name.starts_with(SAVE_PREFIX)
+          && !SIDE_FILE_EXTENSIONS
+            .iter()
+            .any(|extension| file_name.ends_with(extension))
+      })
       .collect::<Vec<_>>();
     available_save_data.sort();
+
+    let thumbnails = available_save_data
+      .iter()
+      .filter_map(|save| load_thumbnail(save).map(|thumbnail| (save.clone(), thumbnail)))
+      .collect();
+
+    let previews = available_save_data
+      .iter()
+      .filter_map(|save| load_preview(save).map(|preview| (save.clone(), preview)))
+      .collect();
+
     Rc::new(Self {
       available_save_data,
+      suspend_save_available: Path::new(&save_data_path(SUSPEND_SAVE_NAME)).exists(),
+      thumbnails,
+      previews,
       phantom: PhantomData,
     })
   }
@@ -100,57 +605,232 @@ impl<Input: Clone + 'static> System for SaveSystem<Input> {
     &self,
     ctx: &crate::system::ProcessContext<Self::Input>,
   ) -> Rc<dyn System<Input = Self::Input>> {
+    /* Back from a game, pick up any saves it wrote */
+    if ctx.resumed_with::<QuitDecision>().is_some() {
+      return Self::start(ctx);
+    }
+
     let new_save_data = ctx.downcast::<SaveData>().and_then(|ctx| {
-      let menu_system = ctx.get::<MenuSystem<_>>().unwrap();
-      let map_system = ctx.get::<MapSystem>().unwrap();
-      let combat_system = ctx.get::<CombatSystem>().unwrap();
-      let physics_system = ctx.get::<PhysicsSystem>().unwrap();
-      let ability_system = ctx.get::<AbilitySystem>().unwrap();
+      let menu_system = ctx.require::<MenuSystem<_>>().unwrap();
+      let map_system = ctx.require::<MapSystem>().unwrap();
+      let combat_system = ctx.require::<CombatSystem>().unwrap();
+      let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+      let ability_system = ctx.require::<AbilitySystem>().unwrap();
+      let speedrun_system = ctx.require::<SpeedrunSystem>().unwrap();
+      let challenge_system = ctx.require::<ChallengeSystem>().unwrap();
+      let tutorial_system = ctx.require::<TutorialSystem>().unwrap();
+      let dialogue_system = ctx.require::<DialogueSystem>().unwrap();
+      let database_system = ctx.require::<DatabaseSystem>().unwrap();
+      let settings_system = ctx.require::<SettingsSystem>().unwrap();
 
-      /* MARK: Save current progress */
-      menu_system.save_point_confirmed_id.map(|player_spawn_id| {
-        let player_entity = physics_system
-          .entities
-          .get(&EntityHandle::RigidBody(physics_system.player_handle))
-          .unwrap();
+      let player_entity = physics_system
+        .entities
+        .get(&EntityHandle::RigidBody(physics_system.player_handle))
+        .unwrap();
 
-        let player_damageable = player_entity.components.get::<Damageable>().unwrap();
+      let player_damageable = player_entity.components.get::<Damageable>().unwrap();
 
+      let current_save_data = |player_spawn_id: i32| SaveData {
+        player_spawn_id,
+        map_name: map_system.current_map_name.clone(),
+        unequipped_modules: combat_system.unequipped_modules.clone(),
+        equipped_modules: combat_system.equipped_modules.data.0,
+        acquired_items: combat_system.acquired_items.clone(),
+        loadout_presets: combat_system.loadout_presets.clone(),
+        active_loadout: combat_system.active_loadout,
+        player_two_loadout: combat_system.player_two_loadout,
+        credits: combat_system.credits,
+        player_health: player_damageable.health,
+        player_max_health: player_damageable.max_health,
+        acquired_boost: ability_system.acquired_boost,
+        acquired_chain: ability_system.acquired_chain,
+        acquired_magnetize: ability_system.acquired_magnetize,
+        acquired_hack: ability_system.acquired_hack,
+        engine_upgrades: ability_system.engine_upgrades.clone(),
+        visited_maps: map_system.map_registry.keys().cloned().collect(),
+        difficulty: ctx.input.difficulty,
+        run_timer: speedrun_system.run.clone(),
+        shown_tutorials: tutorial_system.shown.clone(),
+        seen_dialogue: dialogue_system.seen.clone(),
+        discovered_logs: database_system.discovered.clone(),
+        player_two_health: physics_system
+          .player_two_handle
+          .and_then(|handle| {
+            physics_system
+              .entities
+              .get(&EntityHandle::RigidBody(handle))
+          })
+          .and_then(|entity| entity.components.get::<Damageable>())
+          .map(|damageable| damageable.health),
+        assist_used: settings_system.assist_used,
+        medals: challenge_system.medals.as_ref().clone(),
+        challenge_ghosts: challenge_system.ghosts.as_ref().clone(),
+        daily_seed: ctx.input.daily_seed,
+        suspended_room: None,
+        map_states: physics_system
+          .map_states()
+          .into_iter()
+          .map(|(map_name, map_state)| (map_name.clone(), map_state.clone()))
+          .collect(),
+      };
+
+      /* MARK: Snapshot progress for the crash handler */
+      if physics_system.frame_count % SNAPSHOT_INTERVAL_FRAMES == 0
+        && player_damageable.health > 0.0
+      {
+        /* The spawn the player entered the current map at is always a valid place to resume */
+        record_snapshot(current_save_data(map_system.target_player_spawn_id));
+      }
+
+      /* MARK: Save & quit, on top of whatever was last saved normally */
+      if menu_system.suspend_confirmed {
         let save_data = SaveData {
-          player_spawn_id,
-          map_name: map_system.current_map_name.clone(),
-          unequipped_modules: combat_system.unequipped_modules.clone(),
-          equipped_modules: combat_system.equipped_modules.data.0,
-          acquired_items: combat_system.acquired_items.clone(),
-          player_health: player_damageable.health,
-          player_max_health: player_damageable.max_health,
-          acquired_boost: ability_system.acquired_boost,
-          acquired_chain: ability_system.acquired_chain,
-          visited_maps: map_system.map_registry.keys().cloned().collect(),
+          suspended_room: Some(physics_system.suspended_room()),
+          ..current_save_data(map_system.target_player_spawn_id)
         };
 
-        let sys_time: DateTime<Utc> = time::SystemTime::now().into();
+        match write_save(SUSPEND_SAVE_NAME, &save_data) {
+          Ok(()) => {
+            record_event("suspended".to_string());
+            log::info("save", "suspended".to_string());
+          }
+          Err(error) => log::error("save", format!("couldn't suspend: {}", error)),
+        }
+      }
 
-        let new_save_path = format!("save_{}", sys_time.format("%+"));
+      /* MARK: Save current progress */
+      menu_system
+        .save_point_confirmed_id
+        .and_then(|player_spawn_id| {
+          let save_data = current_save_data(player_spawn_id);
 
-        fs::write(
-          save_data_path(&new_save_path),
-          serde_json::to_string_pretty(&save_data).unwrap(),
-        )
-        .unwrap();
+          let sys_time: DateTime<Utc> = time::SystemTime::now().into();
 
-        new_save_path
-      })
+          let new_save_path = format!("{}{}", SAVE_PREFIX, sys_time.format("%+"));
+
+          /* A failed save leaves the game running, the player can try another save point */
+          if let Err(error) = write_save(&new_save_path, &save_data) {
+            log::error(
+              "save",
+              format!("couldn't write {}: {}", new_save_path, error),
+            );
+            return None;
+          }
+
+          record_event(format!("saved {}", new_save_path));
+          log::info("save", format!("saved {}", new_save_path));
+          /* The save itself is fine without one, the load menu reads it in full instead */
+          if let Err(error) = write_preview(&new_save_path, &save_data) {
+            log::warn(
+              "save",
+              format!("couldn't write a preview of {}: {}", new_save_path, error),
+            );
+          }
+          let preview = SavePreview::from(&save_data);
+          record_snapshot(save_data);
+
+          let thumbnail = capture_thumbnail();
+          thumbnail.export_png(&thumbnail_path(&new_save_path));
+
+          Some((new_save_path, Texture2D::from_image(&thumbnail), preview))
+        })
     });
 
     Rc::new(SaveSystem {
       available_save_data: self
         .available_save_data
         .iter()
-        .chain(new_save_data.iter())
+        .chain(new_save_data.iter().map(|(save, _, _)| save))
         .cloned()
         .collect(),
+      suspend_save_available: self.suspend_save_available,
+      thumbnails: self
+        .thumbnails
+        .clone()
+        .into_iter()
+        .chain(
+          new_save_data
+            .iter()
+            .map(|(save, thumbnail, _)| (save.clone(), thumbnail.clone())),
+        )
+        .collect(),
+      previews: self
+        .previews
+        .clone()
+        .into_iter()
+        .chain(new_save_data.map(|(save, _, preview)| (save, preview)))
+        .collect(),
       phantom: PhantomData,
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /* A fresh file in the temp directory for each test, so they can run side by side */
+  fn temp_save_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("longinus_{}_{}", name, std::process::id()));
+    [TEMP_EXTENSION, CHECKSUM_EXTENSION, BACKUP_EXTENSION]
+      .iter()
+      .map(|extension| format!("{}{}", path.display(), extension))
+      .chain([path.display().to_string()])
+      .for_each(|path| {
+        let _ = fs::remove_file(path);
+      });
+    path.display().to_string()
+  }
+
+  #[test]
+  fn verified_files_read_back_what_was_written() {
+    let path = temp_save_path("round_trip");
+
+    write_verified(&path, b"{\"credits\": 3}").unwrap();
+
+    assert_eq!(read_verified(&path).unwrap(), "{\"credits\": 3}");
+    assert!(!Path::new(&checksum_path(&path)).exists());
+  }
+
+  #[test]
+  fn damaged_files_fail_their_checksum() {
+    let path = temp_save_path("damaged");
+
+    write_verified(&path, b"{\"credits\": 3}").unwrap();
+    let damaged = fs::read_to_string(&path).unwrap().replace('3', "9");
+    fs::write(&path, damaged).unwrap();
+
+    assert!(read_verified(&path).is_err());
+  }
+
+  #[test]
+  fn backups_hold_the_save_before_the_last_write() {
+    let path = temp_save_path("rotate");
+
+    rotate_backup(&path);
+    write_verified(&path, b"{\"credits\": 1}").unwrap();
+    assert!(!Path::new(&backup_path(&path)).exists());
+
+    rotate_backup(&path);
+    write_verified(&path, b"{\"credits\": 2}").unwrap();
+    assert_eq!(read_verified(&path).unwrap(), "{\"credits\": 2}");
+    assert_eq!(
+      read_verified(&backup_path(&path)).unwrap(),
+      "{\"credits\": 1}"
+    );
+  }
+
+  #[test]
+  fn older_files_are_checked_against_their_side_checksum() {
+    let path = temp_save_path("legacy");
+
+    fs::write(&path, "{}").unwrap();
+    assert_eq!(read_verified(&path).unwrap(), "{}");
+
+    fs::write(checksum_path(&path), checksum(b"{}")).unwrap();
+    assert_eq!(read_verified(&path).unwrap(), "{}");
+
+    fs::write(&path, "{ }").unwrap();
+    assert!(read_verified(&path).is_err());
+  }
+}
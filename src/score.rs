@@ -0,0 +1,197 @@
+use std::{collections::HashMap, fs, rc::Rc};
+
+use crate::{
+  controls::ControlsSystem,
+  log,
+  physics::PhysicsSystem,
+  save::SaveData,
+  storage,
+  system::{Requirement, System},
+};
+
+/* Frames after a kill that the next one still adds to the combo */
+const COMBO_WINDOW_FRAMES: f32 = 120.0;
+const MAX_COMBO_MULTIPLIER: i32 = 8;
+const KILL_SCORE: i32 = 100;
+
+/* Shown once an encounter in a scored room is won */
+#[derive(Clone, Debug)]
+pub struct RoomResults {
+  pub score: i32,
+  pub best_combo: i32,
+  /* The room's best before this result */
+  pub high_score: Option<i32>,
+}
+
+impl RoomResults {
+  pub fn new_high_score(&self) -> bool {
+    self
+      .high_score
+      .is_none_or(|high_score| self.score > high_score)
+  }
+}
+
+fn high_scores_path() -> String {
+  storage::data_path("high_scores.json")
+}
+
+fn load_high_scores() -> HashMap<String, i32> {
+  fs::read_to_string(high_scores_path())
+    .ok()
+    .and_then(|raw_file| serde_json::from_str(&raw_file).ok())
+    .unwrap_or_default()
+}
+
+/* Keeps the score as the room's best if it beats it, writing the high scores out when it does */
+fn record_high_score(
+  high_scores: &Rc<HashMap<String, i32>>,
+  map_name: &str,
+  score: i32,
+) -> Rc<HashMap<String, i32>> {
+  if score <= 0
+    || high_scores
+      .get(map_name)
+      .is_some_and(|high_score| *high_score >= score)
+  {
+    return Rc::clone(high_scores);
+  }
+
+  let high_scores = high_scores
+    .iter()
+    .map(|(map_name, high_score)| (map_name.clone(), *high_score))
+    .chain([(map_name.to_string(), score)])
+    .collect::<HashMap<_, _>>();
+
+  if let Err(error) = fs::create_dir_all(storage::data_dir()).and_then(|_| {
+    fs::write(
+      high_scores_path(),
+      serde_json::to_string_pretty(&high_scores).unwrap(),
+    )
+  }) {
+    log::error("score", format!("failed to write high scores: {}", error));
+  }
+
+  Rc::new(high_scores)
+}
+
+/* Scoring for rooms with the `Scored` property, kills in quick succession building a combo */
+pub struct ScoreSystem {
+  pub map_name: String,
+  pub score: i32,
+  /* Kills in the current combo, which ends once the window after the last one runs out */
+  pub combo: i32,
+  pub combo_frames: f32,
+  pub best_combo: i32,
+  /* Best score reached in each room, across every save */
+  pub high_scores: Rc<HashMap<String, i32>>,
+  /* Set on the frame an encounter in a scored room is won */
+  pub results: Option<RoomResults>,
+}
+
+impl ScoreSystem {
+  pub fn multiplier(&self) -> i32 {
+    self.combo.clamp(1, MAX_COMBO_MULTIPLIER)
+  }
+}
+
+impl System for ScoreSystem {
+  type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<PhysicsSystem>(),
+      Requirement::of::<ControlsSystem<_>>(),
+    ]
+  }
+
+  fn start(ctx: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
+  where
+    Self: Sized,
+  {
+    Rc::new(Self {
+      map_name: ctx.input.map_name.clone(),
+      score: 0,
+      combo: 0,
+      combo_frames: 0.0,
+      best_combo: 0,
+      high_scores: Rc::new(load_high_scores()),
+      results: None,
+    })
+  }
+
+  fn run(
+    &self,
+    ctx: &crate::system::ProcessContext<Self::Input>,
+  ) -> Rc<dyn System<Input = Self::Input>> {
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+    let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
+
+    /* MARK: Record the room's score on leaving it, starting the next from nothing */
+    if physics_system.map_name != self.map_name {
+      return Rc::new(Self {
+        map_name: physics_system.map_name.clone(),
+        score: 0,
+        combo: 0,
+        combo_frames: 0.0,
+        best_combo: 0,
+        high_scores: record_high_score(&self.high_scores, &self.map_name, self.score),
+        results: None,
+      });
+    }
+
+    if !physics_system.scored {
+      return Rc::new(Self {
+        map_name: self.map_name.clone(),
+        high_scores: Rc::clone(&self.high_scores),
+        results: None,
+        ..*self
+      });
+    }
+
+    /* MARK: Let the combo lapse once the window after the last kill runs out */
+    let combo_frames = if controls_system.simulation_frozen() {
+      self.combo_frames
+    } else {
+      (self.combo_frames - controls_system.time_scale).max(0.0)
+    };
+    let combo = if combo_frames > 0.0 { self.combo } else { 0 };
+
+    /* MARK: Score kills at the multiplier the combo has built up to */
+    let (score, combo) =
+      (0..physics_system.new_kills).fold((self.score, combo), |(score, combo), _| {
+        let combo = combo + 1;
+        (
+          score + KILL_SCORE * combo.clamp(1, MAX_COMBO_MULTIPLIER),
+          combo,
+        )
+      });
+    let combo_frames = if physics_system.new_kills > 0 {
+      COMBO_WINDOW_FRAMES
+    } else {
+      combo_frames
+    };
+    let best_combo = self.best_combo.max(combo);
+
+    /* MARK: Show the results of a won encounter, keeping the score if it's the room's best */
+    let results = physics_system.encounter_cleared.then(|| RoomResults {
+      score,
+      best_combo,
+      high_score: self.high_scores.get(&self.map_name).copied(),
+    });
+    let high_scores = if results.is_some() {
+      record_high_score(&self.high_scores, &self.map_name, score)
+    } else {
+      Rc::clone(&self.high_scores)
+    };
+
+    Rc::new(Self {
+      map_name: self.map_name.clone(),
+      score,
+      combo,
+      combo_frames,
+      best_combo,
+      high_scores,
+      results,
+    })
+  }
+}
@@ -0,0 +1,251 @@
+use std::{
+  collections::{HashMap, HashSet},
+  env::current_dir,
+  fs,
+  path::Path,
+  sync::{Arc, Mutex},
+};
+
+use macroquad::rand::RandGenerator;
+use rapier2d::{na::Vector2, prelude::*};
+use rhai::{AST, Array, Dynamic, Engine, Map as ScriptMap, Scope};
+
+use crate::{load_map::MapEnemyName, log, units::vec_zero};
+
+/* Scripts are looked up by file stem, so `assets/scripts/turret.rhai` backs an
+enemy named "Scripted:turret" in map data */
+const SCRIPT_DIRECTORY: &str = "assets/scripts";
+const SCRIPT_EXTENSION: &str = "rhai";
+const SCRIPT_BEHAVIOR_FN: &str = "behavior";
+
+pub type ScriptState = Dynamic;
+
+#[derive(Clone)]
+struct ScriptRng(Arc<RandGenerator>);
+
+impl ScriptRng {
+  fn range(&mut self, low: f64, high: f64) -> f64 {
+    self.0.gen_range(low as f32, high as f32) as f64
+  }
+}
+
+/* MARK: Read-only view handed to scripts */
+
+pub struct ScriptView {
+  pub translation: Vector2<f32>,
+  pub linvel: Vector2<f32>,
+  pub player_translation: Vector2<f32>,
+  pub seed: u64,
+}
+
+fn vector_to_script(vector: &Vector2<f32>) -> Dynamic {
+  Dynamic::from_array(vec![
+    Dynamic::from_float(vector.x as f64),
+    Dynamic::from_float(vector.y as f64),
+  ])
+}
+
+fn script_to_f32(value: &Dynamic) -> Option<f32> {
+  value
+    .as_float()
+    .ok()
+    .map(|float| float as f32)
+    .or(value.as_int().ok().map(|int| int as f32))
+}
+
+fn script_to_vector(value: &Dynamic) -> Option<Vector2<f32>> {
+  let array = value.clone().try_cast::<Array>()?;
+  match array.as_slice() {
+    [x, y] => Some(vector![script_to_f32(x)?, script_to_f32(y)?]),
+    _ => None,
+  }
+}
+
+fn script_field<T>(map: &ScriptMap, field: &str, convert: fn(&Dynamic) -> Option<T>) -> Option<T> {
+  map.get(field).and_then(convert)
+}
+
+/* MARK: What a script hands back */
+
+pub struct ScriptProjectile {
+  /* Always normalized, shots aimed nowhere are dropped */
+  pub direction: Vector2<f32>,
+  pub speed: f32,
+  pub damage: f32,
+  pub radius: f32,
+}
+
+pub struct ScriptSpawn {
+  pub name: MapEnemyName,
+  pub initial_force: Vector2<f32>,
+}
+
+pub struct ScriptDecision {
+  pub movement_force: Vector2<f32>,
+  pub projectiles: Vec<ScriptProjectile>,
+  pub spawns: Vec<ScriptSpawn>,
+  /* Name of a dialogue to play, for bosses announcing a new phase */
+  pub dialogue: Option<String>,
+  pub state: ScriptState,
+}
+
+const SCRIPT_PROJECTILE_DEFAULT_SPEED: f32 = 1.0;
+const SCRIPT_PROJECTILE_DEFAULT_DAMAGE: f32 = 5.0;
+const SCRIPT_PROJECTILE_DEFAULT_RADIUS: f32 = 0.2;
+
+fn script_array(map: &ScriptMap, field: &str) -> Vec<ScriptMap> {
+  map
+    .get(field)
+    .and_then(|value| value.clone().try_cast::<Array>())
+    .unwrap_or_default()
+    .into_iter()
+    .filter_map(|value| value.try_cast::<ScriptMap>())
+    .collect()
+}
+
+fn decision_from_script(result: ScriptMap, previous_state: &ScriptState) -> ScriptDecision {
+  let projectiles = script_array(&result, "projectiles")
+    .iter()
+    .filter_map(|projectile| {
+      Some(ScriptProjectile {
+        direction: script_field(projectile, "direction", script_to_vector)?.try_normalize(0.0)?,
+        speed: script_field(projectile, "speed", script_to_f32)
+          .unwrap_or(SCRIPT_PROJECTILE_DEFAULT_SPEED),
+        damage: script_field(projectile, "damage", script_to_f32)
+          .unwrap_or(SCRIPT_PROJECTILE_DEFAULT_DAMAGE),
+        radius: script_field(projectile, "radius", script_to_f32)
+          .unwrap_or(SCRIPT_PROJECTILE_DEFAULT_RADIUS),
+      })
+    })
+    .collect();
+
+  let spawns = script_array(&result, "spawns")
+    .iter()
+    .filter_map(|spawn| {
+      let name = spawn.get("name")?.clone().into_string().ok()?;
+      Some(ScriptSpawn {
+        name: MapEnemyName::try_from(name)
+          .map_err(|error| script_warning(format!("can't spawn {}", error)))
+          .ok()?,
+        initial_force: script_field(spawn, "force", script_to_vector).unwrap_or(vec_zero()),
+      })
+    })
+    .collect();
+
+  ScriptDecision {
+    movement_force: script_field(&result, "force", script_to_vector).unwrap_or(vec_zero()),
+    projectiles,
+    spawns,
+    dialogue: result
+      .get("dialogue")
+      .and_then(|dialogue| dialogue.clone().into_string().ok()),
+    state: result
+      .get("state")
+      .cloned()
+      .unwrap_or(previous_state.clone()),
+  }
+}
+
+/* MARK: Registry */
+
+pub struct ScriptRegistry {
+  engine: Engine,
+  scripts: HashMap<String, AST>,
+  /* Scripts that are missing or have failed once, left idle from then on so they only warn once */
+  disabled: Mutex<HashSet<String>>,
+}
+
+fn script_warning(message: String) {
+  log::warn("script", message);
+}
+
+impl ScriptRegistry {
+  pub fn load() -> Self {
+    let mut engine = Engine::new();
+    engine
+      .register_type_with_name::<ScriptRng>("Rng")
+      .register_fn("range", ScriptRng::range);
+
+    let script_directory = Path::new(&current_dir().unwrap()).join(SCRIPT_DIRECTORY);
+
+    let scripts = fs::read_dir(script_directory)
+      .map(|entries| {
+        entries
+          .flatten()
+          .map(|entry| entry.path())
+          .collect::<Vec<_>>()
+      })
+      .unwrap_or_default()
+      .into_iter()
+      .filter(|path| path.extension().is_some_and(|ext| ext == SCRIPT_EXTENSION))
+      .filter_map(|path| {
+        let name = path.file_stem()?.to_str()?.to_string();
+        match engine.compile_file(path.clone()) {
+          Ok(ast) => Some((name, ast)),
+          Err(error) => {
+            script_warning(format!("{} failed to compile: {}", path.display(), error));
+            None
+          }
+        }
+      })
+      .collect();
+
+    Self {
+      engine,
+      scripts,
+      disabled: Mutex::new(HashSet::new()),
+    }
+  }
+
+  pub fn behavior(&self, name: &str, state: &ScriptState, view: ScriptView) -> ScriptDecision {
+    let idle = || ScriptDecision {
+      movement_force: vec_zero(),
+      projectiles: vec![],
+      spawns: vec![],
+      dialogue: None,
+      state: state.clone(),
+    };
+
+    if self.disabled.lock().unwrap().contains(name) {
+      return idle();
+    }
+
+    /* Enemies without a registered script stand idle */
+    let Some(ast) = self.scripts.get(name) else {
+      self.disable(name, format!("no script named {}", name));
+      return idle();
+    };
+
+    let rng = RandGenerator::new();
+    rng.srand(view.seed);
+
+    let mut script_view = ScriptMap::new();
+    script_view.insert("position".into(), vector_to_script(&view.translation));
+    script_view.insert("velocity".into(), vector_to_script(&view.linvel));
+    script_view.insert(
+      "player_position".into(),
+      vector_to_script(&view.player_translation),
+    );
+    script_view.insert("rng".into(), Dynamic::from(ScriptRng(Arc::new(rng))));
+
+    match self.engine.call_fn::<ScriptMap>(
+      &mut Scope::new(),
+      ast,
+      SCRIPT_BEHAVIOR_FN,
+      (script_view, state.clone()),
+    ) {
+      Ok(result) => decision_from_script(result, state),
+      Err(error) => {
+        self.disable(name, format!("{} failed and is disabled: {}", name, error));
+        idle()
+      }
+    }
+  }
+
+  /* Enemies decide on several threads at once, so only the first to disable a script warns */
+  fn disable(&self, name: &str, message: String) {
+    if self.disabled.lock().unwrap().insert(name.to_string()) {
+      script_warning(message);
+    }
+  }
+}
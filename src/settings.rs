@@ -0,0 +1,351 @@
+use std::{fs, rc::Rc};
+
+use rapier2d::prelude::IntegrationParameters;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  locale::{self, Language},
+  log::{self, LogLevel},
+  menu::MenuSystem,
+  palette::{self, PaletteKind},
+  save::SaveData,
+  storage,
+  system::{Requirement, System},
+  text::{self, UiScale},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub enum AimAssist {
+  /* No assistance at all */
+  Pure,
+  Low,
+  #[default]
+  Medium,
+  High,
+}
+
+impl AimAssist {
+  pub const ALL: [AimAssist; 4] = [
+    AimAssist::Pure,
+    AimAssist::Low,
+    AimAssist::Medium,
+    AimAssist::High,
+  ];
+
+  /* Fraction of the angle between the reticle and the target that shots bend by */
+  pub fn strength(&self) -> f32 {
+    match self {
+      AimAssist::Pure => 0.0,
+      AimAssist::Low => 0.25,
+      AimAssist::Medium => 0.5,
+      AimAssist::High => 0.8,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      AimAssist::Pure => "pure",
+      AimAssist::Low => "low",
+      AimAssist::Medium => "medium",
+      AimAssist::High => "high",
+    }
+  }
+
+  pub fn cycle(&self, step: i32) -> Self {
+    let index = AimAssist::ALL
+      .iter()
+      .position(|aim_assist| aim_assist == self)
+      .unwrap() as i32;
+
+    AimAssist::ALL[(index + step).rem_euclid(AimAssist::ALL.len() as i32) as usize]
+  }
+}
+
+/* Which input boosts, the face button being easier to reach than the trigger */
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub enum BoostBinding {
+  #[default]
+  Trigger,
+  FaceButton,
+}
+
+impl BoostBinding {
+  pub const ALL: [BoostBinding; 2] = [BoostBinding::Trigger, BoostBinding::FaceButton];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      BoostBinding::Trigger => "trigger",
+      BoostBinding::FaceButton => "face_button",
+    }
+  }
+
+  pub fn cycle(&self, step: i32) -> Self {
+    let index = BoostBinding::ALL
+      .iter()
+      .position(|boost_binding| boost_binding == self)
+      .unwrap() as i32;
+
+    BoostBinding::ALL[(index + step).rem_euclid(BoostBinding::ALL.len() as i32) as usize]
+  }
+}
+
+/* Trades physics accuracy for speed, lower settings can let fast things pass through each other */
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub enum PhysicsQuality {
+  Low,
+  #[default]
+  Normal,
+  High,
+}
+
+impl PhysicsQuality {
+  pub const ALL: [PhysicsQuality; 3] = [
+    PhysicsQuality::Low,
+    PhysicsQuality::Normal,
+    PhysicsQuality::High,
+  ];
+
+  /* Solver iterations, and how many times a body with CCD on can be stopped within a step */
+  pub fn apply(&self, integration_parameters: IntegrationParameters) -> IntegrationParameters {
+    let (num_solver_iterations, max_ccd_substeps) = match self {
+      PhysicsQuality::Low => (2, 1),
+      PhysicsQuality::Normal => (4, 1),
+      PhysicsQuality::High => (8, 4),
+    };
+
+    IntegrationParameters {
+      num_solver_iterations,
+      max_ccd_substeps,
+      ..integration_parameters
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      PhysicsQuality::Low => "low",
+      PhysicsQuality::Normal => "normal",
+      PhysicsQuality::High => "high",
+    }
+  }
+
+  pub fn cycle(&self, step: i32) -> Self {
+    let index = PhysicsQuality::ALL
+      .iter()
+      .position(|physics_quality| physics_quality == self)
+      .unwrap() as i32;
+
+    PhysicsQuality::ALL[(index + step).rem_euclid(PhysicsQuality::ALL.len() as i32) as usize]
+  }
+}
+
+/* Percentage of normal speed the whole simulation runs at */
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct GameSpeed(u8);
+
+impl Default for GameSpeed {
+  fn default() -> Self {
+    GameSpeed(100)
+  }
+}
+
+impl GameSpeed {
+  const SLOWEST: u8 = 50;
+  const STEP: u8 = 10;
+  const STEPS: i32 = 6;
+
+  pub fn factor(&self) -> f32 {
+    self.0 as f32 / 100.0
+  }
+
+  pub fn percent(&self) -> u8 {
+    self.0
+  }
+
+  pub fn cycle(&self, step: i32) -> Self {
+    let index = (self.0.clamp(GameSpeed::SLOWEST, 100) - GameSpeed::SLOWEST) / GameSpeed::STEP;
+
+    GameSpeed(
+      GameSpeed::SLOWEST
+        + ((index as i32 + step).rem_euclid(GameSpeed::STEPS) as u8) * GameSpeed::STEP,
+    )
+  }
+}
+
+/* Percentage of the damage that lands on the players, down to none at all */
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct DamageTaken(u8);
+
+impl Default for DamageTaken {
+  fn default() -> Self {
+    DamageTaken(100)
+  }
+}
+
+impl DamageTaken {
+  const STEP: u8 = 25;
+  const STEPS: i32 = 5;
+
+  pub fn factor(&self) -> f32 {
+    self.0 as f32 / 100.0
+  }
+
+  pub fn percent(&self) -> u8 {
+    self.0
+  }
+
+  pub fn cycle(&self, step: i32) -> Self {
+    let index = self.0.min(100) / DamageTaken::STEP;
+
+    DamageTaken(((index as i32 + step).rem_euclid(DamageTaken::STEPS) as u8) * DamageTaken::STEP)
+  }
+}
+
+/* Help for players who want it, using any of it marks the save */
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct Assist {
+  #[serde(default)]
+  pub damage_taken: DamageTaken,
+  /* Boosting never uses up a charge */
+  #[serde(default)]
+  pub infinite_boost: bool,
+  #[serde(default)]
+  pub no_knockback: bool,
+}
+
+impl Assist {
+  pub fn enabled(&self) -> bool {
+    *self != Assist::default()
+  }
+}
+
+/* Player preferences which apply across every save */
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct Settings {
+  #[serde(default)]
+  pub aim_assist: AimAssist,
+  /* Lowest severity printed to the console */
+  #[serde(default)]
+  pub log_level: LogLevel,
+  /* Also write log lines to logs/ in the data directory */
+  #[serde(default)]
+  pub log_to_file: bool,
+  #[serde(default)]
+  pub speedrun_timer: bool,
+  /* Lets a second gamepad join as player two */
+  #[serde(default)]
+  pub coop: bool,
+  #[serde(default)]
+  pub palette: PaletteKind,
+  #[serde(default)]
+  pub high_contrast: bool,
+  /* Scales all interface text on top of the window size */
+  #[serde(default)]
+  pub ui_scale: UiScale,
+  #[serde(default)]
+  pub language: Language,
+  /* Pressing fire starts firing until it's pressed again */
+  #[serde(default)]
+  pub toggle_fire: bool,
+  /* Aim stays where it was last pointed when the stick is let go */
+  #[serde(default)]
+  pub sticky_aim: bool,
+  #[serde(default)]
+  pub boost_binding: BoostBinding,
+  #[serde(default)]
+  pub game_speed: GameSpeed,
+  /* Dots out where shots will fly over the next second */
+  #[serde(default)]
+  pub trajectory_preview: bool,
+  /* Points out enemies and pickups just off screen from its edges */
+  #[serde(default)]
+  pub radar: bool,
+  /* Draws a line along the aim up to the first wall in the way */
+  #[serde(default)]
+  pub aim_line: bool,
+  /* Plays back the best run through challenge rooms next to the player */
+  #[serde(default)]
+  pub ghost_replay: bool,
+  #[serde(default)]
+  pub physics_quality: PhysicsQuality,
+  #[serde(default)]
+  pub assist: Assist,
+}
+
+impl Settings {
+  /* Logging, colors, text and strings are read from statics, so changes have to be pushed out to them */
+  fn apply(&self) {
+    log::configure(self.log_level, self.log_to_file);
+    palette::configure(self.palette, self.high_contrast);
+    text::configure(self.ui_scale);
+    locale::configure(self.language);
+  }
+}
+
+fn settings_path() -> String {
+  storage::data_path("settings.json")
+}
+
+fn load_settings() -> Settings {
+  fs::read_to_string(settings_path())
+    .ok()
+    .and_then(|raw_file| serde_json::from_str(&raw_file).ok())
+    .unwrap_or_default()
+}
+
+pub struct SettingsSystem {
+  pub settings: Settings,
+  /* Latched once assist mode is turned on, and carried over from the save */
+  pub assist_used: bool,
+}
+
+impl System for SettingsSystem {
+  type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![Requirement::of::<MenuSystem<_>>()]
+  }
+
+  fn start(ctx: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
+  where
+    Self: Sized,
+  {
+    let settings = load_settings();
+    settings.apply();
+
+    Rc::new(Self {
+      settings,
+      assist_used: ctx.input.assist_used || settings.assist.enabled(),
+    })
+  }
+
+  fn run(
+    &self,
+    ctx: &crate::system::ProcessContext<Self::Input>,
+  ) -> Rc<dyn System<Input = Self::Input>> {
+    let menu_system = ctx.require::<MenuSystem<_>>().unwrap();
+
+    /* MARK: Persist settings changed from the menu */
+    if let Some(settings) = menu_system.settings_update {
+      if let Err(error) = fs::create_dir_all(storage::data_dir()).and_then(|_| {
+        fs::write(
+          settings_path(),
+          serde_json::to_string_pretty(&settings).unwrap(),
+        )
+      }) {
+        log::error("settings", format!("failed to write settings: {}", error));
+      }
+
+      settings.apply();
+
+      return Rc::new(Self {
+        settings,
+        assist_used: self.assist_used || settings.assist.enabled(),
+      });
+    }
+
+    Rc::new(Self {
+      settings: self.settings,
+      assist_used: self.assist_used,
+    })
+  }
+}
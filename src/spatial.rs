@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use rapier2d::na::Vector2;
+
+type Cell = (i32, i32);
+type Cells<T> = HashMap<Cell, Vec<(Vector2<f32>, T)>>;
+
+/* Buckets positions into square cells so neighbor lookups only visit the cells
+overlapping the query radius instead of every item */
+pub struct SpatialHash<T> {
+  cell_size: f32,
+  cells: Cells<T>,
+}
+
+impl<T> SpatialHash<T> {
+  pub fn new(cell_size: f32, items: impl IntoIterator<Item = (Vector2<f32>, T)>) -> Self {
+    let mut cells: Cells<T> = HashMap::new();
+
+    items.into_iter().for_each(|(position, item)| {
+      cells
+        .entry(cell_of(cell_size, &position))
+        .or_default()
+        .push((position, item))
+    });
+
+    Self { cell_size, cells }
+  }
+
+  pub fn within(
+    &self,
+    center: Vector2<f32>,
+    radius: f32,
+  ) -> impl Iterator<Item = &(Vector2<f32>, T)> {
+    let (min_x, min_y) = cell_of(self.cell_size, &(center - Vector2::new(radius, radius)));
+    let (max_x, max_y) = cell_of(self.cell_size, &(center + Vector2::new(radius, radius)));
+
+    (min_x..=max_x)
+      .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+      .filter_map(|cell| self.cells.get(&cell))
+      .flatten()
+      .filter(move |(position, _)| (position - center).magnitude() <= radius)
+  }
+
+  pub fn nearest(
+    &self,
+    center: Vector2<f32>,
+    radius: f32,
+    predicate: impl Fn(&T) -> bool,
+  ) -> Option<&(Vector2<f32>, T)> {
+    self
+      .within(center, radius)
+      .filter(|(_, item)| predicate(item))
+      .min_by(|(a, _), (b, _)| {
+        (a - center)
+          .magnitude()
+          .total_cmp(&(b - center).magnitude())
+      })
+  }
+}
+
+fn cell_of(cell_size: f32, position: &Vector2<f32>) -> Cell {
+  (
+    (position.x / cell_size).floor() as i32,
+    (position.y / cell_size).floor() as i32,
+  )
+}
@@ -0,0 +1,177 @@
+use std::{fs, rc::Rc};
+
+use macroquad::time::get_frame_time;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  controls::ControlsSystem,
+  log,
+  menu::MenuSystem,
+  physics::PhysicsSystem,
+  save::{MapState, SaveData},
+  storage,
+  system::{Requirement, System},
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Split {
+  pub label: String,
+  pub time: f32,
+}
+
+/* Play time and splits since the new game this save descends from */
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct RunTimer {
+  pub time: f32,
+  pub splits: Vec<Split>,
+}
+
+pub fn format_run_time(time: f32) -> String {
+  let centiseconds = (time * 100.0) as u32;
+  format!(
+    "{}:{:02}.{:02}",
+    centiseconds / 6000,
+    (centiseconds / 100) % 60,
+    centiseconds % 100
+  )
+}
+
+fn best_splits_path() -> String {
+  storage::data_path("splits.json")
+}
+
+fn load_best_splits() -> Vec<Split> {
+  fs::read_to_string(best_splits_path())
+    .ok()
+    .and_then(|raw_file| serde_json::from_str(&raw_file).ok())
+    .unwrap_or_default()
+}
+
+fn persistent_kill_count<'a>(map_states: impl Iterator<Item = &'a MapState>) -> usize {
+  map_states
+    .map(|map_state| map_state.killed_enemies.len())
+    .sum()
+}
+
+pub struct SpeedrunSystem {
+  pub run: RunTimer,
+  /* Fastest time each split has been reached in, by position in the run */
+  pub best_splits: Rc<Vec<Split>>,
+  pub persistent_kills: usize,
+}
+
+impl SpeedrunSystem {
+  /* Difference from the best time for the latest split, if the best run took the same route */
+  pub fn last_split_delta(&self) -> Option<(&Split, f32)> {
+    let index = self.run.splits.len().checked_sub(1)?;
+    let split = &self.run.splits[index];
+    let best = self.best_splits.get(index)?;
+
+    (best.label == split.label).then_some((split, split.time - best.time))
+  }
+}
+
+impl System for SpeedrunSystem {
+  type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<PhysicsSystem>(),
+      Requirement::of::<MenuSystem<_>>(),
+      Requirement::of::<ControlsSystem<_>>(),
+    ]
+  }
+
+  fn start(ctx: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
+  where
+    Self: Sized,
+  {
+    Rc::new(Self {
+      run: ctx.input.run_timer.clone(),
+      best_splits: Rc::new(load_best_splits()),
+      persistent_kills: persistent_kill_count(ctx.input.map_states.values()),
+    })
+  }
+
+  fn run(
+    &self,
+    ctx: &crate::system::ProcessContext<Self::Input>,
+  ) -> Rc<dyn System<Input = Self::Input>> {
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+    let menu_system = ctx.require::<MenuSystem<_>>().unwrap();
+    let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
+
+    /* MARK: Only count time spent playing */
+    let time = if menu_system.active_menus.is_empty() && !controls_system.simulation_frozen() {
+      self.run.time + get_frame_time()
+    } else {
+      self.run.time
+    };
+
+    /* MARK: Split on map transitions and non-respawning enemy kills */
+    let persistent_kills = persistent_kill_count(physics_system.map_states.values());
+
+    let new_splits = physics_system
+      .load_new_map
+      .iter()
+      .map(|(map_name, _)| map_name.clone())
+      .chain(
+        (persistent_kills > self.persistent_kills)
+          .then(|| format!("{} kill", physics_system.map_name)),
+      )
+      .map(|label| Split { label, time })
+      .collect::<Vec<_>>();
+
+    if new_splits.is_empty() {
+      return Rc::new(Self {
+        run: RunTimer {
+          time,
+          splits: self.run.splits.clone(),
+        },
+        best_splits: Rc::clone(&self.best_splits),
+        persistent_kills,
+      });
+    }
+
+    let splits = [self.run.splits.clone(), new_splits].concat();
+
+    /* MARK: Keep the best time reached at each split */
+    let best_splits = splits
+      .iter()
+      .enumerate()
+      .map(|(index, split)| match self.best_splits.get(index) {
+        Some(best) if best.label != split.label || best.time <= split.time => best.clone(),
+        _ => split.clone(),
+      })
+      .chain(self.best_splits.iter().skip(splits.len()).cloned())
+      .collect::<Vec<_>>();
+
+    let best_splits = if best_splits
+      .iter()
+      .zip(self.best_splits.iter())
+      .all(|(split, best)| split.time == best.time)
+      && best_splits.len() == self.best_splits.len()
+    {
+      Rc::clone(&self.best_splits)
+    } else {
+      if let Err(error) = fs::create_dir_all(storage::data_dir()).and_then(|_| {
+        fs::write(
+          best_splits_path(),
+          serde_json::to_string_pretty(&best_splits).unwrap(),
+        )
+      }) {
+        log::error(
+          "speedrun",
+          format!("failed to write best splits: {}", error),
+        );
+      }
+      Rc::new(best_splits)
+    };
+
+    Rc::new(Self {
+      run: RunTimer { time, splits },
+      best_splits,
+      persistent_kills,
+    })
+  }
+}
@@ -0,0 +1,58 @@
+use std::{
+  env::{self, current_dir},
+  path::{Path, PathBuf},
+  sync::OnceLock,
+};
+
+/* Where saves, settings, splits and logs are written */
+pub const DATA_DIR_FLAG: &str = "--data-dir";
+pub const DATA_DIR_ENV: &str = "LONGINUS_DATA_DIR";
+
+const APP_DIR_NAME: &str = "longinus";
+
+/* Resolved once, the same path is needed from inside and outside of the game loop */
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/* Has to be called before anything reads or writes storage to take effect */
+pub fn set_data_dir(path: &str) {
+  let _ = DATA_DIR.set(PathBuf::from(path));
+}
+
+/* The directory the OS expects per-user app data in */
+fn platform_data_dir() -> Option<PathBuf> {
+  let from_env = |name: &str| {
+    env::var_os(name)
+      .filter(|value| !value.is_empty())
+      .map(PathBuf::from)
+  };
+
+  if cfg!(target_os = "windows") {
+    from_env("APPDATA")
+  } else if cfg!(target_os = "macos") {
+    from_env("HOME").map(|home| home.join("Library").join("Application Support"))
+  } else {
+    from_env("XDG_DATA_HOME")
+      .or_else(|| from_env("HOME").map(|home| home.join(".local").join("share")))
+  }
+  .map(|dir| dir.join(APP_DIR_NAME))
+}
+
+/* The flag wins over the env var, falling back to ./storage when the platform gives nothing */
+pub fn data_dir() -> &'static Path {
+  DATA_DIR.get_or_init(|| {
+    env::var_os(DATA_DIR_ENV)
+      .filter(|value| !value.is_empty())
+      .map(PathBuf::from)
+      .or_else(platform_data_dir)
+      .unwrap_or_else(|| current_dir().unwrap().join("storage"))
+  })
+}
+
+pub fn data_path(file_name: &str) -> String {
+  data_dir()
+    .join(file_name)
+    .as_os_str()
+    .to_str()
+    .unwrap()
+    .to_string()
+}
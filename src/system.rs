@@ -1,7 +1,27 @@
-use std::{any::Any, rc::Rc};
+use std::{
+  any::{Any, type_name},
+  fmt,
+  pin::Pin,
+  rc::Rc,
+  time::Instant,
+};
 
 use macroquad::window::next_frame;
 
+use crate::{
+  log,
+  profiler::{self, FrameProfile, allocation_count},
+};
+
+fn short_type_name<T: ?Sized>() -> &'static str {
+  let full_name = type_name::<T>();
+  let without_generics = full_name.split('<').next().unwrap_or(full_name);
+  without_generics
+    .rsplit("::")
+    .next()
+    .unwrap_or(without_generics)
+}
+
 pub trait System: Any {
   type Input: Clone + 'static;
 
@@ -10,15 +30,142 @@ pub trait System: Any {
     Self: Sized;
 
   fn run(&self, _: &ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>;
+
+  /* Used to label the system in the profiler overlay */
+  fn name(&self) -> &'static str {
+    short_type_name::<Self>()
+  }
+
+  /* Systems this one requires, checked once every system in the process has started */
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![]
+  }
+
+  /* Logged with a slow frame, e.g. what inside the system took the time */
+  fn diagnostics(&self) -> Vec<String> {
+    vec![]
+  }
+
+  /* Written out with a slow frame when they're being dumped */
+  fn snapshot(&self) -> Option<serde_json::Value> {
+    None
+  }
+}
+
+pub struct Requirement<Input: Clone + 'static> {
+  pub name: &'static str,
+  present: fn(&ProcessContext<Input>) -> bool,
+}
+
+impl<Input: Clone + 'static> Requirement<Input> {
+  pub fn of<Target: System<Input = Input>>() -> Self {
+    Self {
+      name: short_type_name::<Target>(),
+      present: |ctx| ctx.get::<Target>().is_some(),
+    }
+  }
+
+  /* For systems shared between processes, only required in the process running on ProcessInput */
+  pub fn when<ProcessInput: Clone + 'static, Target: System<Input = ProcessInput>>() -> Self {
+    Self {
+      name: short_type_name::<Target>(),
+      present: |ctx| {
+        ctx
+          .downcast::<ProcessInput>()
+          .is_none_or(|ctx| ctx.get::<Target>().is_some())
+      },
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct MissingSystem {
+  pub missing: &'static str,
+  /* None while the process is starting, or when required from outside any system */
+  pub requester: Option<&'static str>,
+}
+
+impl fmt::Display for MissingSystem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.requester {
+      Some(requester) => write!(
+        f,
+        "{} requires {}, which is not part of this process",
+        requester, self.missing
+      ),
+      None => write!(
+        f,
+        "{} is not part of this process, or has not started yet",
+        self.missing
+      ),
+    }
+  }
+}
+
+/* So that unwrapping a require reads the same as the message */
+impl fmt::Debug for MissingSystem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Display::fmt(self, f)
+  }
+}
+
+/* A process started by another, which stays suspended until this finishes */
+pub type SubProcess = Pin<Box<dyn Future<Output = Rc<dyn Any>>>>;
+
+/* What a running process does before its next frame */
+pub enum Step<Output> {
+  Continue,
+  Push(SubProcess),
+  Exit(Output),
 }
 
 #[derive(Clone)]
 pub struct ProcessContext<Input: Clone + 'static> {
   pub systems: Vec<Rc<dyn System<Input = Input>>>,
   pub input: Input,
+  pub profile: Rc<FrameProfile>,
+  /* The system currently being run */
+  pub requester: Option<&'static str>,
+  /* Output of the sub-process that just finished, only set for the frame the process resumes on */
+  pub sub_process_output: Option<Rc<dyn Any>>,
 }
 
 impl<Input: Clone + 'static> ProcessContext<Input> {
+  pub fn require<Target>(&self) -> Result<Rc<Target>, MissingSystem>
+  where
+    Target: System<Input = Input>,
+  {
+    self.get::<Target>().ok_or(MissingSystem {
+      missing: short_type_name::<Target>(),
+      requester: self.requester,
+    })
+  }
+
+  pub fn resumed_with<Output: 'static>(&self) -> Option<Rc<Output>> {
+    self
+      .sub_process_output
+      .as_ref()
+      .and_then(|output| Rc::clone(output).downcast::<Output>().ok())
+  }
+
+  /* Every requirement declared by a system that has no matching system in the process */
+  pub fn validate(&self) -> Vec<MissingSystem> {
+    self
+      .systems
+      .iter()
+      .flat_map(|system| {
+        system
+          .requires()
+          .into_iter()
+          .filter(|requirement| !(requirement.present)(self))
+          .map(|requirement| MissingSystem {
+            missing: requirement.name,
+            requester: Some(system.name()),
+          })
+      })
+      .collect()
+  }
+
   pub fn get<Target>(&self) -> Option<Rc<Target>>
   where
     Target: System<Input = Input>,
@@ -31,8 +178,7 @@ impl<Input: Clone + 'static> ProcessContext<Input> {
           .downcast::<Target>()
           .is_ok()
       })
-      .map(|system| (Rc::clone(system) as Rc<dyn Any>).downcast::<Target>().ok())
-      .flatten();
+      .and_then(|system| (Rc::clone(system) as Rc<dyn Any>).downcast::<Target>().ok());
   }
 
   pub fn downcast<Target: Clone + 'static>(&self) -> Option<&ProcessContext<Target>> {
@@ -58,28 +204,67 @@ impl<Input: Clone + 'static> ProcessContext<Input> {
         })
         .collect(),
       input: self.input.clone(),
+      profile: Rc::clone(&self.profile),
+      requester: self.requester,
+      sub_process_output: self.sub_process_output.clone(),
     })
   }
 
-  pub async fn run<Output, Terminator>(self: &Rc<Self>, terminator: Terminator) -> Output
+  fn requested_by(&self, requester: &'static str) -> Self {
+    Self {
+      requester: Some(requester),
+      ..self.clone()
+    }
+  }
+
+  pub async fn run_stack<Output, Stepper>(self: &Rc<Self>, stepper: Stepper) -> Output
   where
-    Terminator: Fn(&ProcessContext<Input>) -> Option<Output>,
+    Stepper: Fn(&ProcessContext<Input>) -> Step<Output>,
   {
     let mut game_state = Rc::clone(self);
     loop {
-      let result = terminator(&game_state);
-
-      if let Some(output) = result {
-        return output;
+      match stepper(&game_state) {
+        Step::Continue => {}
+        Step::Exit(output) => return output,
+        Step::Push(sub_process) => {
+          let sub_process_output = sub_process.await;
+          game_state = Rc::new(ProcessContext {
+            sub_process_output: Some(sub_process_output),
+            ..game_state.as_ref().clone()
+          });
+        }
       }
 
-      game_state = game_state
-        .systems
-        .iter()
-        .enumerate()
-        .fold(Rc::clone(&game_state), |temp_state, (index, system)| {
-          temp_state.with(index, &system.run(&temp_state))
-        });
+      let allocations_before = allocation_count();
+
+      let (next_state, system_timings) = game_state.systems.iter().enumerate().fold(
+        (Rc::clone(&game_state), Vec::new()),
+        |(temp_state, system_timings), (index, system)| {
+          let started = Instant::now();
+          let next_system = system.run(&temp_state.requested_by(system.name()));
+          let elapsed_ms = started.elapsed().as_secs_f32() * 1000.0;
+
+          (
+            temp_state.with(index, &next_system),
+            system_timings
+              .into_iter()
+              .chain([(next_system.name(), elapsed_ms)])
+              .collect(),
+          )
+        },
+      );
+
+      game_state = Rc::new(ProcessContext {
+        profile: Rc::new(FrameProfile {
+          system_timings,
+          allocations: allocation_count() - allocations_before,
+        }),
+        requester: None,
+        sub_process_output: None,
+        ..next_state.as_ref().clone()
+      });
+
+      profiler::report_slow_frame(&game_state.profile, &game_state.systems);
 
       next_frame().await
     }
@@ -89,7 +274,9 @@ impl<Input: Clone + 'static> ProcessContext<Input> {
   where
     Terminator: Fn(&ProcessContext<Input>) -> Option<Output>,
   {
-    Rc::new(self).run(terminator).await
+    Rc::new(self)
+      .run_stack(|ctx| terminator(ctx).map_or(Step::Continue, Step::Exit))
+      .await
   }
 }
 
@@ -117,11 +304,15 @@ impl<Input: Clone + 'static> Process<Input> {
     };
   }
 
+  /* Panics before the first frame if any system's requirements are not met */
   pub fn start(&self) -> ProcessContext<Input> {
-    self.ctx_initializers.iter().fold(
+    let ctx = self.ctx_initializers.iter().fold(
       ProcessContext {
         systems: vec![],
         input: self.input.clone(),
+        profile: Rc::new(FrameProfile::default()),
+        requester: None,
+        sub_process_output: None,
       },
       |ctx: ProcessContext<Input>, initializer| {
         let new_vec = ctx
@@ -133,8 +324,31 @@ impl<Input: Clone + 'static> Process<Input> {
         return ProcessContext {
           systems: new_vec,
           input: ctx.input,
+          profile: ctx.profile,
+          requester: None,
+          sub_process_output: None,
         };
       },
-    )
+    );
+
+    let missing_systems = ctx.validate();
+    missing_systems.iter().for_each(|missing_system| {
+      log::error("system", missing_system.to_string());
+    });
+
+    if let Some(missing_system) = missing_systems.first() {
+      panic!("{}", missing_system);
+    }
+
+    ctx
+  }
+
+  /* Starts when pushed, and hands whatever the terminator returns back to the parent */
+  pub fn sub_process<Output, Terminator>(self, terminator: Terminator) -> SubProcess
+  where
+    Output: 'static,
+    Terminator: Fn(&ProcessContext<Input>) -> Option<Output> + 'static,
+  {
+    Box::pin(async move { Rc::new(self.start().run_move(terminator).await) as Rc<dyn Any> })
   }
 }
@@ -0,0 +1,174 @@
+use std::{
+  cell::RefCell,
+  env::current_dir,
+  fs,
+  sync::atomic::{AtomicU8, Ordering},
+};
+
+use macroquad::{
+  color::Color,
+  text::{Font, TextDimensions, TextParams, draw_text_ex, load_ttf_font_from_bytes, measure_text},
+  window::screen_height,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::log;
+
+/* Text sizes are given for a window this tall, and scaled to the real one */
+pub const VIRTUAL_HEIGHT: f32 = 1080.0;
+
+/* Falls back to macroquad's built in font when missing */
+const UI_FONT_PATH: &str = "assets/fonts/ui.ttf";
+
+/* Gap between wrapped lines, as a fraction of the text size */
+const LINE_SPACING: f32 = 1.25;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub enum UiScale {
+  Small,
+  #[default]
+  Normal,
+  Large,
+  Larger,
+}
+
+impl UiScale {
+  pub const ALL: [UiScale; 4] = [
+    UiScale::Small,
+    UiScale::Normal,
+    UiScale::Large,
+    UiScale::Larger,
+  ];
+
+  pub fn factor(&self) -> f32 {
+    match self {
+      UiScale::Small => 0.85,
+      UiScale::Normal => 1.0,
+      UiScale::Large => 1.25,
+      UiScale::Larger => 1.5,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      UiScale::Small => "small",
+      UiScale::Normal => "normal",
+      UiScale::Large => "large",
+      UiScale::Larger => "larger",
+    }
+  }
+
+  pub fn cycle(&self, step: i32) -> Self {
+    let index = UiScale::ALL
+      .iter()
+      .position(|ui_scale| ui_scale == self)
+      .unwrap() as i32;
+
+    UiScale::ALL[(index + step).rem_euclid(UiScale::ALL.len() as i32) as usize]
+  }
+}
+
+/* Where `x` sits along the text, plain draw_ui_text starts the text at it */
+#[derive(Clone, Copy)]
+pub enum Align {
+  Center,
+  Right,
+}
+
+/* Like the palette, text is drawn from all over so its configuration lives outside of the
+systems. Fonts hold GPU resources, which only the main thread may touch */
+static UI_SCALE: AtomicU8 = AtomicU8::new(UiScale::Normal as u8);
+
+thread_local! {
+  static UI_FONT: RefCell<Option<Font>> = const { RefCell::new(None) };
+}
+
+pub fn load_fonts() {
+  let font_path = current_dir().unwrap().join(UI_FONT_PATH);
+
+  let font = fs::read(&font_path)
+    .map_err(|error| error.to_string())
+    .and_then(|bytes| load_ttf_font_from_bytes(&bytes).map_err(|error| error.to_string()));
+
+  match font {
+    Ok(font) => UI_FONT.with(|ui_font| *ui_font.borrow_mut() = Some(font)),
+    Err(error) => log::info(
+      "text",
+      format!(
+        "using the built in font, couldn't load {}: {}",
+        font_path.display(),
+        error
+      ),
+    ),
+  }
+}
+
+pub fn configure(ui_scale: UiScale) {
+  UI_SCALE.store(ui_scale as u8, Ordering::Relaxed);
+}
+
+fn ui_scale() -> UiScale {
+  UiScale::ALL[UI_SCALE.load(Ordering::Relaxed) as usize]
+}
+
+/* Pixel size of text authored at `size` for the virtual resolution */
+pub fn text_size(size: f32) -> f32 {
+  size * screen_height() / VIRTUAL_HEIGHT * ui_scale().factor()
+}
+
+pub fn line_height(size: f32) -> f32 {
+  text_size(size) * LINE_SPACING
+}
+
+pub fn measure_ui_text(text: &str, size: f32) -> TextDimensions {
+  UI_FONT.with(|ui_font| measure_text(text, ui_font.borrow().as_ref(), text_size(size) as u16, 1.0))
+}
+
+/* Drop in for draw_text, with the UI font and scaling applied */
+pub fn draw_ui_text(text: &str, x: f32, y: f32, size: f32, color: Color) -> TextDimensions {
+  UI_FONT.with(|ui_font| {
+    draw_text_ex(
+      text,
+      x,
+      y,
+      TextParams {
+        font: ui_font.borrow().as_ref(),
+        font_size: text_size(size) as u16,
+        color,
+        ..Default::default()
+      },
+    )
+  })
+}
+
+pub fn draw_ui_text_aligned(
+  text: &str,
+  x: f32,
+  y: f32,
+  size: f32,
+  color: Color,
+  align: Align,
+) -> TextDimensions {
+  let width = measure_ui_text(text, size).width;
+
+  let left = match align {
+    Align::Center => x - width / 2.0,
+    Align::Right => x - width,
+  };
+
+  draw_ui_text(text, left, y, size, color)
+}
+
+/* Greedy word wrap to a width in pixels, words longer than a line get a line to themselves */
+pub fn wrap_text(text: &str, size: f32, max_width: f32) -> Vec<String> {
+  text.split_whitespace().fold(Vec::new(), |mut lines, word| {
+    match lines.last_mut() {
+      Some(line) if measure_ui_text(&format!("{} {}", line, word), size).width <= max_width => {
+        line.push(' ');
+        line.push_str(word);
+      }
+      _ => lines.push(word.to_string()),
+    }
+    lines
+  })
+}
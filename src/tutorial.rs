@@ -0,0 +1,140 @@
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  controls::{ControlMode, ControlsSystem},
+  ecs::TutorialTrigger,
+  menu::MenuSystem,
+  physics::PhysicsSystem,
+  save::SaveData,
+  system::{Requirement, System},
+};
+
+/* Frames a prompt stays on screen before dismissing itself */
+const TUTORIAL_PROMPT_FRAMES: i32 = 300;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum TutorialAction {
+  Move,
+  Aim,
+  Fire,
+  Boost,
+  Chain,
+  Magnetize,
+  LockOn,
+  Inventory,
+  Map,
+  Pause,
+}
+
+impl TutorialAction {
+  /* Mirrors the bindings in ControlsSystem */
+  pub fn glyph(&self, control_mode: ControlMode) -> &'static str {
+    match control_mode {
+      ControlMode::Keyboard => match self {
+        TutorialAction::Move => "WASD",
+        TutorialAction::Aim => "Mouse",
+        TutorialAction::Fire => "LMB",
+        TutorialAction::Boost => "LCtrl",
+        TutorialAction::Chain => "C",
+        TutorialAction::Magnetize => "F",
+        TutorialAction::LockOn => "Q",
+        TutorialAction::Inventory => "E",
+        TutorialAction::Map => "Tab",
+        TutorialAction::Pause => "Esc",
+      },
+      ControlMode::GamePad => match self {
+        TutorialAction::Move => "LS",
+        TutorialAction::Aim => "RS",
+        TutorialAction::Fire => "RT",
+        TutorialAction::Boost => "LT",
+        TutorialAction::Chain => "LB",
+        TutorialAction::Magnetize => "RB",
+        TutorialAction::LockOn => "R3",
+        TutorialAction::Inventory => "X",
+        TutorialAction::Map => "Y",
+        TutorialAction::Pause => "Select",
+      },
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct TutorialPrompt {
+  pub action: TutorialAction,
+  pub text: String,
+  pub frames_remaining: i32,
+}
+
+pub struct TutorialSystem {
+  pub active_prompt: Option<TutorialPrompt>,
+  /* Each action is only ever explained once per save */
+  pub shown: Vec<TutorialAction>,
+}
+
+impl System for TutorialSystem {
+  type Input = SaveData;
+
+  fn requires(&self) -> Vec<Requirement<Self::Input>> {
+    vec![
+      Requirement::of::<PhysicsSystem>(),
+      Requirement::of::<ControlsSystem<_>>(),
+      Requirement::of::<MenuSystem<_>>(),
+    ]
+  }
+
+  fn start(ctx: &crate::system::ProcessContext<Self::Input>) -> Rc<dyn System<Input = Self::Input>>
+  where
+    Self: Sized,
+  {
+    Rc::new(Self {
+      active_prompt: None,
+      shown: ctx.input.shown_tutorials.clone(),
+    })
+  }
+
+  fn run(
+    &self,
+    ctx: &crate::system::ProcessContext<Self::Input>,
+  ) -> Rc<dyn System<Input = Self::Input>> {
+    let physics_system = ctx.require::<PhysicsSystem>().unwrap();
+    let controls_system = ctx.require::<ControlsSystem<_>>().unwrap();
+    let menu_system = ctx.require::<MenuSystem<_>>().unwrap();
+
+    /* MARK: Find unseen triggers the player is inside of */
+    let triggered = physics_system
+      .entered_trigger(|trigger: &TutorialTrigger| !self.shown.contains(&trigger.action));
+
+    if let Some((trigger, _)) = triggered {
+      return Rc::new(Self {
+        active_prompt: Some(TutorialPrompt {
+          action: trigger.action,
+          text: trigger.text.clone(),
+          frames_remaining: TUTORIAL_PROMPT_FRAMES,
+        }),
+        shown: [self.shown.clone(), vec![trigger.action]].concat(),
+      });
+    }
+
+    /* MARK: Count down the shown prompt, holding it while paused */
+    let active_prompt = self.active_prompt.as_ref().and_then(|prompt| {
+      let frames_remaining =
+        if !menu_system.active_menus.is_empty() || controls_system.simulation_frozen() {
+          prompt.frames_remaining
+        } else {
+          prompt.frames_remaining - 1
+        };
+
+      (frames_remaining > 0).then(|| TutorialPrompt {
+        frames_remaining,
+        ..prompt.clone()
+      })
+    });
+
+    Rc::new(Self {
+      active_prompt,
+      shown: self.shown.clone(),
+    })
+  }
+}